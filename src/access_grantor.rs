@@ -0,0 +1,226 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use tokio::time::{interval, MissedTickBehavior};
+use tracing::{error, info, warn};
+
+use crate::{
+    access_request_store::{
+        AccessRequest, AccessRequestState, AccessRequestStore, RedisAccessRequestStore,
+    },
+    config::{AccessRequestsConf, BasinConfig},
+    controller::naming::glue_database_name,
+    descriptor_store::{DescriptorStore, RedisDescriptorStore},
+    fluid::descriptor::{
+        database::DatabaseDescriptor, table::TableDescriptor, DescriptorKind, StorageEngine,
+    },
+    provisioner::lake_formation::{GrantTarget, LakeFormationProvisioner},
+};
+
+// Applies the grants of approved access requests and revokes those of withdrawn ones. Granted
+// requests are reapplied on every pass so a grant removed out of band comes back, the request is
+// the source of truth. That includes a grant shared with a request revoked since.
+pub struct AccessGrantor {
+    conf: AccessRequestsConf,
+    access_request_store: RedisAccessRequestStore,
+    descriptor_store: RedisDescriptorStore,
+    lake_formation_provisioner: LakeFormationProvisioner,
+}
+
+impl AccessGrantor {
+    pub async fn new(conf: &BasinConfig) -> Result<Option<Self>> {
+        let Some(access_requests) = &conf.access_requests else {
+            return Ok(None);
+        };
+
+        Ok(Some(AccessGrantor {
+            conf: access_requests.clone(),
+            access_request_store: RedisAccessRequestStore::new(&conf.redis).await?,
+            descriptor_store: RedisDescriptorStore::new(&conf.redis).await?,
+            lake_formation_provisioner: LakeFormationProvisioner::new(&conf.aws_creds),
+        }))
+    }
+
+    pub async fn grant_loop(&self) -> ! {
+        let mut ticker = interval(Duration::from_secs(self.conf.interval_secs));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+
+            info!("Applying access grants");
+            match self.revoke_withdrawn().await {
+                Ok(revoked) => info!(revoked, "finished revoking access grants"),
+                Err(e) => error!(?e, "error when revoking access grants"),
+            }
+            match self.grant_approved().await {
+                Ok(granted) => info!(granted, "finished applying access grants"),
+                Err(e) => error!(?e, "error when applying access grants"),
+            }
+        }
+    }
+
+    async fn grant_approved(&self) -> Result<usize> {
+        let mut granted = 0;
+        for mut request in self.access_request_store.list_requests().await? {
+            if !matches!(
+                request.state,
+                AccessRequestState::Approved | AccessRequestState::Granted
+            ) {
+                continue;
+            }
+
+            match self.grant(&request).await {
+                Ok(()) => {
+                    if request.state == AccessRequestState::Granted && request.error.is_none() {
+                        continue;
+                    }
+                    info!(request_id = request.request_id, "access granted");
+                    request.state = AccessRequestState::Granted;
+                    request.granted_at.get_or_insert_with(Utc::now);
+                    request.error = None;
+                    granted += 1;
+                }
+                Err(e) => {
+                    warn!(
+                        request_id = request.request_id,
+                        ?e,
+                        "failed to grant access"
+                    );
+                    let error = format!("{:#}", e);
+                    if request.error.as_ref() == Some(&error) {
+                        continue;
+                    }
+                    request.error = Some(error);
+                }
+            }
+            self.write_back(&request).await?;
+        }
+
+        Ok(granted)
+    }
+
+    async fn revoke_withdrawn(&self) -> Result<usize> {
+        let mut revoked = 0;
+        for mut request in self.access_request_store.list_requests().await? {
+            if request.state != AccessRequestState::Revoking {
+                continue;
+            }
+
+            match self.revoke(&request).await {
+                Ok(()) => {
+                    info!(request_id = request.request_id, "access revoked");
+                    request.state = AccessRequestState::Revoked;
+                    request.error = None;
+                    revoked += 1;
+                }
+                Err(e) => {
+                    warn!(
+                        request_id = request.request_id,
+                        ?e,
+                        "failed to revoke access"
+                    );
+                    let error = format!("{:#}", e);
+                    if request.error.as_ref() == Some(&error) {
+                        continue;
+                    }
+                    request.error = Some(error);
+                }
+            }
+            self.write_back(&request).await?;
+        }
+
+        Ok(revoked)
+    }
+
+    // NOTE: the request may have been decided or withdrawn while this pass worked on it, what
+    //       was done is picked up from the request as it is now on the next pass
+    async fn write_back(&self, request: &AccessRequest) -> Result<()> {
+        if !self.access_request_store.update_request(request).await? {
+            info!(
+                request_id = request.request_id,
+                "access request changed during the pass, leaving it to the next"
+            );
+        }
+        Ok(())
+    }
+
+    async fn revoke(&self, request: &AccessRequest) -> Result<()> {
+        // Nothing was granted on a descriptor that's gone, glue dropped it along with the grants
+        let Some(target) =
+            grant_target(&self.descriptor_store, request.kind, &request.descriptor_id).await?
+        else {
+            return Ok(());
+        };
+
+        self.lake_formation_provisioner
+            .revoke(&request.principal_arn, &target, &request.permissions)
+            .await
+    }
+
+    async fn grant(&self, request: &AccessRequest) -> Result<()> {
+        let target = grant_target(&self.descriptor_store, request.kind, &request.descriptor_id)
+            .await?
+            .ok_or_else(|| {
+                anyhow!(
+                    "{} '{}' is gone or no longer in glue",
+                    request.kind,
+                    request.descriptor_id
+                )
+            })?;
+
+        self.lake_formation_provisioner
+            .grant(&request.principal_arn, &target, &request.permissions)
+            .await
+    }
+}
+
+// What a grant on the descriptor is made on, None unless it's a glue database or table
+pub async fn grant_target(
+    descriptor_store: &RedisDescriptorStore,
+    kind: DescriptorKind,
+    descriptor_id: &str,
+) -> Result<Option<GrantTarget>> {
+    let table = match kind {
+        DescriptorKind::Database => None,
+        DescriptorKind::Table => {
+            let Some(table) = descriptor_store
+                .get_descriptor::<TableDescriptor>(descriptor_id, DescriptorKind::Table)
+                .await?
+            else {
+                return Ok(None);
+            };
+            Some(table)
+        }
+        DescriptorKind::Flow | DescriptorKind::LandingZone => return Ok(None),
+    };
+
+    let database_id = table
+        .as_ref()
+        .map_or(descriptor_id, |t| t.database.as_str());
+    let Some(database) = descriptor_store
+        .get_descriptor::<DatabaseDescriptor>(database_id, DescriptorKind::Database)
+        .await?
+    else {
+        return Ok(None);
+    };
+
+    let engine = table
+        .as_ref()
+        .and_then(|t| t.engine)
+        .unwrap_or(database.engine);
+    if engine != StorageEngine::Glue {
+        return Ok(None);
+    }
+
+    Ok(Some(match table {
+        None => GrantTarget::Database {
+            database: glue_database_name(&database),
+        },
+        Some(table) => GrantTarget::Table {
+            database: glue_database_name(&database),
+            table: table.name,
+        },
+    }))
+}
@@ -0,0 +1,157 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use redis::{AsyncCommands, Script};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::RedisConf, fluid::descriptor::DescriptorKind, redis_connection::RedisConnector,
+    redis_namespace::prefixed,
+};
+
+const ACCESS_REQUESTS_KEY: &str = "access-requests";
+
+// Writes the request unless the stored one has moved on from the revision it was read at
+const UPDATE_SCRIPT: &str = r#"
+local current = redis.call("HGET", KEYS[1], ARGV[1])
+if not current then
+    return 0
+end
+if (cjson.decode(current)["revision"] or 0) ~= tonumber(ARGV[2]) then
+    return 0
+end
+redis.call("HSET", KEYS[1], ARGV[1], ARGV[3])
+return 1
+"#;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessRequestState {
+    // Waiting on an approver
+    Pending,
+    // Approved, the grant hasn't landed yet
+    Approved,
+    // The grant is in place, and is reapplied should it go missing
+    Granted,
+    Denied,
+    // Withdrawn, the grant hasn't been revoked yet
+    Revoking,
+    Revoked,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessPermission {
+    Select,
+    Insert,
+    Delete,
+    Describe,
+    Alter,
+    // Databases only
+    CreateTable,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AccessRequest {
+    pub request_id: String,
+    // IAM role or user the grant is made to
+    pub principal_arn: String,
+    pub requester: String,
+    // `database` or `table`
+    pub kind: DescriptorKind,
+    pub descriptor_id: String,
+    pub permissions: Vec<AccessPermission>,
+    pub justification: String,
+    pub state: AccessRequestState,
+    pub requested_at: DateTime<Utc>,
+    pub decided_by: Option<String>,
+    pub decided_at: Option<DateTime<Utc>>,
+    pub comment: Option<String>,
+    pub granted_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub revoked_by: Option<String>,
+    #[serde(default)]
+    pub revoked_at: Option<DateTime<Utc>>,
+    // Why the last attempt at the grant or its revocation failed, cleared once it succeeds
+    pub error: Option<String>,
+    // Bumped on every update, writes based on an older read are refused
+    #[serde(default)]
+    pub revision: u64,
+}
+
+#[async_trait::async_trait]
+pub(crate) trait AccessRequestStore {
+    // False when a request with the id already exists
+    async fn create_request(&self, request: &AccessRequest) -> Result<bool>;
+    // Writes back a request read from the store, false when it's been updated since
+    async fn update_request(&self, request: &AccessRequest) -> Result<bool>;
+    async fn get_request(&self, request_id: &str) -> Result<Option<AccessRequest>>;
+    async fn list_requests(&self) -> Result<Vec<AccessRequest>>;
+}
+
+#[derive(Debug)]
+pub struct RedisAccessRequestStore {
+    connector: RedisConnector,
+    key_prefix: String,
+}
+
+#[async_trait::async_trait]
+impl AccessRequestStore for RedisAccessRequestStore {
+    async fn create_request(&self, request: &AccessRequest) -> Result<bool> {
+        let mut conn = self.connector.get_connection().await?;
+        let created: bool = conn
+            .hset_nx(
+                self.key(ACCESS_REQUESTS_KEY),
+                &request.request_id,
+                serde_json::to_string(request)?,
+            )
+            .await?;
+        Ok(created)
+    }
+
+    async fn update_request(&self, request: &AccessRequest) -> Result<bool> {
+        let mut conn = self.connector.get_connection().await?;
+        let updated = AccessRequest {
+            revision: request.revision + 1,
+            ..request.clone()
+        };
+        let written: i64 = Script::new(UPDATE_SCRIPT)
+            .key(self.key(ACCESS_REQUESTS_KEY))
+            .arg(&request.request_id)
+            .arg(request.revision)
+            .arg(serde_json::to_string(&updated)?)
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(written == 1)
+    }
+
+    async fn get_request(&self, request_id: &str) -> Result<Option<AccessRequest>> {
+        let mut conn = self.connector.get_connection().await?;
+        let request: Option<String> = conn.hget(self.key(ACCESS_REQUESTS_KEY), request_id).await?;
+        Ok(request.map(|r| serde_json::from_str(&r)).transpose()?)
+    }
+
+    async fn list_requests(&self) -> Result<Vec<AccessRequest>> {
+        let mut conn = self.connector.get_connection().await?;
+        let records: Vec<String> = conn.hvals(self.key(ACCESS_REQUESTS_KEY)).await?;
+
+        let mut requests = Vec::new();
+        for record in records {
+            requests.push(serde_json::from_str::<AccessRequest>(&record)?);
+        }
+        requests.sort_by_key(|r| r.requested_at);
+        Ok(requests)
+    }
+}
+
+impl RedisAccessRequestStore {
+    pub async fn new(conf: &RedisConf) -> Result<Self> {
+        Ok(Self {
+            connector: RedisConnector::new(conf)?,
+            key_prefix: conf.key_prefix.clone(),
+        })
+    }
+
+    fn key(&self, key: &str) -> String {
+        prefixed(&self.key_prefix, key)
+    }
+}
@@ -0,0 +1,20 @@
+pub mod access_requests;
+pub mod admin;
+pub mod approvals;
+pub mod archive;
+pub mod backfill;
+pub mod bundle;
+pub mod cost;
+pub mod dashboard;
+pub mod deletion;
+pub mod diff;
+pub mod events;
+pub mod history;
+pub mod list;
+pub mod metadata;
+pub mod partitions;
+pub mod preview;
+pub mod quality;
+pub mod snapshot;
+pub mod spec;
+pub mod tokens;
@@ -0,0 +1,340 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    access_grantor::grant_target,
+    access_request_store::{
+        AccessPermission, AccessRequest, AccessRequestState, AccessRequestStore,
+    },
+    api_tokens::TokenGrant,
+    config::AccessRequestsConf,
+    fluid::descriptor::DescriptorKind,
+    request_id::RequestId,
+    AppContext,
+};
+
+#[derive(Deserialize)]
+pub struct NewAccessRequest {
+    principal_arn: String,
+    // `database` or `table`
+    kind: DescriptorKind,
+    descriptor_id: String,
+    permissions: Vec<AccessPermission>,
+    justification: String,
+}
+
+#[derive(Deserialize)]
+pub struct AccessDecision {
+    #[serde(default)]
+    comment: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct AccessRequestQuery {
+    state: Option<AccessRequestState>,
+}
+
+fn access_requests_conf(ctx: &AppContext) -> Result<&AccessRequestsConf, axum::response::Response> {
+    ctx.access_requests.as_ref().ok_or_else(|| {
+        (
+            StatusCode::NOT_IMPLEMENTED,
+            "access requests aren't configured",
+        )
+            .into_response()
+    })
+}
+
+// The token the request is made with, access requests are only configured alongside api tokens
+fn token_name(grant: Option<Extension<TokenGrant>>) -> Result<String, axum::response::Response> {
+    grant
+        .map(|g| g.0.name)
+        .ok_or_else(|| (StatusCode::FORBIDDEN, "an api token is needed").into_response())
+}
+
+pub async fn create_access_request(
+    State(ctx): State<Arc<AppContext>>,
+    Extension(request_id): Extension<RequestId>,
+    grant: Option<Extension<TokenGrant>>,
+    Json(new_request): Json<NewAccessRequest>,
+) -> axum::response::Response {
+    if let Err(response) = access_requests_conf(&ctx) {
+        return response;
+    }
+    let requester = match token_name(grant) {
+        Ok(t) => t,
+        Err(response) => return response,
+    };
+    if new_request.permissions.is_empty() {
+        return (StatusCode::BAD_REQUEST, "at least one permission is needed").into_response();
+    }
+    if new_request.kind != DescriptorKind::Database
+        && new_request
+            .permissions
+            .contains(&AccessPermission::CreateTable)
+    {
+        return (
+            StatusCode::BAD_REQUEST,
+            "create_table can only be requested on databases",
+        )
+            .into_response();
+    }
+
+    match grant_target(
+        &ctx.descriptor_store,
+        new_request.kind,
+        &new_request.descriptor_id,
+    )
+    .await
+    {
+        Ok(Some(_)) => (),
+        Ok(None) => {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "access can only be requested to existing glue databases and tables",
+            )
+                .into_response()
+        }
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    }
+
+    let request = AccessRequest {
+        request_id: Uuid::new_v4().to_string(),
+        principal_arn: new_request.principal_arn,
+        requester,
+        kind: new_request.kind,
+        descriptor_id: new_request.descriptor_id,
+        permissions: new_request.permissions,
+        justification: new_request.justification,
+        state: AccessRequestState::Pending,
+        requested_at: Utc::now(),
+        decided_by: None,
+        decided_at: None,
+        comment: None,
+        granted_at: None,
+        revoked_by: None,
+        revoked_at: None,
+        error: None,
+        revision: 0,
+    };
+    match ctx.access_request_store.create_request(&request).await {
+        Ok(true) => (),
+        Ok(false) => {
+            return (
+                StatusCode::CONFLICT,
+                format!("access request '{}' already exists", request.request_id),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("failed to store access request: {:?}", e),
+            )
+                .into_response()
+        }
+    }
+
+    info!(
+        access_request_id = request.request_id,
+        request_id = request_id.0,
+        descriptor_id = request.descriptor_id,
+        "access requested"
+    );
+    (StatusCode::ACCEPTED, Json(request)).into_response()
+}
+
+pub async fn list_access_requests(
+    State(ctx): State<Arc<AppContext>>,
+    Query(query): Query<AccessRequestQuery>,
+) -> axum::response::Response {
+    match ctx.access_request_store.list_requests().await {
+        Ok(requests) => Json(
+            requests
+                .into_iter()
+                .filter(|r| query.state.map_or(true, |s| r.state == s))
+                .collect::<Vec<_>>(),
+        )
+        .into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response(),
+    }
+}
+
+pub async fn get_access_request(
+    State(ctx): State<Arc<AppContext>>,
+    Path(request_id): Path<String>,
+) -> axum::response::Response {
+    match ctx.access_request_store.get_request(&request_id).await {
+        Ok(Some(request)) => Json(request).into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response(),
+    }
+}
+
+// NOTE: the grant itself is made by the access grantor on its next pass
+pub async fn approve_access_request(
+    State(ctx): State<Arc<AppContext>>,
+    grant: Option<Extension<TokenGrant>>,
+    Path(request_id): Path<String>,
+    Json(decision): Json<AccessDecision>,
+) -> axum::response::Response {
+    decide(
+        &ctx,
+        grant,
+        &request_id,
+        decision,
+        AccessRequestState::Approved,
+    )
+    .await
+}
+
+pub async fn deny_access_request(
+    State(ctx): State<Arc<AppContext>>,
+    grant: Option<Extension<TokenGrant>>,
+    Path(request_id): Path<String>,
+    Json(decision): Json<AccessDecision>,
+) -> axum::response::Response {
+    decide(
+        &ctx,
+        grant,
+        &request_id,
+        decision,
+        AccessRequestState::Denied,
+    )
+    .await
+}
+
+// Withdraws an approved or granted request, the access grantor revokes the grant on its next pass
+pub async fn revoke_access_request(
+    State(ctx): State<Arc<AppContext>>,
+    grant: Option<Extension<TokenGrant>>,
+    Path(request_id): Path<String>,
+    Json(decision): Json<AccessDecision>,
+) -> axum::response::Response {
+    let (approver, mut request) = match approver_request(&ctx, grant, &request_id).await {
+        Ok(t) => t,
+        Err(response) => return response,
+    };
+    if !matches!(
+        request.state,
+        AccessRequestState::Approved | AccessRequestState::Granted
+    ) {
+        return (
+            StatusCode::CONFLICT,
+            format!(
+                "only approved requests can be revoked ({:?})",
+                request.state
+            ),
+        )
+            .into_response();
+    }
+
+    request.state = AccessRequestState::Revoking;
+    request.revoked_by = Some(approver);
+    request.revoked_at = Some(Utc::now());
+    if let Some(comment) = decision.comment {
+        request.comment = Some(comment);
+    }
+    if let Err(response) = write_back(&ctx, &request).await {
+        return response;
+    }
+
+    info!(
+        request_id,
+        revoked_by = request.revoked_by.as_deref(),
+        "access request revoked"
+    );
+    Json(request).into_response()
+}
+
+// The approver and the request they're deciding on. Approvers are the tokens decisions are made
+// with, authenticate only lets tokens scoped `access-requests:decide` through to here.
+async fn approver_request(
+    ctx: &AppContext,
+    grant: Option<Extension<TokenGrant>>,
+    request_id: &str,
+) -> Result<(String, AccessRequest), axum::response::Response> {
+    access_requests_conf(ctx)?;
+    let approver = token_name(grant)?;
+
+    match ctx.access_request_store.get_request(request_id).await {
+        Ok(Some(t)) => Ok((approver, t)),
+        Ok(None) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(e) => {
+            Err((StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response())
+        }
+    }
+}
+
+async fn write_back(
+    ctx: &AppContext,
+    request: &AccessRequest,
+) -> Result<(), axum::response::Response> {
+    match ctx.access_request_store.update_request(request).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err((
+            StatusCode::CONFLICT,
+            "request changed while being updated, fetch it and try again",
+        )
+            .into_response()),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("failed to store access request: {:?}", e),
+        )
+            .into_response()),
+    }
+}
+
+async fn decide(
+    ctx: &AppContext,
+    grant: Option<Extension<TokenGrant>>,
+    request_id: &str,
+    decision: AccessDecision,
+    state: AccessRequestState,
+) -> axum::response::Response {
+    let (approver, mut request) = match approver_request(ctx, grant, request_id).await {
+        Ok(t) => t,
+        Err(response) => return response,
+    };
+    if request.state != AccessRequestState::Pending {
+        return (
+            StatusCode::CONFLICT,
+            format!("request has already been decided ({:?})", request.state),
+        )
+            .into_response();
+    }
+    if request.requester == approver {
+        return (
+            StatusCode::FORBIDDEN,
+            "requests can't be decided by their requester",
+        )
+            .into_response();
+    }
+
+    request.state = state;
+    request.decided_by = Some(approver);
+    request.decided_at = Some(Utc::now());
+    request.comment = decision.comment;
+    if let Err(response) = write_back(ctx, &request).await {
+        return response;
+    }
+
+    info!(
+        request_id,
+        state = ?request.state,
+        decided_by = request.decided_by.as_deref(),
+        "access request decided"
+    );
+    Json(request).into_response()
+}
@@ -0,0 +1,321 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    deployment_state_store::{
+        DeploymentHistory, DeploymentInfo, DeploymentState, DeploymentStateStore,
+    },
+    descriptor_store::DescriptorStore,
+    fluid::descriptor::DescriptorKind,
+    queue_stats::{self, EventWatcherLag, QueueDepth},
+    replay_store::{ReplayRecord, ReplayStore},
+    request_id::RequestId,
+    s3_source_store::S3SourceStore,
+    AppContext,
+};
+
+#[derive(Deserialize, Default)]
+pub struct ReplayRequest {
+    // Restrict the replay to these kinds, defaults to every kind
+    #[serde(default)]
+    kinds: Vec<String>,
+    // Only replay descriptors currently in this state
+    state: Option<DeploymentState>,
+}
+
+#[derive(Serialize)]
+pub struct ReplayProgress {
+    replay_id: String,
+    total: usize,
+    // Descriptors which have been reconciled since the replay started
+    completed: usize,
+    states: BTreeMap<String, usize>,
+}
+
+// NOTE: controllers reconcile every stored descriptor on each pass, so resetting the deployment
+//       state to Pending is enough to get the descriptors picked up again and their new outcome
+//       recorded against the replay
+pub async fn start_replay(
+    State(ctx): State<Arc<AppContext>>,
+    Extension(request_id): Extension<RequestId>,
+    payload: Option<Json<ReplayRequest>>,
+) -> axum::response::Response {
+    let request = payload.map(|Json(t)| t).unwrap_or_default();
+    // NOTE: parsed here rather than by serde, a body that doesn't deserialize would be taken as no
+    //       body at all and replay every kind
+    let kinds: Vec<DescriptorKind> = if request.kinds.is_empty() {
+        DescriptorKind::ALL.to_vec()
+    } else {
+        match request.kinds.iter().map(|k| k.parse()).collect() {
+            Ok(t) => t,
+            Err(e) => return (StatusCode::UNPROCESSABLE_ENTITY, format!("{}", e)).into_response(),
+        }
+    };
+
+    let mut descriptor_ids = Vec::new();
+    for &kind in kinds.iter() {
+        let descriptors = match ctx
+            .descriptor_store
+            .list_descriptors::<serde_json::Value>(kind)
+            .await
+        {
+            Ok(t) => t,
+            Err(e) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("failed to list descriptors: {:?}", e),
+                )
+                    .into_response()
+            }
+        };
+        descriptor_ids.extend(
+            descriptors
+                .iter()
+                .filter_map(|d| d.get("id").and_then(|id| id.as_str()))
+                .map(|id| id.to_string()),
+        );
+    }
+
+    let mut replayed = Vec::new();
+    for id in descriptor_ids {
+        if let Some(state) = request.state {
+            match ctx.deployment_state_store.get_state(&id).await {
+                Ok(Some(info)) if info.state == state => (),
+                Ok(_) => continue,
+                Err(e) => {
+                    return (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        format!("failed to get deployment state: {:?}", e),
+                    )
+                        .into_response()
+                }
+            }
+        }
+
+        if let Err(e) = ctx
+            .deployment_state_store
+            .set_state(
+                &id,
+                &DeploymentInfo {
+                    state: DeploymentState::Pending,
+                    description: Some("replay requested".to_string()),
+                    request_id: Some(request_id.0.clone()),
+                    updated_at: None,
+                    permanent_failure: false,
+                    attempts: 0,
+                    delete_after: None,
+                    history: DeploymentHistory::default(),
+                },
+            )
+            .await
+        {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("failed to set deployment state: {:?}", e),
+            )
+                .into_response();
+        }
+        replayed.push(id);
+    }
+
+    // NOTE: minted here rather than taken from the request id, a caller reusing one would
+    //       overwrite the record of an earlier replay
+    let record = ReplayRecord {
+        replay_id: Uuid::new_v4().to_string(),
+        started_at: Utc::now(),
+        kinds,
+        state: request.state,
+        descriptor_ids: replayed,
+    };
+    if let Err(e) = ctx.replay_store.put_replay(&record).await {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("failed to store replay: {:?}", e),
+        )
+            .into_response();
+    }
+
+    info!(
+        replay_id = record.replay_id,
+        request_id = request_id.0,
+        count = record.descriptor_ids.len(),
+        "started replay"
+    );
+    (StatusCode::ACCEPTED, Json(record)).into_response()
+}
+
+pub async fn get_replay(
+    State(ctx): State<Arc<AppContext>>,
+    Path(replay_id): Path<String>,
+) -> axum::response::Response {
+    let record = match ctx.replay_store.get_replay(&replay_id).await {
+        Ok(Some(t)) => t,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    };
+
+    let mut progress = ReplayProgress {
+        replay_id: record.replay_id,
+        total: record.descriptor_ids.len(),
+        completed: 0,
+        states: BTreeMap::new(),
+    };
+    for id in record.descriptor_ids.iter() {
+        let info = match ctx.deployment_state_store.get_state(id).await {
+            Ok(t) => t,
+            Err(e) => {
+                return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e))
+                    .into_response()
+            }
+        };
+
+        let state = info
+            .as_ref()
+            .map(|i| i.state)
+            .unwrap_or(DeploymentState::Unknown);
+        *progress.states.entry(format!("{:?}", state)).or_insert(0) += 1;
+
+        if let Some(info) = info
+            && info.state != DeploymentState::Pending
+            && info.updated_at.map_or(false, |t| t > record.started_at)
+        {
+            progress.completed += 1;
+        }
+    }
+
+    Json(progress).into_response()
+}
+
+// NOTE: the descriptor is validated again on the controller's next pass and goes straight back
+//       into quarantine if whatever it was rejected for hasn't been fixed
+pub async fn release_quarantine(
+    State(ctx): State<Arc<AppContext>>,
+    Extension(request_id): Extension<RequestId>,
+    Path(descriptor_id): Path<String>,
+) -> axum::response::Response {
+    match ctx.deployment_state_store.get_state(&descriptor_id).await {
+        Ok(Some(info)) if info.state == DeploymentState::Quarantined => (),
+        Ok(Some(_)) => {
+            return (StatusCode::CONFLICT, "descriptor isn't quarantined").into_response();
+        }
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    }
+
+    let info = DeploymentInfo {
+        state: DeploymentState::Pending,
+        description: Some("released from quarantine".to_string()),
+        request_id: Some(request_id.0),
+        updated_at: None,
+        permanent_failure: false,
+        attempts: 0,
+        delete_after: None,
+        history: DeploymentHistory::default(),
+    };
+    if let Err(e) = ctx
+        .deployment_state_store
+        .set_state(&descriptor_id, &info)
+        .await
+    {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("failed to set deployment state: {:?}", e),
+        )
+            .into_response();
+    }
+
+    info!(descriptor_id, "released descriptor from quarantine");
+    (StatusCode::ACCEPTED, Json(info)).into_response()
+}
+
+// Syncs from git right away, for the repository's push webhook
+pub async fn trigger_git_sync(State(ctx): State<Arc<AppContext>>) -> axum::response::Response {
+    let Some(git_sync) = &ctx.git_sync else {
+        return (StatusCode::NOT_FOUND, "git sync isn't configured").into_response();
+    };
+    git_sync.trigger();
+    StatusCode::ACCEPTED.into_response()
+}
+
+// How every descriptor object the s3 source has seen was last ingested, by key
+pub async fn get_s3_source(State(ctx): State<Arc<AppContext>>) -> axum::response::Response {
+    match ctx.s3_source_store.list_statuses().await {
+        Ok(statuses) => Json(statuses.into_iter().collect::<BTreeMap<_, _>>()).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response(),
+    }
+}
+
+// The settings this replica is running with, defaults included and secrets redacted
+pub async fn get_config(State(ctx): State<Arc<AppContext>>) -> Json<serde_json::Value> {
+    Json(ctx.effective_settings.clone())
+}
+
+#[derive(Serialize)]
+pub struct KindQueue {
+    #[serde(flatten)]
+    depth: QueueDepth,
+    // Reconciles completed by this replica, averaged over the last few minutes
+    reconciles_per_minute: f64,
+}
+
+#[derive(Serialize)]
+pub struct QueueReport {
+    kinds: BTreeMap<String, KindQueue>,
+    // None until the sqs event watcher has polled on this replica
+    event_watcher: Option<EventWatcherLag>,
+}
+
+// Whether basin is keeping up, pending work is counted across every replica
+pub async fn get_queues(State(ctx): State<Arc<AppContext>>) -> axum::response::Response {
+    let mut kinds = BTreeMap::new();
+    for kind in DescriptorKind::ALL {
+        let ids: Vec<String> = match ctx
+            .descriptor_store
+            .list_descriptors::<serde_json::Value>(kind)
+            .await
+        {
+            Ok(t) => t
+                .iter()
+                .filter_map(|d| d["id"].as_str().map(str::to_string))
+                .collect(),
+            Err(e) => {
+                return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e))
+                    .into_response()
+            }
+        };
+        let states = match ctx.deployment_state_store.get_states(&ids).await {
+            Ok(t) => t,
+            Err(e) => {
+                return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e))
+                    .into_response()
+            }
+        };
+        kinds.insert(
+            kind.as_str().to_string(),
+            KindQueue {
+                depth: queue_stats::queue_depth(&states),
+                reconciles_per_minute: queue_stats::reconciles_per_minute(kind),
+            },
+        );
+    }
+
+    Json(QueueReport {
+        kinds,
+        event_watcher: queue_stats::event_watcher(),
+    })
+    .into_response()
+}
@@ -0,0 +1,305 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::{Duration, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tracing::info;
+
+use crate::{
+    api_tokens::TokenGrant,
+    approval_store::{Approval, ApprovalOperation, ApprovalState, ApprovalStore},
+    config::ApprovalsConf,
+    deployment_state_store::{
+        DeploymentHistory, DeploymentInfo, DeploymentState, DeploymentStateStore,
+    },
+    descriptor_store::DescriptorStore,
+    fluid::descriptor::{
+        database::DatabaseDescriptor, flow::FlowDescriptor, landing_zone::LandingZoneDescriptor,
+        table::TableDescriptor, DescriptorKind, IdentifiableDescriptor, Owner,
+    },
+    state_events::StateEventType,
+    AppContext,
+};
+
+#[derive(Deserialize)]
+pub struct ApprovalDecision {
+    #[serde(default)]
+    comment: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ApprovalQuery {
+    state: Option<ApprovalState>,
+}
+
+fn approvals_conf(ctx: &AppContext) -> Result<&ApprovalsConf, axum::response::Response> {
+    ctx.approvals
+        .as_ref()
+        .ok_or_else(|| (StatusCode::NOT_IMPLEMENTED, "approvals aren't configured").into_response())
+}
+
+// Holds the operation back until it's approved, the descriptor's stored revision is left where it
+// is meanwhile
+pub async fn request_approval(ctx: &AppContext, approval: Approval) -> axum::response::Response {
+    if let Err(e) = ctx.approval_gate.hold(&approval).await {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("failed to hold operation for approval: {:?}", e),
+        )
+            .into_response();
+    }
+    (StatusCode::ACCEPTED, Json(approval)).into_response()
+}
+
+// The token the request was made with, approvals are only configured alongside api tokens
+pub fn requester(grant: Option<&TokenGrant>) -> Option<String> {
+    grant.map(|g| g.name.clone())
+}
+
+pub async fn list_approvals(
+    State(ctx): State<Arc<AppContext>>,
+    Query(query): Query<ApprovalQuery>,
+) -> axum::response::Response {
+    match ctx.approval_store.list_approvals().await {
+        Ok(approvals) => Json(
+            approvals
+                .into_iter()
+                .filter(|a| query.state.map_or(true, |s| a.state == s))
+                .collect::<Vec<_>>(),
+        )
+        .into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response(),
+    }
+}
+
+pub async fn get_approval(
+    State(ctx): State<Arc<AppContext>>,
+    Path(approval_id): Path<String>,
+) -> axum::response::Response {
+    match ctx.approval_store.get_approval(&approval_id).await {
+        Ok(Some(approval)) => Json(approval).into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response(),
+    }
+}
+
+pub async fn approve(
+    State(ctx): State<Arc<AppContext>>,
+    grant: Option<Extension<TokenGrant>>,
+    Path(approval_id): Path<String>,
+    Json(decision): Json<ApprovalDecision>,
+) -> axum::response::Response {
+    decide(
+        &ctx,
+        grant.map(|g| g.0),
+        &approval_id,
+        decision,
+        ApprovalState::Approved,
+    )
+    .await
+}
+
+pub async fn reject(
+    State(ctx): State<Arc<AppContext>>,
+    grant: Option<Extension<TokenGrant>>,
+    Path(approval_id): Path<String>,
+    Json(decision): Json<ApprovalDecision>,
+) -> axum::response::Response {
+    decide(
+        &ctx,
+        grant.map(|g| g.0),
+        &approval_id,
+        decision,
+        ApprovalState::Rejected,
+    )
+    .await
+}
+
+// The approver is the token the decision was made with, authenticate only lets tokens scoped
+// `approvals:decide` through to here
+async fn decide(
+    ctx: &AppContext,
+    grant: Option<TokenGrant>,
+    approval_id: &str,
+    decision: ApprovalDecision,
+    state: ApprovalState,
+) -> axum::response::Response {
+    if let Err(response) = approvals_conf(ctx) {
+        return response;
+    }
+    let Some(approver) = grant.map(|g| g.name) else {
+        return (StatusCode::FORBIDDEN, "deciding needs an api token").into_response();
+    };
+
+    let mut approval = match ctx.approval_store.get_approval(approval_id).await {
+        Ok(Some(t)) => t,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    };
+    if approval.state != ApprovalState::Pending {
+        return (
+            StatusCode::CONFLICT,
+            format!("approval has already been decided ({:?})", approval.state),
+        )
+            .into_response();
+    }
+    if approval.requested_by.as_ref() == Some(&approver) {
+        return (
+            StatusCode::FORBIDDEN,
+            "operations can't be approved by whoever requested them",
+        )
+            .into_response();
+    }
+
+    // NOTE: a later submission or deletion replaces the deployment state, the operation waiting
+    //       here is then out of date and can't go ahead
+    match ctx
+        .deployment_state_store
+        .get_state(&approval.descriptor_id)
+        .await
+    {
+        Ok(Some(info))
+            if info.state == DeploymentState::AwaitingApproval
+                && info.request_id.as_deref() == Some(approval_id) => {}
+        Ok(_) => {
+            return (
+                StatusCode::CONFLICT,
+                "the descriptor has changed since the approval was requested",
+            )
+                .into_response()
+        }
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    }
+
+    let result = match state {
+        ApprovalState::Approved => apply(ctx, &approval, &approver).await,
+        // Whatever was stored before goes back to being reconciled
+        _ => {
+            ctx.deployment_state_store
+                .set_state(
+                    &approval.descriptor_id,
+                    &DeploymentInfo {
+                        state: DeploymentState::Pending,
+                        description: Some(format!("rejected by {}", approver)),
+                        request_id: Some(approval.approval_id.clone()),
+                        updated_at: None,
+                        permanent_failure: false,
+                        attempts: 0,
+                        delete_after: None,
+                        history: DeploymentHistory::default(),
+                    },
+                )
+                .await
+        }
+    };
+    if let Err(e) = result {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("failed to carry out the decision: {:?}", e),
+        )
+            .into_response();
+    }
+
+    approval.state = state;
+    approval.decided_by = Some(approver);
+    approval.decided_at = Some(Utc::now());
+    approval.comment = decision.comment;
+    if let Err(e) = ctx.approval_store.put_approval(&approval).await {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("failed to store approval: {:?}", e),
+        )
+            .into_response();
+    }
+
+    info!(
+        approval_id,
+        state = ?approval.state,
+        decided_by = approval.decided_by.as_deref(),
+        "approval decided"
+    );
+    Json(approval).into_response()
+}
+
+async fn apply(ctx: &AppContext, approval: &Approval, approver: &str) -> Result<()> {
+    match approval.operation {
+        ApprovalOperation::Delete => {
+            ctx.deployment_state_store
+                .set_state(
+                    &approval.descriptor_id,
+                    &DeploymentInfo {
+                        state: DeploymentState::Deleting,
+                        description: Some(format!("deletion approved by {}", approver)),
+                        request_id: Some(approval.approval_id.clone()),
+                        updated_at: None,
+                        permanent_failure: false,
+                        attempts: 0,
+                        delete_after: Some(
+                            Utc::now() + Duration::seconds(ctx.deletion.grace_period_secs as i64),
+                        ),
+                        history: DeploymentHistory::default(),
+                    },
+                )
+                .await
+        }
+        ApprovalOperation::BreakingChange => {
+            let descriptor = approval
+                .descriptor
+                .clone()
+                .ok_or_else(|| anyhow::anyhow!("approval holds no descriptor"))?;
+            let owner = match approval.kind {
+                DescriptorKind::Database => store::<DatabaseDescriptor>(ctx, descriptor).await?,
+                DescriptorKind::Table => store::<TableDescriptor>(ctx, descriptor).await?,
+                DescriptorKind::Flow => store::<FlowDescriptor>(ctx, descriptor).await?,
+                DescriptorKind::LandingZone => {
+                    store::<LandingZoneDescriptor>(ctx, descriptor).await?
+                }
+            };
+
+            let info = DeploymentInfo {
+                state: DeploymentState::Pending,
+                description: Some(format!("approved by {}", approver)),
+                request_id: Some(approval.approval_id.clone()),
+                updated_at: None,
+                permanent_failure: false,
+                attempts: 0,
+                delete_after: None,
+                history: DeploymentHistory::default(),
+            };
+            ctx.deployment_state_store
+                .set_state(&approval.descriptor_id, &info)
+                .await?;
+            ctx.state_event_publisher
+                .publish(
+                    StateEventType::DescriptorStored,
+                    approval.kind,
+                    &approval.descriptor_id,
+                    owner.as_ref(),
+                    &info,
+                )
+                .await;
+            Ok(())
+        }
+    }
+}
+
+async fn store<Descriptor: IdentifiableDescriptor + Serialize + DeserializeOwned + Sync>(
+    ctx: &AppContext,
+    descriptor: serde_json::Value,
+) -> Result<Option<Owner>> {
+    let descriptor: Descriptor = serde_json::from_value(descriptor)?;
+    ctx.descriptor_store
+        .store_descriptor::<Descriptor>(&descriptor)
+        .await?;
+    Ok(descriptor.owner())
+}
@@ -0,0 +1,28 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+
+use crate::AppContext;
+
+pub async fn get_archived_states(
+    State(ctx): State<Arc<AppContext>>,
+    Path(descriptor_id): Path<String>,
+) -> axum::response::Response {
+    let Some(archiver) = &ctx.deployment_archiver else {
+        return (
+            StatusCode::NOT_FOUND,
+            "deployment state archival is not enabled",
+        )
+            .into_response();
+    };
+
+    match archiver.get_archived(&descriptor_id).await {
+        Ok(history) => Json(history).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response(),
+    }
+}
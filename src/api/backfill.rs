@@ -0,0 +1,111 @@
+use std::{str::FromStr, sync::Arc};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    backfill_store::{BackfillRecord, BackfillStatus, BackfillStore},
+    descriptor_store::DescriptorStore,
+    fluid::descriptor::flow::{FlowCondition, FlowConditionMode, FlowDescriptor},
+    request_id::RequestId,
+    AppContext,
+};
+
+// Guards against counting the ticks of a every-second schedule over a decade
+const MAX_COUNTED_RUNS: usize = 100_000;
+
+#[derive(Deserialize)]
+pub struct BackfillRequest {
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+}
+
+// NOTE: the flow controller picks up requested backfills on its next pass and adds them to the
+//       flow's waterwheel job as extra triggers bounded to the range, waterwheel then catches up
+//       on every tick in it
+pub async fn start_backfill(
+    State(ctx): State<Arc<AppContext>>,
+    Extension(request_id): Extension<RequestId>,
+    Path(flow_id): Path<String>,
+    Json(request): Json<BackfillRequest>,
+) -> axum::response::Response {
+    if request.start >= request.end {
+        return (StatusCode::BAD_REQUEST, "start must be before end").into_response();
+    }
+    if request.end > Utc::now() {
+        return (StatusCode::BAD_REQUEST, "end must not be in the future").into_response();
+    }
+
+    let flow: FlowDescriptor = match ctx
+        .descriptor_store
+        .get_descriptor(&flow_id, DescriptorKind::Flow)
+        .await
+    {
+        Ok(Some(t)) => t,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    };
+
+    // Backfill triggers replay the flow's schedule, and have to be able to start runs on their own
+    let Some(schedule) = flow.all_conditions().find_map(|c| match c {
+        FlowCondition::Cron(t) => Some(t.schedule.clone()),
+        _ => None,
+    }) else {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "only flows with a cron condition can be backfilled",
+        )
+            .into_response();
+    };
+    if flow.condition_mode == FlowConditionMode::All {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "flows whose conditions must all be met can't be backfilled",
+        )
+            .into_response();
+    }
+
+    let record = BackfillRecord {
+        backfill_id: Uuid::new_v4().to_string(),
+        flow_id: flow_id.clone(),
+        start: request.start,
+        end: request.end,
+        status: BackfillStatus::Requested,
+        requested_at: Utc::now(),
+        submitted_at: None,
+        finished_runs: 0,
+        completed_at: None,
+        expected_runs: cron::Schedule::from_str(&schedule).ok().map(|s| {
+            s.after(&request.start)
+                .take_while(|t| *t <= request.end)
+                .take(MAX_COUNTED_RUNS)
+                .count()
+        }),
+    };
+
+    if let Err(e) = ctx.backfill_store.put_backfill(&record).await {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("failed to store backfill: {:?}", e),
+        )
+            .into_response();
+    }
+
+    info!(
+        flow_id,
+        backfill_id = record.backfill_id,
+        request_id = request_id.0,
+        "backfill requested"
+    );
+    (StatusCode::ACCEPTED, Json(record)).into_response()
+}
@@ -0,0 +1,399 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{header::CONTENT_TYPE, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{json, Value};
+use tracing::info;
+
+use super::approvals;
+use crate::{
+    api_tokens::{self, TokenGrant},
+    approval_gate::Admission,
+    approval_store::Approval,
+    bundle_store::{BundleRecord, BundleStore},
+    constants::CONTROLLER_DISABLED,
+    deployment_state_store::{
+        DeploymentHistory, DeploymentInfo, DeploymentState, DeploymentStateStore,
+    },
+    descriptor_store::{DescriptorStore, NameTaken},
+    fluid::{
+        descriptor::{bundle::BundleDescriptor, DescriptorKind, IdentifiableDescriptor},
+        encoding::{self, PayloadFormat},
+    },
+    payload_limits::PayloadLimited,
+    policy::PolicyDenied,
+    request_id::RequestId,
+    state_events::StateEventType,
+    AppContext,
+};
+
+#[derive(Serialize)]
+pub struct BundleStatus {
+    bundle_id: String,
+    // Failed when any member is, Succeeded once every member is
+    state: DeploymentState,
+    request_id: Option<String>,
+    submitted_at: DateTime<Utc>,
+    members: Vec<BundleMemberStatus>,
+}
+
+#[derive(Serialize)]
+pub struct BundleMemberStatus {
+    id: String,
+    kind: DescriptorKind,
+    // None when the member has no state, e.g. it's since been deleted
+    state: Option<DeploymentState>,
+    description: Option<String>,
+}
+
+// Takes a database with its tables and flows, going by the request's content type like single
+// descriptors. Nothing is stored unless every member is valid, allowed by policy and admitted.
+pub async fn submit_bundle(
+    State(ctx): State<Arc<AppContext>>,
+    Extension(request_id): Extension<RequestId>,
+    grant: Option<Extension<TokenGrant>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> axum::response::Response {
+    let format = match PayloadFormat::from_content_type(
+        headers.get(CONTENT_TYPE).and_then(|v| v.to_str().ok()),
+    ) {
+        Ok(t) => t,
+        Err(e) => return (StatusCode::UNSUPPORTED_MEDIA_TYPE, format!("{}", e)).into_response(),
+    };
+    let bundle: BundleDescriptor = match encoding::decode(format, &body) {
+        Ok(t) => t,
+        Err(e) => {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("invalid bundle: {:#}", e),
+            )
+                .into_response()
+        }
+    };
+
+    let external = match bundle.validate() {
+        Ok(t) => t,
+        Err(e) => {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("invalid bundle: {:#}", e),
+            )
+                .into_response()
+        }
+    };
+    // NOTE: references outside the bundle have to resolve now, not whenever the flow reconciles
+    for (kind, id) in external {
+        match ctx
+            .descriptor_store
+            .get_descriptor::<Value>(&id, kind)
+            .await
+        {
+            Ok(Some(_)) => {}
+            Ok(None) => {
+                return (
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    format!("{} '{}' is neither in the bundle nor stored", kind, id),
+                )
+                    .into_response()
+            }
+            Err(e) => {
+                return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e))
+                    .into_response()
+            }
+        }
+    }
+
+    if let Err(e) = check_members(&ctx, &bundle).await {
+        return e.into_response();
+    }
+    if let Err(response) = authorize_members(&ctx, grant.as_ref().map(|g| &g.0), &bundle).await {
+        return response;
+    }
+
+    let held = match admit_members(&ctx, &bundle, grant.as_ref().map(|g| &g.0), &request_id).await {
+        Ok(t) => t,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("could not check for breaking changes: {:?}", e),
+            )
+                .into_response()
+        }
+    };
+    // NOTE: a bundle is applied whole or not at all. While any member waits on an approver
+    //       nothing in it is stored, approving stores that member's submission and the bundle
+    //       goes in once it's submitted again.
+    if !held.is_empty() {
+        info!(
+            bundle_id = bundle.id,
+            held = held.len(),
+            "bundle held for approval"
+        );
+        return (
+            StatusCode::ACCEPTED,
+            Json(json!({ "awaiting_approval": held })),
+        )
+            .into_response();
+    }
+
+    let members = match bundle.members() {
+        Ok(t) => t,
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    };
+    let owners = std::iter::once(bundle.database.owner())
+        .chain(bundle.tables.iter().map(|t| t.owner()))
+        .chain(bundle.flows.iter().map(|f| f.owner()));
+    let mut states = Vec::new();
+    let mut state_writes = Vec::new();
+    for ((kind, id, _), owner) in members.iter().zip(owners) {
+        let info = DeploymentInfo {
+            state: DeploymentState::Pending,
+            description: (!ctx.controllers.is_enabled(*kind))
+                .then(|| CONTROLLER_DISABLED.to_string()),
+            request_id: Some(request_id.0.clone()),
+            updated_at: None,
+            permanent_failure: false,
+            attempts: 0,
+            delete_after: None,
+            history: DeploymentHistory::default(),
+        };
+        match ctx.deployment_state_store.state_write(id, &info).await {
+            Ok(t) => state_writes.push(t),
+            Err(e) => {
+                return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e))
+                    .into_response()
+            }
+        }
+        states.push((*kind, id, owner, info));
+    }
+
+    // NOTE: membership goes first, so the reconcilers never see the members without it
+    if let Err(e) = ctx
+        .bundle_store
+        .put_bundle(&BundleRecord::new(&bundle, Some(request_id.0.clone())))
+        .await
+    {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("failed to store bundle: {:?}", e),
+        )
+            .into_response();
+    }
+    // The members are stored along with their Pending states, a reconciler never picks up a
+    // member still showing what it was before
+    if let Err(e) = ctx
+        .descriptor_store
+        .store_descriptors(&members, &state_writes)
+        .await
+    {
+        return match e.downcast_ref::<NameTaken>() {
+            Some(taken) => (StatusCode::CONFLICT, taken.to_string()),
+            None => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("failed to store descriptors: {:?}", e),
+            ),
+        }
+        .into_response();
+    }
+
+    for (kind, id, owner, info) in states.iter() {
+        ctx.state_event_publisher
+            .publish(
+                StateEventType::DescriptorStored,
+                *kind,
+                id,
+                owner.as_ref(),
+                info,
+            )
+            .await;
+    }
+
+    info!(
+        bundle_id = bundle.id,
+        members = members.len(),
+        "stored bundle"
+    );
+    StatusCode::ACCEPTED.into_response()
+}
+
+// Members whose changes would break their consumers, held for an approver instead of stored
+async fn admit_members(
+    ctx: &AppContext,
+    bundle: &BundleDescriptor,
+    grant: Option<&TokenGrant>,
+    request_id: &RequestId,
+) -> Result<Vec<Approval>> {
+    let requested_by = approvals::requester(grant);
+    let request_id = Some(request_id.0.clone());
+    let mut admissions = vec![
+        ctx.approval_gate
+            .admit(&bundle.database, requested_by.clone(), request_id.clone())
+            .await?,
+    ];
+    for table in bundle.tables.iter() {
+        admissions.push(
+            ctx.approval_gate
+                .admit(table, requested_by.clone(), request_id.clone())
+                .await?,
+        );
+    }
+    for flow in bundle.flows.iter() {
+        admissions.push(
+            ctx.approval_gate
+                .admit(flow, requested_by.clone(), request_id.clone())
+                .await?,
+        );
+    }
+    Ok(admissions
+        .into_iter()
+        .filter_map(|a| match a {
+            Admission::Admitted => None,
+            Admission::Held(approval) => Some(approval),
+        })
+        .collect())
+}
+
+// A scoped token needs to be able to write every member
+async fn authorize_members(
+    ctx: &AppContext,
+    grant: Option<&TokenGrant>,
+    bundle: &BundleDescriptor,
+) -> Result<(), axum::response::Response> {
+    api_tokens::authorize_write(ctx, grant, &bundle.database).await?;
+    for table in bundle.tables.iter() {
+        api_tokens::authorize_write(ctx, grant, table).await?;
+    }
+    for flow in bundle.flows.iter() {
+        api_tokens::authorize_write(ctx, grant, flow).await?;
+    }
+    Ok(())
+}
+
+// Limits and policy, checked for every member before any of them is stored
+async fn check_members(
+    ctx: &AppContext,
+    bundle: &BundleDescriptor,
+) -> Result<(), (StatusCode, String)> {
+    let limits = std::iter::once(bundle.database.check_limits(&ctx.limits))
+        .chain(bundle.tables.iter().map(|t| t.check_limits(&ctx.limits)))
+        .chain(bundle.flows.iter().map(|f| f.check_limits(&ctx.limits)));
+    for (id, result) in bundle.member_ids().zip(limits) {
+        if let Err(e) = result {
+            return Err((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("'{}' exceeds limits: {}", id, e),
+            ));
+        }
+    }
+
+    let Some(policy_checker) = &ctx.policy_checker else {
+        return Ok(());
+    };
+    let mut results = vec![policy_checker.check(&bundle.database).await];
+    for table in bundle.tables.iter() {
+        results.push(policy_checker.check(table).await);
+    }
+    for flow in bundle.flows.iter() {
+        results.push(policy_checker.check(flow).await);
+    }
+
+    let mut reasons = Vec::new();
+    for (id, result) in bundle.member_ids().zip(results) {
+        let Err(e) = result else {
+            continue;
+        };
+        match e.downcast_ref::<PolicyDenied>() {
+            Some(denied) => reasons.push(format!("'{}' {}", id, denied)),
+            None => {
+                return Err((
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    format!("could not check '{}' against policy: {:#}", id, e),
+                ))
+            }
+        }
+    }
+    if !reasons.is_empty() {
+        return Err((StatusCode::FORBIDDEN, reasons.join("; ")));
+    }
+
+    Ok(())
+}
+
+pub async fn get_bundle_status(
+    State(ctx): State<Arc<AppContext>>,
+    Path(bundle_id): Path<String>,
+) -> axum::response::Response {
+    let record = match ctx.bundle_store.get_bundle(&bundle_id).await {
+        Ok(Some(t)) => t,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    };
+
+    let kinds = std::iter::once(DescriptorKind::Database)
+        .chain(record.tables.iter().map(|_| DescriptorKind::Table))
+        .chain(record.flows.iter().map(|_| DescriptorKind::Flow));
+    let mut members = Vec::new();
+    for (id, kind) in record.members().zip(kinds) {
+        let info = match ctx.deployment_state_store.get_state(id).await {
+            Ok(t) => t,
+            Err(e) => {
+                return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e))
+                    .into_response()
+            }
+        };
+        members.push(BundleMemberStatus {
+            id: id.to_string(),
+            kind,
+            state: info.as_ref().map(|i| i.state),
+            description: info.and_then(|i| i.description),
+        });
+    }
+
+    Json(BundleStatus {
+        state: aggregate_state(&members),
+        bundle_id: record.bundle_id,
+        request_id: record.request_id,
+        submitted_at: record.submitted_at,
+        members,
+    })
+    .into_response()
+}
+
+fn aggregate_state(members: &[BundleMemberStatus]) -> DeploymentState {
+    let states: Vec<Option<DeploymentState>> = members.iter().map(|m| m.state).collect();
+    if states.iter().any(|s| {
+        matches!(
+            s,
+            Some(DeploymentState::Failed | DeploymentState::Quarantined)
+        )
+    }) {
+        DeploymentState::Failed
+    } else if states
+        .iter()
+        .all(|s| *s == Some(DeploymentState::Succeeded))
+    {
+        DeploymentState::Succeeded
+    } else if states.iter().any(|s| {
+        matches!(
+            s,
+            Some(DeploymentState::Succeeded | DeploymentState::Deploying)
+        )
+    }) {
+        DeploymentState::Deploying
+    } else {
+        DeploymentState::Pending
+    }
+}
@@ -0,0 +1,64 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+
+use crate::{
+    controller::naming::{maintenance_flow_id, quality_flow_id, statistics_flow_id},
+    descriptor_store::DescriptorStore,
+    fluid::descriptor::{table::TableDescriptor, DescriptorKind},
+    AppContext,
+};
+
+// Month to date spend on a descriptor's resources, as far as cost explorer can attribute it.
+// Glue tables and s3 prefixes can't be tagged, a table's spend is that of the flows basin runs
+// for it while its storage is counted against its database's bucket.
+pub async fn get_cost(
+    kind: DescriptorKind,
+    State(ctx): State<Arc<AppContext>>,
+    Path(descriptor_id): Path<String>,
+) -> axum::response::Response {
+    let attributed_ids = match kind {
+        DescriptorKind::Table => match ctx
+            .descriptor_store
+            .get_descriptor::<TableDescriptor>(&descriptor_id, kind)
+            .await
+        {
+            Ok(Some(table)) => vec![
+                statistics_flow_id(&table),
+                maintenance_flow_id(&table),
+                quality_flow_id(&table),
+            ],
+            Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+            Err(e) => {
+                return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e))
+                    .into_response()
+            }
+        },
+        _ => match ctx
+            .descriptor_store
+            .get_descriptor::<serde_json::Value>(&descriptor_id, kind)
+            .await
+        {
+            Ok(Some(_)) => vec![],
+            Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+            Err(e) => {
+                return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e))
+                    .into_response()
+            }
+        },
+    };
+
+    match ctx
+        .cost_reporter
+        .month_to_date(&descriptor_id, &attributed_ids)
+        .await
+    {
+        Ok(report) => Json(report).into_response(),
+        Err(e) => (StatusCode::BAD_GATEWAY, format!("error {:?}", e)).into_response(),
+    }
+}
@@ -0,0 +1,179 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use anyhow::Result;
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    deployment_state_store::{DeploymentInfo, DeploymentState, DeploymentStateStore},
+    descriptor_store::DescriptorStore,
+    fluid::descriptor::DescriptorKind,
+    AppContext,
+};
+
+// NOTE: compiled in so the ui ships with the binary, there's nothing to serve from disk
+const INDEX_HTML: &str = include_str!("dashboard/index.html");
+const APP_JS: &str = include_str!("dashboard/app.js");
+
+const MAX_LISTED: usize = 20;
+
+#[derive(Serialize, Clone)]
+pub struct DashboardRow {
+    id: String,
+    name: String,
+    kind: DescriptorKind,
+    // Absent until the descriptor's first reconcile is recorded
+    state: Option<DeploymentState>,
+    description: Option<String>,
+    permanent_failure: bool,
+    attempts: u32,
+    last_transition: Option<DateTime<Utc>>,
+    // When basin last found the deployed resources changed outside of it and restored them
+    drifted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct Overview {
+    // Descriptor counts by kind, then by deployment state
+    states: BTreeMap<&'static str, BTreeMap<String, usize>>,
+    // Failed and quarantined, most recently failed first
+    recent_failures: Vec<DashboardRow>,
+    // Most recently drifted first
+    drifted: Vec<DashboardRow>,
+}
+
+pub async fn index() -> impl IntoResponse {
+    Html(INDEX_HTML)
+}
+
+pub async fn app_js() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "application/javascript")], APP_JS)
+}
+
+pub async fn get_overview(State(ctx): State<Arc<AppContext>>) -> axum::response::Response {
+    let deployment_states = match load_states(&ctx).await {
+        Ok(t) => t,
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    };
+
+    let mut states = BTreeMap::new();
+    let mut rows = Vec::new();
+    for kind in DescriptorKind::ALL {
+        let kind_rows = match load_rows(&ctx, kind, &deployment_states).await {
+            Ok(t) => t,
+            Err(e) => {
+                return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e))
+                    .into_response()
+            }
+        };
+
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for row in kind_rows.iter() {
+            let state = row.state.map_or("None".to_string(), |s| format!("{:?}", s));
+            *counts.entry(state).or_default() += 1;
+        }
+        states.insert(kind.as_str(), counts);
+        rows.extend(kind_rows);
+    }
+
+    let mut drifted: Vec<_> = rows
+        .iter()
+        .filter(|r| r.drifted_at.is_some())
+        .cloned()
+        .collect();
+    drifted.sort_by(|a, b| b.drifted_at.cmp(&a.drifted_at));
+    drifted.truncate(MAX_LISTED);
+
+    let mut recent_failures: Vec<_> = rows
+        .into_iter()
+        .filter(|r| {
+            matches!(
+                r.state,
+                Some(DeploymentState::Failed | DeploymentState::Quarantined)
+            )
+        })
+        .collect();
+    recent_failures.sort_by(|a, b| b.last_transition.cmp(&a.last_transition));
+    recent_failures.truncate(MAX_LISTED);
+
+    Json(Overview {
+        states,
+        recent_failures,
+        drifted,
+    })
+    .into_response()
+}
+
+pub async fn list_kind(
+    State(ctx): State<Arc<AppContext>>,
+    Path(kind): Path<String>,
+) -> axum::response::Response {
+    let Ok(kind) = kind.parse::<DescriptorKind>() else {
+        return (StatusCode::NOT_FOUND, format!("unknown kind {}", kind)).into_response();
+    };
+
+    let deployment_states = match load_states(&ctx).await {
+        Ok(t) => t,
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    };
+
+    match load_rows(&ctx, kind, &deployment_states).await {
+        Ok(mut rows) => {
+            rows.sort_by(|a, b| a.name.cmp(&b.name));
+            Json(rows).into_response()
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response(),
+    }
+}
+
+async fn load_states(ctx: &AppContext) -> Result<HashMap<String, DeploymentInfo>> {
+    Ok(ctx
+        .deployment_state_store
+        .list_states()
+        .await?
+        .into_iter()
+        .collect())
+}
+
+async fn load_rows(
+    ctx: &AppContext,
+    kind: DescriptorKind,
+    states: &HashMap<String, DeploymentInfo>,
+) -> Result<Vec<DashboardRow>> {
+    // NOTE: descriptors are read as plain json, the ui only needs their id and name
+    let descriptors = ctx.descriptor_store.list_descriptors::<Value>(kind).await?;
+
+    Ok(descriptors
+        .into_iter()
+        .filter_map(|d| {
+            let id = d["id"].as_str()?.to_string();
+            let name = d["name"].as_str().unwrap_or_default().to_string();
+            let info = states.get(&id);
+            Some(DashboardRow {
+                drifted_at: info.and_then(|i| i.history.last_drift()).map(|t| t.at),
+                state: info.map(|i| i.state),
+                description: info.and_then(|i| i.description.clone()),
+                permanent_failure: info.map_or(false, |i| i.permanent_failure),
+                attempts: info.map_or(0, |i| i.attempts),
+                last_transition: info.and_then(|i| i.history.last_transition),
+                id,
+                name,
+                kind,
+            })
+        })
+        .collect())
+}
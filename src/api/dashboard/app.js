@@ -0,0 +1,103 @@
+"use strict";
+
+const REFRESH_MS = 15000;
+const STATES = ["Pending", "Deploying", "Succeeded", "Failed", "Deleting", "Deleted", "Quarantined", "Unknown", "None"];
+
+let selectedKind = "database";
+
+function el(tag, text, className) {
+  const e = document.createElement(tag);
+  if (text !== undefined && text !== null) e.textContent = text;
+  if (className) e.className = className;
+  return e;
+}
+
+function row(cells, header) {
+  const tr = el("tr");
+  for (const cell of cells) {
+    if (cell instanceof Node) {
+      const td = el(header ? "th" : "td");
+      td.appendChild(cell);
+      tr.appendChild(td);
+    } else {
+      tr.appendChild(el(header ? "th" : "td", cell));
+    }
+  }
+  return tr;
+}
+
+function stateCell(state) {
+  return el("span", state || "None", "state " + (state || "Unknown"));
+}
+
+function since(at) {
+  return at ? new Date(at).toLocaleString() : "";
+}
+
+function descriptorRows(table, rows, withKind) {
+  table.replaceChildren();
+  const header = ["id", "name", "state", "attempts", "last change", "drift", "description"];
+  table.appendChild(row(withKind ? ["kind", ...header] : header, true));
+  if (rows.length === 0) {
+    table.appendChild(row([el("span", "nothing here", "muted")]));
+    return;
+  }
+  for (const r of rows) {
+    const description = (r.permanent_failure ? "[permanent] " : "") + (r.description || "");
+    const cells = [
+      r.id,
+      r.name,
+      stateCell(r.state),
+      String(r.attempts),
+      since(r.last_transition),
+      el("span", r.drifted_at ? since(r.drifted_at) : "", "drift"),
+      description,
+    ];
+    table.appendChild(row(withKind ? [r.kind, ...cells] : cells));
+  }
+}
+
+async function fetchJson(path) {
+  const resp = await fetch(path);
+  if (!resp.ok) throw new Error(path + ": " + resp.status + " " + (await resp.text()));
+  return resp.json();
+}
+
+async function refreshOverview() {
+  const overview = await fetchJson("/ui/api/overview");
+
+  const states = document.getElementById("states");
+  states.replaceChildren(row(["kind", ...STATES], true));
+  const kinds = document.getElementById("kinds");
+  kinds.replaceChildren();
+  for (const [kind, counts] of Object.entries(overview.states)) {
+    states.appendChild(row([kind, ...STATES.map((s) => String(counts[s] || 0))]));
+
+    const link = el("a", kind, kind === selectedKind ? "active" : "");
+    link.onclick = () => {
+      selectedKind = kind;
+      refresh();
+    };
+    kinds.appendChild(link);
+  }
+
+  descriptorRows(document.getElementById("failures"), overview.recent_failures, true);
+  descriptorRows(document.getElementById("drifted"), overview.drifted, true);
+}
+
+async function refreshDescriptors() {
+  const rows = await fetchJson("/ui/api/descriptors/" + encodeURIComponent(selectedKind));
+  descriptorRows(document.getElementById("descriptors"), rows, false);
+}
+
+async function refresh() {
+  try {
+    await Promise.all([refreshOverview(), refreshDescriptors()]);
+    document.getElementById("refreshed").textContent = "last at " + new Date().toLocaleTimeString();
+  } catch (e) {
+    document.getElementById("refreshed").textContent = "refresh failed: " + e.message;
+  }
+}
+
+refresh();
+setInterval(refresh, REFRESH_MS);
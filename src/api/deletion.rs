@@ -0,0 +1,294 @@
+use std::{collections::HashSet, sync::Arc};
+
+use anyhow::Result;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::{Duration, Utc};
+use serde::Deserialize;
+use tracing::info;
+use uuid::Uuid;
+
+use super::{approvals, list::resolve_name};
+use crate::{
+    api_tokens::TokenGrant,
+    approval_store::{Approval, ApprovalOperation, ApprovalState},
+    deployment_state_store::{
+        DeploymentHistory, DeploymentInfo, DeploymentState, DeploymentStateStore,
+    },
+    descriptor_store::DescriptorStore,
+    fluid::descriptor::{
+        database::DatabaseDescriptor,
+        flow::{FlowDescriptor, FlowStepTransformation},
+        table::TableDescriptor,
+        DescriptorKind,
+    },
+    request_id::RequestId,
+    AppContext,
+};
+
+#[derive(Deserialize)]
+pub struct NameQuery {
+    name: String,
+    // The database id for tables
+    namespace: Option<String>,
+}
+
+// NOTE: nothing is torn down here, the descriptor's controller does that once the grace period
+//       has passed and until then the deletion can be undone with a restore
+pub async fn delete_descriptor(
+    kind: DescriptorKind,
+    State(ctx): State<Arc<AppContext>>,
+    Extension(request_id): Extension<RequestId>,
+    grant: Option<Extension<TokenGrant>>,
+    Path(descriptor_id): Path<String>,
+) -> axum::response::Response {
+    match ctx
+        .descriptor_store
+        .get_descriptor::<serde_json::Value>(&descriptor_id, kind)
+        .await
+    {
+        Ok(Some(_)) => (),
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    }
+
+    let prior = match ctx.deployment_state_store.get_state(&descriptor_id).await {
+        Ok(t) => t,
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    };
+    // Deleting again doesn't push the teardown back
+    if let Some(info) = &prior
+        && matches!(
+            info.state,
+            DeploymentState::Deleting
+                | DeploymentState::Deleted
+                | DeploymentState::AwaitingApproval
+        )
+    {
+        return (StatusCode::ACCEPTED, Json(info.clone())).into_response();
+    }
+
+    // NOTE: nothing cascades, dependents are deleted first so what goes is always explicit
+    match dependents(&ctx, kind, &descriptor_id).await {
+        Ok(dependents) if dependents.is_empty() => (),
+        Ok(dependents) => {
+            return (
+                StatusCode::CONFLICT,
+                format!(
+                    "descriptors depending on '{}' have to be deleted first: {}",
+                    descriptor_id,
+                    dependents.join(", ")
+                ),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    }
+
+    if ctx.approvals.as_ref().is_some_and(|a| a.deletions) {
+        let approval = Approval {
+            approval_id: Uuid::new_v4().to_string(),
+            kind,
+            descriptor_id,
+            operation: ApprovalOperation::Delete,
+            reasons: vec!["deletion requested".to_string()],
+            descriptor: None,
+            state: ApprovalState::Pending,
+            requested_by: approvals::requester(grant.as_ref().map(|g| &g.0)),
+            request_id: Some(request_id.0),
+            requested_at: Utc::now(),
+            decided_by: None,
+            decided_at: None,
+            comment: None,
+        };
+        return approvals::request_approval(&ctx, approval).await;
+    }
+
+    let info = DeploymentInfo {
+        state: DeploymentState::Deleting,
+        description: Some("deletion requested".to_string()),
+        request_id: Some(request_id.0),
+        updated_at: None,
+        permanent_failure: false,
+        attempts: 0,
+        delete_after: Some(Utc::now() + Duration::seconds(ctx.deletion.grace_period_secs as i64)),
+        history: DeploymentHistory::default(),
+    };
+    if let Err(e) = ctx
+        .deployment_state_store
+        .set_state(&descriptor_id, &info)
+        .await
+    {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("failed to set deployment state: {:?}", e),
+        )
+            .into_response();
+    }
+
+    info!(
+        descriptor_id,
+        %kind,
+        delete_after = ?info.delete_after,
+        "descriptor marked for deletion"
+    );
+    (StatusCode::ACCEPTED, Json(info)).into_response()
+}
+
+// Ids of what would break were the descriptor torn down, a database's tables and the flows
+// building into it or waiting on its tables. Those already on their way out don't count.
+async fn dependents(
+    ctx: &AppContext,
+    kind: DescriptorKind,
+    descriptor_id: &str,
+) -> Result<Vec<String>> {
+    let (database_name, table_ids) = match kind {
+        DescriptorKind::Database => {
+            let Some(database) = ctx
+                .descriptor_store
+                .get_descriptor::<DatabaseDescriptor>(descriptor_id, kind)
+                .await?
+            else {
+                return Ok(vec![]);
+            };
+            let table_ids: HashSet<String> = ctx
+                .descriptor_store
+                .list_descriptors::<TableDescriptor>(DescriptorKind::Table)
+                .await?
+                .into_iter()
+                .filter(|t| t.database == descriptor_id)
+                .map(|t| t.id)
+                .collect();
+            (Some(database.name), table_ids)
+        }
+        DescriptorKind::Table => (None, HashSet::from([descriptor_id.to_string()])),
+        _ => return Ok(vec![]),
+    };
+
+    let flow_ids = ctx
+        .descriptor_store
+        .list_descriptors::<FlowDescriptor>(DescriptorKind::Flow)
+        .await?
+        .into_iter()
+        .filter(|flow| {
+            flow.upstream_tables().any(|t| table_ids.contains(t))
+                || flow.steps.iter().any(|step| match &step.transformation {
+                    FlowStepTransformation::Dbt(t) => Some(&t.database) == database_name.as_ref(),
+                    _ => false,
+                })
+        })
+        .map(|flow| flow.id);
+
+    let mut dependents = vec![];
+    for id in table_ids
+        .into_iter()
+        .filter(|id| id != descriptor_id)
+        .chain(flow_ids)
+    {
+        match ctx.deployment_state_store.get_state(&id).await? {
+            Some(info)
+                if matches!(
+                    info.state,
+                    DeploymentState::Deleting | DeploymentState::Deleted
+                ) => {}
+            _ => dependents.push(id),
+        }
+    }
+    dependents.sort();
+    Ok(dependents)
+}
+
+pub async fn delete_descriptor_by_name(
+    kind: DescriptorKind,
+    State(ctx): State<Arc<AppContext>>,
+    Extension(request_id): Extension<RequestId>,
+    grant: Option<Extension<TokenGrant>>,
+    Query(query): Query<NameQuery>,
+) -> axum::response::Response {
+    let descriptor_id =
+        match resolve_name(&ctx, kind, query.namespace.as_deref(), &query.name).await {
+            Ok(Some(t)) => t,
+            Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+            Err(resp) => return resp,
+        };
+    delete_descriptor(
+        kind,
+        State(ctx),
+        Extension(request_id),
+        grant,
+        Path(descriptor_id),
+    )
+    .await
+}
+
+pub async fn restore_descriptor(
+    kind: DescriptorKind,
+    State(ctx): State<Arc<AppContext>>,
+    Extension(request_id): Extension<RequestId>,
+    Path(descriptor_id): Path<String>,
+) -> axum::response::Response {
+    match ctx
+        .descriptor_store
+        .get_descriptor::<serde_json::Value>(&descriptor_id, kind)
+        .await
+    {
+        Ok(Some(_)) => (),
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    }
+
+    match ctx.deployment_state_store.get_state(&descriptor_id).await {
+        Ok(Some(info)) if info.state == DeploymentState::Deleting => (),
+        Ok(_) => {
+            return (
+                StatusCode::CONFLICT,
+                "only descriptors awaiting deletion can be restored",
+            )
+                .into_response()
+        }
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    }
+
+    // NOTE: a teardown already under way can't be stopped, the controller's next pass puts back
+    //       whatever it managed to remove
+    if let Err(e) = ctx
+        .deployment_state_store
+        .set_state(
+            &descriptor_id,
+            &DeploymentInfo {
+                state: DeploymentState::Pending,
+                description: Some("restored".to_string()),
+                request_id: Some(request_id.0),
+                updated_at: None,
+                permanent_failure: false,
+                attempts: 0,
+                delete_after: None,
+                history: DeploymentHistory::default(),
+            },
+        )
+        .await
+    {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("failed to set deployment state: {:?}", e),
+        )
+            .into_response();
+    }
+
+    info!(descriptor_id, %kind, "descriptor restored");
+    StatusCode::ACCEPTED.into_response()
+}
@@ -0,0 +1,270 @@
+use std::{collections::BTreeSet, sync::Arc};
+
+use anyhow::Result;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    controller::naming::{glue_database_name, table_location},
+    descriptor_store::DescriptorStore,
+    fluid::descriptor::{
+        database::DatabaseDescriptor, table::TableDescriptor, DescriptorKind,
+        IdentifiableDescriptor, StorageEngine,
+    },
+    provisioner::column_types::glue_type,
+    AppContext,
+};
+
+#[derive(Deserialize)]
+pub struct DiffQuery {
+    // Also compare against what's deployed, only supported for glue tables
+    #[serde(default)]
+    live: bool,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+#[derive(Serialize, Debug)]
+pub struct FieldChange {
+    // e.g. `steps[1].transformation.sql.sql`
+    path: String,
+    change: FieldChangeKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    from: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    to: Option<Value>,
+}
+
+#[derive(Serialize)]
+pub struct LiveDiff {
+    exists: bool,
+    changes: Vec<FieldChange>,
+}
+
+#[derive(Serialize)]
+pub struct DescriptorDiff {
+    // False when nothing is stored under the id yet, every field of the candidate is then added
+    stored: bool,
+    changes: Vec<FieldChange>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    live: Option<LiveDiff>,
+}
+
+// NOTE: both sides go through the descriptor type first so defaulted fields don't show up as
+//       changes, nothing is stored or reconciled
+pub async fn diff_descriptor<T: IdentifiableDescriptor + Serialize + DeserializeOwned>(
+    kind: DescriptorKind,
+    State(ctx): State<Arc<AppContext>>,
+    Path(id): Path<String>,
+    Query(query): Query<DiffQuery>,
+    Json(candidate): Json<T>,
+) -> axum::response::Response {
+    if candidate.id() != id {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("descriptor id {} doesn't match {}", candidate.id(), id),
+        )
+            .into_response();
+    }
+    if query.live && kind != DescriptorKind::Table {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "live diffs are only supported for tables",
+        )
+            .into_response();
+    }
+
+    let stored = match ctx.descriptor_store.get_descriptor::<T>(&id, kind).await {
+        Ok(t) => t,
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    };
+    let candidate_value = serde_json::to_value(&candidate).unwrap_or_default();
+    let stored_value = stored
+        .as_ref()
+        .map(|s| serde_json::to_value(s).unwrap_or_default());
+
+    let mut changes = Vec::new();
+    diff_values(
+        "",
+        stored_value.as_ref().unwrap_or(&Value::Null),
+        &candidate_value,
+        &mut changes,
+    );
+
+    let live = if query.live {
+        // NOTE: re-read as a table, the kind was checked above
+        let table = match serde_json::from_value::<TableDescriptor>(candidate_value) {
+            Ok(t) => t,
+            Err(e) => {
+                return (StatusCode::UNPROCESSABLE_ENTITY, format!("error {:?}", e)).into_response()
+            }
+        };
+        match live_table_diff(&ctx, &table).await {
+            Ok(Ok(t)) => Some(t),
+            Ok(Err(msg)) => return (StatusCode::UNPROCESSABLE_ENTITY, msg).into_response(),
+            Err(e) => {
+                return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e))
+                    .into_response()
+            }
+        }
+    } else {
+        None
+    };
+
+    Json(DescriptorDiff {
+        stored: stored.is_some(),
+        changes,
+        live,
+    })
+    .into_response()
+}
+
+// The glue table the candidate would produce against the one deployed. The outer error is a
+// lookup failure, the inner one a candidate that can't be compared.
+async fn live_table_diff(
+    ctx: &AppContext,
+    table: &TableDescriptor,
+) -> Result<Result<LiveDiff, String>> {
+    let Some(db) = ctx
+        .descriptor_store
+        .get_descriptor::<DatabaseDescriptor>(&table.database, DescriptorKind::Database)
+        .await?
+    else {
+        return Ok(Err(format!("database {} isn't stored", table.database)));
+    };
+    let engine = table.engine.unwrap_or(db.engine);
+    if engine != StorageEngine::Glue {
+        return Ok(Err(format!(
+            "live diffs are only supported for glue tables, not {:?}",
+            engine
+        )));
+    }
+    let (bucket, prefix) = match table_location(table, &db) {
+        Ok(t) => t,
+        Err(e) => return Ok(Err(e.to_string())),
+    };
+
+    let expected = json!({
+        "location": format!("s3://{}/{}", bucket, prefix),
+        // NOTE: glue keeps partition keys apart from the other columns
+        "columns": table.columns.iter()
+            .filter(|c| !table.partitioned_by.contains(&c.name))
+            .map(|c| json!({
+                "name": c.name,
+                "type": glue_type(&c.codec.kind),
+                "comment": c.summary,
+            }))
+            .collect::<Vec<_>>(),
+        "partition_keys": table.partitioned_by,
+    });
+
+    let Some(deployed) = ctx
+        .glue_provisioner
+        .get_table(&glue_database_name(&db), &table.name)
+        .await?
+    else {
+        return Ok(Ok(LiveDiff {
+            exists: false,
+            changes: vec![],
+        }));
+    };
+    let storage = deployed.table().and_then(|t| t.storage_descriptor());
+    let actual = json!({
+        "location": storage.and_then(|s| s.location()),
+        "columns": storage
+            .and_then(|s| s.columns())
+            .unwrap_or_default()
+            .iter()
+            .map(|c| json!({
+                "name": c.name(),
+                "type": c.r#type(),
+                "comment": c.comment(),
+            }))
+            .collect::<Vec<_>>(),
+        "partition_keys": deployed
+            .table()
+            .and_then(|t| t.partition_keys())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|c| c.name())
+            .collect::<Vec<_>>(),
+    });
+
+    let mut changes = Vec::new();
+    diff_values("", &actual, &expected, &mut changes);
+    Ok(Ok(LiveDiff {
+        exists: true,
+        changes,
+    }))
+}
+
+// Objects are compared key by key and arrays index by index, anything else is compared whole
+fn diff_values(path: &str, from: &Value, to: &Value, changes: &mut Vec<FieldChange>) {
+    match (from, to) {
+        (Value::Object(f), Value::Object(t)) => {
+            let keys: BTreeSet<&String> = f.keys().chain(t.keys()).collect();
+            for key in keys {
+                let child = if path.is_empty() {
+                    key.to_string()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                diff_field(child, f.get(key), t.get(key), changes);
+            }
+        }
+        (Value::Array(f), Value::Array(t)) => {
+            for i in 0..f.len().max(t.len()) {
+                diff_field(format!("{}[{}]", path, i), f.get(i), t.get(i), changes);
+            }
+        }
+        (Value::Null, Value::Object(_)) if path.is_empty() => {
+            diff_values(path, &json!({}), to, changes);
+        }
+        _ if from != to => changes.push(FieldChange {
+            path: path.to_string(),
+            change: FieldChangeKind::Changed,
+            from: Some(from.clone()),
+            to: Some(to.clone()),
+        }),
+        _ => {}
+    }
+}
+
+fn diff_field(
+    path: String,
+    from: Option<&Value>,
+    to: Option<&Value>,
+    changes: &mut Vec<FieldChange>,
+) {
+    // NOTE: a null field and a missing one are the same to the descriptor types
+    match (from.filter(|v| !v.is_null()), to.filter(|v| !v.is_null())) {
+        (Some(f), Some(t)) => diff_values(&path, f, t, changes),
+        (None, Some(t)) => changes.push(FieldChange {
+            path,
+            change: FieldChangeKind::Added,
+            from: None,
+            to: Some(t.clone()),
+        }),
+        (Some(f), None) => changes.push(FieldChange {
+            path,
+            change: FieldChangeKind::Removed,
+            from: Some(f.clone()),
+            to: None,
+        }),
+        (None, None) => {}
+    }
+}
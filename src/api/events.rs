@@ -0,0 +1,43 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+
+use crate::{event_record_store::EventRecordStore, AppContext};
+
+const DEFAULT_RECENT_LIMIT: usize = 50;
+
+#[derive(Deserialize)]
+pub struct RecentEventsQuery {
+    limit: Option<usize>,
+}
+
+pub async fn get_recent_events(
+    State(ctx): State<Arc<AppContext>>,
+    Query(query): Query<RecentEventsQuery>,
+) -> axum::response::Response {
+    match ctx
+        .event_record_store
+        .list_recent(query.limit.unwrap_or(DEFAULT_RECENT_LIMIT))
+        .await
+    {
+        Ok(records) => Json(records).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response(),
+    }
+}
+
+pub async fn get_event(
+    State(ctx): State<Arc<AppContext>>,
+    Path(event_id): Path<String>,
+) -> axum::response::Response {
+    match ctx.event_record_store.get_record(&event_id).await {
+        Ok(Some(record)) => Json(record).into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response(),
+    }
+}
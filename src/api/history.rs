@@ -0,0 +1,90 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{
+    deployment_state_store::DeploymentStateStore, descriptor_store::DescriptorStore,
+    fluid::descriptor::DescriptorKind, AppContext,
+};
+
+#[derive(Deserialize)]
+pub struct AtQuery {
+    // RFC 3339, the descriptor as it was stored at that time rather than as it is now
+    at: Option<DateTime<Utc>>,
+}
+
+pub async fn get_descriptor(
+    kind: DescriptorKind,
+    State(ctx): State<Arc<AppContext>>,
+    Path(descriptor_id): Path<String>,
+    Query(query): Query<AtQuery>,
+) -> axum::response::Response {
+    let info = match ctx.deployment_state_store.get_state(&descriptor_id).await {
+        Ok(t) => t,
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    };
+
+    let Some(at) = query.at else {
+        return match ctx
+            .descriptor_store
+            .get_descriptor::<Value>(&descriptor_id, kind)
+            .await
+        {
+            Ok(Some(descriptor)) => Json(json!({
+                "id": descriptor_id,
+                "kind": kind,
+                "descriptor": descriptor,
+                "state": info,
+            }))
+            .into_response(),
+            Ok(None) => StatusCode::NOT_FOUND.into_response(),
+            Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response(),
+        };
+    };
+
+    let revision = match ctx
+        .descriptor_store
+        .get_descriptor_at::<Value>(&descriptor_id, kind, at)
+        .await
+    {
+        Ok(Some(t)) => t,
+        Ok(None) => {
+            return (
+                StatusCode::NOT_FOUND,
+                "no revision of the descriptor was stored by then",
+            )
+                .into_response()
+        }
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    };
+
+    // NOTE: the state comes from the transition history, which only keeps the most recent
+    //       transitions. It's null when the state at the time has already been dropped.
+    let state = info.and_then(|info| {
+        info.history
+            .transitions
+            .into_iter()
+            .find(|transition| transition.at <= at)
+    });
+    Json(json!({
+        "id": descriptor_id,
+        "kind": kind,
+        "at": at,
+        "stored_at": revision.stored_at,
+        "descriptor": revision.descriptor,
+        "state": state,
+    }))
+    .into_response()
+}
@@ -0,0 +1,277 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    deployment_state_store::{DeploymentInfo, DeploymentState, DeploymentStateStore},
+    descriptor_store::DescriptorStore,
+    fluid::descriptor::{DescriptorKind, IdentifiableDescriptor},
+    AppContext,
+};
+
+const DEFAULT_PAGE_LIMIT: usize = 50;
+const MAX_PAGE_LIMIT: usize = 500;
+
+#[derive(Deserialize)]
+pub struct ListQuery {
+    limit: Option<usize>,
+    // Opaque cursor handed back from the previous page, omitted for the first page
+    cursor: Option<String>,
+    state: Option<DeploymentState>,
+    name_contains: Option<String>,
+    // Matches the owner's team, email or slack channel, descriptor listings only
+    owner: Option<String>,
+    // Exact name, looked up rather than scanned for. Tables are named within their database, its
+    // id goes in `namespace`. Deployment listings need the `kind` too, and otherwise use it to
+    // narrow where `name_contains` looks for names.
+    name: Option<String>,
+    namespace: Option<String>,
+    kind: Option<DescriptorKind>,
+}
+
+#[derive(Serialize)]
+pub struct Page<T> {
+    items: Vec<T>,
+    // Absent once the listing is exhausted
+    next_cursor: Option<String>,
+    // When the page's items were read from the store, they're consistent as of this instant.
+    // Pages read through a cursor can each be from a different instant.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    read_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct DeploymentListItem {
+    id: String,
+    #[serde(flatten)]
+    info: DeploymentInfo,
+}
+
+impl ListQuery {
+    fn limit(&self) -> usize {
+        self.limit
+            .unwrap_or(DEFAULT_PAGE_LIMIT)
+            .clamp(1, MAX_PAGE_LIMIT)
+    }
+
+    fn cursor(&self) -> Result<u64, axum::response::Response> {
+        match &self.cursor {
+            None => Ok(0),
+            Some(t) => t
+                .parse()
+                .map_err(|_| (StatusCode::BAD_REQUEST, "invalid cursor").into_response()),
+        }
+    }
+}
+
+fn next_cursor(cursor: u64) -> Option<String> {
+    // NOTE: a SCAN cursor of 0 means the iteration has wrapped around
+    if cursor == 0 {
+        None
+    } else {
+        Some(cursor.to_string())
+    }
+}
+
+// NOTE: filters are applied to each scanned page, so a filtered page may hold fewer items than
+//       `limit` (or none at all) while `next_cursor` is still set. Callers should keep following
+//       the cursor until it's absent.
+pub async fn list_descriptors<T: IdentifiableDescriptor + Serialize + DeserializeOwned + Send>(
+    kind: DescriptorKind,
+    State(ctx): State<Arc<AppContext>>,
+    Query(query): Query<ListQuery>,
+) -> axum::response::Response {
+    let cursor = match query.cursor() {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let (descriptors, cursor, read_at) = match &query.name {
+        Some(name) => {
+            let id = match resolve_name(&ctx, kind, query.namespace.as_deref(), name).await {
+                Ok(t) => t,
+                Err(resp) => return resp,
+            };
+            let descriptor = match id {
+                Some(id) => ctx.descriptor_store.get_descriptor::<T>(&id, kind).await,
+                None => Ok(None),
+            };
+            match descriptor {
+                Ok(t) => (t.into_iter().collect(), 0, None),
+                Err(e) => {
+                    return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e))
+                        .into_response()
+                }
+            }
+        }
+        None => match ctx
+            .descriptor_store
+            .list_descriptors_page::<T>(kind, cursor, query.limit())
+            .await
+        {
+            Ok(page) => (page.descriptors, page.next_cursor, Some(page.read_at)),
+            Err(e) => {
+                return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e))
+                    .into_response()
+            }
+        },
+    };
+
+    let mut items = Vec::new();
+    for descriptor in descriptors {
+        if let Some(needle) = &query.name_contains
+            && !descriptor.name().contains(needle.as_str())
+        {
+            continue;
+        }
+
+        if let Some(owner) = &query.owner
+            && !descriptor.owner().map_or(false, |o| o.matches(owner))
+        {
+            continue;
+        }
+
+        if let Some(state) = query.state {
+            match ctx.deployment_state_store.get_state(&descriptor.id()).await {
+                Ok(Some(info)) if info.state == state => {}
+                Ok(_) => continue,
+                Err(e) => {
+                    return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e))
+                        .into_response()
+                }
+            }
+        }
+
+        items.push(descriptor);
+    }
+
+    Json(Page {
+        items,
+        next_cursor: next_cursor(cursor),
+        read_at,
+    })
+    .into_response()
+}
+
+pub async fn list_deployment_states(
+    State(ctx): State<Arc<AppContext>>,
+    Query(query): Query<ListQuery>,
+) -> axum::response::Response {
+    let cursor = match query.cursor() {
+        Ok(t) => t,
+        Err(resp) => return resp,
+    };
+
+    let listed = match (&query.name, query.kind) {
+        (Some(name), Some(kind)) => {
+            let id = match resolve_name(&ctx, kind, query.namespace.as_deref(), name).await {
+                Ok(t) => t,
+                Err(resp) => return resp,
+            };
+            match id {
+                Some(id) => ctx
+                    .deployment_state_store
+                    .get_state(&id)
+                    .await
+                    .map(|info| (0, info.map(|info| (id, info)).into_iter().collect())),
+                None => Ok((0, Vec::new())),
+            }
+        }
+        (Some(_), None) => {
+            return (StatusCode::BAD_REQUEST, "looking up a name needs its kind").into_response()
+        }
+        (None, _) => {
+            ctx.deployment_state_store
+                .list_states_page(cursor, query.limit())
+                .await
+        }
+    };
+    let (cursor, states) = match listed {
+        Ok(t) => t,
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    };
+
+    let mut items = Vec::new();
+    for (id, info) in states {
+        if query.state.is_some_and(|s| info.state != s) {
+            continue;
+        }
+
+        // NOTE: states don't know their descriptor's name, it's read from the descriptor itself.
+        //       Those whose descriptor has been forgotten have no name left to match.
+        if let Some(needle) = &query.name_contains {
+            let name = match descriptor_name(&ctx, &id, query.kind).await {
+                Ok(t) => t,
+                Err(e) => {
+                    return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e))
+                        .into_response()
+                }
+            };
+            if !name.is_some_and(|n| n.contains(needle.as_str())) {
+                continue;
+            }
+        }
+
+        items.push(DeploymentListItem { id, info });
+    }
+
+    Json(Page {
+        items,
+        next_cursor: next_cursor(cursor),
+        read_at: None,
+    })
+    .into_response()
+}
+
+// Name of the descriptor with the id, looked for under every kind unless it's known
+async fn descriptor_name(
+    ctx: &AppContext,
+    id: &str,
+    kind: Option<DescriptorKind>,
+) -> Result<Option<String>> {
+    let kinds = match kind {
+        Some(kind) => vec![kind],
+        None => DescriptorKind::ALL.to_vec(),
+    };
+    for kind in kinds {
+        if let Some(descriptor) = ctx
+            .descriptor_store
+            .get_descriptor::<serde_json::Value>(id, kind)
+            .await?
+        {
+            return Ok(descriptor["name"].as_str().map(str::to_string));
+        }
+    }
+    Ok(None)
+}
+
+// Id of the descriptor stored under the name, the error response is ready to return
+pub async fn resolve_name(
+    ctx: &AppContext,
+    kind: DescriptorKind,
+    namespace: Option<&str>,
+    name: &str,
+) -> Result<Option<String>, axum::response::Response> {
+    if kind == DescriptorKind::Table && namespace.is_none() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "tables are named within their database, its id goes in `namespace`",
+        )
+            .into_response());
+    }
+
+    ctx.descriptor_store
+        .get_id_by_name(kind, namespace, name)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response())
+}
@@ -0,0 +1,88 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::Serialize;
+
+use crate::{
+    descriptor_store::DescriptorStore,
+    fluid::descriptor::{
+        database::DatabaseDescriptor,
+        table::{DataClassification, DescriptiveMetadata, TableDescriptor},
+        DescriptorKind, Owner,
+    },
+    AppContext,
+};
+
+#[derive(Serialize)]
+pub struct TableMetadata {
+    id: String,
+    name: String,
+    database: String,
+    summary: String,
+    owner: Option<Owner>,
+    classification: Option<DataClassification>,
+    metadata: Option<DescriptiveMetadata>,
+    columns: Vec<ColumnMetadata>,
+}
+
+#[derive(Serialize)]
+pub struct ColumnMetadata {
+    name: String,
+    summary: String,
+    metadata: Option<DescriptiveMetadata>,
+}
+
+// A table's descriptive side for catalog uis, with whatever it inherits from its database
+pub async fn get_table_metadata(
+    State(ctx): State<Arc<AppContext>>,
+    Path(id): Path<String>,
+) -> axum::response::Response {
+    let table = match ctx
+        .descriptor_store
+        .get_descriptor::<TableDescriptor>(&id, DescriptorKind::Table)
+        .await
+    {
+        Ok(Some(t)) => t,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    };
+    // NOTE: a table can be stored ahead of its database, it just has nothing to inherit yet
+    let database = match ctx
+        .descriptor_store
+        .get_descriptor::<DatabaseDescriptor>(&table.database, DescriptorKind::Database)
+        .await
+    {
+        Ok(t) => t,
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    };
+    let table = table.with_defaults(database.as_ref().and_then(|d| d.defaults.as_ref()));
+
+    Json(TableMetadata {
+        id: table.id,
+        name: table.name,
+        database: table.database,
+        summary: table.summary,
+        owner: table.owner,
+        classification: table.classification,
+        metadata: table.metadata,
+        columns: table
+            .columns
+            .into_iter()
+            .map(|c| ColumnMetadata {
+                name: c.name,
+                summary: c.summary,
+                metadata: c.metadata,
+            })
+            .collect(),
+    })
+    .into_response()
+}
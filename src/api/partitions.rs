@@ -0,0 +1,460 @@
+use std::sync::Arc;
+
+use anyhow::{bail, ensure, Result};
+use aws_sdk_glue::model::{PartitionInput, StorageDescriptor};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::{Duration, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::task;
+use tracing::{error, info};
+
+use crate::{
+    controller::naming::glue_database_name,
+    descriptor_store::DescriptorStore,
+    fluid::descriptor::{
+        database::DatabaseDescriptor,
+        table::{TableColumnAttribute, TableColumnType, TableDescriptor},
+        DescriptorKind, StorageEngine,
+    },
+    partition_repair_store::{PartitionRepair, PartitionRepairStore, RepairStatus},
+    provisioner::{glue::GlueTableClient, s3::split_s3_uri},
+    request_id::RequestId,
+    AppContext,
+};
+
+// Partitions taken in one batch request
+const MAX_PARTITIONS_PER_REQUEST: usize = 1000;
+// A repair still unfinished after this long is assumed to have been cut short
+const REPAIR_STALE_AFTER_SECS: i64 = 60 * 60;
+
+#[derive(Deserialize)]
+pub struct PartitionValues {
+    // One per partition key, in the order of the table's partitioned_by
+    values: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct PartitionBatch {
+    partitions: Vec<PartitionValues>,
+}
+
+#[derive(Serialize)]
+pub struct PartitionRegistration {
+    registered: Vec<Vec<String>>,
+    // Already registered before the request, nothing was changed for them
+    existing: Vec<Vec<String>>,
+}
+
+pub async fn register_partition(
+    State(ctx): State<Arc<AppContext>>,
+    Path(table_id): Path<String>,
+    Json(partition): Json<PartitionValues>,
+) -> axum::response::Response {
+    register(&ctx, &table_id, vec![partition.values]).await
+}
+
+pub async fn register_partitions(
+    State(ctx): State<Arc<AppContext>>,
+    Path(table_id): Path<String>,
+    Json(batch): Json<PartitionBatch>,
+) -> axum::response::Response {
+    if batch.partitions.len() > MAX_PARTITIONS_PER_REQUEST {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!(
+                "at most {} partitions can be registered at once",
+                MAX_PARTITIONS_PER_REQUEST
+            ),
+        )
+            .into_response();
+    }
+    register(
+        &ctx,
+        &table_id,
+        batch.partitions.into_iter().map(|p| p.values).collect(),
+    )
+    .await
+}
+
+// The table partitions are registered for, as it's deployed to glue
+struct PartitionTarget {
+    table: TableDescriptor,
+    db_name: String,
+    storage: StorageDescriptor,
+}
+
+async fn partition_target(
+    ctx: &AppContext,
+    table_id: &str,
+) -> Result<PartitionTarget, axum::response::Response> {
+    let table = match ctx
+        .descriptor_store
+        .get_descriptor::<TableDescriptor>(table_id, DescriptorKind::Table)
+        .await
+    {
+        Ok(Some(t)) => t,
+        Ok(None) => return Err(StatusCode::NOT_FOUND.into_response()),
+        Err(e) => {
+            return Err(
+                (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response(),
+            )
+        }
+    };
+    let db = match ctx
+        .descriptor_store
+        .get_descriptor::<DatabaseDescriptor>(&table.database, DescriptorKind::Database)
+        .await
+    {
+        Ok(Some(t)) => t,
+        Ok(None) => {
+            return Err((
+                StatusCode::CONFLICT,
+                format!("database {} isn't stored", table.database),
+            )
+                .into_response())
+        }
+        Err(e) => {
+            return Err(
+                (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response(),
+            )
+        }
+    };
+    let table = table.with_defaults(db.defaults.as_ref());
+    if table.engine.unwrap_or(db.engine) != StorageEngine::Glue || table.format.is_some() {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "partitions are only registered for plain glue tables",
+        )
+            .into_response());
+    }
+    if table.partitioned_by.is_empty() {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "the table isn't partitioned",
+        )
+            .into_response());
+    }
+    // NOTE: athena ignores registered partitions once the table's are projected
+    if table.partition_projection.is_some() {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "the table's partitions are projected, they don't need registering",
+        )
+            .into_response());
+    }
+
+    let db_name = glue_database_name(&db);
+    let deployed = match ctx.glue_provisioner.get_table(&db_name, &table.name).await {
+        Ok(t) => t,
+        Err(e) => {
+            return Err(
+                (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response(),
+            )
+        }
+    };
+    let Some(deployed) = deployed.as_ref().and_then(|t| t.table()) else {
+        return Err((StatusCode::CONFLICT, "the table isn't deployed to glue yet").into_response());
+    };
+    let deployed_keys: Vec<&str> = deployed
+        .partition_keys()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|c| c.name())
+        .collect();
+    if deployed_keys != table.partitioned_by {
+        return Err((
+            StatusCode::CONFLICT,
+            format!(
+                "the glue table is partitioned by {:?} rather than {:?}, it hasn't been reconciled yet",
+                deployed_keys, table.partitioned_by
+            ),
+        )
+            .into_response());
+    }
+    let Some(storage) = deployed.storage_descriptor().cloned() else {
+        return Err((
+            StatusCode::CONFLICT,
+            "the glue table has no storage descriptor",
+        )
+            .into_response());
+    };
+
+    Ok(PartitionTarget {
+        table,
+        db_name,
+        storage,
+    })
+}
+
+// NOTE: a failure part way through leaves the earlier partitions registered, registering is
+//       idempotent so the whole request can just be retried
+async fn register(
+    ctx: &AppContext,
+    table_id: &str,
+    partitions: Vec<Vec<String>>,
+) -> axum::response::Response {
+    let target = match partition_target(ctx, table_id).await {
+        Ok(t) => t,
+        Err(response) => return response,
+    };
+
+    let mut unique: Vec<Vec<String>> = Vec::new();
+    for values in partitions {
+        if let Err(e) = check_values(&target.table, &values) {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("invalid partition {:?}: {:#}", values, e),
+            )
+                .into_response();
+        }
+        if !unique.contains(&values) {
+            unique.push(values);
+        }
+    }
+
+    let existing = match create_partitions(ctx, &target, &unique).await {
+        Ok(t) => t,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("failed to register partitions: {:?}", e),
+            )
+                .into_response()
+        }
+    };
+
+    let (existing, registered): (Vec<_>, Vec<_>) =
+        unique.into_iter().partition(|v| existing.contains(v));
+    info!(
+        table_id,
+        registered = registered.len(),
+        existing = existing.len(),
+        "registered table partitions"
+    );
+    Json(PartitionRegistration {
+        registered,
+        existing,
+    })
+    .into_response()
+}
+
+// Returns the values of the partitions which were already registered
+async fn create_partitions(
+    ctx: &AppContext,
+    target: &PartitionTarget,
+    partitions: &[Vec<String>],
+) -> Result<Vec<Vec<String>>> {
+    let inputs = partitions
+        .iter()
+        .map(|values| partition_input(&target.table.partitioned_by, &target.storage, values))
+        .collect();
+    ctx.glue_provisioner
+        .create_partitions(&target.db_name, &target.table.name, inputs)
+        .await
+}
+
+// Discovers the table's partitions from the objects under its location and registers any that
+// aren't yet, in the background. Progress shows in the table's status.
+pub async fn repair_partitions(
+    State(ctx): State<Arc<AppContext>>,
+    Extension(request_id): Extension<RequestId>,
+    Path(table_id): Path<String>,
+) -> axum::response::Response {
+    let target = match partition_target(&ctx, &table_id).await {
+        Ok(t) => t,
+        Err(response) => return response,
+    };
+
+    match ctx.partition_repair_store.get_repair(&table_id).await {
+        // NOTE: a repair cut short by a restart never finishes, it's taken over once it's stale
+        Ok(Some(repair))
+            if !repair.is_finished()
+                && Utc::now() - repair.started_at < Duration::seconds(REPAIR_STALE_AFTER_SECS) =>
+        {
+            return (StatusCode::CONFLICT, Json(repair)).into_response()
+        }
+        Ok(_) => (),
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    }
+
+    let mut repair = PartitionRepair {
+        repair_id: request_id.0,
+        table_id: table_id.clone(),
+        status: RepairStatus::Listing,
+        started_at: Utc::now(),
+        finished_at: None,
+        objects_scanned: 0,
+        objects_skipped: 0,
+        partitions_found: 0,
+        partitions_registered: 0,
+        error: None,
+    };
+    if let Err(e) = ctx.partition_repair_store.put_repair(&repair).await {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("failed to store repair: {:?}", e),
+        )
+            .into_response();
+    }
+
+    info!(
+        table_id,
+        repair_id = repair.repair_id,
+        "starting partition repair"
+    );
+    let response = (StatusCode::ACCEPTED, Json(repair.clone())).into_response();
+    task::spawn(async move {
+        if let Err(e) = repair_table(&ctx, &target, &mut repair).await {
+            error!(table_id, ?e, "partition repair failed");
+            repair.status = RepairStatus::Failed;
+            repair.error = Some(format!("{:#}", e));
+        } else {
+            repair.status = RepairStatus::Succeeded;
+        }
+        repair.finished_at = Some(Utc::now());
+        if let Err(e) = ctx.partition_repair_store.put_repair(&repair).await {
+            error!(table_id, ?e, "failed to store partition repair outcome");
+        }
+    });
+    response
+}
+
+async fn repair_table(
+    ctx: &AppContext,
+    target: &PartitionTarget,
+    repair: &mut PartitionRepair,
+) -> Result<()> {
+    let location = target.storage.location().unwrap_or_default();
+    let Some((bucket, prefix)) = split_s3_uri(location) else {
+        bail!("table location '{}' isn't an s3 prefix", location);
+    };
+    // NOTE: the whole listing is held in memory, fine for tables up to a few million objects
+    let prefix = format!("{}/", prefix);
+    let objects = ctx.s3_provisioner.list_objects(&bucket, &prefix).await?;
+
+    let mut partitions: Vec<Vec<String>> = Vec::new();
+    for object in objects.iter() {
+        repair.objects_scanned += 1;
+        let path = object.key.strip_prefix(&prefix).unwrap_or_default();
+        match partition_values(&target.table, path) {
+            Some(values) if !partitions.contains(&values) => partitions.push(values),
+            Some(_) => (),
+            None => repair.objects_skipped += 1,
+        }
+    }
+    repair.partitions_found = partitions.len();
+    repair.status = RepairStatus::Registering;
+    ctx.partition_repair_store.put_repair(repair).await?;
+
+    let existing = create_partitions(ctx, target, &partitions).await?;
+    repair.partitions_registered = partitions.len() - existing.len();
+    info!(
+        table_id = repair.table_id,
+        found = repair.partitions_found,
+        registered = repair.partitions_registered,
+        skipped = repair.objects_skipped,
+        "finished partition repair"
+    );
+    Ok(())
+}
+
+// The partition an object is in from its path under the table's location, laid out as
+// `{key}={value}/...` for each partition key in order. None for objects outside that layout.
+fn partition_values(table: &TableDescriptor, path: &str) -> Option<Vec<String>> {
+    let segments: Vec<&str> = path.split('/').collect();
+    // NOTE: the last segment is the object's name, even if it's empty for a directory marker
+    if segments.len() <= table.partitioned_by.len() {
+        return None;
+    }
+    let values = table
+        .partitioned_by
+        .iter()
+        .zip(segments)
+        .map(|(key, segment)| {
+            let (name, value) = segment.split_once('=')?;
+            (name == key).then(|| value.to_string())
+        })
+        .collect::<Option<Vec<_>>>()?;
+    check_values(table, &values).ok()?;
+    Some(values)
+}
+
+fn check_values(table: &TableDescriptor, values: &[String]) -> Result<()> {
+    let columns = table.partition_columns();
+    ensure!(
+        values.len() == columns.len(),
+        "expected {} values, one for each of {:?}",
+        columns.len(),
+        table.partitioned_by
+    );
+    for (column, value) in columns.into_iter().zip(values) {
+        check_value(column, value)?;
+    }
+    Ok(())
+}
+
+// NOTE: values end up in the partition's path, so those which would need escaping are refused
+fn check_value(column: &TableColumnAttribute, value: &str) -> Result<()> {
+    ensure!(
+        !value.is_empty() && !value.contains(['/', '=']) && !value.chars().any(char::is_control),
+        "'{}' is empty or has a character that can't be part of a path",
+        column.name
+    );
+    let parses = match column.codec.kind {
+        TableColumnType::Int => value.parse::<i32>().is_ok(),
+        TableColumnType::Long => value.parse::<i64>().is_ok(),
+        TableColumnType::Float | TableColumnType::Double => value.parse::<f64>().is_ok(),
+        TableColumnType::Boolean => matches!(value, "true" | "false"),
+        TableColumnType::String => true,
+        TableColumnType::Date => NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok(),
+        TableColumnType::Timestamp => {
+            NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f").is_ok()
+        }
+        TableColumnType::Complex => bail!("'{}' can't be a partition key", column.name),
+    };
+    ensure!(
+        parses,
+        "'{}' isn't a valid {:?} for '{}'",
+        value,
+        column.codec.kind,
+        column.name
+    );
+    Ok(())
+}
+
+// The partition is stored like the table, under `{table location}/{key}={value}/...`
+fn partition_input(
+    keys: &[String],
+    storage: &StorageDescriptor,
+    values: &[String],
+) -> PartitionInput {
+    let mut location = storage
+        .location()
+        .unwrap_or_default()
+        .trim_end_matches('/')
+        .to_string();
+    for (key, value) in keys.iter().zip(values) {
+        location.push_str(&format!("/{}={}", key, value));
+    }
+    location.push('/');
+
+    let storage = StorageDescriptor::builder()
+        .set_columns(storage.columns().map(|c| c.to_vec()))
+        .location(location)
+        .set_input_format(storage.input_format().map(str::to_string))
+        .set_output_format(storage.output_format().map(str::to_string))
+        .set_serde_info(storage.serde_info().cloned())
+        .compressed(storage.compressed())
+        .build();
+    PartitionInput::builder()
+        .set_values(Some(values.to_vec()))
+        .storage_descriptor(storage)
+        .build()
+}
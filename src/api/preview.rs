@@ -0,0 +1,126 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::{
+    controller::naming::glue_database_name,
+    descriptor_store::DescriptorStore,
+    fluid::descriptor::{
+        database::DatabaseDescriptor, table::TableDescriptor, DescriptorKind, StorageEngine,
+    },
+    AppContext,
+};
+
+#[derive(Deserialize)]
+pub struct PreviewQuery {
+    #[serde(default = "default_preview_limit")]
+    limit: usize,
+}
+
+fn default_preview_limit() -> usize {
+    50
+}
+
+// The table's first rows as athena reads them, to check a table actually reads once it's deployed
+pub async fn preview_table(
+    State(ctx): State<Arc<AppContext>>,
+    Path(table_id): Path<String>,
+    Query(query): Query<PreviewQuery>,
+) -> axum::response::Response {
+    let (Some(conf), Some(athena)) = (&ctx.preview, &ctx.preview_provisioner) else {
+        return (StatusCode::NOT_IMPLEMENTED, "previews aren't configured").into_response();
+    };
+    if query.limit == 0 || query.limit > conf.max_rows {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("limit must be between 1 and {}", conf.max_rows),
+        )
+            .into_response();
+    }
+
+    let table = match ctx
+        .descriptor_store
+        .get_descriptor::<TableDescriptor>(&table_id, DescriptorKind::Table)
+        .await
+    {
+        Ok(Some(t)) => t,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    };
+    let db = match ctx
+        .descriptor_store
+        .get_descriptor::<DatabaseDescriptor>(&table.database, DescriptorKind::Database)
+        .await
+    {
+        Ok(Some(t)) => t,
+        Ok(None) => {
+            return (
+                StatusCode::CONFLICT,
+                format!("database {} isn't stored", table.database),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    };
+    let engine = table.engine.unwrap_or(db.engine);
+    if engine != StorageEngine::Glue {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!(
+                "previews are only supported for glue tables, not {:?}",
+                engine
+            ),
+        )
+            .into_response();
+    }
+
+    // NOTE: athena runs as basin, which lake formation doesn't mask for, so masked columns are
+    //       left out rather than shown to whoever asks
+    let columns: Vec<String> = table
+        .columns
+        .iter()
+        .filter(|c| c.masking.is_none())
+        .map(|c| format!("\"{}\"", c.name))
+        .collect();
+    if columns.is_empty() {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "every column of the table is masked",
+        )
+            .into_response();
+    }
+
+    let db_name = glue_database_name(&db);
+    let sql = format!(
+        "SELECT {} FROM \"{}\".\"{}\" LIMIT {}",
+        columns.join(", "),
+        db_name,
+        table.name,
+        query.limit
+    );
+    match athena
+        .query_rows(&db_name, &sql, query.limit, conf.max_bytes_scanned)
+        .await
+    {
+        Ok(rows) => Json(rows).into_response(),
+        Err(e) => {
+            warn!(table_id, ?e, "table preview failed");
+            (
+                StatusCode::BAD_GATEWAY,
+                format!("preview query failed: {:#}", e),
+            )
+                .into_response()
+        }
+    }
+}
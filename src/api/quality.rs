@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use tracing::info;
+
+use crate::{
+    controller::quality::expectation_suite,
+    descriptor_store::DescriptorStore,
+    fluid::descriptor::{database::DatabaseDescriptor, table::TableDescriptor, DescriptorKind},
+    quality_store::{QualityResult, QualityRuleResult, QualityStore},
+    AppContext,
+};
+
+#[derive(Deserialize)]
+pub struct QualityReport {
+    // Echoed from the suite's `meta.basin.descriptor_hash`
+    descriptor_hash: String,
+    succeeded: bool,
+    rules: Vec<QualityRuleResult>,
+}
+
+// The table's quality rules as a great expectations suite, fetched by its validation flow
+pub async fn get_quality_suite(
+    State(ctx): State<Arc<AppContext>>,
+    Path(id): Path<String>,
+) -> axum::response::Response {
+    let table = match ctx
+        .descriptor_store
+        .get_descriptor::<TableDescriptor>(&id, DescriptorKind::Table)
+        .await
+    {
+        Ok(Some(t)) => t,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    };
+    let Some(quality) = &table.quality else {
+        return (StatusCode::NOT_FOUND, "table has no quality rules").into_response();
+    };
+    let database = match ctx
+        .descriptor_store
+        .get_descriptor::<DatabaseDescriptor>(&table.database, DescriptorKind::Database)
+        .await
+    {
+        Ok(Some(t)) => t,
+        Ok(None) => {
+            return (StatusCode::NOT_FOUND, "table's database isn't stored yet").into_response()
+        }
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    };
+
+    Json(expectation_suite(&table, &database, quality)).into_response()
+}
+
+// Where validation flows report back to, the latest report is shown in the table's status
+pub async fn report_quality_result(
+    State(ctx): State<Arc<AppContext>>,
+    Path(id): Path<String>,
+    Json(report): Json<QualityReport>,
+) -> axum::response::Response {
+    let table = match ctx
+        .descriptor_store
+        .get_descriptor::<TableDescriptor>(&id, DescriptorKind::Table)
+        .await
+    {
+        Ok(Some(t)) => t,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    };
+    let Some(quality) = &table.quality else {
+        return (StatusCode::NOT_FOUND, "table has no quality rules").into_response();
+    };
+    if let Some(r) = report.rules.iter().find(|r| r.rule >= quality.rules.len()) {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("table has no quality rule {}", r.rule),
+        )
+            .into_response();
+    }
+
+    let result = QualityResult {
+        descriptor_hash: report.descriptor_hash,
+        succeeded: report.succeeded,
+        rules: report.rules,
+        reported_at: Utc::now(),
+    };
+    info!(
+        table_id = %id,
+        succeeded = result.succeeded,
+        "recording quality result"
+    );
+    match ctx.quality_store.set_result(&id, &result).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response(),
+    }
+}
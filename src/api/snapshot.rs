@@ -0,0 +1,192 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Bytes,
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Extension, Json,
+};
+use serde::Deserialize;
+use tracing::info;
+
+use crate::{
+    descriptor_encryption,
+    request_id::RequestId,
+    snapshot::{
+        check_entries, open_snapshot, restore_entries, seal_snapshot, take_snapshot, Snapshot,
+        SnapshotEntry,
+    },
+    store_migration::store_digest,
+    AppContext,
+};
+
+const JSON_CONTENT_TYPE: &str = "application/json";
+const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+const SEALED_CONTENT_TYPE: &str = "text/plain";
+const DIGEST_PAGE_SIZE: usize = 500;
+
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    // `json` (default) or `ndjson`, one entry per line
+    #[serde(default)]
+    format: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ImportQuery {
+    // Replace descriptors and states which already exist instead of skipping them
+    #[serde(default)]
+    overwrite: bool,
+}
+
+pub async fn export_snapshot(
+    State(ctx): State<Arc<AppContext>>,
+    Query(query): Query<ExportQuery>,
+) -> axum::response::Response {
+    let snapshot = match take_snapshot(&ctx.descriptor_store, &ctx.deployment_state_store).await {
+        Ok(t) => t,
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    };
+    info!(entries = snapshot.entries.len(), "exported snapshot");
+
+    let (content_type, body) = match query.format.as_deref() {
+        None | Some("json") => (JSON_CONTENT_TYPE, serde_json::to_string(&snapshot)),
+        Some("ndjson") => (
+            NDJSON_CONTENT_TYPE,
+            snapshot
+                .entries
+                .iter()
+                .map(|entry| serde_json::to_string(entry).map(|line| line + "\n"))
+                .collect::<Result<String, _>>(),
+        ),
+        Some(other) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                format!("unknown format {}, expected json or ndjson", other),
+            )
+                .into_response()
+        }
+    };
+    let body = match body {
+        Ok(t) => t,
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    };
+
+    // NOTE: a sealed snapshot is one envelope whichever format is inside it
+    if !descriptor_encryption::is_installed() {
+        return ([(header::CONTENT_TYPE, content_type)], body).into_response();
+    }
+    match seal_snapshot(body).await {
+        Ok(sealed) => ([(header::CONTENT_TYPE, SEALED_CONTENT_TYPE)], sealed).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response(),
+    }
+}
+
+// Accepts either export format, ndjson when sent with its content type, sealed or not
+pub async fn import_snapshot(
+    State(ctx): State<Arc<AppContext>>,
+    Extension(request_id): Extension<RequestId>,
+    Query(query): Query<ImportQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> axum::response::Response {
+    let is_ndjson = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map_or(false, |v| v.starts_with(NDJSON_CONTENT_TYPE));
+    let body = match String::from_utf8(body.to_vec()) {
+        Ok(t) => t,
+        Err(e) => {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("invalid snapshot: {}", e),
+            )
+                .into_response()
+        }
+    };
+    let body = match open_snapshot(body).await {
+        Ok(t) => t,
+        Err(e) => {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("invalid snapshot: {:#}", e),
+            )
+                .into_response()
+        }
+    };
+    let entries = if is_ndjson {
+        body.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str::<SnapshotEntry>)
+            .collect::<Result<Vec<_>, _>>()
+    } else {
+        serde_json::from_str::<Snapshot>(&body).map(|s| s.entries)
+    };
+    let entries = match entries {
+        Ok(t) => t,
+        Err(e) => {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("invalid snapshot: {}", e),
+            )
+                .into_response()
+        }
+    };
+
+    // NOTE: descriptors are checked up front so a bad snapshot doesn't leave a partial import
+    if let Err(e) = check_entries(&entries) {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("invalid snapshot: {:#}", e),
+        )
+            .into_response();
+    }
+
+    let summary = match restore_entries(
+        &ctx.descriptor_store,
+        &ctx.deployment_state_store,
+        Some(&ctx.approval_gate),
+        Some(&request_id.0),
+        entries,
+        query.overwrite,
+    )
+    .await
+    {
+        Ok(t) => t,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("import stopped partway through: {:?}", e),
+            )
+                .into_response()
+        }
+    };
+
+    info!(
+        descriptors = summary.descriptors,
+        deployment_states = summary.deployment_states,
+        skipped = summary.skipped.len(),
+        awaiting_approval = summary.awaiting_approval.len(),
+        "imported snapshot"
+    );
+    Json(summary).into_response()
+}
+
+// Counts and hashes of everything stored, to check a store migration from either side of it
+pub async fn get_store_digest(State(ctx): State<Arc<AppContext>>) -> axum::response::Response {
+    match store_digest(
+        &ctx.descriptor_store,
+        &ctx.deployment_state_store,
+        DIGEST_PAGE_SIZE,
+    )
+    .await
+    {
+        Ok(digest) => Json(digest).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response(),
+    }
+}
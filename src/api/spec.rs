@@ -0,0 +1,39 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+
+use crate::{
+    controller::{base::BaseController, error::ControllerReconciliationError},
+    fluid::descriptor::flow::FlowDescriptor,
+    AppContext,
+};
+
+// What a flow would deploy as on its backend, without storing or deploying it. Lets a change to a
+// descriptor, or to basin, be checked against the specs it generates before it's submitted.
+pub async fn preview_flow_spec(
+    State(ctx): State<Arc<AppContext>>,
+    Json(descriptor): Json<FlowDescriptor>,
+) -> axum::response::Response {
+    if let Err(e) = ctx.flow_controller.validate(&descriptor).await {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("invalid flow: {:#}", e),
+        )
+            .into_response();
+    }
+
+    match ctx.flow_controller.generate_spec(&descriptor).await {
+        Ok(spec) => Json(spec).into_response(),
+        Err(e) => match e.downcast_ref::<ControllerReconciliationError>() {
+            Some(
+                ControllerReconciliationError::ControllerError(_)
+                | ControllerReconciliationError::DependencyMissing(_),
+            ) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("could not generate spec: {:#}", e),
+            )
+                .into_response(),
+            _ => (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response(),
+        },
+    }
+}
@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    api_token_store::{ApiToken, ApiTokenStore},
+    api_tokens::{mint_secret, Scopes},
+    config::ApiTokensConf,
+    request_id::RequestId,
+    AppContext,
+};
+
+#[derive(Deserialize)]
+pub struct NewApiToken {
+    name: String,
+    scopes: Vec<String>,
+}
+
+// The only time the secret is handed out, only its hash is stored
+#[derive(Serialize)]
+pub struct MintedApiToken {
+    #[serde(flatten)]
+    token: ApiToken,
+    secret: String,
+}
+
+fn api_tokens_conf(ctx: &AppContext) -> Result<&ApiTokensConf, axum::response::Response> {
+    ctx.api_tokens.as_ref().ok_or_else(|| {
+        (StatusCode::NOT_IMPLEMENTED, "api tokens aren't configured").into_response()
+    })
+}
+
+pub async fn mint_token(
+    State(ctx): State<Arc<AppContext>>,
+    Extension(request_id): Extension<RequestId>,
+    Json(new_token): Json<NewApiToken>,
+) -> axum::response::Response {
+    if let Err(response) = api_tokens_conf(&ctx) {
+        return response;
+    }
+    if new_token.name.is_empty() || new_token.scopes.is_empty() {
+        return (StatusCode::BAD_REQUEST, "a token needs a name and scopes").into_response();
+    }
+    if let Err(e) = Scopes::parse(&new_token.scopes) {
+        return (StatusCode::BAD_REQUEST, format!("{:#}", e)).into_response();
+    }
+
+    let (secret, secret_hash) = mint_secret();
+    let token = ApiToken {
+        token_id: Uuid::new_v4().to_string(),
+        name: new_token.name,
+        scopes: new_token.scopes,
+        created_at: Utc::now(),
+    };
+    if let Err(e) = ctx.api_token_store.create_token(&secret_hash, &token).await {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("failed to store api token: {:?}", e),
+        )
+            .into_response();
+    }
+
+    info!(
+        token_id = token.token_id,
+        token = token.name,
+        scopes = ?token.scopes,
+        request_id = request_id.0,
+        "minted api token"
+    );
+    (StatusCode::CREATED, Json(MintedApiToken { token, secret })).into_response()
+}
+
+pub async fn list_tokens(State(ctx): State<Arc<AppContext>>) -> axum::response::Response {
+    if let Err(response) = api_tokens_conf(&ctx) {
+        return response;
+    }
+    match ctx.api_token_store.list_tokens().await {
+        Ok(tokens) => Json(tokens).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response(),
+    }
+}
+
+pub async fn revoke_token(
+    State(ctx): State<Arc<AppContext>>,
+    Extension(request_id): Extension<RequestId>,
+    Path(token_id): Path<String>,
+) -> axum::response::Response {
+    if let Err(response) = api_tokens_conf(&ctx) {
+        return response;
+    }
+    match ctx.api_token_store.revoke_token(&token_id).await {
+        Ok(true) => {
+            info!(token_id, request_id = request_id.0, "revoked api token");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(false) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response(),
+    }
+}
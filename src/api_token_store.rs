@@ -0,0 +1,103 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::RedisConf, redis_connection::RedisConnector, redis_namespace::prefixed};
+
+// Tokens by the sha256 of their secret, and that hash by the token's id for revoking it. The
+// secret itself is only ever handed back when the token is minted.
+const API_TOKENS_KEY: &str = "api-tokens";
+const API_TOKEN_IDS_KEY: &str = "api-tokens-by-id";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApiToken {
+    pub token_id: String,
+    // Who or what holds it, e.g. `analytics-ci`
+    pub name: String,
+    // e.g. `table:write`, `flow:read`, `namespace:analytics/*`, see api_tokens::Scopes
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[async_trait::async_trait]
+pub(crate) trait ApiTokenStore {
+    async fn create_token(&self, secret_hash: &str, token: &ApiToken) -> Result<()>;
+    async fn get_token(&self, secret_hash: &str) -> Result<Option<ApiToken>>;
+    async fn list_tokens(&self) -> Result<Vec<ApiToken>>;
+    // False when there's no token with the id
+    async fn revoke_token(&self, token_id: &str) -> Result<bool>;
+}
+
+#[derive(Debug)]
+pub struct RedisApiTokenStore {
+    connector: RedisConnector,
+    key_prefix: String,
+}
+
+#[async_trait::async_trait]
+impl ApiTokenStore for RedisApiTokenStore {
+    async fn create_token(&self, secret_hash: &str, token: &ApiToken) -> Result<()> {
+        let mut conn = self.connector.get_connection().await?;
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .hset(
+                self.key(API_TOKENS_KEY),
+                secret_hash,
+                serde_json::to_string(token)?,
+            )
+            .ignore()
+            .hset(self.key(API_TOKEN_IDS_KEY), &token.token_id, secret_hash)
+            .ignore();
+        pipe.query_async::<_, ()>(&mut conn).await?;
+        Ok(())
+    }
+
+    async fn get_token(&self, secret_hash: &str) -> Result<Option<ApiToken>> {
+        let mut conn = self.connector.get_connection().await?;
+        let token: Option<String> = conn.hget(self.key(API_TOKENS_KEY), secret_hash).await?;
+        Ok(token.map(|t| serde_json::from_str(&t)).transpose()?)
+    }
+
+    async fn list_tokens(&self) -> Result<Vec<ApiToken>> {
+        let mut conn = self.connector.get_connection().await?;
+        let records: Vec<String> = conn.hvals(self.key(API_TOKENS_KEY)).await?;
+
+        let mut tokens = Vec::new();
+        for record in records {
+            tokens.push(serde_json::from_str::<ApiToken>(&record)?);
+        }
+        tokens.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+        Ok(tokens)
+    }
+
+    async fn revoke_token(&self, token_id: &str) -> Result<bool> {
+        let mut conn = self.connector.get_connection().await?;
+        let secret_hash: Option<String> = conn.hget(self.key(API_TOKEN_IDS_KEY), token_id).await?;
+        let Some(secret_hash) = secret_hash else {
+            return Ok(false);
+        };
+
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .hdel(self.key(API_TOKENS_KEY), &secret_hash)
+            .ignore()
+            .hdel(self.key(API_TOKEN_IDS_KEY), token_id)
+            .ignore();
+        pipe.query_async::<_, ()>(&mut conn).await?;
+        Ok(true)
+    }
+}
+
+impl RedisApiTokenStore {
+    pub async fn new(conf: &RedisConf) -> Result<Self> {
+        Ok(Self {
+            connector: RedisConnector::new(conf)?,
+            key_prefix: conf.key_prefix.clone(),
+        })
+    }
+
+    fn key(&self, key: &str) -> String {
+        prefixed(&self.key_prefix, key)
+    }
+}
@@ -0,0 +1,391 @@
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use axum::{
+    extract::{Query, State},
+    http::{header::AUTHORIZATION, Method, Request, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::RngCore;
+use regex::Regex;
+use serde::{de::DeserializeOwned, Deserialize};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::{
+    api_token_store::ApiTokenStore,
+    descriptor_store::DescriptorStore,
+    fluid::descriptor::{
+        database::DatabaseDescriptor, flow::FlowDescriptor, landing_zone::LandingZoneDescriptor,
+        table::TableDescriptor, DescriptorKind, IdentifiableDescriptor,
+    },
+    metrics, AppContext,
+};
+
+// Left open with tokens on, probes and scrapes don't carry one
+const OPEN_PATHS: &[&str] = &["/healthcheck", "/metrics"];
+// Operator routes, minting and revoking tokens among them, only take the admin token
+const ADMIN_PATH_PREFIX: &str = "/api/v1/admin/";
+const API_PATH_PREFIX: &str = "/api/v1/";
+// Span kinds but only need the scopes for the kinds they carry, their handlers check those
+const HANDLER_CHECKED_PATHS: &[&str] = &["/api/v1/bundle/reconcile"];
+const TOKEN_PREFIX: &str = "basin_";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+// Signing off on what someone else asked for, neither comes with any kind's scopes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    // `approvals:decide`, approving and rejecting held operations
+    Approvals,
+    // `access-requests:decide`, approving, denying and revoking access requests
+    AccessRequests,
+}
+
+// What a token may do, from scopes like:
+//   `table:read`, `flow:write`      per kind, `*` for every kind, write implies read
+//   `namespace:analytics/*`          narrows writes to descriptors whose `{owner team}/{name}`
+//                                    matches, `*` matching anything. Without any, writes reach
+//                                    descriptors of every team.
+//   `approvals:decide`               see Decision, `access-requests:decide` likewise
+#[derive(Debug, Clone)]
+pub struct Scopes {
+    kinds: Vec<(Option<DescriptorKind>, Access)>,
+    namespaces: Vec<Regex>,
+    decisions: Vec<Decision>,
+}
+
+impl Scopes {
+    pub fn parse(scopes: &[String]) -> Result<Self> {
+        let mut parsed = Scopes {
+            kinds: vec![],
+            namespaces: vec![],
+            decisions: vec![],
+        };
+        for scope in scopes {
+            let Some((subject, verb)) = scope.split_once(':') else {
+                bail!(
+                    "invalid scope '{}', expected `{{kind}}:{{read|write}}`",
+                    scope
+                );
+            };
+            if subject == "namespace" {
+                let pattern = verb
+                    .split('*')
+                    .map(regex::escape)
+                    .collect::<Vec<_>>()
+                    .join(".*");
+                parsed
+                    .namespaces
+                    .push(Regex::new(&format!("^{}$", pattern))?);
+                continue;
+            }
+            if scope == "approvals:decide" {
+                parsed.decisions.push(Decision::Approvals);
+                continue;
+            }
+            if scope == "access-requests:decide" {
+                parsed.decisions.push(Decision::AccessRequests);
+                continue;
+            }
+            let kind = match subject {
+                "*" => None,
+                kind => Some(kind.parse::<DescriptorKind>()?),
+            };
+            let access = match verb {
+                "read" => Access::Read,
+                "write" => Access::Write,
+                _ => bail!("invalid scope '{}', expected read or write", scope),
+            };
+            parsed.kinds.push((kind, access));
+        }
+        Ok(parsed)
+    }
+
+    // The admin token's, everything everywhere
+    fn all() -> Self {
+        Scopes {
+            kinds: vec![(None, Access::Write)],
+            namespaces: vec![],
+            decisions: vec![Decision::Approvals, Decision::AccessRequests],
+        }
+    }
+
+    // `kind` None is every kind at once, for routes that aren't about one kind
+    pub fn allows(&self, kind: Option<DescriptorKind>, access: Access) -> bool {
+        self.kinds.iter().any(|(k, a)| {
+            (k.is_none() || *k == kind) && (*a == Access::Write || access == Access::Read)
+        })
+    }
+
+    pub fn allows_namespace(&self, namespace: &str) -> bool {
+        self.namespaces.is_empty() || self.namespaces.iter().any(|n| n.is_match(namespace))
+    }
+
+    pub fn may_decide(&self, decision: Decision) -> bool {
+        self.decisions.contains(&decision)
+    }
+
+    pub fn may_write<Descriptor: IdentifiableDescriptor>(&self, descriptor: &Descriptor) -> bool {
+        self.allows(Some(descriptor.kind()), Access::Write)
+            && self.allows_namespace(&namespace_of(descriptor))
+    }
+}
+
+// The caller a request was authenticated as, handed to handlers as an extension
+#[derive(Debug, Clone)]
+pub struct TokenGrant {
+    pub name: String,
+    pub scopes: Arc<Scopes>,
+}
+
+// A fresh token secret and the hash it's stored under
+pub fn mint_secret() -> (String, String) {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let secret = format!("{}{}", TOKEN_PREFIX, URL_SAFE_NO_PAD.encode(bytes));
+    let hash = secret_hash(&secret);
+    (secret, hash)
+}
+
+pub fn secret_hash(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
+
+// What namespace scopes are matched against
+pub fn namespace_of<Descriptor: IdentifiableDescriptor>(descriptor: &Descriptor) -> String {
+    let team = descriptor.owner().map(|o| o.team).unwrap_or_default();
+    format!("{}/{}", team, descriptor.name())
+}
+
+#[derive(Deserialize)]
+struct NameQuery {
+    name: String,
+    namespace: Option<String>,
+}
+
+// Layered on the whole api when tokens are configured. Checks the bearer token and the scope the
+// route needs for its kind, and for writes to a stored descriptor its namespace. Submissions and
+// bundles carry their descriptors in the body, their handlers check those.
+pub async fn authenticate<B>(
+    State(ctx): State<Arc<AppContext>>,
+    mut req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let Some(conf) = &ctx.api_tokens else {
+        return next.run(req).await;
+    };
+    let path = req.uri().path().to_string();
+    if OPEN_PATHS.contains(&path.as_str()) {
+        return next.run(req).await;
+    }
+
+    let Some(secret) = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+    else {
+        return (StatusCode::UNAUTHORIZED, "an api token is needed").into_response();
+    };
+    let hash = secret_hash(secret);
+
+    let grant = if hash == conf.admin_token_sha256 {
+        TokenGrant {
+            name: "admin".to_string(),
+            scopes: Arc::new(Scopes::all()),
+        }
+    } else if path.starts_with(ADMIN_PATH_PREFIX) {
+        return (StatusCode::FORBIDDEN, "admin routes need the admin token").into_response();
+    } else {
+        let token = match ctx.api_token_store.get_token(&hash).await {
+            Ok(Some(t)) => t,
+            Ok(None) => {
+                return (StatusCode::UNAUTHORIZED, "unknown or revoked api token").into_response()
+            }
+            Err(e) => {
+                return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e))
+                    .into_response()
+            }
+        };
+        // NOTE: scopes are checked when minting, one failing to parse now means the token is
+        //       from a newer basin, it's refused rather than read more permissively
+        match Scopes::parse(&token.scopes) {
+            Ok(scopes) => TokenGrant {
+                name: token.name,
+                scopes: Arc::new(scopes),
+            },
+            Err(e) => return (StatusCode::FORBIDDEN, format!("{:#}", e)).into_response(),
+        }
+    };
+
+    if HANDLER_CHECKED_PATHS.contains(&path.as_str()) {
+        req.extensions_mut().insert(grant);
+        return next.run(req).await;
+    }
+    // NOTE: deciders are named by their token, the decision handlers take who decided from here
+    if let Some(decision) = required_decision(req.method(), &path) {
+        if !grant.scopes.may_decide(decision) {
+            return forbidden(&grant, &path);
+        }
+        req.extensions_mut().insert(grant);
+        return next.run(req).await;
+    }
+
+    let (kind, access) = required_access(req.method(), &path);
+    if !grant.scopes.allows(kind, access) {
+        return forbidden(&grant, &path);
+    }
+
+    if let Some(kind) = kind
+        && access == Access::Write
+    {
+        match stored_namespace(&ctx, kind, req.uri()).await {
+            Ok(Some(namespace)) if !grant.scopes.allows_namespace(&namespace) => {
+                return forbidden(&grant, &path)
+            }
+            Ok(_) => {}
+            Err(e) => {
+                return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e))
+                    .into_response()
+            }
+        }
+    }
+
+    req.extensions_mut().insert(grant);
+    next.run(req).await
+}
+
+fn forbidden(grant: &TokenGrant, path: &str) -> Response {
+    warn!(token = grant.name, path, "api token is missing a scope");
+    metrics::counter_inc(
+        "basin_api_forbidden_total",
+        &[("token", grant.name.as_str())],
+    );
+    (
+        StatusCode::FORBIDDEN,
+        format!("token '{}' isn't scoped for this", grant.name),
+    )
+        .into_response()
+}
+
+// The kind a route is about, None when it spans kinds, and whether it changes anything. Diffs,
+// previews and spec renders are posted but only read.
+fn required_access(method: &Method, path: &str) -> (Option<DescriptorKind>, Access) {
+    let access = if method == Method::GET
+        || method == Method::HEAD
+        || ["/diff", "/preview", "/spec"]
+            .iter()
+            .any(|suffix| path.ends_with(suffix))
+    {
+        Access::Read
+    } else {
+        Access::Write
+    };
+    let kind = path
+        .strip_prefix(API_PATH_PREFIX)
+        .and_then(|rest| rest.split('/').next())
+        .and_then(|segment| segment.parse::<DescriptorKind>().ok());
+    (kind, access)
+}
+
+// Routes signing off on someone else's request, `/api/v1/{approvals|access-requests}/{id}/{verb}`
+fn required_decision(method: &Method, path: &str) -> Option<Decision> {
+    if method != Method::POST {
+        return None;
+    }
+    let rest = path.strip_prefix(API_PATH_PREFIX)?;
+    let (collection, rest) = rest.split_once('/')?;
+    let (_, verb) = rest.split_once('/')?;
+    match (collection, verb) {
+        ("approvals", "approve" | "reject") => Some(Decision::Approvals),
+        ("access-requests", "approve" | "deny" | "revoke") => Some(Decision::AccessRequests),
+        _ => None,
+    }
+}
+
+// The namespace of the stored descriptor a write targets, by id in the path or by name in the
+// query. None for routes that don't target one, or when it isn't stored.
+async fn stored_namespace(
+    ctx: &AppContext,
+    kind: DescriptorKind,
+    uri: &Uri,
+) -> Result<Option<String>> {
+    // `/api/v1/{kind}/{id}/..`
+    let mut segments = uri.path().trim_end_matches('/').split('/').skip(4);
+    let id = match segments.next() {
+        Some("reconcile" | "spec") => return Ok(None),
+        Some(id) => id.to_string(),
+        None => {
+            let Ok(Query(query)) = Query::<NameQuery>::try_from_uri(uri) else {
+                return Ok(None);
+            };
+            match ctx
+                .descriptor_store
+                .get_id_by_name(kind, query.namespace.as_deref(), &query.name)
+                .await?
+            {
+                Some(id) => id,
+                None => return Ok(None),
+            }
+        }
+    };
+
+    match kind {
+        DescriptorKind::Database => namespace_of_stored::<DatabaseDescriptor>(ctx, kind, &id).await,
+        DescriptorKind::Table => namespace_of_stored::<TableDescriptor>(ctx, kind, &id).await,
+        DescriptorKind::Flow => namespace_of_stored::<FlowDescriptor>(ctx, kind, &id).await,
+        DescriptorKind::LandingZone => {
+            namespace_of_stored::<LandingZoneDescriptor>(ctx, kind, &id).await
+        }
+    }
+}
+
+async fn namespace_of_stored<Descriptor: IdentifiableDescriptor + DeserializeOwned>(
+    ctx: &AppContext,
+    kind: DescriptorKind,
+    id: &str,
+) -> Result<Option<String>> {
+    let descriptor = ctx
+        .descriptor_store
+        .get_descriptor::<Descriptor>(id, kind)
+        .await
+        .with_context(|| format!("could not read {} {}", kind, id))?;
+    Ok(descriptor.as_ref().map(namespace_of))
+}
+
+// Handlers' check of a descriptor submitted in the body, against its namespace both as submitted
+// and as stored so a token can't take over another team's descriptor by changing its owner
+pub async fn authorize_write<Descriptor: IdentifiableDescriptor + DeserializeOwned>(
+    ctx: &AppContext,
+    grant: Option<&TokenGrant>,
+    descriptor: &Descriptor,
+) -> Result<(), Response> {
+    let Some(grant) = grant else {
+        return Ok(());
+    };
+    let kind = descriptor.kind();
+    let stored = match ctx
+        .descriptor_store
+        .get_descriptor::<Descriptor>(&descriptor.id(), kind)
+        .await
+    {
+        Ok(t) => t,
+        Err(e) => {
+            return Err(
+                (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response(),
+            )
+        }
+    };
+    let path = format!("{}{}/{}", API_PATH_PREFIX, kind, descriptor.id());
+    if !grant.scopes.may_write(descriptor) || stored.is_some_and(|s| !grant.scopes.may_write(&s)) {
+        return Err(forbidden(grant, &path));
+    }
+    Ok(())
+}
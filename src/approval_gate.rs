@@ -0,0 +1,161 @@
+use anyhow::{ensure, Result};
+use chrono::Utc;
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    approval_store::{
+        Approval, ApprovalOperation, ApprovalState, ApprovalStore, RedisApprovalStore,
+    },
+    config::BasinConfig,
+    deployment_state_store::{
+        DeploymentHistory, DeploymentInfo, DeploymentState, DeploymentStateStore,
+        RedisDeploymentStateStore,
+    },
+    descriptor_store::{DescriptorStore, RedisDescriptorStore},
+    fluid::descriptor::IdentifiableDescriptor,
+};
+
+pub enum Admission {
+    Admitted,
+    // Waiting on an approver, the submission is only stored once it's approved
+    Held(Approval),
+}
+
+// Holds operations back for a second person. Submissions that would break the consumers of what's
+// stored are held whichever way they arrive, everything that stores descriptors (the api, bundles,
+// git, s3, events, custom resources, imports) is admitted here first.
+pub struct ApprovalGate {
+    breaking_changes: bool,
+    descriptor_store: RedisDescriptorStore,
+    deployment_state_store: RedisDeploymentStateStore,
+    approval_store: RedisApprovalStore,
+}
+
+impl ApprovalGate {
+    pub async fn new(conf: &BasinConfig) -> Result<Self> {
+        Ok(ApprovalGate {
+            breaking_changes: conf.approvals.as_ref().is_some_and(|a| a.breaking_changes),
+            descriptor_store: RedisDescriptorStore::new(&conf.redis).await?,
+            deployment_state_store: RedisDeploymentStateStore::new(&conf.redis).await?,
+            approval_store: RedisApprovalStore::new(&conf.redis).await?,
+        })
+    }
+
+    // Admits everything, as when breaking changes don't need approval
+    #[cfg(test)]
+    pub async fn for_tests(redis: &crate::config::RedisConf) -> Self {
+        ApprovalGate {
+            breaking_changes: false,
+            descriptor_store: RedisDescriptorStore::new(redis).await.unwrap(),
+            deployment_state_store: RedisDeploymentStateStore::new(redis).await.unwrap(),
+            approval_store: RedisApprovalStore::new(redis).await.unwrap(),
+        }
+    }
+
+    // Whether the descriptor can replace what's stored, or has to wait for an approver first
+    pub async fn admit<Descriptor>(
+        &self,
+        descriptor: &Descriptor,
+        requested_by: Option<String>,
+        request_id: Option<String>,
+    ) -> Result<Admission>
+    where
+        Descriptor: IdentifiableDescriptor + Serialize + DeserializeOwned + Sync,
+    {
+        if !self.breaking_changes {
+            return Ok(Admission::Admitted);
+        }
+        let Some(stored) = self
+            .descriptor_store
+            .get_descriptor::<Descriptor>(&descriptor.id(), descriptor.kind())
+            .await?
+        else {
+            return Ok(Admission::Admitted);
+        };
+        let reasons = descriptor.breaking_changes(&stored);
+        if reasons.is_empty() {
+            return Ok(Admission::Admitted);
+        }
+
+        // NOTE: sources that resync (git, s3, custom resources) offer the same submission every
+        //       pass. It's held by the approval already waiting on it rather than a new one, and
+        //       once rejected it isn't put up again from there until it changes.
+        let submission = serde_json::to_value(descriptor)?;
+        if let Some(last) = self.last_approval(&descriptor.id()).await?
+            && last.descriptor.as_ref() == Some(&submission)
+            && (last.state == ApprovalState::Pending
+                || (last.state == ApprovalState::Rejected && requested_by.is_none()))
+        {
+            return Ok(Admission::Held(last));
+        }
+
+        let approval = Approval {
+            approval_id: Uuid::new_v4().to_string(),
+            kind: descriptor.kind(),
+            descriptor_id: descriptor.id(),
+            operation: ApprovalOperation::BreakingChange,
+            reasons,
+            descriptor: Some(submission),
+            state: ApprovalState::Pending,
+            requested_by,
+            request_id,
+            requested_at: Utc::now(),
+            decided_by: None,
+            decided_at: None,
+            comment: None,
+        };
+        self.hold(&approval).await?;
+        Ok(Admission::Held(approval))
+    }
+
+    // Records the approval and keeps the descriptor's stored revision where it is meanwhile
+    pub async fn hold(&self, approval: &Approval) -> Result<()> {
+        ensure!(
+            self.approval_store.create_approval(approval).await?,
+            "approval {} already exists",
+            approval.approval_id
+        );
+        let info = DeploymentInfo {
+            state: DeploymentState::AwaitingApproval,
+            description: Some(format!(
+                "awaiting approval: {}",
+                approval.reasons.join("; ")
+            )),
+            request_id: Some(approval.approval_id.clone()),
+            updated_at: None,
+            permanent_failure: false,
+            attempts: 0,
+            delete_after: None,
+            history: DeploymentHistory::default(),
+        };
+        self.deployment_state_store
+            .set_state(&approval.descriptor_id, &info)
+            .await?;
+
+        info!(
+            approval_id = approval.approval_id,
+            descriptor_id = approval.descriptor_id,
+            operation = ?approval.operation,
+            requested_by = approval.requested_by.as_deref(),
+            request_id = approval.request_id.as_deref(),
+            "operation awaiting approval"
+        );
+        Ok(())
+    }
+
+    // The approval behind the descriptor's deployment state, decisions carry the approval's id
+    // along as the request id
+    async fn last_approval(&self, descriptor_id: &str) -> Result<Option<Approval>> {
+        let Some(approval_id) = self
+            .deployment_state_store
+            .get_state(descriptor_id)
+            .await?
+            .and_then(|info| info.request_id)
+        else {
+            return Ok(None);
+        };
+        self.approval_store.get_approval(&approval_id).await
+    }
+}
@@ -0,0 +1,123 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::RedisConf, fluid::descriptor::DescriptorKind, redis_connection::RedisConnector,
+    redis_namespace::prefixed,
+};
+
+const APPROVALS_KEY: &str = "approvals";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalState {
+    // Waiting on an approver, the descriptor sits in AwaitingApproval meanwhile
+    Pending,
+    Approved,
+    Rejected,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalOperation {
+    Delete,
+    // A submission that would break the descriptor's consumers, e.g. dropping a table's column
+    BreakingChange,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Approval {
+    pub approval_id: String,
+    pub kind: DescriptorKind,
+    pub descriptor_id: String,
+    pub operation: ApprovalOperation,
+    // What makes the operation destructive
+    pub reasons: Vec<String>,
+    // The submission held back until it's approved, breaking changes only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub descriptor: Option<serde_json::Value>,
+    pub state: ApprovalState,
+    pub requested_by: Option<String>,
+    // What asked for the operation, e.g. the api request id or the git commit
+    #[serde(default)]
+    pub request_id: Option<String>,
+    pub requested_at: DateTime<Utc>,
+    pub decided_by: Option<String>,
+    pub decided_at: Option<DateTime<Utc>>,
+    pub comment: Option<String>,
+}
+
+#[async_trait::async_trait]
+pub(crate) trait ApprovalStore {
+    // False when an approval with the id already exists
+    async fn create_approval(&self, approval: &Approval) -> Result<bool>;
+    async fn put_approval(&self, approval: &Approval) -> Result<()>;
+    async fn get_approval(&self, approval_id: &str) -> Result<Option<Approval>>;
+    async fn list_approvals(&self) -> Result<Vec<Approval>>;
+}
+
+#[derive(Debug)]
+pub struct RedisApprovalStore {
+    connector: RedisConnector,
+    key_prefix: String,
+}
+
+#[async_trait::async_trait]
+impl ApprovalStore for RedisApprovalStore {
+    async fn create_approval(&self, approval: &Approval) -> Result<bool> {
+        let mut conn = self.connector.get_connection().await?;
+        let created: bool = conn
+            .hset_nx(
+                self.key(APPROVALS_KEY),
+                &approval.approval_id,
+                serde_json::to_string(approval)?,
+            )
+            .await?;
+        Ok(created)
+    }
+
+    async fn put_approval(&self, approval: &Approval) -> Result<()> {
+        let mut conn = self.connector.get_connection().await?;
+        let _: () = conn
+            .hset(
+                self.key(APPROVALS_KEY),
+                &approval.approval_id,
+                serde_json::to_string(approval)?,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn get_approval(&self, approval_id: &str) -> Result<Option<Approval>> {
+        let mut conn = self.connector.get_connection().await?;
+        let approval: Option<String> = conn.hget(self.key(APPROVALS_KEY), approval_id).await?;
+        Ok(approval.map(|a| serde_json::from_str(&a)).transpose()?)
+    }
+
+    async fn list_approvals(&self) -> Result<Vec<Approval>> {
+        let mut conn = self.connector.get_connection().await?;
+        let records: Vec<String> = conn.hvals(self.key(APPROVALS_KEY)).await?;
+
+        let mut approvals = Vec::new();
+        for record in records {
+            approvals.push(serde_json::from_str::<Approval>(&record)?);
+        }
+        approvals.sort_by_key(|a| a.requested_at);
+        Ok(approvals)
+    }
+}
+
+impl RedisApprovalStore {
+    pub async fn new(conf: &RedisConf) -> Result<Self> {
+        Ok(Self {
+            connector: RedisConnector::new(conf)?,
+            key_prefix: conf.key_prefix.clone(),
+        })
+    }
+
+    fn key(&self, key: &str) -> String {
+        prefixed(&self.key_prefix, key)
+    }
+}
@@ -0,0 +1,141 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use aws_config::{AppName, ConfigLoader};
+use aws_smithy_client::{
+    erase::DynConnector,
+    http_connector::{ConnectorSettings, HttpConnector},
+    hyper_ext,
+};
+use aws_smithy_http::{body::SdkBody, result::ConnectorError};
+use aws_smithy_types::{
+    retry::{RetryConfig, RetryMode},
+    timeout::TimeoutConfig,
+};
+use tower::Service;
+use tracing::{debug_span, Instrument};
+
+use crate::{config::AwsClientConf, metrics};
+
+// Every aws client is built from the SdkConfig this loads, so they all share its retries,
+// timeouts, user agent and instrumented connector
+pub fn loader(conf: &AwsClientConf) -> ConfigLoader {
+    let timeout_config = TimeoutConfig::builder()
+        .connect_timeout(Duration::from_secs(conf.connect_timeout_secs))
+        .operation_attempt_timeout(Duration::from_secs(conf.attempt_timeout_secs))
+        .operation_timeout(Duration::from_secs(conf.operation_timeout_secs))
+        .build();
+    let connector = hyper_ext::Adapter::builder()
+        .connector_settings(ConnectorSettings::from_timeout_config(&timeout_config))
+        .build(aws_smithy_client::conns::https());
+
+    aws_config::from_env()
+        // NOTE: adaptive retries also slow down the client when aws starts throttling, rather
+        //       than every reconcile retrying into the same rate limit
+        .retry_config(
+            RetryConfig::standard()
+                .with_retry_mode(RetryMode::Adaptive)
+                .with_max_attempts(conf.max_attempts),
+        )
+        .timeout_config(timeout_config)
+        // NOTE: app names can't hold a `/`, it's sent as `app/basin-<version>`
+        .app_name(
+            AppName::new(format!("basin-{}", env!("CARGO_PKG_VERSION")))
+                .expect("basin's version is a valid app name"),
+        )
+        .http_connector(HttpConnector::Prebuilt(Some(DynConnector::new(
+            InstrumentedConnector { inner: connector },
+        ))))
+}
+
+// Times every request sent to aws and counts them by service, operation and response status.
+// Sits below the retry layer, so each attempt is counted on its own.
+#[derive(Clone, Debug)]
+struct InstrumentedConnector<C> {
+    inner: C,
+}
+
+impl<C> Service<http::Request<SdkBody>> for InstrumentedConnector<C>
+where
+    C: Service<http::Request<SdkBody>, Response = http::Response<SdkBody>, Error = ConnectorError>,
+    C::Future: Send + 'static,
+{
+    type Response = http::Response<SdkBody>;
+    type Error = ConnectorError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<SdkBody>) -> Self::Future {
+        let service = signed_service(&req).unwrap_or("unknown").to_string();
+        let operation = operation(&req);
+        let span = debug_span!("aws_request", %service, %operation);
+        let started = Instant::now();
+        let response = self.inner.call(req);
+
+        Box::pin(
+            async move {
+                let result = response.await;
+                let status = match &result {
+                    Ok(resp) => resp.status().as_str().to_string(),
+                    Err(_) => "connection_error".to_string(),
+                };
+                let labels = [
+                    ("service", service.as_str()),
+                    ("operation", operation.as_str()),
+                ];
+                metrics::counter_inc(
+                    "basin_aws_requests_total",
+                    &[labels[0], labels[1], ("status", &status)],
+                );
+                metrics::counter_add(
+                    "basin_aws_request_seconds_sum",
+                    &labels,
+                    started.elapsed().as_secs_f64(),
+                );
+                metrics::counter_inc("basin_aws_request_seconds_count", &labels);
+                result
+            }
+            .instrument(span),
+        )
+    }
+}
+
+// Requests are signed by the time they reach the connector, the service is part of the
+// signature's credential scope: `Credential=<key>/<date>/<region>/<service>/aws4_request`
+fn signed_service(req: &http::Request<SdkBody>) -> Option<&str> {
+    let auth = req
+        .headers()
+        .get(http::header::AUTHORIZATION)?
+        .to_str()
+        .ok()?;
+    let scope = auth.split("Credential=").nth(1)?;
+    scope.split(['/', ',']).nth(3)
+}
+
+// Json protocol services name the operation in x-amz-target, query protocol ones (sns, sqs) in
+// the form body. Everything else (s3) only has its http method to go on.
+fn operation(req: &http::Request<SdkBody>) -> String {
+    if let Some(target) = req
+        .headers()
+        .get("x-amz-target")
+        .and_then(|v| v.to_str().ok())
+    {
+        return target.rsplit('.').next().unwrap_or(target).to_string();
+    }
+    if let Some(action) = req
+        .body()
+        .bytes()
+        .and_then(|b| std::str::from_utf8(b).ok())
+        .and_then(|form| form.split('&').find_map(|kv| kv.strip_prefix("Action=")))
+    {
+        return action.to_string();
+    }
+    req.method().to_string()
+}
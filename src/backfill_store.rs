@@ -0,0 +1,89 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::RedisConf, redis_connection::RedisConnector, redis_namespace::prefixed};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackfillStatus {
+    // Accepted but not yet part of the flow's waterwheel job
+    Requested,
+    // Waterwheel has the backfill trigger and is working through the range
+    Submitted,
+    // Every run in the range has finished, the trigger has been dropped from the job
+    Completed,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BackfillRecord {
+    pub backfill_id: String,
+    pub flow_id: String,
+    // Runs are triggered for every schedule tick in [start, end]
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub status: BackfillStatus,
+    pub requested_at: DateTime<Utc>,
+    pub submitted_at: Option<DateTime<Utc>>,
+    // Schedule ticks in the range, None when the schedule couldn't be evaluated
+    pub expected_runs: Option<usize>,
+    // Runs in the range waterwheel has finished, successfully or not
+    #[serde(default)]
+    pub finished_runs: usize,
+    #[serde(default)]
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+#[async_trait::async_trait]
+pub(crate) trait BackfillStore {
+    async fn put_backfill(&self, record: &BackfillRecord) -> Result<()>;
+    async fn list_backfills(&self, flow_id: &str) -> Result<Vec<BackfillRecord>>;
+}
+
+#[derive(Debug)]
+pub struct RedisBackfillStore {
+    connector: RedisConnector,
+    key_prefix: String,
+}
+
+#[async_trait::async_trait]
+impl BackfillStore for RedisBackfillStore {
+    async fn put_backfill(&self, record: &BackfillRecord) -> Result<()> {
+        let mut conn = self.connector.get_connection().await?;
+        let _: () = conn
+            .hset(
+                self.key(&format!("backfill/{}", record.flow_id)),
+                &record.backfill_id,
+                serde_json::to_string(record)?,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn list_backfills(&self, flow_id: &str) -> Result<Vec<BackfillRecord>> {
+        let mut conn = self.connector.get_connection().await?;
+        let records: Vec<String> = conn
+            .hvals(self.key(&format!("backfill/{}", flow_id)))
+            .await?;
+
+        let mut backfills = Vec::new();
+        for record in records {
+            backfills.push(serde_json::from_str::<BackfillRecord>(&record)?);
+        }
+        backfills.sort_by_key(|b| b.requested_at);
+        Ok(backfills)
+    }
+}
+
+impl RedisBackfillStore {
+    pub async fn new(conf: &RedisConf) -> Result<Self> {
+        Ok(Self {
+            connector: RedisConnector::new(conf)?,
+            key_prefix: conf.key_prefix.clone(),
+        })
+    }
+
+    fn key(&self, key: &str) -> String {
+        prefixed(&self.key_prefix, key)
+    }
+}
@@ -0,0 +1,126 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::RedisConf,
+    fluid::descriptor::{bundle::BundleDescriptor, DescriptorKind},
+    redis_connection::RedisConnector,
+    redis_namespace::prefixed,
+};
+
+// Which descriptors were submitted together as a bundle, what the reconcilers order them by and
+// what the bundle's status is aggregated from
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BundleRecord {
+    pub bundle_id: String,
+    pub database: String,
+    pub tables: Vec<String>,
+    pub flows: Vec<String>,
+    pub request_id: Option<String>,
+    pub submitted_at: DateTime<Utc>,
+}
+
+impl BundleRecord {
+    pub fn new(bundle: &BundleDescriptor, request_id: Option<String>) -> Self {
+        BundleRecord {
+            bundle_id: bundle.id.clone(),
+            database: bundle.database.id.clone(),
+            tables: bundle.tables.iter().map(|t| t.id.clone()).collect(),
+            flows: bundle.flows.iter().map(|f| f.id.clone()).collect(),
+            request_id,
+            submitted_at: Utc::now(),
+        }
+    }
+
+    // Members of the stages before the descriptor's, which have to be deployed before it is
+    pub fn predecessors(&self, kind: DescriptorKind) -> Vec<&str> {
+        match kind {
+            DescriptorKind::Database | DescriptorKind::LandingZone => vec![],
+            DescriptorKind::Table => vec![self.database.as_str()],
+            DescriptorKind::Flow => std::iter::once(self.database.as_str())
+                .chain(self.tables.iter().map(String::as_str))
+                .collect(),
+        }
+    }
+
+    pub fn members(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.database.as_str())
+            .chain(self.tables.iter().map(String::as_str))
+            .chain(self.flows.iter().map(String::as_str))
+    }
+}
+
+#[async_trait::async_trait]
+pub(crate) trait BundleStore {
+    async fn get_bundle(&self, bundle_id: &str) -> Result<Option<BundleRecord>>;
+    // The bundle the descriptor was last submitted in, if it was
+    async fn get_bundle_of(&self, descriptor_id: &str) -> Result<Option<BundleRecord>>;
+    async fn put_bundle(&self, record: &BundleRecord) -> Result<()>;
+}
+
+#[derive(Debug)]
+pub struct RedisBundleStore {
+    connector: RedisConnector,
+    key_prefix: String,
+}
+
+#[async_trait::async_trait]
+impl BundleStore for RedisBundleStore {
+    async fn get_bundle(&self, bundle_id: &str) -> Result<Option<BundleRecord>> {
+        let mut conn = self.connector.get_connection().await?;
+        let record: Option<String> = conn.get(self.key(&format!("bundle/{}", bundle_id))).await?;
+        Ok(match record {
+            Some(r) => Some(serde_json::from_str(&r)?),
+            None => None,
+        })
+    }
+
+    async fn get_bundle_of(&self, descriptor_id: &str) -> Result<Option<BundleRecord>> {
+        let mut conn = self.connector.get_connection().await?;
+        let bundle_id: Option<String> = conn
+            .get(self.key(&format!("bundle-member/{}", descriptor_id)))
+            .await?;
+        let Some(bundle_id) = bundle_id else {
+            return Ok(None);
+        };
+        // NOTE: a descriptor moved to another bundle is only a member of that one
+        Ok(self
+            .get_bundle(&bundle_id)
+            .await?
+            .filter(|b| b.members().any(|id| id == descriptor_id)))
+    }
+
+    async fn put_bundle(&self, record: &BundleRecord) -> Result<()> {
+        let mut conn = self.connector.get_connection().await?;
+
+        let mut pipe = redis::pipe();
+        pipe.atomic().set(
+            self.key(&format!("bundle/{}", record.bundle_id)),
+            serde_json::to_string(record)?,
+        );
+        for member in record.members() {
+            pipe.set(
+                self.key(&format!("bundle-member/{}", member)),
+                &record.bundle_id,
+            );
+        }
+        pipe.query_async::<_, ()>(&mut conn).await?;
+
+        Ok(())
+    }
+}
+
+impl RedisBundleStore {
+    pub async fn new(conf: &RedisConf) -> Result<Self> {
+        Ok(Self {
+            connector: RedisConnector::new(conf)?,
+            key_prefix: conf.key_prefix.clone(),
+        })
+    }
+
+    fn key(&self, key: &str) -> String {
+        prefixed(&self.key_prefix, key)
+    }
+}
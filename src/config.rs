@@ -1,53 +1,2059 @@
-use crate::constants::APP_NAME;
+use std::collections::HashMap;
 
-use anyhow::Result;
+use crate::{
+    aws_client,
+    constants::APP_NAME,
+    fluid::descriptor::{database::DatabaseDescriptor, flow::FlowBackend, Catalog, DescriptorKind},
+};
+
+use anyhow::{bail, Context, Result};
 use aws_config::SdkConfig;
-use config::Config;
-use serde::Deserialize;
+use config::{Config, ValueKind};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 
 pub struct BasinConfig {
     pub name: String,
-    // FIXME: just use the WaterwheelConf struct
-    pub waterwheel_username: String,
-    pub waterwheel_password: String,
-    pub waterwheel_project: String,
-    pub waterwheel_url: String,
+    pub waterwheel: WaterwheelConf,
     pub event_sqs_url: String,
-    pub redis_url: String,
+    pub event_source: EventSource,
+    pub kubernetes: KubernetesConf,
+    pub redis: RedisConf,
+    pub descriptor_encryption: Option<DescriptorEncryptionConf>,
     pub aws_creds: SdkConfig,
+    pub http: HttpConf,
+    pub reconciler: ReconcilerConf,
+    pub controllers: ControllersConf,
+    pub sharding: Option<ShardingConf>,
+    pub log_format: LogFormat,
+    pub id_generator: IdGenerator,
+    pub event_watcher: EventWatcherConf,
+    pub git_sync: Option<GitSyncConf>,
+    pub s3_source: Option<S3SourceConf>,
+    pub retention: Option<RetentionConf>,
+    pub backup: Option<BackupConf>,
+    pub freshness: Option<FreshnessConf>,
+    pub stuck_deployments: Option<StuckDeploymentsConf>,
+    pub data_triggers: Option<DataTriggersConf>,
+    pub smoke_tests: Option<SmokeTestConf>,
+    pub preview: Option<PreviewConf>,
+    pub deletion: DeletionConf,
+    pub state_events: Option<StateEventsConf>,
+    pub server: ServerConf,
+    pub limits: LimitsConf,
+    pub policy: Option<PolicyConf>,
+    pub validation_plugins: Vec<ValidationPluginConf>,
+    pub rate_limits: Option<RateLimitsConf>,
+    pub api_tokens: Option<ApiTokensConf>,
+    pub read_only: bool,
+    pub store_migration: Option<StoreMigrationConf>,
+    pub storage: StorageConf,
+    pub cost: CostConf,
+    pub sql: SqlConf,
+    pub flow_images: FlowImagesConf,
+    pub dbt: DbtConf,
+    pub spark: SparkConf,
+    pub flows: FlowsConf,
+    pub snowflake: Option<SnowflakeConf>,
+    pub bigquery: Option<BigQueryConf>,
+    pub unity_catalog: Option<UnityCatalogConf>,
+    pub hive_metastore: Option<HiveMetastoreConf>,
+    pub trino: Option<TrinoConf>,
+    pub redshift: Option<RedshiftConf>,
+    pub datahub: Option<DataHubConf>,
+    pub iam: Option<IamConf>,
+    pub table_maintenance: Option<TableMaintenanceConf>,
+    pub quality: Option<QualityConf>,
+    pub masking: Option<MaskingConf>,
+    pub landing_zones: Option<LandingZonesConf>,
+    pub access_requests: Option<AccessRequestsConf>,
+    pub approvals: Option<ApprovalsConf>,
+    pub quotas: Option<QuotasConf>,
+    pub fault_injection: Option<FaultInjectionConf>,
+    // The settings above as json with secrets redacted, for reporting what's in use
+    pub effective_settings: serde_json::Value,
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 struct ConfFileSettings {
     name: String,
     waterwheel: WaterwheelConf,
+    // Only needed when events are read from sqs
+    #[serde(default)]
     event_sqs_url: String,
-    redis_url: String,
+    #[serde(default)]
+    event_source: EventSource,
+    #[serde(default)]
+    kubernetes: KubernetesConf,
+    redis_url: Option<String>,
+    redis_sentinel: Option<RedisSentinelConf>,
+    #[serde(default)]
+    redis_cluster_nodes: Vec<String>,
+    // Namespaces every key basin writes so environments can share a redis
+    #[serde(default)]
+    redis_key_prefix: String,
+    descriptor_encryption: Option<DescriptorEncryptionConf>,
+    // Sends every aws call to this endpoint instead, e.g. a localstack container
+    aws_endpoint_url: Option<String>,
+    #[serde(default)]
+    aws_client: AwsClientConf,
+    #[serde(default)]
+    http: HttpConf,
+    #[serde(default)]
+    reconciler: ReconcilerConf,
+    #[serde(default)]
+    controllers: ControllersSettings,
+    sharding: Option<ShardingConf>,
+    #[serde(default)]
+    log_format: LogFormat,
+    #[serde(default)]
+    id_generator: IdGenerator,
+    #[serde(default)]
+    event_watcher: EventWatcherConf,
+    git_sync: Option<GitSyncConf>,
+    s3_source: Option<S3SourceConf>,
+    retention: Option<RetentionConf>,
+    backup: Option<BackupConf>,
+    freshness: Option<FreshnessConf>,
+    stuck_deployments: Option<StuckDeploymentsConf>,
+    data_triggers: Option<DataTriggersConf>,
+    smoke_tests: Option<SmokeTestConf>,
+    preview: Option<PreviewConf>,
+    #[serde(default)]
+    deletion: DeletionConf,
+    state_events: Option<StateEventsConf>,
+    #[serde(default)]
+    server: ServerConf,
+    #[serde(default)]
+    limits: LimitsConf,
+    policy: Option<PolicyConf>,
+    #[serde(default)]
+    validation_plugins: Vec<ValidationPluginConf>,
+    rate_limits: Option<RateLimitsConf>,
+    api_tokens: Option<ApiTokensConf>,
+    // Serves reads only and runs nothing that writes to the store, for cutting over to a migrated
+    // store without anything changing underneath the migration
+    #[serde(default)]
+    read_only: bool,
+    store_migration: Option<StoreMigrationSettings>,
+    #[serde(default)]
+    storage: StorageConf,
+    #[serde(default)]
+    cost: CostConf,
+    #[serde(default)]
+    sql: SqlConf,
+    #[serde(default)]
+    flow_images: FlowImagesConf,
+    #[serde(default)]
+    dbt: DbtConf,
+    #[serde(default)]
+    spark: SparkConf,
+    #[serde(default)]
+    flows: FlowsConf,
+    snowflake: Option<SnowflakeConf>,
+    bigquery: Option<BigQueryConf>,
+    unity_catalog: Option<UnityCatalogConf>,
+    hive_metastore: Option<HiveMetastoreConf>,
+    trino: Option<TrinoConf>,
+    redshift: Option<RedshiftConf>,
+    datahub: Option<DataHubConf>,
+    iam: Option<IamConf>,
+    table_maintenance: Option<TableMaintenanceConf>,
+    quality: Option<QualityConf>,
+    masking: Option<MaskingConf>,
+    landing_zones: Option<LandingZonesConf>,
+    access_requests: Option<AccessRequestsConf>,
+    approvals: Option<ApprovalsConf>,
+    quotas: Option<QuotasConf>,
+    fault_injection: Option<FaultInjectionConf>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct WaterwheelConf {
+    pub username: String,
+    pub password: String,
+    pub project: String,
+    pub url: String,
+    // Per request, a retried call can take up to max_attempts times as long
+    #[serde(default = "default_waterwheel_request_timeout_secs")]
+    pub request_timeout_secs: u64,
+    // Attempts per call while waterwheel is unavailable, the first one included
+    #[serde(default = "default_waterwheel_max_attempts")]
+    pub max_attempts: u32,
+    // Doubled after every failed attempt
+    #[serde(default = "default_waterwheel_retry_delay_ms")]
+    pub retry_delay_ms: u64,
+}
+
+fn default_waterwheel_request_timeout_secs() -> u64 {
+    30
+}
+
+fn default_waterwheel_max_attempts() -> u32 {
+    3
+}
+
+fn default_waterwheel_retry_delay_ms() -> u64 {
+    500
+}
+
+#[derive(Clone, Debug)]
+pub struct RedisConf {
+    pub topology: RedisTopology,
+    pub key_prefix: String,
+}
+
+#[derive(Clone, Debug)]
+pub enum RedisTopology {
+    Single {
+        url: String,
+    },
+    Sentinel {
+        service_name: String,
+        nodes: Vec<String>,
+        tls: bool,
+        password: Option<String>,
+        db: i64,
+    },
+    Cluster {
+        nodes: Vec<String>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct RedisSentinelConf {
+    service_name: String,
+    // Sentinel node urls, use rediss:// for tls to the sentinels themselves
+    nodes: Vec<String>,
+    // Whether the resolved master should be connected to over tls
+    #[serde(default)]
+    tls: bool,
+    password: Option<String>,
+    #[serde(default)]
+    db: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+// How ids are made for descriptors submitted without one
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum IdGenerator {
+    // Time ordered, ids sort by when their descriptor was first submitted
+    #[default]
+    UuidV7,
+    UuidV4,
+}
+
+impl IdGenerator {
+    pub fn generate(&self) -> String {
+        match self {
+            IdGenerator::UuidV7 => uuid::Uuid::now_v7().to_string(),
+            IdGenerator::UuidV4 => uuid::Uuid::new_v4().to_string(),
+        }
+    }
+}
+
+// Each controller's settings, resolved against `[reconciler]` when basin starts
+#[derive(Serialize, Clone, Debug)]
+pub struct ControllersConf {
+    pub database: ControllerConf,
+    pub table: ControllerConf,
+    pub flow: ControllerConf,
+    pub landing_zone: ControllerConf,
+}
+
+impl ControllersConf {
+    // Disabled controllers' descriptors are still stored, they just sit in Pending
+    pub fn is_enabled(&self, kind: DescriptorKind) -> bool {
+        self.get(kind).enabled
+    }
+
+    pub fn get(&self, kind: DescriptorKind) -> &ControllerConf {
+        match kind {
+            DescriptorKind::Database => &self.database,
+            DescriptorKind::Table => &self.table,
+            DescriptorKind::Flow => &self.flow,
+            DescriptorKind::LandingZone => &self.landing_zone,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ControllerConf {
+    // When off nothing of this kind is reconciled, for rolling basin out a kind at a time
+    pub enabled: bool,
+    // Maximum number of descriptors reconciled concurrently by the controller
+    pub parallelism: usize,
+    // Upper bound on a single descriptor's reconcile
+    pub reconcile_timeout_secs: u64,
+    // How often the controller goes over its descriptors
+    pub interval_ms: u64,
+    // Failed descriptors are retried less often the longer they've been failing, up to this
+    pub max_retry_backoff_secs: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+struct ControllersSettings {
+    #[serde(default)]
+    database: ControllerSettings,
+    #[serde(default)]
+    table: ControllerSettings,
+    #[serde(default)]
+    flow: ControllerSettings,
+    #[serde(default)]
+    landing_zone: ControllerSettings,
+}
+
+// `[controllers.<kind>]`, anything left out is taken from `[reconciler]`
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+struct ControllerSettings {
+    enabled: Option<bool>,
+    parallelism: Option<usize>,
+    reconcile_timeout_secs: Option<u64>,
+    interval_ms: Option<u64>,
+    max_retry_backoff_secs: Option<u64>,
+}
+
+impl ControllerSettings {
+    fn resolve(&self, defaults: &ReconcilerConf) -> ControllerConf {
+        ControllerConf {
+            enabled: self.enabled.unwrap_or(true),
+            parallelism: self.parallelism.unwrap_or(defaults.parallelism),
+            reconcile_timeout_secs: self
+                .reconcile_timeout_secs
+                .unwrap_or(defaults.reconcile_timeout_secs),
+            interval_ms: self.interval_ms.unwrap_or(defaults.interval_ms),
+            max_retry_backoff_secs: self
+                .max_retry_backoff_secs
+                .unwrap_or(defaults.max_retry_backoff_secs),
+        }
+    }
+}
+
+// Settings every controller's reconciler runs with unless its `[controllers.<kind>]` section says
+// otherwise
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReconcilerConf {
+    #[serde(default = "default_parallelism")]
+    pub parallelism: usize,
+    #[serde(default = "default_reconcile_timeout_secs")]
+    pub reconcile_timeout_secs: u64,
+    #[serde(default = "default_reconcile_interval_ms")]
+    pub interval_ms: u64,
+    #[serde(default = "default_max_retry_backoff_secs")]
+    pub max_retry_backoff_secs: u64,
+    // Reconcile locks outlive the deadline by this much, covering the state write after it
+    #[serde(default = "default_lock_margin_secs")]
+    pub lock_margin_secs: u64,
+}
+
+impl Default for ReconcilerConf {
+    fn default() -> Self {
+        ReconcilerConf {
+            parallelism: default_parallelism(),
+            reconcile_timeout_secs: default_reconcile_timeout_secs(),
+            interval_ms: default_reconcile_interval_ms(),
+            max_retry_backoff_secs: default_max_retry_backoff_secs(),
+            lock_margin_secs: default_lock_margin_secs(),
+        }
+    }
+}
+
+fn default_parallelism() -> usize {
+    4
+}
+
+fn default_reconcile_timeout_secs() -> u64 {
+    120
+}
+
+fn default_reconcile_interval_ms() -> u64 {
+    5000
+}
+
+fn default_max_retry_backoff_secs() -> u64 {
+    10 * 60
+}
+
+fn default_lock_margin_secs() -> u64 {
+    30
+}
+
+// Rejects settings no reconciler can run with, reported against where they were set
+fn validate_controller(kind: DescriptorKind, conf: &ControllerConf) -> Result<()> {
+    if conf.parallelism == 0 || conf.reconcile_timeout_secs == 0 {
+        bail!(
+            "controllers.{} parallelism and reconcile_timeout_secs must be at least 1",
+            kind
+        );
+    }
+    if conf.interval_ms < 100 {
+        bail!("controllers.{} interval_ms must be at least 100", kind);
+    }
+    if conf.max_retry_backoff_secs.saturating_mul(1000) < conf.interval_ms {
+        bail!(
+            "controllers.{} max_retry_backoff_secs can't be shorter than its interval_ms",
+            kind
+        );
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EventWatcherConf {
+    // How long processed event ids and descriptor revisions are remembered for deduplication
+    #[serde(default = "default_dedup_ttl_secs")]
+    pub dedup_ttl_secs: u64,
+    // For fetching each upstream descriptor, which is capped at `limits.max_body_bytes` like a
+    // submitted one
+    #[serde(default = "default_fetch_timeout_secs")]
+    pub fetch_timeout_secs: u64,
+    // Events and custom resources of these kinds are ignored rather than stored
+    #[serde(default)]
+    pub disabled_kinds: Vec<DescriptorKind>,
+    // Validates events against a registered json schema, events which don't match are dropped
+    #[serde(default)]
+    pub schema_registry: Option<SchemaRegistryConf>,
+}
+
+// Syncs descriptors from a git repository, see git_sync
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GitSyncConf {
+    pub repo_url: String,
+    #[serde(default = "default_git_sync_branch")]
+    pub branch: String,
+    // Directory in the repository the per kind directories are under, the root when empty
+    #[serde(default)]
+    pub path: String,
+    #[serde(default = "default_git_sync_checkout_dir")]
+    pub checkout_dir: String,
+    #[serde(default = "default_git_sync_interval_secs")]
+    pub interval_secs: u64,
+}
+
+fn default_git_sync_branch() -> String {
+    "main".to_string()
+}
+
+fn default_git_sync_checkout_dir() -> String {
+    "/tmp/basin-git-sync".to_string()
+}
+
+fn default_git_sync_interval_secs() -> u64 {
+    300
+}
+
+// Polls a bucket for descriptors, see s3_source
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct S3SourceConf {
+    pub bucket: String,
+    // Prefix the per kind prefixes are under, the bucket's root when empty
+    #[serde(default)]
+    pub prefix: String,
+    #[serde(default = "default_s3_source_interval_secs")]
+    pub interval_secs: u64,
+}
+
+fn default_s3_source_interval_secs() -> u64 {
+    60
+}
+
+// Exactly one of confluent or glue has to be set
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SchemaRegistryConf {
+    #[serde(default)]
+    pub confluent: Option<ConfluentRegistryConf>,
+    #[serde(default)]
+    pub glue: Option<GlueRegistryConf>,
+    // How long the latest schema version is used before checking for a newer one
+    #[serde(default = "default_schema_refresh_secs")]
+    pub refresh_secs: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ConfluentRegistryConf {
+    pub url: String,
+    // Subject the event schema is registered under, its latest version is used
+    pub subject: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GlueRegistryConf {
+    pub registry_name: String,
+    pub schema_name: String,
+}
+
+fn default_schema_refresh_secs() -> u64 {
+    300
+}
+
+impl EventWatcherConf {
+    pub fn ingests(&self, kind: DescriptorKind) -> bool {
+        !self.disabled_kinds.contains(&kind)
+    }
+}
+
+impl Default for EventWatcherConf {
+    fn default() -> Self {
+        EventWatcherConf {
+            dedup_ttl_secs: default_dedup_ttl_secs(),
+            fetch_timeout_secs: default_fetch_timeout_secs(),
+            disabled_kinds: vec![],
+            schema_registry: None,
+        }
+    }
+}
+
+fn default_dedup_ttl_secs() -> u64 {
+    24 * 60 * 60
+}
+
+fn default_fetch_timeout_secs() -> u64 {
+    30
+}
+
+// Where descriptors are ingested from, the http api is always available
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EventSource {
+    // Descriptor events on `event_sqs_url`
+    #[default]
+    Sqs,
+    // Database, Table, Flow and LandingZone custom resources in the cluster basin runs in
+    Kubernetes,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct KubernetesConf {
+    // Namespace custom resources are read from, every namespace when unset
+    pub namespace: Option<String>,
+    #[serde(default = "default_crd_group")]
+    pub group: String,
+    #[serde(default = "default_crd_version")]
+    pub version: String,
+    // Seconds between listing custom resources and writing their status back
+    #[serde(default = "default_crd_poll_interval_secs")]
+    pub poll_interval_secs: u64,
+}
+
+impl Default for KubernetesConf {
+    fn default() -> Self {
+        KubernetesConf {
+            namespace: None,
+            group: default_crd_group(),
+            version: default_crd_version(),
+            poll_interval_secs: default_crd_poll_interval_secs(),
+        }
+    }
+}
+
+fn default_crd_group() -> String {
+    "basin.uint0.io".to_string()
+}
+
+fn default_crd_version() -> String {
+    "v1alpha1".to_string()
+}
+
+fn default_crd_poll_interval_secs() -> u64 {
+    10
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RetentionConf {
+    // States of deleted descriptors are archived out of redis this long after the deletion
+    #[serde(default = "default_terminal_state_days")]
+    pub terminal_state_days: u64,
+    pub archive_bucket: String,
+    #[serde(default = "default_archive_prefix")]
+    pub archive_prefix: String,
+    #[serde(default = "default_archive_interval_secs")]
+    pub interval_secs: u64,
+}
+
+fn default_terminal_state_days() -> u64 {
+    30
+}
+
+fn default_archive_prefix() -> String {
+    "deployment-states".to_string()
+}
+
+fn default_archive_interval_secs() -> u64 {
+    60 * 60
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BackupConf {
+    pub bucket: String,
+    // Snapshots are written to `{prefix}/{taken_at_millis}.json`
+    #[serde(default = "default_backup_prefix")]
+    pub prefix: String,
+    #[serde(default = "default_backup_interval_secs")]
+    pub interval_secs: u64,
+    // Older snapshots are pruned, the latest one is always kept
+    #[serde(default = "default_backup_retention_days")]
+    pub retention_days: u64,
 }
 
-#[derive(Deserialize, Clone)]
-struct WaterwheelConf {
-    username: String,
-    password: String,
-    project: String,
-    url: String,
+fn default_backup_prefix() -> String {
+    "basin-snapshots".to_string()
+}
+
+fn default_backup_interval_secs() -> u64 {
+    60 * 60
+}
+
+fn default_backup_retention_days() -> u64 {
+    14
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FreshnessConf {
+    // How often tables with a freshness sla are checked
+    #[serde(default = "default_freshness_interval_secs")]
+    pub interval_secs: u64,
+}
+
+fn default_freshness_interval_secs() -> u64 {
+    5 * 60
+}
+
+// Looks for descriptors nothing has touched while they're Pending or Deploying, e.g. a replica
+// died mid reconcile, and resets them to Pending
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StuckDeploymentsConf {
+    // How long a descriptor can sit without its state being written before it's stuck
+    #[serde(default = "default_stuck_threshold_secs")]
+    pub threshold_secs: u64,
+    #[serde(default = "default_stuck_interval_secs")]
+    pub interval_secs: u64,
+}
+
+fn default_stuck_threshold_secs() -> u64 {
+    60 * 60
+}
+
+fn default_stuck_interval_secs() -> u64 {
+    5 * 60
+}
+
+// Splits descriptors between basin replicas on a hash ring, each replica only reconciling the
+// descriptors it owns. Membership is kept in redis.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ShardingConf {
+    // Has to be unique and should survive restarts, defaults to $HOSTNAME (the pod name)
+    pub replica_id: Option<String>,
+    #[serde(default = "default_shard_heartbeat_interval_secs")]
+    pub heartbeat_interval_secs: u64,
+    // Replicas which haven't heartbeated for this long are dropped and their share rebalanced
+    #[serde(default = "default_shard_member_ttl_secs")]
+    pub member_ttl_secs: u64,
+    // Points each replica gets on the ring, more spreads descriptors more evenly
+    #[serde(default = "default_shard_virtual_nodes")]
+    pub virtual_nodes: usize,
+}
+
+fn default_shard_heartbeat_interval_secs() -> u64 {
+    10
+}
+
+fn default_shard_member_ttl_secs() -> u64 {
+    30
+}
+
+fn default_shard_virtual_nodes() -> usize {
+    64
+}
+
+// Governance checks descriptors have to pass before they're stored, from any source
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PolicyConf {
+    #[serde(default)]
+    pub opa: Option<OpaPolicyConf>,
+    #[serde(default)]
+    pub webhook: Option<PolicyWebhookConf>,
+    #[serde(default = "default_policy_timeout_secs")]
+    pub timeout_secs: u64,
+    // Store descriptors when a check can't be made, rather than refusing them until it can
+    #[serde(default)]
+    pub fail_open: bool,
+}
+
+// Queried with `{"input": {"kind", "descriptor"}}`, denying with a `deny` set of reasons or
+// `allow = false`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct OpaPolicyConf {
+    pub url: String,
+    // Package path of the policy, e.g. basin/descriptors
+    pub path: String,
+}
+
+// Posted `{"kind", "descriptor"}`, answering `{"allowed": bool, "reasons": [..]}`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PolicyWebhookConf {
+    pub url: String,
+    pub bearer_token: Option<String>,
+}
+
+fn default_policy_timeout_secs() -> u64 {
+    5
+}
+
+// A wasm module run over descriptors in the validate stage, see controller::validation_plugins
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ValidationPluginConf {
+    pub name: String,
+    pub path: String,
+    // Kinds the plugin validates, every kind when empty
+    #[serde(default)]
+    pub kinds: Vec<DescriptorKind>,
+    #[serde(default = "default_plugin_max_memory_mb")]
+    pub max_memory_mb: usize,
+    // Roughly the wasm instructions a single run may execute
+    #[serde(default = "default_plugin_max_fuel")]
+    pub max_fuel: u64,
+    #[serde(default = "default_plugin_timeout_ms")]
+    pub timeout_ms: u64,
+    // Largest verdict read back out of the plugin
+    #[serde(default = "default_plugin_max_verdict_kb")]
+    pub max_verdict_kb: usize,
+}
+
+fn default_plugin_max_memory_mb() -> usize {
+    16
+}
+
+fn default_plugin_max_fuel() -> u64 {
+    100_000_000
+}
+
+fn default_plugin_timeout_ms() -> u64 {
+    200
+}
+
+fn default_plugin_max_verdict_kb() -> usize {
+    64
+}
+
+// Envelope encrypts descriptors at rest, each under a kms generated data key
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DescriptorEncryptionConf {
+    // Key id, arn or alias of the kms key data keys are generated under
+    pub kms_key_id: String,
+    // A data key is reused for this long before a new one is generated
+    #[serde(default = "default_data_key_ttl_secs")]
+    pub data_key_ttl_secs: u64,
+}
+
+fn default_data_key_ttl_secs() -> u64 {
+    3600
+}
+
+// Starts flows with upstream table conditions when objects land under those tables' locations
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DataTriggersConf {
+    // Queue the buckets' s3:ObjectCreated:* notifications are sent to, directly or through sns
+    pub sqs_url: String,
+    // A flow isn't started again for arrivals within this long of its last data triggered run
+    #[serde(default = "default_data_trigger_min_interval_secs")]
+    pub min_interval_secs: u64,
+}
+
+fn default_data_trigger_min_interval_secs() -> u64 {
+    5 * 60
+}
+
+// Queries glue tables through athena once they've been created or changed
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SmokeTestConf {
+    #[serde(default = "default_athena_workgroup")]
+    pub workgroup: String,
+    // Where athena writes query results, e.g. `s3://bucket/athena/`. Can be left out when the
+    // workgroup has its own output location.
+    #[serde(default)]
+    pub output_location: Option<String>,
+    #[serde(default = "default_smoke_test_timeout_secs")]
+    pub timeout_secs: u64,
+    // Fail the reconcile when the query fails, otherwise the failure is only recorded
+    #[serde(default)]
+    pub fail_reconcile: bool,
+}
+
+fn default_athena_workgroup() -> String {
+    "primary".to_string()
+}
+
+fn default_smoke_test_timeout_secs() -> u64 {
+    60
+}
+
+// Lets the api preview a glue table's rows through athena
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PreviewConf {
+    #[serde(default = "default_athena_workgroup")]
+    pub workgroup: String,
+    // As for smoke tests, can be left out when the workgroup has its own output location
+    #[serde(default)]
+    pub output_location: Option<String>,
+    #[serde(default = "default_preview_timeout_secs")]
+    pub timeout_secs: u64,
+    // Most rows a preview can ask for
+    #[serde(default = "default_preview_max_rows")]
+    pub max_rows: usize,
+    // Previews are cancelled once they've scanned this much, athena bills by bytes scanned
+    #[serde(default = "default_preview_max_bytes_scanned")]
+    pub max_bytes_scanned: u64,
+}
+
+fn default_preview_timeout_secs() -> u64 {
+    30
+}
+
+fn default_preview_max_rows() -> usize {
+    100
+}
+
+fn default_preview_max_bytes_scanned() -> u64 {
+    1024 * 1024 * 1024
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DeletionConf {
+    // How long a deleted descriptor can be restored for before its resources are torn down
+    #[serde(default = "default_deletion_grace_period_secs")]
+    pub grace_period_secs: u64,
+}
+
+impl Default for DeletionConf {
+    fn default() -> Self {
+        DeletionConf {
+            grace_period_secs: default_deletion_grace_period_secs(),
+        }
+    }
+}
+
+fn default_deletion_grace_period_secs() -> u64 {
+    24 * 60 * 60
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StateEventsConf {
+    // Events go to the sns topic when one is set, otherwise to the eventbridge bus
+    pub sns_topic_arn: Option<String>,
+    #[serde(default = "default_event_bus_name")]
+    pub event_bus_name: String,
+    // Eventbridge `source` of the events, for rules to match on
+    #[serde(default = "default_state_event_source")]
+    pub source: String,
+}
+
+fn default_event_bus_name() -> String {
+    "default".to_string()
+}
+
+fn default_state_event_source() -> String {
+    "basin".to_string()
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ServerConf {
+    #[serde(default = "default_bind_address")]
+    pub bind_address: String,
+    pub tls: Option<TlsConf>,
+}
+
+impl Default for ServerConf {
+    fn default() -> Self {
+        ServerConf {
+            bind_address: default_bind_address(),
+            tls: None,
+        }
+    }
+}
+
+fn default_bind_address() -> String {
+    "0.0.0.0:3000".to_string()
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TlsConf {
+    // PEM encoded certificate chain and private key served by the API
+    pub cert_path: String,
+    pub key_path: String,
+    // When set, clients must present a certificate signed by this CA (mTLS)
+    pub client_ca_path: Option<String>,
+}
+
+// Descriptor submits are held to a token bucket per client, told apart by their api token's name
+// when tokens are on and by an api key header otherwise. Requests without either share a bucket.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RateLimitsConf {
+    #[serde(default = "default_rate_limit_client_header")]
+    pub client_header: String,
+    #[serde(default = "default_rate_limit_requests_per_minute")]
+    pub requests_per_minute: u32,
+    // Submits a client can make back to back before being held to requests_per_minute
+    #[serde(default = "default_rate_limit_burst")]
+    pub burst: u32,
+    // requests_per_minute for particular api keys
+    #[serde(default)]
+    pub clients: HashMap<String, u32>,
+}
+
+impl RateLimitsConf {
+    pub fn requests_per_minute_for(&self, client: &str) -> u32 {
+        self.clients
+            .get(client)
+            .copied()
+            .unwrap_or(self.requests_per_minute)
+    }
+}
+
+// Api callers present a bearer token minted through the admin api, whose scopes decide what they
+// may read and write. The admin api itself takes the admin token.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ApiTokensConf {
+    // Hex sha256 of the admin token, so the token itself isn't kept in the config
+    pub admin_token_sha256: String,
+}
+
+fn default_rate_limit_client_header() -> String {
+    "x-api-key".to_string()
+}
+
+fn default_rate_limit_requests_per_minute() -> u32 {
+    60
+}
+
+fn default_rate_limit_burst() -> u32 {
+    20
+}
+
+// Where `basin migrate-store` copies descriptors and deployment states to, set up like basin's own
+// redis settings
+#[derive(Serialize, Deserialize, Clone)]
+struct StoreMigrationSettings {
+    redis_url: Option<String>,
+    redis_sentinel: Option<RedisSentinelConf>,
+    #[serde(default)]
+    redis_cluster_nodes: Vec<String>,
+    #[serde(default)]
+    redis_key_prefix: String,
+    #[serde(default = "default_store_migration_batch_size")]
+    batch_size: usize,
+}
+
+fn default_store_migration_batch_size() -> usize {
+    500
+}
+
+#[derive(Clone, Debug)]
+pub struct StoreMigrationConf {
+    pub target: RedisConf,
+    // Descriptors or states read from the source per page
+    pub batch_size: usize,
+}
+
+// The http client shared by the event watcher, waterwheel and policy checks
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HttpConf {
+    #[serde(default = "default_http_connect_timeout_secs")]
+    pub connect_timeout_secs: u64,
+    // For requests which don't set a timeout of their own
+    #[serde(default = "default_http_request_timeout_secs")]
+    pub request_timeout_secs: u64,
+    // Every request is sent through this proxy, `NO_PROXY` isn't consulted once it's set
+    pub proxy_url: Option<String>,
+    // Pem certificates trusted on top of the built in roots, for upstreams behind a private ca
+    pub ca_bundle_path: Option<String>,
+    // Idle connections are kept open this long for reuse
+    #[serde(default = "default_http_pool_idle_timeout_secs")]
+    pub pool_idle_timeout_secs: u64,
+    #[serde(default = "default_http_pool_max_idle_per_host")]
+    pub pool_max_idle_per_host: usize,
+}
+
+impl Default for HttpConf {
+    fn default() -> Self {
+        HttpConf {
+            connect_timeout_secs: default_http_connect_timeout_secs(),
+            request_timeout_secs: default_http_request_timeout_secs(),
+            proxy_url: None,
+            ca_bundle_path: None,
+            pool_idle_timeout_secs: default_http_pool_idle_timeout_secs(),
+            pool_max_idle_per_host: default_http_pool_max_idle_per_host(),
+        }
+    }
+}
+
+fn default_http_connect_timeout_secs() -> u64 {
+    5
+}
+
+fn default_http_request_timeout_secs() -> u64 {
+    60
+}
+
+fn default_http_pool_idle_timeout_secs() -> u64 {
+    90
+}
+
+fn default_http_pool_max_idle_per_host() -> usize {
+    16
+}
+
+// Shared by every aws client basin builds
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AwsClientConf {
+    // Attempts per call, the first one included
+    #[serde(default = "default_aws_max_attempts")]
+    pub max_attempts: u32,
+    #[serde(default = "default_aws_connect_timeout_secs")]
+    pub connect_timeout_secs: u64,
+    #[serde(default = "default_aws_attempt_timeout_secs")]
+    pub attempt_timeout_secs: u64,
+    // Every attempt and the backoff between them included
+    #[serde(default = "default_aws_operation_timeout_secs")]
+    pub operation_timeout_secs: u64,
+}
+
+impl Default for AwsClientConf {
+    fn default() -> Self {
+        AwsClientConf {
+            max_attempts: default_aws_max_attempts(),
+            connect_timeout_secs: default_aws_connect_timeout_secs(),
+            attempt_timeout_secs: default_aws_attempt_timeout_secs(),
+            operation_timeout_secs: default_aws_operation_timeout_secs(),
+        }
+    }
+}
+
+fn default_aws_max_attempts() -> u32 {
+    5
+}
+
+fn default_aws_connect_timeout_secs() -> u64 {
+    5
+}
+
+fn default_aws_attempt_timeout_secs() -> u64 {
+    30
+}
+
+fn default_aws_operation_timeout_secs() -> u64 {
+    120
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LimitsConf {
+    #[serde(default = "default_max_body_bytes")]
+    pub max_body_bytes: usize,
+    #[serde(default = "default_max_name_len")]
+    pub max_name_len: usize,
+    #[serde(default = "default_max_summary_len")]
+    pub max_summary_len: usize,
+    #[serde(default = "default_max_sql_len")]
+    pub max_sql_len: usize,
+    #[serde(default = "default_max_columns")]
+    pub max_columns: usize,
+    #[serde(default = "default_max_steps")]
+    pub max_steps: usize,
+}
+
+impl Default for LimitsConf {
+    fn default() -> Self {
+        LimitsConf {
+            max_body_bytes: default_max_body_bytes(),
+            max_name_len: default_max_name_len(),
+            max_summary_len: default_max_summary_len(),
+            max_sql_len: default_max_sql_len(),
+            max_columns: default_max_columns(),
+            max_steps: default_max_steps(),
+        }
+    }
+}
+
+fn default_max_body_bytes() -> usize {
+    1024 * 1024
+}
+
+fn default_max_name_len() -> usize {
+    255
+}
+
+fn default_max_summary_len() -> usize {
+    4096
+}
+
+fn default_max_sql_len() -> usize {
+    64 * 1024
+}
+
+fn default_max_columns() -> usize {
+    1000
+}
+
+fn default_max_steps() -> usize {
+    100
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CostConf {
+    // Descriptor labels copied onto provisioned resources as cost allocation tags
+    #[serde(default = "default_propagated_labels")]
+    pub propagated_labels: Vec<String>,
+}
+
+impl Default for CostConf {
+    fn default() -> Self {
+        CostConf {
+            propagated_labels: default_propagated_labels(),
+        }
+    }
+}
+
+fn default_propagated_labels() -> Vec<String> {
+    vec!["team".to_string(), "cost-center".to_string()]
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct StorageConf {
+    // Regexes a table's bucket must fully match for it to override its location, no overrides
+    // are accepted when empty
+    #[serde(default)]
+    pub allowed_location_buckets: Vec<String>,
+    // Copy existing objects across when a table's location changes, the old location is never
+    // deleted from
+    #[serde(default)]
+    pub copy_on_relocate: bool,
+    // Catalog glue engine databases are registered in unless they pick one
+    #[serde(default)]
+    pub catalog: Catalog,
+}
+
+// Compiles allow-list patterns anchored to match the whole value, failing on the first invalid one
+pub fn full_match_patterns(patterns: &[String]) -> Result<Vec<Regex>> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            Regex::new(&format!("^(?:{})$", pattern))
+                .with_context(|| format!("invalid pattern '{}'", pattern))
+        })
+        .collect()
+}
+
+impl StorageConf {
+    pub fn catalog_for(&self, descriptor: &DatabaseDescriptor) -> Catalog {
+        descriptor.catalog.unwrap_or(self.catalog)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SqlConf {
+    // Dialect flow SQL steps are parsed with during validation
+    #[serde(default)]
+    pub dialect: SqlDialect,
+    // Log the tables each SQL step reads from and writes to
+    #[serde(default)]
+    pub extract_lineage: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SqlDialect {
+    #[default]
+    Athena,
+    Trino,
+    Spark,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FlowImagesConf {
+    // Image steps run in when they don't name one, always allowed
+    #[serde(default = "default_flow_image")]
+    pub default_image: String,
+    // Regexes an image's repository (`registry/repo`, no tag or digest) must fully match for a
+    // step to use it, steps can only use the default image when empty
+    #[serde(default)]
+    pub allowed: Vec<String>,
+    // Reject step images that aren't pinned to a `@sha256:` digest
+    #[serde(default)]
+    pub require_digest: bool,
+}
+
+impl Default for FlowImagesConf {
+    fn default() -> Self {
+        FlowImagesConf {
+            default_image: default_flow_image(),
+            allowed: vec![],
+            require_digest: false,
+        }
+    }
+}
+
+fn default_flow_image() -> String {
+    "bash".to_string()
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct DbtConf {
+    // Image dbt steps run in unless they name one. Needs git and dbt-athena, and like the default
+    // flow image it's handed `-c <script>` so its entrypoint has to be a shell
+    #[serde(default)]
+    pub runner_image: Option<String>,
+    // Where athena writes query results, dbt steps are rejected until this is set
+    #[serde(default)]
+    pub s3_staging_dir: Option<String>,
+    #[serde(default)]
+    pub work_group: Option<String>,
+    #[serde(default)]
+    pub threads: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SparkConf {
+    #[serde(default)]
+    pub runner: SparkRunner,
+    // Image spark steps run in unless they name one, handed `-c <script>` like the default flow
+    // image. Needs spark-submit for the `submit` runner and the aws cli for `emr_serverless`.
+    #[serde(default)]
+    pub image: Option<String>,
+    // Master url for the `submit` runner
+    #[serde(default = "default_spark_master")]
+    pub master: String,
+    #[serde(default)]
+    pub emr_serverless: Option<EmrServerlessConf>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SparkRunner {
+    // spark-submit inside the step's container
+    #[default]
+    Submit,
+    // Start a job run on an emr serverless application and wait for it to finish
+    EmrServerless,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EmrServerlessConf {
+    pub application_id: String,
+    pub execution_role_arn: String,
+    // Seconds between job run status checks
+    #[serde(default = "default_emr_poll_interval")]
+    pub poll_interval: u64,
+}
+
+impl Default for SparkConf {
+    fn default() -> Self {
+        SparkConf {
+            runner: SparkRunner::default(),
+            image: None,
+            master: default_spark_master(),
+            emr_serverless: None,
+        }
+    }
+}
+
+fn default_spark_master() -> String {
+    "local[*]".to_string()
+}
+
+fn default_emr_poll_interval() -> u64 {
+    30
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct FlowsConf {
+    // Backend flows that don't pick one are deployed to
+    #[serde(default)]
+    pub default_backend: FlowBackend,
+    // Steps run as glue jobs on the glue and step_functions backends
+    #[serde(default)]
+    pub glue: Option<GlueFlowConf>,
+    #[serde(default)]
+    pub step_functions: Option<StepFunctionsFlowConf>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GlueFlowConf {
+    // Role the glue jobs run as
+    pub role_arn: String,
+    // Script sql steps run with, it's handed the statement in the `--sql` argument
+    pub sql_script_location: String,
+    #[serde(default = "default_glue_version")]
+    pub glue_version: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StepFunctionsFlowConf {
+    // Role state machines run as, needs to be able to start and watch the glue jobs
+    pub role_arn: String,
+    // Role eventbridge starts state machine executions as
+    pub events_role_arn: String,
+}
+
+fn default_glue_version() -> String {
+    "4.0".to_string()
+}
+
+// Needed by databases and tables with `engine = "snowflake"`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SnowflakeConf {
+    // Account identifier, e.g. `myorg-myaccount`
+    pub account: String,
+    pub token: String,
+    // `OAUTH` or `KEYPAIR_JWT`
+    #[serde(default = "default_snowflake_token_type")]
+    pub token_type: String,
+    #[serde(default)]
+    pub warehouse: Option<String>,
+    #[serde(default)]
+    pub role: Option<String>,
+    // Schema tables are created in, every basin database gets one
+    #[serde(default = "default_snowflake_schema")]
+    pub schema: String,
+    // Seconds a statement may run for before snowflake cancels it
+    #[serde(default = "default_snowflake_statement_timeout")]
+    pub statement_timeout: u64,
+}
+
+// Needed by databases and tables with `engine = "bigquery"`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BigQueryConf {
+    pub project_id: String,
+    // Service account key file, the account needs to be able to create datasets and tables
+    pub credentials_file: String,
+    // Where new datasets are created
+    #[serde(default = "default_bigquery_location")]
+    pub location: String,
+}
+
+// When set glue databases and tables are also registered in unity catalog
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UnityCatalogConf {
+    // e.g. `https://dbc-1234.cloud.databricks.com`
+    pub workspace_url: String,
+    pub token: String,
+    // Catalog basin creates schemas in, it has to exist already
+    pub catalog: String,
+    // Storage credential external locations are created with, needs read access to every bucket
+    // basin provisions
+    pub storage_credential: String,
+    // Format external tables are registered with
+    #[serde(default = "default_unity_data_source_format")]
+    pub data_source_format: String,
+}
+
+fn default_unity_data_source_format() -> String {
+    "PARQUET".to_string()
+}
+
+// Needed when databases are registered in a hive metastore instead of glue, no kerberos or sasl
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HiveMetastoreConf {
+    // e.g. `thrift://metastore:9083`
+    pub uri: String,
+    // Idle connections kept open to the metastore
+    #[serde(default = "default_hive_metastore_pool_size")]
+    pub pool_size: usize,
+    // Applies to connecting and to each read or write on a connection
+    #[serde(default = "default_hive_metastore_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_hive_metastore_pool_size() -> usize {
+    4
+}
+
+fn default_hive_metastore_timeout_secs() -> u64 {
+    30
+}
+
+// When set glue engine databases and tables are registered with a trino coordinator once they're
+// provisioned
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TrinoConf {
+    // e.g. `https://trino.internal:8443`
+    pub coordinator_url: String,
+    #[serde(default = "default_trino_user")]
+    pub user: String,
+    // Sent as basic auth, trino only accepts it over https
+    #[serde(default)]
+    pub password: Option<String>,
+    // Trino catalog over the glue catalog or hive metastore basin registers databases in
+    pub catalog: String,
+    #[serde(default = "default_trino_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_trino_user() -> String {
+    "basin".to_string()
+}
+
+fn default_trino_timeout_secs() -> u64 {
+    60
+}
+
+// When set glue engine databases in the glue catalog get a redshift external schema over them once
+// they're provisioned, so redshift spectrum can query their tables
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RedshiftConf {
+    // Exactly one of a provisioned cluster or a serverless workgroup
+    #[serde(default)]
+    pub cluster_identifier: Option<String>,
+    #[serde(default)]
+    pub workgroup_name: Option<String>,
+    // Redshift database the external schemas are created in
+    pub database: String,
+    // Clusters only, connects as this user through temporary credentials when there's no secret
+    #[serde(default)]
+    pub db_user: Option<String>,
+    // Secrets manager secret holding the credentials to connect with
+    #[serde(default)]
+    pub secret_arn: Option<String>,
+    // Role redshift assumes to read the glue catalog and the tables' data
+    pub iam_role_arn: String,
+    // Prepended to the glue database name to name its external schema
+    #[serde(default)]
+    pub schema_prefix: String,
+    #[serde(default = "default_redshift_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_redshift_timeout_secs() -> u64 {
+    60
+}
+
+// When set glue engine databases, tables and flows are pushed to datahub once they're
+// provisioned, as containers, datasets and data jobs
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DataHubConf {
+    // DataHub's metadata service, e.g. `https://datahub-gms.internal:8080`
+    pub gms_url: String,
+    // Personal access token, sent as a bearer token
+    #[serde(default)]
+    pub token: Option<String>,
+    // Environment datasets and flows are registered under, e.g. `PROD` or `DEV`
+    #[serde(default = "default_datahub_env")]
+    pub env: String,
+    #[serde(default = "default_datahub_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_datahub_env() -> String {
+    "PROD".to_string()
+}
+
+fn default_datahub_timeout_secs() -> u64 {
+    30
+}
+
+// When set glue engine databases get a reader and a writer iam role over their bucket and catalog
+// entry, listed along with what they grant in the database's deployment status
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct IamConf {
+    // Principals allowed to assume the roles, e.g. an account's root or an sso role's arn
+    pub trusted_principals: Vec<String>,
+    // Path the roles are created under, starting and ending with `/`
+    #[serde(default = "default_iam_role_path")]
+    pub role_path: String,
+    // Caps what the roles can ever be granted, whatever their inline policies say
+    #[serde(default)]
+    pub permissions_boundary_arn: Option<String>,
+}
+
+fn default_iam_role_path() -> String {
+    "/basin/".to_string()
+}
+
+// Needed for tables asking for maintenance, their maintenance flows run this spark application
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TableMaintenanceConf {
+    // Called with `--table <db.table> --format <iceberg|delta> --action <action>`, retention
+    // taking actions get `--retain-hours <n>` too. A `.py` on s3 to run on the glue backends.
+    pub application: String,
+}
+
+// Needed for tables declaring quality rules, their validation flows run this spark application
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct QualityConf {
+    // Called with `--table <db.table> --suite-url <url> --results-url <url>`, is expected to fetch
+    // the great expectations suite from the first, validate the table against it and post what it
+    // found to the second. Gets `--fail-on-error` when broken rules should fail the run.
+    pub application: String,
+    // Where validation runs reach basin's api, e.g. `http://basin.data-platform.svc:3000`
+    pub basin_url: String,
+}
+
+// Needed for glue tables with masked columns, lake formation hides them behind a data cells filter
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MaskingConf {
+    // Account id of the glue catalog basin's tables are in
+    pub catalog_id: String,
+    // Principals only let see glue tables through the filter, e.g. analyst role arns. Anyone
+    // granted the table itself still sees every column.
+    #[serde(default)]
+    pub lake_formation_principals: Vec<String>,
+}
+
+// Needed for landing zones, their conversion flows run this spark application
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LandingZonesConf {
+    // Called with `--source <s3 uri> --format <csv|json> --table <db.table>`, is expected to move
+    // the files it has converted out of the landing prefix
+    pub conversion_application: String,
+    // Queue told about every file uploaded to a landing zone, no bucket notifications when unset
+    #[serde(default)]
+    pub notification_queue_arn: Option<String>,
+}
+
+// Access to glue databases and tables can be requested through the api, approved requests are
+// granted with lake formation. Approvers are the api tokens scoped `access-requests:decide`, nobody
+// can decide on a request their own token made.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AccessRequestsConf {
+    // How often approved requests' grants are (re)applied
+    #[serde(default = "default_grant_interval_secs")]
+    pub interval_secs: u64,
+}
+
+fn default_grant_interval_secs() -> u64 {
+    5 * 60
+}
+
+// Destructive operations wait on a second person before going ahead. Approvers are the api tokens
+// scoped `approvals:decide`, nobody can decide on an operation their own token asked for.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ApprovalsConf {
+    // Deletions made through the api
+    #[serde(default = "default_approve_deletions")]
+    pub deletions: bool,
+    // Submissions that drop or retype a table's columns, or make one of them required, however
+    // they arrive
+    #[serde(default = "default_approve_breaking_changes")]
+    pub breaking_changes: bool,
+}
+
+fn default_approve_deletions() -> bool {
+    true
+}
+
+fn default_approve_breaking_changes() -> bool {
+    true
+}
+
+// Service quota codes checked before basin creates a resource counting against them, resources
+// without a code aren't checked. `aws service-quotas list-service-quotas --service-code <s3|glue>`
+// lists the codes.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct QuotasConf {
+    #[serde(default)]
+    pub s3_buckets: Option<String>,
+    #[serde(default)]
+    pub glue_databases: Option<String>,
+    // Tables per account, counting them walks every database so the count is cached too
+    #[serde(default)]
+    pub glue_tables: Option<String>,
+    // How long quota values and usage are reused for
+    #[serde(default = "default_quota_cache_secs")]
+    pub cache_secs: u64,
+}
+
+fn default_quota_cache_secs() -> u64 {
+    5 * 60
+}
+
+// For testing only, makes provisioner calls slow or fail on purpose to see how retries and
+// partially provisioned descriptors are handled. Only honoured by builds with the
+// `fault-injection` feature.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FaultInjectionConf {
+    // Applies to every operation not listed in `operations`
+    #[serde(default)]
+    pub default: FaultConf,
+    // Keyed by `{provisioner}.{operation}`, e.g. `glue.create_database`
+    #[serde(default)]
+    pub operations: HashMap<String, FaultConf>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct FaultConf {
+    // Share of calls failing, between 0 and 1
+    #[serde(default)]
+    pub error_rate: f64,
+    #[serde(default)]
+    pub error_kind: FaultKind,
+    // Added before every call, failing or not
+    #[serde(default)]
+    pub latency_ms: u64,
+}
+
+// What an injected failure looks like to basin, access_denied and validation are permanent
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FaultKind {
+    #[default]
+    Throttling,
+    Unavailable,
+    AccessDenied,
+    Validation,
+}
+
+impl FaultKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FaultKind::Throttling => "throttling",
+            FaultKind::Unavailable => "unavailable",
+            FaultKind::AccessDenied => "access_denied",
+            FaultKind::Validation => "validation",
+        }
+    }
+
+    pub fn is_permanent(&self) -> bool {
+        matches!(self, FaultKind::AccessDenied | FaultKind::Validation)
+    }
+}
+
+fn default_bigquery_location() -> String {
+    "US".to_string()
+}
+
+fn default_snowflake_token_type() -> String {
+    "OAUTH".to_string()
+}
+
+fn default_snowflake_schema() -> String {
+    "basin".to_string()
+}
+
+fn default_snowflake_statement_timeout() -> u64 {
+    60
+}
+
+// Settings holding credentials, blanked wherever they appear when the settings are reported
+const SECRET_SETTINGS: &[&str] = &["password", "token", "bearer_token"];
+const REDACTED: &str = "redacted";
+
+fn redact_secrets(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(fields) => {
+            for (key, field) in fields.iter_mut() {
+                if SECRET_SETTINGS.contains(&key.as_str()) && !field.is_null() {
+                    *field = serde_json::Value::String(REDACTED.to_string());
+                } else {
+                    redact_secrets(field);
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(redact_secrets),
+        // NOTE: urls can carry credentials as well, e.g. redis://:password@host
+        serde_json::Value::String(s) => {
+            if let Ok(mut url) = reqwest::Url::parse(s)
+                && url.password().is_some()
+                && url.set_password(Some(REDACTED)).is_ok()
+            {
+                *s = url.to_string();
+            }
+        }
+        _ => {}
+    }
+}
+
+// Settings without a default, checked up front so a missing one is reported along with the rest
+const REQUIRED_SETTINGS: &[&str] = &[
+    "name",
+    "waterwheel.username",
+    "waterwheel.password",
+    "waterwheel.project",
+    "waterwheel.url",
+];
+
+// `BASIN_WATERWHEEL__URL` sets `waterwheel.url`, matching env_var_name
+// NOTE: the prefix separator would default to the nesting one, `BASIN__WATERWHEEL__URL`
+fn environment() -> config::Environment {
+    config::Environment::with_prefix(APP_NAME)
+        .prefix_separator("_")
+        .separator("__")
 }
 
 pub async fn init(file: &str) -> Result<BasinConfig> {
-    let conf_file_settings = Config::builder()
-        .add_source(config::File::with_name(file))
-        .add_source(config::Environment::with_prefix(APP_NAME).separator("__"))
-        .build()?
-        .try_deserialize::<ConfFileSettings>()?;
+    // NOTE: the file is optional so basin can be configured from the environment alone
+    let mut builder = Config::builder()
+        .add_source(config::File::with_name(file).required(false))
+        .add_source(environment());
+    for (key, value) in json_env_overrides()? {
+        builder = builder.set_override(key, value)?;
+    }
+    let conf = builder.build()?;
+
+    let missing: Vec<String> = REQUIRED_SETTINGS
+        .iter()
+        .filter(|key| conf.get::<config::Value>(key).is_err())
+        .map(|key| format!("{} ({})", key, env_var_name(key)))
+        .collect();
+    if !missing.is_empty() {
+        bail!(
+            "missing required settings, set them in {} or the environment: {}",
+            file,
+            missing.join(", ")
+        );
+    }
+
+    let conf_file_settings = conf.try_deserialize::<ConfFileSettings>()?;
+
+    let redis = redis_conf(
+        redis_topology(
+            conf_file_settings.redis_url.clone(),
+            conf_file_settings.redis_sentinel.clone(),
+            conf_file_settings.redis_cluster_nodes.clone(),
+        )?,
+        conf_file_settings.redis_key_prefix.clone(),
+    );
+
+    let store_migration = match &conf_file_settings.store_migration {
+        Some(migration) => {
+            let topology = redis_topology(
+                migration.redis_url.clone(),
+                migration.redis_sentinel.clone(),
+                migration.redis_cluster_nodes.clone(),
+            )
+            .context("store_migration")?;
+            if migration.batch_size == 0 {
+                bail!("store_migration.batch_size must be at least 1");
+            }
+            Some(StoreMigrationConf {
+                target: redis_conf(topology, migration.redis_key_prefix.clone()),
+                batch_size: migration.batch_size,
+            })
+        }
+        None => None,
+    };
+
+    if conf_file_settings.fault_injection.is_some() && !cfg!(feature = "fault-injection") {
+        bail!("fault_injection is set but basin wasn't built with the fault-injection feature");
+    }
+
+    if conf_file_settings.storage.catalog == Catalog::Hive
+        && conf_file_settings.hive_metastore.is_none()
+    {
+        bail!("hive_metastore must be set when storage.catalog is hive");
+    }
+
+    full_match_patterns(&conf_file_settings.storage.allowed_location_buckets)
+        .context("storage.allowed_location_buckets")?;
+    full_match_patterns(&conf_file_settings.flow_images.allowed).context("flow_images.allowed")?;
+
+    if let Some(sharding) = &conf_file_settings.sharding
+        && (sharding.virtual_nodes == 0
+            || sharding.member_ttl_secs <= sharding.heartbeat_interval_secs)
+    {
+        bail!("sharding needs virtual_nodes and a member_ttl_secs longer than the heartbeat");
+    }
+
+    if let Some(rate_limits) = &conf_file_settings.rate_limits
+        && (rate_limits.burst == 0
+            || rate_limits.requests_per_minute == 0
+            || rate_limits.clients.values().any(|&q| q == 0))
+    {
+        bail!("rate_limits quotas and burst must be at least 1");
+    }
+
+    if let Some(api_tokens) = &conf_file_settings.api_tokens
+        && (api_tokens.admin_token_sha256.len() != 64
+            || !api_tokens
+                .admin_token_sha256
+                .chars()
+                .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c)))
+    {
+        bail!("api_tokens.admin_token_sha256 must be a sha256 in lowercase hex");
+    }
+
+    if let Some(preview) = &conf_file_settings.preview
+        && (preview.max_rows == 0 || preview.max_rows > 1000)
+    {
+        bail!("preview max_rows must be between 1 and 1000");
+    }
+
+    if conf_file_settings.access_requests.is_some() && conf_file_settings.api_tokens.is_none() {
+        bail!("access_requests need api_tokens to tell requesters and approvers apart");
+    }
+    if conf_file_settings.approvals.is_some() && conf_file_settings.api_tokens.is_none() {
+        bail!("approvals need api_tokens to tell requesters and approvers apart");
+    }
+
+    if let Some(encryption) = &conf_file_settings.descriptor_encryption
+        && encryption.kms_key_id.is_empty()
+    {
+        bail!("descriptor_encryption.kms_key_id must be set");
+    }
+
+    if let Some(registry) = &conf_file_settings.event_watcher.schema_registry
+        && registry.confluent.is_some() == registry.glue.is_some()
+    {
+        bail!("event_watcher.schema_registry needs exactly one of confluent or glue");
+    }
+
+    if let Some(redshift) = &conf_file_settings.redshift
+        && redshift.cluster_identifier.is_some() == redshift.workgroup_name.is_some()
+    {
+        bail!("redshift needs exactly one of cluster_identifier or workgroup_name");
+    }
+
+    if let Some(iam) = &conf_file_settings.iam {
+        if iam.trusted_principals.is_empty() {
+            bail!("iam needs at least one trusted principal");
+        }
+        if !iam.role_path.starts_with('/') || !iam.role_path.ends_with('/') {
+            bail!("iam role_path must start and end with '/'");
+        }
+    }
+
+    if let Some(policy) = &conf_file_settings.policy
+        && policy.opa.is_none()
+        && policy.webhook.is_none()
+    {
+        bail!("policy needs opa, a webhook or both");
+    }
+
+    if conf_file_settings.validation_plugins.iter().any(|p| {
+        p.max_memory_mb == 0 || p.max_fuel == 0 || p.timeout_ms == 0 || p.max_verdict_kb == 0
+    }) {
+        bail!("validation_plugins limits must be at least 1");
+    }
+
+    let reconciler = &conf_file_settings.reconciler;
+    if reconciler.lock_margin_secs == 0 {
+        bail!("reconciler.lock_margin_secs must be at least 1");
+    }
+    let settings = &conf_file_settings.controllers;
+    let controllers = ControllersConf {
+        database: settings.database.resolve(reconciler),
+        table: settings.table.resolve(reconciler),
+        flow: settings.flow.resolve(reconciler),
+        landing_zone: settings.landing_zone.resolve(reconciler),
+    };
+    for kind in DescriptorKind::ALL {
+        validate_controller(kind, controllers.get(kind))?;
+    }
+
+    // NOTE: reconciles write the state when they finish, a shorter threshold would reset ones
+    //       still running
+    if let Some(stuck) = &conf_file_settings.stuck_deployments
+        && DescriptorKind::ALL
+            .iter()
+            .any(|kind| stuck.threshold_secs <= controllers.get(*kind).reconcile_timeout_secs)
+    {
+        bail!("stuck_deployments.threshold_secs must be longer than every controller's reconcile_timeout_secs");
+    }
+
+    if conf_file_settings.waterwheel.max_attempts == 0 {
+        bail!("waterwheel.max_attempts must be at least 1");
+    }
+
+    if conf_file_settings.aws_client.max_attempts == 0 {
+        bail!("aws_client.max_attempts must be at least 1");
+    }
+
+    let mut aws_loader = aws_client::loader(&conf_file_settings.aws_client);
+    if let Some(url) = &conf_file_settings.aws_endpoint_url {
+        aws_loader = aws_loader.endpoint_url(url);
+    }
+
+    if conf_file_settings.event_source == EventSource::Sqs
+        && conf_file_settings.event_sqs_url.is_empty()
+    {
+        bail!("event_sqs_url must be set when the event source is sqs");
+    }
+
+    // NOTE: what basin actually runs with, defaults filled in and controllers as resolved
+    let mut effective_settings = serde_json::to_value(&conf_file_settings)?;
+    effective_settings["controllers"] = serde_json::to_value(&controllers)?;
+    redact_secrets(&mut effective_settings);
 
     Ok(BasinConfig {
         name: conf_file_settings.name,
-        redis_url: conf_file_settings.redis_url,
+        redis,
+        descriptor_encryption: conf_file_settings.descriptor_encryption,
         event_sqs_url: conf_file_settings.event_sqs_url,
-        waterwheel_username: conf_file_settings.waterwheel.username,
-        waterwheel_password: conf_file_settings.waterwheel.password,
-        waterwheel_project: conf_file_settings.waterwheel.project,
-        waterwheel_url: conf_file_settings.waterwheel.url,
-        aws_creds: aws_config::load_from_env().await,
+        event_source: conf_file_settings.event_source,
+        kubernetes: conf_file_settings.kubernetes,
+        waterwheel: conf_file_settings.waterwheel,
+        aws_creds: aws_loader.load().await,
+        http: conf_file_settings.http,
+        reconciler: conf_file_settings.reconciler,
+        controllers,
+        sharding: conf_file_settings.sharding,
+        log_format: conf_file_settings.log_format,
+        id_generator: conf_file_settings.id_generator,
+        event_watcher: conf_file_settings.event_watcher,
+        git_sync: conf_file_settings.git_sync,
+        s3_source: conf_file_settings.s3_source,
+        retention: conf_file_settings.retention,
+        backup: conf_file_settings.backup,
+        freshness: conf_file_settings.freshness,
+        stuck_deployments: conf_file_settings.stuck_deployments,
+        data_triggers: conf_file_settings.data_triggers,
+        smoke_tests: conf_file_settings.smoke_tests,
+        preview: conf_file_settings.preview,
+        deletion: conf_file_settings.deletion,
+        state_events: conf_file_settings.state_events,
+        server: conf_file_settings.server,
+        limits: conf_file_settings.limits,
+        policy: conf_file_settings.policy,
+        validation_plugins: conf_file_settings.validation_plugins,
+        rate_limits: conf_file_settings.rate_limits,
+        api_tokens: conf_file_settings.api_tokens,
+        read_only: conf_file_settings.read_only,
+        store_migration,
+        storage: conf_file_settings.storage,
+        cost: conf_file_settings.cost,
+        sql: conf_file_settings.sql,
+        flow_images: conf_file_settings.flow_images,
+        dbt: conf_file_settings.dbt,
+        spark: conf_file_settings.spark,
+        flows: conf_file_settings.flows,
+        snowflake: conf_file_settings.snowflake,
+        bigquery: conf_file_settings.bigquery,
+        unity_catalog: conf_file_settings.unity_catalog,
+        hive_metastore: conf_file_settings.hive_metastore,
+        trino: conf_file_settings.trino,
+        redshift: conf_file_settings.redshift,
+        datahub: conf_file_settings.datahub,
+        iam: conf_file_settings.iam,
+        table_maintenance: conf_file_settings.table_maintenance,
+        quality: conf_file_settings.quality,
+        masking: conf_file_settings.masking,
+        landing_zones: conf_file_settings.landing_zones,
+        access_requests: conf_file_settings.access_requests,
+        approvals: conf_file_settings.approvals,
+        quotas: conf_file_settings.quotas,
+        fault_injection: conf_file_settings.fault_injection,
+        effective_settings,
+    })
+}
+
+fn redis_topology(
+    url: Option<String>,
+    sentinel: Option<RedisSentinelConf>,
+    cluster_nodes: Vec<String>,
+) -> Result<RedisTopology> {
+    Ok(match (url, sentinel, cluster_nodes) {
+        (Some(url), None, nodes) if nodes.is_empty() => RedisTopology::Single { url },
+        (None, Some(sentinel), nodes) if nodes.is_empty() => RedisTopology::Sentinel {
+            service_name: sentinel.service_name,
+            nodes: sentinel.nodes,
+            tls: sentinel.tls,
+            password: sentinel.password,
+            db: sentinel.db,
+        },
+        (None, None, nodes) if !nodes.is_empty() => RedisTopology::Cluster { nodes },
+        _ => bail!("exactly one of redis_url, redis_sentinel or redis_cluster_nodes must be set"),
     })
 }
+
+fn redis_conf(topology: RedisTopology, key_prefix: String) -> RedisConf {
+    RedisConf {
+        key_prefix: match topology {
+            // NOTE: wrapping the prefix in a hash tag pins every basin key to a single slot,
+            //       keeping multi-key commands (MGET, pipelines) valid in cluster mode and letting
+            //       keyspace scans be routed to the slot's owner, see RedisConnection
+            RedisTopology::Cluster { .. } => format!(
+                "{{{}}}",
+                if key_prefix.is_empty() {
+                    APP_NAME.to_ascii_lowercase()
+                } else {
+                    key_prefix
+                }
+            ),
+            _ => key_prefix,
+        },
+        topology,
+    }
+}
+
+// Environment variables holding a json array or object, e.g.
+// `BASIN_REDIS_CLUSTER_NODES='["redis://a:6379"]'`, are decoded so list and nested settings can
+// be set without a file. Plain values are left to the environment source.
+fn json_env_overrides() -> Result<Vec<(String, config::Value)>> {
+    let prefix = format!("{}_", APP_NAME);
+    let mut overrides = vec![];
+    for (name, value) in std::env::vars() {
+        let Some(key) = name.strip_prefix(&prefix) else {
+            continue;
+        };
+        let trimmed = value.trim_start();
+        if !trimmed.starts_with('[') && !trimmed.starts_with('{') {
+            continue;
+        }
+
+        let json: serde_json::Value =
+            serde_json::from_str(&value).with_context(|| format!("{} is not valid json", name))?;
+        overrides.push((
+            key.to_ascii_lowercase().replace("__", "."),
+            json_to_config_value(json),
+        ));
+    }
+    Ok(overrides)
+}
+
+fn json_to_config_value(json: serde_json::Value) -> config::Value {
+    let kind = match json {
+        serde_json::Value::Null => ValueKind::Nil,
+        serde_json::Value::Bool(b) => ValueKind::Boolean(b),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => ValueKind::I64(i),
+            None => ValueKind::Float(n.as_f64().unwrap_or_default()),
+        },
+        serde_json::Value::String(s) => ValueKind::String(s),
+        serde_json::Value::Array(items) => {
+            ValueKind::Array(items.into_iter().map(json_to_config_value).collect())
+        }
+        serde_json::Value::Object(fields) => ValueKind::Table(
+            fields
+                .into_iter()
+                .map(|(k, v)| (k, json_to_config_value(v)))
+                .collect(),
+        ),
+    };
+    config::Value::new(None, kind)
+}
+
+fn env_var_name(key: &str) -> String {
+    format!(
+        "{}_{}",
+        APP_NAME,
+        key.replace('.', "__").to_ascii_uppercase()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_nested_settings_from_the_environment() {
+        let conf = Config::builder()
+            .add_source(environment().source(Some(HashMap::from([(
+                env_var_name("waterwheel.url"),
+                "http://waterwheel:8080".to_string(),
+            )]))))
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            conf.get_string("waterwheel.url").unwrap(),
+            "http://waterwheel:8080"
+        );
+    }
+}
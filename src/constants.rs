@@ -1,2 +1,22 @@
 pub const APP_NAME: &str = "BASIN";
 pub const DEFAULT_CONF: &str = "./basin.toml";
+// Description of Pending deployment states whose kind's controller is switched off
+pub const CONTROLLER_DISABLED: &str = "controller disabled";
+// Tag/parameter holding the hash of the descriptor a resource was provisioned from
+pub const DESCRIPTOR_HASH_KEY: &str = "basin_descriptor_hash";
+// Cost allocation tag naming the descriptor a resource belongs to, what cost reports filter on
+pub const DESCRIPTOR_ID_TAG_KEY: &str = "basin_descriptor_id";
+// Tags naming who to contact about a resource, from the descriptor's owner
+pub const OWNER_TEAM_TAG_KEY: &str = "basin_owner_team";
+pub const OWNER_EMAIL_TAG_KEY: &str = "basin_owner_email";
+pub const OWNER_SLACK_CHANNEL_TAG_KEY: &str = "basin_owner_slack_channel";
+// Table parameter holding the table's data classification
+pub const TABLE_CLASSIFICATION_KEY: &str = "basin_classification";
+// Column parameters documenting a column's default and generation expressions
+pub const COLUMN_DEFAULT_KEY: &str = "basin_default";
+pub const COLUMN_GENERATED_KEY: &str = "basin_generated";
+// Table and column parameters holding their descriptive metadata, glossary terms comma separated
+// and docs and the deprecation as json
+pub const GLOSSARY_TERMS_KEY: &str = "basin_glossary_terms";
+pub const DOCS_KEY: &str = "basin_docs";
+pub const DEPRECATION_KEY: &str = "basin_deprecation";
@@ -2,4 +2,11 @@ pub mod base;
 pub mod database;
 pub mod error;
 pub mod flow;
+pub mod flow_spec;
+pub mod landing_zone;
+pub mod maintenance;
+pub mod naming;
+pub mod quality;
+pub mod reconciler;
 pub mod table;
+pub mod validation_plugins;
@@ -1,23 +1,60 @@
 use super::base::BaseController;
 use super::error::ControllerReconciliationError;
-use crate::config::BasinConfig;
-use crate::descriptor_store::{DescriptorStore, RedisDescriptorStore};
-use crate::provisioner::s3::S3Provisioner;
-use crate::{fluid::descriptor::database::DatabaseDescriptor, provisioner::glue::GlueProvisioner};
+use super::naming::{
+    bigquery_dataset_name, cost_tags, glue_database_name, iam_role_name, s3_bucket_name,
+    snowflake_database_name,
+};
+use crate::config::{BasinConfig, CostConf, StorageConf};
+use crate::constants::DESCRIPTOR_HASH_KEY;
+use crate::fluid::descriptor::{descriptor_hash, Catalog, StorageEngine};
+use crate::iam_role_store::{DatabaseRole, IamRoleStore, RedisIamRoleStore};
+use crate::provisioner::bigquery::BigQueryProvisioner;
+use crate::provisioner::datahub::DataHubProvisioner;
+use crate::provisioner::hive_metastore::HiveMetastoreProvisioner;
+use crate::provisioner::iam::IamProvisioner;
+use crate::provisioner::redshift::RedshiftProvisioner;
+use crate::provisioner::s3::{S3BucketClient, S3Provisioner};
+use crate::provisioner::service_quotas::{QuotaChecker, QuotaResource};
+use crate::provisioner::snowflake::SnowflakeProvisioner;
+use crate::provisioner::trino::TrinoProvisioner;
+use crate::provisioner::unity_catalog::UnityCatalogProvisioner;
+use crate::{
+    fluid::descriptor::database::DatabaseDescriptor,
+    provisioner::glue::{GlueDatabaseClient, GlueProvisioner},
+};
 
-use anyhow::{ensure, Result};
+use anyhow::{anyhow, ensure, Result};
 use regex::Regex;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
 use tokio::try_join;
 
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
 const VALIDATION_REGEX_NAME: &str = r"^[a-z0-9_]+$";
 
+// Access the roles a glue engine database gets grant, one role each
+const IAM_ROLE_ACCESS: &[&str] = &["read", "write"];
+const IAM_POLICY_NAME: &str = "basin-database-access";
+// Longest role name iam accepts
+const MAX_IAM_ROLE_NAME_LEN: usize = 64;
+
 #[derive(Debug)]
 pub struct DatabaseController {
-    descriptor_store: RedisDescriptorStore,
-    glue_provisioner: GlueProvisioner,
-    s3_provisioner: S3Provisioner,
+    cost: CostConf,
+    storage: StorageConf,
+    glue_provisioner: Box<dyn GlueDatabaseClient>,
+    hive_metastore_provisioner: Option<HiveMetastoreProvisioner>,
+    s3_provisioner: Box<dyn S3BucketClient>,
+    quota_checker: QuotaChecker,
+    snowflake_provisioner: Option<SnowflakeProvisioner>,
+    bigquery_provisioner: Option<BigQueryProvisioner>,
+    unity_catalog_provisioner: Option<UnityCatalogProvisioner>,
+    trino_provisioner: Option<TrinoProvisioner>,
+    redshift_provisioner: Option<RedshiftProvisioner>,
+    datahub_provisioner: Option<DataHubProvisioner>,
+    iam_provisioner: Option<IamProvisioner>,
+    iam_role_store: RedisIamRoleStore,
 }
 
 #[async_trait::async_trait]
@@ -32,6 +69,50 @@ impl BaseController<DatabaseDescriptor> for DatabaseController {
                 descriptor.name, VALIDATION_REGEX_NAME
             )
         );
+        ensure!(
+            descriptor.engine != StorageEngine::Snowflake || self.snowflake_provisioner.is_some(),
+            "snowflake isn't configured"
+        );
+        ensure!(
+            descriptor.engine != StorageEngine::Bigquery || self.bigquery_provisioner.is_some(),
+            "bigquery isn't configured"
+        );
+        ensure!(
+            descriptor.catalog.is_none() || descriptor.engine == StorageEngine::Glue,
+            "only glue engine databases can pick a catalog"
+        );
+        ensure!(
+            self.iam_provisioner.is_none()
+                || descriptor.engine != StorageEngine::Glue
+                || IAM_ROLE_ACCESS
+                    .iter()
+                    .all(|a| iam_role_name(descriptor, a).len() <= MAX_IAM_ROLE_NAME_LEN),
+            format!(
+                "name '{}' is too long for the database's iam role names",
+                descriptor.name
+            )
+        );
+        ensure!(
+            self.storage.catalog_for(descriptor) != Catalog::Hive
+                || self.hive_metastore_provisioner.is_some(),
+            "hive metastore isn't configured"
+        );
+        if let Some(defaults) = &descriptor.defaults {
+            ensure!(
+                descriptor.engine == StorageEngine::Glue
+                    || (defaults.format.is_none()
+                        && defaults.compression.is_none()
+                        && defaults.lifecycle.is_none()),
+                "only glue engine databases can default a format, compression or lifecycle"
+            );
+            ensure!(
+                defaults
+                    .lifecycle
+                    .as_ref()
+                    .map_or(true, |l| l.expire_after_days > 0),
+                "lifecycle expire_after_days must be at least 1"
+            );
+        }
 
         Ok(())
     }
@@ -42,37 +123,116 @@ impl BaseController<DatabaseDescriptor> for DatabaseController {
         debug!("Full descriptor to be reconciled is {:?}", descriptor);
 
         info!("Delegating resource reconciliation to clients");
-        try_join!(
-            self.reconcile_s3(&descriptor),
-            self.reconcile_glue(&descriptor),
-            self.reconcile_iam(),
-        )
-        .inspect_err(|e| error!(?e, "Resource reconciliation failed"))
-        .map_err(|e| ControllerReconciliationError::ProvisionerError(e.into()))?;
+        let result = match descriptor.engine {
+            StorageEngine::Glue => {
+                match try_join!(
+                    self.reconcile_s3(&descriptor),
+                    self.reconcile_catalog(&descriptor),
+                    self.reconcile_iam(&descriptor),
+                ) {
+                    Ok(_) => self.reconcile_unity_catalog(&descriptor).await,
+                    Err(e) => Err(e),
+                }
+            }
+            StorageEngine::Snowflake => self.reconcile_snowflake(&descriptor).await,
+            StorageEngine::Bigquery => self.reconcile_bigquery(&descriptor).await,
+        };
+        result
+            .inspect_err(|e| error!(?e, "Resource reconciliation failed"))
+            .map_err(|e| ControllerReconciliationError::provisioner(e.into()))?;
+        if descriptor.engine == StorageEngine::Glue {
+            self.register_trino_schema(&descriptor).await;
+            self.register_redshift_schema(&descriptor).await;
+            self.push_datahub_database(&descriptor).await;
+        }
 
         info!("Finished resource reconciliation");
         Ok(())
     }
 
-    async fn list_descriptors(&self) -> Result<Vec<DatabaseDescriptor>> {
-        Ok(self
-            .descriptor_store
-            .list_descriptors::<DatabaseDescriptor>("database")
-            .await?)
+    #[tracing::instrument(level = "info", name = "db_teardown", skip(self, descriptor), fields(descriptor_id = %descriptor.id))]
+    async fn teardown(&self, descriptor: &DatabaseDescriptor) -> Result<()> {
+        info!("Tearing down database");
+
+        let result = match descriptor.engine {
+            // NOTE: only the catalog entry goes, the bucket and its data are left behind
+            StorageEngine::Glue => match self.storage.catalog_for(descriptor) {
+                Catalog::Glue => {
+                    self.glue_provisioner
+                        .delete_database(&glue_database_name(&descriptor))
+                        .await
+                }
+                Catalog::Hive => match &self.hive_metastore_provisioner {
+                    Some(p) => p.delete_database(&glue_database_name(&descriptor)).await,
+                    None => Err(anyhow!("hive metastore isn't configured")),
+                },
+            },
+            StorageEngine::Snowflake => match &self.snowflake_provisioner {
+                Some(p) => p.drop_database(&snowflake_database_name(&descriptor)).await,
+                None => Err(anyhow!("snowflake isn't configured")),
+            },
+            StorageEngine::Bigquery => match &self.bigquery_provisioner {
+                Some(p) => p.delete_dataset(&bigquery_dataset_name(&descriptor)).await,
+                None => Err(anyhow!("bigquery isn't configured")),
+            },
+        };
+        result
+            .inspect_err(|e| error!(?e, "Resource teardown failed"))
+            .map_err(|e| ControllerReconciliationError::provisioner(e.into()))?;
+        if descriptor.engine == StorageEngine::Glue {
+            self.teardown_iam(&descriptor)
+                .await
+                .inspect_err(|e| error!(?e, "IAM teardown failed"))
+                .map_err(|e| ControllerReconciliationError::provisioner(e.into()))?;
+        }
+
+        info!("Finished resource teardown");
+        Ok(())
     }
 }
 
 impl DatabaseController {
     pub async fn new(conf: &BasinConfig) -> Result<Self> {
         Ok(DatabaseController {
-            descriptor_store: RedisDescriptorStore::new(&conf.redis_url).await?,
-            glue_provisioner: GlueProvisioner::new(&conf.aws_creds),
-            s3_provisioner: S3Provisioner::new(&conf.aws_creds),
+            cost: conf.cost.clone(),
+            storage: conf.storage.clone(),
+            glue_provisioner: Box::new(GlueProvisioner::new(&conf.aws_creds)),
+            hive_metastore_provisioner: conf
+                .hive_metastore
+                .as_ref()
+                .map(HiveMetastoreProvisioner::new),
+            s3_provisioner: Box::new(S3Provisioner::new(&conf.aws_creds)),
+            quota_checker: QuotaChecker::new(conf.quotas.as_ref(), &conf.aws_creds),
+            snowflake_provisioner: conf.snowflake.as_ref().map(SnowflakeProvisioner::new),
+            bigquery_provisioner: conf
+                .bigquery
+                .as_ref()
+                .map(BigQueryProvisioner::new)
+                .transpose()?,
+            unity_catalog_provisioner: conf
+                .unity_catalog
+                .as_ref()
+                .map(UnityCatalogProvisioner::new),
+            trino_provisioner: conf.trino.as_ref().map(TrinoProvisioner::new),
+            redshift_provisioner: conf
+                .redshift
+                .as_ref()
+                .map(|c| RedshiftProvisioner::new(&conf.aws_creds, c)),
+            datahub_provisioner: conf
+                .datahub
+                .as_ref()
+                .map(DataHubProvisioner::new)
+                .transpose()?,
+            iam_provisioner: conf
+                .iam
+                .as_ref()
+                .map(|c| IamProvisioner::new(&conf.aws_creds, c)),
+            iam_role_store: RedisIamRoleStore::new(&conf.redis).await?,
         })
     }
 
     async fn reconcile_s3(&self, descriptor: &DatabaseDescriptor) -> Result<()> {
-        let s3_name = Self::s3_name_for(&descriptor);
+        let s3_name = s3_bucket_name(&descriptor);
         info!("Reconciling s3 resource");
 
         debug!(s3_name, "Fetching s3 bucket");
@@ -85,15 +245,26 @@ impl DatabaseController {
         if bucket_exists {
             info!("found bucket in s3");
             self.s3_provisioner
-                .update_bucket(&s3_name)
+                .update_bucket(
+                    &s3_name,
+                    &descriptor_hash(descriptor),
+                    &self.cost_tags(descriptor),
+                )
                 .await
                 .inspect_err(|e| error!(?e, "got unexpected error when updating s3 bucket"))?;
             info!("finished updating s3 bucket");
         } else {
             info!("s3 bucket does not exist. provisioning a new one");
 
+            self.quota_checker
+                .ensure_capacity(QuotaResource::S3Buckets)
+                .await?;
             self.s3_provisioner
-                .create_bucket(&s3_name)
+                .create_bucket(
+                    &s3_name,
+                    &descriptor_hash(descriptor),
+                    &self.cost_tags(descriptor),
+                )
                 .await
                 .inspect_err(|e| error!(?e, "got unexpected error when creating s3 bucket"))?;
         }
@@ -101,8 +272,15 @@ impl DatabaseController {
         Ok(())
     }
 
+    async fn reconcile_catalog(&self, descriptor: &DatabaseDescriptor) -> Result<()> {
+        match self.storage.catalog_for(descriptor) {
+            Catalog::Glue => self.reconcile_glue(descriptor).await,
+            Catalog::Hive => self.reconcile_hive(descriptor).await,
+        }
+    }
+
     async fn reconcile_glue(&self, descriptor: &DatabaseDescriptor) -> Result<()> {
-        let glue_name = Self::glue_name_for(&descriptor);
+        let glue_name = glue_database_name(&descriptor);
         info!("Reconciling glue resource");
 
         debug!(glue_name, "Fetching glue resource");
@@ -118,7 +296,9 @@ impl DatabaseController {
                     .update_database(
                         &glue_name,
                         &descriptor.summary,
-                        &format!("s3://{}", Self::s3_name_for(&descriptor)),
+                        &format!("s3://{}", s3_bucket_name(&descriptor)),
+                        &descriptor_hash(descriptor),
+                        &self.cost_tags(descriptor),
                     )
                     .await
                     .inspect_err(|e| {
@@ -129,11 +309,16 @@ impl DatabaseController {
             None => {
                 info!("glue database does not exist, provisioning a new one");
 
+                self.quota_checker
+                    .ensure_capacity(QuotaResource::GlueDatabases)
+                    .await?;
                 self.glue_provisioner
                     .create_database(
                         &glue_name,
                         &descriptor.summary,
-                        &format!("s3://{}", Self::s3_name_for(&descriptor)),
+                        &format!("s3://{}", s3_bucket_name(&descriptor)),
+                        &descriptor_hash(descriptor),
+                        &self.cost_tags(descriptor),
                     )
                     .await
                     .inspect_err(|e| {
@@ -144,16 +329,497 @@ impl DatabaseController {
         Ok(())
     }
 
-    async fn reconcile_iam(&self) -> Result<()> {
+    async fn reconcile_hive(&self, descriptor: &DatabaseDescriptor) -> Result<()> {
+        let hive = self
+            .hive_metastore_provisioner
+            .as_ref()
+            .ok_or_else(|| anyhow!("hive metastore isn't configured"))?;
+        // NOTE: named as in glue so flows' SQL doesn't change with the catalog
+        let name = glue_database_name(&descriptor);
+        let location = format!("s3://{}", s3_bucket_name(&descriptor));
+        let hash = descriptor_hash(descriptor);
+        info!(name, "Reconciling hive database");
+
+        match hive.get_database(&name).await? {
+            Some(t) if t.parameters.get(DESCRIPTOR_HASH_KEY) == Some(&hash) => {
+                debug!(?t, "hive database is up to date");
+            }
+            Some(t) => {
+                debug!(?t, "hive database");
+                hive.update_database(&name, &descriptor.summary, &location, &hash)
+                    .await
+                    .inspect_err(|e| {
+                        error!(?e, "got unexpected error when updating hive database")
+                    })?;
+                info!("finished updating hive database");
+            }
+            None => {
+                info!("hive database does not exist, provisioning a new one");
+                hive.create_database(&name, &descriptor.summary, &location, &hash)
+                    .await
+                    .inspect_err(|e| {
+                        error!(?e, "got unexpected error when creating hive database")
+                    })?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn reconcile_snowflake(&self, descriptor: &DatabaseDescriptor) -> Result<()> {
+        let name = snowflake_database_name(&descriptor);
+        info!(name, "Reconciling snowflake database");
+
+        self.snowflake_provisioner
+            .as_ref()
+            .ok_or_else(|| anyhow!("snowflake isn't configured"))?
+            .create_database(&name, &descriptor.summary)
+            .await
+            .inspect_err(|e| error!(?e, "got unexpected error when creating snowflake database"))?;
+
+        info!("finished reconciling snowflake database");
+        Ok(())
+    }
+
+    async fn reconcile_bigquery(&self, descriptor: &DatabaseDescriptor) -> Result<()> {
+        let name = bigquery_dataset_name(&descriptor);
+        info!(name, "Reconciling bigquery dataset");
+
+        self.bigquery_provisioner
+            .as_ref()
+            .ok_or_else(|| anyhow!("bigquery isn't configured"))?
+            .put_dataset(&name, &descriptor.summary)
+            .await
+            .inspect_err(|e| {
+                error!(?e, "got unexpected error when reconciling bigquery dataset")
+            })?;
+
+        info!("finished reconciling bigquery dataset");
+        Ok(())
+    }
+
+    // Mirrors the glue database into unity catalog when it's configured
+    async fn reconcile_unity_catalog(&self, descriptor: &DatabaseDescriptor) -> Result<()> {
+        let Some(unity_catalog) = &self.unity_catalog_provisioner else {
+            return Ok(());
+        };
+        info!("Reconciling unity catalog schema");
+
+        unity_catalog
+            .put_external_location(&s3_bucket_name(&descriptor))
+            .await
+            .inspect_err(|e| {
+                error!(
+                    ?e,
+                    "got unexpected error when reconciling external location"
+                )
+            })?;
+        unity_catalog
+            .put_schema(&glue_database_name(&descriptor), &descriptor.summary)
+            .await
+            .inspect_err(|e| {
+                error!(
+                    ?e,
+                    "got unexpected error when reconciling unity catalog schema"
+                )
+            })?;
+
+        info!("finished reconciling unity catalog schema");
+        Ok(())
+    }
+
+    // NOTE: best effort, trino picks the schema up by itself once its cache expires
+    async fn register_trino_schema(&self, descriptor: &DatabaseDescriptor) {
+        let Some(trino) = &self.trino_provisioner else {
+            return;
+        };
+
+        if let Err(e) = trino
+            .register_schema(
+                &glue_database_name(&descriptor),
+                &format!("s3://{}", s3_bucket_name(&descriptor)),
+            )
+            .await
+        {
+            warn!(?e, "failed to register schema with trino");
+        }
+    }
+
+    // NOTE: best effort, retried on the database's next reconcile. Spectrum only reads the glue
+    //       catalog, databases in the hive metastore are left out.
+    async fn register_redshift_schema(&self, descriptor: &DatabaseDescriptor) {
+        let Some(redshift) = &self.redshift_provisioner else {
+            return;
+        };
+        if self.storage.catalog_for(descriptor) != Catalog::Glue {
+            return;
+        }
+
+        if let Err(e) = redshift
+            .put_external_schema(&glue_database_name(&descriptor))
+            .await
+        {
+            warn!(?e, "failed to register external schema with redshift");
+        }
+    }
+
+    // NOTE: best effort, the catalog catches up on the database's next reconcile
+    async fn push_datahub_database(&self, descriptor: &DatabaseDescriptor) {
+        let Some(datahub) = &self.datahub_provisioner else {
+            return;
+        };
+
+        if let Err(e) = datahub
+            .put_database(
+                self.storage.catalog_for(descriptor).as_str(),
+                &glue_database_name(&descriptor),
+                &descriptor.summary,
+                descriptor.owner.as_ref(),
+            )
+            .await
+        {
+            warn!(?e, "failed to push database to datahub");
+        }
+    }
+
+    fn cost_tags(&self, descriptor: &DatabaseDescriptor) -> BTreeMap<String, String> {
+        cost_tags(
+            &descriptor.id,
+            &descriptor.labels,
+            descriptor.owner.as_ref(),
+            &self.cost,
+        )
+    }
+
+    // Puts a reader and a writer role over the database when iam is configured. The roles and a
+    // readable line per permission they're granted are kept for the database's deployment status,
+    // so consumers can find the role to assume without asking.
+    async fn reconcile_iam(&self, descriptor: &DatabaseDescriptor) -> Result<()> {
+        let Some(iam) = &self.iam_provisioner else {
+            return Ok(());
+        };
+        info!("Reconciling iam roles");
+
+        let mut roles = vec![];
+        for access in IAM_ROLE_ACCESS {
+            let name = iam_role_name(descriptor, access);
+            let grants = self.iam_grants(descriptor, access);
+            let policy = json!({
+                "Version": "2012-10-17",
+                "Statement": grants.iter().map(|(s, _)| s).collect::<Vec<_>>(),
+            });
+
+            let role_arn = iam
+                .put_role(&name, &self.cost_tags(descriptor))
+                .await
+                .inspect_err(|e| error!(?e, name, "got unexpected error when putting iam role"))?;
+            iam.put_role_policy(&name, IAM_POLICY_NAME, &policy.to_string())
+                .await
+                .inspect_err(|e| {
+                    error!(?e, name, "got unexpected error when putting iam policy")
+                })?;
+
+            roles.push(DatabaseRole {
+                access: access.to_string(),
+                role_arn,
+                permissions: grants.into_iter().map(|(_, summary)| summary).collect(),
+            });
+        }
+        self.iam_role_store
+            .set_roles(&descriptor.id, &roles)
+            .await?;
+
+        info!("finished reconciling iam roles");
         Ok(())
     }
 
-    // TODO: dedupe between this and table(table_input) controller
-    fn glue_name_for(descriptor: &DatabaseDescriptor) -> String {
-        format!("zone_{}", descriptor.name)
+    // NOTE: roles are only deleted while iam is configured, turning it off leaves them behind
+    async fn teardown_iam(&self, descriptor: &DatabaseDescriptor) -> Result<()> {
+        let Some(iam) = &self.iam_provisioner else {
+            return Ok(());
+        };
+
+        for access in IAM_ROLE_ACCESS {
+            iam.delete_role(&iam_role_name(descriptor, access)).await?;
+        }
+        self.iam_role_store.delete_roles(&descriptor.id).await
+    }
+
+    // The statements of the role granting `access` to the database, each with a readable summary.
+    // Databases in the hive metastore only get the bucket, their catalog isn't behind iam.
+    fn iam_grants(&self, descriptor: &DatabaseDescriptor, access: &str) -> Vec<(Value, String)> {
+        let bucket = s3_bucket_name(descriptor);
+        let database = glue_database_name(descriptor);
+        let glue_resources = json!([
+            "arn:aws:glue:*:*:catalog",
+            format!("arn:aws:glue:*:*:database/{}", database),
+            format!("arn:aws:glue:*:*:table/{}/*", database),
+        ]);
+        let in_glue = self.storage.catalog_for(descriptor) == Catalog::Glue;
+
+        let mut grants = vec![
+            (
+                json!({
+                    "Effect": "Allow",
+                    "Action": ["s3:ListBucket", "s3:GetBucketLocation"],
+                    "Resource": format!("arn:aws:s3:::{}", bucket),
+                }),
+                format!("list objects in s3://{}", bucket),
+            ),
+            (
+                json!({
+                    "Effect": "Allow",
+                    "Action": ["s3:GetObject"],
+                    "Resource": format!("arn:aws:s3:::{}/*", bucket),
+                }),
+                format!("read objects in s3://{}", bucket),
+            ),
+        ];
+        if in_glue {
+            grants.push((
+                json!({
+                    "Effect": "Allow",
+                    "Action": [
+                        "glue:GetDatabase",
+                        "glue:GetTable",
+                        "glue:GetTables",
+                        "glue:GetPartition",
+                        "glue:GetPartitions",
+                        "glue:BatchGetPartition",
+                    ],
+                    "Resource": glue_resources,
+                }),
+                format!(
+                    "read the glue database {}, its tables and their partitions",
+                    database
+                ),
+            ));
+        }
+        if access == "write" {
+            grants.push((
+                json!({
+                    "Effect": "Allow",
+                    "Action": ["s3:PutObject", "s3:DeleteObject", "s3:AbortMultipartUpload"],
+                    "Resource": format!("arn:aws:s3:::{}/*", bucket),
+                }),
+                format!("write and delete objects in s3://{}", bucket),
+            ));
+            if in_glue {
+                grants.push((
+                    json!({
+                        "Effect": "Allow",
+                        "Action": [
+                            "glue:CreatePartition",
+                            "glue:BatchCreatePartition",
+                            "glue:UpdatePartition",
+                            "glue:DeletePartition",
+                            "glue:BatchDeletePartition",
+                        ],
+                        "Resource": glue_resources,
+                    }),
+                    format!(
+                        "add, update and delete partitions of the glue database {}'s tables",
+                        database
+                    ),
+                ));
+            }
+        }
+        grants
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_config::SdkConfig;
+    use aws_sdk_glue::output::GetDatabaseOutput;
+    use mockall::predicate::eq;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        config::{RedisConf, RedisTopology},
+        provisioner::{glue::MockGlueDatabaseClient, s3::MockS3BucketClient},
+    };
+
+    // NOTE: nothing listens on the redis port, the store is only there to build the controller
+    async fn controller(
+        glue: MockGlueDatabaseClient,
+        s3: MockS3BucketClient,
+    ) -> DatabaseController {
+        let redis = RedisConf {
+            topology: RedisTopology::Single {
+                url: "redis://127.0.0.1:1".to_string(),
+            },
+            key_prefix: String::new(),
+        };
+        DatabaseController {
+            cost: CostConf::default(),
+            storage: StorageConf::default(),
+            glue_provisioner: Box::new(glue),
+            hive_metastore_provisioner: None,
+            s3_provisioner: Box::new(s3),
+            // NOTE: nothing is looked up without quotas configured
+            quota_checker: QuotaChecker::new(None, &SdkConfig::builder().build()),
+            snowflake_provisioner: None,
+            bigquery_provisioner: None,
+            unity_catalog_provisioner: None,
+            trino_provisioner: None,
+            redshift_provisioner: None,
+            datahub_provisioner: None,
+            iam_provisioner: None,
+            iam_role_store: RedisIamRoleStore::new(&redis).await.unwrap(),
+        }
+    }
+
+    fn database(engine: &str) -> DatabaseDescriptor {
+        serde_json::from_value(json!({
+            "id": "db-sales",
+            "name": "sales",
+            "summary": "sales data",
+            "engine": engine,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn reconcile_creates_missing_bucket_and_database() {
+        let mut s3 = MockS3BucketClient::new();
+        s3.expect_bucket_exists()
+            .with(eq("cz-vaporeon-db-sales"))
+            .returning(|_| Ok(false));
+        s3.expect_create_bucket()
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+        s3.expect_update_bucket().never();
+        let mut glue = MockGlueDatabaseClient::new();
+        glue.expect_get_database()
+            .with(eq("zone_sales"))
+            .returning(|_| Ok(None));
+        glue.expect_create_database()
+            .withf(|_, _, location, _, _| location.to_string() == "s3://cz-vaporeon-db-sales")
+            .times(1)
+            .returning(|_, _, _, _, _| Ok(()));
+        glue.expect_update_database().never();
+
+        controller(glue, s3)
+            .await
+            .reconcile(&database("glue"))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn reconcile_updates_existing_bucket_and_database() {
+        let mut s3 = MockS3BucketClient::new();
+        s3.expect_bucket_exists().returning(|_| Ok(true));
+        s3.expect_update_bucket()
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+        s3.expect_create_bucket().never();
+        let mut glue = MockGlueDatabaseClient::new();
+        glue.expect_get_database()
+            .returning(|_| Ok(Some(GetDatabaseOutput::builder().build())));
+        glue.expect_update_database()
+            .times(1)
+            .returning(|_, _, _, _, _| Ok(()));
+        glue.expect_create_database().never();
+
+        controller(glue, s3)
+            .await
+            .reconcile(&database("glue"))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn reconcile_fails_when_glue_fails_after_the_bucket() {
+        let mut s3 = MockS3BucketClient::new();
+        s3.expect_bucket_exists().returning(|_| Ok(false));
+        s3.expect_create_bucket().returning(|_, _, _| Ok(()));
+        let mut glue = MockGlueDatabaseClient::new();
+        glue.expect_get_database().returning(|_| Ok(None));
+        glue.expect_create_database()
+            .returning(|_, _, _, _, _| Err(anyhow!("glue is down")));
+
+        let e = controller(glue, s3)
+            .await
+            .reconcile(&database("glue"))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<ControllerReconciliationError>(),
+            Some(ControllerReconciliationError::ProvisionerError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn validate_rejects_engines_that_arent_configured() {
+        // NOTE: the mocks fail the test on any call, nothing should be provisioned
+        let controller = controller(MockGlueDatabaseClient::new(), MockS3BucketClient::new()).await;
+
+        let e = controller
+            .validate(&database("snowflake"))
+            .await
+            .unwrap_err();
+        assert_eq!(e.to_string(), "snowflake isn't configured");
+        assert!(controller.validate(&database("bigquery")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn teardown_deletes_the_database_and_keeps_the_bucket() {
+        let mut glue = MockGlueDatabaseClient::new();
+        glue.expect_delete_database()
+            .with(eq("zone_sales"))
+            .times(1)
+            .returning(|_| Ok(()));
+
+        controller(glue, MockS3BucketClient::new())
+            .await
+            .teardown(&database("glue"))
+            .await
+            .unwrap();
     }
 
-    fn s3_name_for(descriptor: &DatabaseDescriptor) -> String {
-        format!("cz-vaporeon-db-{}", descriptor.name.replace("_", "-"))
+    #[tokio::test]
+    async fn iam_grants_only_let_the_writer_role_write() {
+        let controller = controller(MockGlueDatabaseClient::new(), MockS3BucketClient::new()).await;
+        let db = database("glue");
+
+        let read: Vec<String> = controller
+            .iam_grants(&db, "read")
+            .into_iter()
+            .map(|(_, summary)| summary)
+            .collect();
+        assert_eq!(
+            read,
+            vec![
+                "list objects in s3://cz-vaporeon-db-sales",
+                "read objects in s3://cz-vaporeon-db-sales",
+                "read the glue database zone_sales, its tables and their partitions",
+            ]
+        );
+
+        let write = controller.iam_grants(&db, "write");
+        assert_eq!(write.len(), 5);
+        assert_eq!(
+            write[3].0["Resource"],
+            json!("arn:aws:s3:::cz-vaporeon-db-sales/*")
+        );
+        assert_eq!(
+            write[4].0["Resource"][1],
+            json!("arn:aws:glue:*:*:database/zone_sales")
+        );
+    }
+
+    #[tokio::test]
+    async fn iam_grants_leave_out_glue_for_databases_in_the_hive_metastore() {
+        let controller = controller(MockGlueDatabaseClient::new(), MockS3BucketClient::new()).await;
+        let mut db = database("glue");
+        db.catalog = Some(Catalog::Hive);
+
+        let write = controller.iam_grants(&db, "write");
+        assert!(write.iter().all(|(statement, _)| statement["Resource"]
+            .as_str()
+            .is_some_and(|r| r.starts_with("arn:aws:s3:::"))));
+        assert_eq!(write.len(), 3);
     }
 }
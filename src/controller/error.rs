@@ -1,13 +1,36 @@
 use thiserror::Error;
 
+use crate::provisioner::error::is_permanent;
+
 #[derive(Error, Debug)]
 pub enum ControllerReconciliationError {
+    // Transient or unclassified, the descriptor is retried on the next pass
     #[error("error from provisioner")]
     ProvisionerError(#[source] anyhow::Error),
+    // Won't succeed until the descriptor is resubmitted, the descriptor isn't retried
+    #[error("permanent error from provisioner")]
+    PermanentProvisionerError(#[source] anyhow::Error),
     #[error("error from controller")]
     ControllerError(#[source] anyhow::Error),
+    #[error("invalid descriptor")]
+    InvalidDescriptor(#[source] anyhow::Error),
+    // Rejected by the controller's validate, the descriptor is quarantined until released
+    #[error("descriptor failed validation")]
+    ValidationFailed(#[source] anyhow::Error),
     #[error("missing dependency `{0}`")]
     DependencyMissing(String),
+    #[error("reconcile exceeded deadline of {0}s")]
+    DeadlineExceeded(u64),
+}
+
+impl ControllerReconciliationError {
+    pub fn provisioner(e: anyhow::Error) -> Self {
+        if is_permanent(&e) {
+            ControllerReconciliationError::PermanentProvisionerError(e)
+        } else {
+            ControllerReconciliationError::ProvisionerError(e)
+        }
+    }
 }
 
 #[derive(Error, Debug)]
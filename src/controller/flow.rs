@@ -1,41 +1,189 @@
-use std::borrow::Cow;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap},
+};
 
-use super::{base::BaseController, error::ControllerReconciliationError};
+use super::{
+    base::BaseController,
+    error::ControllerReconciliationError,
+    flow_spec::FlowSpec,
+    naming::{
+        cost_tags, glue_database_name, glue_job_name, glue_trigger_name, glue_workflow_name,
+        schedule_rule_name, state_machine_name,
+    },
+};
 use crate::{
-    config::BasinConfig,
+    backfill_store::{BackfillRecord, BackfillStatus, BackfillStore, RedisBackfillStore},
+    config::{
+        full_match_patterns, BasinConfig, CostConf, DbtConf, FlowImagesConf, FlowsConf, SparkConf,
+        SparkRunner, SqlConf, StepFunctionsFlowConf, StorageConf,
+    },
+    constants::DESCRIPTOR_HASH_KEY,
+    deployment_state_store::{
+        DeploymentHistory, DeploymentInfo, DeploymentState, DeploymentStateStore,
+        RedisDeploymentStateStore,
+    },
     descriptor_store::{DescriptorStore, RedisDescriptorStore},
-    fluid::descriptor::flow::{FlowCondition, FlowDescriptor, FlowStepTransformation},
-    provisioner::waterwheel::{
-        WaterwheelDockerTask, WaterwheelJob, WaterwheelTask, WaterwheelTrigger,
+    fluid::descriptor::{
+        database::DatabaseDescriptor,
+        descriptor_hash,
+        flow::{
+            FlowBackend, FlowCondition, FlowConditionMode, FlowDbtTransformation, FlowDescriptor,
+            FlowResourceQuantities, FlowSparkTransformation, FlowStep, FlowStepTransformation,
+            FlowUpstreamKind,
+        },
+        table::TableDescriptor,
+        DescriptorKind, StorageEngine,
+    },
+    provisioner::{
+        datahub::{DataHubFlow, DataHubJob, DataHubProvisioner},
+        glue_workflow::{GlueJobSpec, GlueTriggerKind, GlueTriggerSpec, GlueWorkflowProvisioner},
+        s3::split_s3_uri,
+        step_functions::{
+            Branch, ParallelState, Retrier, State, StateMachineDefinition,
+            StepFunctionsProvisioner, TaskState, Transition, GLUE_START_JOB_RUN_SYNC,
+        },
+        waterwheel::{
+            external_task_ref, WaterwheelClient, WaterwheelDockerTask, WaterwheelJob,
+            WaterwheelProvisioner, WaterwheelResourceList, WaterwheelResources, WaterwheelTask,
+            WaterwheelTrigger, TRIGGER_DATETIME_PLACEHOLDER,
+        },
     },
+    sql_validation::{parse_sql, referenced_tables},
+    state_events::{StateEventPublisher, StateEventType},
+    templating::{TemplateContext, TemplateReferences, TRIGGER_DATE_PLACEHOLDER},
 };
 
-use anyhow::{anyhow, bail, Result};
-use serde::Serialize;
-use tracing::{debug, error, info};
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use tracing::{debug, error, info, warn};
 
 const PRIMORDIAL_TIME: &str = "2000-01-01T00:00:00Z";
+// Profile and target name of the dbt profile generated for dbt steps
+const DBT_TARGET: &str = "basin";
+const DEFAULT_DBT_THREADS: u32 = 4;
 
-#[derive(Serialize, Debug)]
-struct WaterwheelCreds {
-    username: String,
-    password: String,
-}
+// Bounds on what a single step may ask for, anything outside these is almost certainly a typo
+const MIN_STEP_CPU_MILLIS: u64 = 100;
+const MAX_STEP_CPU_MILLIS: u64 = 16_000;
+const MIN_STEP_MEMORY_BYTES: u64 = 64 * 1024 * 1024;
+const MAX_STEP_MEMORY_BYTES: u64 = 64 * 1024 * 1024 * 1024;
+const MAX_STEP_RETRIES: u32 = 10;
+const MAX_STEP_RETRY_DELAY_SECS: u64 = 60 * 60;
 
 pub struct FlowController {
+    cost: CostConf,
+    sql: SqlConf,
+    images: FlowImagesConf,
+    // Compiled once from flow_images.allowed
+    allowed_images: Vec<Regex>,
+    dbt: DbtConf,
+    spark: SparkConf,
+    flows: FlowsConf,
+    storage: StorageConf,
+    aws_region: Option<String>,
     descriptor_store: RedisDescriptorStore,
-    waterwheel_creds: WaterwheelCreds,
+    deployment_state_store: RedisDeploymentStateStore,
+    backfill_store: RedisBackfillStore,
     waterwheel_project: String,
-    waterwheel_url: String,
-    http_client: reqwest::Client,
+    waterwheel: Box<dyn WaterwheelClient>,
+    glue: GlueWorkflowProvisioner,
+    step_functions: StepFunctionsProvisioner,
+    state_event_publisher: StateEventPublisher,
+    datahub_provisioner: Option<DataHubProvisioner>,
 }
 
 // TODO: support different deployment targets (i.e. airflow)
 #[async_trait::async_trait]
 impl BaseController<FlowDescriptor> for FlowController {
-    async fn validate(&self, descriptor: &FlowDescriptor) -> Result<()> {
+    async fn validate(&self, raw_descriptor: &FlowDescriptor) -> Result<()> {
+        let templates = self.template_context(raw_descriptor).await?;
+        let descriptor = &self.render_descriptor(raw_descriptor, &templates)?;
+        let backend = self.backend(descriptor);
+        for step in descriptor.steps.iter() {
+            Self::validate_step_policy(step)
+                .with_context(|| format!("step '{}' is invalid", step.name))?;
+            if let Some(image) = &step.image {
+                self.validate_step_image(image)
+                    .with_context(|| format!("step '{}' uses a disallowed image", step.name))?;
+            }
+
+            match &step.transformation {
+                FlowStepTransformation::Sql(t) => {
+                    let statements = parse_sql(&t.sql, self.sql.dialect)
+                        .with_context(|| format!("step '{}' has invalid sql", step.name))?;
+                    if self.sql.extract_lineage {
+                        info!(
+                            step = step.name,
+                            tables = ?referenced_tables(&statements),
+                            "extracted sql step lineage"
+                        );
+                    }
+                }
+                // Runner settings only apply to waterwheel, glue rejects what it can't run itself
+                FlowStepTransformation::Dbt(_) if backend == FlowBackend::Waterwheel => {
+                    ensure!(
+                        step.image.is_some() || self.dbt.runner_image.is_some(),
+                        "step '{}' is a dbt step but no dbt runner image is configured",
+                        step.name
+                    );
+                    ensure!(
+                        self.dbt.s3_staging_dir.is_some(),
+                        "step '{}' is a dbt step but no athena staging dir is configured",
+                        step.name
+                    );
+                }
+                FlowStepTransformation::Spark(t) if backend == FlowBackend::Waterwheel => {
+                    self.validate_spark_step(t, step.image.is_some())
+                        .with_context(|| {
+                            format!("step '{}' is an invalid spark step", step.name)
+                        })?;
+                }
+                FlowStepTransformation::Dbt(_) | FlowStepTransformation::Spark(_) => {}
+            }
+        }
+
+        ensure!(
+            descriptor.all_conditions().next().is_some(),
+            "flow has no conditions"
+        );
+        ensure!(
+            descriptor.condition_mode == FlowConditionMode::Any
+                || descriptor.upstream_tables().next().is_none(),
+            "flows with upstream tables can only start on any of their conditions"
+        );
+
+        match backend {
+            FlowBackend::Glue => {
+                self.build_glue_flow(descriptor)?;
+                return Ok(());
+            }
+            FlowBackend::StepFunctions => {
+                self.build_state_machine(descriptor)?;
+                return Ok(());
+            }
+            FlowBackend::Waterwheel => {}
+        }
+
         // NOTE: actual validation is handled downstream, this checks what we support generating specs for
-        self.build_waterwheel_job_spec(descriptor)?;
+        //       upstream flows may not have arrived yet so they aren't resolved here
+        if let Some(database) = Self::missing_dbt_database(descriptor, &templates) {
+            debug!(
+                database,
+                "dbt database hasn't arrived yet, skipping job spec check"
+            );
+            return Ok(());
+        }
+        self.build_waterwheel_job_spec(
+            descriptor,
+            &descriptor_hash(raw_descriptor),
+            &templates,
+            &[],
+            &[],
+        )?;
         Ok(())
     }
 
@@ -43,121 +191,1511 @@ impl BaseController<FlowDescriptor> for FlowController {
     async fn reconcile(&self, descriptor: &FlowDescriptor) -> Result<()> {
         info!("Performing reconciliation for flow");
 
-        let job_spec = self
-            .build_waterwheel_job_spec(descriptor)
+        let mut backfills = self.backfill_store.list_backfills(&descriptor.id).await?;
+        if self.backend(descriptor) == FlowBackend::Waterwheel {
+            backfills = self.track_backfills(descriptor, backfills).await?;
+        }
+        let result = match self.build_spec(descriptor, &backfills).await? {
+            FlowSpec::Waterwheel { job } => {
+                self.reconcile_waterwheel(descriptor, &job, backfills).await
+            }
+            FlowSpec::Glue {
+                workflow_name,
+                description,
+                jobs,
+                triggers,
+            } => {
+                if !backfills.is_empty() {
+                    warn!("backfills aren't supported on the glue backend, ignoring them");
+                }
+                self.reconcile_glue(descriptor, &workflow_name, &description, &jobs, &triggers)
+                    .await
+            }
+            FlowSpec::StepFunctions {
+                state_machine_name,
+                jobs,
+                definition,
+                schedules,
+            } => {
+                if !backfills.is_empty() {
+                    warn!(
+                        "backfills aren't supported on the step_functions backend, ignoring them"
+                    );
+                }
+                self.reconcile_step_functions(
+                    descriptor,
+                    &state_machine_name,
+                    &jobs,
+                    &definition,
+                    &schedules,
+                )
+                .await
+            }
+        };
+        result?;
+        self.push_datahub_flow(descriptor).await;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "info", name = "flow_teardown", skip(self, descriptor), fields(descriptor_id = %descriptor.id))]
+    async fn teardown(&self, descriptor: &FlowDescriptor) -> Result<()> {
+        info!("Tearing down flow");
+
+        let result = match self.backend(descriptor) {
+            FlowBackend::Waterwheel => self.teardown_waterwheel(descriptor).await,
+            FlowBackend::Glue => self.teardown_glue(descriptor).await,
+            FlowBackend::StepFunctions => self.teardown_step_functions(descriptor).await,
+        };
+        result
+            .inspect_err(|e| error!(?e, "Resource teardown failed"))
+            .map_err(|e| ControllerReconciliationError::provisioner(e.into()))?;
+
+        info!("Finished resource teardown");
+        Ok(())
+    }
+}
+
+impl FlowController {
+    pub async fn new(conf: &BasinConfig) -> Result<Self> {
+        Ok(FlowController {
+            cost: conf.cost.clone(),
+            sql: conf.sql.clone(),
+            images: conf.flow_images.clone(),
+            allowed_images: full_match_patterns(&conf.flow_images.allowed)?,
+            dbt: conf.dbt.clone(),
+            spark: conf.spark.clone(),
+            flows: conf.flows.clone(),
+            storage: conf.storage.clone(),
+            aws_region: conf.aws_creds.region().map(|r| r.to_string()),
+            descriptor_store: RedisDescriptorStore::new(&conf.redis).await?,
+            deployment_state_store: RedisDeploymentStateStore::new(&conf.redis).await?,
+            backfill_store: RedisBackfillStore::new(&conf.redis).await?,
+            waterwheel_project: conf.waterwheel.project.clone(),
+            waterwheel: Box::new(WaterwheelProvisioner::new(&conf.waterwheel)?),
+            glue: GlueWorkflowProvisioner::new(&conf.aws_creds),
+            step_functions: StepFunctionsProvisioner::new(&conf.aws_creds),
+            state_event_publisher: StateEventPublisher::new(conf),
+            datahub_provisioner: conf
+                .datahub
+                .as_ref()
+                .map(DataHubProvisioner::new)
+                .transpose()?,
+        })
+    }
+
+    fn validate_step_policy(step: &FlowStep) -> Result<()> {
+        if let Some(resources) = &step.resources {
+            for quantities in [&resources.requests, &resources.limits]
+                .into_iter()
+                .flatten()
+            {
+                if let Some(cpu) = &quantities.cpu {
+                    let millis = parse_cpu_millis(cpu)?;
+                    ensure!(
+                        (MIN_STEP_CPU_MILLIS..=MAX_STEP_CPU_MILLIS).contains(&millis),
+                        "cpu '{}' must be between {}m and {}m",
+                        cpu,
+                        MIN_STEP_CPU_MILLIS,
+                        MAX_STEP_CPU_MILLIS
+                    );
+                }
+                if let Some(memory) = &quantities.memory {
+                    let bytes = parse_memory_bytes(memory)?;
+                    ensure!(
+                        (MIN_STEP_MEMORY_BYTES..=MAX_STEP_MEMORY_BYTES).contains(&bytes),
+                        "memory '{}' must be between {}Mi and {}Gi",
+                        memory,
+                        MIN_STEP_MEMORY_BYTES / (1024 * 1024),
+                        MAX_STEP_MEMORY_BYTES / (1024 * 1024 * 1024)
+                    );
+                }
+            }
+
+            // A request above its limit would never be scheduled
+            if let (Some(requests), Some(limits)) = (&resources.requests, &resources.limits) {
+                if let (Some(req), Some(lim)) = (&requests.cpu, &limits.cpu) {
+                    ensure!(
+                        parse_cpu_millis(req)? <= parse_cpu_millis(lim)?,
+                        "cpu request '{}' exceeds limit '{}'",
+                        req,
+                        lim
+                    );
+                }
+                if let (Some(req), Some(lim)) = (&requests.memory, &limits.memory) {
+                    ensure!(
+                        parse_memory_bytes(req)? <= parse_memory_bytes(lim)?,
+                        "memory request '{}' exceeds limit '{}'",
+                        req,
+                        lim
+                    );
+                }
+            }
+        }
+
+        if let Some(retries) = step.retries {
+            ensure!(
+                retries <= MAX_STEP_RETRIES,
+                "retries must be at most {}",
+                MAX_STEP_RETRIES
+            );
+        }
+        if let Some(retry_delay) = &step.retry_delay {
+            ensure!(step.retries.is_some(), "retry_delay is set without retries");
+            let secs = parse_duration_secs(retry_delay)?;
+            ensure!(
+                secs <= MAX_STEP_RETRY_DELAY_SECS,
+                "retry_delay '{}' must be at most {}s",
+                retry_delay,
+                MAX_STEP_RETRY_DELAY_SECS
+            );
+        }
+
+        Ok(())
+    }
+
+    fn validate_step_image(&self, image: &str) -> Result<()> {
+        let (repository, digest) = match image.split_once('@') {
+            Some((name, digest)) => (name, Some(digest)),
+            None => (image, None),
+        };
+        // NOTE: a tag is whatever follows the last `:` after the last `/`, anything before that
+        //       is a registry port
+        let repository = match repository.rsplit_once(':') {
+            Some((name, tag)) if !tag.contains('/') => name,
+            _ => repository,
+        };
+
+        let allowed = self
+            .allowed_images
+            .iter()
+            .any(|pattern| pattern.is_match(repository));
+        ensure!(
+            allowed,
+            "image repository '{}' is not allowed. Must match one of '{:?}'",
+            repository,
+            self.images.allowed
+        );
+
+        if self.images.require_digest {
+            let pinned = digest
+                .and_then(|d| d.strip_prefix("sha256:"))
+                .map_or(false, |hex| {
+                    hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit())
+                });
+            ensure!(
+                pinned,
+                "image '{}' must be pinned to a digest (`@sha256:...`)",
+                image
+            );
+        }
+
+        Ok(())
+    }
+
+    fn backend(&self, descriptor: &FlowDescriptor) -> FlowBackend {
+        descriptor.backend.unwrap_or(self.flows.default_backend)
+    }
+
+    // Renders the descriptor and generates what it deploys as on its backend
+    pub(super) async fn build_spec(
+        &self,
+        descriptor: &FlowDescriptor,
+        backfills: &[BackfillRecord],
+    ) -> Result<FlowSpec> {
+        let templates = self.template_context(descriptor).await?;
+        let rendered = self
+            .render_descriptor(descriptor, &templates)
             .map_err(|e| ControllerReconciliationError::ControllerError(e.into()))?;
+        if let Some(database) = Self::missing_dbt_database(&rendered, &templates) {
+            return Err(ControllerReconciliationError::DependencyMissing(database).into());
+        }
+
+        let spec = match self.backend(descriptor) {
+            FlowBackend::Waterwheel => {
+                let upstream_refs = self.resolve_upstream_refs(&rendered).await?;
+                let job = self
+                    .build_waterwheel_job_spec(
+                        &rendered,
+                        &descriptor_hash(descriptor),
+                        &templates,
+                        &upstream_refs,
+                        backfills,
+                    )
+                    .map_err(|e| ControllerReconciliationError::ControllerError(e.into()))?;
+                FlowSpec::Waterwheel { job }
+            }
+            FlowBackend::Glue => {
+                let (jobs, triggers) = self
+                    .build_glue_flow(&rendered)
+                    .map_err(|e| ControllerReconciliationError::ControllerError(e.into()))?;
+                FlowSpec::Glue {
+                    workflow_name: glue_workflow_name(&rendered),
+                    description: rendered.summary.clone(),
+                    jobs,
+                    triggers,
+                }
+            }
+            FlowBackend::StepFunctions => {
+                let (jobs, definition, schedules) = self
+                    .build_state_machine(&rendered)
+                    .map_err(|e| ControllerReconciliationError::ControllerError(e.into()))?;
+                FlowSpec::StepFunctions {
+                    state_machine_name: state_machine_name(&rendered),
+                    jobs,
+                    definition,
+                    schedules,
+                }
+            }
+        };
+        Ok(spec)
+    }
+
+    // What the descriptor would deploy as if it were reconciled now, nothing is provisioned
+    pub async fn generate_spec(&self, descriptor: &FlowDescriptor) -> Result<FlowSpec> {
+        let backfills = self.backfill_store.list_backfills(&descriptor.id).await?;
+        self.build_spec(descriptor, &backfills).await
+    }
+
+    async fn reconcile_waterwheel(
+        &self,
+        descriptor: &FlowDescriptor,
+        job_spec: &WaterwheelJob,
+        backfills: Vec<BackfillRecord>,
+    ) -> Result<()> {
         info!(
             id = job_spec.uuid,
             "Sending job specification to waterwheel"
         );
         debug!("job_spec: {:?}", job_spec);
 
-        info!(id = job_spec.uuid, "Logging in to waterwheel");
-        let login_resp = self.http_client
-            .post(format!("{}/login", self.waterwheel_url))
-            .form(&self.waterwheel_creds)
-            .send()
+        // NOTE: waterwheel only hears from basin when a job is submitted, so check what it's
+        //       actually running rather than assuming the last submission stuck. Waterwheel fills
+        //       in defaults of its own, the hash the job was submitted with is compared instead.
+        let live_job = self
+            .waterwheel
+            .get_job(&job_spec.uuid)
             .await
-            .map_err(|e| ControllerReconciliationError::ProvisionerError(e.into()))?;
-        
-        let login_status = login_resp.status();
-        if !login_status.is_success() {
-            error!(
-                status = login_status.as_u16(),
-                "error logging into waterwheel"
-            );
-            return Err(ControllerReconciliationError::ProvisionerError(anyhow!(
-                "error logging into waterwheel"
-            )).into());
-        }
-
-        // FIXME: do this once globally and only resignin on expiry
-        let cookie = login_resp.headers()
-            .get("set-cookie")
-            .ok_or(ControllerReconciliationError::ProvisionerError(anyhow!(
-                "error getting cookie from waterwheel",
-            )))?
-            .to_str()?;
-
-        let resp = self
-            .http_client
-            .post(format!("{}/api/jobs", self.waterwheel_url))
-            .header("cookie", cookie)
-            .json(&job_spec)
-            .send()
+            .map_err(ControllerReconciliationError::provisioner)?;
+        let live_hash = live_job
+            .as_ref()
+            .and_then(|job| submitted_hash(&job.description));
+        if live_hash.is_some() && live_hash == submitted_hash(&job_spec.description) {
+            info!("Waterwheel job matches the descriptor, skipping submission");
+        } else {
+            self.record_waterwheel_drift(descriptor, live_job.is_some())
+                .await;
+            self.waterwheel
+                .put_job(job_spec)
+                .await
+                .map_err(ControllerReconciliationError::provisioner)?;
+        }
+
+        for backfill in backfills
+            .into_iter()
+            .filter(|b| b.status == BackfillStatus::Requested)
+        {
+            info!(backfill_id = backfill.backfill_id, "Submitted backfill");
+            self.backfill_store
+                .put_backfill(&BackfillRecord {
+                    status: BackfillStatus::Submitted,
+                    submitted_at: Some(Utc::now()),
+                    ..backfill
+                })
+                .await?;
+        }
+        Ok(())
+    }
+
+    // Counts the finished runs of each submitted backfill, those with every run finished are
+    // completed and left out of the job from then on
+    async fn track_backfills(
+        &self,
+        descriptor: &FlowDescriptor,
+        backfills: Vec<BackfillRecord>,
+    ) -> Result<Vec<BackfillRecord>> {
+        if !backfills
+            .iter()
+            .any(|b| b.status == BackfillStatus::Submitted)
+        {
+            return Ok(backfills);
+        }
+
+        let runs = self
+            .waterwheel
+            .get_job_runs(&descriptor.id)
             .await
-            .map_err(|e| ControllerReconciliationError::ProvisionerError(e.into()))?;
+            .map_err(ControllerReconciliationError::provisioner)?;
 
-        let status = resp.status();
-        if !status.is_success() {
-            let resp_msg = resp
-                .text()
-                .await
-                .map_err(|e| ControllerReconciliationError::ProvisionerError(e.into()))?;
-            error!(
-                status = status.as_u16(),
-                resp_msg, "error when submitting job to waterwheel",
-            );
-            return Err(ControllerReconciliationError::ProvisionerError(anyhow!(
-                "error when submitting job to waterwheel"
+        let mut tracked = Vec::with_capacity(backfills.len());
+        for backfill in backfills {
+            if backfill.status != BackfillStatus::Submitted {
+                tracked.push(backfill);
+                continue;
+            }
+
+            // NOTE: waterwheel keeps one run per trigger time, the flow's own schedule may have
+            //       run some of the range already
+            let finished_runs = runs
+                .iter()
+                .filter(|r| r.is_finished())
+                .filter(|r| {
+                    r.trigger_datetime >= backfill.start && r.trigger_datetime <= backfill.end
+                })
+                .map(|r| r.trigger_datetime.timestamp())
+                .collect::<BTreeSet<_>>()
+                .len();
+            // Backfills whose runs couldn't be counted are left to run out rather than guessed at
+            let completed = backfill
+                .expected_runs
+                .is_some_and(|expected| finished_runs >= expected);
+            if finished_runs == backfill.finished_runs && !completed {
+                tracked.push(backfill);
+                continue;
+            }
+
+            let updated = if completed {
+                info!(backfill_id = backfill.backfill_id, "Completed backfill");
+                BackfillRecord {
+                    status: BackfillStatus::Completed,
+                    finished_runs,
+                    completed_at: Some(Utc::now()),
+                    ..backfill
+                }
+            } else {
+                BackfillRecord {
+                    finished_runs,
+                    ..backfill
+                }
+            };
+            self.backfill_store.put_backfill(&updated).await?;
+            tracked.push(updated);
+        }
+        Ok(tracked)
+    }
+
+    // A flow that last deployed cleanly but no longer matches waterwheel was changed outside
+    // basin. The note is written as its own transition, the reconcile's outcome follows it.
+    async fn record_waterwheel_drift(&self, descriptor: &FlowDescriptor, exists: bool) {
+        let prior = match self.deployment_state_store.get_state(&descriptor.id).await {
+            Ok(Some(t)) if t.state == DeploymentState::Succeeded => t,
+            Ok(_) => return,
+            Err(e) => {
+                warn!(?e, "could not fetch deployment state");
+                return;
+            }
+        };
+
+        let description = if exists {
+            "job was externally modified in waterwheel, restoring it"
+        } else {
+            "job was externally deleted from waterwheel, restoring it"
+        };
+        warn!(description);
+        let info = DeploymentInfo {
+            state: DeploymentState::Deploying,
+            description: Some(description.to_string()),
+            history: DeploymentHistory::default(),
+            ..prior
+        };
+        if let Err(e) = self
+            .deployment_state_store
+            .set_state(&descriptor.id, &info)
+            .await
+        {
+            warn!(?e, "failed to record waterwheel drift");
+        }
+        self.state_event_publisher
+            .publish(
+                StateEventType::DriftDetected,
+                DescriptorKind::Flow,
+                &descriptor.id,
+                descriptor.owner.as_ref(),
+                &info,
+            )
+            .await;
+    }
+
+    async fn reconcile_glue(
+        &self,
+        descriptor: &FlowDescriptor,
+        workflow_name: &str,
+        description: &str,
+        jobs: &[GlueJobSpec],
+        triggers: &[GlueTriggerSpec],
+    ) -> Result<()> {
+        info!(workflow_name, "Provisioning glue workflow");
+        self.provision_glue_flow(
+            workflow_name,
+            description,
+            &self.cost_tags(descriptor),
+            jobs,
+            triggers,
+        )
+        .await
+        .inspect_err(|e| error!(?e, "Resource reconciliation failed"))
+        .map_err(|e| ControllerReconciliationError::provisioner(e.into()))?;
+
+        info!("Provisioned glue workflow");
+        Ok(())
+    }
+
+    async fn provision_glue_flow(
+        &self,
+        workflow_name: &str,
+        description: &str,
+        tags: &BTreeMap<String, String>,
+        jobs: &[GlueJobSpec],
+        triggers: &[GlueTriggerSpec],
+    ) -> Result<()> {
+        let existing = self.glue.workflow_members(workflow_name).await?;
+
+        self.glue
+            .put_workflow(workflow_name, description, tags)
+            .await?;
+        // NOTE: jobs have to exist before a trigger can start them
+        for job in jobs {
+            self.glue.put_job(job).await?;
+        }
+        for trigger in triggers {
+            self.glue.put_trigger(trigger).await?;
+        }
+
+        // Steps removed from the flow, triggers go first so nothing starts a job mid delete
+        for trigger in existing.triggers {
+            if !triggers.iter().any(|t| t.name == trigger) {
+                info!(trigger, "Removing stale glue trigger");
+                self.glue.delete_trigger(&trigger).await?;
+            }
+        }
+        for job in existing.jobs {
+            if !jobs.iter().any(|j| j.name == job) {
+                info!(job, "Removing stale glue job");
+                self.glue.delete_job(&job).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // A glue job per step, chained by conditional triggers on each step's parents. The first
+    // steps hang off a single scheduled trigger, so flows need exactly one cron condition.
+    fn build_glue_flow(
+        &self,
+        descriptor: &FlowDescriptor,
+    ) -> Result<(Vec<GlueJobSpec>, Vec<GlueTriggerSpec>)> {
+        let mut schedules = vec![];
+        for condition in descriptor.all_conditions() {
+            match condition {
+                FlowCondition::Cron(c) => schedules.push(aws_cron_schedule(&c.schedule)?),
+                // Started through start_run when data lands
+                FlowCondition::Upstream(u) if u.kind == FlowUpstreamKind::Table => {}
+                FlowCondition::Upstream(_) => {
+                    bail!("upstream conditions aren't supported on the glue backend")
+                }
+            }
+        }
+        let [schedule] = schedules.as_slice() else {
+            bail!("flows on the glue backend need exactly one cron condition");
+        };
+
+        let workflow_name = glue_workflow_name(descriptor);
+        let mut jobs = vec![];
+        let mut triggers = vec![];
+        let mut root_jobs = vec![];
+        for step in descriptor.steps.iter() {
+            let job = self.glue_job_spec(descriptor, step)?;
+            let job_name = job.name.clone();
+            jobs.push(job);
+
+            if step.parents.is_empty() {
+                root_jobs.push(job_name);
+            } else {
+                triggers.push(GlueTriggerSpec {
+                    name: glue_trigger_name(descriptor, Some(&step.name)),
+                    workflow_name: workflow_name.clone(),
+                    kind: GlueTriggerKind::Conditional(
+                        step.parents
+                            .iter()
+                            .map(|p| glue_job_name(descriptor, p))
+                            .collect(),
+                    ),
+                    jobs: vec![job_name],
+                });
+            }
+        }
+
+        triggers.insert(
+            0,
+            GlueTriggerSpec {
+                name: glue_trigger_name(descriptor, None),
+                workflow_name,
+                kind: GlueTriggerKind::Scheduled(schedule.clone()),
+                jobs: root_jobs,
+            },
+        );
+
+        Ok((jobs, triggers))
+    }
+
+    async fn reconcile_step_functions(
+        &self,
+        descriptor: &FlowDescriptor,
+        name: &str,
+        jobs: &[GlueJobSpec],
+        definition: &StateMachineDefinition,
+        schedules: &[(String, String)],
+    ) -> Result<()> {
+        let conf = self.flows.step_functions.as_ref().ok_or_else(|| {
+            ControllerReconciliationError::ControllerError(anyhow!(
+                "the step_functions flow backend isn't configured"
             ))
-            .into());
+        })?;
+
+        info!(name, "Provisioning state machine");
+        self.provision_state_machine(descriptor, conf, jobs, definition, schedules)
+            .await
+            .inspect_err(|e| error!(?e, "Resource reconciliation failed"))
+            .map_err(|e| ControllerReconciliationError::provisioner(e.into()))?;
+
+        info!("Provisioned state machine");
+        Ok(())
+    }
+
+    async fn provision_state_machine(
+        &self,
+        descriptor: &FlowDescriptor,
+        conf: &StepFunctionsFlowConf,
+        jobs: &[GlueJobSpec],
+        definition: &StateMachineDefinition,
+        schedules: &[(String, String)],
+    ) -> Result<()> {
+        let name = state_machine_name(descriptor);
+        let existing_jobs = self.step_functions.state_machine_jobs(&name).await?;
+        for job in jobs {
+            self.glue.put_job(job).await?;
+        }
+
+        let arn = self
+            .step_functions
+            .put_state_machine(
+                &name,
+                definition,
+                &conf.role_arn,
+                &self.cost_tags(descriptor),
+            )
+            .await?;
+        for (rule, schedule) in schedules {
+            self.step_functions
+                .put_schedule_rule(rule, schedule, &arn, &conf.events_role_arn)
+                .await?;
+        }
+
+        // Rules of cron conditions that have since been removed
+        for rule in self.schedule_rules(descriptor).await? {
+            if !schedules.iter().any(|(r, _)| *r == rule) {
+                info!(rule, "Removing stale schedule rule");
+                self.step_functions.delete_schedule_rule(&rule).await?;
+            }
+        }
+
+        // Jobs of steps removed from the flow, only once the state machine no longer runs them
+        for job in existing_jobs {
+            if !jobs.iter().any(|j| j.name == job) {
+                info!(job, "Removing stale glue job");
+                self.glue.delete_job(&job).await?;
+            }
         }
 
-        info!("Submitted job to waterwheel");
         Ok(())
     }
 
-    async fn list_descriptors(&self) -> Result<Vec<FlowDescriptor>> {
+    // Schedule rules currently starting the flow's state machine
+    async fn schedule_rules(&self, descriptor: &FlowDescriptor) -> Result<Vec<String>> {
+        let own_rule = Regex::new(&format!(
+            "^{}(_[0-9]+)?$",
+            regex::escape(&schedule_rule_name(descriptor, 0))
+        ))?;
         Ok(self
-            .descriptor_store
-            .list_descriptors::<FlowDescriptor>("flow")
-            .await?)
+            .step_functions
+            .list_schedule_rules(&schedule_rule_name(descriptor, 0))
+            .await?
+            .into_iter()
+            .filter(|rule| own_rule.is_match(rule))
+            .collect())
     }
-}
 
-impl FlowController {
-    pub async fn new(conf: &BasinConfig) -> Result<Self> {
-        Ok(FlowController {
-            descriptor_store: RedisDescriptorStore::new(&conf.redis_url).await?,
-            waterwheel_creds: WaterwheelCreds {
-                username: conf.waterwheel_username.clone(),
-                password: conf.waterwheel_password.clone(),
-            },
-            waterwheel_project: conf.waterwheel_project.clone(),
-            waterwheel_url: conf.waterwheel_url.clone(),
-            http_client: reqwest::Client::new(),
+    // Runs the flow once outside its schedule, for data arriving in a table it's waiting on
+    #[tracing::instrument(level = "info", skip(self, descriptor), fields(descriptor_id = %descriptor.id))]
+    pub async fn start_run(
+        &self,
+        descriptor: &FlowDescriptor,
+        trigger_datetime: DateTime<Utc>,
+    ) -> Result<()> {
+        match self.backend(descriptor) {
+            FlowBackend::Waterwheel => {
+                self.start_waterwheel_run(descriptor, trigger_datetime)
+                    .await
+            }
+            FlowBackend::Glue => {
+                self.glue
+                    .start_workflow_run(&glue_workflow_name(descriptor))
+                    .await
+            }
+            FlowBackend::StepFunctions => {
+                self.step_functions
+                    .start_execution(&state_machine_name(descriptor))
+                    .await
+            }
+        }
+    }
+
+    // Activates the tokens of the job's first tasks for the trigger time, waterwheel runs the
+    // rest of the job from there as it would for a cron trigger
+    async fn start_waterwheel_run(
+        &self,
+        descriptor: &FlowDescriptor,
+        trigger_datetime: DateTime<Utc>,
+    ) -> Result<()> {
+        // NOTE: data events can be delivered more than once, a run for the time already exists
+        //       then and activating the tokens again would run the job twice
+        if self
+            .waterwheel
+            .get_job_runs(&descriptor.id)
+            .await?
+            .iter()
+            .any(|r| r.trigger_datetime.timestamp() == trigger_datetime.timestamp())
+        {
+            info!(%trigger_datetime, "Waterwheel already has a run for the trigger time");
+            return Ok(());
+        }
+
+        for step in descriptor.steps.iter().filter(|s| s.parents.is_empty()) {
+            self.waterwheel
+                .activate_token(&descriptor.id, &step.name, trigger_datetime)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn teardown_waterwheel(&self, descriptor: &FlowDescriptor) -> Result<()> {
+        // NOTE: paused first so a job whose delete fails doesn't keep running while the
+        //       teardown is retried
+        self.waterwheel.set_paused(&descriptor.id, true).await?;
+        self.waterwheel.delete_job(&descriptor.id).await
+    }
+
+    async fn teardown_glue(&self, descriptor: &FlowDescriptor) -> Result<()> {
+        let workflow_name = glue_workflow_name(descriptor);
+        let members = self.glue.workflow_members(&workflow_name).await?;
+
+        // NOTE: triggers first so nothing starts a job mid delete
+        for trigger in members.triggers {
+            self.glue.delete_trigger(&trigger).await?;
+        }
+        for job in members.jobs {
+            self.glue.delete_job(&job).await?;
+        }
+        self.glue.delete_workflow(&workflow_name).await
+    }
+
+    async fn teardown_step_functions(&self, descriptor: &FlowDescriptor) -> Result<()> {
+        for rule in self.schedule_rules(descriptor).await? {
+            self.step_functions.delete_schedule_rule(&rule).await?;
+        }
+        self.step_functions
+            .delete_state_machine(&state_machine_name(descriptor))
+            .await?;
+        // NOTE: glue doesn't complain about deleting jobs that don't exist
+        for step in descriptor.steps.iter() {
+            self.glue
+                .delete_job(&glue_job_name(descriptor, &step.name))
+                .await?;
+        }
+        Ok(())
+    }
+
+    // A glue job per step, run by a state machine that eventbridge starts for each cron condition.
+    // Steps are grouped into layers by their depth in the step graph and each layer runs its
+    // steps in parallel once the previous one has finished.
+    // NOTE: a step can end up waiting on more than its parents, an arbitrary dag doesn't map onto
+    //       nested parallel states without running steps twice
+    fn build_state_machine(
+        &self,
+        descriptor: &FlowDescriptor,
+    ) -> Result<(
+        Vec<GlueJobSpec>,
+        StateMachineDefinition,
+        Vec<(String, String)>,
+    )> {
+        ensure!(
+            self.flows.step_functions.is_some(),
+            "the step_functions flow backend isn't configured"
+        );
+
+        let mut schedules = vec![];
+        for condition in descriptor.all_conditions() {
+            match condition {
+                FlowCondition::Cron(c) => schedules.push((
+                    schedule_rule_name(descriptor, schedules.len()),
+                    aws_cron_schedule(&c.schedule)?,
+                )),
+                // Started through start_run when data lands
+                FlowCondition::Upstream(u) if u.kind == FlowUpstreamKind::Table => {}
+                FlowCondition::Upstream(_) => {
+                    bail!("upstream conditions aren't supported on the step_functions backend")
+                }
+            }
+        }
+        ensure!(
+            descriptor.condition_mode == FlowConditionMode::Any || schedules.len() <= 1,
+            "the step_functions backend can only start flows on any of their conditions"
+        );
+
+        let mut jobs = vec![];
+        let mut layers: Vec<Vec<(String, State)>> = vec![];
+        for (step, depth) in step_depths(&descriptor.steps)? {
+            let job = GlueJobSpec {
+                // Retries are handled by the state machine so they show up in its history
+                max_retries: None,
+                ..self.glue_job_spec(descriptor, step)?
+            };
+
+            let retry = match step.retries {
+                Some(retries) => vec![Retrier {
+                    error_equals: vec!["States.ALL".to_string()],
+                    max_attempts: retries,
+                    interval_seconds: step
+                        .retry_delay
+                        .as_deref()
+                        .map(parse_duration_secs)
+                        .transpose()?,
+                }],
+                None => vec![],
+            };
+            let task = TaskState {
+                resource: GLUE_START_JOB_RUN_SYNC.to_string(),
+                parameters: json!({ "JobName": job.name }),
+                timeout_seconds: Some(parse_duration_secs(&step.timeout)?),
+                retry,
+                transition: Transition::End(true),
+            };
+            jobs.push(job);
+
+            if layers.len() <= depth {
+                layers.resize_with(depth + 1, Vec::new);
+            }
+            layers[depth].push((step.name.clone(), State::Task(task)));
+        }
+
+        let layer_names: Vec<String> = layers
+            .iter()
+            .enumerate()
+            .map(|(i, layer)| match layer.as_slice() {
+                [(name, _)] => name.clone(),
+                _ => format!("layer_{}", i),
+            })
+            .collect();
+        let mut states = BTreeMap::new();
+        for (i, mut layer) in layers.into_iter().enumerate() {
+            let transition = match layer_names.get(i + 1) {
+                Some(next) => Transition::Next(next.clone()),
+                None => Transition::End(true),
+            };
+
+            let state = if layer.len() == 1 {
+                let (_, State::Task(mut task)) = layer.remove(0) else {
+                    unreachable!("layers only hold task states");
+                };
+                task.transition = transition;
+                State::Task(task)
+            } else {
+                State::Parallel(ParallelState {
+                    branches: layer
+                        .into_iter()
+                        .map(|(name, state)| Branch {
+                            start_at: name.clone(),
+                            states: BTreeMap::from([(name, state)]),
+                        })
+                        .collect(),
+                    transition,
+                })
+            };
+            states.insert(layer_names[i].clone(), state);
+        }
+
+        let definition = StateMachineDefinition {
+            comment: descriptor.summary.clone(),
+            start_at: layer_names
+                .first()
+                .cloned()
+                .ok_or_else(|| anyhow!("flow has no steps"))?,
+            states,
+        };
+
+        Ok((jobs, definition, schedules))
+    }
+
+    // Glue job running a single step, shared by the backends that run steps on glue
+    fn glue_job_spec(&self, descriptor: &FlowDescriptor, step: &FlowStep) -> Result<GlueJobSpec> {
+        let glue = self
+            .flows
+            .glue
+            .as_ref()
+            .ok_or_else(|| anyhow!("running steps on glue isn't configured"))?;
+
+        let mut default_arguments = HashMap::new();
+        let script_location = match &step.transformation {
+            FlowStepTransformation::Sql(t) => {
+                ensure!(
+                    !uses_trigger_time(&t.sql),
+                    "step '{}' uses ds/ts, which aren't available when steps run on glue",
+                    step.name
+                );
+                default_arguments.insert("--sql".to_string(), t.sql.clone());
+                glue.sql_script_location.clone()
+            }
+            FlowStepTransformation::Spark(t) => {
+                ensure!(
+                    t.application.ends_with(".py") && split_s3_uri(&t.application).is_some(),
+                    "step '{}' must run a .py script from s3 when steps run on glue",
+                    step.name
+                );
+                ensure!(
+                    !t.args.iter().any(|a| uses_trigger_time(a)),
+                    "step '{}' uses ds/ts, which aren't available when steps run on glue",
+                    step.name
+                );
+                // NOTE: glue only takes a single `--conf`, the rest ride along in its value
+                if !t.conf.is_empty() {
+                    let conf: Vec<String> =
+                        t.conf.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+                    default_arguments.insert("--conf".to_string(), conf.join(" --conf "));
+                }
+                // Glue arguments are named, positional args are handed over as a json list
+                if !t.args.is_empty() {
+                    default_arguments
+                        .insert("--basin_args".to_string(), serde_json::to_string(&t.args)?);
+                }
+                t.application.clone()
+            }
+            FlowStepTransformation::Dbt(_) => {
+                bail!(
+                    "step '{}' is a dbt step, which can't run on glue",
+                    step.name
+                )
+            }
+        };
+
+        let timeout_secs = parse_duration_secs(&step.timeout)
+            .with_context(|| format!("step '{}' has an invalid timeout", step.name))?;
+        Ok(GlueJobSpec {
+            name: glue_job_name(descriptor, &step.name),
+            description: step.summary.clone(),
+            role_arn: glue.role_arn.clone(),
+            script_location,
+            glue_version: glue.glue_version.clone(),
+            default_arguments,
+            max_retries: step.retries.map(|r| r as i32),
+            timeout: Some(((timeout_secs + 59) / 60) as i32),
+            tags: self.cost_tags(descriptor),
         })
     }
 
-    fn build_waterwheel_job_spec(&self, raw_descriptor: &FlowDescriptor) -> Result<WaterwheelJob> {
+    fn cost_tags(&self, descriptor: &FlowDescriptor) -> BTreeMap<String, String> {
+        cost_tags(
+            &descriptor.id,
+            &descriptor.labels,
+            descriptor.owner.as_ref(),
+            &self.cost,
+        )
+    }
+
+    fn waterwheel_resource_list(quantities: FlowResourceQuantities) -> WaterwheelResourceList {
+        WaterwheelResourceList {
+            cpu: quantities.cpu,
+            memory: quantities.memory,
+        }
+    }
+
+    // Only what the descriptor's templates refer to is read from the store. Bare table names could
+    // be in any database and dbt steps are handed every table, flows using either read them all.
+    async fn template_context(&self, descriptor: &FlowDescriptor) -> Result<TemplateContext> {
+        let references = TemplateReferences::collect(templated_fields(descriptor))?;
+        let has_dbt_steps = descriptor
+            .steps
+            .iter()
+            .any(|step| matches!(step.transformation, FlowStepTransformation::Dbt(_)));
+
+        let templates = if has_dbt_steps || references.tables.iter().any(|t| !t.contains('.')) {
+            TemplateContext::new(
+                &self
+                    .descriptor_store
+                    .list_descriptors::<DatabaseDescriptor>(DescriptorKind::Database)
+                    .await?,
+                &self
+                    .descriptor_store
+                    .list_descriptors::<TableDescriptor>(DescriptorKind::Table)
+                    .await?,
+            )
+        } else {
+            let database_names: BTreeSet<&str> = references
+                .databases
+                .iter()
+                .map(String::as_str)
+                .chain(
+                    references
+                        .tables
+                        .iter()
+                        .filter_map(|t| t.split_once('.').map(|(db, _)| db)),
+                )
+                .collect();
+            let mut databases: BTreeMap<&str, DatabaseDescriptor> = BTreeMap::new();
+            for name in database_names {
+                if let Some(database) = self
+                    .get_by_name::<DatabaseDescriptor>(DescriptorKind::Database, None, name)
+                    .await?
+                {
+                    databases.insert(name, database);
+                }
+            }
+
+            let mut tables = Vec::new();
+            for (db_name, table_name) in references.tables.iter().filter_map(|t| t.split_once('.'))
+            {
+                let Some(database) = databases.get(db_name) else {
+                    continue;
+                };
+                if let Some(table) = self
+                    .get_by_name::<TableDescriptor>(
+                        DescriptorKind::Table,
+                        Some(&database.id),
+                        table_name,
+                    )
+                    .await?
+                {
+                    tables.push(table);
+                }
+            }
+
+            TemplateContext::new(&databases.into_values().collect::<Vec<_>>(), &tables)
+        };
+
+        // NOTE: flows are often submitted alongside the tables they use, they wait for them
+        //       rather than being rejected
+        if let Some(name) = templates.missing(&references) {
+            return Err(ControllerReconciliationError::DependencyMissing(name).into());
+        }
+        Ok(templates)
+    }
+
+    async fn get_by_name<T: DeserializeOwned>(
+        &self,
+        kind: DescriptorKind,
+        namespace: Option<&str>,
+        name: &str,
+    ) -> Result<Option<T>> {
+        match self
+            .descriptor_store
+            .get_id_by_name(kind, namespace, name)
+            .await?
+        {
+            Some(id) => self.descriptor_store.get_descriptor::<T>(&id, kind).await,
+            None => Ok(None),
+        }
+    }
+
+    // Renders the templated fields of the descriptor, see `TemplateContext` for what's available
+    fn render_descriptor(
+        &self,
+        descriptor: &FlowDescriptor,
+        templates: &TemplateContext,
+    ) -> Result<FlowDescriptor> {
+        let mut rendered = descriptor.clone();
+        rendered.summary = templates
+            .render(&descriptor.summary)
+            .context("summary has an invalid template")?;
+        for step in rendered.steps.iter_mut() {
+            match &mut step.transformation {
+                FlowStepTransformation::Sql(t) => {
+                    t.sql = templates.render(&t.sql).with_context(|| {
+                        format!("step '{}' has an invalid sql template", step.name)
+                    })?;
+                }
+                FlowStepTransformation::Dbt(_) => {}
+                FlowStepTransformation::Spark(t) => {
+                    for arg in t.args.iter_mut() {
+                        *arg = templates.render(arg).with_context(|| {
+                            format!("step '{}' has an invalid spark arg template", step.name)
+                        })?;
+                    }
+                }
+            }
+        }
+
+        Ok(rendered)
+    }
+
+    // First database a dbt step builds into that basin doesn't know about yet
+    fn missing_dbt_database(
+        descriptor: &FlowDescriptor,
+        templates: &TemplateContext,
+    ) -> Option<String> {
+        descriptor
+            .steps
+            .iter()
+            .find_map(|step| match &step.transformation {
+                FlowStepTransformation::Dbt(t) if templates.database(&t.database).is_none() => {
+                    Some(t.database.clone())
+                }
+                _ => None,
+            })
+    }
+
+    // Checks the project out and runs `dbt build` against a profile targeting the step's database.
+    // Physical table names are passed in as the `basin_tables` var so models don't hardcode them.
+    fn dbt_script(&self, t: &FlowDbtTransformation, templates: &TemplateContext) -> Result<String> {
+        let (schema, bucket) = templates
+            .database(&t.database)
+            .ok_or_else(|| anyhow!("unknown database '{}'", t.database))?;
+        let s3_staging_dir = self
+            .dbt
+            .s3_staging_dir
+            .clone()
+            .ok_or_else(|| anyhow!("no athena staging dir is configured"))?;
+
+        // NOTE: yaml is a superset of json so the profile doesn't need a yaml serializer
+        let profiles = json!({
+            DBT_TARGET: {
+                "target": DBT_TARGET,
+                "outputs": {
+                    DBT_TARGET: {
+                        "type": "athena",
+                        "s3_staging_dir": s3_staging_dir,
+                        "s3_data_dir": format!("s3://{}/", bucket),
+                        "region_name": self.aws_region,
+                        "database": "awsdatacatalog",
+                        "schema": schema,
+                        "work_group": self.dbt.work_group,
+                        "threads": self.dbt.threads.unwrap_or(DEFAULT_DBT_THREADS),
+                    }
+                }
+            }
+        });
+        let vars = json!({ "basin_tables": templates.tables() });
+
+        let mut build = format!(
+            "dbt build --profiles-dir /tmp/profiles --profile {} --target {} --vars {}",
+            DBT_TARGET,
+            DBT_TARGET,
+            shell_escape::escape(Cow::from(vars.to_string()))
+        );
+        if let Some(selector) = &t.selector {
+            build.push_str(&format!(
+                " --select {}",
+                shell_escape::escape(Cow::from(selector))
+            ));
+        }
+
+        // NOTE: fetching the ref rather than cloning a branch lets commit shas be pinned
+        Ok([
+            "set -euo pipefail".to_string(),
+            "git init -q /tmp/project".to_string(),
+            "cd /tmp/project".to_string(),
+            format!(
+                "git fetch -q --depth 1 {} {}",
+                shell_escape::escape(Cow::from(&t.repository)),
+                shell_escape::escape(Cow::from(&t.git_ref))
+            ),
+            "git checkout -q FETCH_HEAD".to_string(),
+            format!(
+                "cd {}",
+                shell_escape::escape(Cow::from(t.project_dir.as_deref().unwrap_or(".")))
+            ),
+            "mkdir -p /tmp/profiles".to_string(),
+            format!(
+                "printf '%s' {} > /tmp/profiles/profiles.yml",
+                shell_escape::escape(Cow::from(profiles.to_string()))
+            ),
+            "dbt deps --profiles-dir /tmp/profiles".to_string(),
+            build,
+        ]
+        .join("\n"))
+    }
+
+    fn validate_spark_step(&self, t: &FlowSparkTransformation, has_image: bool) -> Result<()> {
+        ensure!(
+            has_image || self.spark.image.is_some(),
+            "no spark image is configured"
+        );
+        ensure!(
+            t.application.ends_with(".py") || t.application.ends_with(".jar"),
+            "application '{}' must be a .py script or a .jar",
+            t.application
+        );
+        ensure!(
+            t.main_class.is_none() || t.application.ends_with(".jar"),
+            "main_class only applies to jar applications"
+        );
+        for key in t.conf.keys() {
+            ensure!(
+                !key.is_empty() && !key.contains(char::is_whitespace) && !key.contains('='),
+                "invalid spark conf key '{}'",
+                key
+            );
+        }
+
+        if self.spark.runner == SparkRunner::EmrServerless {
+            ensure!(
+                self.spark.emr_serverless.is_some(),
+                "the emr_serverless spark runner isn't configured"
+            );
+            ensure!(
+                split_s3_uri(&t.application).is_some(),
+                "application '{}' must be an s3 uri to run on emr serverless",
+                t.application
+            );
+        }
+        Ok(())
+    }
+
+    fn spark_script(&self, t: &FlowSparkTransformation, step_name: &str) -> Result<String> {
+        match self.spark.runner {
+            SparkRunner::Submit => {
+                let mut command = vec![
+                    "spark-submit".to_string(),
+                    format!(
+                        "--master {}",
+                        shell_escape::escape(Cow::from(&self.spark.master))
+                    ),
+                ];
+                if let Some(main_class) = &t.main_class {
+                    command.push(format!(
+                        "--class {}",
+                        shell_escape::escape(Cow::from(main_class))
+                    ));
+                }
+                for (key, value) in t.conf.iter() {
+                    command.push(format!(
+                        "--conf {}",
+                        shell_escape::escape(Cow::from(format!("{}={}", key, value)))
+                    ));
+                }
+                command.push(shell_escape::escape(Cow::from(&t.application)).into_owned());
+                command.extend(
+                    t.args
+                        .iter()
+                        .map(|arg| shell_escape::escape(Cow::from(arg)).into_owned()),
+                );
+                Ok(command.join(" "))
+            }
+            SparkRunner::EmrServerless => {
+                let emr =
+                    self.spark.emr_serverless.as_ref().ok_or_else(|| {
+                        anyhow!("the emr_serverless spark runner isn't configured")
+                    })?;
+
+                let mut submit_params: Vec<String> = vec![];
+                if let Some(main_class) = &t.main_class {
+                    submit_params.push(format!("--class {}", main_class));
+                }
+                for (key, value) in t.conf.iter() {
+                    submit_params.push(format!("--conf {}={}", key, value));
+                }
+
+                let mut spark_submit = json!({
+                    "entryPoint": t.application,
+                    "entryPointArguments": t.args,
+                });
+                if !submit_params.is_empty() {
+                    spark_submit["sparkSubmitParameters"] = json!(submit_params.join(" "));
+                }
+                let job_driver = json!({ "sparkSubmit": spark_submit });
+
+                let application_id = shell_escape::escape(Cow::from(&emr.application_id));
+                // NOTE: the task only finishes once the job run does so waterwheel's retries and
+                //       downstream dependencies behave the same as for in-container steps
+                Ok([
+                    "set -euo pipefail".to_string(),
+                    format!(
+                        "run_id=$(aws emr-serverless start-job-run --application-id {} \
+                         --execution-role-arn {} --name {} --job-driver {} \
+                         --query jobRunId --output text)",
+                        application_id,
+                        shell_escape::escape(Cow::from(&emr.execution_role_arn)),
+                        shell_escape::escape(Cow::from(step_name)),
+                        shell_escape::escape(Cow::from(job_driver.to_string())),
+                    ),
+                    "echo \"started emr serverless job run $run_id\"".to_string(),
+                    "while true; do".to_string(),
+                    format!(
+                        "  state=$(aws emr-serverless get-job-run --application-id {} \
+                         --job-run-id \"$run_id\" --query jobRun.state --output text)",
+                        application_id,
+                    ),
+                    "  case \"$state\" in".to_string(),
+                    "    SUCCESS) exit 0 ;;".to_string(),
+                    "    FAILED|CANCELLED) echo \"job run $run_id $state\"; exit 1 ;;".to_string(),
+                    "  esac".to_string(),
+                    format!("  sleep {}", emr.poll_interval),
+                    "done".to_string(),
+                ]
+                .join("\n"))
+            }
+        }
+    }
+
+    // NOTE: best effort, the catalog catches up on the flow's next reconcile
+    async fn push_datahub_flow(&self, descriptor: &FlowDescriptor) {
+        let Some(datahub) = &self.datahub_provisioner else {
+            return;
+        };
+
+        let result = match self.datahub_upstreams(datahub, descriptor).await {
+            Ok((input_datasets, upstream_jobs)) => {
+                datahub
+                    .put_flow(&DataHubFlow {
+                        name: descriptor.name.clone(),
+                        description: descriptor.summary.clone(),
+                        owner: descriptor.owner.clone(),
+                        jobs: descriptor
+                            .steps
+                            .iter()
+                            .map(|step| {
+                                let is_root = step.parents.is_empty();
+                                DataHubJob {
+                                    name: step.name.clone(),
+                                    description: step.summary.clone(),
+                                    input_datasets: if is_root {
+                                        input_datasets.clone()
+                                    } else {
+                                        Vec::new()
+                                    },
+                                    input_jobs: step
+                                        .parents
+                                        .iter()
+                                        .map(|parent| datahub.job_urn(&descriptor.name, parent))
+                                        .chain(upstream_jobs.iter().filter(|_| is_root).cloned())
+                                        .collect(),
+                                }
+                            })
+                            .collect(),
+                    })
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            warn!(?e, "failed to push flow to datahub");
+        }
+    }
+
+    // Lineage the flow's root steps get from its upstream conditions, the datasets of glue engine
+    // tables and the final jobs of upstream flows. Upstreams basin doesn't know about yet are left
+    // out until a later reconcile.
+    async fn datahub_upstreams(
+        &self,
+        datahub: &DataHubProvisioner,
+        descriptor: &FlowDescriptor,
+    ) -> Result<(Vec<String>, Vec<String>)> {
+        let mut datasets = Vec::new();
+        let mut jobs = Vec::new();
+        for condition in descriptor.all_conditions() {
+            let FlowCondition::Upstream(upstream) = condition else {
+                continue;
+            };
+            match upstream.kind {
+                FlowUpstreamKind::Table => {
+                    let Some(table) = self
+                        .descriptor_store
+                        .get_descriptor::<TableDescriptor>(
+                            &upstream.upstream,
+                            DescriptorKind::Table,
+                        )
+                        .await?
+                    else {
+                        continue;
+                    };
+                    let Some(database) = self
+                        .descriptor_store
+                        .get_descriptor::<DatabaseDescriptor>(
+                            &table.database,
+                            DescriptorKind::Database,
+                        )
+                        .await?
+                    else {
+                        continue;
+                    };
+                    if table.engine.unwrap_or(database.engine) == StorageEngine::Glue {
+                        datasets.push(datahub.dataset_urn(
+                            self.storage.catalog_for(&database).as_str(),
+                            &glue_database_name(&database),
+                            &table.name,
+                        ));
+                    }
+                }
+                FlowUpstreamKind::Flow => {
+                    let Some(upstream_flow) = self
+                        .descriptor_store
+                        .get_descriptor::<FlowDescriptor>(&upstream.upstream, DescriptorKind::Flow)
+                        .await?
+                    else {
+                        continue;
+                    };
+                    jobs.extend(
+                        upstream_flow
+                            .sink_steps()
+                            .map(|step| datahub.job_urn(&upstream_flow.name, &step.name)),
+                    );
+                }
+            }
+        }
+        Ok((datasets, jobs))
+    }
+
+    // Waterwheel dependencies on the final tasks of every upstream flow this flow waits on
+    async fn resolve_upstream_refs(&self, descriptor: &FlowDescriptor) -> Result<Vec<String>> {
+        let mut refs = vec![];
+        for condition in descriptor.all_conditions() {
+            let FlowCondition::Upstream(upstream) = condition else {
+                continue;
+            };
+            // NOTE: tables only need to exist, their data arriving is what starts the flow
+            if upstream.kind == FlowUpstreamKind::Table {
+                self.descriptor_store
+                    .get_descriptor::<TableDescriptor>(&upstream.upstream, DescriptorKind::Table)
+                    .await?
+                    .ok_or_else(|| {
+                        ControllerReconciliationError::DependencyMissing(upstream.upstream.clone())
+                    })?;
+                continue;
+            }
+
+            let upstream_flow: FlowDescriptor = self
+                .descriptor_store
+                .get_descriptor(&upstream.upstream, DescriptorKind::Flow)
+                .await?
+                .ok_or_else(|| {
+                    ControllerReconciliationError::DependencyMissing(upstream.upstream.clone())
+                })?;
+            refs.extend(upstream_flow.sink_steps().map(|step| {
+                external_task_ref(&self.waterwheel_project, &upstream_flow.name, &step.name)
+            }));
+        }
+        Ok(refs)
+    }
+
+    fn build_waterwheel_job_spec(
+        &self,
+        raw_descriptor: &FlowDescriptor,
+        // Of the descriptor as stored, before rendering
+        stored_hash: &str,
+        templates: &TemplateContext,
+        upstream_refs: &[String],
+        backfills: &[BackfillRecord],
+    ) -> Result<WaterwheelJob> {
         let descriptor = raw_descriptor.clone();
 
         let mut triggers: Vec<WaterwheelTrigger> = vec![];
-        match descriptor.condition {
-            FlowCondition::Cron(cron_condition) => {
+        for condition in descriptor.all_conditions() {
+            match condition {
+                FlowCondition::Cron(cron_condition) => {
+                    // NOTE: the first trigger keeps the name single condition flows have always
+                    //       used so existing jobs aren't churned
+                    let name = match triggers.len() {
+                        0 => "cron".to_string(),
+                        n => format!("cron_{}", n),
+                    };
+                    triggers.push(WaterwheelTrigger {
+                        name,
+                        start: PRIMORDIAL_TIME.to_string(),
+                        end: None,
+                        cron: cron_condition.schedule.clone(),
+                    });
+                }
+                // Handled through upstream_refs, or start_run for upstream tables
+                FlowCondition::Upstream(_) => {}
+            }
+        }
+
+        // Backfills replay the first schedule over their range as triggers of their own, until
+        // waterwheel has worked through them
+        let backfills: Vec<&BackfillRecord> = backfills
+            .iter()
+            .filter(|b| b.status != BackfillStatus::Completed)
+            .collect();
+        let schedule = triggers.first().map(|t| t.cron.clone());
+        if let Some(schedule) = schedule
+            && descriptor.condition_mode == FlowConditionMode::Any
+        {
+            for backfill in backfills {
                 triggers.push(WaterwheelTrigger {
-                    name: "cron".to_string(),
-                    start: PRIMORDIAL_TIME.to_string(),
-                    cron: cron_condition.schedule.clone(),
+                    name: format!("backfill_{}", backfill.backfill_id),
+                    start: backfill.start.to_rfc3339(),
+                    end: Some(backfill.end.to_rfc3339()),
+                    cron: schedule.clone(),
                 });
             }
-            t => {
-                error!("Unsupported trigger condition {:?}", t);
-                bail!("unsupported trigger condition");
-            }
+        } else if !backfills.is_empty() {
+            warn!("flow can no longer be backfilled, ignoring its backfills");
         }
 
+        // Every condition gates the first steps, waterwheel waits on all of them unless told
+        // otherwise so `any` sets a threshold of one
+        let mut root_depends: Vec<String> = triggers
+            .iter()
+            .map(|t| format!("trigger/{}", t.name))
+            .collect();
+        root_depends.extend(upstream_refs.iter().cloned());
+        let root_threshold = match descriptor.condition_mode {
+            FlowConditionMode::Any if root_depends.len() > 1 => Some(1),
+            _ => None,
+        };
+
         let mut tasks: Vec<WaterwheelTask> = vec![];
         for step in descriptor.steps.into_iter() {
             let task = match step.transformation {
                 FlowStepTransformation::Sql(t) => {
                     let escaped_sql = shell_escape::escape(Cow::from(t.sql));
                     WaterwheelDockerTask {
-                        image: "bash".to_string(),
-                        args: vec!["-c".to_string(), format!("echo \"{}\"", escaped_sql)],
+                        image: step
+                            .image
+                            .unwrap_or_else(|| self.images.default_image.clone()),
+                        args: vec![
+                            "-c".to_string(),
+                            expand_trigger_date(format!("echo \"{}\"", escaped_sql)),
+                        ],
+                        resources: step.resources.map(|r| WaterwheelResources {
+                            requests: r.requests.map(Self::waterwheel_resource_list),
+                            limits: r.limits.map(Self::waterwheel_resource_list),
+                        }),
                     }
                 }
+                FlowStepTransformation::Dbt(t) => WaterwheelDockerTask {
+                    image: step
+                        .image
+                        .or_else(|| self.dbt.runner_image.clone())
+                        .ok_or_else(|| anyhow!("no dbt runner image is configured"))?,
+                    args: vec!["-c".to_string(), self.dbt_script(&t, templates)?],
+                    resources: step.resources.map(|r| WaterwheelResources {
+                        requests: r.requests.map(Self::waterwheel_resource_list),
+                        limits: r.limits.map(Self::waterwheel_resource_list),
+                    }),
+                },
+                FlowStepTransformation::Spark(t) => WaterwheelDockerTask {
+                    image: step
+                        .image
+                        .or_else(|| self.spark.image.clone())
+                        .ok_or_else(|| anyhow!("no spark image is configured"))?,
+                    args: vec![
+                        "-c".to_string(),
+                        expand_trigger_date(self.spark_script(&t, &step.name)?),
+                    ],
+                    resources: step.resources.map(|r| WaterwheelResources {
+                        requests: r.requests.map(Self::waterwheel_resource_list),
+                        limits: r.limits.map(Self::waterwheel_resource_list),
+                    }),
+                },
             };
 
             let depends: Vec<String> = step
@@ -166,25 +1704,327 @@ impl FlowController {
                 .map(|x| format!("task/{}", x))
                 .collect();
 
+            let is_root = depends.is_empty();
             tasks.push(WaterwheelTask {
                 name: step.name.clone(),
                 docker: task,
-                depends: if depends.is_empty() {
-                    vec!["trigger/cron".to_string()]
+                depends: if is_root {
+                    root_depends.clone()
                 } else {
                     depends
                 },
+                threshold: if is_root { root_threshold } else { None },
+                retries: step.retries,
+                retry_delay: step.retry_delay,
             })
         }
 
+        // NOTE: the job also depends on backfills, upstreams and config, which the descriptor's
+        //       hash alone wouldn't change with
+        let submitted_hash = descriptor_hash(&(stored_hash, &triggers, &tasks));
         Ok(WaterwheelJob {
             uuid: descriptor.id.clone(),
             project: self.waterwheel_project.clone(),
             name: descriptor.name.clone(),
-            description: descriptor.summary.clone(),
+            // NOTE: waterwheel jobs have nowhere else to keep metadata
+            description: format!(
+                "{}\n\n{}: {}",
+                descriptor.summary, DESCRIPTOR_HASH_KEY, submitted_hash
+            ),
             paused: false,
             triggers,
             tasks,
         })
     }
 }
+
+// Hash a waterwheel job was submitted with, from the last line of its description
+fn submitted_hash(description: &str) -> Option<&str> {
+    description
+        .lines()
+        .last()?
+        .strip_prefix(DESCRIPTOR_HASH_KEY)?
+        .strip_prefix(": ")
+}
+
+// Fields rendered as templates, see `FlowController::render_descriptor`
+fn templated_fields(descriptor: &FlowDescriptor) -> impl Iterator<Item = &str> {
+    let steps = descriptor
+        .steps
+        .iter()
+        .flat_map(|step| match &step.transformation {
+            FlowStepTransformation::Sql(t) => vec![t.sql.as_str()],
+            FlowStepTransformation::Dbt(_) => vec![],
+            FlowStepTransformation::Spark(t) => t.args.iter().map(String::as_str).collect(),
+        });
+    std::iter::once(descriptor.summary.as_str()).chain(steps)
+}
+
+fn uses_trigger_time(rendered: &str) -> bool {
+    rendered.contains(TRIGGER_DATETIME_PLACEHOLDER) || rendered.contains(TRIGGER_DATE_PLACEHOLDER)
+}
+
+// Waterwheel only expands the trigger datetime, scripts using `ds` have it cut to a date by the
+// shell once waterwheel has filled the datetime in
+fn expand_trigger_date(script: String) -> String {
+    if !script.contains(TRIGGER_DATE_PLACEHOLDER) {
+        return script;
+    }
+    format!(
+        "trigger_datetime={}\nscript={}\neval \"${{script//{}/${{trigger_datetime:0:10}}}}\"",
+        shell_escape::escape(Cow::from(TRIGGER_DATETIME_PLACEHOLDER)),
+        shell_escape::escape(Cow::from(script)),
+        TRIGGER_DATE_PLACEHOLDER
+    )
+}
+
+// `500m` -> 500, `2` -> 2000
+fn parse_cpu_millis(quantity: &str) -> Result<u64> {
+    let parsed = match quantity.strip_suffix('m') {
+        Some(millis) => millis.parse::<u64>().ok(),
+        None => quantity
+            .parse::<f64>()
+            .ok()
+            .filter(|cores| cores.is_finite() && *cores >= 0.0)
+            .map(|cores| (cores * 1000.0).round() as u64),
+    };
+    parsed.ok_or_else(|| anyhow!("invalid cpu quantity '{}'", quantity))
+}
+
+// `512Mi` -> 536870912, `1G` -> 1000000000
+fn parse_memory_bytes(quantity: &str) -> Result<u64> {
+    const SUFFIXES: &[(&str, u64)] = &[
+        ("Ki", 1 << 10),
+        ("Mi", 1 << 20),
+        ("Gi", 1 << 30),
+        ("Ti", 1 << 40),
+        ("k", 1_000),
+        ("M", 1_000_000),
+        ("G", 1_000_000_000),
+        ("T", 1_000_000_000_000),
+    ];
+
+    let (number, multiplier) = SUFFIXES
+        .iter()
+        .find_map(|(suffix, multiplier)| {
+            quantity
+                .strip_suffix(suffix)
+                .map(|number| (number, *multiplier))
+        })
+        .unwrap_or((quantity, 1));
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| anyhow!("invalid memory quantity '{}'", quantity))
+}
+
+// `30s` -> 30, `5m` -> 300, `1h` -> 3600
+// Steps paired with how far they are from the flow's first steps, which sit at depth 0
+fn step_depths(steps: &[FlowStep]) -> Result<Vec<(&FlowStep, usize)>> {
+    let mut depths: HashMap<&str, usize> = HashMap::new();
+    while depths.len() < steps.len() {
+        let known = depths.len();
+        for step in steps {
+            if depths.contains_key(step.name.as_str()) {
+                continue;
+            }
+            let parent_depths: Option<Vec<usize>> = step
+                .parents
+                .iter()
+                .map(|p| depths.get(p.as_str()).copied())
+                .collect();
+            if let Some(parent_depths) = parent_depths {
+                let depth = parent_depths.into_iter().max().map_or(0, |d| d + 1);
+                depths.insert(&step.name, depth);
+            }
+        }
+        ensure!(
+            depths.len() > known,
+            "steps have unknown parents or depend on each other in a cycle"
+        );
+    }
+
+    Ok(steps.iter().map(|s| (s, depths[s.name.as_str()])).collect())
+}
+
+// Converts a flow cron schedule (`sec min hour day month weekday [year]`) into the
+// `cron(min hour day month weekday year)` form glue and eventbridge take, neither can schedule
+// more than once a minute
+fn aws_cron_schedule(schedule: &str) -> Result<String> {
+    let fields: Vec<&str> = schedule.split_whitespace().collect();
+    let [seconds, rest @ ..] = fields.as_slice() else {
+        bail!("invalid cron schedule '{}'", schedule);
+    };
+    ensure!(
+        rest.len() == 5 || rest.len() == 6,
+        "invalid cron schedule '{}'",
+        schedule
+    );
+    ensure!(
+        *seconds == "0",
+        "aws schedules can't run more than once a minute"
+    );
+
+    let mut fields: Vec<String> = rest.iter().map(|f| f.to_string()).collect();
+    if fields.len() == 5 {
+        fields.push("*".to_string());
+    }
+    // One of day of month and day of week has to be `?`
+    match (fields[2].as_str(), fields[4].as_str()) {
+        (_, "*") => fields[4] = "?".to_string(),
+        ("*", _) => fields[2] = "?".to_string(),
+        _ => bail!("aws schedules can't restrict both the day of month and the day of week"),
+    }
+
+    Ok(format!("cron({})", fields.join(" ")))
+}
+
+// e.g. `90s`, `30m`, `6h` or `2d`, plain numbers are seconds
+pub fn parse_duration_secs(duration: &str) -> Result<u64> {
+    let (number, multiplier) = match duration.char_indices().last() {
+        Some((i, 's')) => (&duration[..i], 1),
+        Some((i, 'm')) => (&duration[..i], 60),
+        Some((i, 'h')) => (&duration[..i], 60 * 60),
+        Some((i, 'd')) => (&duration[..i], 24 * 60 * 60),
+        _ => (duration, 1),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| anyhow!("invalid duration '{}'", duration))
+}
+
+#[cfg(test)]
+impl FlowController {
+    // NOTE: nothing listens on the redis port, the stores are only there to build the controller
+    pub(super) async fn for_tests(waterwheel: Box<dyn WaterwheelClient>, flows: FlowsConf) -> Self {
+        let redis = crate::config::RedisConf {
+            topology: crate::config::RedisTopology::Single {
+                url: "redis://127.0.0.1:1".to_string(),
+            },
+            key_prefix: String::new(),
+        };
+        let aws = aws_config::SdkConfig::builder().build();
+        FlowController {
+            cost: CostConf::default(),
+            sql: SqlConf::default(),
+            images: FlowImagesConf::default(),
+            allowed_images: vec![],
+            dbt: DbtConf::default(),
+            spark: SparkConf::default(),
+            flows,
+            storage: StorageConf::default(),
+            aws_region: None,
+            descriptor_store: RedisDescriptorStore::new(&redis).await.unwrap(),
+            deployment_state_store: RedisDeploymentStateStore::new(&redis).await.unwrap(),
+            backfill_store: RedisBackfillStore::new(&redis).await.unwrap(),
+            waterwheel_project: "basin".to_string(),
+            waterwheel,
+            glue: GlueWorkflowProvisioner::new(&aws),
+            step_functions: StepFunctionsProvisioner::new(&aws),
+            state_event_publisher: StateEventPublisher::default(),
+            datahub_provisioner: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mockall::{predicate::eq, Sequence};
+    use serde_json::json;
+
+    use super::*;
+    use crate::provisioner::waterwheel::MockWaterwheelClient;
+
+    async fn controller(waterwheel: MockWaterwheelClient) -> FlowController {
+        FlowController::for_tests(Box::new(waterwheel), FlowsConf::default()).await
+    }
+
+    fn flow() -> FlowDescriptor {
+        serde_json::from_value(json!({
+            "id": "flow-orders",
+            "name": "orders",
+            "summary": "loads orders",
+            "backend": "waterwheel",
+            "steps": [],
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn reconcile_waterwheel_leaves_a_matching_job_alone() {
+        let job = WaterwheelJob {
+            uuid: "flow-orders".to_string(),
+            description: format!("loads orders\n{}: abc", DESCRIPTOR_HASH_KEY),
+            ..Default::default()
+        };
+        let mut waterwheel = MockWaterwheelClient::new();
+        waterwheel
+            .expect_get_job()
+            .with(eq("flow-orders"))
+            .returning(|_| {
+                Ok(Some(WaterwheelJob {
+                    uuid: "flow-orders".to_string(),
+                    // NOTE: waterwheel fills in defaults of its own, only the hash is compared
+                    paused: true,
+                    description: format!("loads orders\n{}: abc", DESCRIPTOR_HASH_KEY),
+                    ..Default::default()
+                }))
+            });
+        waterwheel.expect_put_job().never();
+
+        controller(waterwheel)
+            .await
+            .reconcile_waterwheel(&flow(), &job, vec![])
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn teardown_pauses_the_job_before_deleting_it() {
+        let mut sequence = Sequence::new();
+        let mut waterwheel = MockWaterwheelClient::new();
+        waterwheel
+            .expect_set_paused()
+            .with(eq("flow-orders"), eq(true))
+            .times(1)
+            .in_sequence(&mut sequence)
+            .returning(|_, _| Ok(()));
+        waterwheel
+            .expect_delete_job()
+            .with(eq("flow-orders"))
+            .times(1)
+            .in_sequence(&mut sequence)
+            .returning(|_| Ok(()));
+
+        controller(waterwheel)
+            .await
+            .teardown(&flow())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn teardown_fails_when_the_delete_fails_after_pausing() {
+        let mut waterwheel = MockWaterwheelClient::new();
+        waterwheel
+            .expect_set_paused()
+            .times(1)
+            .returning(|_, _| Ok(()));
+        waterwheel
+            .expect_delete_job()
+            .returning(|_| Err(anyhow!("waterwheel is down")));
+
+        let e = controller(waterwheel)
+            .await
+            .teardown(&flow())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<ControllerReconciliationError>(),
+            Some(ControllerReconciliationError::ProvisionerError(_))
+        ));
+    }
+}
@@ -0,0 +1,120 @@
+use serde::Serialize;
+
+use crate::provisioner::{
+    glue_workflow::{GlueJobSpec, GlueTriggerSpec},
+    step_functions::StateMachineDefinition,
+    waterwheel::WaterwheelJob,
+};
+
+// Everything a flow deploys as on its backend, generated without touching the backend. The
+// reconciler provisions exactly this, so previewing it shows what a descriptor would deploy and
+// pinning it in golden files catches regressions in the generated specs.
+#[derive(Serialize, Debug)]
+#[serde(tag = "backend", rename_all = "snake_case")]
+pub enum FlowSpec {
+    Waterwheel {
+        job: WaterwheelJob,
+    },
+    Glue {
+        workflow_name: String,
+        description: String,
+        jobs: Vec<GlueJobSpec>,
+        triggers: Vec<GlueTriggerSpec>,
+    },
+    StepFunctions {
+        state_machine_name: String,
+        jobs: Vec<GlueJobSpec>,
+        definition: StateMachineDefinition,
+        // Rule name and schedule of each cron condition
+        schedules: Vec<(String, String)>,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use serde_json::Value;
+
+    use super::*;
+    use crate::{
+        config::{FlowsConf, GlueFlowConf, StepFunctionsFlowConf},
+        controller::flow::FlowController,
+        fluid::descriptor::flow::{FlowBackend, FlowDescriptor},
+        provisioner::waterwheel::MockWaterwheelClient,
+    };
+
+    fn flows_conf() -> FlowsConf {
+        FlowsConf {
+            default_backend: FlowBackend::Waterwheel,
+            glue: Some(GlueFlowConf {
+                role_arn: "arn:aws:iam::123456789012:role/basin-glue".to_string(),
+                sql_script_location: "s3://basin-scripts/run_sql.py".to_string(),
+                glue_version: "4.0".to_string(),
+            }),
+            step_functions: Some(StepFunctionsFlowConf {
+                role_arn: "arn:aws:iam::123456789012:role/basin-sfn".to_string(),
+                events_role_arn: "arn:aws:iam::123456789012:role/basin-events".to_string(),
+            }),
+        }
+    }
+
+    // Builds the spec samples/flows/simple_flow.json deploys as on `backend`
+    async fn simple_flow_spec(backend: FlowBackend) -> Value {
+        let controller =
+            FlowController::for_tests(Box::new(MockWaterwheelClient::new()), flows_conf()).await;
+        let mut descriptor: FlowDescriptor =
+            serde_json::from_str(include_str!("../../samples/flows/simple_flow.json")).unwrap();
+        descriptor.backend = Some(backend);
+
+        let spec = controller.build_spec(&descriptor, &[]).await.unwrap();
+        serde_json::to_value(spec).unwrap()
+    }
+
+    // Compares `actual` with src/controller/golden/{name}.json, run with BASIN_UPDATE_GOLDEN=1 to
+    // write the file instead once a change to it is intended
+    fn assert_golden(name: &str, actual: &Value) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("src/controller/golden")
+            .join(format!("{}.json", name));
+        if std::env::var_os("BASIN_UPDATE_GOLDEN").is_some() {
+            let pretty = serde_json::to_string_pretty(actual).unwrap();
+            fs::write(&path, pretty + "\n").expect("failed to write golden file");
+            return;
+        }
+
+        let expected: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap())
+            .expect("golden file isn't json");
+        assert!(
+            expected == *actual,
+            "{} doesn't match {}\nexpected: {}\nactual: {}",
+            name,
+            path.display(),
+            serde_json::to_string_pretty(&expected).unwrap(),
+            serde_json::to_string_pretty(actual).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn waterwheel_spec_matches_golden_file() {
+        let mut spec = simple_flow_spec(FlowBackend::Waterwheel).await;
+        // NOTE: the description carries the descriptor's hash, which moves with every field added
+        //       to descriptors
+        if let Some(description) = spec.pointer_mut("/job/description") {
+            *description = Value::String("[redacted]".to_string());
+        }
+        assert_golden("simple_flow.waterwheel", &spec);
+    }
+
+    #[tokio::test]
+    async fn glue_spec_matches_golden_file() {
+        let spec = simple_flow_spec(FlowBackend::Glue).await;
+        assert_golden("simple_flow.glue", &spec);
+    }
+
+    #[tokio::test]
+    async fn step_functions_spec_matches_golden_file() {
+        let spec = simple_flow_spec(FlowBackend::StepFunctions).await;
+        assert_golden("simple_flow.step_functions", &spec);
+    }
+}
@@ -0,0 +1,390 @@
+use std::time::Duration;
+
+use crate::{
+    approval_gate::ApprovalGate,
+    config::{BasinConfig, LandingZonesConf},
+    deployment_state_store::RedisDeploymentStateStore,
+    descriptor_store::{DescriptorStore, RedisDescriptorStore},
+    fluid::descriptor::{
+        database::DatabaseDescriptor,
+        flow::{
+            FlowCondition, FlowConditionMode, FlowCronCondition, FlowDescriptor,
+            FlowSparkTransformation, FlowStep, FlowStepTransformation,
+        },
+        landing_zone::LandingZoneDescriptor,
+        table::TableDescriptor,
+        DescriptorKind, StorageEngine,
+    },
+    policy::PolicyChecker,
+    provisioner::s3::S3Provisioner,
+    reconcile_lock_store::{ReconcileLockStore, RedisReconcileLockStore},
+};
+
+use anyhow::{anyhow, bail, ensure, Result};
+use regex::Regex;
+use tracing::{debug, error, info};
+
+use super::{
+    base::BaseController,
+    error::ControllerReconciliationError,
+    maintenance::{put_maintenance_flow, retire_maintenance_flow},
+    naming::{
+        conversion_flow_id, conversion_flow_name, glue_database_name, landing_notification_id,
+        landing_zone_location,
+    },
+};
+
+const VALIDATION_REGEX_NAME: &str = r"^[a-z0-9_]+$";
+const DEFAULT_CONVERSION_SCHEDULE: &str = "0 0 * * * *";
+// A bucket's notification lease outlives a read and rewrite of its notifications many times over
+const NOTIFICATION_LEASE_TTL: Duration = Duration::from_secs(30);
+const NOTIFICATION_LEASE_ATTEMPTS: usize = 20;
+const NOTIFICATION_LEASE_RETRY: Duration = Duration::from_millis(250);
+
+pub struct LandingZoneController {
+    landing_zones: Option<LandingZonesConf>,
+    descriptor_store: RedisDescriptorStore,
+    deployment_state_store: RedisDeploymentStateStore,
+    // Conversion flows are admitted like any submitted flow
+    policy_checker: Option<PolicyChecker>,
+    approval_gate: ApprovalGate,
+    lock_store: RedisReconcileLockStore,
+    s3_provisioner: S3Provisioner,
+}
+
+#[async_trait::async_trait]
+impl BaseController<LandingZoneDescriptor> for LandingZoneController {
+    async fn validate(&self, descriptor: &LandingZoneDescriptor) -> Result<()> {
+        ensure!(
+            Regex::new(VALIDATION_REGEX_NAME)
+                .unwrap()
+                .is_match(&descriptor.name),
+            format!(
+                "Invalid name '{}'. Must match '{}'",
+                descriptor.name, VALIDATION_REGEX_NAME
+            )
+        );
+        ensure!(
+            self.landing_zones.is_some(),
+            "landing zones aren't configured"
+        );
+
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "info", name = "landing_zone_reconcile", skip(self, descriptor), fields(descriptor_id = %descriptor.id))]
+    async fn reconcile(&self, descriptor: &LandingZoneDescriptor) -> Result<()> {
+        info!("Performing reconciliation for landing zone");
+        debug!("Full descriptor to be reconciled is {:?}", descriptor);
+
+        info!("Checking for dependency {}", descriptor.table);
+        let Some(table_descriptor) = self
+            .descriptor_store
+            .get_descriptor::<TableDescriptor>(&descriptor.table, DescriptorKind::Table)
+            .await?
+        else {
+            info!("Depended table could not be found");
+            return Err(
+                ControllerReconciliationError::DependencyMissing(descriptor.table.clone()).into(),
+            );
+        };
+        let Some(db_descriptor) = self
+            .descriptor_store
+            .get_descriptor::<DatabaseDescriptor>(
+                &table_descriptor.database,
+                DescriptorKind::Database,
+            )
+            .await?
+        else {
+            info!("Depended database could not be found");
+            return Err(ControllerReconciliationError::DependencyMissing(
+                table_descriptor.database.clone(),
+            )
+            .into());
+        };
+        info!("Dependency met");
+
+        // NOTE: the conversion is a spark job writing through the glue catalog
+        let engine = table_descriptor.engine.unwrap_or(db_descriptor.engine);
+        if engine != StorageEngine::Glue {
+            return Err(ControllerReconciliationError::InvalidDescriptor(anyhow!(
+                "landing zones can only feed glue tables, not {:?}",
+                engine
+            ))
+            .into());
+        }
+
+        info!("Delegating resource reconcilation to clients");
+        self.reconcile_s3_prefix(descriptor, &db_descriptor)
+            .await
+            .inspect_err(|e| error!(?e, "Resource reconcicliation failed"))
+            .map_err(|e| ControllerReconciliationError::provisioner(e.into()))?;
+        self.reconcile_notification(descriptor, &db_descriptor)
+            .await
+            .inspect_err(|e| error!(?e, "Resource reconcicliation failed"))
+            .map_err(|e| ControllerReconciliationError::provisioner(e.into()))?;
+        self.reconcile_conversion_flow(descriptor, &table_descriptor, &db_descriptor)
+            .await
+            .inspect_err(|e| error!(?e, "Resource reconcicliation failed"))
+            .map_err(|e| ControllerReconciliationError::ControllerError(e.into()))?;
+
+        info!("Finished resource reconciliation");
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "info", name = "landing_zone_teardown", skip(self, descriptor), fields(descriptor_id = %descriptor.id))]
+    async fn teardown(&self, descriptor: &LandingZoneDescriptor) -> Result<()> {
+        info!("Tearing down landing zone");
+
+        retire_maintenance_flow(
+            &self.descriptor_store,
+            &self.deployment_state_store,
+            &descriptor.id,
+            &conversion_flow_id(descriptor),
+        )
+        .await?;
+
+        let Some(table_descriptor) = self
+            .descriptor_store
+            .get_descriptor::<TableDescriptor>(&descriptor.table, DescriptorKind::Table)
+            .await?
+        else {
+            info!("Depended table is gone, nothing left to tear down");
+            return Ok(());
+        };
+        let Some(db_descriptor) = self
+            .descriptor_store
+            .get_descriptor::<DatabaseDescriptor>(
+                &table_descriptor.database,
+                DescriptorKind::Database,
+            )
+            .await?
+        else {
+            // NOTE: the database takes its bucket with it when it's torn down
+            info!("Depended database is gone, nothing left to tear down");
+            return Ok(());
+        };
+
+        self.teardown_s3_prefix(descriptor, &db_descriptor)
+            .await
+            .inspect_err(|e| error!(?e, "Resource teardown failed"))
+            .map_err(|e| ControllerReconciliationError::provisioner(e.into()))?;
+
+        info!("Finished resource teardown");
+        Ok(())
+    }
+}
+
+impl LandingZoneController {
+    pub async fn new(conf: &BasinConfig) -> Result<Self> {
+        Ok(LandingZoneController {
+            landing_zones: conf.landing_zones.clone(),
+            descriptor_store: RedisDescriptorStore::new(&conf.redis).await?,
+            deployment_state_store: RedisDeploymentStateStore::new(&conf.redis).await?,
+            policy_checker: PolicyChecker::new(conf)?,
+            approval_gate: ApprovalGate::new(conf).await?,
+            lock_store: RedisReconcileLockStore::new(&conf.redis).await?,
+            s3_provisioner: S3Provisioner::new(&conf.aws_creds),
+        })
+    }
+
+    async fn reconcile_s3_prefix(
+        &self,
+        zone_descriptor: &LandingZoneDescriptor,
+        db_descriptor: &DatabaseDescriptor,
+    ) -> Result<()> {
+        let (bucket, prefix) = &landing_zone_location(zone_descriptor, db_descriptor);
+        info!(bucket, prefix, "Reconciling s3 prefix");
+
+        match self.s3_provisioner.get_path_marker(bucket, prefix).await? {
+            None => {
+                info!("s3 prefix is unclaimed, creating it");
+                self.s3_provisioner.create_prefix(bucket, prefix).await?;
+            }
+            Some(marker) if marker.descriptor_id == zone_descriptor.id => {
+                debug!(?marker, "s3 prefix already owned by this descriptor");
+            }
+            Some(marker) => {
+                bail!(
+                    "s3://{}/{} is owned by descriptor '{}', refusing to take it over",
+                    bucket,
+                    prefix,
+                    marker.descriptor_id
+                );
+            }
+        }
+
+        self.s3_provisioner
+            .put_path_marker(bucket, prefix, &zone_descriptor.id, None)
+            .await?;
+
+        Ok(())
+    }
+
+    // Stops notifications and releases the prefix, files not converted yet are left in place
+    async fn teardown_s3_prefix(
+        &self,
+        zone_descriptor: &LandingZoneDescriptor,
+        db_descriptor: &DatabaseDescriptor,
+    ) -> Result<()> {
+        let (bucket, prefix) = landing_zone_location(zone_descriptor, db_descriptor);
+        self.update_notification(&bucket, &landing_notification_id(zone_descriptor), None)
+            .await?;
+
+        if let Some(marker) = self
+            .s3_provisioner
+            .get_path_marker(&bucket, &prefix)
+            .await?
+            && marker.descriptor_id == zone_descriptor.id
+        {
+            self.s3_provisioner
+                .delete_path_marker(&bucket, &prefix)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    // Announces uploads on the configured queue, removed again once it's unconfigured
+    async fn reconcile_notification(
+        &self,
+        zone_descriptor: &LandingZoneDescriptor,
+        db_descriptor: &DatabaseDescriptor,
+    ) -> Result<()> {
+        let (bucket, prefix) = landing_zone_location(zone_descriptor, db_descriptor);
+        let id = landing_notification_id(zone_descriptor);
+        let queue_arn = self
+            .landing_zones
+            .as_ref()
+            .and_then(|c| c.notification_queue_arn.as_deref());
+
+        self.update_notification(&bucket, &id, queue_arn.map(|arn| (prefix.as_str(), arn)))
+            .await
+    }
+
+    // Sends the prefix's uploads to the queue, or stops sending them when there's none.
+    // NOTE: a bucket's notifications are read and replaced as a whole. Zones sharing the bucket
+    //       would drop each other's if two replicas rewrote them at once, so the bucket is leased
+    //       and its notifications only read once the lease is held.
+    async fn update_notification(
+        &self,
+        bucket: &str,
+        id: &str,
+        prefix_queue: Option<(&str, &str)>,
+    ) -> Result<()> {
+        let lease = format!("bucket-notifications/{}", bucket);
+        let mut token = None;
+        for _ in 0..NOTIFICATION_LEASE_ATTEMPTS {
+            token = self
+                .lock_store
+                .try_lease(&lease, NOTIFICATION_LEASE_TTL)
+                .await?;
+            if token.is_some() {
+                break;
+            }
+            tokio::time::sleep(NOTIFICATION_LEASE_RETRY).await;
+        }
+        let Some(token) = token else {
+            bail!(
+                "notifications of bucket {} are being updated by another replica",
+                bucket
+            );
+        };
+
+        let result = match prefix_queue {
+            Some((prefix, queue_arn)) => {
+                self.s3_provisioner
+                    .put_prefix_notification(bucket, id, prefix, queue_arn)
+                    .await
+            }
+            None => {
+                self.s3_provisioner
+                    .delete_prefix_notification(bucket, id)
+                    .await
+            }
+        };
+        let released = self.lock_store.release(&lease, &token).await;
+        result?;
+        released
+    }
+
+    // Hands the conversion to the flow controller as a flow of its own
+    async fn reconcile_conversion_flow(
+        &self,
+        zone_descriptor: &LandingZoneDescriptor,
+        table_descriptor: &TableDescriptor,
+        db_descriptor: &DatabaseDescriptor,
+    ) -> Result<()> {
+        let conf = self
+            .landing_zones
+            .as_ref()
+            .ok_or_else(|| anyhow!("landing zones aren't configured"))?;
+
+        let (bucket, prefix) = landing_zone_location(zone_descriptor, db_descriptor);
+        let table = format!(
+            "{}.{}",
+            glue_database_name(db_descriptor),
+            table_descriptor.name
+        );
+        let conditions = match zone_descriptor.conditions.as_slice() {
+            [] => vec![FlowCondition::Cron(FlowCronCondition {
+                schedule: DEFAULT_CONVERSION_SCHEDULE.to_string(),
+            })],
+            conditions => conditions.to_vec(),
+        };
+        let flow = FlowDescriptor {
+            id: conversion_flow_id(zone_descriptor),
+            name: conversion_flow_name(zone_descriptor),
+            summary: format!(
+                "Converts {} files landed in {} into {}",
+                zone_descriptor.format.as_str(),
+                zone_descriptor.name,
+                table
+            ),
+            condition: None,
+            conditions,
+            condition_mode: FlowConditionMode::Any,
+            steps: vec![FlowStep {
+                name: "convert".to_string(),
+                summary: format!("Convert landed files into {}", table),
+                parents: vec![],
+                timeout: "2h".to_string(),
+                transformation: FlowStepTransformation::Spark(FlowSparkTransformation {
+                    application: conf.conversion_application.clone(),
+                    main_class: None,
+                    args: vec![
+                        "--source".to_string(),
+                        format!("s3://{}/{}", bucket, prefix),
+                        "--format".to_string(),
+                        zone_descriptor.format.as_str().to_string(),
+                        "--table".to_string(),
+                        table,
+                    ],
+                    conf: Default::default(),
+                }),
+                image: None,
+                resources: None,
+                retries: Some(1),
+                retry_delay: None,
+            }],
+            backend: zone_descriptor.backend,
+            priority: zone_descriptor.priority,
+            labels: zone_descriptor.labels.clone(),
+            owner: zone_descriptor
+                .owner
+                .clone()
+                .or_else(|| table_descriptor.owner.clone())
+                .or_else(|| db_descriptor.owner.clone()),
+        };
+
+        put_maintenance_flow(
+            &self.descriptor_store,
+            &self.deployment_state_store,
+            self.policy_checker.as_ref(),
+            &self.approval_gate,
+            &zone_descriptor.id,
+            &flow,
+        )
+        .await
+    }
+}
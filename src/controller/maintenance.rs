@@ -0,0 +1,119 @@
+use anyhow::Result;
+use chrono::Utc;
+use tracing::info;
+
+use crate::{
+    approval_gate::{Admission, ApprovalGate},
+    deployment_state_store::{
+        DeploymentHistory, DeploymentInfo, DeploymentState, DeploymentStateStore,
+        RedisDeploymentStateStore,
+    },
+    descriptor_store::{DescriptorStore, RedisDescriptorStore},
+    fluid::descriptor::{flow::FlowDescriptor, DescriptorKind},
+    policy::PolicyChecker,
+};
+
+// Flows basin generates on behalf of another descriptor (e.g. a table's statistics refresh).
+// They're admitted and stored like any submitted flow so the flow controller deploys them, and go
+// through the usual deletion path once their owner stops asking for them.
+
+// Stores the flow when it differs from what's stored, or brings it back if it was being retired.
+// Like any submission it has to pass policy, and a breaking change waits on an approver.
+pub async fn put_maintenance_flow(
+    descriptor_store: &RedisDescriptorStore,
+    deployment_state_store: &RedisDeploymentStateStore,
+    policy_checker: Option<&PolicyChecker>,
+    approval_gate: &ApprovalGate,
+    owner_id: &str,
+    flow: &FlowDescriptor,
+) -> Result<()> {
+    let stored = descriptor_store
+        .get_descriptor::<serde_json::Value>(&flow.id, DescriptorKind::Flow)
+        .await?;
+    let retiring = matches!(
+        deployment_state_store.get_state(&flow.id).await?,
+        Some(DeploymentInfo {
+            state: DeploymentState::Deleting | DeploymentState::Deleted,
+            ..
+        })
+    );
+    if !retiring && stored.as_ref() == Some(&serde_json::to_value(flow)?) {
+        return Ok(());
+    }
+    if let Some(policy_checker) = policy_checker {
+        policy_checker.check(flow).await?;
+    }
+    if let Admission::Held(approval) = approval_gate
+        .admit(flow, None, Some(format!("maintenance:{}", owner_id)))
+        .await?
+    {
+        info!(
+            flow_id = flow.id,
+            owner_id,
+            approval_id = approval.approval_id,
+            "holding maintenance flow for approval"
+        );
+        return Ok(());
+    }
+
+    info!(flow_id = flow.id, owner_id, "storing maintenance flow");
+    descriptor_store.store_descriptor(flow).await?;
+    deployment_state_store
+        .set_state(
+            &flow.id,
+            &DeploymentInfo {
+                state: DeploymentState::Pending,
+                description: Some(format!("maintenance flow of {}", owner_id)),
+                request_id: None,
+                updated_at: None,
+                permanent_failure: false,
+                attempts: 0,
+                delete_after: None,
+                history: DeploymentHistory::default(),
+            },
+        )
+        .await
+}
+
+// Has the flow torn down on the flow controller's next pass, no grace period since nothing but
+// basin depends on it
+pub async fn retire_maintenance_flow(
+    descriptor_store: &RedisDescriptorStore,
+    deployment_state_store: &RedisDeploymentStateStore,
+    owner_id: &str,
+    flow_id: &str,
+) -> Result<()> {
+    if descriptor_store
+        .get_descriptor::<serde_json::Value>(flow_id, DescriptorKind::Flow)
+        .await?
+        .is_none()
+    {
+        return Ok(());
+    }
+    let prior = deployment_state_store.get_state(flow_id).await?;
+    if let Some(info) = &prior
+        && matches!(
+            info.state,
+            DeploymentState::Deleting | DeploymentState::Deleted
+        )
+    {
+        return Ok(());
+    }
+
+    info!(flow_id, owner_id, "retiring maintenance flow");
+    deployment_state_store
+        .set_state(
+            flow_id,
+            &DeploymentInfo {
+                state: DeploymentState::Deleting,
+                description: Some(format!("no longer needed by {}", owner_id)),
+                request_id: None,
+                updated_at: None,
+                permanent_failure: false,
+                attempts: 0,
+                delete_after: Some(Utc::now()),
+                history: DeploymentHistory::default(),
+            },
+        )
+        .await
+}
@@ -0,0 +1,183 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    config::CostConf,
+    constants::{
+        DESCRIPTOR_ID_TAG_KEY, OWNER_EMAIL_TAG_KEY, OWNER_SLACK_CHANNEL_TAG_KEY, OWNER_TEAM_TAG_KEY,
+    },
+    fluid::descriptor::{
+        database::DatabaseDescriptor, flow::FlowDescriptor, landing_zone::LandingZoneDescriptor,
+        table::TableDescriptor, Owner,
+    },
+    provisioner::s3::split_s3_uri,
+};
+
+// Physical names basin gives the resources it provisions for a descriptor
+
+pub fn glue_database_name(descriptor: &DatabaseDescriptor) -> String {
+    format!("zone_{}", descriptor.name)
+}
+
+// Unquoted so snowflake stores it upper cased, the same name glue gets
+pub fn snowflake_database_name(descriptor: &DatabaseDescriptor) -> String {
+    glue_database_name(descriptor)
+}
+
+pub fn bigquery_dataset_name(descriptor: &DatabaseDescriptor) -> String {
+    glue_database_name(descriptor)
+}
+
+pub fn s3_bucket_name(descriptor: &DatabaseDescriptor) -> String {
+    format!("cz-vaporeon-db-{}", descriptor.name.replace("_", "-"))
+}
+
+// Role consumers assume for `access` (`read` or `write`) to the database's bucket and catalog entry
+pub fn iam_role_name(descriptor: &DatabaseDescriptor, access: &str) -> String {
+    format!("basin-db-{}-{}", descriptor.name.replace("_", "-"), access)
+}
+
+// Bucket and prefix the table's data lives under, the descriptor's override wins over convention
+pub fn table_location(
+    table_descriptor: &TableDescriptor,
+    db_descriptor: &DatabaseDescriptor,
+) -> Result<(String, String)> {
+    match &table_descriptor.location {
+        Some(location) => {
+            split_s3_uri(location).ok_or_else(|| anyhow!("invalid table location '{}'", location))
+        }
+        None => Ok((
+            s3_bucket_name(&db_descriptor),
+            table_descriptor.name.clone(),
+        )),
+    }
+}
+
+// Flow refreshing the table's column statistics
+pub fn statistics_flow_id(descriptor: &TableDescriptor) -> String {
+    format!("{}-statistics", descriptor.id)
+}
+
+pub fn statistics_flow_name(
+    table_descriptor: &TableDescriptor,
+    db_descriptor: &DatabaseDescriptor,
+) -> String {
+    format!(
+        "{}_{}_statistics",
+        db_descriptor.name, table_descriptor.name
+    )
+}
+
+// Flow running an iceberg or delta table's maintenance
+pub fn maintenance_flow_id(descriptor: &TableDescriptor) -> String {
+    format!("{}-maintenance", descriptor.id)
+}
+
+pub fn maintenance_flow_name(
+    table_descriptor: &TableDescriptor,
+    db_descriptor: &DatabaseDescriptor,
+) -> String {
+    format!(
+        "{}_{}_maintenance",
+        db_descriptor.name, table_descriptor.name
+    )
+}
+
+// Flow validating a table against its quality rules
+pub fn quality_flow_id(descriptor: &TableDescriptor) -> String {
+    format!("{}-quality", descriptor.id)
+}
+
+pub fn quality_flow_name(
+    table_descriptor: &TableDescriptor,
+    db_descriptor: &DatabaseDescriptor,
+) -> String {
+    format!("{}_{}_quality", db_descriptor.name, table_descriptor.name)
+}
+
+// Prefix producers upload the landing zone's files to, in its table's database bucket
+pub fn landing_zone_location(
+    zone_descriptor: &LandingZoneDescriptor,
+    db_descriptor: &DatabaseDescriptor,
+) -> (String, String) {
+    (
+        s3_bucket_name(&db_descriptor),
+        format!("_landing/{}", zone_descriptor.name),
+    )
+}
+
+// Flow converting a landing zone's files into its table
+pub fn conversion_flow_id(descriptor: &LandingZoneDescriptor) -> String {
+    format!("{}-conversion", descriptor.id)
+}
+
+pub fn conversion_flow_name(descriptor: &LandingZoneDescriptor) -> String {
+    format!("{}_landing_conversion", descriptor.name)
+}
+
+pub fn landing_notification_id(descriptor: &LandingZoneDescriptor) -> String {
+    format!("basin-landing-{}", descriptor.name)
+}
+
+// Bucket lifecycle rule expiring a table's data
+pub fn table_lifecycle_rule_id(descriptor: &TableDescriptor) -> String {
+    format!("basin-table-{}", descriptor.id)
+}
+
+pub fn glue_workflow_name(descriptor: &FlowDescriptor) -> String {
+    format!("basin-{}", descriptor.name)
+}
+
+pub fn glue_job_name(descriptor: &FlowDescriptor, step: &str) -> String {
+    format!("basin-{}-{}", descriptor.name, step)
+}
+
+// Starts the step once its parents have succeeded, or on schedule for the flow's first steps
+pub fn glue_trigger_name(descriptor: &FlowDescriptor, step: Option<&str>) -> String {
+    match step {
+        Some(step) => format!("basin-{}-{}", descriptor.name, step),
+        None => format!("basin-{}-start", descriptor.name),
+    }
+}
+
+pub fn state_machine_name(descriptor: &FlowDescriptor) -> String {
+    format!("basin-{}", descriptor.name)
+}
+
+// Eventbridge rule starting the flow's state machine for one of its cron conditions
+pub fn schedule_rule_name(descriptor: &FlowDescriptor, index: usize) -> String {
+    match index {
+        0 => format!("basin-{}-cron", descriptor.name),
+        n => format!("basin-{}-cron_{}", descriptor.name, n),
+    }
+}
+
+// Cost allocation and ownership tags for the resources provisioned for a descriptor
+pub fn cost_tags(
+    descriptor_id: &str,
+    labels: &BTreeMap<String, String>,
+    owner: Option<&Owner>,
+    conf: &CostConf,
+) -> BTreeMap<String, String> {
+    let mut tags: BTreeMap<String, String> = labels
+        .iter()
+        .filter(|(k, _)| conf.propagated_labels.contains(k))
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+    tags.insert(DESCRIPTOR_ID_TAG_KEY.to_string(), descriptor_id.to_string());
+    if let Some(owner) = owner {
+        tags.insert(OWNER_TEAM_TAG_KEY.to_string(), owner.team.clone());
+        if let Some(email) = &owner.email {
+            tags.insert(OWNER_EMAIL_TAG_KEY.to_string(), email.clone());
+        }
+        // NOTE: `#` isn't allowed in aws tag values
+        if let Some(channel) = &owner.slack_channel {
+            tags.insert(
+                OWNER_SLACK_CHANNEL_TAG_KEY.to_string(),
+                channel.trim_start_matches('#').to_string(),
+            );
+        }
+    }
+    tags
+}
@@ -0,0 +1,79 @@
+use serde_json::json;
+
+use super::naming::glue_database_name;
+use crate::fluid::descriptor::{
+    database::DatabaseDescriptor,
+    descriptor_hash,
+    quality::{QualityRule, TableQuality},
+    table::TableDescriptor,
+};
+
+// Pinned so suites read the same whichever great expectations the validation application bundles
+const GREAT_EXPECTATIONS_VERSION: &str = "0.18.12";
+
+// Renders a table's quality rules as a great expectations suite, one expectation per rule in the
+// same order. Each expectation carries its rule's position in `meta.rule` for results to refer to.
+pub fn expectation_suite(
+    table_descriptor: &TableDescriptor,
+    db_descriptor: &DatabaseDescriptor,
+    quality: &TableQuality,
+) -> serde_json::Value {
+    let expectations: Vec<serde_json::Value> = quality
+        .rules
+        .iter()
+        .enumerate()
+        .map(|(i, rule)| {
+            let (expectation_type, kwargs) = expectation(rule);
+            json!({
+                "expectation_type": expectation_type,
+                "kwargs": kwargs,
+                "meta": { "rule": i },
+            })
+        })
+        .collect();
+
+    json!({
+        "expectation_suite_name": format!(
+            "{}.{}",
+            glue_database_name(db_descriptor),
+            table_descriptor.name
+        ),
+        "expectations": expectations,
+        "meta": {
+            "great_expectations_version": GREAT_EXPECTATIONS_VERSION,
+            "basin": {
+                "table_id": table_descriptor.id,
+                "descriptor_hash": descriptor_hash(table_descriptor),
+            },
+        },
+    })
+}
+
+fn expectation(rule: &QualityRule) -> (&'static str, serde_json::Value) {
+    match rule {
+        QualityRule::NotNull { column } => (
+            "expect_column_values_to_not_be_null",
+            json!({ "column": column }),
+        ),
+        QualityRule::Unique { column } => (
+            "expect_column_values_to_be_unique",
+            json!({ "column": column }),
+        ),
+        QualityRule::AcceptedValues { column, values } => (
+            "expect_column_values_to_be_in_set",
+            json!({ "column": column, "value_set": values }),
+        ),
+        QualityRule::Between { column, min, max } => (
+            "expect_column_values_to_be_between",
+            json!({ "column": column, "min_value": min, "max_value": max }),
+        ),
+        QualityRule::Matches { column, pattern } => (
+            "expect_column_values_to_match_regex",
+            json!({ "column": column, "regex": pattern }),
+        ),
+        QualityRule::RowCount { min, max } => (
+            "expect_table_row_count_to_be_between",
+            json!({ "min_value": min, "max_value": max }),
+        ),
+    }
+}
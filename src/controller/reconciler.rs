@@ -0,0 +1,597 @@
+use std::{cmp::Reverse, marker::PhantomData, sync::Arc, time::Instant};
+
+use anyhow::Result;
+use chrono::Utc;
+use futures::future::join_all;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::{
+    sync::Semaphore,
+    time::{interval, timeout, Duration, MissedTickBehavior},
+};
+use tracing::{debug, error, info, info_span, warn, Instrument};
+
+use crate::{
+    bundle_store::{BundleStore, RedisBundleStore},
+    config::{BasinConfig, ControllerConf},
+    deployment_state_store::{
+        DeploymentHistory, DeploymentInfo, DeploymentState, DeploymentStateStore,
+        RedisDeploymentStateStore,
+    },
+    descriptor_store::{DescriptorStore, RedisDescriptorStore},
+    fluid::descriptor::{DescriptorKind, IdentifiableDescriptor},
+    metrics, queue_stats,
+    reconcile_lock_store::{ReconcileLockStore, RedisReconcileLockStore},
+    sharding::ShardMembership,
+    state_events::{StateEventPublisher, StateEventType},
+};
+
+use super::{
+    base::BaseController, error::ControllerReconciliationError,
+    validation_plugins::ValidationPlugins,
+};
+
+// Runs a controller against every stored descriptor of its kind: ordering work by priority,
+// bounding concurrency, retrying failures with backoff, driving deletions and recording the
+// outcome. Controllers only provide validate, reconcile and teardown.
+pub struct Reconciler<Descriptor, Controller> {
+    kind: DescriptorKind,
+    conf: ControllerConf,
+    // Leases outlive the reconcile deadline by this much, covering the state write after it
+    lock_margin: Duration,
+    controller: Controller,
+    descriptor_store: RedisDescriptorStore,
+    deployment_state_store: RedisDeploymentStateStore,
+    lock_store: RedisReconcileLockStore,
+    bundle_store: RedisBundleStore,
+    // Only set when replicas share out descriptors, otherwise every replica goes over all of them
+    shards: Option<Arc<ShardMembership>>,
+    state_event_publisher: StateEventPublisher,
+    validation_plugins: Option<ValidationPlugins>,
+    descriptor_kind: PhantomData<fn() -> Descriptor>,
+}
+
+impl<Descriptor, Controller> Reconciler<Descriptor, Controller>
+where
+    Descriptor: IdentifiableDescriptor + DeserializeOwned + Serialize + Sync + Send,
+    Controller: BaseController<Descriptor> + Sync,
+{
+    pub async fn new(
+        conf: &BasinConfig,
+        kind: DescriptorKind,
+        controller: Controller,
+        shards: Option<Arc<ShardMembership>>,
+    ) -> Result<Self> {
+        Ok(Reconciler {
+            kind,
+            conf: conf.controllers.get(kind).clone(),
+            lock_margin: Duration::from_secs(conf.reconciler.lock_margin_secs),
+            controller,
+            descriptor_store: RedisDescriptorStore::new(&conf.redis).await?,
+            deployment_state_store: RedisDeploymentStateStore::new(&conf.redis).await?,
+            lock_store: RedisReconcileLockStore::new(&conf.redis).await?,
+            bundle_store: RedisBundleStore::new(&conf.redis).await?,
+            shards,
+            state_event_publisher: StateEventPublisher::new(conf),
+            validation_plugins: ValidationPlugins::new(conf, kind)?,
+            descriptor_kind: PhantomData,
+        })
+    }
+
+    pub async fn run(&self) {
+        let mut ticker = interval(Duration::from_millis(self.conf.interval_ms));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            info!("running reconciliation");
+            ticker.tick().await;
+
+            match self.reconcile_all().await {
+                Ok(_) => info!("got ok from reconcile_all"),
+                Err(e) => error!("got err from reconcile_all {:?}", e),
+            }
+        }
+    }
+
+    async fn reconcile_all(&self) -> Result<()> {
+        let mut descriptors = self
+            .descriptor_store
+            .list_descriptors::<Descriptor>(self.kind)
+            .await?;
+        if let Some(shards) = &self.shards {
+            descriptors.retain(|d| shards.owns(&d.id()));
+            metrics::gauge_set(
+                "basin_shard_owned_descriptors",
+                &[("kind", self.kind.as_str())],
+                descriptors.len() as f64,
+            );
+        }
+        self.report_queue_depth(&descriptors).await;
+
+        // NOTE: the semaphore bounds how many descriptors are in flight at once, the rest
+        //       of the futures just sit waiting on a permit. Permits are handed out in the order
+        //       they're requested, so ordering the work by priority makes this a priority queue.
+        descriptors.sort_by_key(|d| Reverse(d.priority()));
+        let enqueued_at = Instant::now();
+        let slots = Semaphore::new(self.conf.parallelism.max(1));
+        join_all(
+            descriptors
+                .iter()
+                .map(|descriptor| self.reconcile_in_slot(descriptor, &slots, enqueued_at)),
+        )
+        .await;
+
+        Ok(())
+    }
+
+    async fn reconcile_in_slot(
+        &self,
+        descriptor: &Descriptor,
+        slots: &Semaphore,
+        enqueued_at: Instant,
+    ) {
+        let permit = match slots.acquire().await {
+            Ok(t) => t,
+            Err(e) => {
+                error!(?e, "reconcile slots closed");
+                return;
+            }
+        };
+        self.report_slot_usage(slots);
+
+        let wait_labels = [
+            ("kind", self.kind.as_str()),
+            ("priority", descriptor.priority().as_str()),
+        ];
+        metrics::counter_add(
+            "basin_reconcile_queue_wait_seconds_sum",
+            &wait_labels,
+            enqueued_at.elapsed().as_secs_f64(),
+        );
+        metrics::counter_inc("basin_reconcile_queue_wait_seconds_count", &wait_labels);
+
+        let id = descriptor.id();
+        let ttl = Duration::from_secs(self.conf.reconcile_timeout_secs) + self.lock_margin;
+        match self.lock_store.try_lock(self.kind, &id, ttl).await {
+            Ok(Some(token)) => {
+                self.reconcile_locked(descriptor).await;
+                if let Err(e) = self.lock_store.unlock(self.kind, &id, &token).await {
+                    warn!(?e, descriptor_id = id, "failed to release reconcile lock");
+                }
+            }
+            Ok(None) => debug!(
+                descriptor_id = id,
+                "descriptor is being reconciled elsewhere"
+            ),
+            Err(e) => error!(?e, descriptor_id = id, "could not take reconcile lock"),
+        }
+
+        drop(permit);
+        self.report_slot_usage(slots);
+    }
+
+    // The stored revision is still what's deployed while an operation on it waits for an approver,
+    // drift is repaired as usual. The state is left as it is so the approval stays decidable.
+    async fn reconcile_awaiting_approval(&self, descriptor: &Descriptor, info: &DeploymentInfo) {
+        let span = info_span!(
+            "reconcile",
+            kind = self.kind.as_str(),
+            descriptor_id = descriptor.id(),
+            request_id = info.request_id.as_deref().unwrap_or("")
+        );
+        let deadline = Duration::from_secs(self.conf.reconcile_timeout_secs);
+        match timeout(deadline, self.validate_and_reconcile(descriptor))
+            .instrument(span.clone())
+            .await
+        {
+            Ok(Ok(_)) => debug!(
+                parent: &span,
+                "reconciled stored revision, operation awaiting approval"
+            ),
+            Ok(Err(e)) => warn!(
+                parent: &span,
+                ?e,
+                "failed to reconcile stored revision, operation awaiting approval"
+            ),
+            Err(_) => warn!(
+                parent: &span,
+                timeout_secs = self.conf.reconcile_timeout_secs,
+                "reconcile exceeded deadline, cancelling"
+            ),
+        }
+        queue_stats::record_reconcile(self.kind);
+    }
+
+    async fn reconcile_locked(&self, descriptor: &Descriptor) {
+        let prior_state = match self
+            .deployment_state_store
+            .get_state(&descriptor.id())
+            .await
+        {
+            Ok(t) => t,
+            Err(e) => {
+                warn!(
+                    ?e,
+                    descriptor_id = descriptor.id(),
+                    "could not fetch deployment state"
+                );
+                None
+            }
+        };
+        if let Some(info) = &prior_state
+            && info.state == DeploymentState::Failed
+            && info.permanent_failure
+        {
+            debug!(
+                descriptor_id = descriptor.id(),
+                "skipping permanently failed descriptor until it is resubmitted"
+            );
+            return;
+        }
+        if let Some(info) = &prior_state
+            && info.state == DeploymentState::Quarantined
+        {
+            debug!(
+                descriptor_id = descriptor.id(),
+                "skipping quarantined descriptor until it is released"
+            );
+            return;
+        }
+        if let Some(info) = &prior_state
+            && info.state == DeploymentState::AwaitingApproval
+        {
+            self.reconcile_awaiting_approval(descriptor, info).await;
+            return;
+        }
+        if let Some(info) = &prior_state
+            && matches!(
+                info.state,
+                DeploymentState::Deleting | DeploymentState::Deleted
+            )
+        {
+            self.advance_deletion(descriptor, info).await;
+            return;
+        }
+        if let Some(info) = &prior_state
+            && let Some(retry_at) = self.retry_at(info)
+            && Utc::now() < retry_at
+        {
+            debug!(
+                descriptor_id = descriptor.id(),
+                %retry_at,
+                "failed descriptor is backing off"
+            );
+            return;
+        }
+
+        // Carry the request id of whoever last touched the descriptor so the reconcile
+        // logs can be correlated with the submit/event that caused it
+        let prior_attempts = prior_state.as_ref().map(|info| (info.state, info.attempts));
+        let request_id = prior_state
+            .as_ref()
+            .and_then(|info| info.request_id.clone());
+        let span = info_span!(
+            "reconcile",
+            kind = self.kind.as_str(),
+            descriptor_id = descriptor.id(),
+            request_id = request_id.as_deref().unwrap_or("")
+        );
+
+        // TODO: circuit break on descriptor id
+        // NOTE: hitting the deadline drops the reconcile future, cancelling whatever provisioner
+        //       call it was parked on. Reconciles are idempotent so the next pass picks it back up.
+        let conf = &self.conf;
+        let deadline = Duration::from_secs(conf.reconcile_timeout_secs);
+        let result = match timeout(deadline, self.validate_and_reconcile(descriptor))
+            .instrument(span.clone())
+            .await
+        {
+            Ok(t) => t,
+            Err(_) => {
+                warn!(
+                    parent: &span,
+                    timeout_secs = conf.reconcile_timeout_secs,
+                    "reconcile exceeded deadline, cancelling"
+                );
+                Err(
+                    ControllerReconciliationError::DeadlineExceeded(conf.reconcile_timeout_secs)
+                        .into(),
+                )
+            }
+        };
+
+        let (state, description, permanent_failure) = match result {
+            Ok(_) => (DeploymentState::Succeeded, None, false),
+            Err(e) => match e.downcast_ref::<ControllerReconciliationError>() {
+                Some(ControllerReconciliationError::DependencyMissing(_)) => {
+                    (DeploymentState::Pending, Some(format!("{:#}", e)), false)
+                }
+                Some(ControllerReconciliationError::ValidationFailed(_)) => (
+                    DeploymentState::Quarantined,
+                    Some(format!("{:#}", e)),
+                    false,
+                ),
+                Some(
+                    ControllerReconciliationError::PermanentProvisionerError(_)
+                    | ControllerReconciliationError::InvalidDescriptor(_),
+                ) => (DeploymentState::Failed, Some(format!("{:#}", e)), true),
+                Some(
+                    ControllerReconciliationError::ProvisionerError(_)
+                    | ControllerReconciliationError::ControllerError(_)
+                    | ControllerReconciliationError::DeadlineExceeded(_),
+                ) => (DeploymentState::Failed, Some(format!("{:#}", e)), false),
+                None => (DeploymentState::Failed, Some(format!("{:?}", e)), false),
+            },
+        };
+
+        metrics::counter_inc(
+            "basin_reconciles_total",
+            &[
+                ("kind", self.kind.as_str()),
+                ("state", format!("{:?}", state).as_str()),
+            ],
+        );
+        queue_stats::record_reconcile(self.kind);
+
+        let attempts = match prior_attempts {
+            Some((DeploymentState::Succeeded, n)) if state == DeploymentState::Succeeded => n,
+            Some((_, n)) => n + 1,
+            None => 1,
+        };
+
+        let info = DeploymentInfo {
+            state,
+            description,
+            request_id,
+            updated_at: None,
+            permanent_failure,
+            attempts,
+            delete_after: None,
+            history: DeploymentHistory::default(),
+        };
+        // NOTE: deletes, approvals, restores and resubmits don't take the reconcile lock, whatever
+        //       they wrote while this reconcile was running is left for the next pass
+        match self
+            .deployment_state_store
+            .set_state_if(&descriptor.id(), prior_state.as_ref(), &info)
+            .await
+        {
+            Ok(false) => info!(
+                parent: &span,
+                "deployment state changed during the reconcile, leaving it"
+            ),
+            Ok(true) => {
+                // NOTE: only changes are published, not every pass over an unchanged descriptor
+                let event_type = match state {
+                    DeploymentState::Succeeded => Some(StateEventType::ReconcileSucceeded),
+                    DeploymentState::Failed | DeploymentState::Quarantined => {
+                        Some(StateEventType::ReconcileFailed)
+                    }
+                    _ => None,
+                };
+                if let Some(event_type) = event_type
+                    && prior_attempts.map(|(prior, _)| prior) != Some(state)
+                {
+                    self.state_event_publisher
+                        .publish(
+                            event_type,
+                            self.kind,
+                            &descriptor.id(),
+                            descriptor.owner().as_ref(),
+                            &info,
+                        )
+                        .await;
+                }
+            }
+            Err(e) => error!(parent: &span, ?e, "failed to record deployment state"),
+        }
+    }
+
+    // NOTE: covers what this replica owns, summed over replicas when sharded
+    async fn report_queue_depth(&self, descriptors: &[Descriptor]) {
+        let ids: Vec<String> = descriptors.iter().map(|d| d.id()).collect();
+        let states = match self.deployment_state_store.get_states(&ids).await {
+            Ok(t) => t,
+            Err(e) => {
+                warn!(?e, "could not fetch deployment states for queue depth");
+                return;
+            }
+        };
+        let depth = queue_stats::queue_depth(&states);
+        let labels = [("kind", self.kind.as_str())];
+        metrics::gauge_set("basin_reconcile_queue_depth", &labels, depth.pending as f64);
+        metrics::gauge_set(
+            "basin_reconcile_queue_oldest_pending_seconds",
+            &labels,
+            depth.oldest_pending_secs.unwrap_or(0) as f64,
+        );
+    }
+
+    // When a failed descriptor is next due. Each retry waits as long as the descriptor has been
+    // failing for, so the wait roughly doubles every attempt.
+    fn retry_at(&self, info: &DeploymentInfo) -> Option<chrono::DateTime<Utc>> {
+        if info.state != DeploymentState::Failed {
+            return None;
+        }
+        let last_attempt = info.updated_at?;
+        let failing_since = info.history.last_transition.unwrap_or(last_attempt);
+
+        let min = Duration::from_millis(self.conf.interval_ms);
+        let max = Duration::from_secs(self.conf.max_retry_backoff_secs).max(min);
+        let backoff = (last_attempt - failing_since)
+            .to_std()
+            .unwrap_or_default()
+            .clamp(min, max);
+        Some(last_attempt + chrono::Duration::from_std(backoff).ok()?)
+    }
+
+    async fn advance_deletion(&self, descriptor: &Descriptor, info: &DeploymentInfo) {
+        let span = info_span!(
+            "teardown",
+            kind = self.kind.as_str(),
+            descriptor_id = descriptor.id(),
+            request_id = info.request_id.as_deref().unwrap_or("")
+        );
+
+        // NOTE: the state is moved to Deleted before the descriptor is forgotten, a descriptor
+        //       without a state would be picked back up as a new one. Forgetting it is retried
+        //       for as long as the tombstone is around.
+        if info.state == DeploymentState::Deleted {
+            if let Err(e) = self
+                .descriptor_store
+                .delete_descriptor(&descriptor.id(), descriptor.kind())
+                .await
+            {
+                error!(parent: &span, ?e, "failed to forget deleted descriptor");
+            }
+            return;
+        }
+
+        if let Some(delete_after) = info.delete_after
+            && Utc::now() < delete_after
+        {
+            debug!(parent: &span, %delete_after, "descriptor is awaiting deletion");
+            return;
+        }
+
+        let conf = &self.conf;
+        let deadline = Duration::from_secs(conf.reconcile_timeout_secs);
+        let result = match timeout(deadline, self.controller.teardown(descriptor))
+            .instrument(span.clone())
+            .await
+        {
+            Ok(t) => t,
+            Err(_) => Err(ControllerReconciliationError::DeadlineExceeded(
+                conf.reconcile_timeout_secs,
+            )
+            .into()),
+        };
+
+        let (state, description) = match result {
+            Ok(_) => {
+                info!(parent: &span, "tore down deleted descriptor");
+                (DeploymentState::Deleted, None)
+            }
+            Err(e) => {
+                error!(parent: &span, ?e, "teardown failed, retrying on the next pass");
+                (DeploymentState::Deleting, Some(format!("{:#}", e)))
+            }
+        };
+        let next_info = DeploymentInfo {
+            state,
+            description,
+            updated_at: None,
+            attempts: info.attempts + 1,
+            delete_after: info
+                .delete_after
+                .filter(|_| state == DeploymentState::Deleting),
+            history: DeploymentHistory::default(),
+            ..info.clone()
+        };
+        match self
+            .deployment_state_store
+            .set_state_if(&descriptor.id(), Some(info), &next_info)
+            .await
+        {
+            Ok(true) => {}
+            Ok(false) => {
+                info!(
+                    parent: &span,
+                    "deployment state changed during the teardown, leaving it"
+                );
+                return;
+            }
+            Err(e) => {
+                error!(parent: &span, ?e, "failed to record deployment state");
+                return;
+            }
+        }
+        if state == DeploymentState::Deleted {
+            self.state_event_publisher
+                .publish(
+                    StateEventType::DescriptorDeleted,
+                    self.kind,
+                    &descriptor.id(),
+                    descriptor.owner().as_ref(),
+                    &next_info,
+                )
+                .await;
+        }
+
+        if state == DeploymentState::Deleted
+            && let Err(e) = self
+                .descriptor_store
+                .delete_descriptor(&descriptor.id(), descriptor.kind())
+                .await
+        {
+            error!(parent: &span, ?e, "failed to forget deleted descriptor");
+        }
+    }
+
+    async fn validate_and_reconcile(&self, descriptor: &Descriptor) -> Result<()> {
+        // NOTE: descriptors can be stored without passing through validation (events, replays),
+        //       so nothing is provisioned until the controller has had a look at it. Those it
+        //       rejects are quarantined rather than retried every pass. Those still waiting on
+        //       a dependency aren't rejected, they're retried once it's arrived.
+        self.controller
+            .validate(descriptor)
+            .await
+            .map_err(
+                |e| match e.downcast_ref::<ControllerReconciliationError>() {
+                    Some(ControllerReconciliationError::DependencyMissing(_)) => e,
+                    _ => ControllerReconciliationError::ValidationFailed(e).into(),
+                },
+            )?;
+        if let Some(plugins) = &self.validation_plugins {
+            plugins.validate(self.kind, descriptor).await?;
+        }
+        self.wait_for_bundle(descriptor).await?;
+        self.controller.reconcile(descriptor).await
+    }
+
+    // Bundle members wait on the stages before theirs, so a bundle's tables aren't provisioned
+    // until its database is, nor its flows until its tables are
+    async fn wait_for_bundle(&self, descriptor: &Descriptor) -> Result<()> {
+        let Some(bundle) = self
+            .bundle_store
+            .get_bundle_of(&descriptor.id())
+            .await
+            .map_err(ControllerReconciliationError::ControllerError)?
+        else {
+            return Ok(());
+        };
+
+        for id in bundle.predecessors(self.kind) {
+            let state = self
+                .deployment_state_store
+                .get_state(id)
+                .await
+                .map_err(ControllerReconciliationError::ControllerError)?
+                .map(|info| info.state);
+            // NOTE: Deploying is drift being repaired, the resources are already there
+            if !matches!(
+                state,
+                Some(DeploymentState::Succeeded | DeploymentState::Deploying)
+            ) {
+                return Err(
+                    ControllerReconciliationError::DependencyMissing(id.to_string()).into(),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn report_slot_usage(&self, slots: &Semaphore) {
+        let parallelism = self.conf.parallelism.max(1);
+        let busy = parallelism - slots.available_permits();
+        metrics::gauge_set(
+            "basin_reconcile_slots_busy",
+            &[("kind", self.kind.as_str())],
+            busy as f64,
+        );
+        metrics::gauge_set(
+            "basin_reconcile_slots_total",
+            &[("kind", self.kind.as_str())],
+            parallelism as f64,
+        );
+    }
+}
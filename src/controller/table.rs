@@ -1,24 +1,74 @@
 use crate::{
-    config::BasinConfig,
+    approval_gate::ApprovalGate,
+    config::{
+        full_match_patterns, BasinConfig, MaskingConf, QualityConf, StorageConf,
+        TableMaintenanceConf,
+    },
+    constants::{
+        COLUMN_DEFAULT_KEY, COLUMN_GENERATED_KEY, DEPRECATION_KEY, DESCRIPTOR_HASH_KEY, DOCS_KEY,
+        GLOSSARY_TERMS_KEY, TABLE_CLASSIFICATION_KEY,
+    },
+    deployment_state_store::RedisDeploymentStateStore,
     descriptor_store::{DescriptorStore, RedisDescriptorStore},
     fluid::descriptor::{
         database::DatabaseDescriptor,
-        table::{TableColumnType, TableDescriptor},
+        descriptor_hash,
+        flow::{
+            FlowCondition, FlowConditionMode, FlowCronCondition, FlowDescriptor,
+            FlowSparkTransformation, FlowSqlTransformation, FlowStep, FlowStepTransformation,
+            FlowUpstreamCondition, FlowUpstreamKind,
+        },
+        table::{
+            DescriptiveMetadata, MaskingPolicy, PartitionProjection, ProjectionMode,
+            TableColumnType, TableDescriptor, TableFormat,
+        },
+        Catalog, DescriptorKind, DescriptorPriority, StorageEngine,
     },
+    policy::PolicyChecker,
+    provisioner::{
+        athena::AthenaProvisioner,
+        bigquery::BigQueryProvisioner,
+        column_types::{glue_type, hive_type},
+        datahub::{DataHubDataset, DataHubProvisioner},
+        glue::{GlueProvisioner, GlueTableClient},
+        hive_metastore::{HiveColumn, HiveMetastoreProvisioner, HiveTableInput},
+        lake_formation::{ColumnFilter, LakeFormationProvisioner},
+        s3::{split_s3_uri, S3Provisioner},
+        service_quotas::{QuotaChecker, QuotaResource},
+        snowflake::SnowflakeProvisioner,
+        trino::TrinoProvisioner,
+        unity_catalog::UnityCatalogProvisioner,
+    },
+    smoke_test_store::{RedisSmokeTestStore, SmokeTestResult, SmokeTestStore},
+    sql_validation::column_default,
 };
 
-use anyhow::{ensure, Result};
-use aws_sdk_glue::{
-    error::{GetTableError, GetTableErrorKind},
-    model::{Column, StorageDescriptor, TableInput},
-};
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use aws_sdk_glue::model::{Column, StorageDescriptor, TableInput};
+use chrono::Utc;
 use regex::Regex;
-use tracing::{debug, error, info};
+use std::collections::{BTreeMap, HashMap};
+use tracing::{debug, error, info, warn};
 
-use super::{base::BaseController, error::ControllerReconciliationError};
+use super::{
+    base::BaseController,
+    error::ControllerReconciliationError,
+    flow::parse_duration_secs,
+    maintenance::{put_maintenance_flow, retire_maintenance_flow},
+    naming::{
+        bigquery_dataset_name, glue_database_name, maintenance_flow_id, maintenance_flow_name,
+        quality_flow_id, quality_flow_name, snowflake_database_name, statistics_flow_id,
+        statistics_flow_name, table_lifecycle_rule_id, table_location,
+    },
+};
 
 const VALIDATION_REGEX_TABLE_NAME: &str = r"^[a-z0-9_]";
 const VALIDATION_REGEX_COLUMN_NAME: &str = r"^[a-z0-9_]";
+// Snowflake and bigquery identifiers are held to more than glue's, snowflake's are left unquoted
+const VALIDATION_REGEX_ENGINE_NAME: &str = r"^[a-z_][a-z0-9_]*$";
+
+// Filter names only have to be unique within their table
+const MASKING_FILTER_NAME: &str = "basin_masked";
 
 static SUPPORTED_COL_TYPES: &'static [TableColumnType] = &[
     TableColumnType::Int,
@@ -32,8 +82,31 @@ static SUPPORTED_COL_TYPES: &'static [TableColumnType] = &[
 ];
 
 pub struct TableController {
+    storage: StorageConf,
+    // Compiled once from storage.allowed_location_buckets
+    allowed_location_buckets: Vec<Regex>,
+    maintenance: Option<TableMaintenanceConf>,
+    quality: Option<QualityConf>,
+    masking: Option<MaskingConf>,
     descriptor_store: RedisDescriptorStore,
-    glue_client: aws_sdk_glue::Client,
+    deployment_state_store: RedisDeploymentStateStore,
+    // Maintenance flows are admitted like any submitted flow
+    policy_checker: Option<PolicyChecker>,
+    approval_gate: ApprovalGate,
+    glue_provisioner: Box<dyn GlueTableClient>,
+    hive_metastore_provisioner: Option<HiveMetastoreProvisioner>,
+    lake_formation_provisioner: LakeFormationProvisioner,
+    s3_provisioner: S3Provisioner,
+    quota_checker: QuotaChecker,
+    snowflake_provisioner: Option<SnowflakeProvisioner>,
+    bigquery_provisioner: Option<BigQueryProvisioner>,
+    unity_catalog_provisioner: Option<UnityCatalogProvisioner>,
+    trino_provisioner: Option<TrinoProvisioner>,
+    datahub_provisioner: Option<DataHubProvisioner>,
+    // Only set when smoke tests are configured
+    athena_provisioner: Option<AthenaProvisioner>,
+    fail_on_smoke_test: bool,
+    smoke_test_store: RedisSmokeTestStore,
 }
 
 #[async_trait::async_trait]
@@ -67,6 +140,162 @@ impl BaseController<TableDescriptor> for TableController {
                     col_desc.codec.kind, SUPPORTED_COL_TYPES,
                 )
             );
+
+            ensure!(
+                col_desc.default.is_none() || col_desc.generated.is_none(),
+                "Column '{}' can't both have a default and be generated",
+                col_desc.name
+            );
+            for expression in col_desc.default.iter().chain(col_desc.generated.iter()) {
+                ensure!(
+                    !expression.trim().is_empty(),
+                    "Column '{}' has an empty default or generation expression",
+                    col_desc.name
+                );
+            }
+            if let Some(metadata) = &col_desc.metadata {
+                metadata
+                    .validate()
+                    .with_context(|| format!("Column '{}' has invalid metadata", col_desc.name))?;
+            }
+            if let Some(masking) = &col_desc.masking {
+                ensure!(
+                    masking.policy == MaskingPolicy::Null
+                        || col_desc.codec.kind == TableColumnType::String,
+                    "Column '{}' can only be masked with null, hash and partial need a string",
+                    col_desc.name
+                );
+                ensure!(
+                    masking.policy != MaskingPolicy::Partial || masking.visible_chars > 0,
+                    "Column '{}' is partially masked but leaves no characters visible, mask it with hash or null instead",
+                    col_desc.name
+                );
+                let role_regex = Regex::new(VALIDATION_REGEX_ENGINE_NAME).unwrap();
+                for role in masking.privileged_roles.iter() {
+                    ensure!(
+                        role_regex.is_match(role),
+                        "Invalid privileged role '{}' on column '{}'. Must match '{}'",
+                        role,
+                        col_desc.name,
+                        VALIDATION_REGEX_ENGINE_NAME
+                    );
+                }
+            }
+        }
+        if let Some(metadata) = &descriptor.metadata {
+            metadata.validate().context("Invalid table metadata")?;
+        }
+
+        if let Some(location) = &descriptor.location {
+            let Some((bucket, _)) = split_s3_uri(location) else {
+                bail!(
+                    "Invalid location '{}'. Must be of the form 's3://bucket/prefix'",
+                    location
+                );
+            };
+
+            let allowed = self
+                .allowed_location_buckets
+                .iter()
+                .any(|pattern| pattern.is_match(&bucket));
+            ensure!(
+                allowed,
+                format!(
+                    "Location bucket '{}' is not allowed. Must match one of '{:?}'",
+                    bucket, self.storage.allowed_location_buckets,
+                )
+            );
+        }
+
+        if let Some(statistics) = &descriptor.statistics {
+            ensure!(
+                !statistics.conditions.is_empty(),
+                "statistics need at least one condition to refresh on"
+            );
+            for column in statistics.columns.iter() {
+                ensure!(
+                    descriptor.columns.iter().any(|c| &c.name == column),
+                    format!(
+                        "Statistics column '{}' is not a column of the table",
+                        column
+                    )
+                );
+            }
+        }
+
+        if let Some(freshness) = &descriptor.freshness {
+            parse_duration_secs(&freshness.max_age)
+                .with_context(|| format!("Invalid freshness max_age '{}'", freshness.max_age))?;
+        }
+
+        // NOTE: the format may come from the database's defaults, that's checked when reconciling
+        if let Some(maintenance) = &descriptor.maintenance {
+            ensure!(
+                self.maintenance.is_some(),
+                "table maintenance isn't configured"
+            );
+            ensure!(
+                maintenance.compaction
+                    || maintenance.snapshot_retention_hours.is_some()
+                    || maintenance.orphan_file_retention_hours.is_some(),
+                "maintenance needs at least one of compaction, snapshot_retention_hours or orphan_file_retention_hours"
+            );
+        }
+
+        if let Some(quality) = &descriptor.quality {
+            ensure!(self.quality.is_some(), "quality rules aren't configured");
+            quality.validate()?;
+            for column in quality.rules.iter().filter_map(|r| r.column()) {
+                ensure!(
+                    descriptor.columns.iter().any(|c| c.name == column),
+                    "Quality rule column '{}' is not a column of the table",
+                    column
+                );
+            }
+        }
+
+        if let Some(lifecycle) = &descriptor.lifecycle {
+            ensure!(
+                lifecycle.expire_after_days > 0,
+                "lifecycle expire_after_days must be at least 1"
+            );
+        }
+
+        for (i, key) in descriptor.partitioned_by.iter().enumerate() {
+            let Some(column) = descriptor.columns.iter().find(|c| &c.name == key) else {
+                bail!("Partition key '{}' is not a column of the table", key);
+            };
+            ensure!(
+                !descriptor.partitioned_by[..i].contains(key),
+                "Partition key '{}' is listed more than once",
+                key
+            );
+            ensure!(
+                column.codec.kind != TableColumnType::Complex && column.generated.is_none(),
+                "Partition key '{}' must be a plain column of a primitive type",
+                key
+            );
+        }
+        ensure!(
+            descriptor.partitioned_by.is_empty()
+                || descriptor.columns.len() > descriptor.partitioned_by.len(),
+            "A table can't be partitioned by all of its columns"
+        );
+        if let Some(projection) = &descriptor.partition_projection {
+            projection_parameters(descriptor, projection)
+                .context("Invalid partition projection")?;
+        }
+
+        // NOTE: tables without an engine follow their database, which may not have arrived yet
+        match descriptor.engine {
+            Some(engine) if engine != StorageEngine::Glue => {
+                self.validate_for_engine(descriptor, engine)?
+            }
+            Some(_) => ensure!(
+                self.masking.is_some() || descriptor.columns.iter().all(|c| c.masking.is_none()),
+                "column masking isn't configured"
+            ),
+            None => {}
         }
 
         Ok(())
@@ -81,9 +310,120 @@ impl BaseController<TableDescriptor> for TableController {
         // Requeue for database dependency, fetch when present
         let depended_db: Option<DatabaseDescriptor> = self
             .descriptor_store
-            .get_descriptor(&descriptor.database, &"database".to_string())
+            .get_descriptor(&descriptor.database, DescriptorKind::Database)
             .await?;
 
+        self.reconcile_with_database(descriptor, depended_db).await
+    }
+
+    #[tracing::instrument(level = "info", name = "table_teardown", skip(self, descriptor), fields(descriptor_id = %descriptor.id))]
+    async fn teardown(&self, descriptor: &TableDescriptor) -> Result<()> {
+        info!("Tearing down table");
+
+        for flow_id in [
+            statistics_flow_id(descriptor),
+            maintenance_flow_id(descriptor),
+            quality_flow_id(descriptor),
+        ] {
+            retire_maintenance_flow(
+                &self.descriptor_store,
+                &self.deployment_state_store,
+                &descriptor.id,
+                &flow_id,
+            )
+            .await?;
+        }
+
+        let Some(db_descriptor) = self
+            .descriptor_store
+            .get_descriptor::<DatabaseDescriptor>(&descriptor.database, DescriptorKind::Database)
+            .await?
+        else {
+            // NOTE: the database takes its tables with it when it's torn down
+            info!("Depended database is gone, nothing left to tear down");
+            return Ok(());
+        };
+
+        let result = match descriptor.engine.unwrap_or(db_descriptor.engine) {
+            StorageEngine::Glue => self.teardown_glue_table(descriptor, &db_descriptor).await,
+            StorageEngine::Snowflake => match &self.snowflake_provisioner {
+                Some(p) => {
+                    p.drop_table(&snowflake_database_name(&db_descriptor), &descriptor.name)
+                        .await
+                }
+                None => Err(anyhow!("snowflake isn't configured")),
+            },
+            StorageEngine::Bigquery => match &self.bigquery_provisioner {
+                Some(p) => {
+                    p.delete_table(&bigquery_dataset_name(&db_descriptor), &descriptor.name)
+                        .await
+                }
+                None => Err(anyhow!("bigquery isn't configured")),
+            },
+        };
+        result
+            .inspect_err(|e| error!(?e, "Resource teardown failed"))
+            .map_err(|e| ControllerReconciliationError::provisioner(e.into()))?;
+
+        info!("Finished resource teardown");
+        Ok(())
+    }
+}
+
+impl TableController {
+    pub async fn new(conf: &BasinConfig) -> Result<Self> {
+        Ok(TableController {
+            storage: conf.storage.clone(),
+            allowed_location_buckets: full_match_patterns(&conf.storage.allowed_location_buckets)?,
+            maintenance: conf.table_maintenance.clone(),
+            quality: conf.quality.clone(),
+            masking: conf.masking.clone(),
+            descriptor_store: RedisDescriptorStore::new(&conf.redis).await?,
+            deployment_state_store: RedisDeploymentStateStore::new(&conf.redis).await?,
+            policy_checker: PolicyChecker::new(conf)?,
+            approval_gate: ApprovalGate::new(conf).await?,
+            glue_provisioner: Box::new(GlueProvisioner::new(&conf.aws_creds)),
+            hive_metastore_provisioner: conf
+                .hive_metastore
+                .as_ref()
+                .map(HiveMetastoreProvisioner::new),
+            lake_formation_provisioner: LakeFormationProvisioner::new(&conf.aws_creds),
+            s3_provisioner: S3Provisioner::new(&conf.aws_creds),
+            quota_checker: QuotaChecker::new(conf.quotas.as_ref(), &conf.aws_creds),
+            snowflake_provisioner: conf.snowflake.as_ref().map(SnowflakeProvisioner::new),
+            bigquery_provisioner: conf
+                .bigquery
+                .as_ref()
+                .map(BigQueryProvisioner::new)
+                .transpose()?,
+            unity_catalog_provisioner: conf
+                .unity_catalog
+                .as_ref()
+                .map(UnityCatalogProvisioner::new),
+            trino_provisioner: conf.trino.as_ref().map(TrinoProvisioner::new),
+            datahub_provisioner: conf
+                .datahub
+                .as_ref()
+                .map(DataHubProvisioner::new)
+                .transpose()?,
+            athena_provisioner: conf
+                .smoke_tests
+                .as_ref()
+                .map(|c| AthenaProvisioner::new(&conf.aws_creds, c)),
+            fail_on_smoke_test: conf
+                .smoke_tests
+                .as_ref()
+                .map_or(false, |c| c.fail_reconcile),
+            smoke_test_store: RedisSmokeTestStore::new(&conf.redis).await?,
+        })
+    }
+
+    // Everything past looking up the table's database, which it waits on while it's missing
+    async fn reconcile_with_database(
+        &self,
+        descriptor: &TableDescriptor,
+        depended_db: Option<DatabaseDescriptor>,
+    ) -> Result<()> {
         let db_descriptor = match depended_db {
             Some(t) => {
                 info!("Found depended database");
@@ -100,30 +440,728 @@ impl BaseController<TableDescriptor> for TableController {
 
         info!("Dependency met");
 
+        // NOTE: everything from here on sees the table as it is with its database's defaults, so
+        //       the hash recorded on its resources changes when the defaults do
+        let descriptor = &descriptor.with_defaults(db_descriptor.defaults.as_ref());
+        if descriptor.maintenance.is_some() && descriptor.format.is_none() {
+            return Err(ControllerReconciliationError::InvalidDescriptor(anyhow!(
+                "maintenance is only supported on iceberg and delta tables"
+            ))
+            .into());
+        }
+        // NOTE: iceberg and delta track partitions in their own metadata, glue never sees them
+        if (!descriptor.partitioned_by.is_empty() || descriptor.partition_projection.is_some())
+            && descriptor.format.is_some()
+        {
+            return Err(ControllerReconciliationError::InvalidDescriptor(anyhow!(
+                "iceberg and delta tables can't set partitioned_by, their writers partition them"
+            ))
+            .into());
+        }
+
+        let engine = descriptor.engine.unwrap_or(db_descriptor.engine);
+        if engine != db_descriptor.engine {
+            return Err(ControllerReconciliationError::InvalidDescriptor(anyhow!(
+                "table engine {:?} doesn't match its database's engine {:?}",
+                engine,
+                db_descriptor.engine
+            ))
+            .into());
+        }
+        if engine != StorageEngine::Glue {
+            self.validate_for_engine(descriptor, engine)
+                .map_err(ControllerReconciliationError::InvalidDescriptor)?;
+            info!(?engine, "Delegating resource reconcilation to engine");
+            let result = match engine {
+                StorageEngine::Snowflake => {
+                    self.reconcile_snowflake_table(&descriptor, &db_descriptor)
+                        .await
+                }
+                StorageEngine::Bigquery => {
+                    self.reconcile_bigquery_table(&descriptor, &db_descriptor)
+                        .await
+                }
+                StorageEngine::Glue => unreachable!("glue tables are reconciled below"),
+            };
+            result
+                .inspect_err(|e| error!(?e, "Resource reconcicliation failed"))
+                .map_err(|e| ControllerReconciliationError::provisioner(e.into()))?;
+
+            info!("Finished resource reconciliation");
+            return Ok(());
+        }
+
+        if self.storage.catalog_for(&db_descriptor) == Catalog::Hive {
+            Self::validate_for_hive(descriptor)
+                .map_err(ControllerReconciliationError::InvalidDescriptor)?;
+            info!("Delegating resource reconcilation to the hive metastore");
+            self.reconcile_s3_prefix(&descriptor, &db_descriptor)
+                .await
+                .inspect_err(|e| error!(?e, "Resource reconcicliation failed"))
+                .map_err(|e| ControllerReconciliationError::provisioner(e.into()))?;
+            self.reconcile_hive_table(&descriptor, &db_descriptor)
+                .await
+                .inspect_err(|e| error!(?e, "Resource reconcicliation failed"))
+                .map_err(|e| ControllerReconciliationError::provisioner(e.into()))?;
+            self.reconcile_unity_catalog_table(&descriptor, &db_descriptor)
+                .await
+                .inspect_err(|e| error!(?e, "Resource reconcicliation failed"))
+                .map_err(|e| ControllerReconciliationError::provisioner(e.into()))?;
+            self.refresh_trino_table(&descriptor, &db_descriptor).await;
+            self.push_datahub_table(&descriptor, &db_descriptor).await;
+
+            info!("Finished resource reconciliation");
+            return Ok(());
+        }
+
         info!("Delegating resource reconcilation to clients");
+        // NOTE: the location has to be claimed before glue is pointed at it
+        self.reconcile_s3_prefix(&descriptor, &db_descriptor)
+            .await
+            .inspect_err(|e| error!(?e, "Resource reconcicliation failed"))
+            .map_err(|e| ControllerReconciliationError::provisioner(e.into()))?;
         self.reconcile_glue_table(&descriptor, &db_descriptor)
             .await
             .inspect_err(|e| error!(?e, "Resource reconcicliation failed"))
-            .map_err(|e| ControllerReconciliationError::ProvisionerError(e.into()))?;
+            .map_err(|e| ControllerReconciliationError::provisioner(e.into()))?;
+        self.reconcile_column_filter(&descriptor, &db_descriptor)
+            .await
+            .inspect_err(|e| error!(?e, "Resource reconcicliation failed"))
+            .map_err(|e| ControllerReconciliationError::provisioner(e.into()))?;
+        self.reconcile_unity_catalog_table(&descriptor, &db_descriptor)
+            .await
+            .inspect_err(|e| error!(?e, "Resource reconcicliation failed"))
+            .map_err(|e| ControllerReconciliationError::provisioner(e.into()))?;
+        self.refresh_trino_table(&descriptor, &db_descriptor).await;
+        self.reconcile_statistics_flow(&descriptor, &db_descriptor)
+            .await
+            .inspect_err(|e| error!(?e, "Resource reconcicliation failed"))
+            .map_err(|e| ControllerReconciliationError::ControllerError(e.into()))?;
+        self.reconcile_maintenance_flow(&descriptor, &db_descriptor)
+            .await
+            .inspect_err(|e| error!(?e, "Resource reconcicliation failed"))
+            .map_err(|e| ControllerReconciliationError::ControllerError(e.into()))?;
+        self.reconcile_quality_flow(&descriptor, &db_descriptor)
+            .await
+            .inspect_err(|e| error!(?e, "Resource reconcicliation failed"))
+            .map_err(|e| ControllerReconciliationError::ControllerError(e.into()))?;
+        self.smoke_test_table(&descriptor, &db_descriptor)
+            .await
+            .inspect_err(|e| error!(?e, "Smoke test failed"))
+            .map_err(|e| ControllerReconciliationError::provisioner(e.into()))?;
+        self.push_datahub_table(&descriptor, &db_descriptor).await;
 
         info!("Finished resource reconciliation");
         Ok(())
     }
 
-    async fn list_descriptors(&self) -> Result<Vec<TableDescriptor>> {
-        Ok(self
-            .descriptor_store
-            .list_descriptors::<TableDescriptor>("table")
-            .await?)
+    // Checks for tables living outside glue
+    fn validate_for_engine(
+        &self,
+        descriptor: &TableDescriptor,
+        engine: StorageEngine,
+    ) -> Result<()> {
+        let configured = match engine {
+            StorageEngine::Glue => true,
+            StorageEngine::Snowflake => self.snowflake_provisioner.is_some(),
+            StorageEngine::Bigquery => self.bigquery_provisioner.is_some(),
+        };
+        ensure!(configured, "{:?} isn't configured", engine);
+        ensure!(
+            descriptor.location.is_none(),
+            "{:?} tables can't set a location",
+            engine
+        );
+        ensure!(
+            descriptor.statistics.is_none(),
+            "{:?} maintains its own statistics",
+            engine
+        );
+        ensure!(
+            descriptor.freshness.is_none(),
+            "freshness is only tracked for glue tables, not {:?}",
+            engine
+        );
+        ensure!(
+            descriptor.quality.is_none(),
+            "quality rules are only validated for glue tables, not {:?}",
+            engine
+        );
+        ensure!(
+            descriptor.format.is_none() && descriptor.maintenance.is_none(),
+            "{:?} tables can't set a format or maintenance",
+            engine
+        );
+        ensure!(
+            descriptor.compression.is_none() && descriptor.lifecycle.is_none(),
+            "{:?} tables can't set a compression or lifecycle",
+            engine
+        );
+        ensure!(
+            descriptor.partitioned_by.is_empty() && descriptor.partition_projection.is_none(),
+            "{:?} tables can't set partitioned_by or partition_projection",
+            engine
+        );
+        ensure!(
+            engine == StorageEngine::Snowflake
+                || descriptor.columns.iter().all(|c| c.masking.is_none()),
+            "{:?} columns can't be masked",
+            engine
+        );
+        // NOTE: snowflake and bigquery take column defaults, neither has generated columns
+        ensure!(
+            descriptor.columns.iter().all(|c| c.generated.is_none()),
+            "{:?} tables can't have generated columns",
+            engine
+        );
+        for column in descriptor.columns.iter() {
+            if let Some(default) = &column.default {
+                column_default(default)
+                    .with_context(|| format!("Column '{}' has an invalid default", column.name))?;
+            }
+        }
+
+        let name_regex = Regex::new(VALIDATION_REGEX_ENGINE_NAME).unwrap();
+        for name in
+            std::iter::once(&descriptor.name).chain(descriptor.columns.iter().map(|c| &c.name))
+        {
+            ensure!(
+                name_regex.is_match(name),
+                format!(
+                    "Invalid {:?} name '{}'. Must match '{}'",
+                    engine, name, VALIDATION_REGEX_ENGINE_NAME,
+                )
+            );
+        }
+
+        Ok(())
     }
-}
 
-impl TableController {
-    pub async fn new(conf: &BasinConfig) -> Result<Self> {
-        Ok(TableController {
-            descriptor_store: RedisDescriptorStore::new(&conf.redis_url).await?,
-            glue_client: aws_sdk_glue::Client::new(&conf.aws_creds),
-        })
+    // Statistics, quality rules, iceberg/delta and smoke tests all go through glue
+    fn validate_for_hive(descriptor: &TableDescriptor) -> Result<()> {
+        ensure!(
+            descriptor.statistics.is_none() && descriptor.quality.is_none(),
+            "statistics and quality rules aren't supported for hive metastore tables"
+        );
+        ensure!(
+            descriptor.format.is_none() && descriptor.maintenance.is_none(),
+            "hive metastore tables can't set a format or maintenance"
+        );
+        ensure!(
+            descriptor
+                .columns
+                .iter()
+                .all(|c| c.default.is_none() && c.generated.is_none()),
+            "hive metastore columns can't have defaults or be generated"
+        );
+        ensure!(
+            descriptor.columns.iter().all(|c| c.masking.is_none()),
+            "hive metastore columns can't be masked, lake formation only governs glue"
+        );
+        ensure!(
+            descriptor.partitioned_by.is_empty() && descriptor.partition_projection.is_none(),
+            "hive metastore tables can't set partitioned_by or partition_projection"
+        );
+
+        Ok(())
+    }
+
+    async fn reconcile_snowflake_table(
+        &self,
+        table_descriptor: &TableDescriptor,
+        db_descriptor: &DatabaseDescriptor,
+    ) -> Result<()> {
+        let snowflake = self
+            .snowflake_provisioner
+            .as_ref()
+            .ok_or_else(|| anyhow!("snowflake isn't configured"))?;
+        let db_name = snowflake_database_name(&db_descriptor);
+
+        match snowflake
+            .table_columns(&db_name, &table_descriptor.name)
+            .await?
+        {
+            None => {
+                info!("snowflake table does not exist, creating it");
+                snowflake
+                    .create_table(
+                        &db_name,
+                        &table_descriptor.name,
+                        &table_descriptor.summary,
+                        &table_descriptor.columns,
+                    )
+                    .await?;
+            }
+            Some(existing) => {
+                let removed: Vec<&String> = existing
+                    .iter()
+                    .filter(|c| !table_descriptor.columns.iter().any(|d| &d.name == *c))
+                    .collect();
+                if !removed.is_empty() {
+                    warn!(
+                        ?removed,
+                        "columns missing from the descriptor are left in place"
+                    );
+                }
+                snowflake
+                    .update_table(
+                        &db_name,
+                        &table_descriptor.name,
+                        &table_descriptor.summary,
+                        &table_descriptor.columns,
+                        &existing,
+                    )
+                    .await?;
+            }
+        }
+        snowflake
+            .put_masking_policies(&db_name, &table_descriptor.name, &table_descriptor.columns)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn reconcile_s3_prefix(
+        &self,
+        table_descriptor: &TableDescriptor,
+        db_descriptor: &DatabaseDescriptor,
+    ) -> Result<()> {
+        let (bucket, prefix) = &table_location(&table_descriptor, &db_descriptor)?;
+        info!(bucket, prefix, "Reconciling s3 prefix");
+
+        match self.s3_provisioner.get_path_marker(bucket, prefix).await? {
+            None => {
+                info!("s3 prefix is unclaimed, creating it");
+                self.s3_provisioner.create_prefix(bucket, prefix).await?;
+            }
+            Some(marker) if marker.descriptor_id == table_descriptor.id => {
+                debug!(?marker, "s3 prefix already owned by this descriptor");
+            }
+            Some(marker) => {
+                bail!(
+                    "s3://{}/{} is owned by descriptor '{}', refusing to take it over",
+                    bucket,
+                    prefix,
+                    marker.descriptor_id
+                );
+            }
+        }
+
+        self.s3_provisioner
+            .put_path_marker(
+                bucket,
+                prefix,
+                &table_descriptor.id,
+                table_descriptor.revision,
+            )
+            .await?;
+        self.s3_provisioner
+            .put_prefix_expiration(
+                bucket,
+                &table_lifecycle_rule_id(table_descriptor),
+                prefix,
+                table_descriptor
+                    .lifecycle
+                    .as_ref()
+                    .map(|l| l.expire_after_days),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn reconcile_bigquery_table(
+        &self,
+        table_descriptor: &TableDescriptor,
+        db_descriptor: &DatabaseDescriptor,
+    ) -> Result<()> {
+        self.bigquery_provisioner
+            .as_ref()
+            .ok_or_else(|| anyhow!("bigquery isn't configured"))?
+            .put_table(
+                &bigquery_dataset_name(&db_descriptor),
+                &table_descriptor.name,
+                &table_descriptor.summary,
+                &table_descriptor.columns,
+            )
+            .await
+    }
+
+    // Hands the statistics refresh to the flow controller as a flow of its own
+    async fn reconcile_statistics_flow(
+        &self,
+        table_descriptor: &TableDescriptor,
+        db_descriptor: &DatabaseDescriptor,
+    ) -> Result<()> {
+        let flow_id = statistics_flow_id(table_descriptor);
+        let Some(statistics) = &table_descriptor.statistics else {
+            return retire_maintenance_flow(
+                &self.descriptor_store,
+                &self.deployment_state_store,
+                &table_descriptor.id,
+                &flow_id,
+            )
+            .await;
+        };
+
+        // NOTE: columns are always spelled out, `FOR ALL COLUMNS` doesn't get past sql validation
+        let columns: Vec<&str> = match statistics.columns.as_slice() {
+            [] => table_descriptor
+                .columns
+                .iter()
+                .map(|c| c.name.as_str())
+                .collect(),
+            columns => columns.iter().map(String::as_str).collect(),
+        };
+        let flow = FlowDescriptor {
+            id: flow_id,
+            name: statistics_flow_name(table_descriptor, db_descriptor),
+            summary: format!("Refreshes column statistics of {}", table_descriptor.name),
+            condition: None,
+            conditions: statistics.conditions.clone(),
+            condition_mode: FlowConditionMode::Any,
+            steps: vec![FlowStep {
+                name: "analyze".to_string(),
+                summary: "Compute column statistics".to_string(),
+                parents: vec![],
+                timeout: "1h".to_string(),
+                transformation: FlowStepTransformation::Sql(FlowSqlTransformation {
+                    sql: format!(
+                        "ANALYZE TABLE {}.{} COMPUTE STATISTICS FOR COLUMNS {}",
+                        glue_database_name(db_descriptor),
+                        table_descriptor.name,
+                        columns.join(", ")
+                    ),
+                }),
+                image: None,
+                resources: None,
+                retries: Some(1),
+                retry_delay: None,
+            }],
+            backend: statistics.backend,
+            priority: DescriptorPriority::Low,
+            labels: table_descriptor.managed_flow_labels(&db_descriptor.labels),
+            owner: table_descriptor
+                .owner
+                .clone()
+                .or_else(|| db_descriptor.owner.clone()),
+        };
+
+        put_maintenance_flow(
+            &self.descriptor_store,
+            &self.deployment_state_store,
+            self.policy_checker.as_ref(),
+            &self.approval_gate,
+            &table_descriptor.id,
+            &flow,
+        )
+        .await
+    }
+
+    // Compaction, snapshot expiry and orphan file cleanup, chained in that order in one flow
+    async fn reconcile_maintenance_flow(
+        &self,
+        table_descriptor: &TableDescriptor,
+        db_descriptor: &DatabaseDescriptor,
+    ) -> Result<()> {
+        let flow_id = maintenance_flow_id(table_descriptor);
+        let (Some(maintenance), Some(format), Some(conf)) = (
+            &table_descriptor.maintenance,
+            table_descriptor.format,
+            &self.maintenance,
+        ) else {
+            return retire_maintenance_flow(
+                &self.descriptor_store,
+                &self.deployment_state_store,
+                &table_descriptor.id,
+                &flow_id,
+            )
+            .await;
+        };
+
+        let table = format!(
+            "{}.{}",
+            glue_database_name(db_descriptor),
+            table_descriptor.name
+        );
+        let actions = [
+            ("compact", maintenance.compaction, None),
+            (
+                "expire_snapshots",
+                maintenance.snapshot_retention_hours.is_some(),
+                maintenance.snapshot_retention_hours,
+            ),
+            (
+                "remove_orphan_files",
+                maintenance.orphan_file_retention_hours.is_some(),
+                maintenance.orphan_file_retention_hours,
+            ),
+        ];
+        let mut steps: Vec<FlowStep> = vec![];
+        for (action, _, retain_hours) in actions.into_iter().filter(|(_, enabled, _)| *enabled) {
+            let mut args = vec![
+                "--table".to_string(),
+                table.clone(),
+                "--format".to_string(),
+                format.as_str().to_string(),
+                "--action".to_string(),
+                action.to_string(),
+            ];
+            if let Some(hours) = retain_hours {
+                args.extend(["--retain-hours".to_string(), hours.to_string()]);
+            }
+            steps.push(FlowStep {
+                name: action.to_string(),
+                summary: format!("Run {} on {}", action, table),
+                parents: steps.last().map(|s| s.name.clone()).into_iter().collect(),
+                timeout: "6h".to_string(),
+                transformation: FlowStepTransformation::Spark(FlowSparkTransformation {
+                    application: conf.application.clone(),
+                    main_class: None,
+                    args,
+                    conf: Default::default(),
+                }),
+                image: None,
+                resources: None,
+                retries: Some(1),
+                retry_delay: None,
+            });
+        }
+
+        let flow = FlowDescriptor {
+            id: flow_id,
+            name: maintenance_flow_name(table_descriptor, db_descriptor),
+            summary: format!("Maintains {} table {}", format.as_str(), table),
+            condition: None,
+            conditions: vec![FlowCondition::Cron(FlowCronCondition {
+                schedule: maintenance.schedule.clone(),
+            })],
+            condition_mode: FlowConditionMode::Any,
+            steps,
+            backend: maintenance.backend,
+            priority: DescriptorPriority::Low,
+            labels: table_descriptor.managed_flow_labels(&db_descriptor.labels),
+            owner: table_descriptor
+                .owner
+                .clone()
+                .or_else(|| db_descriptor.owner.clone()),
+        };
+
+        put_maintenance_flow(
+            &self.descriptor_store,
+            &self.deployment_state_store,
+            self.policy_checker.as_ref(),
+            &self.approval_gate,
+            &table_descriptor.id,
+            &flow,
+        )
+        .await
+    }
+
+    // Registers the glue table's location in unity catalog when it's configured
+    // Validates the table against its rules after each run of the flow loading it. The suite is
+    // fetched from basin when the run starts so it always matches the stored descriptor.
+    async fn reconcile_quality_flow(
+        &self,
+        table_descriptor: &TableDescriptor,
+        db_descriptor: &DatabaseDescriptor,
+    ) -> Result<()> {
+        let flow_id = quality_flow_id(table_descriptor);
+        let (Some(quality), Some(conf)) = (&table_descriptor.quality, &self.quality) else {
+            return retire_maintenance_flow(
+                &self.descriptor_store,
+                &self.deployment_state_store,
+                &table_descriptor.id,
+                &flow_id,
+            )
+            .await;
+        };
+
+        let table = format!(
+            "{}.{}",
+            glue_database_name(db_descriptor),
+            table_descriptor.name
+        );
+        let api_url = format!(
+            "{}/api/v1/table/{}/quality",
+            conf.basin_url.trim_end_matches('/'),
+            table_descriptor.id
+        );
+        let mut args = vec![
+            "--table".to_string(),
+            table.clone(),
+            "--suite-url".to_string(),
+            format!("{}/suite", api_url),
+            "--results-url".to_string(),
+            format!("{}/results", api_url),
+        ];
+        if quality.fail_on_error {
+            args.push("--fail-on-error".to_string());
+        }
+
+        let flow = FlowDescriptor {
+            id: flow_id,
+            name: quality_flow_name(table_descriptor, db_descriptor),
+            summary: format!("Validates {} against its quality rules", table),
+            condition: None,
+            conditions: vec![FlowCondition::Upstream(FlowUpstreamCondition {
+                upstream: quality.flow.clone(),
+                kind: FlowUpstreamKind::Flow,
+            })],
+            condition_mode: FlowConditionMode::Any,
+            steps: vec![FlowStep {
+                name: "validate".to_string(),
+                summary: format!("Validate {} with great expectations", table),
+                parents: vec![],
+                timeout: "1h".to_string(),
+                transformation: FlowStepTransformation::Spark(FlowSparkTransformation {
+                    application: conf.application.clone(),
+                    main_class: None,
+                    args,
+                    conf: Default::default(),
+                }),
+                image: None,
+                resources: None,
+                retries: Some(1),
+                retry_delay: None,
+            }],
+            backend: quality.backend,
+            priority: DescriptorPriority::Low,
+            labels: table_descriptor.managed_flow_labels(&db_descriptor.labels),
+            owner: table_descriptor
+                .owner
+                .clone()
+                .or_else(|| db_descriptor.owner.clone()),
+        };
+
+        put_maintenance_flow(
+            &self.descriptor_store,
+            &self.deployment_state_store,
+            self.policy_checker.as_ref(),
+            &self.approval_gate,
+            &table_descriptor.id,
+            &flow,
+        )
+        .await
+    }
+
+    // Masked columns are left out of the table's one data cells filter, the configured principals
+    // select through it. Tables with nothing masked have theirs removed.
+    async fn reconcile_column_filter(
+        &self,
+        table_descriptor: &TableDescriptor,
+        db_descriptor: &DatabaseDescriptor,
+    ) -> Result<()> {
+        let masked: Vec<String> = table_descriptor
+            .columns
+            .iter()
+            .filter(|c| c.masking.is_some())
+            .map(|c| c.name.clone())
+            .collect();
+        let Some(masking) = &self.masking else {
+            ensure!(masked.is_empty(), "column masking isn't configured");
+            return Ok(());
+        };
+        let filter = ColumnFilter {
+            catalog_id: masking.catalog_id.clone(),
+            database: glue_database_name(db_descriptor),
+            table: table_descriptor.name.clone(),
+            name: MASKING_FILTER_NAME.to_string(),
+        };
+
+        if masked.is_empty() {
+            return self
+                .lake_formation_provisioner
+                .delete_column_filter(&filter)
+                .await;
+        }
+        self.lake_formation_provisioner
+            .put_column_filter(&filter, &masked)
+            .await?;
+        // NOTE: grants go with a filter when it's dropped, so they're repeated every reconcile
+        for principal in masking.lake_formation_principals.iter() {
+            self.lake_formation_provisioner
+                .grant_column_filter(principal, &filter)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn reconcile_unity_catalog_table(
+        &self,
+        table_descriptor: &TableDescriptor,
+        db_descriptor: &DatabaseDescriptor,
+    ) -> Result<()> {
+        let Some(unity_catalog) = &self.unity_catalog_provisioner else {
+            return Ok(());
+        };
+
+        let (bucket, prefix) = table_location(&table_descriptor, &db_descriptor)?;
+        // NOTE: tables can live outside their database's bucket
+        unity_catalog.put_external_location(&bucket).await?;
+        unity_catalog
+            .put_external_table(
+                &glue_database_name(&db_descriptor),
+                &table_descriptor.name,
+                &table_descriptor.summary,
+                &format!("s3://{}/{}", bucket, prefix),
+                &table_descriptor.columns,
+            )
+            .await
+    }
+
+    // NOTE: best effort, trino sees the change by itself once its metastore cache expires
+    async fn refresh_trino_table(
+        &self,
+        table_descriptor: &TableDescriptor,
+        db_descriptor: &DatabaseDescriptor,
+    ) {
+        let Some(trino) = &self.trino_provisioner else {
+            return;
+        };
+
+        if let Err(e) = trino
+            .refresh_table(&glue_database_name(&db_descriptor), &table_descriptor.name)
+            .await
+        {
+            warn!(?e, "failed to refresh table in trino");
+        }
+    }
+
+    // NOTE: best effort, the catalog catches up on the table's next reconcile
+    async fn push_datahub_table(
+        &self,
+        table_descriptor: &TableDescriptor,
+        db_descriptor: &DatabaseDescriptor,
+    ) {
+        let Some(datahub) = &self.datahub_provisioner else {
+            return;
+        };
+
+        let result = match table_location(table_descriptor, db_descriptor) {
+            Ok((bucket, prefix)) => {
+                datahub
+                    .put_dataset(&DataHubDataset {
+                        platform: self.storage.catalog_for(db_descriptor).as_str(),
+                        database: glue_database_name(&db_descriptor),
+                        table: table_descriptor.name.clone(),
+                        description: table_descriptor.summary.clone(),
+                        location: format!("s3://{}/{}", bucket, prefix),
+                        columns: table_descriptor.columns.clone(),
+                        owner: table_descriptor
+                            .owner
+                            .clone()
+                            .or_else(|| db_descriptor.owner.clone()),
+                        metadata: table_descriptor.metadata.clone(),
+                    })
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            warn!(?e, "failed to push table to datahub");
+        }
     }
 
     async fn reconcile_glue_table(
@@ -131,107 +1169,652 @@ impl TableController {
         table_descriptor: &TableDescriptor,
         db_descriptor: &DatabaseDescriptor,
     ) -> Result<()> {
-        let db_name = Self::glue_name_for(&db_descriptor);
+        let db_name = glue_database_name(&db_descriptor);
 
         let table = self
-            .glue_client
-            .get_table()
-            .database_name(db_name)
-            .name(&table_descriptor.name)
-            .send()
-            .await
-            .map_err(|e| e.into_service_error());
+            .glue_provisioner
+            .get_table(&db_name, &table_descriptor.name)
+            .await?;
 
         match table {
-            Err(GetTableError {
-                kind: GetTableErrorKind::EntityNotFoundException(_),
-                ..
-            }) => {
+            None => {
+                self.quota_checker
+                    .ensure_capacity(QuotaResource::GlueTables)
+                    .await?;
                 self.create_table(table_descriptor, db_descriptor).await?;
             }
-            Ok(_) => {
-                self.update_table(table_descriptor, db_descriptor).await?;
+            Some(t) => {
+                let current_location = t
+                    .table()
+                    .and_then(|t| t.storage_descriptor())
+                    .and_then(|s| s.location());
+                let (bucket, prefix) = table_location(&table_descriptor, &db_descriptor)?;
+                if let Some(current) = current_location
+                    && current != format!("s3://{}/{}", bucket, prefix)
+                {
+                    self.relocate(table_descriptor, current, &bucket, &prefix)
+                        .await?;
+                }
+
+                let parameters = t.table().and_then(|t| t.parameters());
+                self.update_table(table_descriptor, db_descriptor, parameters)
+                    .await?;
             }
-            Err(e) => return Err(e.into()),
         }
 
         Ok(())
     }
 
-    async fn create_table(
+    async fn reconcile_hive_table(
         &self,
         table_descriptor: &TableDescriptor,
         db_descriptor: &DatabaseDescriptor,
     ) -> Result<()> {
-        let db_name = Self::glue_name_for(&db_descriptor);
-        let table_input = Self::build_table_input(table_descriptor, db_descriptor);
+        let hive = self
+            .hive_metastore_provisioner
+            .as_ref()
+            .ok_or_else(|| anyhow!("hive metastore isn't configured"))?;
+        let db_name = glue_database_name(&db_descriptor);
+        let (bucket, prefix) = table_location(&table_descriptor, &db_descriptor)?;
+        let location = format!("s3://{}/{}", bucket, prefix);
+        let hash = descriptor_hash(table_descriptor);
 
-        self.glue_client
-            .create_table()
-            .database_name(db_name)
-            .table_input(table_input)
-            .send()
-            .await
-            .map_err(|e| e.into_service_error())?;
+        let table = HiveTableInput {
+            name: table_descriptor.name.clone(),
+            columns: table_descriptor
+                .columns
+                .iter()
+                .map(|c| HiveColumn {
+                    name: c.name.clone(),
+                    r#type: hive_type(&c.codec.kind).to_string(),
+                    comment: c.summary.clone(),
+                })
+                .collect(),
+            location: location.clone(),
+            parameters: BTreeMap::from([
+                ("comment".to_string(), table_descriptor.summary.clone()),
+                (DESCRIPTOR_HASH_KEY.to_string(), hash.clone()),
+            ]),
+        };
+
+        match hive.get_table(&db_name, &table_descriptor.name).await? {
+            None => {
+                info!("hive table does not exist, creating it");
+                hive.create_table(&db_name, table).await?;
+            }
+            Some(t) if t.parameters.get(DESCRIPTOR_HASH_KEY) == Some(&hash) => {
+                debug!(?t, "hive table is up to date");
+            }
+            Some(t) => {
+                if let Some(current) = &t.location
+                    && current != &location
+                {
+                    self.relocate(table_descriptor, current, &bucket, &prefix)
+                        .await?;
+                }
+                hive.update_table(&db_name, table).await?;
+            }
+        }
 
         Ok(())
     }
 
-    async fn update_table(
+    // Queries the table through athena, catching serde and location mistakes glue accepts without
+    // complaint. Only rerun once the descriptor changes, or while a failure is failing reconciles.
+    async fn smoke_test_table(
         &self,
         table_descriptor: &TableDescriptor,
         db_descriptor: &DatabaseDescriptor,
     ) -> Result<()> {
-        let db_name = Self::glue_name_for(&db_descriptor);
-        let table_input = Self::build_table_input(table_descriptor, db_descriptor);
+        let Some(athena) = &self.athena_provisioner else {
+            return Ok(());
+        };
 
-        self.glue_client
-            .update_table()
-            .database_name(db_name)
-            .table_input(table_input)
-            .send()
-            .await
-            .map_err(|e| e.into_service_error())?;
+        let hash = descriptor_hash(table_descriptor);
+        if let Some(previous) = self
+            .smoke_test_store
+            .get_result(&table_descriptor.id)
+            .await?
+            && previous.descriptor_hash == hash
+            && (previous.succeeded || !self.fail_on_smoke_test)
+        {
+            return Ok(());
+        }
+
+        let db_name = glue_database_name(db_descriptor);
+        let query = format!(
+            "SELECT * FROM \"{}\".\"{}\" LIMIT 1",
+            db_name, table_descriptor.name
+        );
+        let outcome = athena.run_query(&db_name, &query).await;
+        match &outcome {
+            Ok(()) => info!("Smoke test query succeeded"),
+            Err(e) => warn!(?e, "Smoke test query failed"),
+        }
+        self.smoke_test_store
+            .set_result(
+                &table_descriptor.id,
+                &SmokeTestResult {
+                    query,
+                    descriptor_hash: hash,
+                    succeeded: outcome.is_ok(),
+                    error: outcome.as_ref().err().map(|e| format!("{:#}", e)),
+                    ran_at: Utc::now(),
+                },
+            )
+            .await?;
+
+        match outcome {
+            Err(e) if self.fail_on_smoke_test => Err(e.context("smoke test query failed")),
+            _ => Ok(()),
+        }
+    }
+
+    // Drops the catalog's table and releases its location, the data under it is left in place
+    async fn teardown_glue_table(
+        &self,
+        table_descriptor: &TableDescriptor,
+        db_descriptor: &DatabaseDescriptor,
+    ) -> Result<()> {
+        let db_name = glue_database_name(&db_descriptor);
+        match self.storage.catalog_for(db_descriptor) {
+            Catalog::Glue => {
+                if let Some(masking) = &self.masking {
+                    self.lake_formation_provisioner
+                        .delete_column_filter(&ColumnFilter {
+                            catalog_id: masking.catalog_id.clone(),
+                            database: db_name.clone(),
+                            table: table_descriptor.name.clone(),
+                            name: MASKING_FILTER_NAME.to_string(),
+                        })
+                        .await?;
+                }
+                self.glue_provisioner
+                    .delete_table(&db_name, &table_descriptor.name)
+                    .await?;
+            }
+            Catalog::Hive => {
+                self.hive_metastore_provisioner
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("hive metastore isn't configured"))?
+                    .delete_table(&db_name, &table_descriptor.name)
+                    .await?;
+            }
+        }
+
+        let (bucket, prefix) = table_location(&table_descriptor, &db_descriptor)?;
+        self.s3_provisioner
+            .put_prefix_expiration(
+                &bucket,
+                &table_lifecycle_rule_id(table_descriptor),
+                &prefix,
+                None,
+            )
+            .await?;
+        if let Some(marker) = self
+            .s3_provisioner
+            .get_path_marker(&bucket, &prefix)
+            .await?
+            && marker.descriptor_id == table_descriptor.id
+        {
+            self.s3_provisioner
+                .delete_path_marker(&bucket, &prefix)
+                .await?;
+        }
 
         Ok(())
     }
 
+    async fn relocate(
+        &self,
+        table_descriptor: &TableDescriptor,
+        from: &str,
+        to_bucket: &str,
+        to_prefix: &str,
+    ) -> Result<()> {
+        info!(from, to_bucket, to_prefix, "Table location changed");
+        // NOTE: glue can hold locations basin never provisioned, only migrate what we can address
+        let Some((from_bucket, from_prefix)) = split_s3_uri(from) else {
+            warn!(
+                from,
+                "previous location is not an s3 prefix, skipping migration"
+            );
+            return Ok(());
+        };
+
+        if self.storage.copy_on_relocate {
+            let copied = self
+                .s3_provisioner
+                .copy_prefix(&from_bucket, &from_prefix, to_bucket, to_prefix)
+                .await?;
+            info!(copied, "copied objects to new table location");
+        } else {
+            warn!(
+                from,
+                "copy_on_relocate is disabled, existing data is left at the old location"
+            );
+        }
+
+        // Release the old location so another table can claim it, data is left in place
+        if let Some(marker) = self
+            .s3_provisioner
+            .get_path_marker(&from_bucket, &from_prefix)
+            .await?
+            && marker.descriptor_id == table_descriptor.id
+        {
+            self.s3_provisioner
+                .delete_path_marker(&from_bucket, &from_prefix)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn create_table(
+        &self,
+        table_descriptor: &TableDescriptor,
+        db_descriptor: &DatabaseDescriptor,
+    ) -> Result<()> {
+        let db_name = glue_database_name(&db_descriptor);
+        let table_input = Self::build_table_input(table_descriptor, db_descriptor, None)?;
+
+        self.glue_provisioner
+            .create_table(&db_name, table_input)
+            .await
+    }
+
+    async fn update_table(
+        &self,
+        table_descriptor: &TableDescriptor,
+        db_descriptor: &DatabaseDescriptor,
+        current_parameters: Option<&HashMap<String, String>>,
+    ) -> Result<()> {
+        let db_name = glue_database_name(&db_descriptor);
+        let table_input =
+            Self::build_table_input(table_descriptor, db_descriptor, current_parameters)?;
+
+        self.glue_provisioner
+            .update_table(&db_name, table_input)
+            .await
+    }
+
     fn build_table_input(
         table_descriptor: &TableDescriptor,
         db_descriptor: &DatabaseDescriptor,
-    ) -> TableInput {
+        current_parameters: Option<&HashMap<String, String>>,
+    ) -> Result<TableInput> {
         let mut storage_descriptor_builder = StorageDescriptor::builder();
+        let mut partition_keys = Vec::new();
         for col_desc in table_descriptor.columns.iter() {
-            storage_descriptor_builder = storage_descriptor_builder.columns(
-                Column::builder()
-                    .name(&col_desc.name)
-                    // TODO: don't abuse the name lol - write a function to convert
-                    .r#type(format!("{:?}", col_desc.codec.kind).to_ascii_lowercase())
-                    .comment(&col_desc.summary)
-                    .build(),
-            );
+            let mut column = Column::builder()
+                .name(&col_desc.name)
+                .r#type(glue_type(&col_desc.codec.kind))
+                .comment(&col_desc.summary);
+            // NOTE: glue and athena don't apply these, nor do iceberg or delta writers read them
+            //       from glue. They're recorded for whatever writes the table to apply itself.
+            if let Some(default) = &col_desc.default {
+                column = column.parameters(COLUMN_DEFAULT_KEY, default);
+            }
+            if let Some(generated) = &col_desc.generated {
+                column = column.parameters(COLUMN_GENERATED_KEY, generated);
+            }
+            if let Some(metadata) = &col_desc.metadata {
+                for (key, value) in metadata_parameters(metadata)? {
+                    column = column.parameters(key, value);
+                }
+            }
+            // NOTE: glue keeps partition keys apart from the columns stored in the files
+            if table_descriptor.partitioned_by.contains(&col_desc.name) {
+                partition_keys.push(column.build());
+            } else {
+                storage_descriptor_builder = storage_descriptor_builder.columns(column.build());
+            }
         }
-        storage_descriptor_builder = storage_descriptor_builder.location(format!(
-            "s3://{}/{}",
-            Self::s3_name_for(&db_descriptor),
-            table_descriptor.name
-        ));
+        partition_keys.sort_by_key(|c: &Column| {
+            table_descriptor
+                .partitioned_by
+                .iter()
+                .position(|key| Some(key.as_str()) == c.name())
+        });
+        let (bucket, prefix) = table_location(&table_descriptor, &db_descriptor)?;
+        storage_descriptor_builder =
+            storage_descriptor_builder.location(format!("s3://{}/{}", bucket, prefix));
 
         let storage_descriptor = storage_descriptor_builder.build();
 
-        TableInput::builder()
+        // NOTE: iceberg and delta writers keep their metadata (e.g. iceberg's metadata_location)
+        //       in the table's parameters, replacing them would orphan the table's data
+        let mut parameters: HashMap<String, String> = match table_descriptor.format {
+            Some(_) => current_parameters.cloned().unwrap_or_default(),
+            None => HashMap::new(),
+        };
+        match table_descriptor.format {
+            Some(TableFormat::Iceberg) => {
+                parameters.insert("table_type".to_string(), "ICEBERG".to_string());
+            }
+            Some(TableFormat::Delta) => {
+                parameters.insert(
+                    "spark.sql.sources.provider".to_string(),
+                    "delta".to_string(),
+                );
+            }
+            None => {}
+        }
+        if let Some(compression) = table_descriptor.compression {
+            parameters.insert(
+                "compressionType".to_string(),
+                compression.as_str().to_string(),
+            );
+            if table_descriptor.format == Some(TableFormat::Iceberg) {
+                parameters.insert(
+                    "write.parquet.compression-codec".to_string(),
+                    compression.as_str().to_string(),
+                );
+            }
+        }
+        if let Some(classification) = table_descriptor.classification {
+            parameters.insert(
+                TABLE_CLASSIFICATION_KEY.to_string(),
+                classification.as_str().to_string(),
+            );
+        }
+        // NOTE: iceberg and delta tables start from their current parameters, so metadata that's
+        //       been dropped from the descriptor has to be cleared
+        for key in [GLOSSARY_TERMS_KEY, DOCS_KEY, DEPRECATION_KEY] {
+            parameters.remove(key);
+        }
+        if let Some(metadata) = &table_descriptor.metadata {
+            parameters.extend(metadata_parameters(metadata)?);
+        }
+        if let Some(projection) = &table_descriptor.partition_projection {
+            parameters.extend(projection_parameters(table_descriptor, projection)?);
+        }
+        parameters.insert(
+            DESCRIPTOR_HASH_KEY.to_string(),
+            descriptor_hash(table_descriptor),
+        );
+
+        Ok(TableInput::builder()
             .name(&table_descriptor.name)
             .description(&table_descriptor.summary)
             .storage_descriptor(storage_descriptor)
-            .build()
+            .set_partition_keys(Some(partition_keys))
+            .set_parameters(Some(parameters))
+            .build())
+    }
+}
+
+// Descriptive metadata as catalog parameters, see the keys for how each is encoded
+fn metadata_parameters(metadata: &DescriptiveMetadata) -> Result<Vec<(String, String)>> {
+    let mut parameters = vec![];
+    if !metadata.glossary_terms.is_empty() {
+        parameters.push((
+            GLOSSARY_TERMS_KEY.to_string(),
+            metadata.glossary_terms.join(","),
+        ));
+    }
+    if !metadata.docs.is_empty() {
+        parameters.push((DOCS_KEY.to_string(), serde_json::to_string(&metadata.docs)?));
+    }
+    if let Some(deprecation) = &metadata.deprecation {
+        parameters.push((
+            DEPRECATION_KEY.to_string(),
+            serde_json::to_string(deprecation)?,
+        ));
+    }
+    Ok(parameters)
+}
+
+// Athena's partition projection parameters for the table's partition keys, erroring when a key's
+// type can't be projected or it's missing what its projection needs
+fn projection_parameters(
+    table: &TableDescriptor,
+    projection: &PartitionProjection,
+) -> Result<Vec<(String, String)>> {
+    ensure!(
+        !table.partitioned_by.is_empty(),
+        "only partitioned tables can be projected"
+    );
+    for key in projection.keys.keys() {
+        ensure!(
+            table.partitioned_by.contains(key),
+            "'{}' isn't a partition key",
+            key
+        );
+    }
+
+    // NOTE: auto is the only mode, matched so adding another points here
+    let ProjectionMode::Auto = projection.projection;
+    let mut parameters = vec![("projection.enabled".to_string(), "true".to_string())];
+    for column in table.partition_columns() {
+        let key = &column.name;
+        let hints = projection.keys.get(key).cloned().unwrap_or_default();
+        let param = |name: &str| format!("projection.{}.{}", key, name);
+        match column.codec.kind {
+            TableColumnType::Date => {
+                let Some(start) = hints.start else {
+                    bail!("date key '{}' needs a start", key);
+                };
+                parameters.extend([
+                    (param("type"), "date".to_string()),
+                    (param("range"), format!("{},NOW", start.format("%Y-%m-%d"))),
+                    (param("format"), "yyyy-MM-dd".to_string()),
+                    (param("interval"), "1".to_string()),
+                    (param("interval.unit"), "DAYS".to_string()),
+                ]);
+            }
+            TableColumnType::String => {
+                ensure!(
+                    !hints.values.is_empty(),
+                    "string key '{}' needs its values",
+                    key
+                );
+                ensure!(
+                    hints
+                        .values
+                        .iter()
+                        .all(|v| !v.is_empty() && !v.contains(',')),
+                    "values of '{}' can't be empty or have a comma",
+                    key
+                );
+                parameters.extend([
+                    (param("type"), "enum".to_string()),
+                    (param("values"), hints.values.join(",")),
+                ]);
+            }
+            TableColumnType::Int | TableColumnType::Long => {
+                let Some((low, high)) = hints.range else {
+                    bail!("integer key '{}' needs a range", key);
+                };
+                ensure!(low <= high, "range of '{}' is empty", key);
+                parameters.extend([
+                    (param("type"), "integer".to_string()),
+                    (param("range"), format!("{},{}", low, high)),
+                ]);
+            }
+            TableColumnType::Boolean => parameters.extend([
+                (param("type"), "enum".to_string()),
+                (param("values"), "true,false".to_string()),
+            ]),
+            ref kind => bail!("{:?} key '{}' can't be projected", kind, key),
+        }
+    }
+    Ok(parameters)
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_config::SdkConfig;
+    use aws_sdk_glue::{model::Table, output::GetTableOutput};
+    use mockall::predicate::eq;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        config::{RedisConf, RedisTopology},
+        provisioner::glue::MockGlueTableClient,
+    };
+
+    // NOTE: nothing listens on the redis port, the stores are only there to build the controller
+    async fn controller(glue: MockGlueTableClient) -> TableController {
+        let redis = RedisConf {
+            topology: RedisTopology::Single {
+                url: "redis://127.0.0.1:1".to_string(),
+            },
+            key_prefix: String::new(),
+        };
+        let aws = SdkConfig::builder().build();
+        TableController {
+            storage: StorageConf::default(),
+            allowed_location_buckets: vec![],
+            maintenance: None,
+            quality: None,
+            masking: None,
+            descriptor_store: RedisDescriptorStore::new(&redis).await.unwrap(),
+            deployment_state_store: RedisDeploymentStateStore::new(&redis).await.unwrap(),
+            policy_checker: None,
+            approval_gate: ApprovalGate::for_tests(&redis).await,
+            glue_provisioner: Box::new(glue),
+            hive_metastore_provisioner: None,
+            lake_formation_provisioner: LakeFormationProvisioner::new(&aws),
+            s3_provisioner: S3Provisioner::new(&aws),
+            quota_checker: QuotaChecker::new(None, &aws),
+            snowflake_provisioner: None,
+            bigquery_provisioner: None,
+            unity_catalog_provisioner: None,
+            trino_provisioner: None,
+            datahub_provisioner: None,
+            athena_provisioner: None,
+            fail_on_smoke_test: false,
+            smoke_test_store: RedisSmokeTestStore::new(&redis).await.unwrap(),
+        }
     }
 
-    // TODO: dedupe between this and db controller
-    fn glue_name_for(descriptor: &DatabaseDescriptor) -> String {
-        format!("zone_{}", descriptor.name)
+    fn database() -> DatabaseDescriptor {
+        serde_json::from_value(json!({
+            "id": "db-sales",
+            "name": "sales",
+            "summary": "sales data",
+            "engine": "glue",
+        }))
+        .unwrap()
     }
 
-    fn s3_name_for(descriptor: &DatabaseDescriptor) -> String {
-        format!("cz-vaporeon-db-{}", descriptor.name.replace("_", "-"))
+    fn table() -> TableDescriptor {
+        serde_json::from_value(json!({
+            "id": "table-orders",
+            "name": "orders",
+            "summary": "orders placed",
+            "database": "db-sales",
+            "columns": [{
+                "id": "order-id",
+                "name": "order_id",
+                "summary": "order id",
+                "codec": { "type": "Long" },
+                "nullable": false,
+            }],
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn reconcile_waits_for_a_missing_database() {
+        // NOTE: the mock fails the test on any call, nothing should be provisioned
+        let e = controller(MockGlueTableClient::new())
+            .await
+            .reconcile_with_database(&table(), None)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<ControllerReconciliationError>(),
+            Some(ControllerReconciliationError::DependencyMissing(db)) if db == "db-sales"
+        ));
+    }
+
+    #[tokio::test]
+    async fn reconcile_glue_table_creates_a_missing_table() {
+        let mut glue = MockGlueTableClient::new();
+        glue.expect_get_table()
+            .with(eq("zone_sales"), eq("orders"))
+            .returning(|_, _| Ok(None));
+        glue.expect_create_table()
+            .withf(|db, input| {
+                db.to_string() == "zone_sales"
+                    && input.name() == Some("orders")
+                    && input.storage_descriptor().and_then(|s| s.location())
+                        == Some("s3://cz-vaporeon-db-sales/orders")
+            })
+            .times(1)
+            .returning(|_, _| Ok(()));
+        glue.expect_update_table().never();
+
+        controller(glue)
+            .await
+            .reconcile_glue_table(&table(), &database())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn reconcile_glue_table_updates_a_table_in_place() {
+        let mut glue = MockGlueTableClient::new();
+        glue.expect_get_table().returning(|_, _| {
+            Ok(Some(
+                GetTableOutput::builder()
+                    .table(
+                        Table::builder()
+                            .name("orders")
+                            .storage_descriptor(
+                                StorageDescriptor::builder()
+                                    .location("s3://cz-vaporeon-db-sales/orders")
+                                    .build(),
+                            )
+                            .build(),
+                    )
+                    .build(),
+            ))
+        });
+        glue.expect_update_table().times(1).returning(|_, _| Ok(()));
+        glue.expect_create_table().never();
+
+        controller(glue)
+            .await
+            .reconcile_glue_table(&table(), &database())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn reconcile_glue_table_fails_when_glue_fails_after_finding_the_table() {
+        let mut glue = MockGlueTableClient::new();
+        glue.expect_get_table()
+            .returning(|_, _| Ok(Some(GetTableOutput::builder().build())));
+        glue.expect_update_table()
+            .returning(|_, _| Err(anyhow!("glue is down")));
+
+        let e = controller(glue)
+            .await
+            .reconcile_glue_table(&table(), &database())
+            .await
+            .unwrap_err();
+        assert_eq!(e.to_string(), "glue is down");
+    }
+
+    #[tokio::test]
+    async fn teardown_keeps_the_location_claimed_when_glue_fails() {
+        // NOTE: s3 isn't reachable either, an s3 error would mean the location was released
+        //       before the table was gone
+        let mut glue = MockGlueTableClient::new();
+        glue.expect_delete_table()
+            .with(eq("zone_sales"), eq("orders"))
+            .times(1)
+            .returning(|_, _| Err(anyhow!("glue is down")));
+
+        let e = controller(glue)
+            .await
+            .teardown_glue_table(&table(), &database())
+            .await
+            .unwrap_err();
+        assert_eq!(e.to_string(), "glue is down");
     }
 }
@@ -0,0 +1,218 @@
+use std::{thread, time::Duration};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{debug, info};
+use wasmtime::{Config, Engine, Instance, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+use super::error::ControllerReconciliationError;
+use crate::{
+    config::{BasinConfig, ValidationPluginConf},
+    fluid::descriptor::DescriptorKind,
+    metrics,
+};
+
+// How often the engine's epoch is bumped, plugin timeouts are counted in these
+const EPOCH_TICK: Duration = Duration::from_millis(10);
+
+// Team supplied validation compiled to wasm, run in the validate stage after the controller's
+// own validation. A plugin exports:
+//   memory
+//   alloc(len: i32) -> i32, space for the input
+//   validate(ptr: i32, len: i32) -> i64, the verdict's `ptr << 32 | len`
+// It's handed `{"kind", "descriptor"}` as json and answers with a json verdict of
+// `{"valid": bool, "errors": [{"path", "message"}]}`. Nothing is imported, plugins can't reach
+// outside their own memory.
+pub struct ValidationPlugins {
+    engine: Engine,
+    plugins: Vec<Plugin>,
+}
+
+struct Plugin {
+    conf: ValidationPluginConf,
+    module: Module,
+}
+
+#[derive(Deserialize)]
+struct Verdict {
+    valid: bool,
+    #[serde(default)]
+    errors: Vec<VerdictError>,
+}
+
+#[derive(Deserialize)]
+struct VerdictError {
+    #[serde(default)]
+    path: Option<String>,
+    message: String,
+}
+
+impl ValidationPlugins {
+    // The plugins validating descriptors of the kind, None when there aren't any
+    pub fn new(conf: &BasinConfig, kind: DescriptorKind) -> Result<Option<Self>> {
+        let confs: Vec<&ValidationPluginConf> = conf
+            .validation_plugins
+            .iter()
+            .filter(|p| p.kinds.is_empty() || p.kinds.contains(&kind))
+            .collect();
+        if confs.is_empty() {
+            return Ok(None);
+        }
+
+        let mut engine_conf = Config::new();
+        engine_conf.consume_fuel(true).epoch_interruption(true);
+        let engine = Engine::new(&engine_conf)?;
+
+        let mut plugins = Vec::new();
+        for plugin in confs {
+            let module = Module::from_file(&engine, &plugin.path)
+                .with_context(|| format!("failed to load validation plugin {}", plugin.name))?;
+            info!(plugin = plugin.name, %kind, "loaded validation plugin");
+            plugins.push(Plugin {
+                conf: plugin.clone(),
+                module,
+            });
+        }
+
+        // NOTE: ticks for the life of the process, the reconcilers holding plugins never stop
+        let ticker = engine.clone();
+        thread::spawn(move || loop {
+            thread::sleep(EPOCH_TICK);
+            ticker.increment_epoch();
+        });
+
+        Ok(Some(ValidationPlugins { engine, plugins }))
+    }
+
+    // Rejections come back as ValidationFailed, failures on basin's side of the sandbox as
+    // ControllerError so the descriptor is retried rather than quarantined
+    pub async fn validate<Descriptor: Serialize>(
+        &self,
+        kind: DescriptorKind,
+        descriptor: &Descriptor,
+    ) -> Result<(), ControllerReconciliationError> {
+        let input = serde_json::to_vec(&json!({
+            "kind": kind,
+            "descriptor": descriptor,
+        }))
+        .map_err(|e| ControllerReconciliationError::ControllerError(e.into()))?;
+
+        let mut failures = Vec::new();
+        for plugin in &self.plugins {
+            let engine = self.engine.clone();
+            let module = plugin.module.clone();
+            let conf = plugin.conf.clone();
+            let input = input.clone();
+            // NOTE: plugins run on the blocking pool, their limits keep them from holding it long
+            let verdict = tokio::task::spawn_blocking(move || run(&engine, &module, &conf, &input))
+                .await
+                .map_err(anyhow::Error::from)
+                .and_then(|run| run)
+                .with_context(|| format!("could not run validation plugin {}", plugin.conf.name))
+                .map_err(ControllerReconciliationError::ControllerError)?
+                .with_context(|| format!("validation plugin {} failed", plugin.conf.name));
+
+            let outcome = match &verdict {
+                Ok(v) if v.valid => "valid",
+                Ok(_) => "invalid",
+                Err(_) => "error",
+            };
+            metrics::counter_inc(
+                "basin_validation_plugin_runs_total",
+                &[("plugin", plugin.conf.name.as_str()), ("outcome", outcome)],
+            );
+
+            // NOTE: a plugin that traps or runs out of fuel does so every time, so it fails
+            //       validation rather than being retried
+            match verdict {
+                Ok(v) if v.valid => {}
+                Ok(v) if v.errors.is_empty() => {
+                    failures.push(format!("{}: descriptor is invalid", plugin.conf.name))
+                }
+                Ok(v) => failures.extend(v.errors.into_iter().map(|e| match e.path {
+                    Some(path) => format!("{}: {}: {}", plugin.conf.name, path, e.message),
+                    None => format!("{}: {}", plugin.conf.name, e.message),
+                })),
+                Err(e) => failures.push(format!("{:#}", e)),
+            }
+        }
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(ControllerReconciliationError::ValidationFailed(anyhow!(
+                failures.join("; ")
+            )))
+        }
+    }
+}
+
+struct PluginState {
+    limits: StoreLimits,
+}
+
+// Runs a plugin in a fresh instance, nothing carries over between descriptors. The outer error
+// is the host failing to set up the run, the inner one the plugin misbehaving.
+fn run(
+    engine: &Engine,
+    module: &Module,
+    conf: &ValidationPluginConf,
+    input: &[u8],
+) -> Result<Result<Verdict>> {
+    let mut store = Store::new(
+        engine,
+        PluginState {
+            limits: StoreLimitsBuilder::new()
+                .memory_size(conf.max_memory_mb * 1024 * 1024)
+                .instances(1)
+                .build(),
+        },
+    );
+    store.limiter(|s| &mut s.limits);
+    store.add_fuel(conf.max_fuel)?;
+    store.set_epoch_deadline((conf.timeout_ms / EPOCH_TICK.as_millis() as u64).max(1));
+
+    Ok(call(&mut store, module, conf, input))
+}
+
+// The plugin's side of a run, everything that can go wrong here is down to the plugin
+fn call(
+    store: &mut Store<PluginState>,
+    module: &Module,
+    conf: &ValidationPluginConf,
+    input: &[u8],
+) -> Result<Verdict> {
+    let instance = Instance::new(&mut *store, module, &[])?;
+    let memory = instance
+        .get_memory(&mut *store, "memory")
+        .ok_or_else(|| anyhow!("plugin doesn't export its memory"))?;
+    let alloc = instance.get_typed_func::<i32, i32>(&mut *store, "alloc")?;
+    let validate = instance.get_typed_func::<(i32, i32), i64>(&mut *store, "validate")?;
+
+    let len = i32::try_from(input.len()).context("descriptor too large for a plugin")?;
+    let ptr = alloc.call(&mut *store, len)?;
+    memory.write(&mut *store, ptr as u32 as usize, input)?;
+    let packed = validate.call(&mut *store, (ptr, len))?;
+
+    // NOTE: the length is the plugin's to pick, so it's checked before anything is allocated
+    let (out_ptr, out_len) = ((packed >> 32) as u32 as usize, packed as u32 as usize);
+    if out_len > conf.max_verdict_kb * 1024 {
+        bail!(
+            "plugin returned a verdict of {} bytes, over the {}kb limit",
+            out_len,
+            conf.max_verdict_kb
+        );
+    }
+    if out_ptr
+        .checked_add(out_len)
+        .map_or(true, |end| end > memory.data_size(&*store))
+    {
+        bail!("plugin returned a verdict outside its memory");
+    }
+    let mut out = vec![0; out_len];
+    memory.read(&*store, out_ptr, &mut out)?;
+    debug!(plugin = conf.name, fuel_used = ?store.fuel_consumed(), "validation plugin ran");
+
+    serde_json::from_slice(&out).context("plugin returned an invalid verdict")
+}
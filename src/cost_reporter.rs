@@ -0,0 +1,106 @@
+use anyhow::{anyhow, Result};
+use aws_sdk_costexplorer::model::{DateInterval, Expression, Granularity, TagValues};
+use chrono::{Datelike, Duration, NaiveDate, Utc};
+use serde::Serialize;
+
+use crate::{
+    config::BasinConfig, constants::DESCRIPTOR_ID_TAG_KEY, provisioner::error::classify_aws_error,
+};
+
+const COST_METRIC: &str = "UnblendedCost";
+
+#[derive(Serialize, Debug)]
+pub struct CostReport {
+    pub descriptor_id: String,
+    // Descriptors whose resources the spend is summed over, the descriptor's own id first
+    pub attributed_ids: Vec<String>,
+    pub start: NaiveDate,
+    // Exclusive
+    pub end: NaiveDate,
+    pub amount: f64,
+    pub unit: String,
+}
+
+// Reports spend on the resources tagged with a descriptor's id. Cost explorer only filters on
+// tags activated as cost allocation tags, `basin_descriptor_id` has to be activated in billing
+// for reports to show anything, and tagged spend shows up with a delay of up to a day.
+#[derive(Debug)]
+pub struct CostReporter {
+    client: aws_sdk_costexplorer::Client,
+}
+
+impl CostReporter {
+    pub fn new(conf: &BasinConfig) -> Self {
+        CostReporter {
+            client: aws_sdk_costexplorer::Client::new(&conf.aws_creds),
+        }
+    }
+
+    // Spend on the resources of `descriptor_id` and of the descriptors in `attributed_ids` which
+    // basin runs on its behalf, e.g. a table's maintenance flow
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn month_to_date(
+        &self,
+        descriptor_id: &str,
+        attributed_ids: &[String],
+    ) -> Result<CostReport> {
+        let attributed_ids: Vec<String> = std::iter::once(descriptor_id.to_string())
+            .chain(attributed_ids.iter().cloned())
+            .collect();
+        let today = Utc::now().date_naive();
+        let start = today
+            .with_day(1)
+            .ok_or_else(|| anyhow!("no first day of month for {}", today))?;
+        let end = today + Duration::days(1);
+
+        let output = self
+            .client
+            .get_cost_and_usage()
+            .time_period(
+                DateInterval::builder()
+                    .start(start.to_string())
+                    .end(end.to_string())
+                    .build(),
+            )
+            .granularity(Granularity::Monthly)
+            .metrics(COST_METRIC)
+            .filter(
+                Expression::builder()
+                    .tags(
+                        TagValues::builder()
+                            .key(DESCRIPTOR_ID_TAG_KEY)
+                            .set_values(Some(attributed_ids.clone()))
+                            .build(),
+                    )
+                    .build(),
+            )
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
+        let mut amount = 0.0;
+        let mut unit = None;
+        for metric in output
+            .results_by_time()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|r| r.total().and_then(|t| t.get(COST_METRIC)))
+        {
+            amount += metric
+                .amount()
+                .unwrap_or("0")
+                .parse::<f64>()
+                .map_err(|e| anyhow!("invalid cost amount: {}", e))?;
+            unit = unit.or_else(|| metric.unit().map(|u| u.to_string()));
+        }
+
+        Ok(CostReport {
+            descriptor_id: descriptor_id.to_string(),
+            attributed_ids,
+            start,
+            end,
+            amount,
+            unit: unit.unwrap_or_else(|| "USD".to_string()),
+        })
+    }
+}
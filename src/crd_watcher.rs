@@ -0,0 +1,418 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use chrono::{Duration as ChronoDuration, SecondsFormat, Utc};
+use kube::{
+    api::{Api, ApiResource, DynamicObject, GroupVersionKind, ListParams, Patch, PatchParams},
+    Client, ResourceExt,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::json;
+use tokio::time::{interval, MissedTickBehavior};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    approval_gate::{Admission, ApprovalGate},
+    config::{
+        BasinConfig, ControllersConf, DeletionConf, EventWatcherConf, KubernetesConf, LimitsConf,
+    },
+    constants::CONTROLLER_DISABLED,
+    deployment_state_store::{
+        DeploymentHistory, DeploymentInfo, DeploymentState, DeploymentStateStore,
+        RedisDeploymentStateStore,
+    },
+    descriptor_store::{DescriptorStore, RedisDescriptorStore},
+    fluid::descriptor::{
+        database::DatabaseDescriptor, flow::FlowDescriptor, landing_zone::LandingZoneDescriptor,
+        table::TableDescriptor, DescriptorKind, IdentifiableDescriptor,
+    },
+    payload_limits::PayloadLimited,
+    policy::PolicyChecker,
+    state_events::{StateEventPublisher, StateEventType},
+};
+
+// Ingests descriptors from Database, Table, Flow and LandingZone custom resources, the cr's spec is the
+// descriptor. Deployment states are written back onto each cr as a `Ready` condition so tools
+// watching the cluster (argo cd and friends) can tell when basin has caught up. Each cr carries a
+// finalizer until the descriptor it stored has been torn down, deleting the cr deletes it.
+pub struct CrdWatcher {
+    client: Client,
+    conf: KubernetesConf,
+    deletion: DeletionConf,
+    descriptor_store: RedisDescriptorStore,
+    deployment_state_store: RedisDeploymentStateStore,
+    limits: LimitsConf,
+    controllers: ControllersConf,
+    event_watcher: EventWatcherConf,
+    state_event_publisher: StateEventPublisher,
+    policy_checker: Option<PolicyChecker>,
+    approval_gate: ApprovalGate,
+}
+
+impl CrdWatcher {
+    pub async fn new(conf: &BasinConfig) -> Result<CrdWatcher> {
+        Ok(CrdWatcher {
+            client: Client::try_default().await?,
+            conf: conf.kubernetes.clone(),
+            deletion: conf.deletion.clone(),
+            descriptor_store: RedisDescriptorStore::new(&conf.redis).await?,
+            deployment_state_store: RedisDeploymentStateStore::new(&conf.redis).await?,
+            limits: conf.limits.clone(),
+            controllers: conf.controllers.clone(),
+            event_watcher: conf.event_watcher.clone(),
+            state_event_publisher: StateEventPublisher::new(conf),
+            policy_checker: PolicyChecker::new(conf)?,
+            approval_gate: ApprovalGate::new(conf).await?,
+        })
+    }
+
+    pub async fn ingest_loop(&self) -> ! {
+        let mut ticker = interval(Duration::from_secs(self.conf.poll_interval_secs));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+            info!("Ingesting custom resources");
+
+            if self.event_watcher.ingests(DescriptorKind::Database)
+                && let Err(e) = self
+                    .ingest_kind::<DatabaseDescriptor>(DescriptorKind::Database, "Database")
+                    .await
+            {
+                error!(?e, "error when ingesting database custom resources");
+            }
+            if self.event_watcher.ingests(DescriptorKind::Table)
+                && let Err(e) = self
+                    .ingest_kind::<TableDescriptor>(DescriptorKind::Table, "Table")
+                    .await
+            {
+                error!(?e, "error when ingesting table custom resources");
+            }
+            if self.event_watcher.ingests(DescriptorKind::Flow)
+                && let Err(e) = self
+                    .ingest_kind::<FlowDescriptor>(DescriptorKind::Flow, "Flow")
+                    .await
+            {
+                error!(?e, "error when ingesting flow custom resources");
+            }
+            if self.event_watcher.ingests(DescriptorKind::LandingZone)
+                && let Err(e) = self
+                    .ingest_kind::<LandingZoneDescriptor>(
+                        DescriptorKind::LandingZone,
+                        "LandingZone",
+                    )
+                    .await
+            {
+                error!(?e, "error when ingesting landing zone custom resources");
+            }
+        }
+    }
+
+    async fn ingest_kind<
+        Descriptor: IdentifiableDescriptor + PayloadLimited + Serialize + DeserializeOwned + Sync,
+    >(
+        &self,
+        kind: DescriptorKind,
+        cr_kind: &str,
+    ) -> Result<()> {
+        let resource = ApiResource::from_gvk(&GroupVersionKind::gvk(
+            &self.conf.group,
+            &self.conf.version,
+            cr_kind,
+        ));
+        let api: Api<DynamicObject> = match &self.conf.namespace {
+            Some(namespace) => Api::namespaced_with(self.client.clone(), namespace, &resource),
+            None => Api::all_with(self.client.clone(), &resource),
+        };
+
+        for object in api.list(&ListParams::default()).await? {
+            // NOTE: one broken resource shouldn't hold up the rest
+            let ingested = match object.metadata.deletion_timestamp {
+                Some(_) => self.delete_object(kind, &resource, &object).await,
+                None => self.ingest_object::<Descriptor>(&resource, &object).await,
+            };
+            if let Err(e) = ingested {
+                warn!(
+                    kind = cr_kind,
+                    name = object.name_any(),
+                    ?e,
+                    "failed to ingest custom resource"
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "info", skip_all, fields(name = object.name_any()))]
+    async fn ingest_object<
+        Descriptor: IdentifiableDescriptor + PayloadLimited + Serialize + DeserializeOwned + Sync,
+    >(
+        &self,
+        resource: &ApiResource,
+        object: &DynamicObject,
+    ) -> Result<()> {
+        let uid = object
+            .uid()
+            .ok_or_else(|| anyhow!("custom resource has no uid"))?;
+        self.add_finalizer(resource, object).await?;
+
+        // The cr's uid doubles as the descriptor id unless the spec pins one, which it has to for
+        // other resources to refer to it
+        let mut spec = object
+            .data
+            .get("spec")
+            .cloned()
+            .ok_or_else(|| anyhow!("custom resource has no spec"))?;
+        let spec_fields = spec
+            .as_object_mut()
+            .ok_or_else(|| anyhow!("custom resource spec is not an object"))?;
+        spec_fields.entry("id").or_insert_with(|| json!(uid));
+
+        let descriptor: Descriptor = serde_json::from_value(spec)?;
+        descriptor.check_limits(&self.limits)?;
+        // NOTE: a denied resource is reported on every poll until it's fixed
+        if let Some(policy_checker) = &self.policy_checker {
+            policy_checker.check(&descriptor).await?;
+        }
+
+        // Resources are relisted every poll, only store descriptors which have changed
+        let stored = self
+            .descriptor_store
+            .get_descriptor::<serde_json::Value>(&descriptor.id(), descriptor.kind())
+            .await?;
+        if stored.as_ref() == Some(&serde_json::to_value(&descriptor)?) {
+            return self.write_status(resource, object, &descriptor.id()).await;
+        }
+        // NOTE: the cr's uid and generation identify what caused the deployment
+        let request_id = format!("{}/{}", uid, object.metadata.generation.unwrap_or_default());
+        match self
+            .approval_gate
+            .admit(&descriptor, None, Some(request_id.clone()))
+            .await?
+        {
+            // NOTE: the Ready condition reports the descriptor as awaiting approval meanwhile
+            Admission::Held(approval) => {
+                info!(
+                    descriptor_id = descriptor.id(),
+                    approval_id = approval.approval_id,
+                    "custom resource changed, holding descriptor for approval"
+                );
+            }
+            Admission::Admitted => {
+                info!(
+                    descriptor_id = descriptor.id(),
+                    "custom resource changed, storing descriptor"
+                );
+                self.descriptor_store
+                    .store_descriptor::<Descriptor>(&descriptor)
+                    .await?;
+                let info = DeploymentInfo {
+                    state: DeploymentState::Pending,
+                    description: (!self.controllers.is_enabled(descriptor.kind()))
+                        .then(|| CONTROLLER_DISABLED.to_string()),
+                    request_id: Some(request_id),
+                    updated_at: None,
+                    permanent_failure: false,
+                    attempts: 0,
+                    delete_after: None,
+                    history: DeploymentHistory::default(),
+                };
+                self.deployment_state_store
+                    .set_state(&descriptor.id(), &info)
+                    .await?;
+                self.state_event_publisher
+                    .publish(
+                        StateEventType::DescriptorStored,
+                        descriptor.kind(),
+                        &descriptor.id(),
+                        descriptor.owner().as_ref(),
+                        &info,
+                    )
+                    .await;
+            }
+        }
+
+        self.write_status(resource, object, &descriptor.id()).await
+    }
+
+    // Marks the cr's descriptor for deletion as the deletion api would, the cr goes once its
+    // controller has torn the descriptor down
+    #[tracing::instrument(level = "info", skip_all, fields(name = object.name_any()))]
+    async fn delete_object(
+        &self,
+        kind: DescriptorKind,
+        resource: &ApiResource,
+        object: &DynamicObject,
+    ) -> Result<()> {
+        if !self.has_finalizer(object) {
+            return Ok(());
+        }
+
+        let descriptor_id = match object.data.get("spec").and_then(|s| s.get("id")) {
+            Some(id) => id
+                .as_str()
+                .ok_or_else(|| anyhow!("custom resource spec id is not a string"))?
+                .to_string(),
+            None => object
+                .uid()
+                .ok_or_else(|| anyhow!("custom resource has no uid"))?,
+        };
+
+        let stored = self
+            .descriptor_store
+            .get_descriptor::<serde_json::Value>(&descriptor_id, kind)
+            .await?;
+        if stored.is_none() {
+            info!(
+                descriptor_id,
+                "descriptor is gone, releasing custom resource"
+            );
+            return self.remove_finalizer(resource, object).await;
+        }
+
+        let prior = self
+            .deployment_state_store
+            .get_state(&descriptor_id)
+            .await?;
+        if prior.as_ref().is_some_and(|info| {
+            matches!(
+                info.state,
+                DeploymentState::Deleting | DeploymentState::Deleted
+            )
+        }) {
+            return self.write_status(resource, object, &descriptor_id).await;
+        }
+
+        info!(
+            descriptor_id,
+            "custom resource deleted, marking descriptor for deletion"
+        );
+        let info = DeploymentInfo {
+            state: DeploymentState::Deleting,
+            description: Some("custom resource deleted".to_string()),
+            request_id: object.uid(),
+            updated_at: None,
+            permanent_failure: false,
+            attempts: 0,
+            delete_after: Some(
+                Utc::now() + ChronoDuration::seconds(self.deletion.grace_period_secs as i64),
+            ),
+            history: DeploymentHistory::default(),
+        };
+        self.deployment_state_store
+            .set_state(&descriptor_id, &info)
+            .await?;
+        self.write_status(resource, object, &descriptor_id).await
+    }
+
+    fn finalizer(&self) -> String {
+        format!("{}/descriptor", self.conf.group)
+    }
+
+    fn has_finalizer(&self, object: &DynamicObject) -> bool {
+        object
+            .metadata
+            .finalizers
+            .as_ref()
+            .is_some_and(|f| f.contains(&self.finalizer()))
+    }
+
+    async fn add_finalizer(&self, resource: &ApiResource, object: &DynamicObject) -> Result<()> {
+        if self.has_finalizer(object) {
+            return Ok(());
+        }
+        let mut finalizers = object.metadata.finalizers.clone().unwrap_or_default();
+        finalizers.push(self.finalizer());
+        self.patch_finalizers(resource, object, finalizers).await
+    }
+
+    async fn remove_finalizer(&self, resource: &ApiResource, object: &DynamicObject) -> Result<()> {
+        let finalizer = self.finalizer();
+        let finalizers = object
+            .metadata
+            .finalizers
+            .iter()
+            .flatten()
+            .filter(|f| **f != finalizer)
+            .cloned()
+            .collect();
+        self.patch_finalizers(resource, object, finalizers).await
+    }
+
+    // NOTE: merge patches replace lists whole, the resource version guards against dropping a
+    //       finalizer something else added since the cr was listed
+    async fn patch_finalizers(
+        &self,
+        resource: &ApiResource,
+        object: &DynamicObject,
+        finalizers: Vec<String>,
+    ) -> Result<()> {
+        let api: Api<DynamicObject> = match object.namespace() {
+            Some(namespace) => Api::namespaced_with(self.client.clone(), &namespace, resource),
+            None => Api::all_with(self.client.clone(), resource),
+        };
+        api.patch(
+            &object.name_any(),
+            &PatchParams::default(),
+            &Patch::Merge(json!({
+                "metadata": {
+                    "finalizers": finalizers,
+                    "resourceVersion": object.metadata.resource_version,
+                }
+            })),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn write_status(
+        &self,
+        resource: &ApiResource,
+        object: &DynamicObject,
+        descriptor_id: &str,
+    ) -> Result<()> {
+        let Some(info) = self.deployment_state_store.get_state(descriptor_id).await? else {
+            return Ok(());
+        };
+
+        let status = json!({
+            "descriptorId": descriptor_id,
+            "observedGeneration": object.metadata.generation,
+            "conditions": [{
+                "type": "Ready",
+                "status": match info.state {
+                    DeploymentState::Succeeded | DeploymentState::Deleted => "True",
+                    DeploymentState::Failed | DeploymentState::Quarantined => "False",
+                    DeploymentState::Deleting | DeploymentState::AwaitingApproval => "Unknown",
+                    DeploymentState::Pending | DeploymentState::Deploying | DeploymentState::Unknown => "Unknown",
+                },
+                "reason": format!("{:?}", info.state),
+                "message": info.description.unwrap_or_default(),
+                "observedGeneration": object.metadata.generation,
+                "lastTransitionTime": info
+                    .history
+                    .last_transition
+                    .unwrap_or_else(Utc::now)
+                    .to_rfc3339_opts(SecondsFormat::Secs, true),
+            }],
+        });
+        if object.data.get("status") == Some(&status) {
+            return Ok(());
+        }
+
+        debug!(descriptor_id, state = ?info.state, "writing status to custom resource");
+        let api: Api<DynamicObject> = match object.namespace() {
+            Some(namespace) => Api::namespaced_with(self.client.clone(), &namespace, resource),
+            None => Api::all_with(self.client.clone(), resource),
+        };
+        api.patch_status(
+            &object.name_any(),
+            &PatchParams::default(),
+            &Patch::Merge(json!({ "status": status })),
+        )
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,331 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use aws_sdk_sqs::model::DeleteMessageBatchRequestEntry;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    config::{BasinConfig, DataTriggersConf},
+    controller::{flow::FlowController, naming::table_location},
+    deployment_state_store::{DeploymentState, DeploymentStateStore, RedisDeploymentStateStore},
+    descriptor_store::{DescriptorStore, RedisDescriptorStore},
+    fluid::descriptor::{
+        database::DatabaseDescriptor, flow::FlowDescriptor, table::TableDescriptor, DescriptorKind,
+    },
+    metrics,
+    provisioner::s3::PATH_MARKER_FILE,
+};
+
+// An object by bucket and key
+type ObjectKey = (String, String);
+
+// Longest sqs lets a receive wait for messages, and the most it hands back at once
+const SQS_WAIT_TIME_SECS: i32 = 20;
+const SQS_MAX_MESSAGES: i32 = 10;
+
+// When notifications are fanned out through an sns topic
+#[derive(Deserialize, Debug)]
+struct SnsEnvelope {
+    #[serde(rename = "Message")]
+    message: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct S3Notification {
+    #[serde(rename = "Records", default)]
+    records: Vec<S3EventRecord>,
+}
+
+#[derive(Deserialize, Debug)]
+struct S3EventRecord {
+    #[serde(rename = "eventName")]
+    event_name: String,
+    #[serde(rename = "eventTime")]
+    event_time: DateTime<Utc>,
+    s3: S3Entity,
+}
+
+#[derive(Deserialize, Debug)]
+struct S3Entity {
+    bucket: S3Bucket,
+    object: S3Object,
+}
+
+#[derive(Deserialize, Debug)]
+struct S3Bucket {
+    name: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct S3Object {
+    // Url encoded, spaces as `+`
+    key: String,
+}
+
+// Reads s3 event notifications for objects created under table locations and starts the flows
+// waiting on those tables through an upstream table condition
+pub struct DataTriggerWatcher {
+    conf: DataTriggersConf,
+    sqs_client: aws_sdk_sqs::Client,
+    descriptor_store: RedisDescriptorStore,
+    deployment_state_store: RedisDeploymentStateStore,
+    flow_controller: FlowController,
+    // When each flow was last started, so a burst of arrivals starts one run
+    last_started: Mutex<HashMap<String, Instant>>,
+}
+
+impl DataTriggerWatcher {
+    pub async fn new(conf: &BasinConfig) -> Result<Option<Self>> {
+        let Some(data_triggers) = &conf.data_triggers else {
+            return Ok(None);
+        };
+
+        Ok(Some(DataTriggerWatcher {
+            conf: data_triggers.clone(),
+            sqs_client: aws_sdk_sqs::Client::new(&conf.aws_creds),
+            descriptor_store: RedisDescriptorStore::new(&conf.redis).await?,
+            deployment_state_store: RedisDeploymentStateStore::new(&conf.redis).await?,
+            flow_controller: FlowController::new(conf).await?,
+            last_started: Mutex::new(HashMap::new()),
+        }))
+    }
+
+    pub async fn watch_loop(&self) -> ! {
+        loop {
+            // NOTE: receives long poll, so this only spins as fast as notifications arrive
+            if let Err(e) = self.receive_set().await {
+                error!(?e, "error when handling s3 notifications");
+                tokio::time::sleep(Duration::from_secs(SQS_WAIT_TIME_SECS as u64)).await;
+            }
+        }
+    }
+
+    async fn receive_set(&self) -> Result<()> {
+        let received = self
+            .sqs_client
+            .receive_message()
+            .queue_url(&self.conf.sqs_url)
+            .max_number_of_messages(SQS_MAX_MESSAGES)
+            .wait_time_seconds(SQS_WAIT_TIME_SECS)
+            .send()
+            .await?;
+        let Some(msgs) = received.messages() else {
+            return Ok(());
+        };
+
+        // Newest arrival per bucket and key, many objects landing at once start each flow once
+        let mut arrivals: BTreeMap<ObjectKey, DateTime<Utc>> = BTreeMap::new();
+        // The objects each message announced, to know which ones to leave for redelivery
+        let mut announced: Vec<Vec<ObjectKey>> = vec![];
+        for msg in msgs {
+            let mut keys = vec![];
+            match msg.body().map(parse_notification).transpose() {
+                Ok(records) => {
+                    for record in records.into_iter().flatten() {
+                        let key = (record.s3.bucket.name, decode_key(&record.s3.object.key));
+                        let arrived_at = arrivals.entry(key.clone()).or_insert(record.event_time);
+                        *arrived_at = (*arrived_at).max(record.event_time);
+                        keys.push(key);
+                    }
+                }
+                // NOTE: nothing will ever make sense of it, redelivering would only repeat this
+                Err(e) => warn!(?e, "could not parse s3 notification, dropping it"),
+            }
+            announced.push(keys);
+        }
+
+        // NOTE: every message is left for redelivery when flows couldn't be looked up, only the
+        //       ones announcing data for a flow that failed to start when some did. The flows
+        //       that started are held back by min_interval_secs when those come around again.
+        let failed = if arrivals.is_empty() {
+            HashSet::new()
+        } else {
+            self.trigger_flows(&arrivals).await?
+        };
+
+        let mut deletions = vec![];
+        for (i, (msg, keys)) in msgs.iter().zip(&announced).enumerate() {
+            if keys.iter().any(|k| failed.contains(k)) {
+                continue;
+            }
+            if let Some(receipt_handle) = msg.receipt_handle() {
+                deletions.push(
+                    DeleteMessageBatchRequestEntry::builder()
+                        .id(i.to_string())
+                        .receipt_handle(receipt_handle)
+                        .build(),
+                );
+            }
+        }
+
+        if !deletions.is_empty() {
+            self.sqs_client
+                .delete_message_batch()
+                .queue_url(&self.conf.sqs_url)
+                .set_entries(Some(deletions))
+                .send()
+                .await?;
+        }
+        Ok(())
+    }
+
+    // Starts the flows waiting on tables with new data, and hands back the objects announcing
+    // data for flows that failed to start
+    async fn trigger_flows(
+        &self,
+        arrivals: &BTreeMap<ObjectKey, DateTime<Utc>>,
+    ) -> Result<HashSet<ObjectKey>> {
+        // Newest arrival per table, and the objects that arrived for it
+        let mut tables: HashMap<String, (DateTime<Utc>, Vec<&ObjectKey>)> = HashMap::new();
+        let databases: HashMap<String, DatabaseDescriptor> = self
+            .descriptor_store
+            .list_descriptors::<DatabaseDescriptor>(DescriptorKind::Database)
+            .await?
+            .into_iter()
+            .map(|db| (db.id.clone(), db))
+            .collect();
+        for table in self
+            .descriptor_store
+            .list_descriptors::<TableDescriptor>(DescriptorKind::Table)
+            .await?
+        {
+            let Some(db) = databases.get(&table.database) else {
+                continue;
+            };
+            let Ok((bucket, prefix)) = table_location(&table, db) else {
+                continue;
+            };
+            let root = format!("{}/", prefix);
+            let objects: Vec<(&ObjectKey, &DateTime<Utc>)> = arrivals
+                .iter()
+                .filter(|((b, key), _)| {
+                    b == &bucket && key.starts_with(&root) && !key.ends_with(PATH_MARKER_FILE)
+                })
+                .collect();
+            if let Some(arrived_at) = objects.iter().map(|(_, arrived_at)| **arrived_at).max() {
+                debug!(table_id = table.id, %arrived_at, "data arrived for table");
+                let keys = objects.into_iter().map(|(key, _)| key).collect();
+                tables.insert(table.id, (arrived_at, keys));
+            }
+        }
+
+        let mut failed = HashSet::new();
+        if tables.is_empty() {
+            return Ok(failed);
+        }
+
+        for flow in self
+            .descriptor_store
+            .list_descriptors::<FlowDescriptor>(DescriptorKind::Flow)
+            .await?
+        {
+            let upstream: Vec<&(DateTime<Utc>, Vec<&ObjectKey>)> = flow
+                .upstream_tables()
+                .filter_map(|t| tables.get(t))
+                .collect();
+            let Some(arrived_at) = upstream.iter().map(|(arrived_at, _)| *arrived_at).max() else {
+                continue;
+            };
+            // One flow failing to start doesn't hold back the others waiting on the same data
+            if let Err(e) = self.start_flow(&flow, arrived_at).await {
+                error!(
+                    ?e,
+                    flow_id = flow.id,
+                    "could not start flow for arrived data"
+                );
+                failed.extend(
+                    upstream
+                        .iter()
+                        .flat_map(|(_, keys)| keys.iter().map(|k| (*k).clone())),
+                );
+            }
+        }
+
+        Ok(failed)
+    }
+
+    async fn start_flow(&self, flow: &FlowDescriptor, arrived_at: DateTime<Utc>) -> Result<()> {
+        // NOTE: a flow that isn't deployed has nothing to start, and doesn't need catching up on
+        //       once it is since its runs read whatever has landed by then
+        match self.deployment_state_store.get_state(&flow.id).await? {
+            Some(info) if info.state == DeploymentState::Succeeded => {}
+            _ => {
+                debug!(
+                    flow_id = flow.id,
+                    "flow isn't deployed, skipping data trigger"
+                );
+                return Ok(());
+            }
+        }
+
+        let min_interval = Duration::from_secs(self.conf.min_interval_secs);
+        if let Some(started) = self
+            .last_started
+            .lock()
+            .expect("data trigger state poisoned")
+            .get(&flow.id)
+            && started.elapsed() < min_interval
+        {
+            debug!(
+                flow_id = flow.id,
+                "flow started recently, skipping data trigger"
+            );
+            return Ok(());
+        }
+
+        info!(flow_id = flow.id, %arrived_at, "Starting flow for arrived data");
+        self.flow_controller.start_run(flow, arrived_at).await?;
+        self.last_started
+            .lock()
+            .expect("data trigger state poisoned")
+            .insert(flow.id.clone(), Instant::now());
+        metrics::counter_inc("basin_data_triggered_runs_total", &[("flow", &flow.id)]);
+
+        Ok(())
+    }
+}
+
+// Only object creation counts as an arrival, removals and s3's test event are left out
+fn parse_notification(body: &str) -> Result<Vec<S3EventRecord>> {
+    if let Ok(envelope) = serde_json::from_str::<SnsEnvelope>(body) {
+        return parse_notification(&envelope.message);
+    }
+    let notification: S3Notification = serde_json::from_str(body)?;
+    Ok(notification
+        .records
+        .into_iter()
+        .filter(|r| r.event_name.starts_with("ObjectCreated:"))
+        .collect())
+}
+
+fn decode_key(key: &str) -> String {
+    let bytes = key.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                match std::str::from_utf8(&bytes[i + 1..i + 3])
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                {
+                    Some(b) => {
+                        decoded.push(b);
+                        i += 2;
+                    }
+                    None => decoded.push(b'%'),
+                }
+            }
+            b => decoded.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
@@ -0,0 +1,177 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use aws_sdk_s3::types::ByteStream;
+use chrono::Utc;
+use tokio::time::{interval, MissedTickBehavior};
+use tracing::{error, info};
+
+use crate::{
+    config::{BasinConfig, RetentionConf},
+    deployment_state_store::{
+        DeploymentInfo, DeploymentState, DeploymentStateStore, RedisDeploymentStateStore,
+    },
+    descriptor_store::{DescriptorStore, RedisDescriptorStore},
+    fluid::descriptor::DescriptorKind,
+};
+
+// Moves the states of deleted descriptors past their retention out of redis and into s3. Archives
+// are written per descriptor (`{prefix}/{id}/{archived_at}.jsonl`) so the history of a single
+// descriptor can be fetched without scanning the whole archive.
+pub struct DeploymentArchiver {
+    conf: RetentionConf,
+    deployment_state_store: RedisDeploymentStateStore,
+    descriptor_store: RedisDescriptorStore,
+    s3_client: aws_sdk_s3::Client,
+}
+
+impl DeploymentArchiver {
+    pub async fn new(conf: &BasinConfig) -> Result<Option<Self>> {
+        let Some(retention) = &conf.retention else {
+            return Ok(None);
+        };
+
+        Ok(Some(DeploymentArchiver {
+            conf: retention.clone(),
+            deployment_state_store: RedisDeploymentStateStore::new(&conf.redis).await?,
+            descriptor_store: RedisDescriptorStore::new(&conf.redis).await?,
+            s3_client: aws_sdk_s3::Client::new(&conf.aws_creds),
+        }))
+    }
+
+    pub async fn archive_loop(&self) -> ! {
+        let mut ticker = interval(Duration::from_secs(self.conf.interval_secs));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+
+            info!("Archiving deleted deployment states");
+            match self.archive_expired().await {
+                Ok(archived) => info!(archived, "finished archiving deployment states"),
+                Err(e) => error!(?e, "error when archiving deployment states"),
+            }
+        }
+    }
+
+    async fn archive_expired(&self) -> Result<usize> {
+        let cutoff = Utc::now() - chrono::Duration::days(self.conf.terminal_state_days as i64);
+
+        let mut archived = 0;
+        for (id, info) in self.deployment_state_store.list_states().await? {
+            // NOTE: failed and succeeded states belong to descriptors which are still stored,
+            //       dropping them would have the reconciler pick the descriptor up as a new one
+            if info.state != DeploymentState::Deleted {
+                continue;
+            }
+            // NOTE: updated_at is bumped by every reconcile pass, the last transition is when the
+            //       descriptor was actually deleted. States written before history was kept have
+            //       no transitions, leave them until they're next touched.
+            let Some(last_transition) = info.history.last_transition else {
+                continue;
+            };
+            if last_transition > cutoff || self.descriptor_exists(&id).await? {
+                continue;
+            }
+
+            // Only delete once the archive write has landed
+            self.put_archive(&id, &info).await?;
+            self.deployment_state_store.delete_state(&id).await?;
+            archived += 1;
+        }
+
+        Ok(archived)
+    }
+
+    // The tombstone outlives the descriptor until forgetting it has succeeded, see
+    // Reconciler::advance_deletion
+    async fn descriptor_exists(&self, id: &str) -> Result<bool> {
+        for kind in DescriptorKind::ALL {
+            if self
+                .descriptor_store
+                .get_descriptor::<serde_json::Value>(id, kind)
+                .await?
+                .is_some()
+            {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    async fn put_archive(&self, id: &str, info: &DeploymentInfo) -> Result<()> {
+        let mut body = serde_json::to_string(info)?;
+        body.push('\n');
+
+        self.s3_client
+            .put_object()
+            .bucket(&self.conf.archive_bucket)
+            .key(format!(
+                "{}/{}/{}.jsonl",
+                self.conf.archive_prefix,
+                id,
+                Utc::now().timestamp_millis()
+            ))
+            .body(ByteStream::from(body.into_bytes()))
+            .send()
+            .await
+            .map_err(|e| e.into_service_error())?;
+
+        Ok(())
+    }
+
+    pub async fn get_archived(&self, id: &str) -> Result<Vec<DeploymentInfo>> {
+        let mut keys = Vec::new();
+        let mut continuation_token: Option<String> = None;
+        loop {
+            let resp = self
+                .s3_client
+                .list_objects_v2()
+                .bucket(&self.conf.archive_bucket)
+                .prefix(format!("{}/{}/", self.conf.archive_prefix, id))
+                .set_continuation_token(continuation_token.take())
+                .send()
+                .await
+                .map_err(|e| e.into_service_error())?;
+
+            for obj in resp.contents().unwrap_or_default() {
+                if let Some(k) = obj.key() {
+                    keys.push(k.to_string());
+                }
+            }
+
+            match resp.next_continuation_token() {
+                Some(t) => continuation_token = Some(t.to_string()),
+                None => break,
+            }
+        }
+        // Keys are suffixed with the archive time so lexical order is chronological
+        keys.sort();
+
+        let mut history = Vec::new();
+        for key in keys {
+            let obj = self
+                .s3_client
+                .get_object()
+                .bucket(&self.conf.archive_bucket)
+                .key(&key)
+                .send()
+                .await
+                .map_err(|e| e.into_service_error())?;
+            let bytes = obj
+                .body
+                .collect()
+                .await
+                .map_err(|e| anyhow!("failed to read archive {}: {:?}", key, e))?
+                .into_bytes();
+
+            for line in String::from_utf8(bytes.to_vec())?.lines() {
+                if !line.is_empty() {
+                    history.push(serde_json::from_str(line)?);
+                }
+            }
+        }
+
+        Ok(history)
+    }
+}
@@ -1,8 +1,28 @@
 use anyhow::Result;
-use redis::AsyncCommands;
+use chrono::{DateTime, Utc};
+use redis::{AsyncCommands, Script};
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug)]
+use crate::{
+    config::RedisConf,
+    redis_connection::RedisConnector,
+    redis_namespace::{prefixed, scan_page},
+};
+
+const MAX_TRANSITIONS: usize = 20;
+
+// Writes the state unless it's been written since it was read, ARGV[1] being what was read ("" for
+// nothing)
+const SET_IF_UNCHANGED_SCRIPT: &str = r#"
+local current = redis.call("GET", KEYS[1])
+if (current or "") ~= ARGV[1] then
+    return 0
+end
+redis.call("SET", KEYS[1], ARGV[2])
+return 1
+"#;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DeploymentState {
     // In descriptor store but not yet processing
     Pending,
@@ -15,52 +35,311 @@ pub enum DeploymentState {
     Failed,
     // Unknown state
     Unknown,
+    // Deletion requested, resources are torn down once `delete_after` passes unless restored
+    Deleting,
+    // Resources have been torn down and the descriptor forgotten
+    Deleted,
+    // Stored but failed validation, left alone until released or resubmitted
+    Quarantined,
+    // A destructive operation is waiting on an approver, the stored revision is still reconciled
+    AwaitingApproval,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DeploymentInfo {
     pub state: DeploymentState,
     pub description: Option<String>,
+    // Request (or event) that last moved the descriptor into this state, used to correlate logs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    // Stamped by the store on every write
+    #[serde(default)]
+    pub updated_at: Option<DateTime<Utc>>,
+    // Set when the failure can't be fixed by retrying, controllers leave the descriptor alone
+    // until it's resubmitted
+    #[serde(default)]
+    pub permanent_failure: bool,
+    // Reconciles since the descriptor was last submitted, repeat successes aren't counted
+    #[serde(default)]
+    pub attempts: u32,
+    // Only set while Deleting, when the grace period for restoring the descriptor runs out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delete_after: Option<DateTime<Utc>>,
+    // Maintained by the store, whatever the caller passes is ignored
+    #[serde(default, flatten)]
+    pub history: DeploymentHistory,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DeploymentHistory {
+    pub created_at: Option<DateTime<Utc>>,
+    // When the state last changed, unlike updated_at this isn't bumped by rewriting the same state
+    pub last_transition: Option<DateTime<Utc>>,
+    // Most recent first, capped at MAX_TRANSITIONS
+    #[serde(default)]
+    pub transitions: Vec<StateTransition>,
+}
+
+impl DeploymentHistory {
+    // NOTE: only drift repair moves a deployed descriptor back to Deploying
+    pub fn last_drift(&self) -> Option<&StateTransition> {
+        self.transitions.iter().find(|t| {
+            t.from == Some(DeploymentState::Succeeded) && t.to == DeploymentState::Deploying
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StateTransition {
+    pub from: Option<DeploymentState>,
+    pub to: DeploymentState,
+    pub at: DateTime<Utc>,
+    pub description: Option<String>,
 }
 
 #[async_trait::async_trait]
 pub(crate) trait DeploymentStateStore {
     async fn set_state(&self, id: &str, info: &DeploymentInfo) -> Result<()>;
+    // Key (without the prefix) and value set_state would write, for setting the state in the same
+    // transaction as something else
+    async fn state_write(&self, id: &str, info: &DeploymentInfo) -> Result<(String, String)>;
+    // Writes the info only while the stored state is still the one `expected` was read as, in the
+    // same state for the same request (None expecting no state at all). A drift note doesn't count
+    // as moving on, it's written by the reconcile that's finishing. False when it's left alone.
+    async fn set_state_if(
+        &self,
+        id: &str,
+        expected: Option<&DeploymentInfo>,
+        info: &DeploymentInfo,
+    ) -> Result<bool>;
     async fn get_state(&self, id: &str) -> Result<Option<DeploymentInfo>>;
+    // In the order of the ids, None for those without a state
+    async fn get_states(&self, ids: &[String]) -> Result<Vec<Option<DeploymentInfo>>>;
+    async fn list_states(&self) -> Result<Vec<(String, DeploymentInfo)>>;
+    async fn list_states_page(
+        &self,
+        cursor: u64,
+        limit: usize,
+    ) -> Result<(u64, Vec<(String, DeploymentInfo)>)>;
+    async fn delete_state(&self, id: &str) -> Result<()>;
+    // Writes the info as given, history included, for restoring exported states
+    async fn restore_state(&self, id: &str, info: &DeploymentInfo) -> Result<()>;
 }
 
 #[derive(Debug)]
 pub struct RedisDeploymentStateStore {
-    client: redis::Client,
+    connector: RedisConnector,
+    key_prefix: String,
 }
 
 #[async_trait::async_trait]
 impl DeploymentStateStore for RedisDeploymentStateStore {
     async fn set_state(&self, id: &str, info: &DeploymentInfo) -> Result<()> {
-        let mut conn = self.client.get_tokio_connection().await?;
-        conn.set(
-            format!("deployment-state/{}", id),
-            serde_json::to_string(info)?,
-        )
-        .await?;
+        let (key, value) = self.state_write(id, info).await?;
+        let mut conn = self.connector.get_connection().await?;
+        conn.set(self.key(&key), value).await?;
         Ok(())
     }
 
+    async fn state_write(&self, id: &str, info: &DeploymentInfo) -> Result<(String, String)> {
+        // NOTE: the read-modify-write isn't atomic, concurrent writers to the same descriptor can
+        //       drop a transition from the history. The state itself is always last writer wins.
+        let prior = self.get_state(id).await?;
+        let info = with_history(prior.as_ref(), info);
+        Ok((
+            format!("deployment-state/{}", id),
+            serde_json::to_string(&info)?,
+        ))
+    }
+
+    async fn set_state_if(
+        &self,
+        id: &str,
+        expected: Option<&DeploymentInfo>,
+        info: &DeploymentInfo,
+    ) -> Result<bool> {
+        let key = self.key(&format!("deployment-state/{}", id));
+        let mut conn = self.connector.get_connection().await?;
+        let current: Option<String> = conn.get(&key).await?;
+        let prior = current
+            .as_deref()
+            .map(serde_json::from_str::<DeploymentInfo>)
+            .transpose()?;
+
+        let unchanged = match (expected, &prior) {
+            (None, None) => true,
+            (Some(expected), Some(prior)) => {
+                prior.request_id == expected.request_id
+                    && (prior.state == expected.state
+                        || (expected.state == DeploymentState::Succeeded
+                            && prior.history.transitions.first().is_some_and(|t| {
+                                t.from == Some(DeploymentState::Succeeded)
+                                    && t.to == DeploymentState::Deploying
+                            })))
+            }
+            _ => false,
+        };
+        if !unchanged {
+            return Ok(false);
+        }
+
+        let info = with_history(prior.as_ref(), info);
+        let written: i64 = Script::new(SET_IF_UNCHANGED_SCRIPT)
+            .key(key)
+            .arg(current.unwrap_or_default())
+            .arg(serde_json::to_string(&info)?)
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(written == 1)
+    }
+
     async fn get_state(&self, id: &str) -> Result<Option<DeploymentInfo>> {
-        let mut conn = self.client.get_tokio_connection().await?;
-        let deployment_info: Option<String> = conn.get(format!("deployment-state/{}", id)).await?;
+        let mut conn = self.connector.get_connection().await?;
+        let deployment_info: Option<String> = conn
+            .get(self.key(&format!("deployment-state/{}", id)))
+            .await?;
         Ok(if let Some(t) = deployment_info {
             Some(serde_json::from_str(&t)?)
         } else {
             None
         })
     }
+
+    async fn get_states(&self, ids: &[String]) -> Result<Vec<Option<DeploymentInfo>>> {
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut conn = self.connector.get_connection().await?;
+
+        let keys: Vec<String> = ids
+            .iter()
+            .map(|id| self.key(&format!("deployment-state/{}", id)))
+            .collect();
+        let values: Vec<Option<String>> =
+            redis::cmd("MGET").arg(&keys).query_async(&mut conn).await?;
+        let mut states = Vec::new();
+        for value in values {
+            states.push(match value {
+                Some(t) => Some(serde_json::from_str(&t)?),
+                None => None,
+            });
+        }
+
+        Ok(states)
+    }
+
+    async fn list_states(&self) -> Result<Vec<(String, DeploymentInfo)>> {
+        let mut conn = self.connector.get_connection().await?;
+
+        let key_root = self.key("deployment-state/");
+        let mut keys: Vec<String> = Vec::new();
+        {
+            let mut iter = conn
+                .scan_match::<_, String>(format!("{}*", key_root))
+                .await?;
+            while let Some(k) = iter.next_item().await {
+                keys.push(k);
+            }
+        }
+
+        let mut states = Vec::new();
+        for key in keys {
+            // NOTE: keys can disappear between the scan and the get, just skip those
+            let info: Option<String> = conn.get(&key).await?;
+            if let Some(t) = info {
+                let id = key.trim_start_matches(&key_root).to_string();
+                states.push((id, serde_json::from_str(&t)?));
+            }
+        }
+
+        Ok(states)
+    }
+
+    async fn list_states_page(
+        &self,
+        cursor: u64,
+        limit: usize,
+    ) -> Result<(u64, Vec<(String, DeploymentInfo)>)> {
+        let mut conn = self.connector.get_connection().await?;
+
+        let key_root = self.key("deployment-state/");
+        let (next_cursor, keys) =
+            scan_page(&mut conn, &format!("{}*", key_root), cursor, limit).await?;
+        if keys.is_empty() {
+            return Ok((next_cursor, vec![]));
+        }
+
+        let values: Vec<Option<String>> =
+            redis::cmd("MGET").arg(&keys).query_async(&mut conn).await?;
+        let mut states = Vec::new();
+        for (key, value) in keys.iter().zip(values) {
+            // NOTE: keys can disappear between the scan and the get, just skip those
+            if let Some(t) = value {
+                let id = key.trim_start_matches(&key_root).to_string();
+                states.push((id, serde_json::from_str(&t)?));
+            }
+        }
+
+        Ok((next_cursor, states))
+    }
+
+    async fn restore_state(&self, id: &str, info: &DeploymentInfo) -> Result<()> {
+        let mut conn = self.connector.get_connection().await?;
+        let _: () = conn
+            .set(
+                self.key(&format!("deployment-state/{}", id)),
+                serde_json::to_string(info)?,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_state(&self, id: &str) -> Result<()> {
+        let mut conn = self.connector.get_connection().await?;
+        let _: () = conn
+            .del(self.key(&format!("deployment-state/{}", id)))
+            .await?;
+        Ok(())
+    }
+}
+
+// The info as it's written over `prior`, stamped and with the transition added to its history
+fn with_history(prior: Option<&DeploymentInfo>, info: &DeploymentInfo) -> DeploymentInfo {
+    let now = Utc::now();
+    let mut history = prior.map(|p| p.history.clone()).unwrap_or_default();
+    history.created_at.get_or_insert(now);
+
+    let from = prior.map(|p| p.state);
+    if from != Some(info.state) {
+        history.last_transition = Some(now);
+        history.transitions.insert(
+            0,
+            StateTransition {
+                from,
+                to: info.state,
+                at: now,
+                description: info.description.clone(),
+            },
+        );
+        history.transitions.truncate(MAX_TRANSITIONS);
+    }
+
+    DeploymentInfo {
+        updated_at: Some(now),
+        history,
+        ..info.clone()
+    }
 }
 
 impl RedisDeploymentStateStore {
-    pub async fn new(url: &str) -> Result<Self> {
-        let client = redis::Client::open(url)?;
+    pub async fn new(conf: &RedisConf) -> Result<Self> {
+        Ok(Self {
+            connector: RedisConnector::new(conf)?,
+            key_prefix: conf.key_prefix.clone(),
+        })
+    }
 
-        Ok(Self { client })
+    fn key(&self, key: &str) -> String {
+        prefixed(&self.key_prefix, key)
     }
 }
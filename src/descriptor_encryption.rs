@@ -0,0 +1,210 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm, Nonce,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use aws_config::SdkConfig;
+use aws_sdk_kms::{model::DataKeySpec, types::Blob};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use tracing::{debug, info};
+
+use crate::{config::DescriptorEncryptionConf, metrics};
+
+static ENCRYPTOR: OnceLock<DescriptorEncryptor> = OnceLock::new();
+
+// Marks a stored value as an envelope, anything else is a plain json descriptor. v2 envelopes are
+// bound to what they were sealed for (see seal), v1 ones from before that still open but are
+// sealed as v2 the next time they're written.
+const ENVELOPE_PREFIX: &str = "enc:v2:";
+const UNBOUND_ENVELOPE_PREFIX: &str = "enc:v1:";
+// Decrypted data keys kept around, only grows as data keys are rotated
+const MAX_CACHED_KEYS: usize = 1024;
+
+struct DataKey {
+    plaintext: Vec<u8>,
+    encrypted: Vec<u8>,
+    generated_at: Instant,
+}
+
+pub struct DescriptorEncryptor {
+    conf: DescriptorEncryptionConf,
+    kms_client: aws_sdk_kms::Client,
+    current_key: tokio::sync::Mutex<Option<DataKey>>,
+    // Decrypted data keys by their encrypted blob, so reads don't go to kms per descriptor
+    decrypted_keys: Mutex<HashMap<Vec<u8>, Vec<u8>>>,
+}
+
+// Turns encryption on for the rest of the process, set once at startup
+pub fn install(conf: &DescriptorEncryptionConf, aws_creds: &SdkConfig) {
+    info!(
+        kms_key_id = conf.kms_key_id,
+        "descriptors are encrypted at rest"
+    );
+    let _ = ENCRYPTOR.set(DescriptorEncryptor {
+        conf: conf.clone(),
+        kms_client: aws_sdk_kms::Client::new(aws_creds),
+        current_key: tokio::sync::Mutex::new(None),
+        decrypted_keys: Mutex::new(HashMap::new()),
+    });
+}
+
+pub fn is_installed() -> bool {
+    ENCRYPTOR.get().is_some()
+}
+
+// What gets written to the store for a descriptor's json, left as is without encryption installed.
+// `bound_to` names where it's written (e.g. `descriptor/table/{id}`), it's authenticated along with
+// the json so the envelope doesn't open anywhere else.
+pub async fn seal(json: String, bound_to: &str) -> Result<String> {
+    let Some(encryptor) = ENCRYPTOR.get() else {
+        return Ok(json);
+    };
+    encryptor.seal(&json, bound_to).await
+}
+
+// The descriptor json back out of a stored value, plain json values pass straight through
+pub async fn open(stored: String, bound_to: &str) -> Result<String> {
+    let (envelope, aad) = if let Some(envelope) = stored.strip_prefix(ENVELOPE_PREFIX) {
+        (envelope, bound_to)
+    } else if let Some(envelope) = stored.strip_prefix(UNBOUND_ENVELOPE_PREFIX) {
+        (envelope, "")
+    } else {
+        return Ok(stored);
+    };
+    let encryptor = ENCRYPTOR.get().ok_or_else(|| {
+        anyhow!("found an encrypted descriptor but descriptor_encryption isn't set")
+    })?;
+    encryptor.open(envelope, aad).await
+}
+
+impl DescriptorEncryptor {
+    async fn seal(&self, json: &str, aad: &str) -> Result<String> {
+        let mut current_key = self.current_key.lock().await;
+        let ttl = Duration::from_secs(self.conf.data_key_ttl_secs);
+        if current_key
+            .as_ref()
+            .map_or(true, |k| k.generated_at.elapsed() >= ttl)
+        {
+            *current_key = Some(self.generate_data_key().await?);
+        }
+        let key = current_key.as_ref().expect("data key was just generated");
+
+        let nonce: [u8; 12] = rand::random();
+        let ciphertext = Aes256Gcm::new_from_slice(&key.plaintext)?
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: json.as_bytes(),
+                    aad: aad.as_bytes(),
+                },
+            )
+            .map_err(|_| anyhow!("failed to encrypt descriptor"))?;
+
+        Ok(format!(
+            "{}{}:{}:{}",
+            ENVELOPE_PREFIX,
+            STANDARD.encode(&key.encrypted),
+            STANDARD.encode(nonce),
+            STANDARD.encode(ciphertext)
+        ))
+    }
+
+    async fn open(&self, envelope: &str, aad: &str) -> Result<String> {
+        let parts: Vec<&str> = envelope.split(':').collect();
+        let [encrypted_key, nonce, ciphertext] = parts[..] else {
+            bail!("malformed descriptor envelope");
+        };
+        let encrypted_key = STANDARD.decode(encrypted_key)?;
+        let nonce = STANDARD.decode(nonce)?;
+        if nonce.len() != 12 {
+            bail!("malformed descriptor envelope nonce");
+        }
+
+        let key = self.decrypt_data_key(encrypted_key).await?;
+        let json = Aes256Gcm::new_from_slice(&key)?
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &STANDARD.decode(ciphertext)?,
+                    aad: aad.as_bytes(),
+                },
+            )
+            .map_err(|_| {
+                anyhow!("failed to decrypt descriptor, wrong data key, tampered or moved")
+            })?;
+        Ok(String::from_utf8(json)?)
+    }
+
+    async fn generate_data_key(&self) -> Result<DataKey> {
+        debug!(kms_key_id = self.conf.kms_key_id, "generating data key");
+        metrics::counter_inc("basin_kms_data_keys_generated_total", &[]);
+        let resp = self
+            .kms_client
+            .generate_data_key()
+            .key_id(&self.conf.kms_key_id)
+            .key_spec(DataKeySpec::Aes256)
+            .send()
+            .await
+            .context("failed to generate data key")?;
+
+        let key = DataKey {
+            plaintext: resp
+                .plaintext()
+                .ok_or_else(|| anyhow!("kms returned no plaintext data key"))?
+                .as_ref()
+                .to_vec(),
+            encrypted: resp
+                .ciphertext_blob()
+                .ok_or_else(|| anyhow!("kms returned no encrypted data key"))?
+                .as_ref()
+                .to_vec(),
+            generated_at: Instant::now(),
+        };
+        self.cache_key(key.encrypted.clone(), key.plaintext.clone());
+        Ok(key)
+    }
+
+    async fn decrypt_data_key(&self, encrypted: Vec<u8>) -> Result<Vec<u8>> {
+        let cached = self
+            .decrypted_keys
+            .lock()
+            .expect("data key cache poisoned")
+            .get(&encrypted)
+            .cloned();
+        if let Some(key) = cached {
+            return Ok(key);
+        }
+
+        metrics::counter_inc("basin_kms_data_keys_decrypted_total", &[]);
+        // NOTE: the key id is taken from the blob, so descriptors sealed before the key id was
+        //       changed still open as long as basin can decrypt under the old key
+        let resp = self
+            .kms_client
+            .decrypt()
+            .ciphertext_blob(Blob::new(encrypted.clone()))
+            .send()
+            .await
+            .context("failed to decrypt data key")?;
+        let key = resp
+            .plaintext()
+            .ok_or_else(|| anyhow!("kms returned no plaintext data key"))?
+            .as_ref()
+            .to_vec();
+        self.cache_key(encrypted, key.clone());
+        Ok(key)
+    }
+
+    fn cache_key(&self, encrypted: Vec<u8>, plaintext: Vec<u8>) {
+        let mut keys = self.decrypted_keys.lock().expect("data key cache poisoned");
+        if keys.len() >= MAX_CACHED_KEYS {
+            keys.clear();
+        }
+        keys.insert(encrypted, plaintext);
+    }
+}
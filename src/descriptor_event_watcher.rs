@@ -1,29 +1,72 @@
-use std::time::Duration;
+use std::{collections::HashSet, time::Duration};
 
-use anyhow::Result;
-use aws_sdk_sqs::model::DeleteMessageBatchRequestEntry;
+use anyhow::{ensure, Result};
+use aws_sdk_sqs::model::{
+    DeleteMessageBatchRequestEntry, Message, MessageSystemAttributeName, QueueAttributeName,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::Utc;
+use reqwest::{
+    header::{
+        HeaderName, ACCEPT, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+    },
+    StatusCode,
+};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
 use tokio::time::{interval, MissedTickBehavior};
 use tracing::{debug, error, info, warn};
 
 use crate::{
-    config::BasinConfig,
+    approval_gate::{Admission, ApprovalGate},
+    config::{BasinConfig, ControllersConf, EventWatcherConf, LimitsConf},
+    constants::CONTROLLER_DISABLED,
     deployment_state_store::{
-        DeploymentInfo, DeploymentState, DeploymentStateStore, RedisDeploymentStateStore,
+        DeploymentHistory, DeploymentInfo, DeploymentState, DeploymentStateStore,
+        RedisDeploymentStateStore,
     },
     descriptor_store::{DescriptorStore, RedisDescriptorStore},
-    fluid::descriptor::{
-        database::DatabaseDescriptor, flow::FlowDescriptor, table::TableDescriptor,
-        IdentifiableDescriptor,
+    event_dedup_store::{EventDedupStore, InFlightEvent, RedisEventDedupStore},
+    event_record_store::{EventOutcome, EventRecord, EventRecordStore, RedisEventRecordStore},
+    event_schema::{EventSchemaValidator, SchemaViolation},
+    fluid::{
+        descriptor::{
+            database::DatabaseDescriptor, flow::FlowDescriptor,
+            landing_zone::LandingZoneDescriptor, table::TableDescriptor, DescriptorKind,
+            IdentifiableDescriptor,
+        },
+        encoding::{self, PayloadFormat},
     },
+    http_client, metrics,
+    payload_limits::PayloadLimited,
+    policy::{PolicyChecker, PolicyDenied},
+    queue_stats::{self, EventWatcherLag},
+    state_events::{StateEventPublisher, StateEventType},
+    upstream_cache_store::{CachedResponse, RedisUpstreamCacheStore, UpstreamCacheStore},
 };
 
+// Most entries sqs accepts in a single batch request
+const SQS_MAX_BATCH_SIZE: usize = 10;
+const MAX_DELETE_ATTEMPTS: u32 = 3;
+
 pub struct DescriptorEventWatcher {
     sqs_client: aws_sdk_sqs::Client,
     sqs_queue_url: String,
+    // Fifo queues deliver each message group in order, which has to be kept up when handling them
+    fifo: bool,
     descriptor_store: RedisDescriptorStore,
     deployment_state_store: RedisDeploymentStateStore,
+    event_record_store: RedisEventRecordStore,
+    event_dedup_store: RedisEventDedupStore,
+    upstream_cache_store: RedisUpstreamCacheStore,
     http_client: reqwest::Client,
+    limits: LimitsConf,
+    controllers: ControllersConf,
+    conf: EventWatcherConf,
+    state_event_publisher: StateEventPublisher,
+    schema_validator: Option<EventSchemaValidator>,
+    policy_checker: Option<PolicyChecker>,
+    approval_gate: ApprovalGate,
 }
 
 #[derive(Deserialize, Debug)]
@@ -31,7 +74,9 @@ struct DescriptorEvent {
     r#type: String,
     #[serde(rename = "descriptorURI")]
     descriptor_uri: String,
-    kind: String, // TODO: enum
+    // Left as sent so events of kinds basin doesn't know are recorded as skipped, rather than
+    // failing to parse at all
+    kind: String,
     revision: u32,
 }
 
@@ -50,9 +95,32 @@ impl DescriptorEventWatcher {
         Ok(DescriptorEventWatcher {
             sqs_client: aws_sdk_sqs::Client::new(&conf.aws_creds),
             sqs_queue_url: conf.event_sqs_url.clone(),
-            descriptor_store: RedisDescriptorStore::new(&conf.redis_url).await?,
-            deployment_state_store: RedisDeploymentStateStore::new(&conf.redis_url).await?,
-            http_client: reqwest::Client::new(),
+            fifo: conf.event_sqs_url.ends_with(".fifo"),
+            descriptor_store: RedisDescriptorStore::new(&conf.redis).await?,
+            deployment_state_store: RedisDeploymentStateStore::new(&conf.redis).await?,
+            event_record_store: RedisEventRecordStore::new(&conf.redis).await?,
+            event_dedup_store: RedisEventDedupStore::new(
+                &conf.redis,
+                conf.event_watcher.dedup_ttl_secs,
+            )
+            .await?,
+            upstream_cache_store: RedisUpstreamCacheStore::new(
+                &conf.redis,
+                conf.event_watcher.dedup_ttl_secs,
+            )
+            .await?,
+            http_client: http_client::shared(),
+            limits: conf.limits.clone(),
+            controllers: conf.controllers.clone(),
+            conf: conf.event_watcher.clone(),
+            state_event_publisher: StateEventPublisher::new(conf),
+            schema_validator: conf
+                .event_watcher
+                .schema_registry
+                .as_ref()
+                .map(|registry| EventSchemaValidator::new(registry, &conf.aws_creds)),
+            policy_checker: PolicyChecker::new(conf)?,
+            approval_gate: ApprovalGate::new(conf).await?,
         })
     }
 
@@ -60,6 +128,12 @@ impl DescriptorEventWatcher {
         let mut ticker = interval(Duration::from_millis(30000));
         ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
+        // NOTE: anything left in flight is redelivered and handled normally, this only keeps that
+        //       from repeating what was already done
+        if let Err(e) = self.recover_in_flight().await {
+            error!(?e, "failed to recover events left in flight");
+        }
+
         loop {
             info!("Ingesting events");
             ticker.tick().await;
@@ -72,23 +146,32 @@ impl DescriptorEventWatcher {
     }
 
     async fn ingest_set(&self) -> Result<()> {
-        let receive_output = self
+        let mut receive_request = self
             .sqs_client
             .receive_message()
             .queue_url(&self.sqs_queue_url)
             .visibility_timeout(10)
-            .send()
-            .await?;
+            .attribute_names(QueueAttributeName::from("SentTimestamp"));
+        if self.fifo {
+            // NOTE: the sdk has no variant for message attributes here, sqs takes the name as is
+            receive_request =
+                receive_request.attribute_names(QueueAttributeName::from("MessageGroupId"));
+        }
+        let receive_output = receive_request.send().await?;
+        self.report_lag(receive_output.messages().unwrap_or_default())
+            .await;
 
         // NOTE: its safe to aggregate these and batch delete them at the end
         //       since in the worst case it the node is lost before deletion they'll just
         //       get picked up by another node. As the operation is idempotent it doesn't matter
         let mut deletions: Vec<(&str, String)> = Vec::new();
+        // Groups with a message left for redelivery, later messages of theirs have to wait for it
+        let mut blocked_groups: HashSet<&str> = HashSet::new();
 
         if let Some(msgs) = receive_output.messages() {
             // TODO: run these concurrently
             for (i, msg) in msgs.iter().enumerate() {
-                if let Some(receipt_handle) = msg.receipt_handle() {
+                let deletion = msg.receipt_handle().map(|receipt_handle| {
                     info!(receipt_handle, "Read message sqs");
 
                     let msg_id = if let Some(x) = msg.message_id() {
@@ -96,105 +179,603 @@ impl DescriptorEventWatcher {
                     } else {
                         i.to_string()
                     };
-                    deletions.push((receipt_handle, msg_id));
-                }
+                    (receipt_handle, msg_id)
+                });
 
-                if let Some(event_str) = msg.body() {
-                    let event: EnvelopedEvent = serde_json::from_str(event_str)?; // FIXME: handle all errors at the end
+                let group_id = msg
+                    .attributes()
+                    .and_then(|a| a.get(&MessageSystemAttributeName::MessageGroupId))
+                    .map(String::as_str);
+                if let Some(group_id) = group_id
+                    && blocked_groups.contains(group_id)
+                {
                     info!(
-                        event_id = event.event_id,
-                        "Received event from event source"
+                        group_id,
+                        "earlier message in group failed, leaving message for redelivery"
                     );
+                    continue;
+                }
 
-                    match event.payload.kind.as_str() {
-                        "database" => {
-                            self.load_upstream_descriptor::<DatabaseDescriptor>(
-                                &event.payload.descriptor_uri,
-                            )
-                            .await?
-                        }
-                        "flow" => {
-                            self.load_upstream_descriptor::<FlowDescriptor>(
-                                &event.payload.descriptor_uri,
-                            )
-                            .await?
+                if let Some(event_str) = msg.body() {
+                    let event: Value = match serde_json::from_str(event_str) {
+                        Ok(t) => t,
+                        Err(e) => {
+                            error!(?e, "could not parse event, leaving it for redelivery");
+                            blocked_groups.extend(group_id);
+                            continue;
                         }
-                        "table" => {
-                            self.load_upstream_descriptor::<TableDescriptor>(
-                                &event.payload.descriptor_uri,
-                            )
-                            .await?
+                    };
+                    if let Some(validator) = &self.schema_validator {
+                        match validator.validate(&event).await {
+                            Ok(()) => {}
+                            Err(e) if e.is::<SchemaViolation>() => {
+                                let msg_id = deletion.as_ref().map(|(_, id)| id.as_str());
+                                self.reject_event(&event, msg_id, &e).await;
+                                deletions.extend(deletion);
+                                continue;
+                            }
+                            Err(e) => {
+                                error!(?e, "could not validate event, leaving it for redelivery");
+                                blocked_groups.extend(group_id);
+                                continue;
+                            }
                         }
-                        k => {
-                            warn!("Unsupported payload kind {}", k);
+                    }
+                    let event: EnvelopedEvent = match serde_json::from_value(event) {
+                        Ok(t) => t,
+                        Err(e) => {
+                            error!(?e, "could not parse event, leaving it for redelivery");
+                            blocked_groups.extend(group_id);
                             continue;
                         }
+                    };
+                    info!(
+                        event_id = event.event_id,
+                        "Received event from event source"
+                    );
+
+                    let result = self.handle_event(&event).await;
+                    self.record_event(&event, &result).await;
+                    // NOTE: denied descriptors are dropped, redelivering them won't change that
+                    if let Err(e) = result
+                        && !e.is::<PolicyDenied>()
+                    {
+                        error!(event_id = event.event_id, ?e, "failed to handle event");
+                        blocked_groups.extend(group_id);
+                        continue;
                     }
                 }
+
+                if let Some(t) = deletion {
+                    deletions.push(t);
+                }
+            }
+        }
+
+        for chunk in deletions.chunks(SQS_MAX_BATCH_SIZE) {
+            self.delete_messages(chunk).await?;
+        }
+
+        Ok(())
+    }
+
+    // Events left in flight by a watcher which died storing them. Those whose deployment state was
+    // set went through, anything after it is skipped when they're redelivered. The rest stored at
+    // most the descriptor, which storing again on redelivery puts right.
+    async fn recover_in_flight(&self) -> Result<()> {
+        for (event_id, event) in self.event_dedup_store.list_in_flight().await? {
+            let state = self
+                .deployment_state_store
+                .get_state(&event.descriptor_id)
+                .await?;
+            if state.and_then(|s| s.request_id).as_deref() == Some(event_id.as_str()) {
+                info!(
+                    event_id,
+                    descriptor_id = event.descriptor_id,
+                    revision = event.revision,
+                    "completing event left in flight"
+                );
+                self.event_dedup_store
+                    .mark_revision_seen(event.kind, &event.descriptor_id, event.revision)
+                    .await?;
+                self.advance_latest_revision(event.kind, &event.descriptor_id, event.revision)
+                    .await?;
+                self.event_dedup_store.mark_event_seen(&event_id).await?;
+            } else {
+                info!(
+                    event_id,
+                    descriptor_id = event.descriptor_id,
+                    "dropping event left in flight before it was stored"
+                );
+            }
+            self.event_dedup_store.finish_event(&event_id).await?;
+        }
+        Ok(())
+    }
+
+    async fn advance_latest_revision(
+        &self,
+        kind: DescriptorKind,
+        descriptor_id: &str,
+        revision: u32,
+    ) -> Result<()> {
+        let latest = self
+            .event_dedup_store
+            .get_latest_revision(kind, descriptor_id)
+            .await?;
+        if latest.map_or(true, |latest| latest < revision) {
+            self.event_dedup_store
+                .set_latest_revision(kind, descriptor_id, revision)
+                .await?;
+        }
+        Ok(())
+    }
+
+    // How far behind the queue ingestion is, the age of the oldest message just received and how
+    // many are still waiting
+    async fn report_lag(&self, msgs: &[Message]) {
+        let now = Utc::now().timestamp_millis();
+        let lag_secs = msgs
+            .iter()
+            .filter_map(|msg| {
+                msg.attributes()?
+                    .get(&MessageSystemAttributeName::SentTimestamp)?
+                    .parse::<i64>()
+                    .ok()
+            })
+            .map(|sent_at| (now - sent_at).max(0) as f64 / 1000.0)
+            .reduce(f64::max);
+
+        let backlog = match self
+            .sqs_client
+            .get_queue_attributes()
+            .queue_url(&self.sqs_queue_url)
+            .attribute_names(QueueAttributeName::ApproximateNumberOfMessages)
+            .send()
+            .await
+        {
+            Ok(output) => output
+                .attributes()
+                .and_then(|a| a.get(&QueueAttributeName::ApproximateNumberOfMessages))
+                .and_then(|n| n.parse::<u64>().ok()),
+            Err(e) => {
+                warn!(?e, "could not fetch event queue backlog");
+                None
             }
+        };
+
+        if let Some(backlog) = backlog {
+            metrics::gauge_set("basin_event_queue_backlog", &[], backlog as f64);
         }
+        // NOTE: an empty receive means the watcher has caught up
+        metrics::gauge_set(
+            "basin_event_watcher_lag_seconds",
+            &[],
+            lag_secs.unwrap_or(0.0),
+        );
+        queue_stats::record_event_watcher(EventWatcherLag {
+            backlog,
+            lag_secs,
+            observed_at: Utc::now(),
+        });
+    }
 
-        if !deletions.is_empty() {
+    // Deletes a batch of at most SQS_MAX_BATCH_SIZE messages, retrying the entries sqs reports as
+    // failed. Messages which still can't be deleted are redelivered and deduplicated then.
+    async fn delete_messages(&self, deletions: &[(&str, String)]) -> Result<()> {
+        let mut pending: Vec<&(&str, String)> = deletions.iter().collect();
+        for attempt in 1..=MAX_DELETE_ATTEMPTS {
             let mut delete_request = self
                 .sqs_client
                 .delete_message_batch()
                 .queue_url(&self.sqs_queue_url);
-            for (receipt_handle, msg_id) in deletions {
+            for (receipt_handle, msg_id) in pending.iter() {
                 delete_request = delete_request.entries(
                     DeleteMessageBatchRequestEntry::builder()
                         .id(msg_id)
-                        .receipt_handle(receipt_handle)
+                        .receipt_handle(*receipt_handle)
                         .build(),
                 )
             }
-            delete_request.send().await?;
+            let output = delete_request.send().await?;
+
+            let failed = output.failed().unwrap_or_default();
+            if failed.is_empty() {
+                return Ok(());
+            }
+            for entry in failed {
+                warn!(
+                    msg_id = entry.id(),
+                    code = entry.code(),
+                    message = entry.message(),
+                    sender_fault = entry.sender_fault(),
+                    attempt,
+                    "failed to delete message from sqs"
+                );
+            }
+            // NOTE: sender faults (e.g. an expired receipt handle) won't go away by retrying
+            pending.retain(|(_, msg_id)| {
+                failed
+                    .iter()
+                    .any(|f| f.id() == Some(msg_id.as_str()) && !f.sender_fault())
+            });
+            if pending.is_empty() {
+                return Ok(());
+            }
         }
 
+        warn!(
+            remaining = pending.len(),
+            "gave up deleting messages from sqs, they'll be redelivered"
+        );
         Ok(())
     }
 
-    // TODO: probably include event_id in span if available
+    // Returns what happened to the event along with the id of the descriptor it referred to, if known
+    async fn handle_event(&self, event: &EnvelopedEvent) -> Result<(EventOutcome, Option<String>)> {
+        if self
+            .event_dedup_store
+            .is_event_seen(&event.event_id)
+            .await?
+        {
+            info!(
+                event_id = event.event_id,
+                "Skipping already processed event"
+            );
+            return Ok((EventOutcome::Duplicate, event.resource.clone()));
+        }
+
+        let Ok(kind) = event.payload.kind.parse::<DescriptorKind>() else {
+            warn!("Unsupported payload kind {}", event.payload.kind);
+            return Ok((EventOutcome::Skipped, None));
+        };
+
+        // NOTE: when the publisher tells us which resource the event is for we can skip the upstream
+        //       fetch entirely, otherwise the revision is checked once the descriptor is fetched
+        if let Some(resource) = &event.resource
+            && self
+                .event_dedup_store
+                .is_revision_seen(kind, resource, event.payload.revision)
+                .await?
+        {
+            info!(
+                event_id = event.event_id,
+                descriptor_id = resource,
+                revision = event.payload.revision,
+                "Skipping already processed descriptor revision"
+            );
+            self.event_dedup_store
+                .mark_event_seen(&event.event_id)
+                .await?;
+            return Ok((EventOutcome::Duplicate, Some(resource.clone())));
+        }
+
+        if !self.conf.ingests(kind) {
+            info!(
+                event_id = event.event_id,
+                %kind,
+                "Skipping event of a disabled kind"
+            );
+            self.event_dedup_store
+                .mark_event_seen(&event.event_id)
+                .await?;
+            return Ok((EventOutcome::Skipped, event.resource.clone()));
+        }
+
+        let (outcome, descriptor_id) = match kind {
+            DescriptorKind::Database => {
+                self.load_upstream_descriptor::<DatabaseDescriptor>(
+                    &event.event_id,
+                    &event.payload.descriptor_uri,
+                    event.payload.revision,
+                )
+                .await?
+            }
+            DescriptorKind::Flow => {
+                self.load_upstream_descriptor::<FlowDescriptor>(
+                    &event.event_id,
+                    &event.payload.descriptor_uri,
+                    event.payload.revision,
+                )
+                .await?
+            }
+            DescriptorKind::Table => {
+                self.load_upstream_descriptor::<TableDescriptor>(
+                    &event.event_id,
+                    &event.payload.descriptor_uri,
+                    event.payload.revision,
+                )
+                .await?
+            }
+            DescriptorKind::LandingZone => {
+                self.load_upstream_descriptor::<LandingZoneDescriptor>(
+                    &event.event_id,
+                    &event.payload.descriptor_uri,
+                    event.payload.revision,
+                )
+                .await?
+            }
+        };
+
+        self.event_dedup_store
+            .mark_event_seen(&event.event_id)
+            .await?;
+        self.event_dedup_store.finish_event(&event.event_id).await?;
+
+        Ok((outcome, Some(descriptor_id)))
+    }
+
+    // NOTE: the event didn't match the schema, so everything recorded about it is best effort
+    async fn reject_event(&self, event: &Value, msg_id: Option<&str>, e: &anyhow::Error) {
+        let event_id = event["event_id"]
+            .as_str()
+            .or(msg_id)
+            .unwrap_or("unknown")
+            .to_string();
+        warn!(event_id, ?e, "event rejected by schema validation");
+        metrics::counter_inc("basin_rejected_events_total", &[("reason", "schema")]);
+
+        let record = EventRecord {
+            event_id: event_id.clone(),
+            kind: event["payload"]["kind"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            descriptor_uri: event["payload"]["descriptorURI"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            descriptor_id: None,
+            revision: event["payload"]["revision"]
+                .as_u64()
+                .and_then(|r| u32::try_from(r).ok()),
+            outcome: EventOutcome::Rejected,
+            error: Some(format!("{:#}", e)),
+            processed_at: Utc::now(),
+        };
+        if let Err(e) = self.event_record_store.put_record(&record).await {
+            warn!(event_id, ?e, "failed to persist event record");
+        }
+    }
+
+    async fn record_event(
+        &self,
+        event: &EnvelopedEvent,
+        result: &Result<(EventOutcome, Option<String>)>,
+    ) {
+        let (outcome, descriptor_id, error) = match result {
+            Ok((outcome, descriptor_id)) => (*outcome, descriptor_id.clone(), None),
+            Err(e) if e.is::<PolicyDenied>() => {
+                (EventOutcome::Rejected, None, Some(format!("{:#}", e)))
+            }
+            Err(e) => (EventOutcome::Failed, None, Some(format!("{:#}", e))),
+        };
+
+        let record = EventRecord {
+            event_id: event.event_id.clone(),
+            kind: event.payload.kind.clone(),
+            descriptor_uri: event.payload.descriptor_uri.clone(),
+            descriptor_id,
+            revision: Some(event.payload.revision),
+            outcome,
+            error,
+            processed_at: Utc::now(),
+        };
+
+        // NOTE: records are informational, failing to write one shouldn't fail the event
+        if let Err(e) = self.event_record_store.put_record(&record).await {
+            warn!(
+                event_id = event.event_id,
+                ?e,
+                "failed to persist event record"
+            );
+        }
+    }
+
+    #[tracing::instrument(level = "info", skip(self), fields(request_id = event_id))]
     async fn load_upstream_descriptor<
-        DescriptorKind: IdentifiableDescriptor + Serialize + DeserializeOwned + Sync,
+        Descriptor: IdentifiableDescriptor + PayloadLimited + Serialize + DeserializeOwned + Sync,
     >(
         &self,
+        event_id: &str,
         descriptor_uri: &str,
-    ) -> Result<()> {
-        // FIXME: handle ssrf
-        debug!(descriptor_uri, "fetching descriptor from upstream");
-        let resp = self.http_client.get(descriptor_uri).send().await?;
+        revision: u32,
+    ) -> Result<(EventOutcome, String)> {
+        let (content_type, body) = self.fetch_upstream(descriptor_uri).await?;
+        let format = PayloadFormat::from_content_type(content_type.as_deref())?;
+        let descriptor: Descriptor = encoding::decode(format, &body)?;
+        descriptor.check_limits(&self.limits)?;
+        if let Some(policy_checker) = &self.policy_checker {
+            policy_checker.check(&descriptor).await?;
+        }
 
-        // TODO: resp.error_for_status()?;
-        let descriptor = match resp.json::<DescriptorKind>().await {
-            Ok(t) => t,
-            Err(e) => return Err(e.into()),
-        };
+        if let Some(latest) = self
+            .event_dedup_store
+            .get_latest_revision(descriptor.kind(), &descriptor.id())
+            .await?
+            && latest >= revision
+        {
+            info!(
+                descriptor_id = descriptor.id(),
+                revision, latest, "descriptor revision isn't newer than the stored one, skipping"
+            );
+            let outcome = if latest == revision {
+                EventOutcome::Duplicate
+            } else {
+                EventOutcome::Stale
+            };
+            return Ok((outcome, descriptor.id()));
+        }
+        if self
+            .event_dedup_store
+            .is_revision_seen(descriptor.kind(), &descriptor.id(), revision)
+            .await?
+        {
+            info!(
+                descriptor_id = descriptor.id(),
+                revision, "descriptor revision already stored, skipping"
+            );
+            return Ok((EventOutcome::Duplicate, descriptor.id()));
+        }
 
-        // TODO: check revision
+        // NOTE: the revision counts as handled once it's held, the approval stores it later
+        if let Admission::Held(approval) = self
+            .approval_gate
+            .admit(&descriptor, None, Some(event_id.to_string()))
+            .await?
+        {
+            info!(
+                descriptor_id = descriptor.id(),
+                approval_id = approval.approval_id,
+                "descriptor would break its consumers, holding it for approval"
+            );
+            self.event_dedup_store
+                .mark_revision_seen(descriptor.kind(), &descriptor.id(), revision)
+                .await?;
+            self.advance_latest_revision(descriptor.kind(), &descriptor.id(), revision)
+                .await?;
+            return Ok((EventOutcome::AwaitingApproval, descriptor.id()));
+        }
 
         info!(
             descriptor_id = descriptor.id(),
             "received and storing descriptor"
         );
+        self.event_dedup_store
+            .begin_event(
+                event_id,
+                &InFlightEvent {
+                    kind: descriptor.kind(),
+                    descriptor_id: descriptor.id(),
+                    revision,
+                },
+            )
+            .await?;
         self.descriptor_store
-            .store_descriptor::<DescriptorKind>(&descriptor)
+            .store_descriptor::<Descriptor>(&descriptor)
             .await?;
 
+        let info = DeploymentInfo {
+            state: DeploymentState::Pending,
+            description: (!self.controllers.is_enabled(descriptor.kind()))
+                .then(|| CONTROLLER_DISABLED.to_string()),
+            // NOTE: the event id doubles as the request id for event sourced descriptors
+            request_id: Some(event_id.to_string()),
+            updated_at: None,
+            permanent_failure: false,
+            attempts: 0,
+            delete_after: None,
+            history: DeploymentHistory::default(),
+        };
         self.deployment_state_store
-            .set_state(
+            .set_state(&descriptor.id(), &info)
+            .await?;
+        self.state_event_publisher
+            .publish(
+                StateEventType::DescriptorStored,
+                descriptor.kind(),
                 &descriptor.id(),
-                &DeploymentInfo {
-                    state: DeploymentState::Pending,
-                    description: None,
-                },
+                descriptor.owner().as_ref(),
+                &info,
             )
-            .await?;
+            .await;
 
         info!(
             descriptor_id = descriptor.id(),
             "stored upstream descriptor into cache"
         );
 
-        Ok(())
+        self.event_dedup_store
+            .mark_revision_seen(descriptor.kind(), &descriptor.id(), revision)
+            .await?;
+        self.advance_latest_revision(descriptor.kind(), &descriptor.id(), revision)
+            .await?;
+
+        Ok((EventOutcome::Stored, descriptor.id()))
+    }
+
+    // Fetches the descriptor's content type and body, conditionally when an earlier response for
+    // the uri is cached so an unchanged descriptor isn't downloaded again
+    async fn fetch_upstream(&self, descriptor_uri: &str) -> Result<(Option<String>, Vec<u8>)> {
+        // FIXME: handle ssrf
+        debug!(descriptor_uri, "fetching descriptor from upstream");
+        let cached = self
+            .upstream_cache_store
+            .get_response(descriptor_uri)
+            .await?;
+        let mut request = self
+            .http_client
+            .get(descriptor_uri)
+            .header(ACCEPT, encoding::ACCEPTED_CONTENT_TYPES)
+            .timeout(Duration::from_secs(self.conf.fetch_timeout_secs));
+        if let Some(cached) = &cached {
+            if let Some(etag) = &cached.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        let resp = request.send().await?;
+
+        if resp.status() == StatusCode::NOT_MODIFIED
+            && let Some(cached) = cached
+        {
+            debug!(
+                descriptor_uri,
+                "upstream descriptor not modified, using the cached one"
+            );
+            metrics::counter_inc(
+                "basin_upstream_fetches_total",
+                &[("outcome", "not_modified")],
+            );
+            return Ok((cached.content_type, STANDARD.decode(cached.body)?));
+        }
+        let mut resp = resp.error_for_status()?;
+
+        let header = |name: HeaderName| {
+            resp.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+        let (etag, last_modified, content_type) =
+            (header(ETAG), header(LAST_MODIFIED), header(CONTENT_TYPE));
+
+        // NOTE: the length is only a hint, the body is still counted as it's read
+        let max_bytes = self.limits.max_body_bytes;
+        ensure!(
+            resp.content_length().unwrap_or(0) <= max_bytes as u64,
+            "upstream descriptor is larger than {} bytes",
+            max_bytes
+        );
+        let mut body = Vec::new();
+        while let Some(chunk) = resp.chunk().await? {
+            body.extend_from_slice(&chunk);
+            ensure!(
+                body.len() <= max_bytes,
+                "upstream descriptor is larger than {} bytes",
+                max_bytes
+            );
+        }
+        metrics::counter_inc("basin_upstream_fetches_total", &[("outcome", "fetched")]);
+
+        // NOTE: the raw response is cached rather than the descriptor, a 304 is decoded and
+        //       checked again like any other fetch
+        if etag.is_some() || last_modified.is_some() {
+            self.upstream_cache_store
+                .put_response(
+                    descriptor_uri,
+                    &CachedResponse {
+                        etag,
+                        last_modified,
+                        content_type: content_type.clone(),
+                        body: STANDARD.encode(&body),
+                    },
+                )
+                .await?;
+        }
+
+        Ok((content_type, body))
     }
 }
@@ -1,35 +1,187 @@
-use anyhow::Result;
-use redis::AsyncCommands;
+use anyhow::{anyhow, ensure, Result};
+use chrono::{DateTime, TimeZone, Utc};
+use redis::{aio::ConnectionLike, AsyncCommands, Script};
 use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
 use std::marker::Sync;
+use thiserror::Error;
 
-use crate::fluid::descriptor::IdentifiableDescriptor;
+use crate::{
+    config::RedisConf,
+    descriptor_encryption,
+    fluid::descriptor::{DescriptorKind, IdentifiableDescriptor},
+    redis_connection::RedisConnector,
+    redis_namespace::{prefixed, scan_page},
+};
 
 #[async_trait::async_trait]
 pub(crate) trait DescriptorStore {
-    async fn get_descriptor<T: DeserializeOwned>(&self, id: &str, kind: &str) -> Result<Option<T>>;
+    async fn get_descriptor<T: DeserializeOwned>(
+        &self,
+        id: &str,
+        kind: DescriptorKind,
+    ) -> Result<Option<T>>;
+    // Err is a NameTaken when the descriptor's name belongs to another descriptor, the name of
+    // what it replaces is released
     async fn store_descriptor<T: IdentifiableDescriptor + Serialize + Sync>(
         &self,
         descriptor: &T,
     ) -> Result<()>;
-    async fn list_descriptors<T: DeserializeOwned + Send>(&self, kind: &str) -> Result<Vec<T>>;
+    // Stores every descriptor at once, either all of them are stored or none are, setting the
+    // `also_set` keys (without the prefix) in the same transaction. Err is a NameTaken when one's
+    // name belongs to another descriptor.
+    async fn store_descriptors(
+        &self,
+        descriptors: &[(DescriptorKind, String, Value)],
+        also_set: &[(String, String)],
+    ) -> Result<()>;
+    async fn list_descriptors<T: DeserializeOwned + Send>(
+        &self,
+        kind: DescriptorKind,
+    ) -> Result<Vec<T>>;
+    async fn delete_descriptor(&self, id: &str, kind: DescriptorKind) -> Result<()>;
+    // Id of the descriptor last stored under the name
+    async fn get_id_by_name(
+        &self,
+        kind: DescriptorKind,
+        namespace: Option<&str>,
+        name: &str,
+    ) -> Result<Option<String>>;
+    // Id of the descriptor stored under the name, `id` when there's none yet
+    async fn claim_name(
+        &self,
+        kind: DescriptorKind,
+        namespace: Option<&str>,
+        name: &str,
+        id: &str,
+    ) -> Result<String>;
+    async fn list_descriptors_page<T: DeserializeOwned + Send>(
+        &self,
+        kind: DescriptorKind,
+        cursor: u64,
+        limit: usize,
+    ) -> Result<DescriptorPage<T>>;
+    // The revision which was stored at `at`, None if it's from before the oldest one kept
+    async fn get_descriptor_at<T: DeserializeOwned>(
+        &self,
+        id: &str,
+        kind: DescriptorKind,
+        at: DateTime<Utc>,
+    ) -> Result<Option<DescriptorRevision<T>>>;
+}
+
+// Stores descriptors along with their revision and name, each one taking four KEYS (descriptor,
+// revisions, name index, name index of what's stored now) and six ARGV (id, stored value or
+// empty, value, revision, whether it has a name, whether what's stored has one) after the revision
+// score, the revision trim rank and the number of descriptors. Any KEYS after the descriptors' are
+// set to the ARGV after theirs along with them. Nothing is written when a descriptor isn't what
+// was read beforehand or its name belongs to another descriptor.
+// NOTE: unnamed descriptors pass their own key in place of the name index keys
+const STORE_SCRIPT: &str = r#"
+local n = tonumber(ARGV[3])
+for i = 0, n - 1 do
+    local k, a = i * 4, 3 + i * 6
+    if (redis.call('GET', KEYS[k + 1]) or '') ~= ARGV[a + 2] then
+        return {'changed', KEYS[k + 1]}
+    end
+    if ARGV[a + 5] == '1' then
+        local owner = redis.call('GET', KEYS[k + 3])
+        if owner and owner ~= ARGV[a + 1] then
+            return {'taken', KEYS[k + 3], owner}
+        end
+    end
+end
+for i = 0, n - 1 do
+    local k, a = i * 4, 3 + i * 6
+    if ARGV[a + 6] == '1' and KEYS[k + 4] ~= KEYS[k + 3]
+        and redis.call('GET', KEYS[k + 4]) == ARGV[a + 1] then
+        redis.call('DEL', KEYS[k + 4])
+    end
+    redis.call('SET', KEYS[k + 1], ARGV[a + 3])
+    if ARGV[a + 5] == '1' then
+        redis.call('SET', KEYS[k + 3], ARGV[a + 1])
+    end
+    redis.call('ZADD', KEYS[k + 2], ARGV[1], ARGV[a + 4])
+    redis.call('ZREMRANGEBYRANK', KEYS[k + 2], 0, ARGV[2])
+end
+for j = n * 4 + 1, #KEYS do
+    redis.call('SET', KEYS[j], ARGV[j - n * 4 + 3 + n * 6])
+end
+return {'ok'}
+"#;
+
+// Deletes KEYS[1] if it's still ARGV[1], and its name index entry KEYS[2] if it still belongs to
+// ARGV[2]
+const DELETE_SCRIPT: &str = r#"
+if (redis.call('GET', KEYS[1]) or '') ~= ARGV[1] then
+    return 0
+end
+redis.call('DEL', KEYS[1])
+if redis.call('GET', KEYS[2]) == ARGV[2] then
+    redis.call('DEL', KEYS[2])
+end
+return 1
+"#;
+
+// Keys fetched per MGET when listing a whole kind, so it doesn't block redis on one huge command
+const MGET_CHUNK_SIZE: usize = 500;
+// Revisions kept per descriptor, the oldest are dropped as new ones are stored
+const MAX_REVISIONS: isize = 50;
+// Reads and writes of a descriptor which are raced by another write before giving up
+const MAX_WRITE_ATTEMPTS: usize = 5;
+
+// A descriptor stored under a name another descriptor already has
+#[derive(Error, Debug)]
+#[error("{name} already belongs to {owner}")]
+pub struct NameTaken {
+    pub name: String,
+    pub owner: String,
+}
+
+struct DescriptorWrite {
+    kind: DescriptorKind,
+    id: String,
+    json: String,
+    name_key: Option<String>,
+}
+
+pub struct DescriptorPage<T> {
+    // Cursor to resume the scan from, 0 once it's complete
+    pub next_cursor: u64,
+    pub descriptors: Vec<T>,
+    // Redis' clock when the page was read, every descriptor in it is as of this instant
+    pub read_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DescriptorRevision<T> {
+    pub stored_at: DateTime<Utc>,
+    pub descriptor: T,
 }
 
 #[derive(Debug)]
 pub struct RedisDescriptorStore {
-    client: redis::Client,
+    connector: RedisConnector,
+    key_prefix: String,
 }
 
 #[async_trait::async_trait]
 impl DescriptorStore for RedisDescriptorStore {
-    async fn get_descriptor<T: DeserializeOwned>(&self, id: &str, kind: &str) -> Result<Option<T>> {
-        let mut conn = self.client.get_tokio_connection().await?;
+    async fn get_descriptor<T: DeserializeOwned>(
+        &self,
+        id: &str,
+        kind: DescriptorKind,
+    ) -> Result<Option<T>> {
+        let mut conn = self.connector.get_connection().await?;
 
-        let descriptor_json: Option<String> =
-            conn.get(format!("descriptor/{}/{}", kind, id)).await?;
+        let descriptor_json: Option<String> = conn
+            .get(self.key(&format!("descriptor/{}/{}", kind, id)))
+            .await?;
 
         Ok(if let Some(t) = descriptor_json {
-            Some(serde_json::from_str(&t)?)
+            Some(serde_json::from_str(
+                &descriptor_encryption::open(t, &descriptor_path(kind, id)).await?,
+            )?)
         } else {
             None
         })
@@ -39,39 +191,413 @@ impl DescriptorStore for RedisDescriptorStore {
         &self,
         descriptor: &T,
     ) -> Result<()> {
-        let mut conn = self.client.get_tokio_connection().await?;
-
-        let descriptor_json: String = serde_json::to_string(descriptor)?;
-        conn.set(
-            format!("descriptor/{}/{}", descriptor.kind(), descriptor.id()),
-            descriptor_json,
+        ensure!(!descriptor.id().is_empty(), "descriptor has no id");
+        self.write_descriptors(
+            vec![DescriptorWrite {
+                kind: descriptor.kind(),
+                id: descriptor.id(),
+                json: serde_json::to_string(descriptor)?,
+                name_key: Some(self.name_key(
+                    descriptor.kind(),
+                    descriptor.namespace().as_deref(),
+                    &descriptor.name(),
+                )),
+            }],
+            &[],
         )
-        .await?;
+        .await
+    }
 
-        Ok(())
+    async fn store_descriptors(
+        &self,
+        descriptors: &[(DescriptorKind, String, Value)],
+        also_set: &[(String, String)],
+    ) -> Result<()> {
+        let mut writes = Vec::new();
+        for (kind, id, descriptor) in descriptors {
+            writes.push(DescriptorWrite {
+                kind: *kind,
+                id: id.clone(),
+                json: serde_json::to_string(descriptor)?,
+                name_key: self.value_name_key(*kind, descriptor),
+            });
+        }
+        self.write_descriptors(writes, also_set).await
     }
 
-    async fn list_descriptors<T: DeserializeOwned + Send>(&self, kind: &str) -> Result<Vec<T>> {
-        let mut conn = self.client.get_tokio_connection().await?;
+    async fn list_descriptors<T: DeserializeOwned + Send>(
+        &self,
+        kind: DescriptorKind,
+    ) -> Result<Vec<T>> {
+        let mut conn = self.connector.get_connection().await?;
 
-        // FIXME: keys is evil and we should probably not be using redis for this...
-        let descriptor_keys: Vec<String> = conn.keys(format!("descriptor/{}/*", kind)).await?;
+        let mut descriptor_keys: Vec<String> = Vec::new();
+        {
+            let mut iter = conn
+                .scan_match::<_, String>(self.key(&format!("descriptor/{}/*", kind)))
+                .await?;
+            while let Some(k) = iter.next_item().await {
+                descriptor_keys.push(k);
+            }
+        }
 
+        let values = get_values(&mut conn, &descriptor_keys).await?;
         let mut descriptors = Vec::new();
-        for d in descriptor_keys {
-            // TODO: theres a toctou here
-            let descriptor: String = conn.get(&d).await?;
-            descriptors.push(serde_json::from_str(&descriptor)?);
+        for (key, value) in values {
+            descriptors.push(serde_json::from_str(
+                &descriptor_encryption::open(value, self.path_of(&key)).await?,
+            )?);
         }
 
         Ok(descriptors)
     }
+
+    // Releases the descriptor's name along with it, unless another descriptor has taken it since
+    async fn delete_descriptor(&self, id: &str, kind: DescriptorKind) -> Result<()> {
+        let mut conn = self.connector.get_connection().await?;
+        let key = self.key(&descriptor_path(kind, id));
+
+        for _ in 0..MAX_WRITE_ATTEMPTS {
+            let current: Option<String> = conn.get(&key).await?;
+            let Some(current) = current else {
+                return Ok(());
+            };
+            let name_key = self
+                .stored_name_key(kind, id, current.clone())
+                .await?
+                .unwrap_or_else(|| key.clone());
+
+            let deleted: bool = Script::new(DELETE_SCRIPT)
+                .key(&key)
+                .key(name_key)
+                .arg(current)
+                .arg(id)
+                .invoke_async(&mut conn)
+                .await?;
+            if deleted {
+                return Ok(());
+            }
+        }
+        Err(anyhow!(
+            "{}/{} kept changing while deleting it, giving up",
+            kind,
+            id
+        ))
+    }
+
+    async fn get_id_by_name(
+        &self,
+        kind: DescriptorKind,
+        namespace: Option<&str>,
+        name: &str,
+    ) -> Result<Option<String>> {
+        let mut conn = self.connector.get_connection().await?;
+        Ok(conn.get(self.name_key(kind, namespace, name)).await?)
+    }
+
+    async fn claim_name(
+        &self,
+        kind: DescriptorKind,
+        namespace: Option<&str>,
+        name: &str,
+        id: &str,
+    ) -> Result<String> {
+        let mut conn = self.connector.get_connection().await?;
+        let key = self.name_key(kind, namespace, name);
+
+        let claimed: bool = conn.set_nx(&key, id).await?;
+        if claimed {
+            return Ok(id.to_string());
+        }
+        let existing: Option<String> = conn.get(&key).await?;
+        existing.ok_or_else(|| anyhow!("name index entry {} vanished while reading it", key))
+    }
+
+    async fn list_descriptors_page<T: DeserializeOwned + Send>(
+        &self,
+        kind: DescriptorKind,
+        cursor: u64,
+        limit: usize,
+    ) -> Result<DescriptorPage<T>> {
+        let mut conn = self.connector.get_connection().await?;
+
+        let (next_cursor, keys) = scan_page(
+            &mut conn,
+            &self.key(&format!("descriptor/{}/*", kind)),
+            cursor,
+            limit,
+        )
+        .await?;
+
+        let (read_at, values) = get_values_at(&mut conn, &keys).await?;
+        let mut descriptors = Vec::new();
+        for (key, value) in values {
+            descriptors.push(serde_json::from_str(
+                &descriptor_encryption::open(value, self.path_of(&key)).await?,
+            )?);
+        }
+
+        Ok(DescriptorPage {
+            next_cursor,
+            descriptors,
+            read_at,
+        })
+    }
+
+    async fn get_descriptor_at<T: DeserializeOwned>(
+        &self,
+        id: &str,
+        kind: DescriptorKind,
+        at: DateTime<Utc>,
+    ) -> Result<Option<DescriptorRevision<T>>> {
+        let mut conn = self.connector.get_connection().await?;
+
+        let revisions: Vec<String> = conn
+            .zrevrangebyscore_limit(
+                self.revisions_key(kind, id),
+                at.timestamp_millis(),
+                "-inf",
+                0,
+                1,
+            )
+            .await?;
+        let Some(revision) = revisions.into_iter().next() else {
+            return Ok(None);
+        };
+
+        let (stored_at, descriptor_json) = revision
+            .split_once(':')
+            .ok_or_else(|| anyhow!("malformed revision of {}/{}", kind, id))?;
+        let stored_at = Utc
+            .timestamp_millis_opt(stored_at.parse()?)
+            .single()
+            .ok_or_else(|| anyhow!("revision of {}/{} has an invalid timestamp", kind, id))?;
+        Ok(Some(DescriptorRevision {
+            stored_at,
+            descriptor: serde_json::from_str(
+                &descriptor_encryption::open(
+                    descriptor_json.to_string(),
+                    &descriptor_path(kind, id),
+                )
+                .await?,
+            )?,
+        }))
+    }
 }
 
 impl RedisDescriptorStore {
-    pub async fn new(url: &str) -> Result<Self> {
-        let client = redis::Client::open(url)?;
+    pub async fn new(conf: &RedisConf) -> Result<Self> {
+        Ok(Self {
+            connector: RedisConnector::new(conf)?,
+            key_prefix: conf.key_prefix.clone(),
+        })
+    }
+
+    fn key(&self, key: &str) -> String {
+        prefixed(&self.key_prefix, key)
+    }
+
+    // A descriptor key without the prefix, what its envelope is bound to
+    fn path_of<'a>(&self, key: &'a str) -> &'a str {
+        key.strip_prefix(&self.key("")).unwrap_or(key)
+    }
+
+    fn revisions_key(&self, kind: DescriptorKind, id: &str) -> String {
+        self.key(&format!("descriptor-revisions/{}/{}", kind, id))
+    }
+
+    // Name index key of a descriptor's json
+    // NOTE: mirrors `IdentifiableDescriptor::namespace`, tables are named within their database
+    fn value_name_key(&self, kind: DescriptorKind, descriptor: &Value) -> Option<String> {
+        let name = descriptor["name"].as_str()?;
+        let namespace = match kind {
+            DescriptorKind::Table => descriptor["database"].as_str(),
+            _ => None,
+        };
+        Some(self.name_key(kind, namespace, name))
+    }
+
+    // Name index key of the descriptor as it's stored, to release the name on rename or delete
+    async fn stored_name_key(
+        &self,
+        kind: DescriptorKind,
+        id: &str,
+        stored: String,
+    ) -> Result<Option<String>> {
+        let descriptor: Value = serde_json::from_str(
+            &descriptor_encryption::open(stored, &descriptor_path(kind, id)).await?,
+        )?;
+        Ok(self.value_name_key(kind, &descriptor))
+    }
 
-        Ok(Self { client })
+    // Stores the descriptors and claims their names in one script, either all of them are stored
+    // or none are. Retried when one of them is stored or deleted concurrently, since the name it
+    // had is only known from reading it first.
+    // NOTE: revisions lead with the time, so storing the same descriptor again adds a revision
+    //       rather than moving the earlier one. They outlive their descriptor, what a deleted one
+    //       looked like can still be found.
+    async fn write_descriptors(
+        &self,
+        writes: Vec<DescriptorWrite>,
+        also_set: &[(String, String)],
+    ) -> Result<()> {
+        if writes.is_empty() {
+            return Ok(());
+        }
+        for (i, write) in writes.iter().enumerate() {
+            if let Some(other) = writes[..i]
+                .iter()
+                .find(|w| w.name_key.is_some() && w.name_key == write.name_key && w.id != write.id)
+            {
+                return Err(NameTaken {
+                    name: self
+                        .path_of(write.name_key.as_deref().unwrap_or_default())
+                        .to_string(),
+                    owner: other.id.clone(),
+                }
+                .into());
+            }
+        }
+
+        let mut conn = self.connector.get_connection().await?;
+        let keys: Vec<String> = writes
+            .iter()
+            .map(|w| self.key(&descriptor_path(w.kind, &w.id)))
+            .collect();
+        let mut sealed = Vec::new();
+        for write in writes.iter() {
+            sealed.push(
+                descriptor_encryption::seal(
+                    write.json.clone(),
+                    &descriptor_path(write.kind, &write.id),
+                )
+                .await?,
+            );
+        }
+
+        let store_script = Script::new(STORE_SCRIPT);
+        for _ in 0..MAX_WRITE_ATTEMPTS {
+            let current: Vec<Option<String>> =
+                redis::cmd("MGET").arg(&keys).query_async(&mut conn).await?;
+
+            let now = Utc::now().timestamp_millis();
+            let mut script = store_script.prepare_invoke();
+            script.arg(now).arg(-(MAX_REVISIONS + 1)).arg(writes.len());
+            for (((write, key), descriptor_json), current) in
+                writes.iter().zip(&keys).zip(&sealed).zip(current)
+            {
+                let stored_name_key = match &current {
+                    Some(t) => {
+                        self.stored_name_key(write.kind, &write.id, t.clone())
+                            .await?
+                    }
+                    None => None,
+                };
+                script
+                    .key(key)
+                    .key(self.revisions_key(write.kind, &write.id))
+                    .key(write.name_key.as_deref().unwrap_or(key))
+                    .key(stored_name_key.as_deref().unwrap_or(key))
+                    .arg(&write.id)
+                    .arg(current.unwrap_or_default())
+                    .arg(descriptor_json)
+                    .arg(format!("{}:{}", now, descriptor_json))
+                    .arg(u8::from(write.name_key.is_some()))
+                    .arg(u8::from(stored_name_key.is_some()));
+            }
+            for (key, value) in also_set {
+                script.key(self.key(key)).arg(value);
+            }
+
+            let outcome: Vec<String> = script.invoke_async(&mut conn).await?;
+            match outcome.as_slice() {
+                [ok] if ok == "ok" => return Ok(()),
+                [changed, _] if changed == "changed" => continue,
+                [taken, name_key, owner] if taken == "taken" => {
+                    return Err(NameTaken {
+                        name: self.path_of(name_key).to_string(),
+                        owner: owner.clone(),
+                    }
+                    .into())
+                }
+                other => return Err(anyhow!("unexpected store script outcome {:?}", other)),
+            }
+        }
+        Err(anyhow!(
+            "descriptors kept changing while storing them, giving up"
+        ))
     }
+
+    fn name_key(&self, kind: DescriptorKind, namespace: Option<&str>, name: &str) -> String {
+        self.key(&match namespace {
+            Some(namespace) => format!("name_index/{}/{}/{}", kind, namespace, name),
+            None => format!("name_index/{}/{}", kind, name),
+        })
+    }
+}
+
+// What a descriptor's envelope is bound to, its key without the prefix so it still opens after the
+// prefix is migrated
+fn descriptor_path(kind: DescriptorKind, id: &str) -> String {
+    format!("descriptor/{}/{}", kind, id)
+}
+
+// Reads the keys in chunked MGETs, each chunk its own round trip so other clients get a turn in
+// between. Values from different chunks can be from either side of a concurrent update.
+// NOTE: keys deleted since they were listed come back empty and are skipped
+async fn get_values<C: ConnectionLike + Send>(
+    conn: &mut C,
+    keys: &[String],
+) -> Result<Vec<(String, String)>> {
+    let mut values = Vec::new();
+    for chunk_keys in keys.chunks(MGET_CHUNK_SIZE) {
+        let chunk: Vec<Option<String>> =
+            redis::cmd("MGET").arg(chunk_keys).query_async(conn).await?;
+        values.extend(
+            chunk_keys
+                .iter()
+                .zip(chunk)
+                .filter_map(|(key, value)| Some((key.clone(), value?))),
+        );
+    }
+    Ok(values)
+}
+
+// Reads a page of keys in one MGET along with redis' TIME in the same MULTI, so every value is
+// read at the same instant rather than some before and some after a concurrent update. Returns
+// that instant along with the values by their keys.
+// NOTE: pages are bounded by the list limit, small enough not to need chunking
+async fn get_values_at<C: ConnectionLike + Send>(
+    conn: &mut C,
+    keys: &[String],
+) -> Result<(DateTime<Utc>, Vec<(String, String)>)> {
+    let mut pipe = redis::pipe();
+    pipe.atomic().cmd("TIME");
+    if !keys.is_empty() {
+        pipe.cmd("MGET").arg(keys);
+    }
+    let results: Vec<redis::Value> = pipe.query_async(conn).await?;
+
+    let mut results = results.into_iter();
+    let (secs, micros): (i64, u32) = redis::from_redis_value(
+        &results
+            .next()
+            .ok_or_else(|| anyhow!("TIME returned nothing"))?,
+    )?;
+    let read_at = Utc
+        .timestamp_opt(secs, micros * 1000)
+        .single()
+        .ok_or_else(|| anyhow!("redis returned an invalid TIME"))?;
+
+    let values: Vec<Option<String>> = match results.next() {
+        Some(result) => redis::from_redis_value(&result)?,
+        None => vec![],
+    };
+    Ok((
+        read_at,
+        keys.iter()
+            .zip(values)
+            .filter_map(|(key, value)| Some((key.clone(), value?)))
+            .collect(),
+    ))
 }
@@ -0,0 +1,197 @@
+use anyhow::Result;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::RedisConf,
+    fluid::descriptor::DescriptorKind,
+    redis_connection::RedisConnector,
+    redis_namespace::{prefixed, scan_page},
+};
+
+const IN_FLIGHT_SCAN_PAGE: usize = 100;
+
+// An event whose descriptor is being stored, checkpointed before anything is written so a watcher
+// that dies part way through can tell what it left behind when it starts again
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InFlightEvent {
+    pub kind: DescriptorKind,
+    pub descriptor_id: String,
+    pub revision: u32,
+}
+
+// Tracks which events, and which descriptor revisions, have already been applied so redelivered
+// or republished events can be skipped without refetching the descriptor
+#[async_trait::async_trait]
+pub(crate) trait EventDedupStore {
+    async fn is_event_seen(&self, event_id: &str) -> Result<bool>;
+    async fn mark_event_seen(&self, event_id: &str) -> Result<()>;
+    async fn is_revision_seen(&self, kind: DescriptorKind, id: &str, revision: u32)
+        -> Result<bool>;
+    async fn mark_revision_seen(&self, kind: DescriptorKind, id: &str, revision: u32)
+        -> Result<()>;
+    // Latest revision stored for the descriptor, kept for good so older ones can't regress it
+    async fn get_latest_revision(&self, kind: DescriptorKind, id: &str) -> Result<Option<u32>>;
+    async fn set_latest_revision(
+        &self,
+        kind: DescriptorKind,
+        id: &str,
+        revision: u32,
+    ) -> Result<()>;
+    async fn begin_event(&self, event_id: &str, event: &InFlightEvent) -> Result<()>;
+    async fn finish_event(&self, event_id: &str) -> Result<()>;
+    async fn list_in_flight(&self) -> Result<Vec<(String, InFlightEvent)>>;
+}
+
+#[derive(Debug)]
+pub struct RedisEventDedupStore {
+    connector: RedisConnector,
+    key_prefix: String,
+    ttl_secs: usize,
+}
+
+#[async_trait::async_trait]
+impl EventDedupStore for RedisEventDedupStore {
+    async fn is_event_seen(&self, event_id: &str) -> Result<bool> {
+        let mut conn = self.connector.get_connection().await?;
+        Ok(conn
+            .exists(self.key(&format!("event-dedup/event/{}", event_id)))
+            .await?)
+    }
+
+    async fn mark_event_seen(&self, event_id: &str) -> Result<()> {
+        let mut conn = self.connector.get_connection().await?;
+        let _: () = conn
+            .set_ex(
+                self.key(&format!("event-dedup/event/{}", event_id)),
+                1,
+                self.ttl_secs,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn is_revision_seen(
+        &self,
+        kind: DescriptorKind,
+        id: &str,
+        revision: u32,
+    ) -> Result<bool> {
+        let mut conn = self.connector.get_connection().await?;
+        Ok(conn
+            .exists(self.key(&format!(
+                "event-dedup/revision/{}/{}/{}",
+                kind, id, revision
+            )))
+            .await?)
+    }
+
+    async fn mark_revision_seen(
+        &self,
+        kind: DescriptorKind,
+        id: &str,
+        revision: u32,
+    ) -> Result<()> {
+        let mut conn = self.connector.get_connection().await?;
+        let _: () = conn
+            .set_ex(
+                self.key(&format!(
+                    "event-dedup/revision/{}/{}/{}",
+                    kind, id, revision
+                )),
+                1,
+                self.ttl_secs,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn get_latest_revision(&self, kind: DescriptorKind, id: &str) -> Result<Option<u32>> {
+        let mut conn = self.connector.get_connection().await?;
+        Ok(conn
+            .get(self.key(&format!("event-dedup/latest/{}/{}", kind, id)))
+            .await?)
+    }
+
+    // NOTE: not compare-and-set, two watchers storing revisions of one descriptor at once can
+    //       still leave the older one recorded
+    async fn set_latest_revision(
+        &self,
+        kind: DescriptorKind,
+        id: &str,
+        revision: u32,
+    ) -> Result<()> {
+        let mut conn = self.connector.get_connection().await?;
+        let _: () = conn
+            .set(
+                self.key(&format!("event-dedup/latest/{}/{}", kind, id)),
+                revision,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn begin_event(&self, event_id: &str, event: &InFlightEvent) -> Result<()> {
+        let mut conn = self.connector.get_connection().await?;
+        let _: () = conn
+            .set_ex(
+                self.key(&format!("event-dedup/in-flight/{}", event_id)),
+                serde_json::to_string(event)?,
+                self.ttl_secs,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn finish_event(&self, event_id: &str) -> Result<()> {
+        let mut conn = self.connector.get_connection().await?;
+        let _: () = conn
+            .del(self.key(&format!("event-dedup/in-flight/{}", event_id)))
+            .await?;
+        Ok(())
+    }
+
+    async fn list_in_flight(&self) -> Result<Vec<(String, InFlightEvent)>> {
+        let mut conn = self.connector.get_connection().await?;
+        let key_root = self.key("event-dedup/in-flight/");
+
+        let mut in_flight = Vec::new();
+        let mut cursor = 0;
+        loop {
+            let (next_cursor, keys) = scan_page(
+                &mut conn,
+                &format!("{}*", key_root),
+                cursor,
+                IN_FLIGHT_SCAN_PAGE,
+            )
+            .await?;
+            for key in keys {
+                let value: Option<String> = conn.get(&key).await?;
+                // NOTE: finished since it was listed
+                let Some(value) = value else {
+                    continue;
+                };
+                let event_id = key.trim_start_matches(&key_root).to_string();
+                in_flight.push((event_id, serde_json::from_str(&value)?));
+            }
+            if next_cursor == 0 {
+                return Ok(in_flight);
+            }
+            cursor = next_cursor;
+        }
+    }
+}
+
+impl RedisEventDedupStore {
+    pub async fn new(conf: &RedisConf, ttl_secs: u64) -> Result<Self> {
+        Ok(Self {
+            connector: RedisConnector::new(conf)?,
+            key_prefix: conf.key_prefix.clone(),
+            ttl_secs: ttl_secs as usize,
+        })
+    }
+
+    fn key(&self, key: &str) -> String {
+        prefixed(&self.key_prefix, key)
+    }
+}
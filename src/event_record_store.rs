@@ -0,0 +1,130 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::RedisConf, redis_connection::RedisConnector, redis_namespace::prefixed};
+
+// Records are only useful for confirming recent deliveries, don't keep them around forever
+const EVENT_RECORD_TTL_SECS: usize = 7 * 24 * 60 * 60;
+const RECENT_EVENTS_KEY: &str = "event-record-recent";
+const RECENT_EVENTS_MAX: isize = 1000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum EventOutcome {
+    // Descriptor was fetched and stored
+    Stored,
+    // Event or descriptor revision was already processed
+    Duplicate,
+    // Descriptor revision is older than the one already stored, dropped rather than regressing it
+    Stale,
+    // Descriptor would break the consumers of the stored one, it's held for an approver
+    AwaitingApproval,
+    // Event was understood but intentionally not acted on (e.g. unsupported kind)
+    Skipped,
+    // Event could not be processed, it will be redelivered
+    Failed,
+    // Event didn't match the registered schema or its descriptor was denied by policy, it was
+    // dropped rather than redelivered
+    Rejected,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EventRecord {
+    pub event_id: String,
+    pub kind: String,
+    pub descriptor_uri: String,
+    pub descriptor_id: Option<String>,
+    // Absent on records of events which couldn't be parsed far enough to tell
+    #[serde(default)]
+    pub revision: Option<u32>,
+    pub outcome: EventOutcome,
+    pub error: Option<String>,
+    pub processed_at: DateTime<Utc>,
+}
+
+#[async_trait::async_trait]
+pub(crate) trait EventRecordStore {
+    async fn put_record(&self, record: &EventRecord) -> Result<()>;
+    async fn get_record(&self, event_id: &str) -> Result<Option<EventRecord>>;
+    async fn list_recent(&self, limit: usize) -> Result<Vec<EventRecord>>;
+}
+
+#[derive(Debug)]
+pub struct RedisEventRecordStore {
+    connector: RedisConnector,
+    key_prefix: String,
+}
+
+#[async_trait::async_trait]
+impl EventRecordStore for RedisEventRecordStore {
+    async fn put_record(&self, record: &EventRecord) -> Result<()> {
+        let mut conn = self.connector.get_connection().await?;
+
+        let recent_key = self.key(RECENT_EVENTS_KEY);
+        redis::pipe()
+            .set_ex(
+                self.key(&format!("event-record/{}", record.event_id)),
+                serde_json::to_string(record)?,
+                EVENT_RECORD_TTL_SECS,
+            )
+            .lrem(&recent_key, 0, &record.event_id)
+            .lpush(&recent_key, &record.event_id)
+            .ltrim(&recent_key, 0, RECENT_EVENTS_MAX - 1)
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_record(&self, event_id: &str) -> Result<Option<EventRecord>> {
+        let mut conn = self.connector.get_connection().await?;
+        let record: Option<String> = conn
+            .get(self.key(&format!("event-record/{}", event_id)))
+            .await?;
+        Ok(if let Some(t) = record {
+            Some(serde_json::from_str(&t)?)
+        } else {
+            None
+        })
+    }
+
+    async fn list_recent(&self, limit: usize) -> Result<Vec<EventRecord>> {
+        let mut conn = self.connector.get_connection().await?;
+
+        let limit = (limit as isize).clamp(1, RECENT_EVENTS_MAX);
+        let event_ids: Vec<String> = conn
+            .lrange(self.key(RECENT_EVENTS_KEY), 0, limit - 1)
+            .await?;
+        if event_ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let keys: Vec<String> = event_ids
+            .iter()
+            .map(|id| self.key(&format!("event-record/{}", id)))
+            .collect();
+        let records: Vec<Option<String>> =
+            redis::cmd("MGET").arg(&keys).query_async(&mut conn).await?;
+
+        // NOTE: records expire independently of the recent list, so skip any that have aged out
+        let mut out = Vec::new();
+        for record in records.into_iter().flatten() {
+            out.push(serde_json::from_str(&record)?);
+        }
+        Ok(out)
+    }
+}
+
+impl RedisEventRecordStore {
+    pub async fn new(conf: &RedisConf) -> Result<Self> {
+        Ok(Self {
+            connector: RedisConnector::new(conf)?,
+            key_prefix: conf.key_prefix.clone(),
+        })
+    }
+
+    fn key(&self, key: &str) -> String {
+        prefixed(&self.key_prefix, key)
+    }
+}
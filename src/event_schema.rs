@@ -0,0 +1,161 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use aws_config::SdkConfig;
+use aws_sdk_glue::model::{DataFormat, SchemaId, SchemaVersionNumber};
+use jsonschema::JSONSchema;
+use serde::Deserialize;
+use serde_json::Value;
+use thiserror::Error;
+use tokio::sync::Mutex;
+use tracing::info;
+
+use crate::config::{ConfluentRegistryConf, GlueRegistryConf, SchemaRegistryConf};
+
+// An event which doesn't match the registered schema. Redelivering it won't change that, so it's
+// dropped rather than left on the queue.
+#[derive(Error, Debug)]
+#[error("event doesn't match schema version {version}: {}", .violations.join("; "))]
+pub struct SchemaViolation {
+    pub version: String,
+    pub violations: Vec<String>,
+}
+
+struct CachedSchema {
+    version: String,
+    schema: Arc<JSONSchema>,
+    fetched_at: Instant,
+}
+
+// Validates events against the latest json schema registered for them, refetched every
+// `refresh_secs` so new schema versions are picked up without a restart
+pub struct EventSchemaValidator {
+    conf: SchemaRegistryConf,
+    http_client: reqwest::Client,
+    glue_client: aws_sdk_glue::Client,
+    cached: Mutex<Option<CachedSchema>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ConfluentSchema {
+    version: u32,
+    // Confluent leaves it out for avro, the default
+    schema_type: Option<String>,
+    schema: String,
+}
+
+impl EventSchemaValidator {
+    pub fn new(conf: &SchemaRegistryConf, aws_creds: &SdkConfig) -> Self {
+        EventSchemaValidator {
+            conf: conf.clone(),
+            http_client: reqwest::Client::new(),
+            glue_client: aws_sdk_glue::Client::new(aws_creds),
+            cached: Mutex::new(None),
+        }
+    }
+
+    // Err is a SchemaViolation when the event is invalid, anything else means the schema couldn't
+    // be fetched and the event should be retried
+    pub async fn validate(&self, event: &Value) -> Result<()> {
+        let (version, schema) = self.schema().await?;
+        let violations: Vec<String> = match schema.validate(event) {
+            Ok(()) => return Ok(()),
+            Err(errors) => errors
+                .map(|e| format!("{} at '{}'", e, e.instance_path))
+                .collect(),
+        };
+        Err(SchemaViolation {
+            version,
+            violations,
+        }
+        .into())
+    }
+
+    async fn schema(&self) -> Result<(String, Arc<JSONSchema>)> {
+        let mut cached = self.cached.lock().await;
+        let refresh = Duration::from_secs(self.conf.refresh_secs);
+        if let Some(c) = cached.as_ref()
+            && c.fetched_at.elapsed() < refresh
+        {
+            return Ok((c.version.clone(), c.schema.clone()));
+        }
+
+        let (version, definition) = match (&self.conf.confluent, &self.conf.glue) {
+            (Some(confluent), _) => self.fetch_confluent(confluent).await?,
+            (None, Some(glue)) => self.fetch_glue(glue).await?,
+            (None, None) => bail!("schema_registry has no registry configured"),
+        };
+        if cached.as_ref().map_or(true, |c| c.version != version) {
+            info!(version, "loaded event schema");
+        }
+
+        let definition: Value =
+            serde_json::from_str(&definition).context("registered event schema isn't json")?;
+        let schema = JSONSchema::compile(&definition)
+            .map_err(|e| anyhow!("registered event schema is invalid: {}", e))?;
+        let schema = Arc::new(schema);
+        *cached = Some(CachedSchema {
+            version: version.clone(),
+            schema: schema.clone(),
+            fetched_at: Instant::now(),
+        });
+        Ok((version, schema))
+    }
+
+    async fn fetch_confluent(&self, conf: &ConfluentRegistryConf) -> Result<(String, String)> {
+        let mut request = self.http_client.get(format!(
+            "{}/subjects/{}/versions/latest",
+            conf.url.trim_end_matches('/'),
+            conf.subject
+        ));
+        if let Some(username) = &conf.username {
+            request = request.basic_auth(username, conf.password.as_ref());
+        }
+        let schema: ConfluentSchema = request
+            .send()
+            .await?
+            .error_for_status()
+            .context("failed to fetch event schema from the schema registry")?
+            .json()
+            .await?;
+
+        if schema.schema_type.as_deref() != Some("JSON") {
+            bail!(
+                "subject '{}' isn't a json schema, events are validated as json",
+                conf.subject
+            );
+        }
+        Ok((schema.version.to_string(), schema.schema))
+    }
+
+    async fn fetch_glue(&self, conf: &GlueRegistryConf) -> Result<(String, String)> {
+        let resp = self
+            .glue_client
+            .get_schema_version()
+            .schema_id(
+                SchemaId::builder()
+                    .registry_name(&conf.registry_name)
+                    .schema_name(&conf.schema_name)
+                    .build(),
+            )
+            .schema_version_number(SchemaVersionNumber::builder().latest_version(true).build())
+            .send()
+            .await
+            .context("failed to fetch event schema from glue")?;
+
+        if resp.data_format() != Some(&DataFormat::Json) {
+            bail!(
+                "schema '{}' isn't a json schema, events are validated as json",
+                conf.schema_name
+            );
+        }
+        let definition = resp
+            .schema_definition()
+            .ok_or_else(|| anyhow!("glue returned no schema definition"))?;
+        Ok((resp.version_number().to_string(), definition.to_string()))
+    }
+}
@@ -1 +1,2 @@
 pub mod descriptor;
+pub mod encoding;
@@ -1,8 +1,165 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+pub mod bundle;
 pub mod database;
 pub mod flow;
+pub mod landing_zone;
+pub mod quality;
 pub mod table;
 
 pub trait IdentifiableDescriptor {
     fn id(&self) -> String;
-    fn kind(&self) -> String;
+    // Only used to fill in the id of descriptors submitted without one
+    fn set_id(&mut self, id: String);
+    fn name(&self) -> String;
+    // Names are unique within a kind and namespace, e.g. tables' within their database
+    fn namespace(&self) -> Option<String> {
+        None
+    }
+    fn kind(&self) -> DescriptorKind;
+    fn priority(&self) -> DescriptorPriority;
+    fn owner(&self) -> Option<Owner>;
+    // What replacing `stored` with this would break for the descriptor's consumers, if anything
+    fn breaking_changes(&self, _stored: &Self) -> Vec<String>
+    where
+        Self: Sized,
+    {
+        vec![]
+    }
+}
+
+// NOTE: matched exhaustively wherever kinds are handled differently, adding one points out every
+//       place it needs handling
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum DescriptorKind {
+    Database,
+    Table,
+    Flow,
+    LandingZone,
+}
+
+impl DescriptorKind {
+    pub const ALL: [DescriptorKind; 4] = [
+        DescriptorKind::Database,
+        DescriptorKind::Table,
+        DescriptorKind::Flow,
+        DescriptorKind::LandingZone,
+    ];
+
+    // Also how the kind appears in routes and store keys
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DescriptorKind::Database => "database",
+            DescriptorKind::Table => "table",
+            DescriptorKind::Flow => "flow",
+            DescriptorKind::LandingZone => "landing_zone",
+        }
+    }
+}
+
+impl fmt::Display for DescriptorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for DescriptorKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DescriptorKind::ALL
+            .into_iter()
+            .find(|k| k.as_str() == s)
+            .ok_or_else(|| anyhow!("unknown kind {}", s))
+    }
+}
+
+// Stamped on provisioned resources so they can be matched against the stored descriptor without
+// comparing them field by field
+pub fn descriptor_hash<T: Serialize>(descriptor: &T) -> String {
+    // NOTE: descriptors only hold structs and ordered maps so their json is stable
+    let json = serde_json::to_vec(descriptor).expect("descriptors always serialize");
+    format!("{:x}", Sha256::digest(json))
+}
+
+// Who to contact about a descriptor's resources, propagated as tags and sent along with state
+// events
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Owner {
+    pub team: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    // e.g. `#data-platform`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slack_channel: Option<String>,
+}
+
+impl Owner {
+    // Any of the team, email or slack channel, the channel with or without its `#`
+    pub fn matches(&self, needle: &str) -> bool {
+        self.team == needle
+            || self.email.as_deref() == Some(needle)
+            || self
+                .slack_channel
+                .as_deref()
+                .map(|c| c.trim_start_matches('#'))
+                == Some(needle.trim_start_matches('#'))
+    }
+}
+
+// NOTE: declaration order matters, higher variants are reconciled first
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum DescriptorPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+    Critical,
+}
+
+impl DescriptorPriority {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DescriptorPriority::Low => "low",
+            DescriptorPriority::Normal => "normal",
+            DescriptorPriority::High => "high",
+            DescriptorPriority::Critical => "critical",
+        }
+    }
+}
+
+// Where a database and its tables are provisioned
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageEngine {
+    // Glue catalog over s3
+    #[default]
+    Glue,
+    Snowflake,
+    Bigquery,
+}
+
+// Metastore glue engine databases and their tables are registered in
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Catalog {
+    #[default]
+    Glue,
+    // A hive metastore reached over thrift, see `[hive_metastore]`
+    Hive,
+}
+
+impl Catalog {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Catalog::Glue => "glue",
+            Catalog::Hive => "hive",
+        }
+    }
 }
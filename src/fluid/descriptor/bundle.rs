@@ -0,0 +1,95 @@
+use std::collections::HashSet;
+
+use anyhow::{bail, ensure, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{
+    database::DatabaseDescriptor,
+    flow::{FlowCondition, FlowDescriptor, FlowUpstreamKind},
+    table::TableDescriptor,
+    DescriptorKind, IdentifiableDescriptor,
+};
+
+// A database along with its tables and flows, submitted as one. It isn't a kind of its own, the
+// members are stored as ordinary descriptors and reconciled by their own controllers, in stages:
+// the database, then its tables, then the flows.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BundleDescriptor {
+    pub id: String,
+    pub database: DatabaseDescriptor,
+    #[serde(default)]
+    pub tables: Vec<TableDescriptor>,
+    #[serde(default)]
+    pub flows: Vec<FlowDescriptor>,
+}
+
+impl BundleDescriptor {
+    // Checks the members against each other. Returns the references which point outside the
+    // bundle, those have to already be stored.
+    pub fn validate(&self) -> Result<Vec<(DescriptorKind, String)>> {
+        let mut ids = HashSet::new();
+        for id in self.member_ids() {
+            ensure!(ids.insert(id), "'{}' is in the bundle more than once", id);
+        }
+
+        for table in self.tables.iter() {
+            ensure!(
+                table.database == self.database.id,
+                "table '{}' belongs to database '{}', not the bundle's database '{}'",
+                table.id,
+                table.database,
+                self.database.id
+            );
+        }
+
+        let tables: HashSet<&str> = self.tables.iter().map(|t| t.id.as_str()).collect();
+        let flows: HashSet<&str> = self.flows.iter().map(|f| f.id.as_str()).collect();
+        let mut external = Vec::new();
+        for flow in self.flows.iter() {
+            for condition in flow.all_conditions() {
+                let FlowCondition::Upstream(upstream) = condition else {
+                    continue;
+                };
+                let (kind, members) = match upstream.kind {
+                    FlowUpstreamKind::Table => (DescriptorKind::Table, &tables),
+                    FlowUpstreamKind::Flow => (DescriptorKind::Flow, &flows),
+                };
+                if upstream.upstream == flow.id {
+                    bail!("flow '{}' can't be its own upstream", flow.id);
+                }
+                if !members.contains(upstream.upstream.as_str()) {
+                    external.push((kind, upstream.upstream.clone()));
+                }
+            }
+        }
+
+        Ok(external)
+    }
+
+    pub fn member_ids(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.database.id.as_str())
+            .chain(self.tables.iter().map(|t| t.id.as_str()))
+            .chain(self.flows.iter().map(|f| f.id.as_str()))
+    }
+
+    // Every member as it's stored, in the order they're reconciled
+    pub fn members(&self) -> Result<Vec<(DescriptorKind, String, Value)>> {
+        let mut members = vec![(
+            DescriptorKind::Database,
+            self.database.id(),
+            serde_json::to_value(&self.database)?,
+        )];
+        for table in self.tables.iter() {
+            members.push((
+                DescriptorKind::Table,
+                table.id(),
+                serde_json::to_value(table)?,
+            ));
+        }
+        for flow in self.flows.iter() {
+            members.push((DescriptorKind::Flow, flow.id(), serde_json::to_value(flow)?));
+        }
+        Ok(members)
+    }
+}
@@ -1,20 +1,55 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
-use super::IdentifiableDescriptor;
+use super::{
+    table::TableDefaults, Catalog, DescriptorKind, DescriptorPriority, IdentifiableDescriptor,
+    Owner, StorageEngine,
+};
 
 // NOTE: probably more thought needs to be put into this esp re versioning
 #[derive(Serialize, Deserialize, Debug)]
 pub struct DatabaseDescriptor {
+    // Assigned by basin when submitted without one
+    #[serde(default)]
     pub id: String,
     pub name: String,
     pub summary: String,
+    #[serde(default)]
+    pub engine: StorageEngine,
+    // Only for the glue engine, `storage.catalog` is used when left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub catalog: Option<Catalog>,
+    #[serde(default)]
+    pub priority: DescriptorPriority,
+    // Free form, those listed in `cost.propagated_labels` end up as tags on provisioned resources
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<Owner>,
+    // Settings the database's tables inherit unless they set their own, resolved when each table
+    // is reconciled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub defaults: Option<TableDefaults>,
 }
 
 impl IdentifiableDescriptor for DatabaseDescriptor {
     fn id(&self) -> String {
         self.id.clone()
     }
-    fn kind(&self) -> String {
-        String::from("database")
+    fn set_id(&mut self, id: String) {
+        self.id = id;
+    }
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+    fn kind(&self) -> DescriptorKind {
+        DescriptorKind::Database
+    }
+    fn priority(&self) -> DescriptorPriority {
+        self.priority
+    }
+    fn owner(&self) -> Option<Owner> {
+        self.owner.clone()
     }
 }
@@ -1,14 +1,46 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
-use super::IdentifiableDescriptor;
+use super::{DescriptorKind, DescriptorPriority, IdentifiableDescriptor, Owner};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct FlowDescriptor {
+    // Assigned by basin when submitted without one
+    #[serde(default)]
     pub id: String,
     pub name: String,
     pub summary: String,
-    pub condition: FlowCondition,
+    // NOTE: single condition form predating `conditions`, still accepted and merged in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<FlowCondition>,
+    #[serde(default)]
+    pub conditions: Vec<FlowCondition>,
+    // How multiple conditions combine to start a run
+    #[serde(default)]
+    pub condition_mode: FlowConditionMode,
     pub steps: Vec<FlowStep>,
+    // Where the flow is deployed, the configured default when unset
+    #[serde(default)]
+    pub backend: Option<FlowBackend>,
+    #[serde(default)]
+    pub priority: DescriptorPriority,
+    // Free form, those listed in `cost.propagated_labels` end up as tags on provisioned resources
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<Owner>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FlowBackend {
+    #[default]
+    Waterwheel,
+    // A glue workflow with a glue job per step
+    Glue,
+    // A step functions state machine running a glue job per step
+    StepFunctions,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -18,6 +50,16 @@ pub enum FlowCondition {
     Upstream(FlowUpstreamCondition),
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FlowConditionMode {
+    // Any one condition starts a run (OR)
+    #[default]
+    Any,
+    // A run starts once every condition has been met (AND)
+    All,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct FlowCronCondition {
     pub schedule: String,
@@ -26,6 +68,18 @@ pub struct FlowCronCondition {
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct FlowUpstreamCondition {
     pub upstream: String,
+    #[serde(default)]
+    pub kind: FlowUpstreamKind,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FlowUpstreamKind {
+    // Runs after each run of the upstream flow
+    #[default]
+    Flow,
+    // Runs when data lands under the upstream table's location, see `[data_triggers]`
+    Table,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -40,12 +94,42 @@ pub struct FlowStep {
     pub parents: Vec<String>, // TODO: serde defaults
     pub timeout: String,
     pub transformation: FlowStepTransformation,
+    // Container image the step runs in, defaults to the configured flow image
+    #[serde(default)]
+    pub image: Option<String>,
+    #[serde(default)]
+    pub resources: Option<FlowStepResources>,
+    // Times a failed run of the step is retried before the flow run fails
+    #[serde(default)]
+    pub retries: Option<u32>,
+    // Wait between retries, e.g. `30s`, `5m`
+    #[serde(default)]
+    pub retry_delay: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FlowStepResources {
+    #[serde(default)]
+    pub requests: Option<FlowResourceQuantities>,
+    #[serde(default)]
+    pub limits: Option<FlowResourceQuantities>,
+}
+
+// Kubernetes style quantities, e.g. cpu `500m` or `2`, memory `512Mi` or `4Gi`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FlowResourceQuantities {
+    #[serde(default)]
+    pub cpu: Option<String>,
+    #[serde(default)]
+    pub memory: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "snake_case")]
 pub enum FlowStepTransformation {
     Sql(FlowSqlTransformation),
+    Dbt(FlowDbtTransformation),
+    Spark(FlowSparkTransformation),
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -53,12 +137,85 @@ pub struct FlowSqlTransformation {
     pub sql: String,
 }
 
+// Runs `dbt build` for a project checked out of git, against a profile basin generates
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FlowDbtTransformation {
+    // Anything `git fetch` accepts, e.g. `https://github.com/org/project.git`
+    pub repository: String,
+    // Branch, tag or commit sha to build
+    pub git_ref: String,
+    // Path to the dbt project within the repository
+    #[serde(default)]
+    pub project_dir: Option<String>,
+    // Passed to `dbt build --select`, builds the whole project when unset
+    #[serde(default)]
+    pub selector: Option<String>,
+    // Name of the basin database models are built into
+    pub database: String,
+}
+
+// Submits a spark application, where it runs is down to the configured spark runner
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FlowSparkTransformation {
+    // Script (`.py`) or jar the application is started from, e.g. `s3://bucket/jobs/etl.py`
+    pub application: String,
+    // Entrypoint class, jars only
+    #[serde(default)]
+    pub main_class: Option<String>,
+    // Passed to the application, templated like sql steps
+    #[serde(default)]
+    pub args: Vec<String>,
+    // Extra `--conf` settings, e.g. `spark.executor.memory = "4g"`
+    #[serde(default)]
+    pub conf: BTreeMap<String, String>,
+}
+
+impl FlowDescriptor {
+    pub fn all_conditions(&self) -> impl Iterator<Item = &FlowCondition> {
+        self.condition.iter().chain(self.conditions.iter())
+    }
+
+    // Tables whose data arriving starts the flow
+    pub fn upstream_tables(&self) -> impl Iterator<Item = &str> {
+        self.all_conditions()
+            .filter_map(|condition| match condition {
+                FlowCondition::Upstream(u) if u.kind == FlowUpstreamKind::Table => {
+                    Some(u.upstream.as_str())
+                }
+                _ => None,
+            })
+    }
+
+    // Steps nothing else depends on, the flow has finished once all of these have
+    pub fn sink_steps(&self) -> impl Iterator<Item = &FlowStep> {
+        self.steps.iter().filter(|step| {
+            !self
+                .steps
+                .iter()
+                .any(|other| other.parents.contains(&step.name))
+        })
+    }
+}
+
 impl IdentifiableDescriptor for FlowDescriptor {
     fn id(&self) -> String {
         self.id.clone()
     }
+    fn set_id(&mut self, id: String) {
+        self.id = id;
+    }
 
-    fn kind(&self) -> String {
-        String::from("flow")
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+    fn kind(&self) -> DescriptorKind {
+        DescriptorKind::Flow
+    }
+
+    fn priority(&self) -> DescriptorPriority {
+        self.priority
+    }
+    fn owner(&self) -> Option<Owner> {
+        self.owner.clone()
     }
 }
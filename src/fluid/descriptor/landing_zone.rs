@@ -0,0 +1,74 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    flow::{FlowBackend, FlowCondition},
+    DescriptorKind, DescriptorPriority, IdentifiableDescriptor, Owner,
+};
+
+// An upload prefix producers drop raw files into, converted into a managed table by a flow basin
+// generates for it
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LandingZoneDescriptor {
+    // Assigned by basin when submitted without one
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    pub summary: String,
+    // Id of the (glue) table descriptor files are converted into
+    pub table: String,
+    pub format: LandingZoneFormat,
+    // When the conversion runs, hourly when unset
+    #[serde(default)]
+    pub conditions: Vec<FlowCondition>,
+    // Where the conversion flow is deployed, the configured default when unset
+    #[serde(default)]
+    pub backend: Option<FlowBackend>,
+    #[serde(default)]
+    pub priority: DescriptorPriority,
+    // Free form, those listed in `cost.propagated_labels` end up as tags on provisioned resources
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<Owner>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LandingZoneFormat {
+    // With a header row
+    Csv,
+    // One object per line
+    Json,
+}
+
+impl LandingZoneFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LandingZoneFormat::Csv => "csv",
+            LandingZoneFormat::Json => "json",
+        }
+    }
+}
+
+impl IdentifiableDescriptor for LandingZoneDescriptor {
+    fn id(&self) -> String {
+        self.id.clone()
+    }
+    fn set_id(&mut self, id: String) {
+        self.id = id;
+    }
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+    fn kind(&self) -> DescriptorKind {
+        DescriptorKind::LandingZone
+    }
+    fn priority(&self) -> DescriptorPriority {
+        self.priority
+    }
+    fn owner(&self) -> Option<Owner> {
+        self.owner.clone()
+    }
+}
@@ -0,0 +1,114 @@
+use anyhow::{anyhow, ensure, Result};
+use serde::{Deserialize, Serialize};
+
+use super::flow::FlowBackend;
+
+// Data quality rules a table's data is validated against after each run of the flow loading it.
+// Glue tables only, validated by a flow basin manages for the table, see `[quality]`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TableQuality {
+    // Id of the flow loading the table, validation runs after each of its runs
+    pub flow: String,
+    pub rules: Vec<QualityRule>,
+    // Fail the validation run on a broken rule rather than only recording it
+    #[serde(default)]
+    pub fail_on_error: bool,
+    // Where validation runs, the configured flow default when unset
+    #[serde(default)]
+    pub backend: Option<FlowBackend>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum QualityRule {
+    NotNull {
+        column: String,
+    },
+    Unique {
+        column: String,
+    },
+    AcceptedValues {
+        column: String,
+        values: Vec<String>,
+    },
+    // Inclusive, either bound can be left off
+    Between {
+        column: String,
+        #[serde(default)]
+        min: Option<f64>,
+        #[serde(default)]
+        max: Option<f64>,
+    },
+    Matches {
+        column: String,
+        pattern: String,
+    },
+    RowCount {
+        #[serde(default)]
+        min: Option<u64>,
+        #[serde(default)]
+        max: Option<u64>,
+    },
+}
+
+impl QualityRule {
+    pub fn column(&self) -> Option<&str> {
+        match self {
+            QualityRule::NotNull { column }
+            | QualityRule::Unique { column }
+            | QualityRule::AcceptedValues { column, .. }
+            | QualityRule::Between { column, .. }
+            | QualityRule::Matches { column, .. } => Some(column),
+            QualityRule::RowCount { .. } => None,
+        }
+    }
+}
+
+impl TableQuality {
+    // Checks what can be checked without the table, columns are checked against it by the caller
+    pub fn validate(&self) -> Result<()> {
+        ensure!(
+            !self.flow.is_empty(),
+            "quality needs the flow loading the table"
+        );
+        ensure!(!self.rules.is_empty(), "quality needs at least one rule");
+        for rule in self.rules.iter() {
+            match rule {
+                QualityRule::AcceptedValues { column, values } => ensure!(
+                    !values.is_empty(),
+                    "accepted_values rule on '{}' needs at least one value",
+                    column
+                ),
+                QualityRule::Between { column, min, max } => {
+                    ensure!(
+                        min.is_some() || max.is_some(),
+                        "between rule on '{}' needs a min, a max or both",
+                        column
+                    );
+                    ensure!(
+                        min.zip(*max).map_or(true, |(min, max)| min <= max),
+                        "between rule on '{}' has a min above its max",
+                        column
+                    );
+                }
+                QualityRule::Matches { column, pattern } => {
+                    regex::Regex::new(pattern).map_err(|e| {
+                        anyhow!("matches rule on '{}' has an invalid pattern: {}", column, e)
+                    })?;
+                }
+                QualityRule::RowCount { min, max } => {
+                    ensure!(
+                        min.is_some() || max.is_some(),
+                        "row_count rule needs a min, a max or both"
+                    );
+                    ensure!(
+                        min.zip(*max).map_or(true, |(min, max)| min <= max),
+                        "row_count rule has a min above its max"
+                    );
+                }
+                QualityRule::NotNull { .. } | QualityRule::Unique { .. } => {}
+            }
+        }
+        Ok(())
+    }
+}
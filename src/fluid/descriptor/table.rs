@@ -1,33 +1,374 @@
+use std::collections::BTreeMap;
+
+use anyhow::{ensure, Context, Result};
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 
-use super::IdentifiableDescriptor;
+use super::{
+    flow::{FlowBackend, FlowCondition},
+    quality::TableQuality,
+    DescriptorKind, DescriptorPriority, IdentifiableDescriptor, Owner, StorageEngine,
+};
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct TableDescriptor {
+    // Assigned by basin when submitted without one
+    #[serde(default)]
     pub id: String,
     pub name: String,
     pub summary: String,
     pub columns: Vec<TableColumnAttribute>,
     pub database: String,
+    // Overrides the conventional `s3://{database bucket}/{name}` location, e.g. `s3://bucket/path`
+    #[serde(default)]
+    pub location: Option<String>,
+    // Defaults to the database's engine, tables can't live on a different engine to their database
+    #[serde(default)]
+    pub engine: Option<StorageEngine>,
+    #[serde(default)]
+    pub priority: DescriptorPriority,
+    // Free form, those listed in `cost.propagated_labels` end up as tags on the flows basin
+    // manages for the table, over the database's labels
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    // Upstream revision of the descriptor, recorded against the resources it owns
+    #[serde(default)]
+    pub revision: Option<u32>,
+    // Glue tables only, column statistics are refreshed by a flow basin manages for the table
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub statistics: Option<TableStatistics>,
+    // Glue tables only, plain (hive style) files when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<TableFormat>,
+    // Iceberg and delta tables only, run as a flow basin manages for the table
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maintenance: Option<TableMaintenance>,
+    // Glue tables only, how stale the table's data is allowed to get before basin flags it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub freshness: Option<TableFreshness>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<Owner>,
+    // Glue tables only, how the table's files are compressed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<TableCompression>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub classification: Option<DataClassification>,
+    // Glue tables only, when the table's objects are expired
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lifecycle: Option<TableLifecycle>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<DescriptiveMetadata>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<TableQuality>,
+    // Glue tables only, columns the table's data is partitioned by in order, e.g. `dt` for
+    // objects under `{location}/dt=2024-01-01/`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub partitioned_by: Vec<String>,
+    // Glue tables only, has athena compute the partitions rather than look them up, so they never
+    // need registering
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub partition_projection: Option<PartitionProjection>,
+}
+
+impl TableDescriptor {
+    // The partition columns in partition key order, ones that aren't columns are left out
+    pub fn partition_columns(&self) -> Vec<&TableColumnAttribute> {
+        self.partitioned_by
+            .iter()
+            .filter_map(|name| self.columns.iter().find(|c| &c.name == name))
+            .collect()
+    }
+
+    // Labels of the flows basin manages for the table, the table's own over its database's
+    pub fn managed_flow_labels(
+        &self,
+        database_labels: &BTreeMap<String, String>,
+    ) -> BTreeMap<String, String> {
+        let mut labels = database_labels.clone();
+        labels.extend(self.labels.clone());
+        labels
+    }
+
+    // The table with anything it leaves unset taken from its database's defaults
+    pub fn with_defaults(&self, defaults: Option<&TableDefaults>) -> TableDescriptor {
+        let mut table = self.clone();
+        let Some(defaults) = defaults else {
+            return table;
+        };
+        table.format = table.format.or(defaults.format);
+        table.compression = table.compression.or(defaults.compression);
+        table.classification = table.classification.or(defaults.classification);
+        table.owner = table.owner.or_else(|| defaults.owner.clone());
+        table.lifecycle = table.lifecycle.or_else(|| defaults.lifecycle.clone());
+        table
+    }
+}
+
+// Set on a database, inherited by each of its tables which doesn't set its own
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct TableDefaults {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<TableFormat>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<TableCompression>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub classification: Option<DataClassification>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<Owner>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lifecycle: Option<TableLifecycle>,
+}
+
+// NOTE: basin only registers the table, the format's metadata is written by whatever writes data
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TableFormat {
+    Iceberg,
+    Delta,
+}
+
+impl TableFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TableFormat::Iceberg => "iceberg",
+            TableFormat::Delta => "delta",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TableCompression {
+    Snappy,
+    Gzip,
+    Zstd,
+    Uncompressed,
+}
+
+impl TableCompression {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TableCompression::Snappy => "snappy",
+            TableCompression::Gzip => "gzip",
+            TableCompression::Zstd => "zstd",
+            TableCompression::Uncompressed => "uncompressed",
+        }
+    }
+}
+
+// How sensitive the table's data is, recorded against the table for governance tooling to act on
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DataClassification {
+    Public,
+    Internal,
+    Confidential,
+    Restricted,
+}
+
+impl DataClassification {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DataClassification::Public => "public",
+            DataClassification::Internal => "internal",
+            DataClassification::Confidential => "confidential",
+            DataClassification::Restricted => "restricted",
+        }
+    }
+}
+
+// NOTE: applies to every object under the table's location, whatever wrote it
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TableLifecycle {
+    pub expire_after_days: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TableMaintenance {
+    // Cron schedule maintenance runs on
+    pub schedule: String,
+    // Rewrite small files into larger ones
+    #[serde(default)]
+    pub compaction: bool,
+    // Expire snapshots older than this
+    #[serde(default)]
+    pub snapshot_retention_hours: Option<u32>,
+    // Remove files no snapshot has referenced for this long
+    #[serde(default)]
+    pub orphan_file_retention_hours: Option<u32>,
+    // Where maintenance runs, the configured flow default when unset
+    #[serde(default)]
+    pub backend: Option<FlowBackend>,
+}
+
+// Data is as fresh as the newest object under the table's location, whatever flow writes it
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TableFreshness {
+    // Oldest the newest data may be, e.g. `6h` or `1d`
+    pub max_age: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TableStatistics {
+    // When to refresh, usually an upstream condition on the flow loading the table
+    pub conditions: Vec<FlowCondition>,
+    // Columns to compute statistics for, all of them when empty
+    #[serde(default)]
+    pub columns: Vec<String>,
+    // Where the refresh runs, the configured flow default when unset
+    #[serde(default)]
+    pub backend: Option<FlowBackend>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PartitionProjection {
+    pub projection: ProjectionMode,
+    // What each partition key's projection can't be worked out from its type, by key
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub keys: BTreeMap<String, PartitionKeyProjection>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectionMode {
+    // Dates are projected daily, strings as enums, integers as ranges and booleans as both values
+    Auto,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PartitionKeyProjection {
+    // Date keys, the first day with data. Days are projected from then until today.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<NaiveDate>,
+    // String keys, every value the key takes
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub values: Vec<String>,
+    // Int and long keys, the lowest and highest value the key takes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub range: Option<(i64, i64)>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct TableColumnAttribute {
     pub id: String,
     pub name: String,
     pub summary: String,
     pub codec: TableColumnCodec,
     pub nullable: bool,
+    // Sql expression for the value writers get when they leave the column out, e.g. `0` or
+    // `current_timestamp()`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    // Sql expression over the table's other columns the column is computed from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generated: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<DescriptiveMetadata>,
+    // Glue and snowflake tables only, how the column is hidden from those not privileged to see it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub masking: Option<ColumnMasking>,
+}
+
+// NOTE: lake formation can only leave columns out, so on glue tables every policy hides the
+//       column from the principals in `[masking]` whatever it asks for
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ColumnMasking {
+    pub policy: MaskingPolicy,
+    // Snowflake roles which see the real values
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub privileged_roles: Vec<String>,
+    // Partial only, trailing characters left as they are
+    #[serde(default = "default_partial_visible_chars")]
+    pub visible_chars: u32,
+}
+
+fn default_partial_visible_chars() -> u32 {
+    4
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MaskingPolicy {
+    // Sha-256 of the value, still joinable on
+    Hash,
+    // All but the trailing `visible_chars` characters starred out
+    Partial,
+    Null,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+// Business context for a table or column beyond its summary, recorded in the catalog for catalog
+// uis to show and link out from
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct DescriptiveMetadata {
+    // Ids of the business glossary terms it's an instance of
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub glossary_terms: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub docs: Vec<DocLink>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deprecation: Option<Deprecation>,
+}
+
+impl DescriptiveMetadata {
+    pub fn validate(&self) -> Result<()> {
+        for (i, term) in self.glossary_terms.iter().enumerate() {
+            ensure!(
+                !term.trim().is_empty() && !term.contains(','),
+                "glossary term id '{}' is empty or has a comma",
+                term
+            );
+            ensure!(
+                !self.glossary_terms[..i].contains(term),
+                "glossary term '{}' is listed more than once",
+                term
+            );
+        }
+        for doc in self.docs.iter() {
+            let url = reqwest::Url::parse(&doc.url)
+                .with_context(|| format!("invalid doc url '{}'", doc.url))?;
+            ensure!(
+                matches!(url.scheme(), "http" | "https"),
+                "doc url '{}' must be http or https",
+                doc.url
+            );
+        }
+        if let Some(deprecation) = &self.deprecation {
+            ensure!(
+                !deprecation.note.trim().is_empty(),
+                "a deprecation needs a note"
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DocLink {
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+// NOTE: only a notice for consumers, a deprecated table or column is provisioned like any other
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Deprecation {
+    pub note: String,
+    // What to use instead, e.g. another table or column
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replaced_by: Option<String>,
+    // When it's expected to go away
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sunset: Option<NaiveDate>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct TableColumnCodec {
     #[serde(rename = "type")]
     pub kind: TableColumnType,
     // FIXME: we don't support any of the constraints
 }
 
-#[derive(PartialEq, Serialize, Deserialize, Debug)]
+#[derive(PartialEq, Serialize, Deserialize, Clone, Debug)]
 pub enum TableColumnType {
     Int,
     Long,
@@ -44,7 +385,46 @@ impl IdentifiableDescriptor for TableDescriptor {
     fn id(&self) -> String {
         self.id.clone()
     }
-    fn kind(&self) -> String {
-        String::from("table")
+    fn set_id(&mut self, id: String) {
+        self.id = id;
+    }
+    fn namespace(&self) -> Option<String> {
+        Some(self.database.clone())
+    }
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+    fn kind(&self) -> DescriptorKind {
+        DescriptorKind::Table
+    }
+    fn priority(&self) -> DescriptorPriority {
+        self.priority
+    }
+    fn owner(&self) -> Option<Owner> {
+        self.owner.clone()
+    }
+    // NOTE: columns are matched by name, a renamed column reads as one dropped and one added
+    fn breaking_changes(&self, stored: &Self) -> Vec<String> {
+        let mut changes = Vec::new();
+        for old in &stored.columns {
+            match self.columns.iter().find(|c| c.name == old.name) {
+                None => changes.push(format!("column {} is dropped", old.name)),
+                Some(new) if new.codec.kind != old.codec.kind => changes.push(format!(
+                    "column {} changes type from {:?} to {:?}",
+                    old.name, old.codec.kind, new.codec.kind
+                )),
+                Some(new) if old.nullable && !new.nullable => {
+                    changes.push(format!("column {} is no longer nullable", old.name))
+                }
+                Some(_) => (),
+            }
+        }
+        if self.partitioned_by != stored.partitioned_by {
+            changes.push(format!(
+                "partition keys change from {:?} to {:?}",
+                stored.partitioned_by, self.partitioned_by
+            ));
+        }
+        changes
     }
 }
@@ -0,0 +1,114 @@
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Result};
+use prost::Message;
+use prost_types::{value::Kind, Struct};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Number, Value};
+
+// What descriptors are requested as when fetched from upstream, json first
+pub const ACCEPTED_CONTENT_TYPES: &str =
+    "application/json, application/yaml;q=0.9, application/avro;q=0.9, application/x-protobuf;q=0.8";
+
+// Encodings descriptors can arrive in. Avro and protobuf are turned into the json form of the
+// descriptor first, so they deserialize into the models the same way json does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadFormat {
+    Json,
+    Yaml,
+    // An avro object container file, which carries the writer's schema with it
+    Avro,
+    // A google.protobuf.Struct, there's no schema to decode anything more specific with
+    Protobuf,
+}
+
+impl PayloadFormat {
+    // NOTE: no content type is taken to be json, as it was before anything else was accepted
+    pub fn from_content_type(content_type: Option<&str>) -> Result<Self> {
+        let Some(content_type) = content_type else {
+            return Ok(PayloadFormat::Json);
+        };
+        let mut params = content_type.split(';').map(str::trim);
+        let mime = params.next().unwrap_or_default().to_ascii_lowercase();
+
+        Ok(match mime.as_str() {
+            "application/json" => PayloadFormat::Json,
+            "application/yaml" | "application/x-yaml" | "text/yaml" => PayloadFormat::Yaml,
+            "application/avro" | "avro/binary" => PayloadFormat::Avro,
+            "application/x-protobuf" | "application/protobuf" => {
+                if let Some(message_type) = params.find_map(|p| p.strip_prefix("messageType="))
+                    && message_type.trim_matches('"') != "google.protobuf.Struct"
+                {
+                    bail!(
+                        "unsupported protobuf message type {}, descriptors are sent as google.protobuf.Struct",
+                        message_type
+                    );
+                }
+                PayloadFormat::Protobuf
+            }
+            _ => bail!("unsupported content type {}", content_type),
+        })
+    }
+
+    // For descriptor files, None when the file isn't a descriptor
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "json" => Some(PayloadFormat::Json),
+            "yaml" | "yml" => Some(PayloadFormat::Yaml),
+            _ => None,
+        }
+    }
+}
+
+pub fn decode<T: DeserializeOwned>(format: PayloadFormat, body: &[u8]) -> Result<T> {
+    let value = match format {
+        // NOTE: both errors carry the line and column they're at
+        PayloadFormat::Json => return Ok(serde_json::from_slice(body)?),
+        PayloadFormat::Yaml => return Ok(serde_yaml::from_slice(body)?),
+        PayloadFormat::Avro => avro_to_json(body)?,
+        PayloadFormat::Protobuf => {
+            let message = Struct::decode(body).context("invalid protobuf Struct")?;
+            struct_to_json(message)
+        }
+    };
+    Ok(serde_json::from_value(value)?)
+}
+
+fn avro_to_json(body: &[u8]) -> Result<Value> {
+    let mut records = apache_avro::Reader::new(body).context("invalid avro container")?;
+    let record = records
+        .next()
+        .ok_or_else(|| anyhow!("avro container holds no records"))??;
+    if records.next().is_some() {
+        bail!("avro container holds more than one descriptor");
+    }
+    Ok(Value::try_from(record)?)
+}
+
+fn struct_to_json(message: Struct) -> Value {
+    Value::Object(
+        message
+            .fields
+            .into_iter()
+            .map(|(k, v)| (k, kind_to_json(v.kind)))
+            .collect::<Map<_, _>>(),
+    )
+}
+
+fn kind_to_json(kind: Option<Kind>) -> Value {
+    match kind {
+        None | Some(Kind::NullValue(_)) => Value::Null,
+        Some(Kind::BoolValue(b)) => Value::Bool(b),
+        Some(Kind::StringValue(s)) => Value::String(s),
+        // NOTE: Struct only has doubles, whole ones are turned back into integers so they still
+        //       deserialize into integer fields
+        Some(Kind::NumberValue(n)) if n.fract() == 0.0 && n.abs() < (1u64 << 53) as f64 => {
+            Value::Number(Number::from(n as i64))
+        }
+        Some(Kind::NumberValue(n)) => Number::from_f64(n).map_or(Value::Null, Value::Number),
+        Some(Kind::StructValue(s)) => struct_to_json(s),
+        Some(Kind::ListValue(l)) => {
+            Value::Array(l.values.into_iter().map(|v| kind_to_json(v.kind)).collect())
+        }
+    }
+}
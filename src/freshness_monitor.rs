@@ -0,0 +1,164 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::Utc;
+use tokio::time::{interval, MissedTickBehavior};
+use tracing::{error, info, warn};
+
+use crate::{
+    config::{BasinConfig, FreshnessConf},
+    controller::{flow::parse_duration_secs, naming::table_location},
+    deployment_state_store::{DeploymentState, DeploymentStateStore, RedisDeploymentStateStore},
+    descriptor_store::{DescriptorStore, RedisDescriptorStore},
+    fluid::descriptor::{
+        database::DatabaseDescriptor, table::TableDescriptor, DescriptorKind, StorageEngine,
+    },
+    freshness_store::{FreshnessStatus, FreshnessStore, RedisFreshnessStore},
+    metrics,
+    provisioner::s3::S3Provisioner,
+    state_events::{StateEventPublisher, StateEventType},
+};
+
+// Checks deployed tables with a freshness sla against the newest object under their location.
+// Only transitions in and out of a breach are published, not every check of a stale table.
+pub struct FreshnessMonitor {
+    conf: FreshnessConf,
+    descriptor_store: RedisDescriptorStore,
+    deployment_state_store: RedisDeploymentStateStore,
+    freshness_store: RedisFreshnessStore,
+    s3_provisioner: S3Provisioner,
+    state_event_publisher: StateEventPublisher,
+}
+
+impl FreshnessMonitor {
+    pub async fn new(conf: &BasinConfig) -> Result<Option<Self>> {
+        let Some(freshness) = &conf.freshness else {
+            return Ok(None);
+        };
+
+        Ok(Some(FreshnessMonitor {
+            conf: freshness.clone(),
+            descriptor_store: RedisDescriptorStore::new(&conf.redis).await?,
+            deployment_state_store: RedisDeploymentStateStore::new(&conf.redis).await?,
+            freshness_store: RedisFreshnessStore::new(&conf.redis).await?,
+            s3_provisioner: S3Provisioner::new(&conf.aws_creds),
+            state_event_publisher: StateEventPublisher::new(conf),
+        }))
+    }
+
+    pub async fn check_loop(&self) -> ! {
+        let mut ticker = interval(Duration::from_secs(self.conf.interval_secs));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+
+            info!("Checking table freshness");
+            match self.check_tables().await {
+                Ok(breached) => info!(breached, "finished checking table freshness"),
+                Err(e) => error!(?e, "error when checking table freshness"),
+            }
+        }
+    }
+
+    async fn check_tables(&self) -> Result<usize> {
+        let mut breached = 0;
+        for table in self
+            .descriptor_store
+            .list_descriptors::<TableDescriptor>(DescriptorKind::Table)
+            .await?
+        {
+            // NOTE: one table's failed probe shouldn't hold up the rest
+            match self.check_table(&table).await {
+                Ok(Some(false)) => breached += 1,
+                Ok(_) => {}
+                Err(e) => warn!(table_id = table.id, ?e, "failed to check table freshness"),
+            }
+        }
+        metrics::gauge_set("basin_tables_freshness_breached", &[], breached as f64);
+
+        Ok(breached)
+    }
+
+    // Whether the table's data is fresh enough, None when it isn't checked
+    async fn check_table(&self, table: &TableDescriptor) -> Result<Option<bool>> {
+        let Some(freshness) = &table.freshness else {
+            self.freshness_store.delete_status(&table.id).await?;
+            return Ok(None);
+        };
+        let Some(info) = self.deployment_state_store.get_state(&table.id).await? else {
+            return Ok(None);
+        };
+        if info.state != DeploymentState::Succeeded {
+            return Ok(None);
+        }
+        let Some(db) = self
+            .descriptor_store
+            .get_descriptor::<DatabaseDescriptor>(&table.database, DescriptorKind::Database)
+            .await?
+        else {
+            return Ok(None);
+        };
+        if table.engine.unwrap_or(db.engine) != StorageEngine::Glue {
+            return Ok(None);
+        }
+
+        let max_age_secs = parse_duration_secs(&freshness.max_age)?;
+        let (bucket, prefix) = table_location(table, &db)?;
+        let last_updated_at = self
+            .s3_provisioner
+            .last_modified(&bucket, &prefix, table.partitioned_by.len())
+            .await?;
+
+        let now = Utc::now();
+        let compliant = last_updated_at.map_or(false, |t| {
+            now - t <= chrono::Duration::seconds(max_age_secs as i64)
+        });
+        let previous = self.freshness_store.get_status(&table.id).await?;
+        let was_compliant = previous.as_ref().map_or(true, |p| p.compliant);
+        let breached_since = match (compliant, previous.and_then(|p| p.breached_since)) {
+            (true, _) => None,
+            (false, Some(since)) => Some(since),
+            (false, None) => Some(now),
+        };
+
+        self.freshness_store
+            .set_status(
+                &table.id,
+                &FreshnessStatus {
+                    max_age_secs,
+                    last_updated_at,
+                    checked_at: now,
+                    compliant,
+                    breached_since,
+                },
+            )
+            .await?;
+
+        if compliant != was_compliant {
+            let event_type = if compliant {
+                info!(table_id = table.id, "table is fresh again");
+                StateEventType::FreshnessRestored
+            } else {
+                warn!(
+                    table_id = table.id,
+                    ?last_updated_at,
+                    max_age = freshness.max_age,
+                    "table breached its freshness sla"
+                );
+                StateEventType::FreshnessBreached
+            };
+            self.state_event_publisher
+                .publish(
+                    event_type,
+                    DescriptorKind::Table,
+                    &table.id,
+                    table.owner.as_ref(),
+                    &info,
+                )
+                .await;
+        }
+
+        Ok(Some(compliant))
+    }
+}
@@ -0,0 +1,76 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::RedisConf, redis_connection::RedisConnector, redis_namespace::prefixed};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FreshnessStatus {
+    pub max_age_secs: u64,
+    // Newest data found under the table's location, None while it's empty
+    pub last_updated_at: Option<DateTime<Utc>>,
+    pub checked_at: DateTime<Utc>,
+    pub compliant: bool,
+    // Start of the current breach, kept across checks so it's clear how long it's been going on
+    pub breached_since: Option<DateTime<Utc>>,
+}
+
+#[async_trait::async_trait]
+pub(crate) trait FreshnessStore {
+    async fn get_status(&self, table_id: &str) -> Result<Option<FreshnessStatus>>;
+    async fn set_status(&self, table_id: &str, status: &FreshnessStatus) -> Result<()>;
+    async fn delete_status(&self, table_id: &str) -> Result<()>;
+}
+
+#[derive(Debug)]
+pub struct RedisFreshnessStore {
+    connector: RedisConnector,
+    key_prefix: String,
+}
+
+#[async_trait::async_trait]
+impl FreshnessStore for RedisFreshnessStore {
+    async fn get_status(&self, table_id: &str) -> Result<Option<FreshnessStatus>> {
+        let mut conn = self.connector.get_connection().await?;
+        let status: Option<String> = conn
+            .get(self.key(&format!("freshness/{}", table_id)))
+            .await?;
+        Ok(match status {
+            Some(s) => Some(serde_json::from_str(&s)?),
+            None => None,
+        })
+    }
+
+    async fn set_status(&self, table_id: &str, status: &FreshnessStatus) -> Result<()> {
+        let mut conn = self.connector.get_connection().await?;
+        let _: () = conn
+            .set(
+                self.key(&format!("freshness/{}", table_id)),
+                serde_json::to_string(status)?,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_status(&self, table_id: &str) -> Result<()> {
+        let mut conn = self.connector.get_connection().await?;
+        let _: () = conn
+            .del(self.key(&format!("freshness/{}", table_id)))
+            .await?;
+        Ok(())
+    }
+}
+
+impl RedisFreshnessStore {
+    pub async fn new(conf: &RedisConf) -> Result<Self> {
+        Ok(Self {
+            connector: RedisConnector::new(conf)?,
+            key_prefix: conf.key_prefix.clone(),
+        })
+    }
+
+    fn key(&self, key: &str) -> String {
+        prefixed(&self.key_prefix, key)
+    }
+}
@@ -0,0 +1,371 @@
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use tokio::{
+    process::Command,
+    sync::Notify,
+    time::{interval, MissedTickBehavior},
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    approval_gate::{Admission, ApprovalGate},
+    config::{BasinConfig, ControllersConf, EventWatcherConf, GitSyncConf, LimitsConf},
+    constants::CONTROLLER_DISABLED,
+    deployment_state_store::{
+        DeploymentHistory, DeploymentInfo, DeploymentState, DeploymentStateStore,
+        RedisDeploymentStateStore,
+    },
+    descriptor_store::{DescriptorStore, RedisDescriptorStore},
+    fluid::{
+        descriptor::{
+            database::DatabaseDescriptor, flow::FlowDescriptor,
+            landing_zone::LandingZoneDescriptor, table::TableDescriptor, DescriptorKind,
+            IdentifiableDescriptor,
+        },
+        encoding::{self, PayloadFormat},
+    },
+    metrics,
+    payload_limits::PayloadLimited,
+    policy::PolicyChecker,
+    state_events::{StateEventPublisher, StateEventType},
+};
+
+// Syncs descriptors from a git repository, laid out as `<path>/<kind>/**/*.{json,yaml,yml}` (e.g.
+// `descriptors/table/sales/orders.yaml`). Added and changed files are stored, descriptors which
+// are stored but no longer in the repository are only flagged, never deleted.
+pub struct GitSync {
+    conf: GitSyncConf,
+    descriptor_store: RedisDescriptorStore,
+    deployment_state_store: RedisDeploymentStateStore,
+    limits: LimitsConf,
+    controllers: ControllersConf,
+    event_watcher: EventWatcherConf,
+    state_event_publisher: StateEventPublisher,
+    policy_checker: Option<PolicyChecker>,
+    approval_gate: ApprovalGate,
+    // Set off by the push webhook, syncing ahead of the next interval
+    trigger: Notify,
+}
+
+#[derive(Debug, Default)]
+struct SyncSummary {
+    stored: usize,
+    unchanged: usize,
+    invalid: usize,
+    orphaned: usize,
+}
+
+impl GitSync {
+    pub async fn new(conf: &BasinConfig) -> Result<Option<Self>> {
+        let Some(git_sync) = &conf.git_sync else {
+            return Ok(None);
+        };
+
+        Ok(Some(GitSync {
+            conf: git_sync.clone(),
+            descriptor_store: RedisDescriptorStore::new(&conf.redis).await?,
+            deployment_state_store: RedisDeploymentStateStore::new(&conf.redis).await?,
+            limits: conf.limits.clone(),
+            controllers: conf.controllers.clone(),
+            event_watcher: conf.event_watcher.clone(),
+            state_event_publisher: StateEventPublisher::new(conf),
+            policy_checker: PolicyChecker::new(conf)?,
+            approval_gate: ApprovalGate::new(conf).await?,
+            trigger: Notify::new(),
+        }))
+    }
+
+    pub fn trigger(&self) {
+        self.trigger.notify_one();
+    }
+
+    pub async fn sync_loop(&self) -> ! {
+        let mut ticker = interval(Duration::from_secs(self.conf.interval_secs));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = self.trigger.notified() => {}
+            }
+
+            info!(
+                repo = %without_userinfo(&self.conf.repo_url),
+                "Syncing descriptors from git"
+            );
+            match self.sync().await {
+                Ok((commit, summary)) => {
+                    metrics::counter_inc("basin_git_sync_runs_total", &[("outcome", "ok")]);
+                    info!(commit, ?summary, "finished syncing descriptors from git");
+                }
+                Err(e) => {
+                    metrics::counter_inc("basin_git_sync_runs_total", &[("outcome", "error")]);
+                    error!(?e, "error when syncing descriptors from git");
+                }
+            }
+        }
+    }
+
+    async fn sync(&self) -> Result<(String, SyncSummary)> {
+        let commit = self.pull().await?;
+        let root = Path::new(&self.conf.checkout_dir).join(&self.conf.path);
+
+        let mut summary = SyncSummary::default();
+        for kind in DescriptorKind::ALL {
+            if !self.event_watcher.ingests(kind) {
+                continue;
+            }
+            let dir = root.join(kind.as_str());
+            match kind {
+                DescriptorKind::Database => {
+                    self.sync_kind::<DatabaseDescriptor>(kind, &dir, &commit, &mut summary)
+                        .await?
+                }
+                DescriptorKind::Table => {
+                    self.sync_kind::<TableDescriptor>(kind, &dir, &commit, &mut summary)
+                        .await?
+                }
+                DescriptorKind::Flow => {
+                    self.sync_kind::<FlowDescriptor>(kind, &dir, &commit, &mut summary)
+                        .await?
+                }
+                DescriptorKind::LandingZone => {
+                    self.sync_kind::<LandingZoneDescriptor>(kind, &dir, &commit, &mut summary)
+                        .await?
+                }
+            }
+        }
+
+        Ok((commit, summary))
+    }
+
+    // Brings the checkout up to the branch's head, cloning it on the first sync
+    async fn pull(&self) -> Result<String> {
+        let dir = self.conf.checkout_dir.as_str();
+        if Path::new(dir).join(".git").exists() {
+            git(&[
+                "-C",
+                dir,
+                "fetch",
+                "--depth",
+                "1",
+                "origin",
+                &self.conf.branch,
+            ])
+            .await?;
+            git(&["-C", dir, "reset", "--hard", "FETCH_HEAD"]).await?;
+        } else {
+            git(&[
+                "clone",
+                "--depth",
+                "1",
+                "--branch",
+                &self.conf.branch,
+                &self.conf.repo_url,
+                dir,
+            ])
+            .await?;
+        }
+        Ok(git(&["-C", dir, "rev-parse", "HEAD"])
+            .await?
+            .trim()
+            .to_string())
+    }
+
+    async fn sync_kind<
+        Descriptor: IdentifiableDescriptor + PayloadLimited + Serialize + DeserializeOwned + Sync,
+    >(
+        &self,
+        kind: DescriptorKind,
+        dir: &Path,
+        commit: &str,
+        summary: &mut SyncSummary,
+    ) -> Result<()> {
+        let mut in_repo = HashSet::new();
+        let mut invalid = 0;
+        for file in descriptor_files(dir)? {
+            // NOTE: one broken file shouldn't hold up the rest of the repository
+            match self.sync_file::<Descriptor>(&file, commit).await {
+                Ok((id, stored)) => {
+                    in_repo.insert(id);
+                    if stored {
+                        summary.stored += 1;
+                    } else {
+                        summary.unchanged += 1;
+                    }
+                }
+                Err(e) => {
+                    warn!(file = %file.display(), ?e, "failed to sync descriptor file");
+                    invalid += 1;
+                }
+            }
+        }
+        summary.invalid += invalid;
+        // NOTE: a file which failed could be for any stored descriptor, nothing is flagged until
+        //       it's fixed
+        if invalid > 0 {
+            return Ok(());
+        }
+
+        let orphaned: Vec<String> = self
+            .descriptor_store
+            .list_descriptors::<Value>(kind)
+            .await?
+            .iter()
+            .filter_map(|d| d["id"].as_str())
+            .filter(|id| !in_repo.contains(*id))
+            .map(str::to_string)
+            .collect();
+        if !orphaned.is_empty() {
+            warn!(
+                %kind,
+                ?orphaned,
+                "descriptors are stored but no longer in the repository, delete them through the api if they're meant to go"
+            );
+        }
+        metrics::gauge_set(
+            "basin_git_sync_orphaned_descriptors",
+            &[("kind", kind.as_str())],
+            orphaned.len() as f64,
+        );
+        summary.orphaned += orphaned.len();
+
+        Ok(())
+    }
+
+    // Stores the file's descriptor if it differs from what's stored, returning its id and whether
+    // it was stored
+    async fn sync_file<
+        Descriptor: IdentifiableDescriptor + PayloadLimited + Serialize + DeserializeOwned + Sync,
+    >(
+        &self,
+        file: &Path,
+        commit: &str,
+    ) -> Result<(String, bool)> {
+        let contents = tokio::fs::read(file).await?;
+        let format = PayloadFormat::from_path(file).expect("only descriptor files are synced");
+        let descriptor: Descriptor = encoding::decode(format, &contents)?;
+        descriptor.check_limits(&self.limits)?;
+
+        let stored = self
+            .descriptor_store
+            .get_descriptor::<Value>(&descriptor.id(), descriptor.kind())
+            .await?;
+        if stored.as_ref() == Some(&serde_json::to_value(&descriptor)?) {
+            return Ok((descriptor.id(), false));
+        }
+        if let Some(policy_checker) = &self.policy_checker {
+            policy_checker.check(&descriptor).await?;
+        }
+        if let Admission::Held(approval) = self
+            .approval_gate
+            .admit(&descriptor, None, Some(format!("git:{}", commit)))
+            .await?
+        {
+            info!(
+                descriptor_id = descriptor.id(),
+                file = %file.display(),
+                approval_id = approval.approval_id,
+                "descriptor changed in git, holding it for approval"
+            );
+            return Ok((descriptor.id(), false));
+        }
+
+        info!(
+            descriptor_id = descriptor.id(),
+            file = %file.display(),
+            "descriptor changed in git, storing it"
+        );
+        self.descriptor_store
+            .store_descriptor::<Descriptor>(&descriptor)
+            .await?;
+        let info = DeploymentInfo {
+            state: DeploymentState::Pending,
+            description: (!self.controllers.is_enabled(descriptor.kind()))
+                .then(|| CONTROLLER_DISABLED.to_string()),
+            // NOTE: the commit identifies what caused the deployment
+            request_id: Some(format!("git:{}", commit)),
+            updated_at: None,
+            permanent_failure: false,
+            attempts: 0,
+            delete_after: None,
+            history: DeploymentHistory::default(),
+        };
+        self.deployment_state_store
+            .set_state(&descriptor.id(), &info)
+            .await?;
+        self.state_event_publisher
+            .publish(
+                StateEventType::DescriptorStored,
+                descriptor.kind(),
+                &descriptor.id(),
+                descriptor.owner().as_ref(),
+                &info,
+            )
+            .await;
+
+        Ok((descriptor.id(), true))
+    }
+}
+
+// NOTE: credentials come from the url or the environment (ssh keys, credential helpers), git is
+//       left to handle them as it would for anyone else
+async fn git(args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .output()
+        .await
+        .context("failed to run git")?;
+    if !output.status.success() {
+        // NOTE: only the subcommand, the rest can hold a url with credentials in it
+        let subcommand = match args {
+            ["-C", _, subcommand, ..] | [subcommand, ..] => *subcommand,
+            [] => "",
+        };
+        bail!(
+            "git {} failed: {}",
+            subcommand,
+            without_userinfo(String::from_utf8_lossy(&output.stderr).trim())
+        );
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+// Blanks the credentials of any url in the text, git echoes the repo url back in its errors
+fn without_userinfo(text: &str) -> Cow<'_, str> {
+    static USERINFO: OnceLock<Regex> = OnceLock::new();
+    USERINFO
+        .get_or_init(|| {
+            Regex::new(r"(?P<scheme>[A-Za-z][A-Za-z0-9+.-]*://)[^/@\s]+@")
+                .expect("userinfo pattern is valid")
+        })
+        .replace_all(text, "${scheme}redacted@")
+}
+
+// Every descriptor file under the directory, none when the kind has no directory
+fn descriptor_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    if !dir.is_dir() {
+        return Ok(files);
+    }
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(descriptor_files(&path)?);
+        } else if PayloadFormat::from_path(&path).is_some() {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
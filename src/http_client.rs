@@ -0,0 +1,51 @@
+use std::{fs, sync::OnceLock, time::Duration};
+
+use anyhow::{anyhow, Context, Result};
+use reqwest::{Certificate, Proxy};
+
+use crate::config::HttpConf;
+
+static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+// Builds the client the event watcher, waterwheel and policy checks share, set once at startup
+pub fn install(conf: &HttpConf) -> Result<()> {
+    let _ = CLIENT.set(build(conf)?);
+    Ok(())
+}
+
+// Clones share one connection pool. Falls back to the default settings if nothing was installed.
+pub fn shared() -> reqwest::Client {
+    CLIENT
+        .get_or_init(|| build(&HttpConf::default()).expect("default http settings are valid"))
+        .clone()
+}
+
+fn build(conf: &HttpConf) -> Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder()
+        .connect_timeout(Duration::from_secs(conf.connect_timeout_secs))
+        .timeout(Duration::from_secs(conf.request_timeout_secs))
+        .pool_idle_timeout(Duration::from_secs(conf.pool_idle_timeout_secs))
+        .pool_max_idle_per_host(conf.pool_max_idle_per_host);
+    if let Some(proxy_url) = &conf.proxy_url {
+        builder = builder.proxy(Proxy::all(proxy_url).context("invalid http.proxy_url")?);
+    }
+    if let Some(path) = &conf.ca_bundle_path {
+        for cert in load_ca_bundle(path)? {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+    Ok(builder.build()?)
+}
+
+fn load_ca_bundle(path: &str) -> Result<Vec<Certificate>> {
+    let pem = fs::read(path).with_context(|| format!("opening ca bundle {}", path))?;
+    let certs = rustls_pemfile::certs(&mut pem.as_slice())?;
+    if certs.is_empty() {
+        return Err(anyhow!("no certificates found in {}", path));
+    }
+
+    Ok(certs
+        .into_iter()
+        .map(|der| Certificate::from_der(&der))
+        .collect::<reqwest::Result<_>>()?)
+}
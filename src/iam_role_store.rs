@@ -0,0 +1,74 @@
+use anyhow::Result;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::RedisConf, redis_connection::RedisConnector, redis_namespace::prefixed};
+
+// A role basin provisioned for a database, for consumers to find the one to assume
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DatabaseRole {
+    // `read` or `write`
+    pub access: String,
+    pub role_arn: String,
+    // What the role is granted, one readable line per statement of its policy
+    pub permissions: Vec<String>,
+}
+
+#[async_trait::async_trait]
+pub(crate) trait IamRoleStore {
+    async fn get_roles(&self, database_id: &str) -> Result<Vec<DatabaseRole>>;
+    async fn set_roles(&self, database_id: &str, roles: &[DatabaseRole]) -> Result<()>;
+    async fn delete_roles(&self, database_id: &str) -> Result<()>;
+}
+
+#[derive(Debug)]
+pub struct RedisIamRoleStore {
+    connector: RedisConnector,
+    key_prefix: String,
+}
+
+#[async_trait::async_trait]
+impl IamRoleStore for RedisIamRoleStore {
+    async fn get_roles(&self, database_id: &str) -> Result<Vec<DatabaseRole>> {
+        let mut conn = self.connector.get_connection().await?;
+        let roles: Option<String> = conn
+            .get(self.key(&format!("iam-roles/{}", database_id)))
+            .await?;
+        Ok(match roles {
+            Some(s) => serde_json::from_str(&s)?,
+            None => vec![],
+        })
+    }
+
+    async fn set_roles(&self, database_id: &str, roles: &[DatabaseRole]) -> Result<()> {
+        let mut conn = self.connector.get_connection().await?;
+        let _: () = conn
+            .set(
+                self.key(&format!("iam-roles/{}", database_id)),
+                serde_json::to_string(roles)?,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_roles(&self, database_id: &str) -> Result<()> {
+        let mut conn = self.connector.get_connection().await?;
+        let _: () = conn
+            .del(self.key(&format!("iam-roles/{}", database_id)))
+            .await?;
+        Ok(())
+    }
+}
+
+impl RedisIamRoleStore {
+    pub async fn new(conf: &RedisConf) -> Result<Self> {
+        Ok(Self {
+            connector: RedisConnector::new(conf)?,
+            key_prefix: conf.key_prefix.clone(),
+        })
+    }
+
+    fn key(&self, key: &str) -> String {
+        prefixed(&self.key_prefix, key)
+    }
+}
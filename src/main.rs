@@ -3,157 +3,1143 @@
 #![feature(never_type)]
 #![feature(result_option_inspect)]
 
+mod access_grantor;
+mod access_request_store;
+mod api;
+mod api_token_store;
+mod api_tokens;
+mod approval_gate;
+mod approval_store;
+mod aws_client;
+mod backfill_store;
+mod bundle_store;
 mod config;
 mod constants;
 mod controller;
+mod cost_reporter;
+mod crd_watcher;
+mod data_trigger_watcher;
+mod deployment_archiver;
 pub mod deployment_state_store;
+mod descriptor_encryption;
 mod descriptor_event_watcher;
 mod descriptor_store;
+mod event_dedup_store;
+mod event_record_store;
+mod event_schema;
 mod fluid;
+mod freshness_monitor;
+mod freshness_store;
+mod git_sync;
+mod http_client;
+mod iam_role_store;
+mod metrics;
+mod partition_repair_store;
+mod payload_limits;
+mod policy;
 mod provisioner;
+mod quality_store;
+mod queue_stats;
+mod rate_limit;
+mod reconcile_lock_store;
+mod redis_connection;
+mod redis_namespace;
+mod replay_store;
+mod request_id;
+mod s3_source;
+mod s3_source_store;
+mod server_tls;
+mod shard_member_store;
+mod sharding;
+mod smoke_test_store;
+mod snapshot;
+mod snapshot_backup;
+mod sql_validation;
+mod state_events;
+mod store_migration;
+mod stuck_deployment_detector;
+mod templating;
+mod upstream_cache_store;
 
+use crate::config::{
+    AccessRequestsConf, ApiTokensConf, ApprovalsConf, BasinConfig, ControllersConf, DeletionConf,
+    EventSource, IdGenerator, LimitsConf, LogFormat, PreviewConf,
+};
+use access_grantor::AccessGrantor;
+use access_request_store::RedisAccessRequestStore;
+use api_token_store::RedisApiTokenStore;
+use api_tokens::TokenGrant;
+use approval_gate::{Admission, ApprovalGate};
+use approval_store::RedisApprovalStore;
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path, State},
+    http::{header::CONTENT_TYPE, HeaderMap, StatusCode},
+    middleware,
     response::IntoResponse,
-    routing::{get, post},
-    Json, Router,
+    routing::{delete, get, post},
+    Extension, Json, Router,
 };
+use backfill_store::{BackfillRecord, BackfillStore, RedisBackfillStore};
+use bundle_store::RedisBundleStore;
+use cost_reporter::CostReporter;
+use crd_watcher::CrdWatcher;
+use data_trigger_watcher::DataTriggerWatcher;
+use deployment_archiver::DeploymentArchiver;
 use deployment_state_store::{
-    DeploymentInfo, DeploymentState, DeploymentStateStore, RedisDeploymentStateStore,
+    DeploymentHistory, DeploymentInfo, DeploymentState, DeploymentStateStore,
+    RedisDeploymentStateStore,
 };
 use descriptor_event_watcher::DescriptorEventWatcher;
-use descriptor_store::{DescriptorStore, RedisDescriptorStore};
-use serde::Serialize;
+use descriptor_store::{DescriptorStore, NameTaken, RedisDescriptorStore};
+use event_record_store::RedisEventRecordStore;
+use freshness_monitor::FreshnessMonitor;
+use freshness_store::{FreshnessStatus, FreshnessStore, RedisFreshnessStore};
+use git_sync::GitSync;
+use iam_role_store::{DatabaseRole, IamRoleStore, RedisIamRoleStore};
+use partition_repair_store::{PartitionRepair, PartitionRepairStore, RedisPartitionRepairStore};
+use payload_limits::PayloadLimited;
+use policy::{PolicyChecker, PolicyDenied};
+use provisioner::{athena::AthenaProvisioner, glue::GlueProvisioner, s3::S3Provisioner};
+use quality_store::{QualityResult, QualityStore, RedisQualityStore};
+use rate_limit::RateLimiter;
+use replay_store::RedisReplayStore;
+use request_id::RequestId;
+use s3_source::S3Source;
+use s3_source_store::RedisS3SourceStore;
+use serde::{de::DeserializeOwned, Serialize};
+use sharding::ShardMembership;
+use smoke_test_store::{RedisSmokeTestStore, SmokeTestResult, SmokeTestStore};
+use snapshot_backup::SnapshotBackup;
+use state_events::{StateEventPublisher, StateEventType};
 use std::{net::SocketAddr, sync::Arc};
+use stuck_deployment_detector::StuckDeploymentDetector;
 use tokio::task;
 
 use controller::{
-    base::BaseController, database::DatabaseController, flow::FlowController,
-    table::TableController,
+    database::DatabaseController, flow::FlowController, landing_zone::LandingZoneController,
+    reconciler::Reconciler, table::TableController,
 };
-use fluid::descriptor::{
-    database::DatabaseDescriptor, flow::FlowDescriptor, table::TableDescriptor,
-    IdentifiableDescriptor,
+use fluid::{
+    descriptor::{
+        database::DatabaseDescriptor, flow::FlowDescriptor, landing_zone::LandingZoneDescriptor,
+        table::TableDescriptor, DescriptorKind, IdentifiableDescriptor,
+    },
+    encoding::{self, PayloadFormat},
 };
 
 struct AppContext {
     descriptor_store: RedisDescriptorStore,
     deployment_state_store: RedisDeploymentStateStore,
+    event_record_store: RedisEventRecordStore,
+    deployment_archiver: Option<Arc<DeploymentArchiver>>,
+    git_sync: Option<Arc<GitSync>>,
+    s3_source_store: RedisS3SourceStore,
+    replay_store: RedisReplayStore,
+    backfill_store: RedisBackfillStore,
+    bundle_store: RedisBundleStore,
+    freshness_store: RedisFreshnessStore,
+    smoke_test_store: RedisSmokeTestStore,
+    quality_store: RedisQualityStore,
+    partition_repair_store: RedisPartitionRepairStore,
+    iam_role_store: RedisIamRoleStore,
+    access_request_store: RedisAccessRequestStore,
+    access_requests: Option<AccessRequestsConf>,
+    approval_store: RedisApprovalStore,
+    approval_gate: ApprovalGate,
+    approvals: Option<ApprovalsConf>,
+    api_token_store: RedisApiTokenStore,
+    api_tokens: Option<ApiTokensConf>,
+    limits: LimitsConf,
+    id_generator: IdGenerator,
+    policy_checker: Option<PolicyChecker>,
+    deletion: DeletionConf,
+    controllers: ControllersConf,
+    effective_settings: serde_json::Value,
+    state_event_publisher: StateEventPublisher,
+    cost_reporter: CostReporter,
+    glue_provisioner: GlueProvisioner,
+    s3_provisioner: S3Provisioner,
+    preview: Option<PreviewConf>,
+    preview_provisioner: Option<AthenaProvisioner>,
+    flow_controller: FlowController,
 }
 
 #[tokio::main]
 async fn main() {
-    let subscriber = tracing_subscriber::FmtSubscriber::builder().finish();
-    tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
-
     let conf = config::init(constants::DEFAULT_CONF)
         .await
         .expect("failed to load configuration");
 
-    let app_context = AppContext {
-        descriptor_store: RedisDescriptorStore::new(&conf.redis_url)
+    let subscriber_builder = tracing_subscriber::FmtSubscriber::builder();
+    match conf.log_format {
+        LogFormat::Text => tracing::subscriber::set_global_default(subscriber_builder.finish()),
+        LogFormat::Json => {
+            tracing::subscriber::set_global_default(subscriber_builder.json().finish())
+        }
+    }
+    .expect("setting default subscriber failed");
+
+    // NOTE: installed ahead of the admin commands too, they read and write descriptors as well
+    if let Some(encryption) = &conf.descriptor_encryption {
+        descriptor_encryption::install(encryption, &conf.aws_creds);
+    }
+    if let Some(faults) = &conf.fault_injection {
+        provisioner::fault_injection::install(faults.clone());
+    }
+    http_client::install(&conf.http).expect("failed to build the http client");
+
+    // NOTE: one-shot admin commands run instead of the server
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("migrate-key-prefix") {
+        let from_prefix = args.get(2).map(String::as_str).unwrap_or("");
+        let migrated = redis_namespace::migrate_prefix(&conf.redis, from_prefix)
             .await
-            .expect("could not construct redis descriptor store"),
-        deployment_state_store: RedisDeploymentStateStore::new(&conf.redis_url)
+            .expect("failed to migrate redis keys");
+        tracing::info!(migrated, from_prefix, "finished migrating redis keys");
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("migrate-store") {
+        let overwrite = args.iter().any(|a| a == "--overwrite");
+        let summary = store_migration::migrate_store(&conf, overwrite)
             .await
-            .expect("could not construct redis deployment state store"),
-    };
+            .expect("failed to migrate the store");
+        tracing::info!(?summary, "finished migrating and verifying the store");
+        return;
+    }
 
-    let db_ctl = DatabaseController::new(&conf)
-        .await
-        .expect("could not construct database controller");
-    let tbl_ctl = TableController::new(&conf)
-        .await
-        .expect("could not construct table controller");
-    let flow_ctl = FlowController::new(&conf)
-        .await
-        .expect("could not construct flow controller");
+    // NOTE: restored before anything starts reconciling so the controllers see the seeded store
+    if let Some(i) = args.iter().position(|a| a == "--restore-from") {
+        let uri = args
+            .get(i + 1)
+            .expect("--restore-from needs an s3://bucket/prefix");
+        snapshot_backup::restore_from(&conf, uri)
+            .await
+            .expect("failed to restore from snapshot");
+    }
 
-    task::spawn(async move {
-        db_ctl.run().await;
-    });
-    task::spawn(async move {
-        tbl_ctl.run().await;
-    });
-    task::spawn(async move {
-        flow_ctl.run().await;
+    let app_context = Arc::new(AppContext {
+        descriptor_store: RedisDescriptorStore::new(&conf.redis)
+            .await
+            .expect("could not construct redis descriptor store"),
+        deployment_state_store: RedisDeploymentStateStore::new(&conf.redis)
+            .await
+            .expect("could not construct redis deployment state store"),
+        event_record_store: RedisEventRecordStore::new(&conf.redis)
+            .await
+            .expect("could not construct redis event record store"),
+        deployment_archiver: DeploymentArchiver::new(&conf)
+            .await
+            .expect("could not construct deployment archiver")
+            .map(Arc::new),
+        git_sync: GitSync::new(&conf)
+            .await
+            .expect("could not construct git sync")
+            .map(Arc::new),
+        s3_source_store: RedisS3SourceStore::new(&conf.redis)
+            .await
+            .expect("could not construct redis s3 source store"),
+        replay_store: RedisReplayStore::new(&conf.redis)
+            .await
+            .expect("could not construct redis replay store"),
+        backfill_store: RedisBackfillStore::new(&conf.redis)
+            .await
+            .expect("could not construct redis backfill store"),
+        bundle_store: RedisBundleStore::new(&conf.redis)
+            .await
+            .expect("could not construct redis bundle store"),
+        freshness_store: RedisFreshnessStore::new(&conf.redis)
+            .await
+            .expect("could not construct redis freshness store"),
+        smoke_test_store: RedisSmokeTestStore::new(&conf.redis)
+            .await
+            .expect("could not construct redis smoke test store"),
+        quality_store: RedisQualityStore::new(&conf.redis)
+            .await
+            .expect("could not construct redis quality store"),
+        partition_repair_store: RedisPartitionRepairStore::new(&conf.redis)
+            .await
+            .expect("could not construct redis partition repair store"),
+        iam_role_store: RedisIamRoleStore::new(&conf.redis)
+            .await
+            .expect("could not construct redis iam role store"),
+        access_request_store: RedisAccessRequestStore::new(&conf.redis)
+            .await
+            .expect("could not construct redis access request store"),
+        access_requests: conf.access_requests.clone(),
+        approval_store: RedisApprovalStore::new(&conf.redis)
+            .await
+            .expect("could not construct redis approval store"),
+        approval_gate: ApprovalGate::new(&conf)
+            .await
+            .expect("could not construct approval gate"),
+        approvals: conf.approvals.clone(),
+        api_token_store: RedisApiTokenStore::new(&conf.redis)
+            .await
+            .expect("could not construct redis api token store"),
+        api_tokens: conf.api_tokens.clone(),
+        limits: conf.limits.clone(),
+        id_generator: conf.id_generator,
+        policy_checker: PolicyChecker::new(&conf).expect("could not construct policy checker"),
+        deletion: conf.deletion.clone(),
+        controllers: conf.controllers.clone(),
+        effective_settings: conf.effective_settings.clone(),
+        state_event_publisher: StateEventPublisher::new(&conf),
+        cost_reporter: CostReporter::new(&conf),
+        glue_provisioner: GlueProvisioner::new(&conf.aws_creds),
+        s3_provisioner: S3Provisioner::new(&conf.aws_creds),
+        preview: conf.preview.clone(),
+        preview_provisioner: conf
+            .preview
+            .as_ref()
+            .map(|c| AthenaProvisioner::for_preview(&conf.aws_creds, c)),
+        flow_controller: FlowController::new(&conf)
+            .await
+            .expect("could not construct flow controller"),
     });
 
-    let event_watcher = DescriptorEventWatcher::new(&conf)
-        .await
-        .expect("could not construct event watcher");
-    task::spawn(async move {
-        event_watcher.ingest_loop().await;
-    });
+    // NOTE: a read only basin is being cut over to a migrated store, nothing may write to it
+    if conf.read_only {
+        tracing::warn!(
+            "read only, controllers and background tasks are off and writes are rejected"
+        );
+    } else {
+        spawn_background_tasks(
+            &conf,
+            app_context.git_sync.clone(),
+            app_context.deployment_archiver.clone(),
+        )
+        .await;
+    }
 
+    let rate_limiter = Arc::new(RateLimiter::new(conf.rate_limits.as_ref()));
     let app = Router::new()
         .route("/healthcheck", get(|| async { "1" }))
+        .route("/metrics", get(|| async { metrics::render() }))
+        .route("/ui", get(api::dashboard::index))
+        .route("/ui/app.js", get(api::dashboard::app_js))
+        .route("/ui/api/overview", get(api::dashboard::get_overview))
+        .route("/ui/api/descriptors/:kind", get(api::dashboard::list_kind))
+        .route(
+            "/api/v1/database",
+            get(|ctx, query| {
+                api::list::list_descriptors::<DatabaseDescriptor>(
+                    DescriptorKind::Database,
+                    ctx,
+                    query,
+                )
+            })
+            .delete(|ctx, request_id, grant, query| {
+                api::deletion::delete_descriptor_by_name(
+                    DescriptorKind::Database,
+                    ctx,
+                    request_id,
+                    grant,
+                    query,
+                )
+            }),
+        )
+        .route(
+            "/api/v1/flow",
+            get(|ctx, query| {
+                api::list::list_descriptors::<FlowDescriptor>(DescriptorKind::Flow, ctx, query)
+            })
+            .delete(|ctx, request_id, grant, query| {
+                api::deletion::delete_descriptor_by_name(
+                    DescriptorKind::Flow,
+                    ctx,
+                    request_id,
+                    grant,
+                    query,
+                )
+            }),
+        )
+        .route(
+            "/api/v1/table",
+            get(|ctx, query| {
+                api::list::list_descriptors::<TableDescriptor>(DescriptorKind::Table, ctx, query)
+            })
+            .delete(|ctx, request_id, grant, query| {
+                api::deletion::delete_descriptor_by_name(
+                    DescriptorKind::Table,
+                    ctx,
+                    request_id,
+                    grant,
+                    query,
+                )
+            }),
+        )
+        .route(
+            "/api/v1/landing_zone",
+            get(|ctx, query| {
+                api::list::list_descriptors::<LandingZoneDescriptor>(
+                    DescriptorKind::LandingZone,
+                    ctx,
+                    query,
+                )
+            })
+            .delete(|ctx, request_id, grant, query| {
+                api::deletion::delete_descriptor_by_name(
+                    DescriptorKind::LandingZone,
+                    ctx,
+                    request_id,
+                    grant,
+                    query,
+                )
+            }),
+        )
         .route(
             "/api/v1/database/reconcile",
-            post(handle_resource_submit::<DatabaseDescriptor>),
+            post(handle_resource_submit::<DatabaseDescriptor>).layer(
+                middleware::from_fn_with_state(rate_limiter.clone(), rate_limit::limit_submits),
+            ),
         )
         .route(
             "/api/v1/flow/reconcile",
-            post(handle_resource_submit::<FlowDescriptor>),
+            post(handle_resource_submit::<FlowDescriptor>).layer(middleware::from_fn_with_state(
+                rate_limiter.clone(),
+                rate_limit::limit_submits,
+            )),
         )
         .route(
             "/api/v1/table/reconcile",
-            post(handle_resource_submit::<TableDescriptor>),
+            post(handle_resource_submit::<TableDescriptor>).layer(middleware::from_fn_with_state(
+                rate_limiter.clone(),
+                rate_limit::limit_submits,
+            )),
+        )
+        .route(
+            "/api/v1/landing_zone/reconcile",
+            post(handle_resource_submit::<LandingZoneDescriptor>).layer(
+                middleware::from_fn_with_state(rate_limiter.clone(), rate_limit::limit_submits),
+            ),
+        )
+        .route(
+            "/api/v1/bundle/reconcile",
+            post(api::bundle::submit_bundle).layer(middleware::from_fn_with_state(
+                rate_limiter,
+                rate_limit::limit_submits,
+            )),
+        )
+        .route(
+            "/api/v1/bundle/:id/status",
+            get(api::bundle::get_bundle_status),
+        )
+        .route(
+            "/api/v1/database/:id",
+            get(|ctx, id, query| {
+                api::history::get_descriptor(DescriptorKind::Database, ctx, id, query)
+            })
+            .delete(|ctx, request_id, grant, id| {
+                api::deletion::delete_descriptor(
+                    DescriptorKind::Database,
+                    ctx,
+                    request_id,
+                    grant,
+                    id,
+                )
+            }),
+        )
+        .route(
+            "/api/v1/flow/:id",
+            get(|ctx, id, query| {
+                api::history::get_descriptor(DescriptorKind::Flow, ctx, id, query)
+            })
+            .delete(|ctx, request_id, grant, id| {
+                api::deletion::delete_descriptor(DescriptorKind::Flow, ctx, request_id, grant, id)
+            }),
+        )
+        .route(
+            "/api/v1/table/:id",
+            get(|ctx, id, query| {
+                api::history::get_descriptor(DescriptorKind::Table, ctx, id, query)
+            })
+            .delete(|ctx, request_id, grant, id| {
+                api::deletion::delete_descriptor(DescriptorKind::Table, ctx, request_id, grant, id)
+            }),
+        )
+        .route(
+            "/api/v1/landing_zone/:id",
+            get(|ctx, id, query| {
+                api::history::get_descriptor(DescriptorKind::LandingZone, ctx, id, query)
+            })
+            .delete(|ctx, request_id, grant, id| {
+                api::deletion::delete_descriptor(
+                    DescriptorKind::LandingZone,
+                    ctx,
+                    request_id,
+                    grant,
+                    id,
+                )
+            }),
+        )
+        .route(
+            "/api/v1/database/:id/restore",
+            post(|ctx, request_id, id| {
+                api::deletion::restore_descriptor(DescriptorKind::Database, ctx, request_id, id)
+            }),
+        )
+        .route(
+            "/api/v1/flow/:id/restore",
+            post(|ctx, request_id, id| {
+                api::deletion::restore_descriptor(DescriptorKind::Flow, ctx, request_id, id)
+            }),
+        )
+        .route(
+            "/api/v1/table/:id/restore",
+            post(|ctx, request_id, id| {
+                api::deletion::restore_descriptor(DescriptorKind::Table, ctx, request_id, id)
+            }),
+        )
+        .route(
+            "/api/v1/landing_zone/:id/restore",
+            post(|ctx, request_id, id| {
+                api::deletion::restore_descriptor(DescriptorKind::LandingZone, ctx, request_id, id)
+            }),
+        )
+        .route(
+            "/api/v1/database/:id/diff",
+            post(|ctx, id, query, body| {
+                api::diff::diff_descriptor::<DatabaseDescriptor>(
+                    DescriptorKind::Database,
+                    ctx,
+                    id,
+                    query,
+                    body,
+                )
+            }),
+        )
+        .route("/api/v1/flow/spec", post(api::spec::preview_flow_spec))
+        .route(
+            "/api/v1/flow/:id/diff",
+            post(|ctx, id, query, body| {
+                api::diff::diff_descriptor::<FlowDescriptor>(
+                    DescriptorKind::Flow,
+                    ctx,
+                    id,
+                    query,
+                    body,
+                )
+            }),
+        )
+        .route(
+            "/api/v1/table/:id/metadata",
+            get(api::metadata::get_table_metadata),
+        )
+        .route(
+            "/api/v1/table/:id/quality/suite",
+            get(api::quality::get_quality_suite),
+        )
+        .route(
+            "/api/v1/table/:id/quality/results",
+            post(api::quality::report_quality_result),
+        )
+        .route(
+            "/api/v1/table/:id/partitions",
+            post(api::partitions::register_partition),
+        )
+        .route(
+            "/api/v1/table/:id/partitions/batch",
+            post(api::partitions::register_partitions),
+        )
+        .route(
+            "/api/v1/table/:id/preview",
+            get(api::preview::preview_table),
+        )
+        .route(
+            "/api/v1/table/:id/repair",
+            post(api::partitions::repair_partitions),
+        )
+        .route(
+            "/api/v1/table/:id/diff",
+            post(|ctx, id, query, body| {
+                api::diff::diff_descriptor::<TableDescriptor>(
+                    DescriptorKind::Table,
+                    ctx,
+                    id,
+                    query,
+                    body,
+                )
+            }),
+        )
+        .route(
+            "/api/v1/landing_zone/:id/diff",
+            post(|ctx, id, query, body| {
+                api::diff::diff_descriptor::<LandingZoneDescriptor>(
+                    DescriptorKind::LandingZone,
+                    ctx,
+                    id,
+                    query,
+                    body,
+                )
+            }),
+        )
+        .route(
+            "/api/v1/database/:id/cost",
+            get(|ctx, id| api::cost::get_cost(DescriptorKind::Database, ctx, id)),
+        )
+        .route(
+            "/api/v1/flow/:id/cost",
+            get(|ctx, id| api::cost::get_cost(DescriptorKind::Flow, ctx, id)),
+        )
+        .route(
+            "/api/v1/table/:id/cost",
+            get(|ctx, id| api::cost::get_cost(DescriptorKind::Table, ctx, id)),
+        )
+        .route(
+            "/api/v1/flow/:id/backfill",
+            post(api::backfill::start_backfill),
+        )
+        .route(
+            "/api/v1/access-requests",
+            get(api::access_requests::list_access_requests)
+                .post(api::access_requests::create_access_request),
+        )
+        .route(
+            "/api/v1/access-requests/:request_id",
+            get(api::access_requests::get_access_request),
+        )
+        .route(
+            "/api/v1/access-requests/:request_id/approve",
+            post(api::access_requests::approve_access_request),
+        )
+        .route(
+            "/api/v1/access-requests/:request_id/deny",
+            post(api::access_requests::deny_access_request),
+        )
+        .route(
+            "/api/v1/access-requests/:request_id/revoke",
+            post(api::access_requests::revoke_access_request),
+        )
+        .route("/api/v1/approvals", get(api::approvals::list_approvals))
+        .route(
+            "/api/v1/approvals/:approval_id",
+            get(api::approvals::get_approval),
+        )
+        .route(
+            "/api/v1/approvals/:approval_id/approve",
+            post(api::approvals::approve),
+        )
+        .route(
+            "/api/v1/approvals/:approval_id/reject",
+            post(api::approvals::reject),
         )
+        .route("/api/v1/status", get(api::list::list_deployment_states))
         .route("/api/v1/status/:id", get(get_deployment_state))
-        .with_state(Arc::new(app_context));
+        .route(
+            "/api/v1/status/:id/archive",
+            get(api::archive::get_archived_states),
+        )
+        .route("/api/v1/admin/replay", post(api::admin::start_replay))
+        .route("/api/v1/admin/git-sync", post(api::admin::trigger_git_sync))
+        .route("/api/v1/admin/s3-source", get(api::admin::get_s3_source))
+        .route("/api/v1/admin/config", get(api::admin::get_config))
+        .route("/api/v1/admin/queues", get(api::admin::get_queues))
+        .route("/api/v1/admin/export", get(api::snapshot::export_snapshot))
+        .route(
+            "/api/v1/admin/store-digest",
+            get(api::snapshot::get_store_digest),
+        )
+        // NOTE: snapshots hold every descriptor, they'd never fit the per descriptor body limit
+        .route(
+            "/api/v1/admin/import",
+            post(api::snapshot::import_snapshot).layer(DefaultBodyLimit::disable()),
+        )
+        .route(
+            "/api/v1/admin/quarantine/:id/release",
+            post(api::admin::release_quarantine),
+        )
+        .route(
+            "/api/v1/admin/replay/:replay_id",
+            get(api::admin::get_replay),
+        )
+        .route(
+            "/api/v1/admin/tokens",
+            get(api::tokens::list_tokens).post(api::tokens::mint_token),
+        )
+        .route(
+            "/api/v1/admin/tokens/:token_id",
+            delete(api::tokens::revoke_token),
+        )
+        .route("/api/v1/events/recent", get(api::events::get_recent_events))
+        .route("/api/v1/events/:event_id", get(api::events::get_event))
+        .layer(DefaultBodyLimit::max(conf.limits.max_body_bytes))
+        .layer(middleware::from_fn_with_state(
+            app_context.clone(),
+            api_tokens::authenticate,
+        ))
+        .layer(middleware::from_fn(request_id::propagate_request_id))
+        .with_state(app_context);
+    let app = if conf.read_only {
+        app.layer(middleware::from_fn(store_migration::reject_writes))
+    } else {
+        app
+    };
+
+    let addr: SocketAddr = conf
+        .server
+        .bind_address
+        .parse()
+        .expect("invalid server bind address");
+    match &conf.server.tls {
+        Some(tls_conf) => {
+            let tls_config =
+                server_tls::rustls_config(tls_conf).expect("could not load server tls config");
+            axum_server::bind_rustls(addr, tls_config)
+                .serve(app.into_make_service())
+                .await
+                .unwrap();
+        }
+        None => {
+            axum::Server::bind(&addr)
+                .serve(app.into_make_service())
+                .await
+                .unwrap();
+        }
+    }
+}
+
+async fn spawn_background_tasks(
+    conf: &BasinConfig,
+    git_sync: Option<Arc<GitSync>>,
+    deployment_archiver: Option<Arc<DeploymentArchiver>>,
+) {
+    let shards = ShardMembership::new(conf)
+        .await
+        .expect("could not join shard membership")
+        .map(Arc::new);
+    if let Some(shards) = shards.clone() {
+        task::spawn(async move {
+            shards.membership_loop().await;
+        });
+    }
+
+    // NOTE: disabled controllers aren't even constructed, their provisioners may not be configured
+    if conf.controllers.database.enabled {
+        let db_ctl = Reconciler::new(
+            conf,
+            DescriptorKind::Database,
+            DatabaseController::new(conf)
+                .await
+                .expect("could not construct database controller"),
+            shards.clone(),
+        )
+        .await
+        .expect("could not construct database reconciler");
+        task::spawn(async move {
+            db_ctl.run().await;
+        });
+    }
+    if conf.controllers.table.enabled {
+        let tbl_ctl = Reconciler::new(
+            conf,
+            DescriptorKind::Table,
+            TableController::new(conf)
+                .await
+                .expect("could not construct table controller"),
+            shards.clone(),
+        )
+        .await
+        .expect("could not construct table reconciler");
+        task::spawn(async move {
+            tbl_ctl.run().await;
+        });
+    }
+    if conf.controllers.flow.enabled {
+        let flow_ctl = Reconciler::new(
+            conf,
+            DescriptorKind::Flow,
+            FlowController::new(conf)
+                .await
+                .expect("could not construct flow controller"),
+            shards.clone(),
+        )
+        .await
+        .expect("could not construct flow reconciler");
+        task::spawn(async move {
+            flow_ctl.run().await;
+        });
+    }
+    if conf.controllers.landing_zone.enabled {
+        let landing_zone_ctl = Reconciler::new(
+            conf,
+            DescriptorKind::LandingZone,
+            LandingZoneController::new(conf)
+                .await
+                .expect("could not construct landing zone controller"),
+            shards.clone(),
+        )
+        .await
+        .expect("could not construct landing zone reconciler");
+        task::spawn(async move {
+            landing_zone_ctl.run().await;
+        });
+    }
+    for kind in DescriptorKind::ALL {
+        if !conf.controllers.is_enabled(kind) {
+            tracing::info!(
+                %kind,
+                "controller disabled, descriptors will be stored but not reconciled"
+            );
+        }
+    }
+
+    match conf.event_source {
+        EventSource::Sqs => {
+            let event_watcher = DescriptorEventWatcher::new(conf)
+                .await
+                .expect("could not construct event watcher");
+            task::spawn(async move {
+                event_watcher.ingest_loop().await;
+            });
+        }
+        EventSource::Kubernetes => {
+            let crd_watcher = CrdWatcher::new(conf)
+                .await
+                .expect("could not construct custom resource watcher");
+            task::spawn(async move {
+                crd_watcher.ingest_loop().await;
+            });
+        }
+    }
+    // NOTE: runs alongside the event source, the repository is just another way in
+    if let Some(git_sync) = git_sync {
+        task::spawn(async move {
+            git_sync.sync_loop().await;
+        });
+    }
+    if let Some(s3_source) = S3Source::new(conf)
+        .await
+        .expect("could not construct s3 source")
+    {
+        task::spawn(async move {
+            s3_source.poll_loop().await;
+        });
+    }
+
+    if let Some(archiver) = deployment_archiver {
+        task::spawn(async move {
+            archiver.archive_loop().await;
+        });
+    }
+
+    if let Some(backup) = SnapshotBackup::new(conf)
+        .await
+        .expect("could not construct snapshot backup")
+    {
+        task::spawn(async move {
+            backup.backup_loop().await;
+        });
+    }
+
+    if let Some(monitor) = FreshnessMonitor::new(conf)
+        .await
+        .expect("could not construct freshness monitor")
+    {
+        task::spawn(async move {
+            monitor.check_loop().await;
+        });
+    }
+
+    if let Some(detector) = StuckDeploymentDetector::new(conf)
+        .await
+        .expect("could not construct stuck deployment detector")
+    {
+        task::spawn(async move {
+            detector.detect_loop().await;
+        });
+    }
+
+    if let Some(watcher) = DataTriggerWatcher::new(conf)
+        .await
+        .expect("could not construct data trigger watcher")
+    {
+        task::spawn(async move {
+            watcher.watch_loop().await;
+        });
+    }
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service())
+    if let Some(grantor) = AccessGrantor::new(conf)
         .await
-        .unwrap();
+        .expect("could not construct access grantor")
+    {
+        task::spawn(async move {
+            grantor.grant_loop().await;
+        });
+    }
+}
+
+#[derive(Serialize)]
+struct DeploymentStatus {
+    #[serde(flatten)]
+    info: DeploymentInfo,
+    // Only ever populated for flows
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    backfills: Vec<BackfillRecord>,
+    // Only ever populated for tables with a freshness sla
+    #[serde(skip_serializing_if = "Option::is_none")]
+    freshness: Option<FreshnessStatus>,
+    // Only ever populated for glue tables when smoke tests are configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    smoke_test: Option<SmokeTestResult>,
+    // Only ever populated for tables with quality rules, once their validation flow has reported
+    #[serde(skip_serializing_if = "Option::is_none")]
+    quality: Option<QualityResult>,
+    // Only ever populated for partitioned glue tables, the latest repair of their partitions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    partition_repair: Option<PartitionRepair>,
+    // Only ever populated for glue engine databases when iam is configured, the roles consumers
+    // assume to read or write the database and what each of them is granted
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    iam_roles: Vec<DatabaseRole>,
 }
 
 async fn get_deployment_state(
     State(ctx): State<Arc<AppContext>>,
     Path(descriptor_id): Path<String>,
 ) -> axum::response::Response {
-    match &ctx.deployment_state_store.get_state(&descriptor_id).await {
-        Ok(Some(state)) => Json(state).into_response(),
-        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+    let info = match ctx.deployment_state_store.get_state(&descriptor_id).await {
+        Ok(Some(t)) => t,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    };
+
+    let backfills = match ctx.backfill_store.list_backfills(&descriptor_id).await {
+        Ok(t) => t,
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    };
+
+    let freshness = match ctx.freshness_store.get_status(&descriptor_id).await {
+        Ok(t) => t,
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    };
+
+    let smoke_test = match ctx.smoke_test_store.get_result(&descriptor_id).await {
+        Ok(t) => t,
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    };
+
+    let quality = match ctx.quality_store.get_result(&descriptor_id).await {
+        Ok(t) => t,
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    };
+
+    let partition_repair = match ctx.partition_repair_store.get_repair(&descriptor_id).await {
+        Ok(t) => t,
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response()
+        }
+    };
+
+    match ctx.iam_role_store.get_roles(&descriptor_id).await {
+        Ok(iam_roles) => Json(DeploymentStatus {
+            info,
+            backfills,
+            freshness,
+            smoke_test,
+            quality,
+            partition_repair,
+            iam_roles,
+        })
+        .into_response(),
         Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("error {:?}", e)).into_response(),
     }
 }
 
-async fn handle_resource_submit<DescriptorKind: IdentifiableDescriptor + Serialize + Sync>(
+// Takes json, yaml, avro or protobuf descriptors, going by the request's content type. Responds
+// with the descriptor's id, which basin assigns when it's left out.
+async fn handle_resource_submit<
+    Descriptor: IdentifiableDescriptor + PayloadLimited + Serialize + DeserializeOwned + Sync,
+>(
     State(ctx): State<Arc<AppContext>>,
-    Json(payload): Json<DescriptorKind>,
-) -> impl IntoResponse {
+    Extension(request_id): Extension<RequestId>,
+    grant: Option<Extension<TokenGrant>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> axum::response::Response {
     let depstate_store = &ctx.deployment_state_store;
     let descriptor_store = &ctx.descriptor_store;
 
-    if let Err(e) = descriptor_store
-        .store_descriptor::<DescriptorKind>(&payload)
-        .await
-    {
+    let format = match PayloadFormat::from_content_type(
+        headers.get(CONTENT_TYPE).and_then(|v| v.to_str().ok()),
+    ) {
+        Ok(t) => t,
+        Err(e) => return (StatusCode::UNSUPPORTED_MEDIA_TYPE, format!("{}", e)).into_response(),
+    };
+    let mut payload: Descriptor = match encoding::decode(format, &body) {
+        Ok(t) => t,
+        Err(e) => {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("invalid descriptor: {:#}", e),
+            )
+                .into_response()
+        }
+    };
+
+    if let Err(e) = payload.check_limits(&ctx.limits) {
         return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("failed to store descriptor: {:?}", e),
-        );
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("descriptor exceeds limits: {}", e),
+        )
+            .into_response();
     }
 
-    if let Err(e) = depstate_store
-        .set_state(
-            &payload.id(),
-            &DeploymentInfo {
-                state: DeploymentState::Pending,
-                description: None,
-            },
+    // Resubmitting without an id updates whatever's stored under the name. The name is only
+    // claimed once the submission has been let through, see below.
+    let unclaimed = payload.id().is_empty();
+    if unclaimed {
+        let id = match descriptor_store
+            .get_id_by_name(
+                payload.kind(),
+                payload.namespace().as_deref(),
+                &payload.name(),
+            )
+            .await
+        {
+            Ok(t) => t.unwrap_or_else(|| ctx.id_generator.generate()),
+            Err(e) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("failed to assign an id: {:?}", e),
+                )
+                    .into_response()
+            }
+        };
+        payload.set_id(id);
+    }
+
+    if let Err(response) =
+        api_tokens::authorize_write(&ctx, grant.as_ref().map(|g| &g.0), &payload).await
+    {
+        return response;
+    }
+
+    if let Some(policy_checker) = &ctx.policy_checker
+        && let Err(e) = policy_checker.check(&payload).await
+    {
+        return match e.downcast_ref::<PolicyDenied>() {
+            Some(denied) => (StatusCode::FORBIDDEN, denied.to_string()),
+            None => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                format!("could not check descriptor against policy: {:#}", e),
+            ),
+        }
+        .into_response();
+    }
+
+    // Changes that would break consumers of what's stored wait for an approver
+    match ctx
+        .approval_gate
+        .admit(
+            &payload,
+            api::approvals::requester(grant.as_ref().map(|g| &g.0)),
+            Some(request_id.0.clone()),
         )
         .await
     {
+        Ok(Admission::Admitted) => {}
+        Ok(Admission::Held(approval)) => {
+            return (StatusCode::ACCEPTED, Json(approval)).into_response()
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("could not check for breaking changes: {:?}", e),
+            )
+                .into_response()
+        }
+    }
+
+    // NOTE: the checks above ran against this id, a concurrent submission which claimed the name
+    //       in the meantime means they have to be run again
+    if unclaimed {
+        match descriptor_store
+            .claim_name(
+                payload.kind(),
+                payload.namespace().as_deref(),
+                &payload.name(),
+                &payload.id(),
+            )
+            .await
+        {
+            Ok(id) if id == payload.id() => {}
+            Ok(_) => {
+                return (
+                    StatusCode::CONFLICT,
+                    format!(
+                        "{} was claimed by another submission, resubmit it",
+                        payload.name()
+                    ),
+                )
+                    .into_response()
+            }
+            Err(e) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("failed to assign an id: {:?}", e),
+                )
+                    .into_response()
+            }
+        }
+    }
+
+    if let Err(e) = descriptor_store
+        .store_descriptor::<Descriptor>(&payload)
+        .await
+    {
+        return match e.downcast_ref::<NameTaken>() {
+            Some(taken) => (StatusCode::CONFLICT, taken.to_string()),
+            None => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("failed to store descriptor: {:?}", e),
+            ),
+        }
+        .into_response();
+    }
+
+    let info = DeploymentInfo {
+        state: DeploymentState::Pending,
+        description: (!ctx.controllers.is_enabled(payload.kind()))
+            .then(|| constants::CONTROLLER_DISABLED.to_string()),
+        request_id: Some(request_id.0),
+        updated_at: None,
+        permanent_failure: false,
+        attempts: 0,
+        delete_after: None,
+        history: DeploymentHistory::default(),
+    };
+    if let Err(e) = depstate_store.set_state(&payload.id(), &info).await {
         return (
             StatusCode::INTERNAL_SERVER_ERROR,
             format!("failed to set deployment state: {:?}", e),
-        );
+        )
+            .into_response();
     }
 
-    (StatusCode::ACCEPTED, "".to_string())
+    ctx.state_event_publisher
+        .publish(
+            StateEventType::DescriptorStored,
+            payload.kind(),
+            &payload.id(),
+            payload.owner().as_ref(),
+            &info,
+        )
+        .await;
+
+    (
+        StatusCode::ACCEPTED,
+        Json(serde_json::json!({ "id": payload.id() })),
+    )
+        .into_response()
 }
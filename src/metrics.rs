@@ -0,0 +1,43 @@
+use std::{collections::BTreeMap, fmt::Write, sync::Mutex};
+
+// NOTE: deliberately tiny, we only need a handful of gauges/counters scraped by prometheus.
+//       keys are (metric name, rendered label set)
+static REGISTRY: Mutex<BTreeMap<(String, String), f64>> = Mutex::new(BTreeMap::new());
+
+fn render_labels(labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+
+    let rendered: Vec<String> = labels
+        .iter()
+        .map(|(k, v)| format!("{}=\"{}\"", k, v.replace('\\', "\\\\").replace('"', "\\\"")))
+        .collect();
+    format!("{{{}}}", rendered.join(","))
+}
+
+pub fn gauge_set(name: &str, labels: &[(&str, &str)], value: f64) {
+    let mut registry = REGISTRY.lock().unwrap();
+    registry.insert((name.to_string(), render_labels(labels)), value);
+}
+
+pub fn counter_add(name: &str, labels: &[(&str, &str)], value: f64) {
+    let mut registry = REGISTRY.lock().unwrap();
+    *registry
+        .entry((name.to_string(), render_labels(labels)))
+        .or_insert(0.0) += value;
+}
+
+pub fn counter_inc(name: &str, labels: &[(&str, &str)]) {
+    counter_add(name, labels, 1.0);
+}
+
+pub fn render() -> String {
+    let registry = REGISTRY.lock().unwrap();
+
+    let mut out = String::new();
+    for ((name, labels), value) in registry.iter() {
+        let _ = writeln!(out, "{}{} {}", name, labels, value);
+    }
+    out
+}
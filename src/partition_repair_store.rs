@@ -0,0 +1,91 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::RedisConf, redis_connection::RedisConnector, redis_namespace::prefixed};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RepairStatus {
+    // Listing the table's location
+    Listing,
+    // Registering the partitions found
+    Registering,
+    Succeeded,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PartitionRepair {
+    pub repair_id: String,
+    pub table_id: String,
+    pub status: RepairStatus,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub objects_scanned: usize,
+    // Objects whose path isn't laid out by the table's partition keys, or has invalid values
+    #[serde(default)]
+    pub objects_skipped: usize,
+    #[serde(default)]
+    pub partitions_found: usize,
+    #[serde(default)]
+    pub partitions_registered: usize,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl PartitionRepair {
+    pub fn is_finished(&self) -> bool {
+        matches!(self.status, RepairStatus::Succeeded | RepairStatus::Failed)
+    }
+}
+
+// Only the latest repair of each table is kept
+#[async_trait::async_trait]
+pub(crate) trait PartitionRepairStore {
+    async fn put_repair(&self, repair: &PartitionRepair) -> Result<()>;
+    async fn get_repair(&self, table_id: &str) -> Result<Option<PartitionRepair>>;
+}
+
+#[derive(Debug)]
+pub struct RedisPartitionRepairStore {
+    connector: RedisConnector,
+    key_prefix: String,
+}
+
+#[async_trait::async_trait]
+impl PartitionRepairStore for RedisPartitionRepairStore {
+    async fn put_repair(&self, repair: &PartitionRepair) -> Result<()> {
+        let mut conn = self.connector.get_connection().await?;
+        let _: () = conn
+            .set(
+                self.key(&format!("partition-repair/{}", repair.table_id)),
+                serde_json::to_string(repair)?,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn get_repair(&self, table_id: &str) -> Result<Option<PartitionRepair>> {
+        let mut conn = self.connector.get_connection().await?;
+        let repair: Option<String> = conn
+            .get(self.key(&format!("partition-repair/{}", table_id)))
+            .await?;
+        Ok(repair.map(|r| serde_json::from_str(&r)).transpose()?)
+    }
+}
+
+impl RedisPartitionRepairStore {
+    pub async fn new(conf: &RedisConf) -> Result<Self> {
+        Ok(Self {
+            connector: RedisConnector::new(conf)?,
+            key_prefix: conf.key_prefix.clone(),
+        })
+    }
+
+    fn key(&self, key: &str) -> String {
+        prefixed(&self.key_prefix, key)
+    }
+}
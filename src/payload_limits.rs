@@ -0,0 +1,142 @@
+use anyhow::{ensure, Result};
+
+use crate::{
+    config::LimitsConf,
+    fluid::descriptor::{
+        database::DatabaseDescriptor,
+        flow::{FlowDescriptor, FlowStepTransformation},
+        landing_zone::LandingZoneDescriptor,
+        table::TableDescriptor,
+    },
+};
+
+// Size caps applied to descriptors before they're stored, independent of controller validation
+pub trait PayloadLimited {
+    fn check_limits(&self, limits: &LimitsConf) -> Result<()>;
+}
+
+fn check_len(field: &str, value: &str, max: usize) -> Result<()> {
+    ensure!(
+        value.len() <= max,
+        format!(
+            "'{}' is {} bytes, exceeding the limit of {}",
+            field,
+            value.len(),
+            max
+        )
+    );
+    Ok(())
+}
+
+impl PayloadLimited for DatabaseDescriptor {
+    fn check_limits(&self, limits: &LimitsConf) -> Result<()> {
+        check_len("name", &self.name, limits.max_name_len)?;
+        check_len("summary", &self.summary, limits.max_summary_len)?;
+        Ok(())
+    }
+}
+
+impl PayloadLimited for TableDescriptor {
+    fn check_limits(&self, limits: &LimitsConf) -> Result<()> {
+        check_len("name", &self.name, limits.max_name_len)?;
+        check_len("summary", &self.summary, limits.max_summary_len)?;
+        ensure!(
+            self.columns.len() <= limits.max_columns,
+            format!(
+                "table has {} columns, exceeding the limit of {}",
+                self.columns.len(),
+                limits.max_columns
+            )
+        );
+        for col in self.columns.iter() {
+            check_len("columns.name", &col.name, limits.max_name_len)?;
+            check_len("columns.summary", &col.summary, limits.max_summary_len)?;
+        }
+        Ok(())
+    }
+}
+
+impl PayloadLimited for FlowDescriptor {
+    fn check_limits(&self, limits: &LimitsConf) -> Result<()> {
+        check_len("name", &self.name, limits.max_name_len)?;
+        check_len("summary", &self.summary, limits.max_summary_len)?;
+        ensure!(
+            self.steps.len() <= limits.max_steps,
+            format!(
+                "flow has {} steps, exceeding the limit of {}",
+                self.steps.len(),
+                limits.max_steps
+            )
+        );
+        for step in self.steps.iter() {
+            check_len("steps.name", &step.name, limits.max_name_len)?;
+            check_len("steps.summary", &step.summary, limits.max_summary_len)?;
+            match &step.transformation {
+                FlowStepTransformation::Sql(t) => {
+                    check_len("steps.transformation.sql", &t.sql, limits.max_sql_len)?
+                }
+                FlowStepTransformation::Dbt(t) => {
+                    check_len(
+                        "steps.transformation.repository",
+                        &t.repository,
+                        limits.max_summary_len,
+                    )?;
+                    check_len(
+                        "steps.transformation.git_ref",
+                        &t.git_ref,
+                        limits.max_name_len,
+                    )?;
+                    if let Some(project_dir) = &t.project_dir {
+                        check_len(
+                            "steps.transformation.project_dir",
+                            project_dir,
+                            limits.max_summary_len,
+                        )?;
+                    }
+                    if let Some(selector) = &t.selector {
+                        check_len(
+                            "steps.transformation.selector",
+                            selector,
+                            limits.max_summary_len,
+                        )?;
+                    }
+                    check_len(
+                        "steps.transformation.database",
+                        &t.database,
+                        limits.max_name_len,
+                    )?;
+                }
+                FlowStepTransformation::Spark(t) => {
+                    check_len(
+                        "steps.transformation.application",
+                        &t.application,
+                        limits.max_summary_len,
+                    )?;
+                    if let Some(main_class) = &t.main_class {
+                        check_len(
+                            "steps.transformation.main_class",
+                            main_class,
+                            limits.max_summary_len,
+                        )?;
+                    }
+                    for arg in t.args.iter() {
+                        check_len("steps.transformation.args", arg, limits.max_sql_len)?;
+                    }
+                    for (key, value) in t.conf.iter() {
+                        check_len("steps.transformation.conf", key, limits.max_name_len)?;
+                        check_len("steps.transformation.conf", value, limits.max_summary_len)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PayloadLimited for LandingZoneDescriptor {
+    fn check_limits(&self, limits: &LimitsConf) -> Result<()> {
+        check_len("name", &self.name, limits.max_name_len)?;
+        check_len("summary", &self.summary, limits.max_summary_len)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,169 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::{
+    config::{BasinConfig, OpaPolicyConf, PolicyConf, PolicyWebhookConf},
+    fluid::descriptor::IdentifiableDescriptor,
+    http_client, metrics,
+};
+
+// A descriptor vetoed by governance policy. It isn't stored, and resubmitting it unchanged won't
+// make a difference.
+#[derive(Error, Debug)]
+#[error("denied by policy: {}", .reasons.join("; "))]
+pub struct PolicyDenied {
+    pub reasons: Vec<String>,
+}
+
+// Checks descriptors against opa and/or an admission webhook before they're stored, a descriptor
+// has to be allowed by every configured check
+pub struct PolicyChecker {
+    conf: PolicyConf,
+    http_client: reqwest::Client,
+}
+
+// The document under the opa policy path, `deny` being the usual set of reasons
+#[derive(Deserialize, Default)]
+struct OpaDecision {
+    #[serde(default)]
+    allow: Option<bool>,
+    #[serde(default)]
+    deny: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct OpaResponse {
+    // Absent when nothing in the policy path is defined, allowing everything
+    #[serde(default)]
+    result: Option<OpaDecision>,
+}
+
+#[derive(Deserialize)]
+struct WebhookResponse {
+    allowed: bool,
+    #[serde(default)]
+    reasons: Vec<String>,
+}
+
+impl PolicyChecker {
+    pub fn new(conf: &BasinConfig) -> Result<Option<Self>> {
+        let Some(policy) = &conf.policy else {
+            return Ok(None);
+        };
+
+        Ok(Some(PolicyChecker {
+            conf: policy.clone(),
+            http_client: http_client::shared(),
+        }))
+    }
+
+    // Err is a PolicyDenied when the descriptor was vetoed, anything else means a check couldn't
+    // be made (and the descriptor was denied since `fail_open` isn't set)
+    pub async fn check<Descriptor: IdentifiableDescriptor + Serialize + Sync>(
+        &self,
+        descriptor: &Descriptor,
+    ) -> Result<()> {
+        let input = json!({
+            "kind": descriptor.kind(),
+            "descriptor": descriptor,
+        });
+
+        let mut reasons = Vec::new();
+        for result in [
+            self.check_opa(&input).await,
+            self.check_webhook(&input).await,
+        ] {
+            match result {
+                Ok(denials) => reasons.extend(denials),
+                Err(e) if self.conf.fail_open => {
+                    warn!(
+                        descriptor_id = descriptor.id(),
+                        ?e,
+                        "policy check failed, allowing descriptor"
+                    );
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        if reasons.is_empty() {
+            return Ok(());
+        }
+        info!(
+            descriptor_id = descriptor.id(),
+            ?reasons,
+            "descriptor denied by policy"
+        );
+        metrics::counter_inc(
+            "basin_policy_denials_total",
+            &[("kind", descriptor.kind().as_str())],
+        );
+        Err(PolicyDenied { reasons }.into())
+    }
+
+    async fn check_opa(&self, input: &serde_json::Value) -> Result<Vec<String>> {
+        let Some(OpaPolicyConf { url, path }) = &self.conf.opa else {
+            return Ok(vec![]);
+        };
+
+        let resp: OpaResponse = self
+            .http_client
+            .post(format!(
+                "{}/v1/data/{}",
+                url.trim_end_matches('/'),
+                path.trim_matches('/')
+            ))
+            .json(&json!({ "input": input }))
+            .timeout(self.timeout())
+            .send()
+            .await?
+            .error_for_status()
+            .context("opa policy query failed")?
+            .json()
+            .await?;
+
+        let decision = resp.result.unwrap_or_default();
+        let mut reasons = decision.deny;
+        if reasons.is_empty() && decision.allow == Some(false) {
+            reasons.push(format!("not allowed by opa policy {}", path));
+        }
+        Ok(reasons)
+    }
+
+    async fn check_webhook(&self, input: &serde_json::Value) -> Result<Vec<String>> {
+        let Some(PolicyWebhookConf { url, bearer_token }) = &self.conf.webhook else {
+            return Ok(vec![]);
+        };
+
+        let mut request = self
+            .http_client
+            .post(url)
+            .json(input)
+            .timeout(self.timeout());
+        if let Some(token) = bearer_token {
+            request = request.bearer_auth(token);
+        }
+        let resp: WebhookResponse = request
+            .send()
+            .await?
+            .error_for_status()
+            .context("policy webhook failed")?
+            .json()
+            .await?;
+
+        Ok(match (resp.allowed, resp.reasons.is_empty()) {
+            (true, _) => vec![],
+            (false, false) => resp.reasons,
+            (false, true) => vec!["not allowed by the policy webhook".to_string()],
+        })
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(self.conf.timeout_secs)
+    }
+}
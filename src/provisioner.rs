@@ -1,3 +1,19 @@
+pub mod athena;
+pub mod bigquery;
+pub mod column_types;
+pub mod datahub;
+pub mod error;
+pub mod fault_injection;
 pub mod glue;
+pub mod glue_workflow;
+pub mod hive_metastore;
+pub mod iam;
+pub mod lake_formation;
+pub mod redshift;
 pub mod s3;
+pub mod service_quotas;
+pub mod snowflake;
+pub mod step_functions;
+pub mod trino;
+pub mod unity_catalog;
 pub mod waterwheel;
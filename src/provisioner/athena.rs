@@ -0,0 +1,231 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use aws_config::SdkConfig;
+use aws_sdk_athena::model::{
+    ColumnInfo, Datum, QueryExecutionContext, QueryExecutionState, ResultConfiguration,
+};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tokio::time::{sleep, Instant};
+use tracing::debug;
+
+use super::{error::classify_aws_error, fault_injection};
+use crate::config::{PreviewConf, SmokeTestConf};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+pub struct AthenaProvisioner {
+    athena_client: aws_sdk_athena::Client,
+    workgroup: String,
+    output_location: Option<String>,
+    timeout_secs: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct QueryColumn {
+    pub name: String,
+    // Athena's type, e.g. `varchar` or `bigint`
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct QueryRows {
+    pub columns: Vec<QueryColumn>,
+    // Keyed by column, numbers and booleans as json numbers and booleans, anything else a string
+    pub rows: Vec<Map<String, Value>>,
+    pub bytes_scanned: Option<i64>,
+}
+
+impl AthenaProvisioner {
+    pub fn new(aws_conf: &SdkConfig, conf: &SmokeTestConf) -> Self {
+        AthenaProvisioner {
+            athena_client: aws_sdk_athena::Client::new(aws_conf),
+            workgroup: conf.workgroup.clone(),
+            output_location: conf.output_location.clone(),
+            timeout_secs: conf.timeout_secs,
+        }
+    }
+
+    pub fn for_preview(aws_conf: &SdkConfig, conf: &PreviewConf) -> Self {
+        AthenaProvisioner {
+            athena_client: aws_sdk_athena::Client::new(aws_conf),
+            workgroup: conf.workgroup.clone(),
+            output_location: conf.output_location.clone(),
+            timeout_secs: conf.timeout_secs,
+        }
+    }
+
+    // Runs the query to completion, results are left wherever the workgroup puts them. Queries
+    // still running at the timeout are cancelled.
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn run_query(&self, database: &str, sql: &str) -> Result<()> {
+        fault_injection::inject("athena.run_query").await?;
+        self.execute(database, sql, None).await?;
+        Ok(())
+    }
+
+    // Runs the query and reads back up to `max_rows` of its results. Queries are cancelled once
+    // they've scanned more than `max_bytes_scanned`.
+    // NOTE: scanning is only checked between polls, a query can overshoot the cap by whatever it
+    //       reads in a second
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn query_rows(
+        &self,
+        database: &str,
+        sql: &str,
+        max_rows: usize,
+        max_bytes_scanned: u64,
+    ) -> Result<QueryRows> {
+        fault_injection::inject("athena.query_rows").await?;
+        let (execution_id, bytes_scanned) =
+            self.execute(database, sql, Some(max_bytes_scanned)).await?;
+
+        // NOTE: the first row of a select's results is its header
+        let resp = self
+            .athena_client
+            .get_query_results()
+            .query_execution_id(&execution_id)
+            .max_results((max_rows + 1).min(1000) as i32)
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+        let result_set = resp.result_set();
+        let column_info = result_set
+            .and_then(|r| r.result_set_metadata())
+            .and_then(|m| m.column_info())
+            .unwrap_or_default();
+
+        let rows = result_set
+            .and_then(|r| r.rows())
+            .unwrap_or_default()
+            .iter()
+            .skip(1)
+            .take(max_rows)
+            .map(|row| {
+                column_info
+                    .iter()
+                    .zip(row.data().unwrap_or_default())
+                    .map(|(column, datum)| {
+                        (
+                            column.name().unwrap_or_default().to_string(),
+                            typed_value(column, datum),
+                        )
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Ok(QueryRows {
+            columns: column_info
+                .iter()
+                .map(|c| QueryColumn {
+                    name: c.name().unwrap_or_default().to_string(),
+                    kind: c.r#type().unwrap_or_default().to_string(),
+                })
+                .collect(),
+            rows,
+            bytes_scanned,
+        })
+    }
+
+    // Starts the query and waits for it to succeed, returning its execution id and how much it
+    // scanned
+    async fn execute(
+        &self,
+        database: &str,
+        sql: &str,
+        max_bytes_scanned: Option<u64>,
+    ) -> Result<(String, Option<i64>)> {
+        let mut request = self
+            .athena_client
+            .start_query_execution()
+            .query_string(sql)
+            .query_execution_context(QueryExecutionContext::builder().database(database).build())
+            .work_group(&self.workgroup);
+        if let Some(location) = &self.output_location {
+            request = request.result_configuration(
+                ResultConfiguration::builder()
+                    .output_location(location)
+                    .build(),
+            );
+        }
+        let started = request
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+        let execution_id = started
+            .query_execution_id()
+            .ok_or_else(|| anyhow!("athena didn't return a query execution id"))?;
+
+        let deadline = Instant::now() + Duration::from_secs(self.timeout_secs);
+        loop {
+            let resp = self
+                .athena_client
+                .get_query_execution()
+                .query_execution_id(execution_id)
+                .send()
+                .await
+                .map_err(|e| classify_aws_error(e.into_service_error()))?;
+            let execution = resp.query_execution();
+            let status = execution.and_then(|q| q.status());
+            let reason = status
+                .and_then(|s| s.state_change_reason())
+                .unwrap_or_default();
+            let bytes_scanned = execution
+                .and_then(|q| q.statistics())
+                .and_then(|s| s.data_scanned_in_bytes());
+
+            match status.and_then(|s| s.state()) {
+                Some(QueryExecutionState::Succeeded) => {
+                    return Ok((execution_id.to_string(), bytes_scanned))
+                }
+                Some(QueryExecutionState::Failed) => {
+                    return Err(anyhow!("query failed: {}", reason));
+                }
+                Some(QueryExecutionState::Cancelled) => {
+                    return Err(anyhow!("query was cancelled: {}", reason));
+                }
+                state => debug!(execution_id, ?state, "waiting on athena query"),
+            }
+
+            let over_cap = max_bytes_scanned
+                .zip(bytes_scanned)
+                .is_some_and(|(cap, scanned)| scanned as u64 > cap);
+            if over_cap || Instant::now() >= deadline {
+                // NOTE: best effort, the query is abandoned either way
+                let _ = self
+                    .athena_client
+                    .stop_query_execution()
+                    .query_execution_id(execution_id)
+                    .send()
+                    .await;
+                return Err(if over_cap {
+                    anyhow!(
+                        "query scanned more than {} bytes",
+                        max_bytes_scanned.unwrap_or_default()
+                    )
+                } else {
+                    anyhow!("query didn't finish within {}s", self.timeout_secs)
+                });
+            }
+            sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+// Athena hands every value back as a string, numbers and booleans are turned back into json's
+fn typed_value(column: &ColumnInfo, datum: &Datum) -> Value {
+    let Some(value) = datum.var_char_value() else {
+        return Value::Null;
+    };
+    let parsed = match column.r#type().unwrap_or_default() {
+        "boolean" => value.parse::<bool>().ok().map(Value::from),
+        "tinyint" | "smallint" | "integer" | "bigint" => value.parse::<i64>().ok().map(Value::from),
+        "float" | "real" | "double" => value.parse::<f64>().ok().map(Value::from),
+        _ => None,
+    };
+    parsed.unwrap_or_else(|| Value::from(value))
+}
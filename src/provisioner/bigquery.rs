@@ -0,0 +1,372 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+
+use super::{
+    column_types::bigquery_type,
+    error::{classify_http_error, classify_http_status},
+    fault_injection,
+};
+use crate::{config::BigQueryConf, fluid::descriptor::table::TableColumnAttribute};
+
+const BIGQUERY_API: &str = "https://bigquery.googleapis.com/bigquery/v2";
+const BIGQUERY_SCOPE: &str = "https://www.googleapis.com/auth/bigquery";
+// Tokens are refreshed this long before google says they expire
+const TOKEN_EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+// The parts of a service account key file basin needs
+#[derive(Deserialize, Clone)]
+struct ServiceAccountKey {
+    client_email: String,
+    private_key: String,
+    token_uri: String,
+}
+
+#[derive(Serialize)]
+struct TokenClaims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: i64,
+    exp: i64,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct TableField {
+    name: String,
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    mode: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(
+        default,
+        rename = "defaultValueExpression",
+        skip_serializing_if = "Option::is_none"
+    )]
+    default_value_expression: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct TableSchema {
+    #[serde(default)]
+    fields: Vec<TableField>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Table {
+    #[serde(default)]
+    schema: TableSchema,
+}
+
+// Creates datasets and tables through the bigquery rest api, authenticating as a service account
+pub struct BigQueryProvisioner {
+    conf: BigQueryConf,
+    key: ServiceAccountKey,
+    http_client: reqwest::Client,
+    token: Mutex<Option<(String, Instant)>>,
+}
+
+impl std::fmt::Debug for BigQueryProvisioner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BigQueryProvisioner")
+            .field("project_id", &self.conf.project_id)
+            .field("client_email", &self.key.client_email)
+            .finish()
+    }
+}
+
+impl BigQueryProvisioner {
+    pub fn new(conf: &BigQueryConf) -> Result<Self> {
+        let key = std::fs::read_to_string(&conf.credentials_file).with_context(|| {
+            format!(
+                "failed to read bigquery credentials from {}",
+                conf.credentials_file
+            )
+        })?;
+
+        Ok(BigQueryProvisioner {
+            conf: conf.clone(),
+            key: serde_json::from_str(&key).context("invalid service account key")?,
+            http_client: reqwest::Client::new(),
+            token: Mutex::new(None),
+        })
+    }
+
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn put_dataset(&self, dataset: &str, description: &str) -> Result<()> {
+        fault_injection::inject("bigquery.put_dataset").await?;
+        let url = format!(
+            "{}/projects/{}/datasets/{}",
+            BIGQUERY_API, self.conf.project_id, dataset
+        );
+
+        match self.get_json::<serde_json::Value>(&url).await? {
+            None => {
+                info!("bigquery dataset does not exist, creating it");
+                self.send_json(
+                    reqwest::Method::POST,
+                    &format!(
+                        "{}/projects/{}/datasets",
+                        BIGQUERY_API, self.conf.project_id
+                    ),
+                    &json!({
+                        "datasetReference": {
+                            "projectId": self.conf.project_id,
+                            "datasetId": dataset,
+                        },
+                        "description": description,
+                        "location": self.conf.location,
+                    }),
+                )
+                .await?;
+            }
+            Some(_) => {
+                self.send_json(
+                    reqwest::Method::PATCH,
+                    &url,
+                    &json!({ "description": description }),
+                )
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Creates the table, or brings an existing one in line with the descriptor. Bigquery can't drop
+    // columns through a schema update so columns missing from the descriptor are kept.
+    #[tracing::instrument(level = "info", skip(self, description, columns))]
+    pub async fn put_table(
+        &self,
+        dataset: &str,
+        table: &str,
+        description: &str,
+        columns: &[TableColumnAttribute],
+    ) -> Result<()> {
+        fault_injection::inject("bigquery.put_table").await?;
+        let url = format!(
+            "{}/projects/{}/datasets/{}/tables/{}",
+            BIGQUERY_API, self.conf.project_id, dataset, table
+        );
+        let mut fields: Vec<TableField> = columns
+            .iter()
+            .map(|c| TableField {
+                name: c.name.clone(),
+                kind: bigquery_type(&c.codec.kind).to_string(),
+                mode: Some(if c.nullable { "NULLABLE" } else { "REQUIRED" }.to_string()),
+                description: Some(c.summary.clone()),
+                default_value_expression: c.default.clone(),
+            })
+            .collect();
+
+        match self.get_json::<Table>(&url).await? {
+            None => {
+                info!("bigquery table does not exist, creating it");
+                self.send_json(
+                    reqwest::Method::POST,
+                    &format!(
+                        "{}/projects/{}/datasets/{}/tables",
+                        BIGQUERY_API, self.conf.project_id, dataset
+                    ),
+                    &json!({
+                        "tableReference": {
+                            "projectId": self.conf.project_id,
+                            "datasetId": dataset,
+                            "tableId": table,
+                        },
+                        "description": description,
+                        "schema": { "fields": fields },
+                    }),
+                )
+                .await?;
+            }
+            Some(existing) => {
+                let removed: Vec<TableField> = existing
+                    .schema
+                    .fields
+                    .into_iter()
+                    .filter(|f| !columns.iter().any(|c| c.name == f.name))
+                    .collect();
+                if !removed.is_empty() {
+                    warn!(
+                        removed = ?removed.iter().map(|f| &f.name).collect::<Vec<_>>(),
+                        "columns missing from the descriptor are left in place"
+                    );
+                    fields.extend(removed);
+                }
+
+                self.send_json(
+                    reqwest::Method::PATCH,
+                    &url,
+                    &json!({
+                        "description": description,
+                        "schema": { "fields": fields },
+                    }),
+                )
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // NOTE: fails while the dataset still has tables in it
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn delete_dataset(&self, dataset: &str) -> Result<()> {
+        fault_injection::inject("bigquery.delete_dataset").await?;
+        self.delete(&format!(
+            "{}/projects/{}/datasets/{}",
+            BIGQUERY_API, self.conf.project_id, dataset
+        ))
+        .await
+    }
+
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn delete_table(&self, dataset: &str, table: &str) -> Result<()> {
+        fault_injection::inject("bigquery.delete_table").await?;
+        self.delete(&format!(
+            "{}/projects/{}/datasets/{}/tables/{}",
+            BIGQUERY_API, self.conf.project_id, dataset, table
+        ))
+        .await
+    }
+
+    async fn get_json<T: for<'de> Deserialize<'de>>(&self, url: &str) -> Result<Option<T>> {
+        let resp = self
+            .http_client
+            .get(url)
+            .bearer_auth(self.access_token().await?)
+            .send()
+            .await
+            .map_err(classify_http_error)?;
+
+        let status = resp.status();
+        if status == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let text = resp.text().await.map_err(classify_http_error)?;
+        if !status.is_success() {
+            return Err(classify_http_status(
+                status,
+                anyhow!("bigquery request failed: {}", text),
+            ));
+        }
+
+        Ok(Some(serde_json::from_str(&text)?))
+    }
+
+    async fn send_json(
+        &self,
+        method: reqwest::Method,
+        url: &str,
+        body: &serde_json::Value,
+    ) -> Result<()> {
+        debug!(%method, url, "sending bigquery request");
+        let resp = self
+            .http_client
+            .request(method, url)
+            .bearer_auth(self.access_token().await?)
+            .json(body)
+            .send()
+            .await
+            .map_err(classify_http_error)?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            let text = resp.text().await.map_err(classify_http_error)?;
+            return Err(classify_http_status(
+                status,
+                anyhow!("bigquery request failed: {}", text),
+            ));
+        }
+
+        Ok(())
+    }
+
+    // Already deleted resources are left be
+    async fn delete(&self, url: &str) -> Result<()> {
+        debug!(url, "sending bigquery delete");
+        let resp = self
+            .http_client
+            .delete(url)
+            .bearer_auth(self.access_token().await?)
+            .send()
+            .await
+            .map_err(classify_http_error)?;
+
+        let status = resp.status();
+        if !status.is_success() && status != StatusCode::NOT_FOUND {
+            let text = resp.text().await.map_err(classify_http_error)?;
+            return Err(classify_http_status(
+                status,
+                anyhow!("bigquery request failed: {}", text),
+            ));
+        }
+
+        Ok(())
+    }
+
+    // Exchanges a self signed jwt for an access token, cached until shortly before it expires
+    async fn access_token(&self) -> Result<String> {
+        let mut token = self.token.lock().await;
+        if let Some((access_token, expires_at)) = token.as_ref()
+            && Instant::now() + TOKEN_EXPIRY_MARGIN < *expires_at
+        {
+            return Ok(access_token.clone());
+        }
+
+        let now = Utc::now().timestamp();
+        let assertion = jsonwebtoken::encode(
+            &Header::new(Algorithm::RS256),
+            &TokenClaims {
+                iss: &self.key.client_email,
+                scope: BIGQUERY_SCOPE,
+                aud: &self.key.token_uri,
+                iat: now,
+                exp: now + 3600,
+            },
+            &EncodingKey::from_rsa_pem(self.key.private_key.as_bytes())?,
+        )?;
+
+        let resp = self
+            .http_client
+            .post(&self.key.token_uri)
+            .form(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                ("assertion", assertion.as_str()),
+            ])
+            .send()
+            .await
+            .map_err(classify_http_error)?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            return Err(classify_http_status(
+                status,
+                anyhow!("failed to get a google access token"),
+            ));
+        }
+
+        let issued: TokenResponse = resp.json().await.map_err(classify_http_error)?;
+        *token = Some((
+            issued.access_token.clone(),
+            Instant::now() + Duration::from_secs(issued.expires_in),
+        ));
+        Ok(issued.access_token)
+    }
+}
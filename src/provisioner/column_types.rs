@@ -0,0 +1,92 @@
+use crate::fluid::descriptor::table::TableColumnType;
+
+// Column types in each engine's own terms
+
+// NOTE: kept to the names basin has always written to glue so existing tables aren't churned
+pub fn glue_type(kind: &TableColumnType) -> &'static str {
+    match kind {
+        TableColumnType::Int => "int",
+        TableColumnType::Long => "long",
+        TableColumnType::Float => "float",
+        TableColumnType::Double => "double",
+        TableColumnType::Boolean => "boolean",
+        TableColumnType::String => "string",
+        TableColumnType::Date => "date",
+        TableColumnType::Timestamp => "timestamp",
+        TableColumnType::Complex => "complex",
+    }
+}
+
+// Hive's own type names, unlike glue the metastore hands them to readers unchecked
+pub fn hive_type(kind: &TableColumnType) -> &'static str {
+    match kind {
+        TableColumnType::Int => "int",
+        TableColumnType::Long => "bigint",
+        TableColumnType::Float => "float",
+        TableColumnType::Double => "double",
+        TableColumnType::Boolean => "boolean",
+        TableColumnType::String => "string",
+        TableColumnType::Date => "date",
+        TableColumnType::Timestamp => "timestamp",
+        // NOTE: unreachable in practice, tables reject complex columns during validation
+        TableColumnType::Complex => "string",
+    }
+}
+
+pub fn snowflake_type(kind: &TableColumnType) -> &'static str {
+    match kind {
+        TableColumnType::Int => "INTEGER",
+        TableColumnType::Long => "BIGINT",
+        TableColumnType::Float => "FLOAT",
+        TableColumnType::Double => "DOUBLE",
+        TableColumnType::Boolean => "BOOLEAN",
+        TableColumnType::String => "VARCHAR",
+        TableColumnType::Date => "DATE",
+        TableColumnType::Timestamp => "TIMESTAMP_NTZ",
+        TableColumnType::Complex => "VARIANT",
+    }
+}
+
+// BigQuery has a single width for integers and floats
+pub fn bigquery_type(kind: &TableColumnType) -> &'static str {
+    match kind {
+        TableColumnType::Int | TableColumnType::Long => "INT64",
+        TableColumnType::Float | TableColumnType::Double => "FLOAT64",
+        TableColumnType::Boolean => "BOOL",
+        TableColumnType::String => "STRING",
+        TableColumnType::Date => "DATE",
+        TableColumnType::Timestamp => "TIMESTAMP",
+        TableColumnType::Complex => "JSON",
+    }
+}
+
+// Unity catalog wants the type's name and its sql spelling
+pub fn unity_type(kind: &TableColumnType) -> (&'static str, &'static str) {
+    match kind {
+        TableColumnType::Int => ("INT", "int"),
+        TableColumnType::Long => ("LONG", "bigint"),
+        TableColumnType::Float => ("FLOAT", "float"),
+        TableColumnType::Double => ("DOUBLE", "double"),
+        TableColumnType::Boolean => ("BOOLEAN", "boolean"),
+        TableColumnType::String => ("STRING", "string"),
+        TableColumnType::Date => ("DATE", "date"),
+        TableColumnType::Timestamp => ("TIMESTAMP", "timestamp"),
+        // NOTE: unreachable in practice, only glue tables are registered and they reject complex
+        TableColumnType::Complex => ("STRING", "string"),
+    }
+}
+
+// DataHub's `SchemaFieldDataType` union member the type falls under
+pub fn datahub_type(kind: &TableColumnType) -> &'static str {
+    match kind {
+        TableColumnType::Int
+        | TableColumnType::Long
+        | TableColumnType::Float
+        | TableColumnType::Double => "com.linkedin.schema.NumberType",
+        TableColumnType::Boolean => "com.linkedin.schema.BooleanType",
+        TableColumnType::String => "com.linkedin.schema.StringType",
+        TableColumnType::Date => "com.linkedin.schema.DateType",
+        TableColumnType::Timestamp => "com.linkedin.schema.TimeType",
+        TableColumnType::Complex => "com.linkedin.schema.RecordType",
+    }
+}
@@ -0,0 +1,370 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use serde_json::json;
+use tracing::debug;
+
+use super::{
+    column_types::datahub_type,
+    error::{classify_http_error, classify_http_status},
+    fault_injection,
+};
+use crate::{
+    config::DataHubConf,
+    fluid::descriptor::{
+        table::{DescriptiveMetadata, TableColumnAttribute},
+        Owner,
+    },
+};
+
+// Who basin's proposals are recorded as coming from
+const DATAHUB_ACTOR: &str = "urn:li:corpuser:basin";
+
+#[derive(Debug)]
+pub struct DataHubDataset {
+    // `glue` or `hive`, whichever catalog the table is registered in
+    pub platform: &'static str,
+    pub database: String,
+    pub table: String,
+    pub description: String,
+    pub location: String,
+    pub columns: Vec<TableColumnAttribute>,
+    pub owner: Option<Owner>,
+    pub metadata: Option<DescriptiveMetadata>,
+}
+
+#[derive(Debug)]
+pub struct DataHubFlow {
+    pub name: String,
+    pub description: String,
+    pub owner: Option<Owner>,
+    pub jobs: Vec<DataHubJob>,
+}
+
+// A step of a flow, with the datasets and jobs it runs after as lineage
+#[derive(Debug)]
+pub struct DataHubJob {
+    pub name: String,
+    pub description: String,
+    pub input_datasets: Vec<String>,
+    pub input_jobs: Vec<String>,
+}
+
+// Pushes what basin knows about its glue engine databases, tables and flows to DataHub as
+// metadata change proposals, so the catalog follows descriptors without a separate ingestion.
+// Aspects are upserted whole, anything edited in the DataHub ui lives in its editable aspects.
+#[derive(Debug)]
+pub struct DataHubProvisioner {
+    conf: DataHubConf,
+    http_client: reqwest::Client,
+}
+
+impl DataHubProvisioner {
+    pub fn new(conf: &DataHubConf) -> Result<Self> {
+        Ok(DataHubProvisioner {
+            conf: conf.clone(),
+            http_client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(conf.timeout_secs))
+                .build()?,
+        })
+    }
+
+    pub fn dataset_urn(&self, platform: &str, database: &str, table: &str) -> String {
+        format!(
+            "urn:li:dataset:(urn:li:dataPlatform:{},{}.{},{})",
+            platform, database, table, self.conf.env
+        )
+    }
+
+    pub fn job_urn(&self, flow: &str, step: &str) -> String {
+        format!("urn:li:dataJob:({},{})", self.flow_urn(flow), step)
+    }
+
+    fn flow_urn(&self, flow: &str) -> String {
+        format!("urn:li:dataFlow:(basin,{},{})", flow, self.conf.env)
+    }
+
+    fn container_urn(&self, platform: &str, database: &str) -> String {
+        format!(
+            "urn:li:container:basin.{}.{}.{}",
+            platform,
+            self.conf.env.to_lowercase(),
+            database
+        )
+    }
+
+    #[tracing::instrument(level = "info", skip(self, description, owner))]
+    pub async fn put_database(
+        &self,
+        platform: &str,
+        database: &str,
+        description: &str,
+        owner: Option<&Owner>,
+    ) -> Result<()> {
+        fault_injection::inject("datahub.put_database").await?;
+        let urn = self.container_urn(platform, database);
+
+        self.ingest(
+            "container",
+            &urn,
+            "containerProperties",
+            json!({ "name": database, "description": description }),
+        )
+        .await?;
+        self.ingest(
+            "container",
+            &urn,
+            "subTypes",
+            json!({ "typeNames": ["Database"] }),
+        )
+        .await?;
+        self.ingest(
+            "container",
+            &urn,
+            "dataPlatformInstance",
+            json!({ "platform": format!("urn:li:dataPlatform:{}", platform) }),
+        )
+        .await?;
+        if let Some(owner) = owner {
+            self.ingest("container", &urn, "ownership", ownership(owner))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "info", skip(self, dataset), fields(database = %dataset.database, table = %dataset.table))]
+    pub async fn put_dataset(&self, dataset: &DataHubDataset) -> Result<()> {
+        fault_injection::inject("datahub.put_dataset").await?;
+        let urn = self.dataset_urn(dataset.platform, &dataset.database, &dataset.table);
+        let metadata = dataset.metadata.clone().unwrap_or_default();
+
+        self.ingest(
+            "dataset",
+            &urn,
+            "datasetProperties",
+            json!({
+                "name": dataset.table,
+                "qualifiedName": format!("{}.{}", dataset.database, dataset.table),
+                "description": dataset.description,
+                "customProperties": { "location": dataset.location },
+            }),
+        )
+        .await?;
+        self.ingest(
+            "dataset",
+            &urn,
+            "container",
+            json!({ "container": self.container_urn(dataset.platform, &dataset.database) }),
+        )
+        .await?;
+        self.ingest("dataset", &urn, "schemaMetadata", schema_metadata(dataset))
+            .await?;
+        // NOTE: always sent so terms, docs and deprecations taken off the descriptor go too
+        self.ingest(
+            "dataset",
+            &urn,
+            "glossaryTerms",
+            glossary_terms(&metadata.glossary_terms),
+        )
+        .await?;
+        self.ingest(
+            "dataset",
+            &urn,
+            "institutionalMemory",
+            json!({
+                "elements": metadata.docs.iter().map(|doc| json!({
+                    "url": doc.url,
+                    "description": doc.title.clone().unwrap_or_else(|| doc.url.clone()),
+                    "createStamp": audit_stamp(),
+                })).collect::<Vec<_>>(),
+            }),
+        )
+        .await?;
+        self.ingest("dataset", &urn, "deprecation", deprecation(&metadata))
+            .await?;
+        if let Some(owner) = &dataset.owner {
+            self.ingest("dataset", &urn, "ownership", ownership(owner))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "info", skip(self, flow), fields(flow = %flow.name))]
+    pub async fn put_flow(&self, flow: &DataHubFlow) -> Result<()> {
+        fault_injection::inject("datahub.put_flow").await?;
+        let urn = self.flow_urn(&flow.name);
+
+        self.ingest(
+            "dataFlow",
+            &urn,
+            "dataFlowInfo",
+            json!({ "name": flow.name, "description": flow.description }),
+        )
+        .await?;
+        if let Some(owner) = &flow.owner {
+            self.ingest("dataFlow", &urn, "ownership", ownership(owner))
+                .await?;
+        }
+
+        for job in flow.jobs.iter() {
+            let job_urn = self.job_urn(&flow.name, &job.name);
+            self.ingest(
+                "dataJob",
+                &job_urn,
+                "dataJobInfo",
+                json!({
+                    "name": job.name,
+                    "description": job.description,
+                    "type": { "string": "COMMAND" },
+                    "flowUrn": urn,
+                }),
+            )
+            .await?;
+            // NOTE: basin only knows what starts a flow, not what it writes, so edges are inputs
+            self.ingest(
+                "dataJob",
+                &job_urn,
+                "dataJobInputOutput",
+                json!({
+                    "inputDatasets": job.input_datasets,
+                    "outputDatasets": [],
+                    "inputDatajobs": job.input_jobs,
+                }),
+            )
+            .await?;
+            if let Some(owner) = &flow.owner {
+                self.ingest("dataJob", &job_urn, "ownership", ownership(owner))
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn ingest(
+        &self,
+        entity_type: &str,
+        urn: &str,
+        aspect_name: &str,
+        aspect: serde_json::Value,
+    ) -> Result<()> {
+        debug!(urn, aspect_name, "sending datahub proposal");
+        let request = self
+            .http_client
+            .post(format!(
+                "{}/aspects?action=ingestProposal",
+                self.conf.gms_url.trim_end_matches('/')
+            ))
+            .header("X-RestLi-Protocol-Version", "2.0.0")
+            .json(&json!({
+                "proposal": {
+                    "entityType": entity_type,
+                    "entityUrn": urn,
+                    "changeType": "UPSERT",
+                    "aspectName": aspect_name,
+                    "aspect": {
+                        "contentType": "application/json",
+                        "value": aspect.to_string(),
+                    },
+                }
+            }));
+        let request = match &self.conf.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        };
+        let resp = request.send().await.map_err(classify_http_error)?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            let text = resp.text().await.map_err(classify_http_error)?;
+            return Err(classify_http_status(
+                status,
+                anyhow!(
+                    "datahub proposal for {} {} failed: {}",
+                    urn,
+                    aspect_name,
+                    text
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+fn schema_metadata(dataset: &DataHubDataset) -> serde_json::Value {
+    let fields: Vec<serde_json::Value> = dataset
+        .columns
+        .iter()
+        .map(|c| {
+            let metadata = c.metadata.clone().unwrap_or_default();
+            // NOTE: fields have no deprecation of their own, it's folded into the description
+            let description = match &metadata.deprecation {
+                Some(d) => format!("{}\n\nDeprecated: {}", c.summary, d.note),
+                None => c.summary.clone(),
+            };
+            json!({
+                "fieldPath": c.name,
+                "nativeDataType": format!("{:?}", c.codec.kind).to_lowercase(),
+                "type": { "type": { datahub_type(&c.codec.kind): {} } },
+                "nullable": c.nullable,
+                "description": description,
+                "glossaryTerms": glossary_terms(&metadata.glossary_terms),
+            })
+        })
+        .collect();
+
+    json!({
+        "schemaName": format!("{}.{}", dataset.database, dataset.table),
+        "platform": format!("urn:li:dataPlatform:{}", dataset.platform),
+        "version": 0,
+        "hash": "",
+        "platformSchema": { "com.linkedin.schema.OtherSchema": { "rawSchema": "" } },
+        "fields": fields,
+    })
+}
+
+fn glossary_terms(terms: &[String]) -> serde_json::Value {
+    json!({
+        "terms": terms
+            .iter()
+            .map(|t| json!({ "urn": format!("urn:li:glossaryTerm:{}", t) }))
+            .collect::<Vec<_>>(),
+        "auditStamp": audit_stamp(),
+    })
+}
+
+fn deprecation(metadata: &DescriptiveMetadata) -> serde_json::Value {
+    match &metadata.deprecation {
+        Some(d) => {
+            let note = match &d.replaced_by {
+                Some(replacement) => format!("{} Use {} instead.", d.note, replacement),
+                None => d.note.clone(),
+            };
+            let mut aspect = json!({ "deprecated": true, "note": note, "actor": DATAHUB_ACTOR });
+            if let Some(sunset) = d.sunset.and_then(|s| s.and_hms_opt(0, 0, 0)) {
+                aspect["decommissionTime"] = json!(sunset.and_utc().timestamp_millis());
+            }
+            aspect
+        }
+        None => json!({ "deprecated": false, "note": "", "actor": DATAHUB_ACTOR }),
+    }
+}
+
+// Teams become groups, DataHub resolves them against whatever its identity sync created
+fn ownership(owner: &Owner) -> serde_json::Value {
+    json!({
+        "owners": [{
+            "owner": format!("urn:li:corpGroup:{}", owner.team),
+            "type": "TECHNICAL_OWNER",
+        }],
+        "lastModified": audit_stamp(),
+    })
+}
+
+fn audit_stamp() -> serde_json::Value {
+    json!({ "time": Utc::now().timestamp_millis(), "actor": DATAHUB_ACTOR })
+}
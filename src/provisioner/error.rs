@@ -0,0 +1,68 @@
+use aws_smithy_types::retry::ProvideErrorKind;
+use reqwest::StatusCode;
+use thiserror::Error;
+
+// AWS error codes retrying won't fix, anything not listed here is assumed to be transient
+const PERMANENT_AWS_ERROR_CODES: &[&str] = &[
+    "AccessDenied",
+    "AccessDeniedException",
+    "UnauthorizedOperation",
+    "InvalidAccessKeyId",
+    "SignatureDoesNotMatch",
+    "ValidationException",
+    "InvalidInputException",
+    "InvalidRequest",
+    "InvalidArgument",
+    "InvalidBucketName",
+    "MalformedXML",
+    "MalformedPolicyDocument",
+    "ResourceNumberLimitExceededException",
+];
+
+// Wraps provisioner failures that need someone to change the descriptor or the environment
+// (bad input, missing permissions) before they can succeed
+#[derive(Error, Debug)]
+#[error("permanent failure")]
+pub struct PermanentFailure(#[source] pub anyhow::Error);
+
+pub fn is_permanent(e: &anyhow::Error) -> bool {
+    e.chain().any(|c| c.is::<PermanentFailure>())
+}
+
+// Expects the service error (`SdkError::into_service_error`), timeouts and dispatch failures end
+// up as unhandled errors without a code and are left as transient
+pub fn classify_aws_error<E>(e: E) -> anyhow::Error
+where
+    E: ProvideErrorKind + std::error::Error + Send + Sync + 'static,
+{
+    let permanent = e.retryable_error_kind().is_none()
+        && e.code()
+            .map_or(false, |c| PERMANENT_AWS_ERROR_CODES.contains(&c));
+
+    if permanent {
+        PermanentFailure(e.into()).into()
+    } else {
+        e.into()
+    }
+}
+
+pub fn classify_http_status(status: StatusCode, e: anyhow::Error) -> anyhow::Error {
+    // NOTE: 408 and 429 are client errors in name only, the same request can succeed later
+    let permanent = status.is_client_error()
+        && status != StatusCode::REQUEST_TIMEOUT
+        && status != StatusCode::TOO_MANY_REQUESTS;
+
+    if permanent {
+        PermanentFailure(e).into()
+    } else {
+        e
+    }
+}
+
+pub fn classify_http_error(e: reqwest::Error) -> anyhow::Error {
+    // Connection, timeout and body errors carry no status and are worth retrying
+    match e.status() {
+        Some(status) => classify_http_status(status, e.into()),
+        None => e.into(),
+    }
+}
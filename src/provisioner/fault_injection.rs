@@ -0,0 +1,59 @@
+use std::{sync::OnceLock, time::Duration};
+
+use anyhow::Result;
+use rand::Rng;
+use thiserror::Error;
+use tracing::{debug, warn};
+
+use super::error::PermanentFailure;
+use crate::{
+    config::{FaultInjectionConf, FaultKind},
+    metrics,
+};
+
+static FAULTS: OnceLock<FaultInjectionConf> = OnceLock::new();
+
+#[derive(Error, Debug)]
+#[error("injected {} failure in {}", .kind.as_str(), .operation)]
+pub struct InjectedFault {
+    pub operation: String,
+    pub kind: FaultKind,
+}
+
+// Turns injection on for the rest of the process, set once at startup
+pub fn install(conf: FaultInjectionConf) {
+    warn!("fault injection is enabled, provisioner calls will fail on purpose");
+    let _ = FAULTS.set(conf);
+}
+
+// Called by provisioners ahead of each operation, `operation` is `{provisioner}.{operation}`
+pub async fn inject(operation: &str) -> Result<()> {
+    let Some(conf) = FAULTS.get() else {
+        return Ok(());
+    };
+    let fault = conf.operations.get(operation).unwrap_or(&conf.default);
+
+    if fault.latency_ms > 0 {
+        tokio::time::sleep(Duration::from_millis(fault.latency_ms)).await;
+    }
+    if !rand::thread_rng().gen_bool(fault.error_rate.clamp(0.0, 1.0)) {
+        return Ok(());
+    }
+
+    let kind = fault.error_kind.as_str();
+    debug!(operation, kind, "injecting failure");
+    metrics::counter_inc(
+        "basin_injected_faults_total",
+        &[("operation", operation), ("kind", kind)],
+    );
+    let e = InjectedFault {
+        operation: operation.to_string(),
+        kind: fault.error_kind,
+    };
+    // NOTE: classified the same way a real error of the kind would be
+    if fault.error_kind.is_permanent() {
+        Err(PermanentFailure(e.into()).into())
+    } else {
+        Err(e.into())
+    }
+}
@@ -1,14 +1,60 @@
-use anyhow::Result;
-use std::option::Option;
+use anyhow::{bail, Result};
+use std::{collections::BTreeMap, option::Option};
 
 use aws_config::SdkConfig;
 use aws_sdk_glue::{
     error::{GetDatabaseError, GetDatabaseErrorKind},
-    model::DatabaseInput,
-    output::GetDatabaseOutput,
+    model::{DatabaseInput, PartitionInput, TableInput},
+    output::{GetDatabaseOutput, GetTableOutput},
     Client,
 };
 
+use super::{error::classify_aws_error, fault_injection};
+use crate::constants::DESCRIPTOR_HASH_KEY;
+
+// Most partitions glue takes in one BatchCreatePartition
+const MAX_PARTITIONS_PER_BATCH: usize = 100;
+
+// What the database controller needs from glue, the seam for running it against a fake
+#[cfg_attr(test, mockall::automock)]
+#[async_trait::async_trait]
+pub(crate) trait GlueDatabaseClient: Send + Sync + std::fmt::Debug {
+    async fn get_database(&self, database_name: &str) -> Result<Option<GetDatabaseOutput>>;
+    async fn create_database(
+        &self,
+        name: &str,
+        description: &str,
+        location: &str,
+        descriptor_hash: &str,
+        cost_tags: &BTreeMap<String, String>,
+    ) -> Result<()>;
+    async fn update_database(
+        &self,
+        name: &str,
+        description: &str,
+        location: &str,
+        descriptor_hash: &str,
+        cost_tags: &BTreeMap<String, String>,
+    ) -> Result<()>;
+    // A database that's already gone counts as deleted
+    async fn delete_database(&self, name: &str) -> Result<()>;
+}
+
+// What the table controller needs from glue, the seam for running it against a fake
+#[cfg_attr(test, mockall::automock)]
+#[async_trait::async_trait]
+pub(crate) trait GlueTableClient: Send + Sync {
+    async fn get_table(
+        &self,
+        database_name: &str,
+        table_name: &str,
+    ) -> Result<Option<GetTableOutput>>;
+    async fn create_table(&self, database_name: &str, table_input: TableInput) -> Result<()>;
+    async fn update_table(&self, database_name: &str, table_input: TableInput) -> Result<()>;
+    // A table that's already gone counts as deleted
+    async fn delete_table(&self, database_name: &str, table_name: &str) -> Result<()>;
+}
+
 #[derive(Debug)]
 pub struct GlueProvisioner {
     glue_client: Client,
@@ -21,8 +67,93 @@ impl GlueProvisioner {
         }
     }
 
+    // Registers the partitions, returning the values of those which were already registered.
+    // Those count as done, so registering the same partitions again is harmless.
+    #[tracing::instrument(level = "info", skip(self, partitions), fields(partitions = partitions.len()))]
+    pub async fn create_partitions(
+        &self,
+        database_name: &str,
+        table_name: &str,
+        partitions: Vec<PartitionInput>,
+    ) -> Result<Vec<Vec<String>>> {
+        let mut existing = vec![];
+        for batch in partitions.chunks(MAX_PARTITIONS_PER_BATCH) {
+            fault_injection::inject("glue.batch_create_partition").await?;
+            let output = self
+                .glue_client
+                .batch_create_partition()
+                .database_name(database_name)
+                .table_name(table_name)
+                .set_partition_input_list(Some(batch.to_vec()))
+                .send()
+                .await
+                .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
+            for error in output.errors().unwrap_or_default() {
+                let values = error.partition_values().unwrap_or_default().to_vec();
+                let detail = error.error_detail();
+                match detail.and_then(|d| d.error_code()) {
+                    Some("AlreadyExistsException") => existing.push(values),
+                    code => bail!(
+                        "failed to register partition {:?}: {} {}",
+                        values,
+                        code.unwrap_or("unknown error"),
+                        detail.and_then(|d| d.error_message()).unwrap_or_default()
+                    ),
+                }
+            }
+        }
+        Ok(existing)
+    }
+
+    async fn tag_database(&self, name: &str, cost_tags: &BTreeMap<String, String>) -> Result<()> {
+        let mut request = self
+            .glue_client
+            .tag_resource()
+            .resource_arn(self.arn_for_database(&name))
+            // TODO: read from config
+            .tags_to_add("provisioner", "basin")
+            .tags_to_add("subporovisioner", "glue")
+            .tags_to_add("basin_version", "0.0.1");
+        for (key, value) in cost_tags {
+            request = request.tags_to_add(key, value);
+        }
+        request
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
+        Ok(())
+    }
+
+    fn build_db_input(
+        name: &str,
+        description: &str,
+        location: &str,
+        descriptor_hash: &str,
+    ) -> DatabaseInput {
+        DatabaseInput::builder()
+            .name(name)
+            .description(description)
+            .location_uri(location)
+            .parameters(DESCRIPTOR_HASH_KEY, descriptor_hash)
+            .build()
+    }
+
+    fn arn_for_database(&self, database_name: &str) -> String {
+        // FIXME: un-hardcode these
+        format!(
+            "arn:aws:glue:{}:{}:database/{}",
+            "us-east-1", "549989278514", database_name
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl GlueDatabaseClient for GlueProvisioner {
     #[tracing::instrument(level = "info", skip(self))]
-    pub async fn get_database(&self, database_name: &str) -> Result<Option<GetDatabaseOutput>> {
+    async fn get_database(&self, database_name: &str) -> Result<Option<GetDatabaseOutput>> {
+        fault_injection::inject("glue.get_database").await?;
         let glue_resource = self
             .glue_client
             .get_database()
@@ -37,48 +168,43 @@ impl GlueProvisioner {
                 ..
             }) => Ok(None),
             Ok(t) => Ok(Some(t)),
-            Err(e) => Err(e.into()),
+            Err(e) => Err(classify_aws_error(e)),
         }
     }
 
     #[tracing::instrument(level = "info", skip(self))]
-    pub async fn create_database(
+    async fn create_database(
         &self,
         name: &str,
         description: &str,
         location: &str,
+        descriptor_hash: &str,
+        cost_tags: &BTreeMap<String, String>,
     ) -> Result<()> {
-        let db_input = Self::build_db_input(name, description, location);
+        fault_injection::inject("glue.create_database").await?;
+        let db_input = Self::build_db_input(name, description, location, descriptor_hash);
 
         self.glue_client
             .create_database()
             .database_input(db_input)
             .send()
             .await
-            .map_err(|e| e.into_service_error())?;
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
 
-        self.glue_client
-            .tag_resource()
-            .resource_arn(self.arn_for_database(&name))
-            // TODO: read from config
-            .tags_to_add("provisioner", "basin")
-            .tags_to_add("subporovisioner", "glue")
-            .tags_to_add("basin_version", "0.0.1")
-            .send()
-            .await
-            .map_err(|e| e.into_service_error())?;
-
-        Ok(())
+        self.tag_database(name, cost_tags).await
     }
 
     #[tracing::instrument(level = "info", skip(self))]
-    pub async fn update_database(
+    async fn update_database(
         &self,
         name: &str,
         description: &str,
         location: &str,
+        descriptor_hash: &str,
+        cost_tags: &BTreeMap<String, String>,
     ) -> Result<()> {
-        let db_input = Self::build_db_input(name, description, location);
+        fault_injection::inject("glue.update_database").await?;
+        let db_input = Self::build_db_input(name, description, location, descriptor_hash);
 
         self.glue_client
             .update_database()
@@ -86,24 +212,99 @@ impl GlueProvisioner {
             .database_input(db_input)
             .send()
             .await
-            .map_err(|e| e.into_service_error())?;
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
 
-        Ok(())
+        // NOTE: tags are only ever added, labels dropped from the descriptor stay on the database
+        self.tag_database(name, cost_tags).await
     }
 
-    fn build_db_input(name: &str, description: &str, location: &str) -> DatabaseInput {
-        DatabaseInput::builder()
+    // NOTE: glue drops the database's tables along with it
+    #[tracing::instrument(level = "info", skip(self))]
+    async fn delete_database(&self, name: &str) -> Result<()> {
+        fault_injection::inject("glue.delete_database").await?;
+        let deleted = self
+            .glue_client
+            .delete_database()
             .name(name)
-            .description(description)
-            .location_uri(location)
-            .build()
+            .send()
+            .await
+            .map_err(|e| e.into_service_error());
+
+        match deleted {
+            Err(e) if e.is_entity_not_found_exception() => Ok(()),
+            Err(e) => Err(classify_aws_error(e)),
+            Ok(_) => Ok(()),
+        }
     }
+}
 
-    fn arn_for_database(&self, database_name: &str) -> String {
-        // FIXME: un-hardcode these
-        format!(
-            "arn:aws:glue:{}:{}:database/{}",
-            "us-east-1", "549989278514", database_name
-        )
+#[async_trait::async_trait]
+impl GlueTableClient for GlueProvisioner {
+    #[tracing::instrument(level = "info", skip(self))]
+    async fn get_table(
+        &self,
+        database_name: &str,
+        table_name: &str,
+    ) -> Result<Option<GetTableOutput>> {
+        fault_injection::inject("glue.get_table").await?;
+        let table = self
+            .glue_client
+            .get_table()
+            .database_name(database_name)
+            .name(table_name)
+            .send()
+            .await
+            .map_err(|e| e.into_service_error());
+
+        match table {
+            Err(e) if e.is_entity_not_found_exception() => Ok(None),
+            Ok(t) => Ok(Some(t)),
+            Err(e) => Err(classify_aws_error(e)),
+        }
+    }
+
+    #[tracing::instrument(level = "info", skip(self, table_input))]
+    async fn create_table(&self, database_name: &str, table_input: TableInput) -> Result<()> {
+        fault_injection::inject("glue.create_table").await?;
+        self.glue_client
+            .create_table()
+            .database_name(database_name)
+            .table_input(table_input)
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "info", skip(self, table_input))]
+    async fn update_table(&self, database_name: &str, table_input: TableInput) -> Result<()> {
+        fault_injection::inject("glue.update_table").await?;
+        self.glue_client
+            .update_table()
+            .database_name(database_name)
+            .table_input(table_input)
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "info", skip(self))]
+    async fn delete_table(&self, database_name: &str, table_name: &str) -> Result<()> {
+        fault_injection::inject("glue.delete_table").await?;
+        let deleted = self
+            .glue_client
+            .delete_table()
+            .database_name(database_name)
+            .name(table_name)
+            .send()
+            .await
+            .map_err(|e| e.into_service_error());
+
+        match deleted {
+            Err(e) if e.is_entity_not_found_exception() => Ok(()),
+            Err(e) => Err(classify_aws_error(e)),
+            Ok(_) => Ok(()),
+        }
     }
 }
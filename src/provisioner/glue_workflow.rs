@@ -0,0 +1,363 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+use aws_config::SdkConfig;
+use aws_sdk_glue::{
+    model::{
+        Action, Condition, JobCommand, JobRunState, JobUpdate, Logical, LogicalOperator, NodeType,
+        Predicate, TriggerType, TriggerUpdate,
+    },
+    Client,
+};
+use serde::Serialize;
+use tracing::info;
+
+use super::{error::classify_aws_error, fault_injection};
+
+// Glue jobs, workflows and triggers backing flows that run on the glue backend
+
+#[derive(Serialize, Debug, Clone)]
+pub struct GlueJobSpec {
+    pub name: String,
+    pub description: String,
+    pub role_arn: String,
+    pub script_location: String,
+    pub glue_version: String,
+    pub default_arguments: HashMap<String, String>,
+    pub max_retries: Option<i32>,
+    // Minutes
+    pub timeout: Option<i32>,
+    pub tags: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct GlueTriggerSpec {
+    pub name: String,
+    pub workflow_name: String,
+    pub kind: GlueTriggerKind,
+    // Jobs the trigger starts
+    pub jobs: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GlueTriggerKind {
+    // Glue flavoured cron, `cron(15 12 * * ? *)`
+    Scheduled(String),
+    // Fires once every listed job has succeeded
+    Conditional(Vec<String>),
+}
+
+#[derive(Debug, Default)]
+pub struct GlueWorkflowMembers {
+    pub jobs: Vec<String>,
+    pub triggers: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct GlueWorkflowProvisioner {
+    glue_client: Client,
+}
+
+impl GlueWorkflowProvisioner {
+    pub fn new(aws_conf: &SdkConfig) -> Self {
+        GlueWorkflowProvisioner {
+            glue_client: Client::new(aws_conf),
+        }
+    }
+
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn put_workflow(
+        &self,
+        name: &str,
+        description: &str,
+        tags: &BTreeMap<String, String>,
+    ) -> Result<()> {
+        fault_injection::inject("glue_workflow.put_workflow").await?;
+        let existing = self
+            .glue_client
+            .get_workflow()
+            .name(name)
+            .send()
+            .await
+            .map_err(|e| e.into_service_error());
+
+        match existing {
+            Err(e) if e.is_entity_not_found_exception() => {
+                info!("glue workflow does not exist, creating it");
+                self.glue_client
+                    .create_workflow()
+                    .name(name)
+                    .description(description)
+                    .set_tags(Some(with_provisioner_tag(tags)))
+                    .send()
+                    .await
+                    .map_err(|e| classify_aws_error(e.into_service_error()))?;
+            }
+            Ok(_) => {
+                self.glue_client
+                    .update_workflow()
+                    .name(name)
+                    .description(description)
+                    .send()
+                    .await
+                    .map_err(|e| classify_aws_error(e.into_service_error()))?;
+            }
+            Err(e) => return Err(classify_aws_error(e)),
+        }
+
+        Ok(())
+    }
+
+    // Jobs and triggers currently attached to the workflow, empty if it doesn't exist
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn workflow_members(&self, name: &str) -> Result<GlueWorkflowMembers> {
+        fault_injection::inject("glue_workflow.workflow_members").await?;
+        let workflow = self
+            .glue_client
+            .get_workflow()
+            .name(name)
+            .include_graph(true)
+            .send()
+            .await
+            .map_err(|e| e.into_service_error());
+
+        let workflow = match workflow {
+            Err(e) if e.is_entity_not_found_exception() => {
+                return Ok(GlueWorkflowMembers::default())
+            }
+            Ok(t) => t,
+            Err(e) => return Err(classify_aws_error(e)),
+        };
+
+        let mut members = GlueWorkflowMembers::default();
+        let nodes = workflow
+            .workflow()
+            .and_then(|w| w.graph())
+            .and_then(|g| g.nodes())
+            .unwrap_or_default();
+        for node in nodes {
+            let Some(node_name) = node.name() else {
+                continue;
+            };
+            match node.r#type() {
+                Some(NodeType::Job) => members.jobs.push(node_name.to_string()),
+                Some(NodeType::Trigger) => members.triggers.push(node_name.to_string()),
+                _ => {}
+            }
+        }
+
+        Ok(members)
+    }
+
+    #[tracing::instrument(level = "info", skip(self, spec), fields(name = spec.name))]
+    pub async fn put_job(&self, spec: &GlueJobSpec) -> Result<()> {
+        fault_injection::inject("glue_workflow.put_job").await?;
+        let existing = self
+            .glue_client
+            .get_job()
+            .job_name(&spec.name)
+            .send()
+            .await
+            .map_err(|e| e.into_service_error());
+
+        let command = JobCommand::builder()
+            .name("glueetl")
+            .script_location(&spec.script_location)
+            .python_version("3")
+            .build();
+
+        match existing {
+            Err(e) if e.is_entity_not_found_exception() => {
+                info!("glue job does not exist, creating it");
+                self.glue_client
+                    .create_job()
+                    .name(&spec.name)
+                    .description(&spec.description)
+                    .role(&spec.role_arn)
+                    .command(command)
+                    .glue_version(&spec.glue_version)
+                    .set_default_arguments(Some(spec.default_arguments.clone()))
+                    .set_max_retries(spec.max_retries)
+                    .set_timeout(spec.timeout)
+                    .set_tags(Some(with_provisioner_tag(&spec.tags)))
+                    .send()
+                    .await
+                    .map_err(|e| classify_aws_error(e.into_service_error()))?;
+            }
+            Ok(_) => {
+                self.glue_client
+                    .update_job()
+                    .job_name(&spec.name)
+                    .job_update(
+                        JobUpdate::builder()
+                            .description(&spec.description)
+                            .role(&spec.role_arn)
+                            .command(command)
+                            .glue_version(&spec.glue_version)
+                            .set_default_arguments(Some(spec.default_arguments.clone()))
+                            .set_max_retries(spec.max_retries)
+                            .set_timeout(spec.timeout)
+                            .build(),
+                    )
+                    .send()
+                    .await
+                    .map_err(|e| classify_aws_error(e.into_service_error()))?;
+            }
+            Err(e) => return Err(classify_aws_error(e)),
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "info", skip(self, spec), fields(name = spec.name))]
+    pub async fn put_trigger(&self, spec: &GlueTriggerSpec) -> Result<()> {
+        fault_injection::inject("glue_workflow.put_trigger").await?;
+        let existing = self
+            .glue_client
+            .get_trigger()
+            .name(&spec.name)
+            .send()
+            .await
+            .map_err(|e| e.into_service_error());
+
+        let (trigger_type, schedule, predicate) = Self::trigger_parts(&spec.kind);
+        let actions: Vec<Action> = spec
+            .jobs
+            .iter()
+            .map(|job| Action::builder().job_name(job).build())
+            .collect();
+
+        match existing {
+            // NOTE: a trigger's type can't be updated in place, it has to be recreated
+            Ok(t) if t.trigger().and_then(|t| t.r#type()) == Some(&trigger_type) => {
+                self.glue_client
+                    .update_trigger()
+                    .name(&spec.name)
+                    .trigger_update(
+                        TriggerUpdate::builder()
+                            .name(&spec.name)
+                            .set_schedule(schedule)
+                            .set_predicate(predicate)
+                            .set_actions(Some(actions))
+                            .build(),
+                    )
+                    .send()
+                    .await
+                    .map_err(|e| classify_aws_error(e.into_service_error()))?;
+                return Ok(());
+            }
+            Ok(_) => {
+                info!("glue trigger changed type, recreating it");
+                self.delete_trigger(&spec.name).await?;
+            }
+            Err(e) if e.is_entity_not_found_exception() => {
+                info!("glue trigger does not exist, creating it");
+            }
+            Err(e) => return Err(classify_aws_error(e)),
+        }
+
+        self.glue_client
+            .create_trigger()
+            .name(&spec.name)
+            .workflow_name(&spec.workflow_name)
+            .r#type(trigger_type)
+            .set_schedule(schedule)
+            .set_predicate(predicate)
+            .set_actions(Some(actions))
+            .start_on_creation(true)
+            .tags("provisioner", "basin")
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn delete_trigger(&self, name: &str) -> Result<()> {
+        fault_injection::inject("glue_workflow.delete_trigger").await?;
+        self.glue_client
+            .delete_trigger()
+            .name(name)
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn delete_job(&self, name: &str) -> Result<()> {
+        fault_injection::inject("glue_workflow.delete_job").await?;
+        self.glue_client
+            .delete_job()
+            .job_name(name)
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+        Ok(())
+    }
+
+    // Runs the workflow now, regardless of its schedule
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn start_workflow_run(&self, name: &str) -> Result<()> {
+        fault_injection::inject("glue_workflow.start_workflow_run").await?;
+        self.glue_client
+            .start_workflow_run()
+            .name(name)
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+        Ok(())
+    }
+
+    // NOTE: glue deletes workflows that don't exist without complaint
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn delete_workflow(&self, name: &str) -> Result<()> {
+        fault_injection::inject("glue_workflow.delete_workflow").await?;
+        self.glue_client
+            .delete_workflow()
+            .name(name)
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+        Ok(())
+    }
+
+    fn trigger_parts(kind: &GlueTriggerKind) -> (TriggerType, Option<String>, Option<Predicate>) {
+        match kind {
+            GlueTriggerKind::Scheduled(schedule) => {
+                (TriggerType::Scheduled, Some(schedule.clone()), None)
+            }
+            GlueTriggerKind::Conditional(jobs) => {
+                let conditions = jobs
+                    .iter()
+                    .map(|job| {
+                        Condition::builder()
+                            .logical_operator(LogicalOperator::Equals)
+                            .job_name(job)
+                            .state(JobRunState::Succeeded)
+                            .build()
+                    })
+                    .collect();
+                (
+                    TriggerType::Conditional,
+                    None,
+                    Some(
+                        Predicate::builder()
+                            .logical(Logical::And)
+                            .set_conditions(Some(conditions))
+                            .build(),
+                    ),
+                )
+            }
+        }
+    }
+}
+
+// NOTE: tags are only set when glue resources are created, existing ones keep whatever they had
+fn with_provisioner_tag(tags: &BTreeMap<String, String>) -> HashMap<String, String> {
+    let mut tags: HashMap<String, String> = tags.clone().into_iter().collect();
+    tags.insert("provisioner".to_string(), "basin".to_string());
+    tags
+}
@@ -0,0 +1,191 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use aws_config::SdkConfig;
+use aws_sdk_iam::{model::Tag, Client};
+use serde_json::json;
+
+use super::{error::classify_aws_error, fault_injection};
+use crate::config::IamConf;
+
+#[derive(Debug)]
+pub struct IamProvisioner {
+    client: Client,
+    conf: IamConf,
+}
+
+impl IamProvisioner {
+    pub fn new(aws_conf: &SdkConfig, conf: &IamConf) -> Self {
+        IamProvisioner {
+            client: Client::new(aws_conf),
+            conf: conf.clone(),
+        }
+    }
+
+    // Creates the role or brings an existing one's trust policy, boundary and tags in line,
+    // returning its arn. The configured principals are the ones trusted to assume it.
+    // NOTE: iam can't move a role to another path, roles keep the path they were created under
+    #[tracing::instrument(level = "info", skip(self, cost_tags))]
+    pub async fn put_role(
+        &self,
+        name: &str,
+        cost_tags: &BTreeMap<String, String>,
+    ) -> Result<String> {
+        let trust_policy = json!({
+            "Version": "2012-10-17",
+            "Statement": [{
+                "Effect": "Allow",
+                "Principal": { "AWS": self.conf.trusted_principals },
+                "Action": "sts:AssumeRole",
+            }],
+        })
+        .to_string();
+        let permissions_boundary_arn = self.conf.permissions_boundary_arn.as_deref();
+
+        fault_injection::inject("iam.get_role").await?;
+        let existing = self
+            .client
+            .get_role()
+            .role_name(name)
+            .send()
+            .await
+            .map_err(|e| e.into_service_error());
+
+        let role = match existing {
+            Ok(t) => {
+                self.update_role(name, &trust_policy, permissions_boundary_arn, cost_tags)
+                    .await?;
+                t.role().cloned()
+            }
+            Err(e) if e.is_no_such_entity_exception() => {
+                fault_injection::inject("iam.create_role").await?;
+                self.client
+                    .create_role()
+                    .role_name(name)
+                    .path(&self.conf.role_path)
+                    .assume_role_policy_document(trust_policy)
+                    .set_permissions_boundary(permissions_boundary_arn.map(str::to_string))
+                    .set_tags(Some(role_tags(cost_tags)))
+                    .send()
+                    .await
+                    .map_err(|e| classify_aws_error(e.into_service_error()))?
+                    .role()
+                    .cloned()
+            }
+            Err(e) => return Err(classify_aws_error(e)),
+        };
+
+        role.and_then(|r| r.arn().map(str::to_string))
+            .ok_or_else(|| anyhow!("iam returned role {} without an arn", name))
+    }
+
+    // NOTE: putting a policy under a name the role already has replaces it
+    #[tracing::instrument(level = "info", skip(self, document))]
+    pub async fn put_role_policy(
+        &self,
+        role_name: &str,
+        policy_name: &str,
+        document: &str,
+    ) -> Result<()> {
+        fault_injection::inject("iam.put_role_policy").await?;
+        self.client
+            .put_role_policy()
+            .role_name(role_name)
+            .policy_name(policy_name)
+            .policy_document(document)
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
+        Ok(())
+    }
+
+    // Deletes the role's inline policies first, iam refuses to delete a role that still has any.
+    // A role that's already gone counts as deleted.
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn delete_role(&self, name: &str) -> Result<()> {
+        fault_injection::inject("iam.delete_role").await?;
+        let policies = self
+            .client
+            .list_role_policies()
+            .role_name(name)
+            .send()
+            .await
+            .map_err(|e| e.into_service_error());
+        let policies = match policies {
+            Ok(t) => t.policy_names().unwrap_or_default().to_vec(),
+            Err(e) if e.is_no_such_entity_exception() => return Ok(()),
+            Err(e) => return Err(classify_aws_error(e)),
+        };
+
+        for policy in policies {
+            self.client
+                .delete_role_policy()
+                .role_name(name)
+                .policy_name(&policy)
+                .send()
+                .await
+                .map_err(|e| classify_aws_error(e.into_service_error()))?;
+        }
+
+        let deleted = self
+            .client
+            .delete_role()
+            .role_name(name)
+            .send()
+            .await
+            .map_err(|e| e.into_service_error());
+        match deleted {
+            Err(e) if e.is_no_such_entity_exception() => Ok(()),
+            Err(e) => Err(classify_aws_error(e)),
+            Ok(_) => Ok(()),
+        }
+    }
+
+    async fn update_role(
+        &self,
+        name: &str,
+        trust_policy: &str,
+        permissions_boundary_arn: Option<&str>,
+        cost_tags: &BTreeMap<String, String>,
+    ) -> Result<()> {
+        fault_injection::inject("iam.update_role").await?;
+        self.client
+            .update_assume_role_policy()
+            .role_name(name)
+            .policy_document(trust_policy)
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
+        // NOTE: a boundary dropped from the config stays on roles created while it was set
+        if let Some(boundary) = permissions_boundary_arn {
+            self.client
+                .put_role_permissions_boundary()
+                .role_name(name)
+                .permissions_boundary(boundary)
+                .send()
+                .await
+                .map_err(|e| classify_aws_error(e.into_service_error()))?;
+        }
+
+        // NOTE: tags are only ever added, labels dropped from the descriptor stay on the role
+        self.client
+            .tag_role()
+            .role_name(name)
+            .set_tags(Some(role_tags(cost_tags)))
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
+        Ok(())
+    }
+}
+
+fn role_tags(cost_tags: &BTreeMap<String, String>) -> Vec<Tag> {
+    [("provisioner", "basin"), ("subprovisioner", "iam")]
+        .into_iter()
+        .chain(cost_tags.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+        .map(|(key, value)| Tag::builder().key(key).value(value).build())
+        .collect()
+}
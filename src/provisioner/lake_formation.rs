@@ -0,0 +1,277 @@
+use anyhow::Result;
+use aws_config::SdkConfig;
+use aws_sdk_lakeformation::{
+    model::{
+        AllRowsWildcard, ColumnWildcard, DataCellsFilter, DataCellsFilterResource,
+        DataLakePrincipal, DatabaseResource, Permission, Resource, RowFilter, TableResource,
+    },
+    Client,
+};
+use tracing::info;
+
+use super::{error::classify_aws_error, fault_injection};
+use crate::access_request_store::AccessPermission;
+
+// What a grant is made on, named as glue knows it
+#[derive(Debug)]
+pub enum GrantTarget {
+    Database { database: String },
+    Table { database: String, table: String },
+}
+
+// A data cells filter over every row and all but the excluded columns of a table
+#[derive(Debug)]
+pub struct ColumnFilter {
+    pub catalog_id: String,
+    pub database: String,
+    pub table: String,
+    pub name: String,
+}
+
+#[derive(Debug)]
+pub struct LakeFormationProvisioner {
+    client: Client,
+}
+
+impl LakeFormationProvisioner {
+    pub fn new(aws_conf: &SdkConfig) -> Self {
+        LakeFormationProvisioner {
+            client: Client::new(aws_conf),
+        }
+    }
+
+    // NOTE: granting what the principal already holds is a no-op, so this is safe to repeat
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn grant(
+        &self,
+        principal_arn: &str,
+        target: &GrantTarget,
+        permissions: &[AccessPermission],
+    ) -> Result<()> {
+        fault_injection::inject("lake_formation.grant").await?;
+        self.client
+            .grant_permissions()
+            .principal(
+                DataLakePrincipal::builder()
+                    .data_lake_principal_identifier(principal_arn)
+                    .build(),
+            )
+            .resource(grant_resource(target))
+            .set_permissions(Some(
+                permissions.iter().map(lake_formation_permission).collect(),
+            ))
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
+        Ok(())
+    }
+
+    // NOTE: lake formation rejects revoking what the principal doesn't hold, which counts as
+    //       revoked here
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn revoke(
+        &self,
+        principal_arn: &str,
+        target: &GrantTarget,
+        permissions: &[AccessPermission],
+    ) -> Result<()> {
+        fault_injection::inject("lake_formation.revoke").await?;
+        let revoked = self
+            .client
+            .revoke_permissions()
+            .principal(
+                DataLakePrincipal::builder()
+                    .data_lake_principal_identifier(principal_arn)
+                    .build(),
+            )
+            .resource(grant_resource(target))
+            .set_permissions(Some(
+                permissions.iter().map(lake_formation_permission).collect(),
+            ))
+            .send()
+            .await
+            .map_err(|e| e.into_service_error());
+
+        match revoked {
+            Ok(_) => Ok(()),
+            Err(e) if e.is_invalid_input_exception() => {
+                info!(?e, "nothing to revoke");
+                Ok(())
+            }
+            Err(e) => Err(classify_aws_error(e)),
+        }
+    }
+
+    // Filters can't be updated, one excluding the wrong columns is dropped and made again. Returns
+    // whether it was.
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn put_column_filter(
+        &self,
+        filter: &ColumnFilter,
+        excluded_columns: &[String],
+    ) -> Result<bool> {
+        fault_injection::inject("lake_formation.put_column_filter").await?;
+        if let Some(existing) = self.get_column_filter(filter).await? {
+            let mut current: Vec<&str> = existing
+                .column_wildcard()
+                .and_then(|w| w.excluded_column_names())
+                .unwrap_or_default()
+                .iter()
+                .map(String::as_str)
+                .collect();
+            let mut wanted: Vec<&str> = excluded_columns.iter().map(String::as_str).collect();
+            current.sort();
+            wanted.sort();
+            if current == wanted {
+                return Ok(false);
+            }
+
+            info!("data cells filter has drifted, making it again");
+            self.delete_column_filter(filter).await?;
+        }
+
+        self.client
+            .create_data_cells_filter()
+            .table_data(
+                DataCellsFilter::builder()
+                    .table_catalog_id(&filter.catalog_id)
+                    .database_name(&filter.database)
+                    .table_name(&filter.table)
+                    .name(&filter.name)
+                    .row_filter(
+                        RowFilter::builder()
+                            .all_rows_wildcard(AllRowsWildcard::builder().build())
+                            .build(),
+                    )
+                    .column_wildcard(
+                        ColumnWildcard::builder()
+                            .set_excluded_column_names(Some(excluded_columns.to_vec()))
+                            .build(),
+                    )
+                    .build(),
+            )
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
+        Ok(true)
+    }
+
+    // NOTE: a no-op when the filter is already gone
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn delete_column_filter(&self, filter: &ColumnFilter) -> Result<()> {
+        fault_injection::inject("lake_formation.delete_column_filter").await?;
+        if self.get_column_filter(filter).await?.is_none() {
+            return Ok(());
+        }
+
+        self.client
+            .delete_data_cells_filter()
+            .table_catalog_id(&filter.catalog_id)
+            .database_name(&filter.database)
+            .table_name(&filter.table)
+            .name(&filter.name)
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
+        Ok(())
+    }
+
+    // Select through the filter, the principal sees none of the columns it leaves out
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn grant_column_filter(
+        &self,
+        principal_arn: &str,
+        filter: &ColumnFilter,
+    ) -> Result<()> {
+        fault_injection::inject("lake_formation.grant_column_filter").await?;
+        self.client
+            .grant_permissions()
+            .principal(
+                DataLakePrincipal::builder()
+                    .data_lake_principal_identifier(principal_arn)
+                    .build(),
+            )
+            .resource(
+                Resource::builder()
+                    .data_cells_filter(
+                        DataCellsFilterResource::builder()
+                            .table_catalog_id(&filter.catalog_id)
+                            .database_name(&filter.database)
+                            .table_name(&filter.table)
+                            .name(&filter.name)
+                            .build(),
+                    )
+                    .build(),
+            )
+            .permissions(Permission::Select)
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
+        Ok(())
+    }
+
+    async fn get_column_filter(&self, filter: &ColumnFilter) -> Result<Option<DataCellsFilter>> {
+        let mut next_token: Option<String> = None;
+        loop {
+            let resp = self
+                .client
+                .list_data_cells_filter()
+                .table(
+                    TableResource::builder()
+                        .catalog_id(&filter.catalog_id)
+                        .database_name(&filter.database)
+                        .name(&filter.table)
+                        .build(),
+                )
+                .set_next_token(next_token.take())
+                .send()
+                .await
+                .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
+            if let Some(existing) = resp
+                .data_cells_filters()
+                .unwrap_or_default()
+                .iter()
+                .find(|f| f.name() == Some(filter.name.as_str()))
+            {
+                return Ok(Some(existing.clone()));
+            }
+
+            match resp.next_token() {
+                Some(t) => next_token = Some(t.to_string()),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+fn grant_resource(target: &GrantTarget) -> Resource {
+    match target {
+        GrantTarget::Database { database } => Resource::builder()
+            .database(DatabaseResource::builder().name(database).build())
+            .build(),
+        GrantTarget::Table { database, table } => Resource::builder()
+            .table(
+                TableResource::builder()
+                    .database_name(database)
+                    .name(table)
+                    .build(),
+            )
+            .build(),
+    }
+}
+
+fn lake_formation_permission(permission: &AccessPermission) -> Permission {
+    match permission {
+        AccessPermission::Select => Permission::Select,
+        AccessPermission::Insert => Permission::Insert,
+        AccessPermission::Delete => Permission::Delete,
+        AccessPermission::Describe => Permission::Describe,
+        AccessPermission::Alter => Permission::Alter,
+        AccessPermission::CreateTable => Permission::CreateTable,
+    }
+}
@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use aws_config::SdkConfig;
+use aws_sdk_redshiftdata::model::StatusString;
+use tokio::time::{sleep, Instant};
+use tracing::debug;
+
+use super::{error::classify_aws_error, fault_injection};
+use crate::config::RedshiftConf;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// Points redshift spectrum at basin's glue databases through external schemas, statements go
+// through the redshift data api so basin never holds a connection to the cluster
+#[derive(Debug)]
+pub struct RedshiftProvisioner {
+    redshift_data_client: aws_sdk_redshiftdata::Client,
+    conf: RedshiftConf,
+}
+
+impl RedshiftProvisioner {
+    pub fn new(aws_conf: &SdkConfig, conf: &RedshiftConf) -> Self {
+        RedshiftProvisioner {
+            redshift_data_client: aws_sdk_redshiftdata::Client::new(aws_conf),
+            conf: conf.clone(),
+        }
+    }
+
+    pub fn schema_name(&self, glue_database: &str) -> String {
+        format!("{}{}", self.conf.schema_prefix, glue_database)
+    }
+
+    // Spectrum reads tables straight from the glue catalog so the schema follows the database by
+    // itself once it exists. NOTE: an existing schema keeps the role it was created with.
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn put_external_schema(&self, glue_database: &str) -> Result<()> {
+        fault_injection::inject("redshift.put_external_schema").await?;
+        self.execute(&format!(
+            "CREATE EXTERNAL SCHEMA IF NOT EXISTS \"{}\" FROM DATA CATALOG DATABASE '{}' IAM_ROLE '{}'",
+            self.schema_name(glue_database),
+            glue_database,
+            self.conf.iam_role_arn
+        ))
+        .await
+    }
+
+    // Runs the statement to completion. Statements still running at the timeout are cancelled.
+    async fn execute(&self, sql: &str) -> Result<()> {
+        debug!(sql, "submitting redshift statement");
+        let mut request = self
+            .redshift_data_client
+            .execute_statement()
+            .sql(sql)
+            .database(&self.conf.database)
+            .set_cluster_identifier(self.conf.cluster_identifier.clone())
+            .set_workgroup_name(self.conf.workgroup_name.clone())
+            .set_secret_arn(self.conf.secret_arn.clone());
+        if self.conf.secret_arn.is_none() {
+            request = request.set_db_user(self.conf.db_user.clone());
+        }
+        let started = request
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+        let statement_id = started
+            .id()
+            .ok_or_else(|| anyhow!("redshift didn't return a statement id"))?;
+
+        let deadline = Instant::now() + Duration::from_secs(self.conf.timeout_secs);
+        loop {
+            let resp = self
+                .redshift_data_client
+                .describe_statement()
+                .id(statement_id)
+                .send()
+                .await
+                .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
+            match resp.status() {
+                Some(StatusString::Finished) => return Ok(()),
+                Some(StatusString::Failed) => {
+                    return Err(anyhow!(
+                        "redshift statement {} failed: {}",
+                        statement_id,
+                        resp.error().unwrap_or_default()
+                    ));
+                }
+                Some(StatusString::Aborted) => {
+                    return Err(anyhow!("redshift statement {} was aborted", statement_id));
+                }
+                status => debug!(statement_id, ?status, "waiting on redshift statement"),
+            }
+
+            if Instant::now() >= deadline {
+                // NOTE: best effort, the statement is abandoned either way
+                let _ = self
+                    .redshift_data_client
+                    .cancel_statement()
+                    .id(statement_id)
+                    .send()
+                    .await;
+                return Err(anyhow!(
+                    "redshift statement {} didn't finish within {}s",
+                    statement_id,
+                    self.conf.timeout_secs
+                ));
+            }
+            sleep(POLL_INTERVAL).await;
+        }
+    }
+}
@@ -1,13 +1,88 @@
-use anyhow::Result;
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
 use aws_config::SdkConfig;
 use aws_sdk_s3::{
-    error::{HeadBucketError, HeadBucketErrorKind},
-    model::{Tag, Tagging},
+    error::{GetObjectError, GetObjectErrorKind, HeadBucketError, HeadBucketErrorKind},
+    model::{
+        BucketLifecycleConfiguration, Event, ExpirationStatus, FilterRule, FilterRuleName,
+        LifecycleExpiration, LifecycleRule, LifecycleRuleFilter, NotificationConfiguration,
+        NotificationConfigurationFilter, QueueConfiguration, S3KeyFilter, Tag, Tagging,
+    },
+    output::GetBucketNotificationConfigurationOutput,
+    types::ByteStream,
     Client,
 };
+use chrono::{DateTime, TimeZone, Utc};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use super::{error::classify_aws_error, fault_injection};
+use crate::constants::DESCRIPTOR_HASH_KEY;
+
+pub const PATH_MARKER_FILE: &str = "_basin_metadata.json";
+
+// Pages of objects (1000 each) a freshness probe looks at before settling for what it's seen
+const MAX_PROBE_PAGES: usize = 10;
+
+// Copy sources are url encoded, slashes between the bucket and key segments are left as they are
+const COPY_SOURCE_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'/')
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+// Dropped at the root of every prefix basin provisions so we can tell whether a location is ours
+// (and which descriptor it belongs to) before pointing anything at it
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PathOwnershipMarker {
+    pub provisioner: String,
+    pub descriptor_id: String,
+    pub revision: Option<u32>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct S3Object {
+    pub key: String,
+    pub etag: String,
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+// Splits `s3://bucket/some/prefix` into its bucket and (slash trimmed) prefix
+pub fn split_s3_uri(uri: &str) -> Option<(String, String)> {
+    let (bucket, prefix) = uri.strip_prefix("s3://")?.split_once('/')?;
+    let prefix = prefix.trim_matches('/');
+    if bucket.is_empty() || prefix.is_empty() {
+        return None;
+    }
+    Some((bucket.to_string(), prefix.to_string()))
+}
 
 // TODO: consider if we'd need a database specific s3 provisioner
 
+// What the database controller needs from s3, the seam for running it against a fake
+#[cfg_attr(test, mockall::automock)]
+#[async_trait::async_trait]
+pub(crate) trait S3BucketClient: Send + Sync + std::fmt::Debug {
+    async fn bucket_exists(&self, name: &str) -> Result<bool>;
+    async fn create_bucket(
+        &self,
+        name: &str,
+        descriptor_hash: &str,
+        cost_tags: &BTreeMap<String, String>,
+    ) -> Result<()>;
+    // Only the descriptor hash and cost tags are touched, other tags on the bucket are kept
+    async fn update_bucket(
+        &self,
+        name: &str,
+        descriptor_hash: &str,
+        cost_tags: &BTreeMap<String, String>,
+    ) -> Result<()>;
+}
+
 #[derive(Debug)]
 pub struct S3Provisioner {
     s3_client: Client,
@@ -21,7 +96,498 @@ impl S3Provisioner {
     }
 
     #[tracing::instrument(level = "info", skip(self))]
-    pub async fn bucket_exists(&self, name: &str) -> Result<bool> {
+    pub async fn create_prefix(&self, bucket: &str, prefix: &str) -> Result<()> {
+        fault_injection::inject("s3.create_prefix").await?;
+        // NOTE: s3 has no real directories, a zero byte `prefix/` object is what the console
+        //       creates and is enough for tooling that lists the parent to see it
+        self.s3_client
+            .put_object()
+            .bucket(bucket)
+            .key(format!("{}/", prefix))
+            .body(ByteStream::from_static(b""))
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn get_path_marker(
+        &self,
+        bucket: &str,
+        prefix: &str,
+    ) -> Result<Option<PathOwnershipMarker>> {
+        fault_injection::inject("s3.get_path_marker").await?;
+        let key = format!("{}/{}", prefix, PATH_MARKER_FILE);
+        let get_resp = self
+            .s3_client
+            .get_object()
+            .bucket(bucket)
+            .key(&key)
+            .send()
+            .await
+            .map_err(|e| e.into_service_error());
+
+        let obj = match get_resp {
+            Ok(t) => t,
+            Err(GetObjectError {
+                kind: GetObjectErrorKind::NoSuchKey(_),
+                ..
+            }) => return Ok(None),
+            Err(e) => return Err(classify_aws_error(e)),
+        };
+        let bytes = obj
+            .body
+            .collect()
+            .await
+            .map_err(|e| anyhow!("failed to read marker {}: {:?}", key, e))?
+            .into_bytes();
+
+        Ok(Some(serde_json::from_slice(&bytes)?))
+    }
+
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn put_path_marker(
+        &self,
+        bucket: &str,
+        prefix: &str,
+        descriptor_id: &str,
+        revision: Option<u32>,
+    ) -> Result<()> {
+        fault_injection::inject("s3.put_path_marker").await?;
+        let marker = PathOwnershipMarker {
+            provisioner: String::from("basin"),
+            descriptor_id: descriptor_id.to_string(),
+            revision,
+            updated_at: Utc::now(),
+        };
+
+        self.s3_client
+            .put_object()
+            .bucket(bucket)
+            .key(format!("{}/{}", prefix, PATH_MARKER_FILE))
+            .content_type("application/json")
+            .body(ByteStream::from(serde_json::to_vec(&marker)?))
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn delete_path_marker(&self, bucket: &str, prefix: &str) -> Result<()> {
+        fault_injection::inject("s3.delete_path_marker").await?;
+        self.s3_client
+            .delete_object()
+            .bucket(bucket)
+            .key(format!("{}/{}", prefix, PATH_MARKER_FILE))
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
+        Ok(())
+    }
+
+    // Sends created objects under the prefix to the queue. A bucket's notifications are replaced
+    // as a whole, so whatever else is configured on it is carried over untouched.
+    // NOTE: concurrent updates to the same bucket drop each other's changes, callers hold the
+    //       bucket's lease around this and delete_prefix_notification
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn put_prefix_notification(
+        &self,
+        bucket: &str,
+        id: &str,
+        prefix: &str,
+        queue_arn: &str,
+    ) -> Result<()> {
+        fault_injection::inject("s3.put_prefix_notification").await?;
+        let wanted = QueueConfiguration::builder()
+            .id(id)
+            .queue_arn(queue_arn)
+            .events(Event::S3ObjectCreated)
+            .filter(
+                NotificationConfigurationFilter::builder()
+                    .key(
+                        S3KeyFilter::builder()
+                            .filter_rules(
+                                FilterRule::builder()
+                                    .name(FilterRuleName::Prefix)
+                                    .value(format!("{}/", prefix))
+                                    .build(),
+                            )
+                            .build(),
+                    )
+                    .build(),
+            )
+            .build();
+
+        let current = self.get_notifications(bucket).await?;
+        let queues = current.queue_configurations().unwrap_or_default();
+        if queues.contains(&wanted) {
+            return Ok(());
+        }
+        let mut queues: Vec<QueueConfiguration> = queues
+            .iter()
+            .filter(|q| q.id() != Some(id))
+            .cloned()
+            .collect();
+        queues.push(wanted);
+
+        self.put_notifications(bucket, &current, queues).await
+    }
+
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn delete_prefix_notification(&self, bucket: &str, id: &str) -> Result<()> {
+        fault_injection::inject("s3.delete_prefix_notification").await?;
+        let current = self.get_notifications(bucket).await?;
+        let queues = current.queue_configurations().unwrap_or_default();
+        if !queues.iter().any(|q| q.id() == Some(id)) {
+            return Ok(());
+        }
+        let queues = queues
+            .iter()
+            .filter(|q| q.id() != Some(id))
+            .cloned()
+            .collect();
+
+        self.put_notifications(bucket, &current, queues).await
+    }
+
+    async fn get_notifications(
+        &self,
+        bucket: &str,
+    ) -> Result<GetBucketNotificationConfigurationOutput> {
+        self.s3_client
+            .get_bucket_notification_configuration()
+            .bucket(bucket)
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))
+    }
+
+    async fn put_notifications(
+        &self,
+        bucket: &str,
+        current: &GetBucketNotificationConfigurationOutput,
+        queues: Vec<QueueConfiguration>,
+    ) -> Result<()> {
+        let notifications = NotificationConfiguration::builder()
+            .set_queue_configurations(Some(queues))
+            .set_topic_configurations(current.topic_configurations().map(|t| t.to_vec()))
+            .set_lambda_function_configurations(
+                current.lambda_function_configurations().map(|l| l.to_vec()),
+            )
+            .set_event_bridge_configuration(current.event_bridge_configuration().cloned())
+            .build();
+
+        self.s3_client
+            .put_bucket_notification_configuration()
+            .bucket(bucket)
+            .notification_configuration(notifications)
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
+        Ok(())
+    }
+
+    // Expires objects under the prefix after the given number of days, or stops expiring them when
+    // there's none. Like notifications, a bucket's lifecycle rules are replaced as a whole so the
+    // other rules on it are carried over.
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn put_prefix_expiration(
+        &self,
+        bucket: &str,
+        id: &str,
+        prefix: &str,
+        days: Option<u32>,
+    ) -> Result<()> {
+        fault_injection::inject("s3.put_prefix_expiration").await?;
+        let wanted = days.map(|days| {
+            LifecycleRule::builder()
+                .id(id)
+                .status(ExpirationStatus::Enabled)
+                .filter(LifecycleRuleFilter::Prefix(format!("{}/", prefix)))
+                .expiration(LifecycleExpiration::builder().days(days as i32).build())
+                .build()
+        });
+
+        let current = self.get_lifecycle_rules(bucket).await?;
+        if current.iter().find(|r| r.id() == Some(id)) == wanted.as_ref() {
+            return Ok(());
+        }
+        let mut rules: Vec<LifecycleRule> =
+            current.into_iter().filter(|r| r.id() != Some(id)).collect();
+        rules.extend(wanted);
+
+        // NOTE: s3 won't take an empty set of rules, the configuration has to go instead
+        if rules.is_empty() {
+            self.s3_client
+                .delete_bucket_lifecycle()
+                .bucket(bucket)
+                .send()
+                .await
+                .map_err(|e| classify_aws_error(e.into_service_error()))?;
+            return Ok(());
+        }
+        self.s3_client
+            .put_bucket_lifecycle_configuration()
+            .bucket(bucket)
+            .lifecycle_configuration(
+                BucketLifecycleConfiguration::builder()
+                    .set_rules(Some(rules))
+                    .build(),
+            )
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
+        Ok(())
+    }
+
+    async fn get_lifecycle_rules(&self, bucket: &str) -> Result<Vec<LifecycleRule>> {
+        match self
+            .s3_client
+            .get_bucket_lifecycle_configuration()
+            .bucket(bucket)
+            .send()
+            .await
+            .map_err(|e| e.into_service_error())
+        {
+            Ok(resp) => Ok(resp.rules().unwrap_or_default().to_vec()),
+            // NOTE: a bucket without any rules has no configuration at all
+            Err(e) if e.code() == Some("NoSuchLifecycleConfiguration") => Ok(vec![]),
+            Err(e) => Err(classify_aws_error(e)),
+        }
+    }
+
+    // Copies every object under one prefix to another, leaving the source untouched. Basin's own
+    // marker and the prefix placeholder are skipped as the destination has its own.
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn copy_prefix(
+        &self,
+        src_bucket: &str,
+        src_prefix: &str,
+        dst_bucket: &str,
+        dst_prefix: &str,
+    ) -> Result<usize> {
+        fault_injection::inject("s3.copy_prefix").await?;
+        let src_root = format!("{}/", src_prefix);
+
+        let mut copied = 0;
+        let mut continuation_token: Option<String> = None;
+        loop {
+            let resp = self
+                .s3_client
+                .list_objects_v2()
+                .bucket(src_bucket)
+                .prefix(&src_root)
+                .set_continuation_token(continuation_token.take())
+                .send()
+                .await
+                .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
+            for obj in resp.contents().unwrap_or_default() {
+                let Some(key) = obj.key() else {
+                    continue;
+                };
+                let relative = key.trim_start_matches(&src_root);
+                if relative.is_empty() || relative == PATH_MARKER_FILE {
+                    continue;
+                }
+
+                let copy_source =
+                    utf8_percent_encode(&format!("{}/{}", src_bucket, key), COPY_SOURCE_ENCODE_SET)
+                        .to_string();
+                self.s3_client
+                    .copy_object()
+                    .copy_source(copy_source)
+                    .bucket(dst_bucket)
+                    .key(format!("{}/{}", dst_prefix, relative))
+                    .send()
+                    .await
+                    .map_err(|e| classify_aws_error(e.into_service_error()))?;
+                copied += 1;
+            }
+
+            match resp.next_continuation_token() {
+                Some(t) => continuation_token = Some(t.to_string()),
+                None => break,
+            }
+        }
+
+        Ok(copied)
+    }
+
+    // When the newest object under the prefix was written, None for an empty prefix. Basin's own
+    // marker doesn't count as data. Bounded so it can run every interval on large tables: the
+    // first `partition_depth` levels are hive style partition directories and only the latest of
+    // each is descended into, and at most MAX_PROBE_PAGES of objects are looked at from there.
+    // NOTE: partitions are compared as strings, their values need to sort that way (zero padded
+    //       hours, iso dates)
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn last_modified(
+        &self,
+        bucket: &str,
+        prefix: &str,
+        partition_depth: usize,
+    ) -> Result<Option<DateTime<Utc>>> {
+        fault_injection::inject("s3.last_modified").await?;
+        let mut root = format!("{}/", prefix);
+        for _ in 0..partition_depth {
+            match self.latest_partition(bucket, &root).await? {
+                Some(partition) => root = partition,
+                // Objects written outside the partition layout are still data
+                None => break,
+            }
+        }
+
+        let mut latest: Option<DateTime<Utc>> = None;
+        let mut continuation_token: Option<String> = None;
+        for _ in 0..MAX_PROBE_PAGES {
+            let resp = self
+                .s3_client
+                .list_objects_v2()
+                .bucket(bucket)
+                .prefix(&root)
+                .set_continuation_token(continuation_token.take())
+                .send()
+                .await
+                .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
+            for obj in resp.contents().unwrap_or_default() {
+                let relative = obj.key().unwrap_or_default().trim_start_matches(&root);
+                if relative.is_empty() || relative == PATH_MARKER_FILE {
+                    continue;
+                }
+                let modified = obj
+                    .last_modified()
+                    .and_then(|t| Utc.timestamp_opt(t.secs(), t.subsec_nanos()).single());
+                if modified > latest {
+                    latest = modified;
+                }
+            }
+
+            match resp.next_continuation_token() {
+                Some(t) => continuation_token = Some(t.to_string()),
+                None => return Ok(latest),
+            }
+        }
+
+        warn!(
+            bucket,
+            prefix = %root,
+            "stopped probing after {} pages, the newest object may have been missed",
+            MAX_PROBE_PAGES
+        );
+        Ok(latest)
+    }
+
+    // The greatest `{column}=` directory directly under the prefix, with its trailing slash
+    async fn latest_partition(&self, bucket: &str, prefix: &str) -> Result<Option<String>> {
+        let mut latest: Option<String> = None;
+        let mut continuation_token: Option<String> = None;
+        loop {
+            let resp = self
+                .s3_client
+                .list_objects_v2()
+                .bucket(bucket)
+                .prefix(prefix)
+                .delimiter("/")
+                .set_continuation_token(continuation_token.take())
+                .send()
+                .await
+                .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
+            for partition in resp
+                .common_prefixes()
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|p| p.prefix())
+                .filter(|p| p.trim_start_matches(prefix).contains('='))
+            {
+                if latest.as_deref().map_or(true, |l| partition > l) {
+                    latest = Some(partition.to_string());
+                }
+            }
+
+            match resp.next_continuation_token() {
+                Some(t) => continuation_token = Some(t.to_string()),
+                None => return Ok(latest),
+            }
+        }
+    }
+
+    // Every object under the prefix, the prefix is taken as is
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn list_objects(&self, bucket: &str, prefix: &str) -> Result<Vec<S3Object>> {
+        fault_injection::inject("s3.list_objects").await?;
+
+        let mut objects = Vec::new();
+        let mut continuation_token: Option<String> = None;
+        loop {
+            let resp = self
+                .s3_client
+                .list_objects_v2()
+                .bucket(bucket)
+                .prefix(prefix)
+                .set_continuation_token(continuation_token.take())
+                .send()
+                .await
+                .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
+            for obj in resp.contents().unwrap_or_default() {
+                let (Some(key), Some(etag)) = (obj.key(), obj.e_tag()) else {
+                    continue;
+                };
+                objects.push(S3Object {
+                    key: key.to_string(),
+                    etag: etag.to_string(),
+                    last_modified: obj
+                        .last_modified()
+                        .and_then(|t| Utc.timestamp_opt(t.secs(), t.subsec_nanos()).single()),
+                });
+            }
+
+            match resp.next_continuation_token() {
+                Some(t) => continuation_token = Some(t.to_string()),
+                None => break,
+            }
+        }
+
+        Ok(objects)
+    }
+
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn get_object(&self, bucket: &str, key: &str) -> Result<Vec<u8>> {
+        fault_injection::inject("s3.get_object").await?;
+        let resp = self
+            .s3_client
+            .get_object()
+            .bucket(bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
+        let bytes = resp
+            .body
+            .collect()
+            .await
+            .map_err(|e| anyhow!("failed to read {}: {:?}", key, e))?
+            .into_bytes();
+
+        Ok(bytes.to_vec())
+    }
+}
+
+#[async_trait::async_trait]
+impl S3BucketClient for S3Provisioner {
+    #[tracing::instrument(level = "info", skip(self))]
+    async fn bucket_exists(&self, name: &str) -> Result<bool> {
+        fault_injection::inject("s3.bucket_exists").await?;
         let head_resp = self
             .s3_client
             .head_bucket()
@@ -36,12 +602,18 @@ impl S3Provisioner {
                 kind: HeadBucketErrorKind::NotFound(_),
                 ..
             }) => Ok(false),
-            Err(t) => Err(t.into()),
+            Err(t) => Err(classify_aws_error(t)),
         }
     }
 
     #[tracing::instrument(level = "info", skip(self))]
-    pub async fn create_bucket(&self, name: &str) -> Result<()> {
+    async fn create_bucket(
+        &self,
+        name: &str,
+        descriptor_hash: &str,
+        cost_tags: &BTreeMap<String, String>,
+    ) -> Result<()> {
+        fault_injection::inject("s3.create_bucket").await?;
         // FIXME: location contraint not being set means this needs to be in use1
         let create_bucket_resp = self
             .s3_client
@@ -52,7 +624,22 @@ impl S3Provisioner {
             .map_err(|e| e.into_service_error());
 
         if let Err(e) = create_bucket_resp && e.is_bucket_already_owned_by_you() {
-            return Err(e.into());
+            return Err(classify_aws_error(e));
+        }
+
+        let mut tagging = Tagging::builder()
+            // TODO: read all of this from config
+            .tag_set(Tag::builder().key("provisioner").value("basin").build())
+            .tag_set(Tag::builder().key("subprovisioner").value("s3").build())
+            .tag_set(Tag::builder().key("basin_version").value("0.0.1").build())
+            .tag_set(
+                Tag::builder()
+                    .key(DESCRIPTOR_HASH_KEY)
+                    .value(descriptor_hash)
+                    .build(),
+            );
+        for (key, value) in cost_tags {
+            tagging = tagging.tag_set(Tag::builder().key(key).value(value).build());
         }
 
         // NOTE: this will overwrite existing tags, its fine since we just created the bucket, and don't care about
@@ -60,24 +647,62 @@ impl S3Provisioner {
         self.s3_client
             .put_bucket_tagging()
             .bucket(name)
-            .tagging(
-                Tagging::builder()
-                    // TODO: read all of this from config
-                    .tag_set(Tag::builder().key("provisioner").value("basin").build())
-                    .tag_set(Tag::builder().key("subprovisioner").value("s3").build())
-                    .tag_set(Tag::builder().key("basin_version").value("0.0.1").build())
-                    .build(),
-            )
+            .tagging(tagging.build())
             .send()
             .await
-            .map_err(|e| e.into_service_error())?;
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
 
         Ok(())
     }
 
     #[tracing::instrument(level = "info", skip(self))]
-    pub async fn update_bucket(&self, name: &str) -> Result<()> {
-        // NOTE: no update operations support at the moment
+    async fn update_bucket(
+        &self,
+        name: &str,
+        descriptor_hash: &str,
+        cost_tags: &BTreeMap<String, String>,
+    ) -> Result<()> {
+        fault_injection::inject("s3.update_bucket").await?;
+        // NOTE: tagging is replaced wholesale, so keep whatever else is on the bucket
+        let tagging = self
+            .s3_client
+            .get_bucket_tagging()
+            .bucket(name)
+            .send()
+            .await
+            .map_err(|e| e.into_service_error());
+        let mut tags: Vec<Tag> = match tagging {
+            Ok(t) => t.tag_set().unwrap_or_default().to_vec(),
+            // NOTE: buckets without any tags answer with an error rather than an empty set
+            Err(e) if e.code() == Some("NoSuchTagSet") => vec![],
+            Err(e) => return Err(classify_aws_error(e)),
+        };
+        let mut wanted: BTreeMap<&str, &str> = cost_tags
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        wanted.insert(DESCRIPTOR_HASH_KEY, descriptor_hash);
+        if wanted.iter().all(|(k, v)| {
+            tags.iter()
+                .any(|t| t.key() == Some(*k) && t.value() == Some(*v))
+        }) {
+            return Ok(());
+        }
+
+        tags.retain(|t| !t.key().is_some_and(|k| wanted.contains_key(k)));
+        tags.extend(
+            wanted
+                .iter()
+                .map(|(k, v)| Tag::builder().key(*k).value(*v).build()),
+        );
+        self.s3_client
+            .put_bucket_tagging()
+            .bucket(name)
+            .tagging(Tagging::builder().set_tag_set(Some(tags)).build())
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
         Ok(())
     }
 }
@@ -0,0 +1,256 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use aws_config::SdkConfig;
+use thiserror::Error;
+use tracing::{info, warn};
+
+use super::error::classify_aws_error;
+use crate::{config::QuotasConf, metrics};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QuotaResource {
+    S3Buckets,
+    GlueDatabases,
+    GlueTables,
+}
+
+impl QuotaResource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QuotaResource::S3Buckets => "s3_buckets",
+            QuotaResource::GlueDatabases => "glue_databases",
+            QuotaResource::GlueTables => "glue_tables",
+        }
+    }
+
+    fn service_code(&self) -> &'static str {
+        match self {
+            QuotaResource::S3Buckets => "s3",
+            QuotaResource::GlueDatabases | QuotaResource::GlueTables => "glue",
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+#[error(
+    "creating this would exceed the {} quota ({} of {} in use), request an increase of service quota {}/{}",
+    .resource.as_str(), .usage, .limit, .resource.service_code(), .quota_code
+)]
+pub struct QuotaExceeded {
+    pub resource: QuotaResource,
+    pub quota_code: String,
+    pub usage: usize,
+    pub limit: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct QuotaUsage {
+    fetched_at: Instant,
+    limit: f64,
+    usage: usize,
+}
+
+// Checks a resource can still be created before basin tries to, so hitting a quota surfaces as
+// an error saying which quota to raise rather than whatever the service fails with. Checks are
+// skipped when quotas aren't configured or can't be looked up.
+#[derive(Debug)]
+pub struct QuotaChecker {
+    conf: Option<QuotasConf>,
+    service_quotas_client: aws_sdk_servicequotas::Client,
+    s3_client: aws_sdk_s3::Client,
+    glue_client: aws_sdk_glue::Client,
+    cache: Mutex<HashMap<QuotaResource, QuotaUsage>>,
+}
+
+impl QuotaChecker {
+    pub fn new(conf: Option<&QuotasConf>, aws_conf: &SdkConfig) -> Self {
+        QuotaChecker {
+            conf: conf.cloned(),
+            service_quotas_client: aws_sdk_servicequotas::Client::new(aws_conf),
+            s3_client: aws_sdk_s3::Client::new(aws_conf),
+            glue_client: aws_sdk_glue::Client::new(aws_conf),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn ensure_capacity(&self, resource: QuotaResource) -> Result<()> {
+        let Some(conf) = &self.conf else {
+            return Ok(());
+        };
+        let quota_code = match resource {
+            QuotaResource::S3Buckets => &conf.s3_buckets,
+            QuotaResource::GlueDatabases => &conf.glue_databases,
+            QuotaResource::GlueTables => &conf.glue_tables,
+        };
+        let Some(quota_code) = quota_code else {
+            return Ok(());
+        };
+
+        let cached = self
+            .cache
+            .lock()
+            .unwrap()
+            .get(&resource)
+            .copied()
+            .filter(|c| c.fetched_at.elapsed() < Duration::from_secs(conf.cache_secs));
+        let current = match cached {
+            Some(t) => t,
+            None => match self.fetch(resource, quota_code).await {
+                Ok(t) => t,
+                Err(e) => {
+                    warn!(?e, "failed to look up quota, skipping the check");
+                    return Ok(());
+                }
+            },
+        };
+
+        let labels = [("resource", resource.as_str())];
+        metrics::gauge_set("basin_quota_usage", &labels, current.usage as f64);
+        metrics::gauge_set("basin_quota_limit", &labels, current.limit);
+
+        if (current.usage + 1) as f64 > current.limit {
+            metrics::counter_inc("basin_quota_exceeded_total", &labels);
+            return Err(QuotaExceeded {
+                resource,
+                quota_code: quota_code.clone(),
+                usage: current.usage,
+                limit: current.limit,
+            }
+            .into());
+        }
+
+        // NOTE: counted as soon as it passes, so creates within the cache window add up
+        self.cache.lock().unwrap().insert(
+            resource,
+            QuotaUsage {
+                usage: current.usage + 1,
+                ..current
+            },
+        );
+        Ok(())
+    }
+
+    async fn fetch(&self, resource: QuotaResource, quota_code: &str) -> Result<QuotaUsage> {
+        let limit = self.quota_value(resource, quota_code).await?;
+        let usage = match resource {
+            QuotaResource::S3Buckets => self.count_buckets().await?,
+            QuotaResource::GlueDatabases => self.list_glue_databases().await?.len(),
+            QuotaResource::GlueTables => {
+                let mut tables = 0;
+                for database in self.list_glue_databases().await? {
+                    tables += self.count_glue_tables(&database).await?;
+                }
+                tables
+            }
+        };
+        info!(resource = resource.as_str(), usage, limit, "fetched quota");
+
+        Ok(QuotaUsage {
+            fetched_at: Instant::now(),
+            limit,
+            usage,
+        })
+    }
+
+    // The account's applied value, or aws' default when it was never changed
+    async fn quota_value(&self, resource: QuotaResource, quota_code: &str) -> Result<f64> {
+        let applied = self
+            .service_quotas_client
+            .get_service_quota()
+            .service_code(resource.service_code())
+            .quota_code(quota_code)
+            .send()
+            .await
+            .map_err(|e| e.into_service_error());
+        let quota = match applied {
+            Ok(t) => t.quota().cloned(),
+            Err(e) if e.is_no_such_resource_exception() => self
+                .service_quotas_client
+                .get_aws_default_service_quota()
+                .service_code(resource.service_code())
+                .quota_code(quota_code)
+                .send()
+                .await
+                .map_err(|e| classify_aws_error(e.into_service_error()))?
+                .quota()
+                .cloned(),
+            Err(e) => return Err(classify_aws_error(e)),
+        };
+
+        quota.and_then(|q| q.value()).ok_or_else(|| {
+            anyhow!(
+                "service quota {}/{} has no value",
+                resource.service_code(),
+                quota_code
+            )
+        })
+    }
+
+    async fn count_buckets(&self) -> Result<usize> {
+        let buckets = self
+            .s3_client
+            .list_buckets()
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+        Ok(buckets.buckets().map_or(0, |b| b.len()))
+    }
+
+    async fn list_glue_databases(&self) -> Result<Vec<String>> {
+        let mut databases = Vec::new();
+        let mut next_token: Option<String> = None;
+        loop {
+            let resp = self
+                .glue_client
+                .get_databases()
+                .set_next_token(next_token.take())
+                .send()
+                .await
+                .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
+            databases.extend(
+                resp.database_list()
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(|d| d.name().map(str::to_string)),
+            );
+
+            match resp.next_token() {
+                Some(t) => next_token = Some(t.to_string()),
+                None => break,
+            }
+        }
+
+        Ok(databases)
+    }
+
+    async fn count_glue_tables(&self, database: &str) -> Result<usize> {
+        let mut count = 0;
+        let mut next_token: Option<String> = None;
+        loop {
+            let resp = self
+                .glue_client
+                .get_tables()
+                .database_name(database)
+                .set_next_token(next_token.take())
+                .send()
+                .await
+                .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
+            count += resp.table_list().map_or(0, |t| t.len());
+
+            match resp.next_token() {
+                Some(t) => next_token = Some(t.to_string()),
+                None => break,
+            }
+        }
+
+        Ok(count)
+    }
+}
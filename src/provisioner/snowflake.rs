@@ -0,0 +1,348 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use super::{
+    column_types::snowflake_type,
+    error::{classify_http_error, classify_http_status},
+    fault_injection,
+};
+use crate::{
+    config::SnowflakeConf,
+    fluid::descriptor::table::{ColumnMasking, MaskingPolicy, TableColumnAttribute},
+    sql_validation::column_default,
+};
+
+// Seconds between checks on statements the sql api hands back before they've finished
+const STATEMENT_POLL_INTERVAL: u64 = 1;
+
+#[derive(Serialize, Debug)]
+struct StatementRequest<'a> {
+    statement: &'a str,
+    timeout: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    warehouse: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    role: Option<&'a str>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct StatementResponse {
+    #[serde(default)]
+    statement_handle: Option<String>,
+    // Every value comes back as a string, or null
+    #[serde(default)]
+    data: Vec<Vec<Option<String>>>,
+}
+
+// Runs statements against snowflake through its sql api
+#[derive(Debug)]
+pub struct SnowflakeProvisioner {
+    conf: SnowflakeConf,
+    http_client: reqwest::Client,
+}
+
+impl SnowflakeProvisioner {
+    pub fn new(conf: &SnowflakeConf) -> Self {
+        SnowflakeProvisioner {
+            conf: conf.clone(),
+            http_client: reqwest::Client::new(),
+        }
+    }
+
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn create_database(&self, name: &str, comment: &str) -> Result<()> {
+        fault_injection::inject("snowflake.create_database").await?;
+        self.execute(&format!("CREATE DATABASE IF NOT EXISTS {}", name))
+            .await?;
+        self.execute(&format!(
+            "ALTER DATABASE {} SET COMMENT = {}",
+            name,
+            quote_literal(comment)
+        ))
+        .await?;
+        self.execute(&format!(
+            "CREATE SCHEMA IF NOT EXISTS {}.{}",
+            name, self.conf.schema
+        ))
+        .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn drop_database(&self, name: &str) -> Result<()> {
+        fault_injection::inject("snowflake.drop_database").await?;
+        self.execute(&format!("DROP DATABASE IF EXISTS {}", name))
+            .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn table_columns(&self, database: &str, table: &str) -> Result<Option<Vec<String>>> {
+        fault_injection::inject("snowflake.table_columns").await?;
+        // NOTE: unquoted identifiers are stored upper cased
+        let rows = self
+            .execute(&format!(
+                "SELECT LOWER(column_name) FROM {}.information_schema.columns \
+                 WHERE table_schema = UPPER({}) AND table_name = UPPER({})",
+                database,
+                quote_literal(&self.conf.schema),
+                quote_literal(table)
+            ))
+            .await?;
+
+        let columns: Vec<String> = rows.into_iter().flatten().flatten().collect();
+        Ok(if columns.is_empty() {
+            None
+        } else {
+            Some(columns)
+        })
+    }
+
+    #[tracing::instrument(level = "info", skip(self, columns))]
+    pub async fn create_table(
+        &self,
+        database: &str,
+        table: &str,
+        comment: &str,
+        columns: &[TableColumnAttribute],
+    ) -> Result<()> {
+        fault_injection::inject("snowflake.create_table").await?;
+        let column_defs = columns
+            .iter()
+            .map(column_definition)
+            .collect::<Result<Vec<_>>>()?;
+        self.execute(&format!(
+            "CREATE TABLE IF NOT EXISTS {}.{}.{} ({}) COMMENT = {}",
+            database,
+            self.conf.schema,
+            table,
+            column_defs.join(", "),
+            quote_literal(comment)
+        ))
+        .await?;
+        Ok(())
+    }
+
+    // Brings an existing table in line with the descriptor. Columns are only ever added, dropping
+    // one loses its data so that's left to a human.
+    #[tracing::instrument(level = "info", skip(self, columns, existing))]
+    pub async fn update_table(
+        &self,
+        database: &str,
+        table: &str,
+        comment: &str,
+        columns: &[TableColumnAttribute],
+        existing: &[String],
+    ) -> Result<()> {
+        fault_injection::inject("snowflake.update_table").await?;
+        let qualified = format!("{}.{}.{}", database, self.conf.schema, table);
+        self.execute(&format!(
+            "ALTER TABLE {} SET COMMENT = {}",
+            qualified,
+            quote_literal(comment)
+        ))
+        .await?;
+
+        for column in columns.iter().filter(|c| !existing.contains(&c.name)) {
+            self.execute(&format!(
+                "ALTER TABLE {} ADD COLUMN {}",
+                qualified,
+                column_definition(column)?
+            ))
+            .await?;
+        }
+        Ok(())
+    }
+
+    // A masking policy per masked column, named after the table and column it's attached to.
+    // Columns which stop being masked have their policy detached, the policy itself is left.
+    #[tracing::instrument(level = "info", skip(self, columns))]
+    pub async fn put_masking_policies(
+        &self,
+        database: &str,
+        table: &str,
+        columns: &[TableColumnAttribute],
+    ) -> Result<()> {
+        fault_injection::inject("snowflake.put_masking_policies").await?;
+        let qualified = format!("{}.{}.{}", database, self.conf.schema, table);
+        let attached: Vec<String> = self
+            .execute(&format!(
+                "SELECT LOWER(ref_column_name) FROM TABLE({}.information_schema.policy_references(\
+                 ref_entity_name => {}, ref_entity_domain => 'table')) \
+                 WHERE policy_kind = 'MASKING_POLICY'",
+                database,
+                quote_literal(&qualified)
+            ))
+            .await?
+            .into_iter()
+            .flatten()
+            .flatten()
+            .collect();
+
+        for column in columns.iter() {
+            let is_attached = attached.contains(&column.name);
+            let Some(masking) = &column.masking else {
+                if is_attached {
+                    self.execute(&format!(
+                        "ALTER TABLE {} MODIFY COLUMN {} UNSET MASKING POLICY",
+                        qualified, column.name
+                    ))
+                    .await?;
+                }
+                continue;
+            };
+
+            let policy = format!(
+                "{}.{}.basin_mask_{}_{}",
+                database, self.conf.schema, table, column.name
+            );
+            let data_type = snowflake_type(&column.codec.kind);
+            let body = masking_body(masking);
+            self.execute(&format!(
+                "CREATE MASKING POLICY IF NOT EXISTS {} AS (val {}) RETURNS {} -> {}",
+                policy, data_type, data_type, body
+            ))
+            .await?;
+            self.execute(&format!(
+                "ALTER MASKING POLICY {} SET BODY -> {}",
+                policy, body
+            ))
+            .await?;
+            if !is_attached {
+                self.execute(&format!(
+                    "ALTER TABLE {} MODIFY COLUMN {} SET MASKING POLICY {}",
+                    qualified, column.name, policy
+                ))
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn drop_table(&self, database: &str, table: &str) -> Result<()> {
+        fault_injection::inject("snowflake.drop_table").await?;
+        self.execute(&format!(
+            "DROP TABLE IF EXISTS {}.{}.{}",
+            database, self.conf.schema, table
+        ))
+        .await?;
+        Ok(())
+    }
+
+    async fn execute(&self, statement: &str) -> Result<Vec<Vec<Option<String>>>> {
+        debug!(statement, "executing snowflake statement");
+        let resp = self
+            .http_client
+            .post(format!("{}/api/v2/statements", self.base_url()))
+            .bearer_auth(&self.conf.token)
+            .header(
+                "X-Snowflake-Authorization-Token-Type",
+                &self.conf.token_type,
+            )
+            .json(&StatementRequest {
+                statement,
+                timeout: self.conf.statement_timeout,
+                warehouse: self.conf.warehouse.as_deref(),
+                role: self.conf.role.as_deref(),
+            })
+            .send()
+            .await
+            .map_err(classify_http_error)?;
+
+        let (mut status, mut body) = Self::read_response(resp).await?;
+        // Statements still running when the request returns are polled until they finish
+        while status == StatusCode::ACCEPTED {
+            let handle = body
+                .statement_handle
+                .clone()
+                .ok_or_else(|| anyhow!("snowflake accepted a statement without a handle"))?;
+            tokio::time::sleep(Duration::from_secs(STATEMENT_POLL_INTERVAL)).await;
+
+            let resp = self
+                .http_client
+                .get(format!("{}/api/v2/statements/{}", self.base_url(), handle))
+                .bearer_auth(&self.conf.token)
+                .header(
+                    "X-Snowflake-Authorization-Token-Type",
+                    &self.conf.token_type,
+                )
+                .send()
+                .await
+                .map_err(classify_http_error)?;
+            (status, body) = Self::read_response(resp).await?;
+        }
+
+        Ok(body.data)
+    }
+
+    async fn read_response(resp: reqwest::Response) -> Result<(StatusCode, StatementResponse)> {
+        let status = resp.status();
+        let text = resp.text().await.map_err(classify_http_error)?;
+        if !status.is_success() {
+            return Err(classify_http_status(
+                status,
+                anyhow!("snowflake statement failed: {}", text),
+            ));
+        }
+
+        Ok((status, serde_json::from_str(&text)?))
+    }
+
+    fn base_url(&self) -> String {
+        format!("https://{}.snowflakecomputing.com", self.conf.account)
+    }
+}
+
+// NOTE: generated columns aren't supported by snowflake, the table controller rejects them
+fn column_definition(column: &TableColumnAttribute) -> Result<String> {
+    let default = match &column.default {
+        Some(d) => format!(" DEFAULT {}", column_default(d)?),
+        None => String::new(),
+    };
+    Ok(format!(
+        "{} {}{}{} COMMENT {}",
+        column.name,
+        snowflake_type(&column.codec.kind),
+        default,
+        if column.nullable { "" } else { " NOT NULL" },
+        quote_literal(&column.summary)
+    ))
+}
+
+// NOTE: hash and partial only apply to string columns, the table controller rejects the rest
+fn masking_body(masking: &ColumnMasking) -> String {
+    let masked = match masking.policy {
+        MaskingPolicy::Hash => "SHA2(val)".to_string(),
+        MaskingPolicy::Partial => format!(
+            "CONCAT(REPEAT('*', GREATEST(LENGTH(val) - {n}, 0)), RIGHT(val, {n}))",
+            n = masking.visible_chars
+        ),
+        MaskingPolicy::Null => "NULL".to_string(),
+    };
+    if masking.privileged_roles.is_empty() {
+        return masked;
+    }
+
+    // Unquoted role names are stored upper cased, which is what CURRENT_ROLE() returns
+    let roles: Vec<String> = masking
+        .privileged_roles
+        .iter()
+        .map(|r| quote_literal(&r.to_uppercase()))
+        .collect();
+    format!(
+        "CASE WHEN CURRENT_ROLE() IN ({}) THEN val ELSE {} END",
+        roles.join(", "),
+        masked
+    )
+}
+
+// Snowflake string literals treat backslashes as escapes as well as doubled quotes
+fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "''"))
+}
@@ -0,0 +1,340 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use aws_config::SdkConfig;
+use aws_sdk_eventbridge::model::{RuleState, Target};
+use aws_sdk_sfn::model::{StateMachineType, Tag};
+use serde::Serialize;
+use tracing::info;
+
+use super::{error::classify_aws_error, fault_injection};
+
+// Runs a glue job and waits for it to finish
+pub const GLUE_START_JOB_RUN_SYNC: &str = "arn:aws:states:::glue:startJobRun.sync";
+
+// Id of the target basin puts on the schedule rules it owns
+const SCHEDULE_TARGET_ID: &str = "basin";
+
+// Amazon States Language, only what basin generates
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct StateMachineDefinition {
+    pub comment: String,
+    pub start_at: String,
+    pub states: BTreeMap<String, State>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "Type")]
+pub enum State {
+    Task(TaskState),
+    Parallel(ParallelState),
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct TaskState {
+    pub resource: String,
+    pub parameters: serde_json::Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_seconds: Option<u64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub retry: Vec<Retrier>,
+    #[serde(flatten)]
+    pub transition: Transition,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct ParallelState {
+    pub branches: Vec<Branch>,
+    #[serde(flatten)]
+    pub transition: Transition,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct Branch {
+    pub start_at: String,
+    pub states: BTreeMap<String, State>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct Retrier {
+    pub error_equals: Vec<String>,
+    pub max_attempts: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval_seconds: Option<u64>,
+}
+
+#[derive(Serialize, Debug)]
+pub enum Transition {
+    Next(String),
+    End(bool),
+}
+
+#[derive(Debug)]
+pub struct StepFunctionsProvisioner {
+    sfn_client: aws_sdk_sfn::Client,
+    events_client: aws_sdk_eventbridge::Client,
+}
+
+impl StepFunctionsProvisioner {
+    pub fn new(aws_conf: &SdkConfig) -> Self {
+        StepFunctionsProvisioner {
+            sfn_client: aws_sdk_sfn::Client::new(aws_conf),
+            events_client: aws_sdk_eventbridge::Client::new(aws_conf),
+        }
+    }
+
+    // Creates or updates the state machine, returning its arn
+    #[tracing::instrument(level = "info", skip(self, definition))]
+    pub async fn put_state_machine(
+        &self,
+        name: &str,
+        definition: &StateMachineDefinition,
+        role_arn: &str,
+        tags: &BTreeMap<String, String>,
+    ) -> Result<String> {
+        fault_injection::inject("step_functions.put_state_machine").await?;
+        let definition = serde_json::to_string(definition)?;
+        let tags: Vec<Tag> = tags
+            .iter()
+            .map(|(k, v)| Tag::builder().key(k).value(v).build())
+            .collect();
+
+        if let Some(arn) = self.find_state_machine(name).await? {
+            self.sfn_client
+                .update_state_machine()
+                .state_machine_arn(&arn)
+                .definition(definition)
+                .role_arn(role_arn)
+                .send()
+                .await
+                .map_err(|e| classify_aws_error(e.into_service_error()))?;
+            self.sfn_client
+                .tag_resource()
+                .resource_arn(&arn)
+                .set_tags(Some(tags))
+                .send()
+                .await
+                .map_err(|e| classify_aws_error(e.into_service_error()))?;
+            return Ok(arn);
+        }
+
+        info!("state machine does not exist, creating it");
+        let created = self
+            .sfn_client
+            .create_state_machine()
+            .name(name)
+            .definition(definition)
+            .role_arn(role_arn)
+            .r#type(StateMachineType::Standard)
+            .set_tags(Some(tags))
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
+        created
+            .state_machine_arn()
+            .map(|arn| arn.to_string())
+            .ok_or_else(|| anyhow!("no arn returned for state machine '{}'", name))
+    }
+
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn delete_state_machine(&self, name: &str) -> Result<()> {
+        fault_injection::inject("step_functions.delete_state_machine").await?;
+        let Some(arn) = self.find_state_machine(name).await? else {
+            return Ok(());
+        };
+
+        self.sfn_client
+            .delete_state_machine()
+            .state_machine_arn(arn)
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+        Ok(())
+    }
+
+    // Starts an execution outside the state machine's schedule rules
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn start_execution(&self, name: &str) -> Result<()> {
+        fault_injection::inject("step_functions.start_execution").await?;
+        let arn = self
+            .find_state_machine(name)
+            .await?
+            .ok_or_else(|| anyhow!("state machine '{}' doesn't exist", name))?;
+
+        self.sfn_client
+            .start_execution()
+            .state_machine_arn(arn)
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+        Ok(())
+    }
+
+    // Glue jobs the state machine's deployed definition runs, empty if it doesn't exist
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn state_machine_jobs(&self, name: &str) -> Result<Vec<String>> {
+        fault_injection::inject("step_functions.state_machine_jobs").await?;
+        let Some(arn) = self.find_state_machine(name).await? else {
+            return Ok(vec![]);
+        };
+
+        let described = self
+            .sfn_client
+            .describe_state_machine()
+            .state_machine_arn(arn)
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+        let definition: serde_json::Value =
+            serde_json::from_str(described.definition().unwrap_or("{}"))?;
+
+        let mut jobs = vec![];
+        collect_job_names(&definition, &mut jobs);
+        Ok(jobs)
+    }
+
+    // NOTE: state machines can only be described by arn, which needs the account id
+    async fn find_state_machine(&self, name: &str) -> Result<Option<String>> {
+        let mut next_token = None;
+        loop {
+            let page = self
+                .sfn_client
+                .list_state_machines()
+                .set_next_token(next_token)
+                .send()
+                .await
+                .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
+            if let Some(found) = page
+                .state_machines()
+                .unwrap_or_default()
+                .iter()
+                .find(|m| m.name() == Some(name))
+            {
+                return Ok(found.state_machine_arn().map(|arn| arn.to_string()));
+            }
+
+            next_token = page.next_token().map(|t| t.to_string());
+            if next_token.is_none() {
+                return Ok(None);
+            }
+        }
+    }
+
+    // Starts the state machine on a schedule, `schedule` is an eventbridge `cron(...)` expression
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn put_schedule_rule(
+        &self,
+        name: &str,
+        schedule: &str,
+        state_machine_arn: &str,
+        role_arn: &str,
+    ) -> Result<()> {
+        fault_injection::inject("step_functions.put_schedule_rule").await?;
+        self.events_client
+            .put_rule()
+            .name(name)
+            .schedule_expression(schedule)
+            .state(RuleState::Enabled)
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
+        self.events_client
+            .put_targets()
+            .rule(name)
+            .targets(
+                Target::builder()
+                    .id(SCHEDULE_TARGET_ID)
+                    .arn(state_machine_arn)
+                    .role_arn(role_arn)
+                    .build(),
+            )
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn list_schedule_rules(&self, prefix: &str) -> Result<Vec<String>> {
+        fault_injection::inject("step_functions.list_schedule_rules").await?;
+        let mut rules = vec![];
+        let mut next_token = None;
+        loop {
+            let page = self
+                .events_client
+                .list_rules()
+                .name_prefix(prefix)
+                .set_next_token(next_token)
+                .send()
+                .await
+                .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
+            rules.extend(
+                page.rules()
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(|r| r.name().map(|n| n.to_string())),
+            );
+
+            next_token = page.next_token().map(|t| t.to_string());
+            if next_token.is_none() {
+                return Ok(rules);
+            }
+        }
+    }
+
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn delete_schedule_rule(&self, name: &str) -> Result<()> {
+        fault_injection::inject("step_functions.delete_schedule_rule").await?;
+        // NOTE: rules with targets can't be deleted
+        self.events_client
+            .remove_targets()
+            .rule(name)
+            .ids(SCHEDULE_TARGET_ID)
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
+        self.events_client
+            .delete_rule()
+            .name(name)
+            .send()
+            .await
+            .map_err(|e| classify_aws_error(e.into_service_error()))?;
+
+        Ok(())
+    }
+}
+
+// Task states run their job through `Parameters.JobName`, parallel states nest them in branches
+fn collect_job_names(value: &serde_json::Value, jobs: &mut Vec<String>) {
+    match value {
+        serde_json::Value::Object(fields) => {
+            if let Some(job) = fields
+                .get("Parameters")
+                .and_then(|p| p.get("JobName"))
+                .and_then(|j| j.as_str())
+            {
+                jobs.push(job.to_string());
+            }
+            for field in fields.values() {
+                collect_job_names(field, jobs);
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for item in items {
+                collect_job_names(item, jobs);
+            }
+        }
+        _ => {}
+    }
+}
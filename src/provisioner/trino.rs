@@ -0,0 +1,153 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use reqwest::{header::CONTENT_TYPE, Method};
+use serde::Deserialize;
+use tokio::time::Instant;
+use tracing::debug;
+
+use super::{
+    error::{classify_http_error, classify_http_status, PermanentFailure},
+    fault_injection,
+};
+use crate::config::TrinoConf;
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct QueryResults {
+    id: String,
+    #[serde(default)]
+    next_uri: Option<String>,
+    #[serde(default)]
+    error: Option<QueryError>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct QueryError {
+    message: String,
+    #[serde(default)]
+    error_name: String,
+    // USER_ERROR, INTERNAL_ERROR, INSUFFICIENT_RESOURCES or EXTERNAL
+    #[serde(default)]
+    error_type: String,
+}
+
+// Tells a trino coordinator about basin's databases and tables through the client REST protocol,
+// so they can be queried as soon as they're provisioned instead of once trino's metastore cache
+// catches up
+#[derive(Debug)]
+pub struct TrinoProvisioner {
+    conf: TrinoConf,
+    http_client: reqwest::Client,
+}
+
+impl TrinoProvisioner {
+    pub fn new(conf: &TrinoConf) -> Self {
+        TrinoProvisioner {
+            conf: conf.clone(),
+            http_client: reqwest::Client::new(),
+        }
+    }
+
+    // NOTE: a no-op when trino already sees the schema, its location is left as the catalog has it
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn register_schema(&self, schema: &str, location: &str) -> Result<()> {
+        fault_injection::inject("trino.register_schema").await?;
+        self.execute(&format!(
+            "CREATE SCHEMA IF NOT EXISTS \"{}\".\"{}\" WITH (location = '{}')",
+            self.conf.catalog, schema, location
+        ))
+        .await
+    }
+
+    // Drops what trino has cached of the table so its next query sees the current definition
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn refresh_table(&self, schema: &str, table: &str) -> Result<()> {
+        fault_injection::inject("trino.refresh_table").await?;
+        self.execute(&format!(
+            "CALL \"{}\".system.flush_metadata_cache(schema_name => '{}', table_name => '{}')",
+            self.conf.catalog, schema, table
+        ))
+        .await
+    }
+
+    // Submits the statement and follows its nextUri until trino is done with it. Statements still
+    // running at the timeout are cancelled.
+    async fn execute(&self, sql: &str) -> Result<()> {
+        debug!(sql, "submitting trino statement");
+        let deadline = Instant::now() + Duration::from_secs(self.conf.timeout_secs);
+        let mut results: QueryResults = self
+            .read(
+                self.request(Method::POST, &self.url("v1/statement"))
+                    .header(CONTENT_TYPE, "text/plain")
+                    .body(sql.to_string()),
+            )
+            .await?;
+
+        loop {
+            if let Some(error) = results.error {
+                let e = anyhow!(
+                    "trino query {} failed with {}: {}",
+                    results.id,
+                    error.error_name,
+                    error.message
+                );
+                return Err(if error.error_type == "USER_ERROR" {
+                    PermanentFailure(e).into()
+                } else {
+                    e
+                });
+            }
+            let Some(next_uri) = results.next_uri else {
+                return Ok(());
+            };
+
+            if Instant::now() >= deadline {
+                // NOTE: best effort, the statement is abandoned either way
+                let _ = self.request(Method::DELETE, &next_uri).send().await;
+                return Err(anyhow!(
+                    "trino query {} didn't finish within {}s",
+                    results.id,
+                    self.conf.timeout_secs
+                ));
+            }
+            results = self.read(self.request(Method::GET, &next_uri)).await?;
+        }
+    }
+
+    async fn read(&self, request: reqwest::RequestBuilder) -> Result<QueryResults> {
+        let resp = request.send().await.map_err(classify_http_error)?;
+
+        let status = resp.status();
+        let text = resp.text().await.map_err(classify_http_error)?;
+        if !status.is_success() {
+            return Err(classify_http_status(
+                status,
+                anyhow!("trino request failed: {}", text),
+            ));
+        }
+
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    fn request(&self, method: Method, url: &str) -> reqwest::RequestBuilder {
+        let request = self
+            .http_client
+            .request(method, url)
+            .header("X-Trino-User", &self.conf.user)
+            .header("X-Trino-Source", "basin");
+        match &self.conf.password {
+            Some(password) => request.basic_auth(&self.conf.user, Some(password)),
+            None => request,
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!(
+            "{}/{}",
+            self.conf.coordinator_url.trim_end_matches('/'),
+            path
+        )
+    }
+}
@@ -0,0 +1,257 @@
+use anyhow::{anyhow, Result};
+use reqwest::{Method, StatusCode};
+use serde::Deserialize;
+use serde_json::json;
+use tracing::{debug, info};
+
+use super::{
+    column_types::unity_type,
+    error::{classify_http_error, classify_http_status},
+    fault_injection,
+};
+use crate::{config::UnityCatalogConf, fluid::descriptor::table::TableColumnAttribute};
+
+#[derive(Deserialize, Debug)]
+struct UnityTable {
+    #[serde(default)]
+    storage_location: Option<String>,
+    #[serde(default)]
+    columns: Vec<UnityColumn>,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct UnityColumn {
+    name: String,
+    type_text: String,
+}
+
+// Mirrors basin's glue databases and tables into a databricks unity catalog as schemas and
+// external tables over the same s3 locations
+#[derive(Debug)]
+pub struct UnityCatalogProvisioner {
+    conf: UnityCatalogConf,
+    http_client: reqwest::Client,
+}
+
+impl UnityCatalogProvisioner {
+    pub fn new(conf: &UnityCatalogConf) -> Self {
+        UnityCatalogProvisioner {
+            conf: conf.clone(),
+            http_client: reqwest::Client::new(),
+        }
+    }
+
+    // Lets unity catalog read the bucket through the configured storage credential
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn put_external_location(&self, bucket: &str) -> Result<()> {
+        fault_injection::inject("unity_catalog.put_external_location").await?;
+        let name = format!("basin_{}", bucket.replace(['-', '.'], "_"));
+        let url = format!("s3://{}/", bucket);
+        let body = json!({
+            "name": name,
+            "url": url,
+            "credential_name": self.conf.storage_credential,
+            "comment": "managed by basin",
+        });
+
+        match self
+            .get::<serde_json::Value>(&format!("external-locations/{}", name))
+            .await?
+        {
+            None => {
+                info!(name, "external location does not exist, creating it");
+                self.send(Method::POST, "external-locations", &body).await?;
+            }
+            Some(_) => {
+                self.send(
+                    Method::PATCH,
+                    &format!("external-locations/{}", name),
+                    &json!({
+                        "url": url,
+                        "credential_name": self.conf.storage_credential,
+                    }),
+                )
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn put_schema(&self, schema: &str, comment: &str) -> Result<()> {
+        fault_injection::inject("unity_catalog.put_schema").await?;
+        let full_name = format!("{}.{}", self.conf.catalog, schema);
+
+        match self
+            .get::<serde_json::Value>(&format!("schemas/{}", full_name))
+            .await?
+        {
+            None => {
+                info!(full_name, "schema does not exist, creating it");
+                self.send(
+                    Method::POST,
+                    "schemas",
+                    &json!({
+                        "name": schema,
+                        "catalog_name": self.conf.catalog,
+                        "comment": comment,
+                    }),
+                )
+                .await?;
+            }
+            Some(_) => {
+                self.send(
+                    Method::PATCH,
+                    &format!("schemas/{}", full_name),
+                    &json!({ "comment": comment }),
+                )
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Registers an external table over `location`. Tables can't be altered through the api so
+    // one whose columns or location have drifted is dropped and registered again, which leaves
+    // the data alone as the table is external.
+    #[tracing::instrument(level = "info", skip(self, comment, columns))]
+    pub async fn put_external_table(
+        &self,
+        schema: &str,
+        table: &str,
+        comment: &str,
+        location: &str,
+        columns: &[TableColumnAttribute],
+    ) -> Result<()> {
+        fault_injection::inject("unity_catalog.put_external_table").await?;
+        let full_name = format!("{}.{}.{}", self.conf.catalog, schema, table);
+        let wanted: Vec<UnityColumn> = columns
+            .iter()
+            .map(|c| UnityColumn {
+                name: c.name.clone(),
+                type_text: unity_type(&c.codec.kind).1.to_string(),
+            })
+            .collect();
+
+        if let Some(existing) = self
+            .get::<UnityTable>(&format!("tables/{}", full_name))
+            .await?
+        {
+            if existing.columns == wanted
+                && existing
+                    .storage_location
+                    .as_deref()
+                    .map(|l| l.trim_end_matches('/'))
+                    == Some(location.trim_end_matches('/'))
+            {
+                debug!(full_name, "external table is up to date");
+                return Ok(());
+            }
+
+            info!(
+                full_name,
+                "external table has drifted, registering it again"
+            );
+            self.send(Method::DELETE, &format!("tables/{}", full_name), &json!({}))
+                .await?;
+        }
+
+        let columns: Vec<serde_json::Value> = columns
+            .iter()
+            .enumerate()
+            .map(|(position, c)| {
+                let (type_name, type_text) = unity_type(&c.codec.kind);
+                json!({
+                    "name": c.name,
+                    "type_name": type_name,
+                    "type_text": type_text,
+                    "type_json": json!({
+                        "name": c.name,
+                        "type": type_text,
+                        "nullable": c.nullable,
+                        "metadata": {},
+                    })
+                    .to_string(),
+                    "position": position,
+                    "nullable": c.nullable,
+                    "comment": c.summary,
+                })
+            })
+            .collect();
+
+        self.send(
+            Method::POST,
+            "tables",
+            &json!({
+                "name": table,
+                "catalog_name": self.conf.catalog,
+                "schema_name": schema,
+                "table_type": "EXTERNAL",
+                "data_source_format": self.conf.data_source_format,
+                "storage_location": location,
+                "comment": comment,
+                "columns": columns,
+            }),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get<T: for<'de> Deserialize<'de>>(&self, path: &str) -> Result<Option<T>> {
+        let resp = self
+            .http_client
+            .get(self.url(path))
+            .bearer_auth(&self.conf.token)
+            .send()
+            .await
+            .map_err(classify_http_error)?;
+
+        let status = resp.status();
+        if status == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let text = resp.text().await.map_err(classify_http_error)?;
+        if !status.is_success() {
+            return Err(classify_http_status(
+                status,
+                anyhow!("unity catalog request failed: {}", text),
+            ));
+        }
+
+        Ok(Some(serde_json::from_str(&text)?))
+    }
+
+    async fn send(&self, method: Method, path: &str, body: &serde_json::Value) -> Result<()> {
+        debug!(%method, path, "sending unity catalog request");
+        let resp = self
+            .http_client
+            .request(method, self.url(path))
+            .bearer_auth(&self.conf.token)
+            .json(body)
+            .send()
+            .await
+            .map_err(classify_http_error)?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            let text = resp.text().await.map_err(classify_http_error)?;
+            return Err(classify_http_status(
+                status,
+                anyhow!("unity catalog request failed: {}", text),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!(
+            "{}/api/2.1/unity-catalog/{}",
+            self.conf.workspace_url.trim_end_matches('/'),
+            path
+        )
+    }
+}
@@ -1,6 +1,359 @@
+use std::{sync::Mutex, time::Duration};
+
+use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use reqwest::{
+    header::{COOKIE, SET_COOKIE},
+    RequestBuilder, Response, StatusCode,
+};
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{error, info, warn};
+
+use super::{error::PermanentFailure, fault_injection};
+use crate::{config::WaterwheelConf, http_client};
+
+// Expanded by waterwheel in task args to the time the trigger fired
+pub const TRIGGER_DATETIME_PLACEHOLDER: &str = "{{ trigger_datetime }}";
+
+#[derive(Error, Debug)]
+pub enum WaterwheelError {
+    #[error("waterwheel rejected basin's credentials ({0})")]
+    Auth(StatusCode),
+    #[error("waterwheel rejected the request ({status}): {message}")]
+    Validation { status: StatusCode, message: String },
+    #[error("waterwheel reported a conflict: {0}")]
+    Conflict(String),
+    #[error("waterwheel is unavailable: {0}")]
+    Unavailable(String),
+    #[error("unexpected response from waterwheel ({status}): {message}")]
+    Unexpected { status: StatusCode, message: String },
+}
+
+impl WaterwheelError {
+    fn from_status(status: StatusCode, message: String) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => WaterwheelError::Auth(status),
+            StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY => {
+                WaterwheelError::Validation { status, message }
+            }
+            StatusCode::CONFLICT => WaterwheelError::Conflict(message),
+            // NOTE: 408 and 429 are client errors in name only, the same request can succeed later
+            StatusCode::REQUEST_TIMEOUT | StatusCode::TOO_MANY_REQUESTS => {
+                WaterwheelError::Unavailable(format!("{}: {}", status, message))
+            }
+            s if s.is_server_error() => WaterwheelError::Unavailable(format!("{}: {}", s, message)),
+            _ => WaterwheelError::Unexpected { status, message },
+        }
+    }
+
+    // Connection, timeout and body errors carry no status and are worth retrying
+    fn from_reqwest(e: reqwest::Error) -> Self {
+        match e.status() {
+            Some(status) => WaterwheelError::from_status(status, e.to_string()),
+            None => WaterwheelError::Unavailable(e.to_string()),
+        }
+    }
+
+    fn is_retryable(&self) -> bool {
+        matches!(self, WaterwheelError::Unavailable(_))
+    }
+
+    // Conflicts are left to the next reconcile, the job has usually settled by then
+    fn is_permanent(&self) -> bool {
+        match self {
+            WaterwheelError::Auth(_) | WaterwheelError::Validation { .. } => true,
+            WaterwheelError::Unexpected { status, .. } => status.is_client_error(),
+            WaterwheelError::Conflict(_) | WaterwheelError::Unavailable(_) => false,
+        }
+    }
+
+    fn classify(self) -> anyhow::Error {
+        if self.is_permanent() {
+            PermanentFailure(self.into()).into()
+        } else {
+            self.into()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WaterwheelJobRun {
+    pub trigger_datetime: DateTime<Utc>,
+    #[serde(default)]
+    pub state: String,
+}
+
+impl WaterwheelJobRun {
+    // Runs still waiting on or running tasks are in any other state
+    pub fn is_finished(&self) -> bool {
+        matches!(self.state.as_str(), "success" | "failure")
+    }
+}
+
+// What the flow controller needs from waterwheel, the seam for running it against a fake
+#[cfg_attr(test, mockall::automock)]
+#[async_trait::async_trait]
+pub(crate) trait WaterwheelClient: Send + Sync {
+    async fn get_job(&self, uuid: &str) -> Result<Option<WaterwheelJob>>;
+    // Creates the job or replaces the one with the same uuid
+    async fn put_job(&self, job: &WaterwheelJob) -> Result<()>;
+    // A job that's already gone counts as deleted
+    async fn delete_job(&self, uuid: &str) -> Result<()>;
+    async fn get_job_runs(&self, uuid: &str) -> Result<Vec<WaterwheelJobRun>>;
+    async fn set_paused(&self, uuid: &str, paused: bool) -> Result<()>;
+    async fn activate_token(
+        &self,
+        uuid: &str,
+        task: &str,
+        trigger_datetime: DateTime<Utc>,
+    ) -> Result<()>;
+}
+
+#[derive(Serialize)]
+struct WaterwheelCreds<'a> {
+    username: &'a str,
+    password: &'a str,
+}
+
+pub struct WaterwheelProvisioner {
+    conf: WaterwheelConf,
+    http_client: reqwest::Client,
+    // Session cookie from the last login, dropped once waterwheel stops accepting it
+    cookie: Mutex<Option<String>>,
+}
+
+impl WaterwheelProvisioner {
+    pub fn new(conf: &WaterwheelConf) -> Result<Self> {
+        Ok(WaterwheelProvisioner {
+            conf: conf.clone(),
+            http_client: http_client::shared(),
+            cookie: Mutex::new(None),
+        })
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.conf.url.trim_end_matches('/'), path)
+    }
+
+    async fn login(&self) -> Result<String, WaterwheelError> {
+        info!("Logging in to waterwheel");
+        let resp = self
+            .http_client
+            .post(self.url("login"))
+            .timeout(self.request_timeout())
+            .form(&WaterwheelCreds {
+                username: &self.conf.username,
+                password: &self.conf.password,
+            })
+            .send()
+            .await
+            .map_err(WaterwheelError::from_reqwest)?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            error!(status = status.as_u16(), "error logging into waterwheel");
+            let message = resp.text().await.unwrap_or_default();
+            return Err(WaterwheelError::from_status(status, message));
+        }
 
-#[derive(Serialize, Deserialize, Debug)]
+        resp.headers()
+            .get(SET_COOKIE)
+            .and_then(|c| c.to_str().ok())
+            .map(str::to_string)
+            .ok_or_else(|| WaterwheelError::Unexpected {
+                status,
+                message: "login response has no session cookie".to_string(),
+            })
+    }
+
+    async fn session(&self) -> Result<String, WaterwheelError> {
+        let cached = self.cookie.lock().unwrap().clone();
+        if let Some(cookie) = cached {
+            return Ok(cookie);
+        }
+        let cookie = self.login().await?;
+        *self.cookie.lock().unwrap() = Some(cookie.clone());
+        Ok(cookie)
+    }
+
+    // Sends the request with the session, logging in again once if the session has expired and
+    // retrying with backoff while waterwheel is unavailable. None when waterwheel answers 404.
+    async fn send<F>(&self, operation: &str, request: F) -> Result<Option<Response>>
+    where
+        F: Fn(&reqwest::Client) -> RequestBuilder + Send + Sync,
+    {
+        if self.cookie.lock().unwrap().is_none() {
+            fault_injection::inject("waterwheel.login").await?;
+        }
+        fault_injection::inject(operation).await?;
+
+        let mut attempt = 1;
+        let mut logged_in_again = false;
+        loop {
+            match self.send_once(&request).await {
+                Err(WaterwheelError::Auth(_)) if !logged_in_again => {
+                    logged_in_again = true;
+                    *self.cookie.lock().unwrap() = None;
+                }
+                Err(e) if e.is_retryable() && attempt < self.conf.max_attempts => {
+                    let delay = self.retry_delay(attempt);
+                    warn!(
+                        operation,
+                        attempt,
+                        ?delay,
+                        ?e,
+                        "waterwheel call failed, retrying"
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result.map_err(WaterwheelError::classify),
+            }
+        }
+    }
+
+    async fn send_once<F>(&self, request: &F) -> Result<Option<Response>, WaterwheelError>
+    where
+        F: Fn(&reqwest::Client) -> RequestBuilder + Send + Sync,
+    {
+        let cookie = self.session().await?;
+        let resp = request(&self.http_client)
+            .header(COOKIE, cookie)
+            .timeout(self.request_timeout())
+            .send()
+            .await
+            .map_err(WaterwheelError::from_reqwest)?;
+
+        let status = resp.status();
+        if status == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            let message = resp.text().await.unwrap_or_default();
+            return Err(WaterwheelError::from_status(status, message));
+        }
+        Ok(Some(resp))
+    }
+
+    fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.conf.request_timeout_secs)
+    }
+
+    fn retry_delay(&self, attempt: u32) -> Duration {
+        Duration::from_millis(
+            self.conf
+                .retry_delay_ms
+                .saturating_mul(1 << (attempt - 1).min(10)),
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl WaterwheelClient for WaterwheelProvisioner {
+    #[tracing::instrument(level = "info", skip(self))]
+    async fn get_job(&self, uuid: &str) -> Result<Option<WaterwheelJob>> {
+        let path = format!("api/jobs/{}", uuid);
+        let Some(resp) = self
+            .send("waterwheel.get_job", |c| c.get(self.url(&path)))
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        // NOTE: a job basin can't make sense of is treated as drifted and overwritten
+        match resp.json::<WaterwheelJob>().await {
+            Ok(job) => Ok(Some(job)),
+            Err(e) => {
+                warn!(?e, "could not parse job from waterwheel");
+                Ok(Some(WaterwheelJob::default()))
+            }
+        }
+    }
+
+    #[tracing::instrument(level = "info", skip(self, job), fields(uuid = %job.uuid))]
+    async fn put_job(&self, job: &WaterwheelJob) -> Result<()> {
+        let resp = self
+            .send("waterwheel.submit_job", |c| {
+                c.post(self.url("api/jobs")).json(job)
+            })
+            .await?;
+        if resp.is_none() {
+            return Err(WaterwheelError::Unexpected {
+                status: StatusCode::NOT_FOUND,
+                message: "job submission endpoint not found".to_string(),
+            }
+            .classify());
+        }
+
+        info!("Submitted job to waterwheel");
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "info", skip(self))]
+    async fn delete_job(&self, uuid: &str) -> Result<()> {
+        let path = format!("api/jobs/{}", uuid);
+        self.send("waterwheel.delete_job", |c| c.delete(self.url(&path)))
+            .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "info", skip(self))]
+    async fn get_job_runs(&self, uuid: &str) -> Result<Vec<WaterwheelJobRun>> {
+        let path = format!("api/jobs/{}/runs", uuid);
+        match self
+            .send("waterwheel.get_job_runs", |c| c.get(self.url(&path)))
+            .await?
+        {
+            Some(resp) => Ok(resp
+                .json()
+                .await
+                .map_err(|e| WaterwheelError::from_reqwest(e).classify())?),
+            None => Ok(vec![]),
+        }
+    }
+
+    // NOTE: waterwheel has no call of its own for this, the job is submitted again with the flag
+    //       flipped
+    #[tracing::instrument(level = "info", skip(self))]
+    async fn set_paused(&self, uuid: &str, paused: bool) -> Result<()> {
+        let Some(job) = self.get_job(uuid).await? else {
+            return Ok(());
+        };
+        if job.paused == paused {
+            return Ok(());
+        }
+        self.put_job(&WaterwheelJob { paused, ..job }).await
+    }
+
+    #[tracing::instrument(level = "info", skip(self))]
+    async fn activate_token(
+        &self,
+        uuid: &str,
+        task: &str,
+        trigger_datetime: DateTime<Utc>,
+    ) -> Result<()> {
+        let path = format!(
+            "api/jobs/{}/tasks/{}/tokens/{}",
+            uuid,
+            task,
+            trigger_datetime.to_rfc3339_opts(SecondsFormat::Secs, true)
+        );
+        let resp = self
+            .send("waterwheel.activate_token", |c| c.put(self.url(&path)))
+            .await?;
+        if resp.is_none() {
+            return Err(WaterwheelError::Unexpected {
+                status: StatusCode::NOT_FOUND,
+                message: format!("task {} of job {} not found", task, uuid),
+            }
+            .classify());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
 pub struct WaterwheelJob {
     pub uuid: String,
     pub project: String,
@@ -11,24 +364,56 @@ pub struct WaterwheelJob {
     pub tasks: Vec<WaterwheelTask>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+// Dependency on a task in another job, `/{project}/{job}/task/{task}`
+pub fn external_task_ref(project: &str, job: &str, task: &str) -> String {
+    format!("/{}/{}/task/{}", project, job, task)
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct WaterwheelTrigger {
     pub name: String,
     // FIXME: probably chrono
     pub start: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<String>,
     pub cron: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct WaterwheelTask {
     pub name: String,
     // FIXME: probably a enum
     pub docker: WaterwheelDockerTask,
     pub depends: Vec<String>,
+    // How many of `depends` must succeed before the task runs, all of them when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_delay: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct WaterwheelDockerTask {
     pub image: String,
     pub args: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resources: Option<WaterwheelResources>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct WaterwheelResources {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests: Option<WaterwheelResourceList>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limits: Option<WaterwheelResourceList>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct WaterwheelResourceList {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<String>,
 }
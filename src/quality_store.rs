@@ -0,0 +1,74 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::RedisConf, redis_connection::RedisConnector, redis_namespace::prefixed};
+
+// What a table's validation flow reported about its latest run
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QualityResult {
+    // Hash of the descriptor the suite was generated from, results against an older suite are
+    // kept but can be told apart
+    pub descriptor_hash: String,
+    pub succeeded: bool,
+    pub rules: Vec<QualityRuleResult>,
+    pub reported_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QualityRuleResult {
+    // Position of the rule in the table's quality rules
+    pub rule: usize,
+    pub succeeded: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unexpected_count: Option<u64>,
+}
+
+#[async_trait::async_trait]
+pub(crate) trait QualityStore {
+    async fn get_result(&self, table_id: &str) -> Result<Option<QualityResult>>;
+    async fn set_result(&self, table_id: &str, result: &QualityResult) -> Result<()>;
+}
+
+#[derive(Debug)]
+pub struct RedisQualityStore {
+    connector: RedisConnector,
+    key_prefix: String,
+}
+
+#[async_trait::async_trait]
+impl QualityStore for RedisQualityStore {
+    async fn get_result(&self, table_id: &str) -> Result<Option<QualityResult>> {
+        let mut conn = self.connector.get_connection().await?;
+        let result: Option<String> = conn.get(self.key(&format!("quality/{}", table_id))).await?;
+        Ok(match result {
+            Some(r) => Some(serde_json::from_str(&r)?),
+            None => None,
+        })
+    }
+
+    async fn set_result(&self, table_id: &str, result: &QualityResult) -> Result<()> {
+        let mut conn = self.connector.get_connection().await?;
+        let _: () = conn
+            .set(
+                self.key(&format!("quality/{}", table_id)),
+                serde_json::to_string(result)?,
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+impl RedisQualityStore {
+    pub async fn new(conf: &RedisConf) -> Result<Self> {
+        Ok(Self {
+            connector: RedisConnector::new(conf)?,
+            key_prefix: conf.key_prefix.clone(),
+        })
+    }
+
+    fn key(&self, key: &str) -> String {
+        prefixed(&self.key_prefix, key)
+    }
+}
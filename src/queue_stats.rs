@@ -0,0 +1,92 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::{
+    deployment_state_store::{DeploymentInfo, DeploymentState},
+    fluid::descriptor::DescriptorKind,
+};
+
+// Reconcile throughput is averaged over this long
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(300);
+
+// NOTE: both only cover this replica, like the metrics registry
+static RECONCILES: Mutex<BTreeMap<DescriptorKind, VecDeque<Instant>>> = Mutex::new(BTreeMap::new());
+static EVENT_WATCHER: Mutex<Option<EventWatcherLag>> = Mutex::new(None);
+
+#[derive(Serialize, Debug, Default)]
+pub struct QueueDepth {
+    // Stored descriptors yet to be deployed, including those waiting on a dependency
+    pub pending: usize,
+    // How long the longest waiting of them has been pending
+    pub oldest_pending_secs: Option<i64>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct EventWatcherLag {
+    // Messages waiting in the queue, as sqs approximates it
+    pub backlog: Option<u64>,
+    // Between the oldest message of the last receive being sent and it being received
+    pub lag_secs: Option<f64>,
+    pub observed_at: DateTime<Utc>,
+}
+
+// NOTE: descriptors without a state yet were stored and not picked up, they count as pending
+pub fn queue_depth(states: &[Option<DeploymentInfo>]) -> QueueDepth {
+    let now = Utc::now();
+    let mut depth = QueueDepth::default();
+    for info in states {
+        let since = match info {
+            None => None,
+            Some(info) if info.state == DeploymentState::Pending => {
+                info.history.last_transition.or(info.updated_at)
+            }
+            Some(_) => continue,
+        };
+        depth.pending += 1;
+        if let Some(since) = since {
+            let age = (now - since).num_seconds();
+            depth.oldest_pending_secs = Some(depth.oldest_pending_secs.map_or(age, |o| o.max(age)));
+        }
+    }
+    depth
+}
+
+pub fn record_reconcile(kind: DescriptorKind) {
+    let mut reconciles = RECONCILES.lock().unwrap();
+    let completed = reconciles.entry(kind).or_default();
+    let now = Instant::now();
+    completed.push_back(now);
+    while completed
+        .front()
+        .map_or(false, |t| now.duration_since(*t) > THROUGHPUT_WINDOW)
+    {
+        completed.pop_front();
+    }
+}
+
+pub fn reconciles_per_minute(kind: DescriptorKind) -> f64 {
+    let reconciles = RECONCILES.lock().unwrap();
+    let Some(completed) = reconciles.get(&kind) else {
+        return 0.0;
+    };
+    let now = Instant::now();
+    let recent = completed
+        .iter()
+        .filter(|t| now.duration_since(**t) <= THROUGHPUT_WINDOW)
+        .count();
+    recent as f64 / (THROUGHPUT_WINDOW.as_secs_f64() / 60.0)
+}
+
+pub fn record_event_watcher(lag: EventWatcherLag) {
+    *EVENT_WATCHER.lock().unwrap() = Some(lag);
+}
+
+pub fn event_watcher() -> Option<EventWatcherLag> {
+    EVENT_WATCHER.lock().unwrap().clone()
+}
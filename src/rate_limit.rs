@@ -0,0 +1,108 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::State,
+    http::{header::RETRY_AFTER, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tracing::warn;
+
+use crate::{api_tokens::TokenGrant, config::RateLimitsConf, metrics};
+
+// Bucket requests without the client header share
+const ANONYMOUS_CLIENT: &str = "anonymous";
+// Buckets untouched for this long would be full again, they're dropped once enough pile up
+const IDLE_BUCKET_TTL: Duration = Duration::from_secs(10 * 60);
+const MAX_BUCKETS: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+// Token buckets per client. Kept in memory, so with several replicas each holds clients to the
+// quota on its own.
+pub struct RateLimiter {
+    conf: Option<RateLimitsConf>,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(conf: Option<&RateLimitsConf>) -> Self {
+        RateLimiter {
+            conf: conf.cloned(),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Takes a token from the client's bucket, or says how long until there's one to take
+    fn acquire(&self, conf: &RateLimitsConf, client: &str) -> Result<(), Duration> {
+        let per_sec = conf.requests_per_minute_for(client) as f64 / 60.0;
+        let burst = conf.burst as f64;
+        let now = Instant::now();
+
+        let mut buckets = self.buckets.lock().expect("rate limiter poisoned");
+        if buckets.len() >= MAX_BUCKETS {
+            buckets.retain(|_, b| now.duration_since(b.refilled_at) < IDLE_BUCKET_TTL);
+        }
+        let bucket = buckets.entry(client.to_string()).or_insert(Bucket {
+            tokens: burst,
+            refilled_at: now,
+        });
+        bucket.tokens = (bucket.tokens
+            + now.duration_since(bucket.refilled_at).as_secs_f64() * per_sec)
+            .min(burst);
+        bucket.refilled_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / per_sec))
+        }
+    }
+}
+
+pub async fn limit_submits<B>(
+    State(limiter): State<Arc<RateLimiter>>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let Some(conf) = &limiter.conf else {
+        return next.run(req).await;
+    };
+    // NOTE: a token identifies the client, the header is only taken at its word without them
+    let client = match req.extensions().get::<TokenGrant>() {
+        Some(grant) => grant.name.as_str(),
+        None => req
+            .headers()
+            .get(conf.client_header.as_str())
+            .and_then(|v| v.to_str().ok())
+            .filter(|v| !v.is_empty())
+            .unwrap_or(ANONYMOUS_CLIENT),
+    };
+
+    match limiter.acquire(conf, client) {
+        Ok(()) => next.run(req).await,
+        Err(retry_after) => {
+            // NOTE: api keys are left out of logs and metrics, the path is enough to spot a loop
+            let path = req.uri().path();
+            warn!(path, ?retry_after, "client is over its submit rate limit");
+            metrics::counter_inc("basin_api_rate_limited_total", &[("path", path)]);
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(
+                    RETRY_AFTER,
+                    (retry_after.as_secs_f64().ceil() as u64).max(1).to_string(),
+                )],
+                "rate limit exceeded, retry later",
+            )
+                .into_response()
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use redis::Script;
+use uuid::Uuid;
+
+use crate::{
+    config::RedisConf, fluid::descriptor::DescriptorKind, redis_connection::RedisConnector,
+    redis_namespace::prefixed,
+};
+
+// Only the holder's token releases the lease, an expired lease may already be someone else's
+const UNLOCK_SCRIPT: &str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+    return redis.call("DEL", KEYS[1])
+end
+return 0
+"#;
+
+// Leases keeping two basin replicas from reconciling the same descriptor at once. Leases expire
+// on their own so a replica dying mid-reconcile doesn't hold on to the descriptor.
+#[async_trait::async_trait]
+pub(crate) trait ReconcileLockStore {
+    // The lease's token, None while another replica holds it
+    async fn try_lock(
+        &self,
+        kind: DescriptorKind,
+        id: &str,
+        ttl: Duration,
+    ) -> Result<Option<String>>;
+    async fn unlock(&self, kind: DescriptorKind, id: &str, token: &str) -> Result<()>;
+    // Leases on anything else only one replica may touch at once, e.g. a bucket's notifications
+    async fn try_lease(&self, name: &str, ttl: Duration) -> Result<Option<String>>;
+    async fn release(&self, name: &str, token: &str) -> Result<()>;
+}
+
+#[derive(Debug)]
+pub struct RedisReconcileLockStore {
+    connector: RedisConnector,
+    key_prefix: String,
+}
+
+#[async_trait::async_trait]
+impl ReconcileLockStore for RedisReconcileLockStore {
+    async fn try_lock(
+        &self,
+        kind: DescriptorKind,
+        id: &str,
+        ttl: Duration,
+    ) -> Result<Option<String>> {
+        self.acquire(&format!("reconcile-lock/{}/{}", kind, id), ttl)
+            .await
+    }
+
+    async fn unlock(&self, kind: DescriptorKind, id: &str, token: &str) -> Result<()> {
+        self.release_key(&format!("reconcile-lock/{}/{}", kind, id), token)
+            .await
+    }
+
+    async fn try_lease(&self, name: &str, ttl: Duration) -> Result<Option<String>> {
+        self.acquire(&format!("lease/{}", name), ttl).await
+    }
+
+    async fn release(&self, name: &str, token: &str) -> Result<()> {
+        self.release_key(&format!("lease/{}", name), token).await
+    }
+}
+
+impl RedisReconcileLockStore {
+    pub async fn new(conf: &RedisConf) -> Result<Self> {
+        Ok(Self {
+            connector: RedisConnector::new(conf)?,
+            key_prefix: conf.key_prefix.clone(),
+        })
+    }
+
+    async fn acquire(&self, key: &str, ttl: Duration) -> Result<Option<String>> {
+        let mut conn = self.connector.get_connection().await?;
+        let token = Uuid::new_v4().to_string();
+        let acquired: Option<String> = redis::cmd("SET")
+            .arg(self.key(key))
+            .arg(&token)
+            .arg("NX")
+            .arg("PX")
+            .arg(ttl.as_millis() as u64)
+            .query_async(&mut conn)
+            .await?;
+        Ok(acquired.map(|_| token))
+    }
+
+    async fn release_key(&self, key: &str, token: &str) -> Result<()> {
+        let mut conn = self.connector.get_connection().await?;
+        let _: i64 = Script::new(UNLOCK_SCRIPT)
+            .key(self.key(key))
+            .arg(token)
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    fn key(&self, key: &str) -> String {
+        prefixed(&self.key_prefix, key)
+    }
+}
@@ -0,0 +1,204 @@
+use anyhow::{bail, Result};
+use redis::{
+    aio::ConnectionLike,
+    cluster::ClusterClient,
+    cluster_async::ClusterConnection,
+    cluster_routing::{get_slot, Route, RoutingInfo, SingleNodeRoutingInfo, SlotAddr},
+    Arg, Cmd, Pipeline, RedisFuture, Value,
+};
+use tracing::warn;
+
+use crate::config::{RedisConf, RedisTopology};
+
+// Hands out connections for whichever redis topology basin is configured against. Stores grab a
+// fresh connection per operation so failover is handled by resolving the topology on connect.
+pub enum RedisConnector {
+    Single(redis::Client),
+    Sentinel {
+        sentinels: Vec<redis::Client>,
+        service_name: String,
+        tls: bool,
+        password: Option<String>,
+        db: i64,
+    },
+    Cluster(ClusterClient),
+}
+
+pub enum RedisConnection {
+    Single(redis::aio::Connection),
+    Cluster(ClusterConnection),
+}
+
+impl RedisConnector {
+    pub fn new(conf: &RedisConf) -> Result<Self> {
+        Ok(match &conf.topology {
+            RedisTopology::Single { url } => {
+                RedisConnector::Single(redis::Client::open(url.as_str())?)
+            }
+            RedisTopology::Sentinel {
+                service_name,
+                nodes,
+                tls,
+                password,
+                db,
+            } => {
+                if nodes.is_empty() {
+                    bail!("redis sentinel requires at least one sentinel node");
+                }
+                RedisConnector::Sentinel {
+                    sentinels: nodes
+                        .iter()
+                        .map(|n| redis::Client::open(n.as_str()))
+                        .collect::<Result<_, _>>()?,
+                    service_name: service_name.clone(),
+                    tls: *tls,
+                    password: password.clone(),
+                    db: *db,
+                }
+            }
+            RedisTopology::Cluster { nodes } => RedisConnector::Cluster(ClusterClient::new(
+                nodes.iter().map(|n| n.as_str()).collect(),
+            )?),
+        })
+    }
+
+    pub async fn get_connection(&self) -> Result<RedisConnection> {
+        Ok(match self {
+            RedisConnector::Single(client) => {
+                RedisConnection::Single(client.get_tokio_connection().await?)
+            }
+            RedisConnector::Sentinel { .. } => {
+                // NOTE: the master is looked up on every connection rather than cached so a
+                //       failover is picked up by the very next operation
+                let master = self.resolve_sentinel_master().await?;
+                RedisConnection::Single(master.get_tokio_connection().await?)
+            }
+            RedisConnector::Cluster(client) => {
+                RedisConnection::Cluster(client.get_async_connection().await?)
+            }
+        })
+    }
+
+    async fn resolve_sentinel_master(&self) -> Result<redis::Client> {
+        let RedisConnector::Sentinel {
+            sentinels,
+            service_name,
+            tls,
+            password,
+            db,
+        } = self
+        else {
+            bail!("not a sentinel connector");
+        };
+
+        for sentinel in sentinels {
+            let mut conn = match sentinel.get_tokio_connection().await {
+                Ok(t) => t,
+                Err(e) => {
+                    warn!(?e, "could not reach redis sentinel, trying the next one");
+                    continue;
+                }
+            };
+
+            let master: Option<(String, u16)> = match redis::cmd("SENTINEL")
+                .arg("get-master-addr-by-name")
+                .arg(service_name)
+                .query_async(&mut conn)
+                .await
+            {
+                Ok(t) => t,
+                Err(e) => {
+                    warn!(
+                        ?e,
+                        "redis sentinel failed to resolve master, trying the next one"
+                    );
+                    continue;
+                }
+            };
+
+            if let Some((host, port)) = master {
+                let scheme = if *tls { "rediss" } else { "redis" };
+                let auth = password
+                    .as_ref()
+                    .map(|p| format!(":{}@", p))
+                    .unwrap_or_default();
+                return Ok(redis::Client::open(format!(
+                    "{}://{}{}:{}/{}",
+                    scheme, auth, host, port, db
+                ))?);
+            }
+        }
+
+        bail!("no redis sentinel could resolve master `{}`", service_name)
+    }
+}
+
+impl std::fmt::Debug for RedisConnector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RedisConnector::Single(client) => f.debug_tuple("Single").field(client).finish(),
+            RedisConnector::Sentinel { service_name, .. } => f
+                .debug_struct("Sentinel")
+                .field("service_name", service_name)
+                .finish_non_exhaustive(),
+            RedisConnector::Cluster(_) => f.write_str("Cluster"),
+        }
+    }
+}
+
+impl ConnectionLike for RedisConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        match self {
+            RedisConnection::Single(conn) => conn.req_packed_command(cmd),
+            RedisConnection::Cluster(conn) => match keyspace_route(cmd) {
+                Some(routing) => Box::pin(conn.route_command(cmd, routing)),
+                None => conn.req_packed_command(cmd),
+            },
+        }
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        match self {
+            RedisConnection::Single(conn) => conn.req_packed_commands(cmd, offset, count),
+            RedisConnection::Cluster(conn) => conn.req_packed_commands(cmd, offset, count),
+        }
+    }
+
+    fn get_db(&self) -> i64 {
+        match self {
+            RedisConnection::Single(conn) => conn.get_db(),
+            RedisConnection::Cluster(conn) => conn.get_db(),
+        }
+    }
+}
+
+// Keyless commands go to a random node in cluster mode. Every basin key carries the prefix's hash
+// tag and so lives on a single slot, KEYS and SCAN over a tagged pattern are sent to its master
+// rather than coming back with whatever the random node happens to hold.
+fn keyspace_route(cmd: &Cmd) -> Option<RoutingInfo> {
+    let mut args = cmd.args_iter().filter_map(|arg| match arg {
+        Arg::Simple(t) => Some(t),
+        Arg::Cursor => None,
+    });
+    let name = args.next()?;
+    let pattern = if name.eq_ignore_ascii_case(b"KEYS") {
+        args.next()?
+    } else if name.eq_ignore_ascii_case(b"SCAN") {
+        args.skip_while(|arg| !arg.eq_ignore_ascii_case(b"MATCH"))
+            .nth(1)?
+    } else {
+        return None;
+    };
+    if !pattern.starts_with(b"{") {
+        return None;
+    }
+
+    Some(RoutingInfo::SingleNode(
+        SingleNodeRoutingInfo::SpecificNode(Route::new(get_slot(pattern), SlotAddr::Master)),
+    ))
+}
@@ -0,0 +1,156 @@
+use anyhow::Result;
+use redis::{aio::ConnectionLike, AsyncCommands};
+use tracing::{info, warn};
+
+use crate::{
+    config::{RedisConf, RedisTopology},
+    redis_connection::RedisConnector,
+};
+
+// Every key family basin writes, used when moving keys between prefixes
+const KEY_FAMILIES: &[&str] = &[
+    "descriptor/",
+    "descriptor-revisions/",
+    "name_index/",
+    "deployment-state/",
+    "event-record/",
+    "event-record-recent",
+    "event-dedup/",
+    "upstream-cache/",
+    "s3-source-objects",
+    "replay/",
+    "backfill/",
+    "bundle/",
+    "bundle-member/",
+    "freshness/",
+    "smoke-test/",
+    "quality/",
+    "shard-members",
+    "partition-repair/",
+    "iam-roles/",
+    "access-requests",
+    "approvals",
+    "api-tokens",
+    "reconcile-lock/",
+    "lease/",
+];
+
+pub fn prefixed(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}/{}", prefix, key)
+    }
+}
+
+// Walks the keyspace with SCAN from `cursor` until roughly `limit` keys matching `pattern` have been
+// found, returning them along with the cursor to resume from (0 once the scan is complete).
+// NOTE: SCAN hands back whole batches so a page can overshoot `limit`, dropping the extra keys
+//       would skip them entirely since the cursor has already moved past them
+pub async fn scan_page<C: ConnectionLike + Send>(
+    conn: &mut C,
+    pattern: &str,
+    mut cursor: u64,
+    limit: usize,
+) -> Result<(u64, Vec<String>)> {
+    let mut keys: Vec<String> = Vec::new();
+    loop {
+        let (next_cursor, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg(pattern)
+            .arg("COUNT")
+            .arg(limit.max(10))
+            .query_async(conn)
+            .await?;
+        keys.extend(batch);
+        cursor = next_cursor;
+
+        if cursor == 0 || keys.len() >= limit {
+            return Ok((cursor, keys));
+        }
+    }
+}
+
+// Renames every basin key under `from_prefix` to live under the configured prefix. Keys which
+// already exist under the target prefix are left alone (and reported) rather than clobbered.
+// NOTE: in cluster mode the prefixes are different hash tags and so different slots, which RENAME
+//       can't cross. Keys are copied with DUMP/RESTORE and the source deleted after, so nothing
+//       should be writing to the source prefix while it's migrated.
+pub async fn migrate_prefix(conf: &RedisConf, from_prefix: &str) -> Result<usize> {
+    let to_prefix = conf.key_prefix.as_str();
+    let cluster = matches!(conf.topology, RedisTopology::Cluster { .. });
+    let mut conn = RedisConnector::new(conf)?.get_connection().await?;
+
+    let source_root = prefixed(from_prefix, "");
+    let mut migrated = 0;
+    for family in KEY_FAMILIES {
+        let source_family = prefixed(from_prefix, family);
+
+        let mut keys: Vec<String> = Vec::new();
+        {
+            let mut iter = conn
+                .scan_match::<_, String>(format!("{}*", source_family))
+                .await?;
+            while let Some(k) = iter.next_item().await {
+                keys.push(k);
+            }
+        }
+
+        for key in keys {
+            let Some(unprefixed) = key.strip_prefix(&source_root) else {
+                continue;
+            };
+            let target = prefixed(to_prefix, unprefixed);
+
+            let renamed = if cluster {
+                move_across_slots(&mut conn, &key, &target).await?
+            } else {
+                conn.rename_nx(&key, &target).await?
+            };
+            if renamed {
+                migrated += 1;
+            } else {
+                warn!(
+                    key,
+                    target, "target key already exists, leaving source key in place"
+                );
+            }
+        }
+        info!(family, "migrated key family");
+    }
+
+    Ok(migrated)
+}
+
+// RENAMENX for keys in different slots, false when the target already exists. Keeps the source's
+// expiry, a key which vanished since it was listed counts as moved.
+async fn move_across_slots<C: ConnectionLike + Send>(
+    conn: &mut C,
+    key: &str,
+    target: &str,
+) -> Result<bool> {
+    let dump: Option<Vec<u8>> = redis::cmd("DUMP").arg(key).query_async(conn).await?;
+    let Some(dump) = dump else {
+        return Ok(true);
+    };
+    // -1 for keys without an expiry, which RESTORE takes as 0, and -2 once the key has expired
+    let ttl_ms: i64 = redis::cmd("PTTL").arg(key).query_async(conn).await?;
+    if ttl_ms == -2 {
+        return Ok(true);
+    }
+
+    let restored: redis::RedisResult<()> = redis::cmd("RESTORE")
+        .arg(target)
+        .arg(ttl_ms.max(0))
+        .arg(dump)
+        .query_async(conn)
+        .await;
+    match restored {
+        Ok(()) => {}
+        Err(e) if e.code() == Some("BUSYKEY") => return Ok(false),
+        Err(e) => return Err(e.into()),
+    }
+    let _: i64 = conn.del(key).await?;
+    Ok(true)
+}
@@ -0,0 +1,71 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::RedisConf, deployment_state_store::DeploymentState, fluid::descriptor::DescriptorKind,
+    redis_connection::RedisConnector, redis_namespace::prefixed,
+};
+
+// Replays are only interesting while they're in flight
+const REPLAY_TTL_SECS: usize = 7 * 24 * 60 * 60;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReplayRecord {
+    pub replay_id: String,
+    pub started_at: DateTime<Utc>,
+    pub kinds: Vec<DescriptorKind>,
+    pub state: Option<DeploymentState>,
+    pub descriptor_ids: Vec<String>,
+}
+
+#[async_trait::async_trait]
+pub(crate) trait ReplayStore {
+    async fn put_replay(&self, record: &ReplayRecord) -> Result<()>;
+    async fn get_replay(&self, replay_id: &str) -> Result<Option<ReplayRecord>>;
+}
+
+#[derive(Debug)]
+pub struct RedisReplayStore {
+    connector: RedisConnector,
+    key_prefix: String,
+}
+
+#[async_trait::async_trait]
+impl ReplayStore for RedisReplayStore {
+    async fn put_replay(&self, record: &ReplayRecord) -> Result<()> {
+        let mut conn = self.connector.get_connection().await?;
+        let _: () = conn
+            .set_ex(
+                self.key(&format!("replay/{}", record.replay_id)),
+                serde_json::to_string(record)?,
+                REPLAY_TTL_SECS,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn get_replay(&self, replay_id: &str) -> Result<Option<ReplayRecord>> {
+        let mut conn = self.connector.get_connection().await?;
+        let record: Option<String> = conn.get(self.key(&format!("replay/{}", replay_id))).await?;
+        Ok(if let Some(t) = record {
+            Some(serde_json::from_str(&t)?)
+        } else {
+            None
+        })
+    }
+}
+
+impl RedisReplayStore {
+    pub async fn new(conf: &RedisConf) -> Result<Self> {
+        Ok(Self {
+            connector: RedisConnector::new(conf)?,
+            key_prefix: conf.key_prefix.clone(),
+        })
+    }
+
+    fn key(&self, key: &str) -> String {
+        prefixed(&self.key_prefix, key)
+    }
+}
@@ -0,0 +1,300 @@
+use std::{collections::HashSet, path::Path, time::Duration};
+
+use anyhow::{ensure, Result};
+use chrono::Utc;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use tokio::time::{interval, MissedTickBehavior};
+use tracing::{error, info, warn};
+
+use crate::{
+    approval_gate::{Admission, ApprovalGate},
+    config::{BasinConfig, ControllersConf, EventWatcherConf, LimitsConf, S3SourceConf},
+    constants::CONTROLLER_DISABLED,
+    deployment_state_store::{
+        DeploymentHistory, DeploymentInfo, DeploymentState, DeploymentStateStore,
+        RedisDeploymentStateStore,
+    },
+    descriptor_store::{DescriptorStore, RedisDescriptorStore},
+    fluid::{
+        descriptor::{
+            database::DatabaseDescriptor, flow::FlowDescriptor,
+            landing_zone::LandingZoneDescriptor, table::TableDescriptor, DescriptorKind,
+            IdentifiableDescriptor,
+        },
+        encoding::{self, PayloadFormat},
+    },
+    metrics,
+    payload_limits::PayloadLimited,
+    policy::{PolicyChecker, PolicyDenied},
+    provisioner::s3::{S3Object, S3Provisioner},
+    s3_source_store::{ObjectOutcome, ObjectStatus, RedisS3SourceStore, S3SourceStore},
+    state_events::{StateEventPublisher, StateEventType},
+};
+
+// Polls a bucket for descriptors, laid out as `<prefix>/<kind>/**/*.{json,yaml,yml}` like git
+// sync's. Objects are read when their etag changes and stored if they differ from what's stored,
+// objects which are removed leave their descriptor in place.
+pub struct S3Source {
+    conf: S3SourceConf,
+    s3_provisioner: S3Provisioner,
+    s3_source_store: RedisS3SourceStore,
+    descriptor_store: RedisDescriptorStore,
+    deployment_state_store: RedisDeploymentStateStore,
+    limits: LimitsConf,
+    controllers: ControllersConf,
+    event_watcher: EventWatcherConf,
+    state_event_publisher: StateEventPublisher,
+    policy_checker: Option<PolicyChecker>,
+    approval_gate: ApprovalGate,
+}
+
+#[derive(Debug, Default)]
+struct PollSummary {
+    stored: usize,
+    unchanged: usize,
+    awaiting_approval: usize,
+    invalid: usize,
+    failed: usize,
+}
+
+impl S3Source {
+    pub async fn new(conf: &BasinConfig) -> Result<Option<Self>> {
+        let Some(s3_source) = &conf.s3_source else {
+            return Ok(None);
+        };
+
+        Ok(Some(S3Source {
+            conf: s3_source.clone(),
+            s3_provisioner: S3Provisioner::new(&conf.aws_creds),
+            s3_source_store: RedisS3SourceStore::new(&conf.redis).await?,
+            descriptor_store: RedisDescriptorStore::new(&conf.redis).await?,
+            deployment_state_store: RedisDeploymentStateStore::new(&conf.redis).await?,
+            limits: conf.limits.clone(),
+            controllers: conf.controllers.clone(),
+            event_watcher: conf.event_watcher.clone(),
+            state_event_publisher: StateEventPublisher::new(conf),
+            policy_checker: PolicyChecker::new(conf)?,
+            approval_gate: ApprovalGate::new(conf).await?,
+        }))
+    }
+
+    pub async fn poll_loop(&self) -> ! {
+        let mut ticker = interval(Duration::from_secs(self.conf.interval_secs));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+
+            info!(bucket = self.conf.bucket, "Polling s3 for descriptors");
+            match self.poll().await {
+                Ok(summary) => {
+                    metrics::counter_inc("basin_s3_source_polls_total", &[("outcome", "ok")]);
+                    info!(?summary, "finished polling s3 for descriptors");
+                }
+                Err(e) => {
+                    metrics::counter_inc("basin_s3_source_polls_total", &[("outcome", "error")]);
+                    error!(?e, "error when polling s3 for descriptors");
+                }
+            }
+        }
+    }
+
+    async fn poll(&self) -> Result<PollSummary> {
+        let root = self.conf.prefix.trim_matches('/');
+        let mut summary = PollSummary::default();
+        let mut in_bucket = HashSet::new();
+        for kind in DescriptorKind::ALL {
+            if !self.event_watcher.ingests(kind) {
+                continue;
+            }
+            let prefix = if root.is_empty() {
+                format!("{}/", kind)
+            } else {
+                format!("{}/{}/", root, kind)
+            };
+
+            let objects = self
+                .s3_provisioner
+                .list_objects(&self.conf.bucket, &prefix)
+                .await?;
+            for object in objects.iter() {
+                if PayloadFormat::from_path(Path::new(&object.key)).is_none() {
+                    continue;
+                }
+                in_bucket.insert(object.key.clone());
+
+                let result = match kind {
+                    DescriptorKind::Database => {
+                        self.poll_object::<DatabaseDescriptor>(object).await
+                    }
+                    DescriptorKind::Table => self.poll_object::<TableDescriptor>(object).await,
+                    DescriptorKind::Flow => self.poll_object::<FlowDescriptor>(object).await,
+                    DescriptorKind::LandingZone => {
+                        self.poll_object::<LandingZoneDescriptor>(object).await
+                    }
+                };
+                match result {
+                    Ok(Some(ObjectOutcome::Stored)) => summary.stored += 1,
+                    Ok(Some(ObjectOutcome::Invalid)) => summary.invalid += 1,
+                    Ok(Some(ObjectOutcome::AwaitingApproval)) => summary.awaiting_approval += 1,
+                    Ok(Some(ObjectOutcome::Unchanged) | None) => summary.unchanged += 1,
+                    // NOTE: its status isn't updated, the object is read again on the next poll
+                    Err(e) => {
+                        warn!(key = object.key, ?e, "failed to ingest descriptor object");
+                        summary.failed += 1;
+                    }
+                }
+            }
+        }
+
+        for key in self.s3_source_store.list_statuses().await?.into_keys() {
+            if !in_bucket.contains(&key) {
+                info!(
+                    key,
+                    "descriptor object is gone, its descriptor is left in place"
+                );
+                self.s3_source_store.delete_status(&key).await?;
+            }
+        }
+
+        Ok(summary)
+    }
+
+    // Ingests the object if its etag changed since it was last read, returning how that went
+    async fn poll_object<
+        Descriptor: IdentifiableDescriptor + PayloadLimited + Serialize + DeserializeOwned + Sync,
+    >(
+        &self,
+        object: &S3Object,
+    ) -> Result<Option<ObjectOutcome>> {
+        if let Some(status) = self.s3_source_store.get_status(&object.key).await?
+            && status.etag == object.etag
+        {
+            return Ok(None);
+        }
+
+        let contents = self
+            .s3_provisioner
+            .get_object(&self.conf.bucket, &object.key)
+            .await?;
+        let mut parsed = self.parse::<Descriptor>(object, &contents);
+        if let Ok(descriptor) = &parsed
+            && let Some(policy_checker) = &self.policy_checker
+            && let Err(e) = policy_checker.check(descriptor).await
+        {
+            // NOTE: only a denial sticks, the policy being unreachable is retried on the next poll
+            if !e.is::<PolicyDenied>() {
+                return Err(e);
+            }
+            parsed = Err(e);
+        }
+        let (outcome, descriptor_id, error) = match parsed {
+            Ok(descriptor) => {
+                let outcome = self.ingest(object, &descriptor).await?;
+                (outcome, Some(descriptor.id()), None)
+            }
+            Err(e) => {
+                warn!(key = object.key, ?e, "invalid descriptor object");
+                (ObjectOutcome::Invalid, None, Some(format!("{:#}", e)))
+            }
+        };
+
+        self.s3_source_store
+            .set_status(
+                &object.key,
+                &ObjectStatus {
+                    etag: object.etag.clone(),
+                    last_modified: object.last_modified,
+                    outcome,
+                    descriptor_id,
+                    error,
+                    ingested_at: Utc::now(),
+                },
+            )
+            .await?;
+        Ok(Some(outcome))
+    }
+
+    // Everything which fails the same way until the object is changed
+    fn parse<
+        Descriptor: IdentifiableDescriptor + PayloadLimited + Serialize + DeserializeOwned + Sync,
+    >(
+        &self,
+        object: &S3Object,
+        contents: &[u8],
+    ) -> Result<Descriptor> {
+        let format = PayloadFormat::from_path(Path::new(&object.key))
+            .expect("only descriptor objects are polled");
+        let descriptor: Descriptor = encoding::decode(format, contents)?;
+        descriptor.check_limits(&self.limits)?;
+        ensure!(!descriptor.id().is_empty(), "descriptor objects need an id");
+        Ok(descriptor)
+    }
+
+    // Stores the descriptor if it differs from what's stored and isn't held for approval
+    async fn ingest<
+        Descriptor: IdentifiableDescriptor + PayloadLimited + Serialize + DeserializeOwned + Sync,
+    >(
+        &self,
+        object: &S3Object,
+        descriptor: &Descriptor,
+    ) -> Result<ObjectOutcome> {
+        let stored = self
+            .descriptor_store
+            .get_descriptor::<Value>(&descriptor.id(), descriptor.kind())
+            .await?;
+        if stored.as_ref() == Some(&serde_json::to_value(descriptor)?) {
+            return Ok(ObjectOutcome::Unchanged);
+        }
+        // NOTE: the object's version identifies what caused the deployment
+        let request_id = format!("s3:{}@{}", object.key, object.etag.trim_matches('"'));
+        if let Admission::Held(approval) = self
+            .approval_gate
+            .admit(descriptor, None, Some(request_id.clone()))
+            .await?
+        {
+            info!(
+                descriptor_id = descriptor.id(),
+                key = object.key,
+                approval_id = approval.approval_id,
+                "descriptor changed in s3, holding it for approval"
+            );
+            return Ok(ObjectOutcome::AwaitingApproval);
+        }
+
+        info!(
+            descriptor_id = descriptor.id(),
+            key = object.key,
+            "descriptor changed in s3, storing it"
+        );
+        self.descriptor_store
+            .store_descriptor::<Descriptor>(descriptor)
+            .await?;
+        let info = DeploymentInfo {
+            state: DeploymentState::Pending,
+            description: (!self.controllers.is_enabled(descriptor.kind()))
+                .then(|| CONTROLLER_DISABLED.to_string()),
+            request_id: Some(request_id),
+            updated_at: None,
+            permanent_failure: false,
+            attempts: 0,
+            delete_after: None,
+            history: DeploymentHistory::default(),
+        };
+        self.deployment_state_store
+            .set_state(&descriptor.id(), &info)
+            .await?;
+        self.state_event_publisher
+            .publish(
+                StateEventType::DescriptorStored,
+                descriptor.kind(),
+                &descriptor.id(),
+                descriptor.owner().as_ref(),
+                &info,
+            )
+            .await;
+
+        Ok(ObjectOutcome::Stored)
+    }
+}
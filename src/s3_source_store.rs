@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::RedisConf, redis_connection::RedisConnector, redis_namespace::prefixed};
+
+const OBJECTS_KEY: &str = "s3-source-objects";
+
+// How the last poll that read an object went, kept until the object is gone from the bucket
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ObjectStatus {
+    // Objects aren't read again until this changes
+    pub etag: String,
+    pub last_modified: Option<DateTime<Utc>>,
+    pub outcome: ObjectOutcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub descriptor_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub ingested_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ObjectOutcome {
+    Stored,
+    // Same as the stored descriptor
+    Unchanged,
+    // Couldn't be decoded, was over the limits or denied by policy
+    Invalid,
+    // A breaking change, held until it's approved
+    AwaitingApproval,
+}
+
+#[async_trait::async_trait]
+pub(crate) trait S3SourceStore {
+    async fn get_status(&self, key: &str) -> Result<Option<ObjectStatus>>;
+    async fn set_status(&self, key: &str, status: &ObjectStatus) -> Result<()>;
+    async fn delete_status(&self, key: &str) -> Result<()>;
+    async fn list_statuses(&self) -> Result<HashMap<String, ObjectStatus>>;
+}
+
+#[derive(Debug)]
+pub struct RedisS3SourceStore {
+    connector: RedisConnector,
+    key_prefix: String,
+}
+
+#[async_trait::async_trait]
+impl S3SourceStore for RedisS3SourceStore {
+    async fn get_status(&self, key: &str) -> Result<Option<ObjectStatus>> {
+        let mut conn = self.connector.get_connection().await?;
+        let status: Option<String> = conn.hget(self.key(OBJECTS_KEY), key).await?;
+        Ok(match status {
+            Some(s) => Some(serde_json::from_str(&s)?),
+            None => None,
+        })
+    }
+
+    async fn set_status(&self, key: &str, status: &ObjectStatus) -> Result<()> {
+        let mut conn = self.connector.get_connection().await?;
+        let _: () = conn
+            .hset(self.key(OBJECTS_KEY), key, serde_json::to_string(status)?)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_status(&self, key: &str) -> Result<()> {
+        let mut conn = self.connector.get_connection().await?;
+        let _: () = conn.hdel(self.key(OBJECTS_KEY), key).await?;
+        Ok(())
+    }
+
+    async fn list_statuses(&self) -> Result<HashMap<String, ObjectStatus>> {
+        let mut conn = self.connector.get_connection().await?;
+        let statuses: HashMap<String, String> = conn.hgetall(self.key(OBJECTS_KEY)).await?;
+        statuses
+            .into_iter()
+            .map(|(key, status)| Ok((key, serde_json::from_str(&status)?)))
+            .collect()
+    }
+}
+
+impl RedisS3SourceStore {
+    pub async fn new(conf: &RedisConf) -> Result<Self> {
+        Ok(Self {
+            connector: RedisConnector::new(conf)?,
+            key_prefix: conf.key_prefix.clone(),
+        })
+    }
+
+    fn key(&self, key: &str) -> String {
+        prefixed(&self.key_prefix, key)
+    }
+}
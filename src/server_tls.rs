@@ -0,0 +1,60 @@
+use std::{fs::File, io::BufReader, sync::Arc};
+
+use anyhow::{anyhow, Context, Result};
+use axum_server::tls_rustls::RustlsConfig;
+use rustls::{
+    server::AllowAnyAuthenticatedClient, Certificate, PrivateKey, RootCertStore, ServerConfig,
+};
+
+use crate::config::TlsConf;
+
+pub fn rustls_config(conf: &TlsConf) -> Result<RustlsConfig> {
+    let certs = load_certs(&conf.cert_path)?;
+    let key = load_private_key(&conf.key_path)?;
+
+    let builder = ServerConfig::builder().with_safe_defaults();
+    let mut server_config = match &conf.client_ca_path {
+        // mTLS, only clients presenting a certificate signed by the configured CA are accepted
+        Some(ca_path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(ca_path)? {
+                roots.add(&cert)?;
+            }
+            builder
+                .with_client_cert_verifier(AllowAnyAuthenticatedClient::new(roots))
+                .with_single_cert(certs, key)?
+        }
+        None => builder.with_no_client_auth().with_single_cert(certs, key)?,
+    };
+    server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    Ok(RustlsConfig::from_config(Arc::new(server_config)))
+}
+
+fn load_certs(path: &str) -> Result<Vec<Certificate>> {
+    let mut reader =
+        BufReader::new(File::open(path).with_context(|| format!("opening certs {}", path))?);
+    let certs = rustls_pemfile::certs(&mut reader)?;
+    if certs.is_empty() {
+        return Err(anyhow!("no certificates found in {}", path));
+    }
+
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn load_private_key(path: &str) -> Result<PrivateKey> {
+    let mut reader =
+        BufReader::new(File::open(path).with_context(|| format!("opening key {}", path))?);
+
+    loop {
+        match rustls_pemfile::read_one(&mut reader)? {
+            Some(
+                rustls_pemfile::Item::PKCS8Key(key)
+                | rustls_pemfile::Item::RSAKey(key)
+                | rustls_pemfile::Item::ECKey(key),
+            ) => return Ok(PrivateKey(key)),
+            Some(_) => continue,
+            None => return Err(anyhow!("no private key found in {}", path)),
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::Utc;
+use redis::AsyncCommands;
+
+use crate::{config::RedisConf, redis_connection::RedisConnector, redis_namespace::prefixed};
+
+// Replicas sharing out descriptors between them. Each one renews its membership on a heartbeat,
+// replicas which stop renewing drop out once their membership expires.
+#[async_trait::async_trait]
+pub(crate) trait ShardMemberStore {
+    async fn heartbeat(&self, member: &str, ttl: Duration) -> Result<()>;
+    // Members whose membership hasn't expired, sorted
+    async fn live_members(&self) -> Result<Vec<String>>;
+}
+
+#[derive(Debug)]
+pub struct RedisShardMemberStore {
+    connector: RedisConnector,
+    key_prefix: String,
+}
+
+// NOTE: members are scored by when they expire, so expired ones can be dropped by score
+#[async_trait::async_trait]
+impl ShardMemberStore for RedisShardMemberStore {
+    async fn heartbeat(&self, member: &str, ttl: Duration) -> Result<()> {
+        let mut conn = self.connector.get_connection().await?;
+        let now = Utc::now().timestamp_millis();
+        let _: () = conn
+            .zadd(
+                self.key("shard-members"),
+                member,
+                now + ttl.as_millis() as i64,
+            )
+            .await?;
+        let _: () = conn
+            .zrembyscore(self.key("shard-members"), "-inf", now)
+            .await?;
+        Ok(())
+    }
+
+    async fn live_members(&self) -> Result<Vec<String>> {
+        let mut conn = self.connector.get_connection().await?;
+        let mut members: Vec<String> = conn
+            .zrangebyscore(
+                self.key("shard-members"),
+                Utc::now().timestamp_millis(),
+                "+inf",
+            )
+            .await?;
+        members.sort();
+        Ok(members)
+    }
+}
+
+impl RedisShardMemberStore {
+    pub async fn new(conf: &RedisConf) -> Result<Self> {
+        Ok(Self {
+            connector: RedisConnector::new(conf)?,
+            key_prefix: conf.key_prefix.clone(),
+        })
+    }
+
+    fn key(&self, key: &str) -> String {
+        prefixed(&self.key_prefix, key)
+    }
+}
@@ -0,0 +1,115 @@
+use std::{sync::RwLock, time::Duration};
+
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use tracing::{error, info};
+
+use crate::{
+    config::{BasinConfig, ShardingConf},
+    metrics,
+    shard_member_store::{RedisShardMemberStore, ShardMemberStore},
+};
+
+// Consistent hash ring over the live replicas, so a replica joining or leaving only moves the
+// descriptors landing next to its points
+struct ShardRing {
+    members: Vec<String>,
+    // Sorted by position, each pointing into members
+    points: Vec<(u64, usize)>,
+}
+
+impl ShardRing {
+    fn new(members: Vec<String>, virtual_nodes: usize) -> Self {
+        let mut points: Vec<(u64, usize)> = members
+            .iter()
+            .enumerate()
+            .flat_map(|(i, member)| {
+                (0..virtual_nodes).map(move |v| (position(&format!("{}#{}", member, v)), i))
+            })
+            .collect();
+        points.sort();
+        ShardRing { members, points }
+    }
+
+    fn owner(&self, id: &str) -> Option<&str> {
+        let at = position(id);
+        let i = self.points.partition_point(|(p, _)| *p < at);
+        let (_, member) = self.points.get(i).or_else(|| self.points.first())?;
+        Some(&self.members[*member])
+    }
+}
+
+// NOTE: every replica has to place ids identically, so this can't be std's randomly keyed hasher
+fn position(key: &str) -> u64 {
+    let digest = Sha256::digest(key.as_bytes());
+    u64::from_be_bytes(digest[..8].try_into().expect("sha256 is 32 bytes"))
+}
+
+// This replica's view of who's sharing the work, refreshed on every heartbeat
+pub struct ShardMembership {
+    conf: ShardingConf,
+    replica_id: String,
+    member_store: RedisShardMemberStore,
+    ring: RwLock<ShardRing>,
+}
+
+impl ShardMembership {
+    pub async fn new(conf: &BasinConfig) -> Result<Option<Self>> {
+        let Some(sharding) = &conf.sharding else {
+            return Ok(None);
+        };
+        let replica_id = match &sharding.replica_id {
+            Some(t) => t.clone(),
+            None => std::env::var("HOSTNAME").unwrap_or_else(|_| uuid::Uuid::new_v4().to_string()),
+        };
+
+        let membership = ShardMembership {
+            conf: sharding.clone(),
+            ring: RwLock::new(ShardRing::new(vec![], sharding.virtual_nodes)),
+            replica_id,
+            member_store: RedisShardMemberStore::new(&conf.redis).await?,
+        };
+        // NOTE: joined before any controller starts, so the first pass already shares out work
+        membership.refresh().await?;
+        Ok(Some(membership))
+    }
+
+    pub async fn membership_loop(&self) -> ! {
+        loop {
+            tokio::time::sleep(Duration::from_secs(self.conf.heartbeat_interval_secs)).await;
+            // NOTE: the last known ring is kept while redis is unreachable. Anything the other
+            //       replicas take over meanwhile is still kept from being reconciled twice at
+            //       once by the reconcile locks.
+            if let Err(e) = self.refresh().await {
+                error!(?e, "failed to refresh shard membership");
+            }
+        }
+    }
+
+    pub fn owns(&self, id: &str) -> bool {
+        self.ring
+            .read()
+            .expect("shard ring poisoned")
+            .owner(id)
+            .map_or(true, |owner| owner == self.replica_id)
+    }
+
+    async fn refresh(&self) -> Result<()> {
+        let ttl = Duration::from_secs(self.conf.member_ttl_secs);
+        self.member_store.heartbeat(&self.replica_id, ttl).await?;
+        let members = self.member_store.live_members().await?;
+        metrics::gauge_set("basin_shard_members", &[], members.len() as f64);
+
+        let mut ring = self.ring.write().expect("shard ring poisoned");
+        if ring.members != members {
+            info!(
+                replica_id = self.replica_id,
+                ?members,
+                "shard membership changed, rebalancing descriptors"
+            );
+            metrics::counter_inc("basin_shard_rebalances_total", &[]);
+            *ring = ShardRing::new(members, self.conf.virtual_nodes);
+        }
+        Ok(())
+    }
+}
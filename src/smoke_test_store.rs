@@ -0,0 +1,66 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::RedisConf, redis_connection::RedisConnector, redis_namespace::prefixed};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SmokeTestResult {
+    pub query: String,
+    // Hash of the descriptor the table was reconciled from when the query ran
+    pub descriptor_hash: String,
+    pub succeeded: bool,
+    pub error: Option<String>,
+    pub ran_at: DateTime<Utc>,
+}
+
+#[async_trait::async_trait]
+pub(crate) trait SmokeTestStore {
+    async fn get_result(&self, table_id: &str) -> Result<Option<SmokeTestResult>>;
+    async fn set_result(&self, table_id: &str, result: &SmokeTestResult) -> Result<()>;
+}
+
+#[derive(Debug)]
+pub struct RedisSmokeTestStore {
+    connector: RedisConnector,
+    key_prefix: String,
+}
+
+#[async_trait::async_trait]
+impl SmokeTestStore for RedisSmokeTestStore {
+    async fn get_result(&self, table_id: &str) -> Result<Option<SmokeTestResult>> {
+        let mut conn = self.connector.get_connection().await?;
+        let result: Option<String> = conn
+            .get(self.key(&format!("smoke-test/{}", table_id)))
+            .await?;
+        Ok(match result {
+            Some(r) => Some(serde_json::from_str(&r)?),
+            None => None,
+        })
+    }
+
+    async fn set_result(&self, table_id: &str, result: &SmokeTestResult) -> Result<()> {
+        let mut conn = self.connector.get_connection().await?;
+        let _: () = conn
+            .set(
+                self.key(&format!("smoke-test/{}", table_id)),
+                serde_json::to_string(result)?,
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+impl RedisSmokeTestStore {
+    pub async fn new(conf: &RedisConf) -> Result<Self> {
+        Ok(Self {
+            connector: RedisConnector::new(conf)?,
+            key_prefix: conf.key_prefix.clone(),
+        })
+    }
+
+    fn key(&self, key: &str) -> String {
+        prefixed(&self.key_prefix, key)
+    }
+}
@@ -0,0 +1,241 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    approval_gate::{Admission, ApprovalGate},
+    deployment_state_store::{DeploymentInfo, DeploymentStateStore, RedisDeploymentStateStore},
+    descriptor_encryption,
+    descriptor_store::{DescriptorStore, RedisDescriptorStore},
+    fluid::descriptor::{
+        database::DatabaseDescriptor, flow::FlowDescriptor, landing_zone::LandingZoneDescriptor,
+        table::TableDescriptor, DescriptorKind, IdentifiableDescriptor,
+    },
+};
+
+// What sealed snapshots are bound to, they're moved between buckets and basins so nothing more
+// specific than being a snapshot
+const SNAPSHOT_BINDING: &str = "snapshot";
+
+// NOTE: descriptor revisions are carried in the descriptors themselves, the event dedup marks
+//       expire on their own and aren't worth restoring
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SnapshotEntry {
+    Descriptor {
+        kind: DescriptorKind,
+        id: String,
+        descriptor: Value,
+    },
+    DeploymentState {
+        id: String,
+        info: DeploymentInfo,
+    },
+}
+
+// Everything basin needs to pick up where it left off with an empty redis
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    pub exported_at: DateTime<Utc>,
+    pub entries: Vec<SnapshotEntry>,
+}
+
+#[derive(Serialize, Default, Debug)]
+pub struct RestoreSummary {
+    pub descriptors: usize,
+    pub deployment_states: usize,
+    // Already present and left alone when not overwriting
+    pub skipped: Vec<String>,
+    // Breaking changes held for approval, their deployment states are left alone too
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub awaiting_approval: Vec<String>,
+}
+
+pub async fn take_snapshot(
+    descriptor_store: &RedisDescriptorStore,
+    deployment_state_store: &RedisDeploymentStateStore,
+) -> Result<Snapshot> {
+    let exported_at = Utc::now();
+    let mut entries = Vec::new();
+    for kind in DescriptorKind::ALL {
+        for descriptor in descriptor_store.list_descriptors::<Value>(kind).await? {
+            entries.push(descriptor_entry(kind, descriptor)?);
+        }
+    }
+
+    for (id, info) in deployment_state_store.list_states().await? {
+        entries.push(SnapshotEntry::DeploymentState { id, info });
+    }
+
+    Ok(Snapshot {
+        exported_at,
+        entries,
+    })
+}
+
+// Snapshots carry every descriptor, so they're sealed like the descriptors are whenever
+// descriptor encryption is installed, wherever they end up. Left as plain json without it.
+pub async fn seal_snapshot(body: String) -> Result<String> {
+    descriptor_encryption::seal(body, SNAPSHOT_BINDING).await
+}
+
+// Plain snapshots pass straight through, sealed ones need descriptor encryption installed
+pub async fn open_snapshot(body: String) -> Result<String> {
+    descriptor_encryption::open(body, SNAPSHOT_BINDING)
+        .await
+        .context("failed to open sealed snapshot")
+}
+
+pub fn descriptor_entry(kind: DescriptorKind, descriptor: Value) -> Result<SnapshotEntry> {
+    let id = descriptor["id"]
+        .as_str()
+        .ok_or_else(|| anyhow!("stored {} descriptor without an id", kind))?
+        .to_string();
+    Ok(SnapshotEntry::Descriptor {
+        kind,
+        id,
+        descriptor,
+    })
+}
+
+// Every descriptor has to parse as its kind, checked before anything is written so a bad
+// snapshot doesn't leave a partial restore behind
+pub fn check_entries(entries: &[SnapshotEntry]) -> Result<()> {
+    for entry in entries {
+        if let SnapshotEntry::Descriptor {
+            kind,
+            id,
+            descriptor,
+        } = entry
+        {
+            let parsed = match kind {
+                DescriptorKind::Database => parse::<DatabaseDescriptor>(descriptor).map(|_| ()),
+                DescriptorKind::Table => parse::<TableDescriptor>(descriptor).map(|_| ()),
+                DescriptorKind::Flow => parse::<FlowDescriptor>(descriptor).map(|_| ()),
+                DescriptorKind::LandingZone => {
+                    parse::<LandingZoneDescriptor>(descriptor).map(|_| ())
+                }
+            };
+            parsed.with_context(|| format!("{} {}", kind, id))?;
+        }
+    }
+    Ok(())
+}
+
+// Deployment states are written as they were, history included. Imports pass the approval gate,
+// restores and migrations of basin's own stores don't.
+pub async fn restore_entries(
+    descriptor_store: &RedisDescriptorStore,
+    deployment_state_store: &RedisDeploymentStateStore,
+    gate: Option<&ApprovalGate>,
+    request_id: Option<&str>,
+    mut entries: Vec<SnapshotEntry>,
+    overwrite: bool,
+) -> Result<RestoreSummary> {
+    check_entries(&entries)?;
+    // NOTE: descriptors go first so a held one's deployment state is known to be skipped
+    entries.sort_by_key(|e| matches!(e, SnapshotEntry::DeploymentState { .. }));
+
+    let mut summary = RestoreSummary::default();
+    for entry in entries {
+        match entry {
+            SnapshotEntry::Descriptor {
+                kind,
+                id,
+                descriptor,
+            } => {
+                if !overwrite
+                    && descriptor_store
+                        .get_descriptor::<Value>(&id, kind)
+                        .await?
+                        .is_some()
+                {
+                    summary.skipped.push(format!("{}/{}", kind, id));
+                    continue;
+                }
+                let stored = match kind {
+                    DescriptorKind::Database => {
+                        store::<DatabaseDescriptor>(descriptor_store, gate, request_id, &descriptor)
+                            .await?
+                    }
+                    DescriptorKind::Table => {
+                        store::<TableDescriptor>(descriptor_store, gate, request_id, &descriptor)
+                            .await?
+                    }
+                    DescriptorKind::Flow => {
+                        store::<FlowDescriptor>(descriptor_store, gate, request_id, &descriptor)
+                            .await?
+                    }
+                    DescriptorKind::LandingZone => {
+                        store::<LandingZoneDescriptor>(
+                            descriptor_store,
+                            gate,
+                            request_id,
+                            &descriptor,
+                        )
+                        .await?
+                    }
+                };
+                if stored {
+                    summary.descriptors += 1;
+                } else {
+                    summary.awaiting_approval.push(id);
+                }
+            }
+            SnapshotEntry::DeploymentState { id, info } => {
+                if summary.awaiting_approval.contains(&id) {
+                    continue;
+                }
+                if !overwrite && deployment_state_store.get_state(&id).await?.is_some() {
+                    summary.skipped.push(format!("deployment-state/{}", id));
+                    continue;
+                }
+                deployment_state_store.restore_state(&id, &info).await?;
+                summary.deployment_states += 1;
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+// Whether there's anything to lose by restoring over the store
+pub async fn store_is_empty(
+    descriptor_store: &RedisDescriptorStore,
+    deployment_state_store: &RedisDeploymentStateStore,
+) -> Result<bool> {
+    for kind in DescriptorKind::ALL {
+        let page = descriptor_store
+            .list_descriptors_page::<Value>(kind, 0, 1)
+            .await?;
+        if !page.descriptors.is_empty() {
+            return Ok(false);
+        }
+    }
+    let (_, states) = deployment_state_store.list_states_page(0, 1).await?;
+    Ok(states.is_empty())
+}
+
+fn parse<T: DeserializeOwned>(descriptor: &Value) -> Result<T> {
+    Ok(serde_json::from_value(descriptor.clone())?)
+}
+
+// Whether the descriptor was stored, it isn't when the gate holds it for approval
+async fn store<T: IdentifiableDescriptor + Serialize + DeserializeOwned + Sync>(
+    descriptor_store: &RedisDescriptorStore,
+    gate: Option<&ApprovalGate>,
+    request_id: Option<&str>,
+    descriptor: &Value,
+) -> Result<bool> {
+    let descriptor = parse::<T>(descriptor)?;
+    if let Some(gate) = gate
+        && let Admission::Held(_) = gate
+            .admit(&descriptor, None, request_id.map(str::to_string))
+            .await?
+    {
+        return Ok(false);
+    }
+    descriptor_store.store_descriptor(&descriptor).await?;
+    Ok(true)
+}
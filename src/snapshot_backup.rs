@@ -0,0 +1,237 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use aws_sdk_s3::types::ByteStream;
+use chrono::Utc;
+use tokio::time::{interval, MissedTickBehavior};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    config::{BackupConf, BasinConfig},
+    deployment_state_store::RedisDeploymentStateStore,
+    descriptor_store::RedisDescriptorStore,
+    provisioner::s3::split_s3_uri,
+    reconcile_lock_store::{ReconcileLockStore, RedisReconcileLockStore},
+    snapshot::{
+        open_snapshot, restore_entries, seal_snapshot, store_is_empty, take_snapshot, Snapshot,
+    },
+};
+
+const BACKUP_LEASE: &str = "snapshot-backup";
+
+// Writes a snapshot of the descriptor and deployment state stores to s3 on a schedule, the same
+// format as the admin export. Keys are `{prefix}/{taken_at_millis}.json` so lexical order is
+// chronological and the latest snapshot is the last key under the prefix. Sealed with the
+// descriptor data keys when descriptor encryption is installed.
+pub struct SnapshotBackup {
+    conf: BackupConf,
+    descriptor_store: RedisDescriptorStore,
+    deployment_state_store: RedisDeploymentStateStore,
+    lock_store: RedisReconcileLockStore,
+    s3_client: aws_sdk_s3::Client,
+}
+
+impl SnapshotBackup {
+    pub async fn new(conf: &BasinConfig) -> Result<Option<Self>> {
+        let Some(backup) = &conf.backup else {
+            return Ok(None);
+        };
+
+        Ok(Some(SnapshotBackup {
+            conf: backup.clone(),
+            descriptor_store: RedisDescriptorStore::new(&conf.redis).await?,
+            deployment_state_store: RedisDeploymentStateStore::new(&conf.redis).await?,
+            lock_store: RedisReconcileLockStore::new(&conf.redis).await?,
+            s3_client: aws_sdk_s3::Client::new(&conf.aws_creds),
+        }))
+    }
+
+    pub async fn backup_loop(&self) -> ! {
+        let mut ticker = interval(Duration::from_secs(self.conf.interval_secs));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+
+            // NOTE: every replica runs this loop, whichever takes the lease backs up and prunes for
+            //       the interval. It's kept after a backup so the others skip their ticks too, and
+            //       only released when the backup fails so another replica can retry.
+            let ttl = Duration::from_secs(self.conf.interval_secs);
+            let token = match self.lock_store.try_lease(BACKUP_LEASE, ttl).await {
+                Ok(Some(t)) => t,
+                Ok(None) => {
+                    debug!("another replica is backing up this interval");
+                    continue;
+                }
+                Err(e) => {
+                    error!(?e, "error when leasing snapshot backup");
+                    continue;
+                }
+            };
+
+            info!("Backing up descriptor and deployment state stores");
+            match self.backup().await {
+                Ok((entries, pruned)) => info!(entries, pruned, "finished snapshot backup"),
+                Err(e) => {
+                    error!(?e, "error when backing up stores");
+                    if let Err(e) = self.lock_store.release(BACKUP_LEASE, &token).await {
+                        warn!(?e, "failed to release snapshot backup lease");
+                    }
+                }
+            }
+        }
+    }
+
+    async fn backup(&self) -> Result<(usize, usize)> {
+        let snapshot = take_snapshot(&self.descriptor_store, &self.deployment_state_store).await?;
+        let entries = snapshot.entries.len();
+
+        self.s3_client
+            .put_object()
+            .bucket(&self.conf.bucket)
+            .key(format!(
+                "{}/{}.json",
+                self.conf.prefix,
+                snapshot.exported_at.timestamp_millis()
+            ))
+            .body(ByteStream::from(
+                seal_snapshot(serde_json::to_string(&snapshot)?)
+                    .await?
+                    .into_bytes(),
+            ))
+            .send()
+            .await
+            .map_err(|e| e.into_service_error())?;
+
+        // Only prune once the new snapshot has landed
+        let pruned = self.prune().await?;
+        Ok((entries, pruned))
+    }
+
+    async fn prune(&self) -> Result<usize> {
+        let cutoff = Utc::now() - chrono::Duration::days(self.conf.retention_days as i64);
+        let mut keys = list_snapshot_keys(&self.s3_client, &self.conf.bucket, &self.conf.prefix)
+            .await?
+            .into_iter()
+            .filter_map(|k| Some((snapshot_taken_at(&k)?, k)))
+            .collect::<Vec<_>>();
+        // NOTE: the latest snapshot survives however old it is
+        keys.pop();
+
+        let mut pruned = 0;
+        for (taken_at, key) in keys {
+            if taken_at >= cutoff.timestamp_millis() {
+                continue;
+            }
+            self.s3_client
+                .delete_object()
+                .bucket(&self.conf.bucket)
+                .key(&key)
+                .send()
+                .await
+                .map_err(|e| e.into_service_error())?;
+            pruned += 1;
+        }
+
+        Ok(pruned)
+    }
+}
+
+// Seeds the stores from the latest snapshot under `s3://bucket/prefix`. Does nothing when redis
+// already has descriptors or deployment states, restoring over a live store would quietly roll
+// it back.
+pub async fn restore_from(conf: &BasinConfig, uri: &str) -> Result<()> {
+    let (bucket, prefix) =
+        split_s3_uri(uri).ok_or_else(|| anyhow!("invalid snapshot location '{}'", uri))?;
+    let descriptor_store = RedisDescriptorStore::new(&conf.redis).await?;
+    let deployment_state_store = RedisDeploymentStateStore::new(&conf.redis).await?;
+
+    if !store_is_empty(&descriptor_store, &deployment_state_store).await? {
+        warn!(uri, "store isn't empty, not restoring from snapshot");
+        return Ok(());
+    }
+
+    let s3_client = aws_sdk_s3::Client::new(&conf.aws_creds);
+    let Some(key) = list_snapshot_keys(&s3_client, &bucket, &prefix)
+        .await?
+        .into_iter()
+        .filter(|k| snapshot_taken_at(k).is_some())
+        .last()
+    else {
+        return Err(anyhow!("no snapshots found under {}", uri));
+    };
+
+    let obj = s3_client
+        .get_object()
+        .bucket(&bucket)
+        .key(&key)
+        .send()
+        .await
+        .map_err(|e| e.into_service_error())?;
+    let bytes = obj
+        .body
+        .collect()
+        .await
+        .map_err(|e| anyhow!("failed to read snapshot {}: {:?}", key, e))?
+        .into_bytes();
+    let body = String::from_utf8(bytes.to_vec())
+        .map_err(|e| anyhow!("snapshot {} isn't utf-8: {}", key, e))?;
+    let snapshot: Snapshot = serde_json::from_str(&open_snapshot(body).await?)?;
+
+    let summary = restore_entries(
+        &descriptor_store,
+        &deployment_state_store,
+        None,
+        None,
+        snapshot.entries,
+        false,
+    )
+    .await?;
+    info!(
+        key,
+        exported_at = %snapshot.exported_at,
+        descriptors = summary.descriptors,
+        deployment_states = summary.deployment_states,
+        "restored stores from snapshot"
+    );
+    Ok(())
+}
+
+// Sorted, so oldest first
+async fn list_snapshot_keys(
+    s3_client: &aws_sdk_s3::Client,
+    bucket: &str,
+    prefix: &str,
+) -> Result<Vec<String>> {
+    let mut keys = Vec::new();
+    let mut continuation_token: Option<String> = None;
+    loop {
+        let resp = s3_client
+            .list_objects_v2()
+            .bucket(bucket)
+            .prefix(format!("{}/", prefix))
+            .set_continuation_token(continuation_token.take())
+            .send()
+            .await
+            .map_err(|e| e.into_service_error())?;
+
+        for obj in resp.contents().unwrap_or_default() {
+            if let Some(k) = obj.key() {
+                keys.push(k.to_string());
+            }
+        }
+
+        match resp.next_continuation_token() {
+            Some(t) => continuation_token = Some(t.to_string()),
+            None => break,
+        }
+    }
+    // NOTE: millisecond timestamps have the same width for the next couple of centuries
+    keys.sort();
+    Ok(keys)
+}
+
+// Anything else under the prefix isn't one of ours
+fn snapshot_taken_at(key: &str) -> Option<i64> {
+    key.rsplit('/').next()?.strip_suffix(".json")?.parse().ok()
+}
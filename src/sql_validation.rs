@@ -0,0 +1,100 @@
+use std::ops::ControlFlow;
+
+use anyhow::{anyhow, bail, Result};
+use sqlparser::{
+    ast::{visit_relations, Expr, Statement, UnaryOperator, Value},
+    dialect::{Dialect, GenericDialect, HiveDialect},
+    parser::Parser,
+    tokenizer::Token,
+};
+
+use crate::config::SqlDialect;
+
+fn dialect_for(dialect: SqlDialect) -> Box<dyn Dialect> {
+    match dialect {
+        // NOTE: sqlparser has no presto/trino dialect, athena's DML is trino so both use the
+        //       generic dialect which accepts a superset of the two
+        SqlDialect::Athena | SqlDialect::Trino => Box::new(GenericDialect {}),
+        SqlDialect::Spark => Box::new(HiveDialect {}),
+    }
+}
+
+pub fn parse_sql(sql: &str, dialect: SqlDialect) -> Result<Vec<Statement>> {
+    let statements = Parser::parse_sql(dialect_for(dialect).as_ref(), sql)
+        .map_err(|e| anyhow!("invalid sql: {}", e))?;
+    if statements.is_empty() {
+        bail!("sql contains no statements");
+    }
+    Ok(statements)
+}
+
+// Names (as written) of every table the statements read from or write to
+// TODO: CTE names are reported alongside real tables
+pub fn referenced_tables(statements: &[Statement]) -> Vec<String> {
+    let mut tables: Vec<String> = Vec::new();
+    for statement in statements {
+        visit_relations(statement, |relation| {
+            let name = relation.to_string();
+            if !tables.contains(&name) {
+                tables.push(name);
+            }
+            ControlFlow::<()>::Continue(())
+        });
+    }
+    tables
+}
+
+// A column default as it's written into DDL, rendered back from what was parsed. Only literals,
+// signed numbers and calls without arguments (e.g. `CURRENT_TIMESTAMP()`) are taken, anything
+// else could reach past the column definition.
+pub fn column_default(default: &str) -> Result<String> {
+    let dialect = GenericDialect {};
+    let mut parser = Parser::new(&dialect)
+        .try_with_sql(default)
+        .map_err(|e| anyhow!("invalid default '{}': {}", default, e))?;
+    let expr = parser
+        .parse_expr()
+        .and_then(|expr| parser.expect_token(&Token::EOF).map(|_| expr))
+        .map_err(|e| anyhow!("invalid default '{}': {}", default, e))?;
+
+    let allowed = match &expr {
+        Expr::Value(_) => true,
+        Expr::UnaryOp {
+            op: UnaryOperator::Minus | UnaryOperator::Plus,
+            expr,
+        } => matches!(**expr, Expr::Value(Value::Number(..))),
+        Expr::Function(function) => function.args.is_empty() && function.over.is_none(),
+        _ => false,
+    };
+    if !allowed {
+        bail!(
+            "default '{}' has to be a literal or a call without arguments",
+            default
+        );
+    }
+    Ok(expr.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn column_default_takes_literals_and_calls() {
+        assert_eq!(column_default("0").unwrap(), "0");
+        assert_eq!(column_default("-1.5").unwrap(), "-1.5");
+        assert_eq!(column_default("'n/a'").unwrap(), "'n/a'");
+        assert_eq!(
+            column_default("current_timestamp()").unwrap(),
+            "current_timestamp()"
+        );
+    }
+
+    #[test]
+    fn column_default_rejects_anything_past_the_expression() {
+        assert!(column_default("0) ; DROP TABLE orders; --").is_err());
+        assert!(column_default("0, other VARCHAR").is_err());
+        assert!(column_default("(SELECT secret FROM vault)").is_err());
+        assert!(column_default("upper(name)").is_err());
+    }
+}
@@ -0,0 +1,193 @@
+use anyhow::{anyhow, Result};
+use aws_sdk_eventbridge::model::PutEventsRequestEntry;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::{
+    config::BasinConfig,
+    deployment_state_store::{DeploymentInfo, DeploymentState},
+    fluid::descriptor::{DescriptorKind, Owner},
+};
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StateEventType {
+    DescriptorStored,
+    ReconcileSucceeded,
+    ReconcileFailed,
+    DriftDetected,
+    DescriptorDeleted,
+    FreshnessBreached,
+    FreshnessRestored,
+    StuckDeploymentReset,
+}
+
+impl StateEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StateEventType::DescriptorStored => "descriptor_stored",
+            StateEventType::ReconcileSucceeded => "reconcile_succeeded",
+            StateEventType::ReconcileFailed => "reconcile_failed",
+            StateEventType::DriftDetected => "drift_detected",
+            StateEventType::DescriptorDeleted => "descriptor_deleted",
+            StateEventType::FreshnessBreached => "freshness_breached",
+            StateEventType::FreshnessRestored => "freshness_restored",
+            StateEventType::StuckDeploymentReset => "stuck_deployment_reset",
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct StateEvent<'a> {
+    event_id: String,
+    r#type: StateEventType,
+    kind: DescriptorKind,
+    descriptor_id: &'a str,
+    state: DeploymentState,
+    description: Option<&'a str>,
+    request_id: Option<&'a str>,
+    // Who to page about failures, when the descriptor names an owner
+    #[serde(skip_serializing_if = "Option::is_none")]
+    owner: Option<&'a Owner>,
+    time: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+enum Sink {
+    Sns {
+        client: aws_sdk_sns::Client,
+        topic_arn: String,
+    },
+    EventBridge {
+        client: aws_sdk_eventbridge::Client,
+        event_bus_name: String,
+        source: String,
+    },
+}
+
+// Publishes deployment state changes for downstream automation to build on. Publishing is best
+// effort, events lost to a failed publish aren't retried, the api stays the source of truth.
+// The default publishes nothing, as without `[state_events]`.
+#[derive(Debug, Default)]
+pub struct StateEventPublisher {
+    sink: Option<Sink>,
+}
+
+impl StateEventPublisher {
+    pub fn new(conf: &BasinConfig) -> Self {
+        let sink = conf
+            .state_events
+            .as_ref()
+            .map(|events| match &events.sns_topic_arn {
+                Some(topic_arn) => Sink::Sns {
+                    client: aws_sdk_sns::Client::new(&conf.aws_creds),
+                    topic_arn: topic_arn.clone(),
+                },
+                None => Sink::EventBridge {
+                    client: aws_sdk_eventbridge::Client::new(&conf.aws_creds),
+                    event_bus_name: events.event_bus_name.clone(),
+                    source: events.source.clone(),
+                },
+            });
+
+        StateEventPublisher { sink }
+    }
+
+    pub async fn publish(
+        &self,
+        event_type: StateEventType,
+        kind: DescriptorKind,
+        descriptor_id: &str,
+        owner: Option<&Owner>,
+        info: &DeploymentInfo,
+    ) {
+        let Some(sink) = &self.sink else {
+            return;
+        };
+
+        let event = StateEvent {
+            event_id: Uuid::new_v4().to_string(),
+            r#type: event_type,
+            kind,
+            descriptor_id,
+            state: info.state,
+            description: info.description.as_deref(),
+            request_id: info.request_id.as_deref(),
+            owner,
+            time: Utc::now(),
+        };
+        debug!(
+            event_id = event.event_id,
+            event_type = event_type.as_str(),
+            descriptor_id,
+            "publishing state event"
+        );
+
+        if let Err(e) = Self::send(sink, &event).await {
+            warn!(
+                event_type = event_type.as_str(),
+                descriptor_id,
+                ?e,
+                "failed to publish state event"
+            );
+        }
+    }
+
+    async fn send(sink: &Sink, event: &StateEvent<'_>) -> Result<()> {
+        let body = serde_json::to_string(event)?;
+
+        match sink {
+            Sink::Sns { client, topic_arn } => {
+                let mut request = client
+                    .publish()
+                    .topic_arn(topic_arn)
+                    .message(body)
+                    .subject(event.r#type.as_str());
+                // NOTE: fifo topics keep each descriptor's events in order. The dedup id is the
+                //       event id rather than a hash of the content: the sdk's retries of a publish
+                //       resend the same id and are dropped, while a transition repeated with the
+                //       same content within sns' five minute dedup window (a descriptor failing
+                //       twice with the same error) is an event of its own that a content hash would
+                //       swallow. Two replicas noticing the same change publish it twice, each with
+                //       an id of its own, consumers go by the state rather than count events.
+                if topic_arn.ends_with(".fifo") {
+                    request = request
+                        .message_group_id(event.descriptor_id)
+                        .message_deduplication_id(&event.event_id);
+                }
+                request.send().await?;
+            }
+            Sink::EventBridge {
+                client,
+                event_bus_name,
+                source,
+            } => {
+                let output = client
+                    .put_events()
+                    .entries(
+                        PutEventsRequestEntry::builder()
+                            .event_bus_name(event_bus_name)
+                            .source(source)
+                            .detail_type(event.r#type.as_str())
+                            .detail(body)
+                            .build(),
+                    )
+                    .send()
+                    .await?;
+                // NOTE: put_events succeeds as a whole even when its entries don't
+                if output.failed_entry_count() > 0 {
+                    let entry = output.entries().and_then(|e| e.first());
+                    return Err(anyhow!(
+                        "eventbridge rejected event: {} {}",
+                        entry.and_then(|e| e.error_code()).unwrap_or_default(),
+                        entry.and_then(|e| e.error_message()).unwrap_or_default()
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
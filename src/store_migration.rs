@@ -0,0 +1,216 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, bail, Result};
+use axum::{
+    http::{Method, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use crate::{
+    config::BasinConfig,
+    deployment_state_store::{DeploymentStateStore, RedisDeploymentStateStore},
+    descriptor_store::{DescriptorStore, RedisDescriptorStore},
+    fluid::descriptor::DescriptorKind,
+    snapshot::{self, SnapshotEntry},
+};
+
+#[derive(Serialize, PartialEq, Eq, Debug)]
+pub struct EntriesDigest {
+    pub count: usize,
+    pub sha256: String,
+}
+
+// What a store holds, comparable across stores regardless of the order they list entries in
+#[derive(Serialize, PartialEq, Eq, Debug)]
+pub struct StoreDigest {
+    pub descriptors: BTreeMap<String, EntriesDigest>,
+    pub deployment_states: EntriesDigest,
+}
+
+#[derive(Debug)]
+pub struct MigrationSummary {
+    pub descriptors: usize,
+    pub deployment_states: usize,
+    pub digest: StoreDigest,
+}
+
+// Copies every descriptor and deployment state from basin's store to the store_migration target a
+// page at a time, then checks both hold the same thing. Anything writing to the source while this
+// runs shows up as a mismatch, so the source should be served read only until the cutover.
+pub async fn migrate_store(conf: &BasinConfig, overwrite: bool) -> Result<MigrationSummary> {
+    let migration = conf
+        .store_migration
+        .as_ref()
+        .ok_or_else(|| anyhow!("store_migration must be set to migrate the store"))?;
+    let source_descriptors = RedisDescriptorStore::new(&conf.redis).await?;
+    let source_states = RedisDeploymentStateStore::new(&conf.redis).await?;
+    let target_descriptors = RedisDescriptorStore::new(&migration.target).await?;
+    let target_states = RedisDeploymentStateStore::new(&migration.target).await?;
+
+    if !overwrite && !snapshot::store_is_empty(&target_descriptors, &target_states).await? {
+        bail!("the target store isn't empty, pass --overwrite to migrate over it");
+    }
+    if !conf.read_only {
+        warn!(
+            "basin isn't configured read only, writes during the migration will fail verification"
+        );
+    }
+
+    let mut migrated_descriptors = 0;
+    for kind in DescriptorKind::ALL {
+        let mut cursor = 0;
+        loop {
+            let page = source_descriptors
+                .list_descriptors_page::<Value>(kind, cursor, migration.batch_size)
+                .await?;
+            let next_cursor = page.next_cursor;
+            let entries = page
+                .descriptors
+                .into_iter()
+                .map(|d| snapshot::descriptor_entry(kind, d))
+                .collect::<Result<Vec<_>>>()?;
+            // NOTE: scans can hand back a key twice, writing it again is harmless
+            migrated_descriptors += snapshot::restore_entries(
+                &target_descriptors,
+                &target_states,
+                None,
+                None,
+                entries,
+                true,
+            )
+            .await?
+            .descriptors;
+            if next_cursor == 0 {
+                break;
+            }
+            cursor = next_cursor;
+        }
+        info!(%kind, "migrated descriptors");
+    }
+
+    let mut migrated_states = 0;
+    let mut cursor = 0;
+    loop {
+        let (next_cursor, page) = source_states
+            .list_states_page(cursor, migration.batch_size)
+            .await?;
+        let entries = page
+            .into_iter()
+            .map(|(id, info)| SnapshotEntry::DeploymentState { id, info })
+            .collect();
+        migrated_states += snapshot::restore_entries(
+            &target_descriptors,
+            &target_states,
+            None,
+            None,
+            entries,
+            true,
+        )
+        .await?
+        .deployment_states;
+        if next_cursor == 0 {
+            break;
+        }
+        cursor = next_cursor;
+    }
+    info!("migrated deployment states");
+
+    let source_digest =
+        store_digest(&source_descriptors, &source_states, migration.batch_size).await?;
+    let target_digest =
+        store_digest(&target_descriptors, &target_states, migration.batch_size).await?;
+    if source_digest != target_digest {
+        bail!(
+            "migrated store doesn't match the source, source: {:?}, target: {:?}",
+            source_digest,
+            target_digest
+        );
+    }
+
+    Ok(MigrationSummary {
+        descriptors: migrated_descriptors,
+        deployment_states: migrated_states,
+        digest: source_digest,
+    })
+}
+
+// Counts and hashes entries sorted by id, two stores holding the same entries digest the same
+pub async fn store_digest(
+    descriptor_store: &RedisDescriptorStore,
+    deployment_state_store: &RedisDeploymentStateStore,
+    batch_size: usize,
+) -> Result<StoreDigest> {
+    let mut descriptors = BTreeMap::new();
+    for kind in DescriptorKind::ALL {
+        let mut entries = BTreeMap::new();
+        let mut cursor = 0;
+        loop {
+            let page = descriptor_store
+                .list_descriptors_page::<Value>(kind, cursor, batch_size)
+                .await?;
+            let next_cursor = page.next_cursor;
+            for descriptor in page.descriptors {
+                let id = descriptor["id"].as_str().unwrap_or_default().to_string();
+                entries.insert(id, serde_json::to_string(&descriptor)?);
+            }
+            if next_cursor == 0 {
+                break;
+            }
+            cursor = next_cursor;
+        }
+        descriptors.insert(kind.to_string(), digest(&entries));
+    }
+
+    let mut states = BTreeMap::new();
+    let mut cursor = 0;
+    loop {
+        let (next_cursor, page) = deployment_state_store
+            .list_states_page(cursor, batch_size)
+            .await?;
+        for (id, info) in page {
+            states.insert(id, serde_json::to_string(&info)?);
+        }
+        if next_cursor == 0 {
+            break;
+        }
+        cursor = next_cursor;
+    }
+
+    Ok(StoreDigest {
+        descriptors,
+        deployment_states: digest(&states),
+    })
+}
+
+fn digest(entries: &BTreeMap<String, String>) -> EntriesDigest {
+    let mut hasher = Sha256::new();
+    for (id, json) in entries {
+        hasher.update(id);
+        hasher.update([0u8]);
+        hasher.update(json);
+        hasher.update([0u8]);
+    }
+    EntriesDigest {
+        count: entries.len(),
+        sha256: format!("{:x}", hasher.finalize()),
+    }
+}
+
+// Layered on the api when basin is read only. Diffs only compare a submission to what's stored so
+// they're let through with the reads.
+pub async fn reject_writes<B>(req: Request<B>, next: Next<B>) -> Response {
+    let method = req.method();
+    if method == Method::GET || method == Method::HEAD || req.uri().path().ends_with("/diff") {
+        return next.run(req).await;
+    }
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        "basin is read only while its store is migrated, retry later",
+    )
+        .into_response()
+}
@@ -0,0 +1,165 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::Utc;
+use serde_json::Value;
+use tokio::time::{interval, MissedTickBehavior};
+use tracing::{error, info, warn};
+
+use crate::{
+    config::{BasinConfig, ControllersConf, StuckDeploymentsConf},
+    constants::CONTROLLER_DISABLED,
+    deployment_state_store::{
+        DeploymentHistory, DeploymentInfo, DeploymentState, DeploymentStateStore,
+        RedisDeploymentStateStore,
+    },
+    descriptor_store::{DescriptorStore, RedisDescriptorStore},
+    fluid::descriptor::{DescriptorKind, Owner},
+    metrics,
+    state_events::{StateEventPublisher, StateEventType},
+};
+
+// Finds descriptors whose state hasn't been written for longer than the threshold while Pending
+// or Deploying. Reconcilers write the state on every pass, even when nothing changed, so these
+// were dropped somewhere (a replica dying mid reconcile, a lost state write). They're reset to
+// Pending, which also holds off flagging them again until another threshold has passed.
+pub struct StuckDeploymentDetector {
+    conf: StuckDeploymentsConf,
+    controllers: ControllersConf,
+    descriptor_store: RedisDescriptorStore,
+    deployment_state_store: RedisDeploymentStateStore,
+    state_event_publisher: StateEventPublisher,
+}
+
+impl StuckDeploymentDetector {
+    pub async fn new(conf: &BasinConfig) -> Result<Option<Self>> {
+        let Some(stuck_deployments) = &conf.stuck_deployments else {
+            return Ok(None);
+        };
+
+        Ok(Some(StuckDeploymentDetector {
+            conf: stuck_deployments.clone(),
+            controllers: conf.controllers.clone(),
+            descriptor_store: RedisDescriptorStore::new(&conf.redis).await?,
+            deployment_state_store: RedisDeploymentStateStore::new(&conf.redis).await?,
+            state_event_publisher: StateEventPublisher::new(conf),
+        }))
+    }
+
+    pub async fn detect_loop(&self) -> ! {
+        let mut ticker = interval(Duration::from_secs(self.conf.interval_secs));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+
+            info!("Checking for stuck deployments");
+            match self.reset_stuck().await {
+                Ok(reset) => info!(reset, "finished checking for stuck deployments"),
+                Err(e) => error!(?e, "error when checking for stuck deployments"),
+            }
+        }
+    }
+
+    async fn reset_stuck(&self) -> Result<usize> {
+        let mut reset = 0;
+        for kind in DescriptorKind::ALL {
+            // NOTE: descriptors of disabled controllers are meant to sit in Pending
+            if !self.controllers.is_enabled(kind) {
+                continue;
+            }
+
+            let descriptors: Vec<(String, Value)> = self
+                .descriptor_store
+                .list_descriptors::<Value>(kind)
+                .await?
+                .into_iter()
+                .filter_map(|d| Some((d["id"].as_str()?.to_string(), d)))
+                .collect();
+            let ids: Vec<String> = descriptors.iter().map(|(id, _)| id.clone()).collect();
+            let states = self.deployment_state_store.get_states(&ids).await?;
+
+            let (mut stuck, mut kind_reset) = (0, 0);
+            for ((id, descriptor), info) in descriptors.iter().zip(states) {
+                let Some(info) = info else {
+                    continue;
+                };
+                if !self.is_stuck(&info) {
+                    continue;
+                }
+                stuck += 1;
+                // NOTE: one failed reset shouldn't hold up the rest
+                let owner: Option<Owner> =
+                    serde_json::from_value(descriptor["owner"].clone()).unwrap_or_default();
+                match self.reset(kind, id, owner.as_ref(), &info).await {
+                    Ok(()) => kind_reset += 1,
+                    Err(e) => warn!(descriptor_id = id, ?e, "failed to reset stuck deployment"),
+                }
+            }
+            metrics::gauge_set(
+                "basin_stuck_deployments",
+                &[("kind", kind.as_str())],
+                stuck as f64,
+            );
+            metrics::counter_add(
+                "basin_stuck_deployments_reset_total",
+                &[("kind", kind.as_str())],
+                kind_reset as f64,
+            );
+            reset += kind_reset;
+        }
+
+        Ok(reset)
+    }
+
+    fn is_stuck(&self, info: &DeploymentInfo) -> bool {
+        if !matches!(
+            info.state,
+            DeploymentState::Pending | DeploymentState::Deploying
+        ) || info.description.as_deref() == Some(CONTROLLER_DISABLED)
+        {
+            return false;
+        }
+        let Some(updated_at) = info.updated_at else {
+            return false;
+        };
+        Utc::now() - updated_at > chrono::Duration::seconds(self.conf.threshold_secs as i64)
+    }
+
+    async fn reset(
+        &self,
+        kind: DescriptorKind,
+        id: &str,
+        owner: Option<&Owner>,
+        stuck: &DeploymentInfo,
+    ) -> Result<()> {
+        let updated_at = stuck.updated_at.unwrap_or_else(Utc::now);
+        warn!(
+            descriptor_id = id,
+            state = ?stuck.state,
+            %updated_at,
+            "deployment is stuck, resetting it to Pending"
+        );
+
+        let info = DeploymentInfo {
+            state: DeploymentState::Pending,
+            description: Some(format!(
+                "reset after being stuck {:?} since {}",
+                stuck.state,
+                updated_at.to_rfc3339()
+            )),
+            request_id: stuck.request_id.clone(),
+            updated_at: None,
+            permanent_failure: false,
+            attempts: stuck.attempts,
+            delete_after: None,
+            history: DeploymentHistory::default(),
+        };
+        self.deployment_state_store.set_state(id, &info).await?;
+        self.state_event_publisher
+            .publish(StateEventType::StuckDeploymentReset, kind, id, owner, &info)
+            .await;
+
+        Ok(())
+    }
+}
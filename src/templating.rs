@@ -0,0 +1,198 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{anyhow, Result};
+use minijinja::{context, value::Value, Environment, Error, ErrorKind, UndefinedBehavior};
+
+use crate::{
+    controller::naming::{glue_database_name, s3_bucket_name, table_location},
+    fluid::descriptor::{database::DatabaseDescriptor, table::TableDescriptor},
+    provisioner::waterwheel::TRIGGER_DATETIME_PLACEHOLDER,
+};
+
+// Stands in for `ds` in rendered templates. Waterwheel only expands the full trigger datetime, the
+// task cuts the date out of it when it runs.
+pub const TRIGGER_DATE_PLACEHOLDER: &str = "__basin_trigger_date__";
+
+// Renders `{{ }}` templates in flow SQL steps and summaries so flows can refer to the resources
+// basin owns without hardcoding their physical names. Available to templates:
+//
+//   ds                     date the run was triggered for, e.g. `2024-01-31`
+//   ts                     when the run was triggered, e.g. `2024-01-31T00:00:00Z`. Both are only
+//                          known once the job fires so they're expanded by the task rather than
+//                          here. Use them inside string literals.
+//   database("db")         glue database name, e.g. `zone_db`
+//   table("db.table")      qualified glue table name, e.g. `zone_db.table`. The database can be
+//                          left off when the table name is unique across databases.
+//   location("db.table")   s3 uri the table's data lives under
+pub struct TemplateContext {
+    databases: HashMap<String, String>,
+    tables: HashMap<String, String>,
+    locations: HashMap<String, String>,
+    buckets: HashMap<String, String>,
+}
+
+impl TemplateContext {
+    pub fn new(databases: &[DatabaseDescriptor], tables: &[TableDescriptor]) -> Self {
+        let databases_by_id: HashMap<&str, &DatabaseDescriptor> =
+            databases.iter().map(|d| (d.id.as_str(), d)).collect();
+
+        let mut ctx = TemplateContext {
+            databases: databases
+                .iter()
+                .map(|d| (d.name.clone(), glue_database_name(d)))
+                .collect(),
+            tables: HashMap::new(),
+            locations: HashMap::new(),
+            buckets: databases
+                .iter()
+                .map(|d| (d.name.clone(), s3_bucket_name(d)))
+                .collect(),
+        };
+
+        let mut unqualified: HashMap<&str, Vec<String>> = HashMap::new();
+        for table in tables {
+            // NOTE: tables whose database hasn't arrived yet can't be named
+            let Some(db) = databases_by_id.get(table.database.as_str()) else {
+                continue;
+            };
+
+            let qualified = format!("{}.{}", db.name, table.name);
+            ctx.tables.insert(
+                qualified.clone(),
+                format!("{}.{}", glue_database_name(db), table.name),
+            );
+            if let Ok((bucket, prefix)) = table_location(table, db) {
+                ctx.locations
+                    .insert(qualified.clone(), format!("s3://{}/{}", bucket, prefix));
+            }
+            unqualified
+                .entry(table.name.as_str())
+                .or_default()
+                .push(qualified);
+        }
+
+        for (name, qualified) in unqualified {
+            if let [qualified] = qualified.as_slice() {
+                if let Some(t) = ctx.tables.get(qualified).cloned() {
+                    ctx.tables.insert(name.to_string(), t);
+                }
+                if let Some(t) = ctx.locations.get(qualified).cloned() {
+                    ctx.locations.insert(name.to_string(), t);
+                }
+            }
+        }
+
+        ctx
+    }
+
+    // Glue database name and bucket of a basin database, by name
+    pub fn database(&self, name: &str) -> Option<(&str, &str)> {
+        Some((self.databases.get(name)?, self.buckets.get(name)?))
+    }
+
+    // Qualified glue names of every table, keyed the same way `table()` is
+    pub fn tables(&self) -> &HashMap<String, String> {
+        &self.tables
+    }
+
+    // First name the templates refer to that isn't known, templates using it can't be rendered
+    // until it's been stored
+    pub fn missing(&self, references: &TemplateReferences) -> Option<String> {
+        let database = references
+            .databases
+            .iter()
+            .find(|name| !self.databases.contains_key(*name));
+        // NOTE: a bare name which is ambiguous between databases isn't missing, it's invalid
+        let table = references.tables.iter().find(|name| {
+            !self.tables.contains_key(*name)
+                && !self
+                    .tables
+                    .keys()
+                    .any(|qualified| qualified.ends_with(&format!(".{}", name)))
+        });
+        database.or(table).cloned()
+    }
+
+    pub fn render(&self, source: &str) -> Result<String> {
+        if !is_templated(source) {
+            return Ok(source.to_string());
+        }
+
+        let mut env = Environment::new();
+        // Typos should fail validation rather than render as empty strings
+        env.set_undefined_behavior(UndefinedBehavior::Strict);
+        env.add_function("database", lookup("database", self.databases.clone()));
+        env.add_function("table", lookup("table", self.tables.clone()));
+        env.add_function("location", lookup("table", self.locations.clone()));
+
+        env.render_str(source, trigger_context())
+            .map_err(|e| anyhow!("failed to render template: {}", e))
+    }
+}
+
+// Names templates look up, found by rendering them against functions which record what they're
+// asked for. Lets only those be read from the store rather than every database and table.
+#[derive(Debug, Default)]
+pub struct TemplateReferences {
+    pub databases: BTreeSet<String>,
+    // As written, either `db.table` or a bare table name
+    pub tables: BTreeSet<String>,
+}
+
+impl TemplateReferences {
+    pub fn collect<'a>(sources: impl IntoIterator<Item = &'a str>) -> Result<Self> {
+        let databases = Arc::new(Mutex::new(BTreeSet::new()));
+        let tables = Arc::new(Mutex::new(BTreeSet::new()));
+
+        let mut env = Environment::new();
+        env.set_undefined_behavior(UndefinedBehavior::Strict);
+        env.add_function("database", record(databases.clone()));
+        env.add_function("table", record(tables.clone()));
+        env.add_function("location", record(tables.clone()));
+        for source in sources.into_iter().filter(|s| is_templated(s)) {
+            env.render_str(source, trigger_context())
+                .map_err(|e| anyhow!("failed to render template: {}", e))?;
+        }
+
+        let databases = std::mem::take(&mut *databases.lock().unwrap());
+        let tables = std::mem::take(&mut *tables.lock().unwrap());
+        Ok(TemplateReferences { databases, tables })
+    }
+}
+
+fn is_templated(source: &str) -> bool {
+    source.contains("{{") || source.contains("{%")
+}
+
+fn trigger_context() -> Value {
+    context! {
+        ds => TRIGGER_DATE_PLACEHOLDER,
+        ts => TRIGGER_DATETIME_PLACEHOLDER,
+    }
+}
+
+fn record(
+    names: Arc<Mutex<BTreeSet<String>>>,
+) -> impl Fn(String) -> Result<String, Error> + Send + Sync + 'static {
+    move |name: String| {
+        names.lock().unwrap().insert(name.clone());
+        Ok(name)
+    }
+}
+
+fn lookup(
+    kind: &'static str,
+    names: HashMap<String, String>,
+) -> impl Fn(String) -> Result<String, Error> + Send + Sync + 'static {
+    move |name: String| {
+        names.get(&name).cloned().ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidOperation,
+                format!("unknown {} '{}'", kind, name),
+            )
+        })
+    }
+}
@@ -0,0 +1,83 @@
+use anyhow::Result;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::RedisConf, descriptor_encryption, redis_connection::RedisConnector,
+    redis_namespace::prefixed,
+};
+
+// The last response fetched for an upstream descriptor uri, kept so the next fetch can be made
+// conditional and a 304 answered from here
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CachedResponse {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub content_type: Option<String>,
+    // Base64 encoded, sealed like a stored descriptor while in redis since it's one too
+    pub body: String,
+}
+
+#[async_trait::async_trait]
+pub(crate) trait UpstreamCacheStore {
+    async fn get_response(&self, uri: &str) -> Result<Option<CachedResponse>>;
+    async fn put_response(&self, uri: &str, response: &CachedResponse) -> Result<()>;
+}
+
+#[derive(Debug)]
+pub struct RedisUpstreamCacheStore {
+    connector: RedisConnector,
+    key_prefix: String,
+    ttl_secs: usize,
+}
+
+#[async_trait::async_trait]
+impl UpstreamCacheStore for RedisUpstreamCacheStore {
+    async fn get_response(&self, uri: &str) -> Result<Option<CachedResponse>> {
+        let mut conn = self.connector.get_connection().await?;
+        let value: Option<String> = conn
+            .get(self.key(&format!("upstream-cache/{}", uri)))
+            .await?;
+        let Some(value) = value else {
+            return Ok(None);
+        };
+        let mut response: CachedResponse = serde_json::from_str(&value)?;
+        response.body =
+            descriptor_encryption::open(response.body, &format!("upstream-cache/{}", uri)).await?;
+        Ok(Some(response))
+    }
+
+    async fn put_response(&self, uri: &str, response: &CachedResponse) -> Result<()> {
+        let sealed = CachedResponse {
+            body: descriptor_encryption::seal(
+                response.body.clone(),
+                &format!("upstream-cache/{}", uri),
+            )
+            .await?,
+            ..response.clone()
+        };
+        let mut conn = self.connector.get_connection().await?;
+        let _: () = conn
+            .set_ex(
+                self.key(&format!("upstream-cache/{}", uri)),
+                serde_json::to_string(&sealed)?,
+                self.ttl_secs,
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+impl RedisUpstreamCacheStore {
+    pub async fn new(conf: &RedisConf, ttl_secs: u64) -> Result<Self> {
+        Ok(Self {
+            connector: RedisConnector::new(conf)?,
+            key_prefix: conf.key_prefix.clone(),
+            ttl_secs: ttl_secs as usize,
+        })
+    }
+
+    fn key(&self, key: &str) -> String {
+        prefixed(&self.key_prefix, key)
+    }
+}
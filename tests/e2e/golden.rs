@@ -0,0 +1,42 @@
+use std::{fs, path::PathBuf};
+
+use serde_json::Value;
+
+// Compares `actual` with tests/e2e/golden/{name}.json. Once a change to it is intended, run with
+// BASIN_UPDATE_GOLDEN=1 to write the file instead and review its diff.
+pub fn assert_golden(name: &str, actual: &Value) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/e2e/golden")
+        .join(format!("{}.json", name));
+    if std::env::var_os("BASIN_UPDATE_GOLDEN").is_some() {
+        let pretty = serde_json::to_string_pretty(actual).unwrap();
+        fs::write(&path, pretty + "\n").expect("failed to write golden file");
+        return;
+    }
+
+    let expected: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap_or_else(|e| {
+        panic!(
+            "can't read {}: {}, run with BASIN_UPDATE_GOLDEN=1 to create it",
+            path.display(),
+            e
+        )
+    }))
+    .expect("golden file isn't json");
+    assert!(
+        expected == *actual,
+        "{} doesn't match {}\nexpected: {}\nactual: {}",
+        name,
+        path.display(),
+        serde_json::to_string_pretty(&expected).unwrap(),
+        serde_json::to_string_pretty(actual).unwrap()
+    );
+}
+
+// Replaces the value at each json pointer, for fields that change without the spec changing
+pub fn redact(value: &mut Value, pointers: &[&str]) {
+    for pointer in pointers {
+        if let Some(v) = value.pointer_mut(pointer) {
+            *v = Value::String("[redacted]".to_string());
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use std::{
+    net::TcpListener,
+    process::{Child, Command},
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use aws_config::SdkConfig;
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use testcontainers::{clients::Cli, core::WaitFor, images::generic::GenericImage, Container};
+
+use crate::waterwheel_stub::WaterwheelStub;
+
+const AWS_REGION: &str = "us-east-1";
+const REDIS_PORT: u16 = 6379;
+const LOCALSTACK_PORT: u16 = 4566;
+// Where descriptors published as events are uploaded for basin to fetch
+const DESCRIPTOR_BUCKET: &str = "basin-e2e-descriptors";
+const WAIT_TIMEOUT: Duration = Duration::from_secs(90);
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+static DOCKER: OnceLock<Cli> = OnceLock::new();
+
+// A basin process with its own redis and localstack containers and waterwheel stub, all of
+// which go away when it's dropped
+pub struct Basin {
+    pub url: String,
+    pub aws: SdkConfig,
+    pub waterwheel: WaterwheelStub,
+    pub event_queue_url: String,
+    aws_endpoint_url: String,
+    http_client: reqwest::Client,
+    process: Child,
+    _redis: Container<'static, GenericImage>,
+    _localstack: Container<'static, GenericImage>,
+}
+
+impl Basin {
+    pub async fn start() -> Basin {
+        let docker = DOCKER.get_or_init(Cli::default);
+
+        let redis = docker.run(
+            GenericImage::new("redis", "7")
+                .with_wait_for(WaitFor::message_on_stdout("Ready to accept connections")),
+        );
+        // NOTE: glue is only emulated by localstack pro, database and table tests need a token
+        let localstack_image = match std::env::var("LOCALSTACK_AUTH_TOKEN") {
+            Ok(token) => GenericImage::new("localstack/localstack-pro", "2.3")
+                .with_env_var("LOCALSTACK_AUTH_TOKEN", token),
+            Err(_) => GenericImage::new("localstack/localstack", "2.3"),
+        };
+        let localstack = docker.run(
+            localstack_image
+                .with_env_var("SERVICES", "s3,glue,sqs")
+                .with_wait_for(WaitFor::message_on_stdout("Ready.")),
+        );
+
+        // NOTE: resolves to 127.0.0.1, subdomains included, so virtual hosted buckets work
+        let aws_endpoint_url = format!(
+            "http://localhost.localstack.cloud:{}",
+            localstack.get_host_port_ipv4(LOCALSTACK_PORT)
+        );
+        let aws = aws_config::from_env()
+            .region(aws_sdk_s3::Region::new(AWS_REGION))
+            .credentials_provider(aws_sdk_s3::Credentials::new(
+                "test", "test", None, None, "e2e",
+            ))
+            .endpoint_url(&aws_endpoint_url)
+            .load()
+            .await;
+        let queue = aws_sdk_sqs::Client::new(&aws)
+            .create_queue()
+            .queue_name("basin-events")
+            .send()
+            .await
+            .expect("failed to create event queue");
+        let event_queue_url = queue.queue_url().unwrap().to_string();
+        aws_sdk_s3::Client::new(&aws)
+            .create_bucket()
+            .bucket(DESCRIPTOR_BUCKET)
+            .send()
+            .await
+            .expect("failed to create descriptor bucket");
+
+        let waterwheel = WaterwheelStub::start();
+        let bind_address = format!("127.0.0.1:{}", free_port());
+        let process = Command::new(env!("CARGO_BIN_EXE_basin"))
+            // NOTE: keeps a basin.toml in the working directory from being picked up
+            .current_dir(std::env::temp_dir())
+            .env("BASIN_NAME", "basin-e2e")
+            .env(
+                "BASIN_REDIS_URL",
+                format!("redis://127.0.0.1:{}", redis.get_host_port_ipv4(REDIS_PORT)),
+            )
+            .env("BASIN_EVENT_SQS_URL", &event_queue_url)
+            .env("BASIN_AWS_ENDPOINT_URL", &aws_endpoint_url)
+            .env("BASIN_WATERWHEEL__URL", &waterwheel.url)
+            .env("BASIN_WATERWHEEL__USERNAME", "basin")
+            .env("BASIN_WATERWHEEL__PASSWORD", "basin")
+            .env("BASIN_WATERWHEEL__PROJECT", "e2e")
+            .env("BASIN_SERVER__BIND_ADDRESS", &bind_address)
+            .env("BASIN_DELETION__GRACE_PERIOD_SECS", "0")
+            .env("BASIN_CONTROLLERS__DATABASE__INTERVAL_MS", "500")
+            .env("BASIN_CONTROLLERS__TABLE__INTERVAL_MS", "500")
+            .env("BASIN_CONTROLLERS__FLOW__INTERVAL_MS", "500")
+            .env("AWS_ACCESS_KEY_ID", "test")
+            .env("AWS_SECRET_ACCESS_KEY", "test")
+            .env("AWS_REGION", AWS_REGION)
+            .spawn()
+            .expect("failed to start basin");
+
+        let basin = Basin {
+            url: format!("http://{}", bind_address),
+            aws,
+            waterwheel,
+            event_queue_url,
+            aws_endpoint_url,
+            http_client: reqwest::Client::new(),
+            process,
+            _redis: redis,
+            _localstack: localstack,
+        };
+        basin.wait_until_healthy().await;
+        basin
+    }
+
+    async fn wait_until_healthy(&self) {
+        let started = Instant::now();
+        while started.elapsed() < WAIT_TIMEOUT {
+            if let Ok(resp) = self
+                .http_client
+                .get(format!("{}/healthcheck", self.url))
+                .send()
+                .await
+                && resp.status().is_success()
+            {
+                return;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+        panic!("basin didn't become healthy within {:?}", WAIT_TIMEOUT);
+    }
+
+    pub async fn submit(&self, kind: &str, descriptor: &Value) -> StatusCode {
+        self.http_client
+            .post(format!("{}/api/v1/{}/reconcile", self.url, kind))
+            .json(descriptor)
+            .send()
+            .await
+            .expect("failed to submit descriptor")
+            .status()
+    }
+
+    // Uploads the descriptor and sends the event an upstream publisher would for it
+    pub async fn publish(&self, kind: &str, descriptor: &Value, revision: u32) {
+        let id = descriptor["id"].as_str().expect("descriptor has no id");
+        let key = format!("{}/{}/{}.json", kind, id, revision);
+        aws_sdk_s3::Client::new(&self.aws)
+            .put_object()
+            .bucket(DESCRIPTOR_BUCKET)
+            .key(&key)
+            .content_type("application/json")
+            .body(descriptor.to_string().into_bytes().into())
+            .send()
+            .await
+            .expect("failed to upload descriptor");
+
+        // NOTE: localstack serves objects without checking signatures, basin fetches them as is
+        let event = json!({
+            "event_id": format!("{}-{}", id, revision),
+            "type": "descriptor_updated",
+            "payload": {
+                "type": "descriptor_updated",
+                "descriptorURI": format!("{}/{}/{}", self.aws_endpoint_url, DESCRIPTOR_BUCKET, key),
+                "kind": kind,
+                "revision": revision,
+            },
+            "resource": id,
+            "time": "2024-01-01T00:00:00Z",
+        });
+        aws_sdk_sqs::Client::new(&self.aws)
+            .send_message()
+            .queue_url(&self.event_queue_url)
+            .message_body(event.to_string())
+            .send()
+            .await
+            .expect("failed to send event");
+    }
+
+    // Messages sqs still holds, in flight ones included
+    pub async fn queued_events(&self) -> usize {
+        let attributes = aws_sdk_sqs::Client::new(&self.aws)
+            .get_queue_attributes()
+            .queue_url(&self.event_queue_url)
+            .attribute_names(aws_sdk_sqs::model::QueueAttributeName::ApproximateNumberOfMessages)
+            .attribute_names(
+                aws_sdk_sqs::model::QueueAttributeName::ApproximateNumberOfMessagesNotVisible,
+            )
+            .send()
+            .await
+            .expect("failed to fetch queue attributes");
+        attributes
+            .attributes()
+            .map(|a| {
+                a.values()
+                    .filter_map(|v| v.parse::<usize>().ok())
+                    .sum::<usize>()
+            })
+            .unwrap_or_default()
+    }
+
+    // What the flow would deploy as, without submitting it
+    pub async fn flow_spec(&self, descriptor: &Value) -> Value {
+        let resp = self
+            .http_client
+            .post(format!("{}/api/v1/flow/spec", self.url))
+            .json(descriptor)
+            .send()
+            .await
+            .expect("failed to fetch flow spec");
+        assert!(
+            resp.status().is_success(),
+            "flow spec failed with {}",
+            resp.status()
+        );
+        resp.json().await.expect("flow spec isn't json")
+    }
+
+    pub async fn delete(&self, kind: &str, id: &str) -> StatusCode {
+        self.http_client
+            .delete(format!("{}/api/v1/{}/{}", self.url, kind, id))
+            .send()
+            .await
+            .expect("failed to delete descriptor")
+            .status()
+    }
+
+    pub async fn status(&self, id: &str) -> Option<Value> {
+        let resp = self
+            .http_client
+            .get(format!("{}/api/v1/status/{}", self.url, id))
+            .send()
+            .await
+            .expect("failed to fetch status");
+        if resp.status() == StatusCode::NOT_FOUND {
+            return None;
+        }
+        Some(resp.json().await.expect("status isn't json"))
+    }
+
+    // Polls the descriptor's status until it's in `state`, panicking with the last one seen
+    pub async fn wait_for_state(&self, id: &str, state: &str) -> Value {
+        let started = Instant::now();
+        let mut last = None;
+        while started.elapsed() < WAIT_TIMEOUT {
+            last = self.status(id).await;
+            if let Some(status) = &last
+                && status["state"] == state
+            {
+                return status.clone();
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+        panic!(
+            "{} didn't reach {} within {:?}, last status {:?}",
+            id, state, WAIT_TIMEOUT, last
+        );
+    }
+}
+
+impl Drop for Basin {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .and_then(|l| l.local_addr())
+        .expect("no free port")
+        .port()
+}
@@ -0,0 +1,155 @@
+#![feature(let_chains)]
+
+// End to end tests running the basin binary against redis and localstack containers and a stub
+// waterwheel, driving it through the api and the sqs event queue and checking what gets
+// provisioned. They need a docker daemon and are only built with
+// `cargo test --features integration-tests --test e2e`, glue is emulated by localstack pro so
+// database and table tests also need LOCALSTACK_AUTH_TOKEN.
+
+mod golden;
+mod harness;
+mod waterwheel_stub;
+
+use serde_json::{json, Value};
+
+use harness::Basin;
+
+fn database(name: &str) -> Value {
+    json!({
+        "id": format!("db-{}", name),
+        "name": name,
+        "summary": "e2e database",
+    })
+}
+
+fn table(database: &Value, name: &str) -> Value {
+    json!({
+        "id": format!("tbl-{}", name),
+        "name": name,
+        "summary": "e2e table",
+        "database": database["id"],
+        "columns": [
+            {
+                "id": "col-id",
+                "name": "id",
+                "summary": "id",
+                "codec": { "type": "Long" },
+                "nullable": false,
+            },
+        ],
+    })
+}
+
+fn flow(id: &str) -> Value {
+    let mut flow: Value =
+        serde_json::from_str(include_str!("../../samples/flows/simple_flow.json")).unwrap();
+    flow["id"] = json!(id);
+    flow
+}
+
+#[tokio::test]
+async fn database_creates_bucket_and_glue_database() {
+    let basin = Basin::start().await;
+    let descriptor = database("e2e_sales");
+
+    assert!(basin.submit("database", &descriptor).await.is_success());
+    basin.wait_for_state("db-e2e_sales", "Succeeded").await;
+
+    aws_sdk_s3::Client::new(&basin.aws)
+        .head_bucket()
+        .bucket("cz-vaporeon-db-e2e-sales")
+        .send()
+        .await
+        .expect("bucket wasn't created");
+    aws_sdk_glue::Client::new(&basin.aws)
+        .get_database()
+        .name("zone_e2e_sales")
+        .send()
+        .await
+        .expect("glue database wasn't created");
+}
+
+#[tokio::test]
+async fn table_waits_for_its_database() {
+    let basin = Basin::start().await;
+    let db = database("e2e_orders");
+    let tbl = table(&db, "orders");
+
+    // NOTE: submitted first, it fails on the missing dependency until the database shows up
+    assert!(basin.submit("table", &tbl).await.is_success());
+    assert!(basin.submit("database", &db).await.is_success());
+    basin.wait_for_state("tbl-orders", "Succeeded").await;
+
+    let table = aws_sdk_glue::Client::new(&basin.aws)
+        .get_table()
+        .database_name("zone_e2e_orders")
+        .name("orders")
+        .send()
+        .await
+        .expect("glue table wasn't created");
+    let columns = table
+        .table()
+        .and_then(|t| t.storage_descriptor())
+        .and_then(|s| s.columns())
+        .unwrap_or_default();
+    assert_eq!(columns.len(), 1);
+}
+
+#[tokio::test]
+async fn flow_deploys_to_waterwheel_and_is_torn_down() {
+    let basin = Basin::start().await;
+    let id = "00000000-0000-0000-0000-0000000000e2";
+
+    assert!(basin.submit("flow", &flow(id)).await.is_success());
+    basin.wait_for_state(id, "Succeeded").await;
+    let job = basin.waterwheel.job(id).expect("job wasn't submitted");
+    assert_eq!(job["project"], "e2e");
+    assert_eq!(job["tasks"].as_array().map(Vec::len), Some(2));
+
+    assert!(basin.delete("flow", id).await.is_success());
+    basin.wait_for_state(id, "Deleted").await;
+    assert!(basin.waterwheel.job(id).is_none());
+}
+
+#[tokio::test]
+async fn flow_published_as_sqs_event_is_deployed() {
+    let basin = Basin::start().await;
+    let id = "00000000-0000-0000-0000-0000000000e4";
+
+    basin.publish("flow", &flow(id), 1).await;
+    basin.wait_for_state(id, "Succeeded").await;
+    let job = basin.waterwheel.job(id).expect("job wasn't submitted");
+    assert_eq!(job["project"], "e2e");
+    // NOTE: handled events are deleted, anything left would be redelivered
+    assert_eq!(basin.queued_events().await, 0);
+}
+
+#[tokio::test]
+async fn flow_spec_matches_golden_file_and_deployed_job() {
+    let basin = Basin::start().await;
+    let id = "00000000-0000-0000-0000-0000000000e3";
+
+    let spec = basin.flow_spec(&flow(id)).await;
+    let mut redacted = spec.clone();
+    // NOTE: the description carries the descriptor's hash, which moves with every field added to
+    //       descriptors
+    golden::redact(&mut redacted, &["/job/description"]);
+    golden::assert_golden("simple_flow.waterwheel", &redacted);
+
+    assert!(basin.submit("flow", &flow(id)).await.is_success());
+    basin.wait_for_state(id, "Succeeded").await;
+    assert_eq!(basin.waterwheel.job(id).as_ref(), Some(&spec["job"]));
+}
+
+#[tokio::test]
+async fn oversized_descriptor_is_rejected() {
+    let basin = Basin::start().await;
+    let mut descriptor = database("e2e_huge");
+    descriptor["summary"] = json!("x".repeat(1 << 20));
+
+    assert!(basin
+        .submit("database", &descriptor)
+        .await
+        .is_client_error());
+    assert!(basin.status("db-e2e_huge").await.is_none());
+}
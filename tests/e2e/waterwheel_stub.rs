@@ -0,0 +1,80 @@
+use std::{
+    collections::HashMap,
+    net::{SocketAddr, TcpListener},
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use serde_json::Value;
+
+type Jobs = Arc<Mutex<HashMap<String, Value>>>;
+
+// Just enough of waterwheel's api for the flow controller: a login handing out a cookie and jobs
+// kept in memory by uuid
+#[derive(Clone)]
+pub struct WaterwheelStub {
+    pub url: String,
+    jobs: Jobs,
+}
+
+impl WaterwheelStub {
+    pub fn start() -> WaterwheelStub {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind waterwheel stub");
+        let addr: SocketAddr = listener.local_addr().unwrap();
+        let jobs = Jobs::default();
+
+        let app = Router::new()
+            .route("/login", post(login))
+            .route("/api/jobs", post(submit_job))
+            .route("/api/jobs/:uuid", get(get_job).delete(delete_job))
+            .with_state(jobs.clone());
+        tokio::spawn(async move {
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(app.into_make_service())
+                .await
+                .unwrap();
+        });
+
+        WaterwheelStub {
+            url: format!("http://{}", addr),
+            jobs,
+        }
+    }
+
+    pub fn job(&self, uuid: &str) -> Option<Value> {
+        self.jobs.lock().unwrap().get(uuid).cloned()
+    }
+}
+
+async fn login() -> impl IntoResponse {
+    ([(header::SET_COOKIE, "session=e2e")], StatusCode::OK)
+}
+
+async fn submit_job(State(jobs): State<Jobs>, Json(job): Json<Value>) -> StatusCode {
+    let Some(uuid) = job["uuid"].as_str() else {
+        return StatusCode::BAD_REQUEST;
+    };
+    jobs.lock().unwrap().insert(uuid.to_string(), job.clone());
+    StatusCode::CREATED
+}
+
+async fn get_job(State(jobs): State<Jobs>, Path(uuid): Path<String>) -> axum::response::Response {
+    match jobs.lock().unwrap().get(&uuid) {
+        Some(job) => Json(job.clone()).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+async fn delete_job(State(jobs): State<Jobs>, Path(uuid): Path<String>) -> StatusCode {
+    match jobs.lock().unwrap().remove(&uuid) {
+        Some(_) => StatusCode::OK,
+        None => StatusCode::NOT_FOUND,
+    }
+}